@@ -12,8 +12,9 @@ extern crate quote;
 extern crate syn;
 
 use proc_macro::TokenStream;
-use proc_macro2::TokenStream as TokenStream2;
+use proc_macro2::{Span, TokenStream as TokenStream2};
 use quote::{format_ident, quote, ToTokens};
+use std::{collections::HashMap, time::Duration};
 use syn::{
     braced,
     ext::IdentExt,
@@ -21,9 +22,11 @@ use syn::{
     parse::{Parse, ParseStream},
     parse_macro_input, parse_quote,
     spanned::Spanned,
-    token::Comma,
-    Attribute, FnArg, Ident, Lit, LitBool, MetaNameValue, Pat, PatType, ReturnType, Token, Type,
-    Visibility,
+    token::{self, Comma},
+    Attribute, Block, FnArg, GenericArgument, GenericParam, Generics, Ident, Lit, LitBool, LitStr,
+    Meta, MetaNameValue, NestedMeta, Pat, PatType, Path, PathArguments, ReturnType, Token,
+    TraitItemConst, TraitItemType, Type, TypeParam, TypeParamBound, TypePath, Visibility,
+    WherePredicate,
 };
 
 /// Accumulates multiple errors into a result.
@@ -42,6 +45,12 @@ struct Service {
     attrs: Vec<Attribute>,
     vis: Visibility,
     ident: Ident,
+    /// The service's type parameters and where clause.
+    generics: Generics,
+    supertraits: Vec<TypeParamBound>,
+    consts: Vec<TraitItemConst>,
+    /// The error type of methods returning `Result<T>`, if set with `type Error = ...;`.
+    error: Option<Type>,
     rpcs: Vec<RpcMethod>,
 }
 
@@ -50,6 +59,157 @@ struct RpcMethod {
     ident: Ident,
     args: Vec<PatType>,
     output: ReturnType,
+    /// The name of the method's variants in the serialized request and response enums, if set
+    /// with `#[tarpc::rename = "..."]`.
+    rename: Option<LitStr>,
+    /// The version of the service that introduced the method, if set with
+    /// `#[tarpc::since = "..."]`.
+    since: Option<LitStr>,
+    /// The version of the service that deprecated the method, if set with
+    /// `#[tarpc::deprecated_since = "..."]`.
+    deprecated_since: Option<LitStr>,
+    /// The deadline the client seeds its requests with, if set with
+    /// `#[tarpc::deadline = "..."]`.
+    deadline: Option<Duration>,
+    /// The id sent in place of the method's variant index, if set with `#[tarpc::id = N]`.
+    id: Option<(u32, Span)>,
+    /// The maximum number of requests of the method that may execute at once, if set with
+    /// `#[tarpc::max_concurrent = N]`.
+    max_concurrent: Option<usize>,
+    /// Whether the client sends the method without awaiting a response, if set with
+    /// `#[tarpc::oneway]`.
+    oneway: bool,
+    /// Whether requests of the method skip their spans, if set with `#[tarpc::no_trace]`.
+    no_trace: bool,
+    /// The `#[serde(...)]` attributes of the method, forwarded onto its request and response
+    /// variants if serde's traits are derived.
+    serde_attrs: Vec<Attribute>,
+    /// The `#[serde(...)]` attributes of each arg, forwarded onto its request variant field if
+    /// serde's traits are derived.
+    arg_serde_attrs: Vec<Vec<Attribute>>,
+    /// The default body of the method in the service trait, if any.
+    default: Option<Block>,
+    /// For each arg, how it's borrowed from an archived request, if it's declared as a reference.
+    borrowed_args: Vec<Option<BorrowedArg>>,
+    /// Whether the method returns `Result<T>` with the service's error type, if that isn't marked
+    /// `#[tarpc::application_error]`, so that the client returns its errors as
+    /// `CallError::Service`.
+    shared_error: bool,
+    /// Whether the method's errors are sent as application errors, if set with
+    /// `#[tarpc::application_error]` on the method or on the service's error type.
+    application_error: bool,
+}
+
+/// A service whose methods are included in another, set with `extends = Path`.
+struct BaseService {
+    /// The variant of the request and response enums wrapping the base service's.
+    variant: Ident,
+    /// The base service trait.
+    service: Path,
+    request: Path,
+    response: Path,
+    client: Path,
+    /// The fn of the client stub returning a client of the base service.
+    client_fn: Ident,
+}
+
+impl BaseService {
+    fn new(service: &Path) -> Self {
+        let variant = service.segments.last().unwrap().ident.clone();
+        let sibling = |name: String| {
+            let mut path = service.clone();
+            path.segments.last_mut().unwrap().ident = Ident::new(&name, variant.span());
+            path
+        };
+        let client_fn = format_ident!("as_{}", camel_to_snake(&variant.unraw().to_string()));
+        Self {
+            request: sibling(format!("{variant}Request")),
+            response: sibling(format!("{variant}Response")),
+            client: sibling(format!("{variant}Client")),
+            service: service.clone(),
+            client_fn,
+            variant,
+        }
+    }
+}
+
+/// An arg borrowed from an archived request, rather than owned by the server.
+struct BorrowedArg {
+    /// The type the client sends the arg as.
+    owned: Type,
+    /// Whether the arg derefs from its archived type, like `&str` from `&ArchivedString`, rather
+    /// than being declared as the archived type itself.
+    deref: bool,
+}
+
+impl BorrowedArg {
+    /// Returns how an arg of type `ty` is borrowed, or None if it's owned.
+    fn parse(ty: &Type) -> syn::Result<Option<Self>> {
+        let Type::Reference(reference) = ty else {
+            return Ok(None);
+        };
+        if let Some(lifetime) = &reference.lifetime {
+            return Err(syn::Error::new(
+                lifetime.span(),
+                "borrowed args can't name a lifetime",
+            ));
+        }
+        if let Some(mutability) = &reference.mutability {
+            return Err(syn::Error::new(
+                mutability.span(),
+                "args can't be borrowed mutably",
+            ));
+        }
+        let borrowed = match &*reference.elem {
+            Type::Path(TypePath { qself: None, path }) if path.is_ident("str") => Some(Self {
+                owned: parse_quote!(::std::string::String),
+                deref: true,
+            }),
+            Type::Slice(slice) => {
+                let elem = &slice.elem;
+                Some(Self {
+                    owned: parse_quote!(::std::vec::Vec<#elem>),
+                    deref: true,
+                })
+            }
+            Type::Path(TypePath { qself: None, path }) => {
+                let segment = path.segments.last().unwrap();
+                let arg = match &segment.arguments {
+                    PathArguments::AngleBracketed(args) if args.args.len() == 1 => {
+                        match &args.args[0] {
+                            GenericArgument::Type(arg) => Some(arg),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                };
+                match (segment.ident.to_string().as_str(), arg) {
+                    ("ArchivedString", None) if segment.arguments.is_empty() => Some(Self {
+                        owned: parse_quote!(::std::string::String),
+                        deref: false,
+                    }),
+                    ("ArchivedVec", Some(elem)) => Some(Self {
+                        owned: parse_quote!(::std::vec::Vec<#elem>),
+                        deref: false,
+                    }),
+                    ("Archived", Some(owned)) => Some(Self {
+                        owned: owned.clone(),
+                        deref: false,
+                    }),
+                    _ => None,
+                }
+            }
+            _ => None,
+        };
+        match borrowed {
+            Some(borrowed) => Ok(Some(borrowed)),
+            None => Err(syn::Error::new_spanned(
+                ty,
+                "borrowed args must be `&str`, `&[T]`, `&ArchivedString`, `&ArchivedVec<T>`, or \
+                 `&Archived<T>`",
+            )),
+        }
+    }
 }
 
 impl Parse for Service {
@@ -58,13 +218,111 @@ impl Parse for Service {
         let vis = input.parse()?;
         input.parse::<Token![trait]>()?;
         let ident: Ident = input.parse()?;
+        let mut generics: Generics = input.parse()?;
+        let mut supertraits = Vec::new();
+        if input.parse::<Option<Token![:]>>()?.is_some() {
+            while !input.peek(Token![where]) && !input.peek(token::Brace) {
+                supertraits.push(input.parse()?);
+                if input.parse::<Option<Token![+]>>()?.is_none() {
+                    break;
+                }
+            }
+        }
+        generics.where_clause = input.parse()?;
         let content;
         braced!(content in input);
+        let mut consts = Vec::new();
+        let mut error = None;
+        let mut application_error = false;
         let mut rpcs = Vec::<RpcMethod>::new();
         while !content.is_empty() {
-            rpcs.push(content.parse()?);
+            let ahead = content.fork();
+            ahead.call(Attribute::parse_outer)?;
+            if ahead.peek(Token![const]) {
+                consts.push(content.parse()?);
+            } else if ahead.peek(Token![type]) {
+                let item: TraitItemType = content.parse()?;
+                match item {
+                    TraitItemType {
+                        ref ident,
+                        ref generics,
+                        ref bounds,
+                        default: Some((_, ref ty)),
+                        ..
+                    } if ident == "Error" && generics.params.is_empty() && bounds.is_empty() => {
+                        if error.replace(ty.clone()).is_some() {
+                            return Err(syn::Error::new(
+                                item.span(),
+                                "`type Error` appears more than once",
+                            ));
+                        }
+                        application_error = parse_application_error_attr(&item.attrs)?;
+                    }
+                    item => {
+                        return Err(syn::Error::new(
+                            item.span(),
+                            "the only associated type supported is `type Error = ...;`, the error \
+                             type of methods returning `Result<T>`",
+                        ))
+                    }
+                }
+            } else {
+                rpcs.push(content.parse()?);
+            }
+        }
+        // Methods returning `Result<T>` return the declared error type.
+        if let Some(error) = &error {
+            for rpc in &mut rpcs {
+                let ReturnType::Type(_, ty) = &mut rpc.output else {
+                    continue;
+                };
+                if let Some([ok]) = result_args(ty).as_deref() {
+                    let ok = (*ok).clone();
+                    **ty = parse_quote!(::core::result::Result<#ok, #error>);
+                    rpc.shared_error = !application_error;
+                    rpc.application_error |= application_error;
+                }
+            }
+        }
+        for rpc in &rpcs {
+            let returns_result = match &rpc.output {
+                ReturnType::Type(_, ty) => matches!(result_args(ty).as_deref(), Some([_, _])),
+                ReturnType::Default => false,
+            };
+            if rpc.application_error && !returns_result {
+                return Err(syn::Error::new(
+                    rpc.ident.span(),
+                    "methods sending application errors must return `Result<T, E>`, where \
+                     `E: Into<tarpc::ApplicationError>`",
+                ));
+            }
         }
         let mut ident_errors = Ok(());
+        for param in &generics.params {
+            match param {
+                GenericParam::Type(TypeParam { ident: param, .. })
+                    if param == "S" || param == "Stub" || param == "Transport" =>
+                {
+                    extend_errors!(
+                        ident_errors,
+                        syn::Error::new(
+                            param.span(),
+                            format!("type parameter name conflicts with generated type parameter `{param}`")
+                        )
+                    );
+                }
+                GenericParam::Type(_) => {}
+                GenericParam::Lifetime(_) | GenericParam::Const(_) => {
+                    extend_errors!(
+                        ident_errors,
+                        syn::Error::new(
+                            param.span(),
+                            "only type parameters are supported on services"
+                        )
+                    );
+                }
+            }
+        }
         for rpc in &rpcs {
             if rpc.ident == "new" {
                 extend_errors!(
@@ -88,12 +346,38 @@ impl Parse for Service {
                 );
             }
         }
+        let mut wire_names = HashMap::new();
+        for rpc in &rpcs {
+            let (name, span) = match &rpc.rename {
+                Some(rename) => (rename.value(), rename.span()),
+                None => (
+                    snake_to_camel(&rpc.ident.unraw().to_string()),
+                    rpc.ident.span(),
+                ),
+            };
+            if let Some(other) = wire_names.insert(name.clone(), &rpc.ident) {
+                extend_errors!(
+                    ident_errors,
+                    syn::Error::new(
+                        span,
+                        format!(
+                            "wire name `{name}` of method `{}` is already used by method `{other}`",
+                            rpc.ident
+                        )
+                    )
+                );
+            }
+        }
         ident_errors?;
 
         Ok(Self {
             attrs,
             vis,
             ident,
+            generics,
+            supertraits,
+            consts,
+            error,
             rpcs,
         })
     }
@@ -101,17 +385,111 @@ impl Parse for Service {
 
 impl Parse for RpcMethod {
     fn parse(input: ParseStream) -> syn::Result<Self> {
-        let attrs = input.call(Attribute::parse_outer)?;
+        let mut attrs = input.call(Attribute::parse_outer)?;
+        let mut errors = Ok(());
+        let mut rename = None;
+        let mut since = None;
+        let mut deprecated_since = None;
+        let mut deadline = None;
+        let mut id = None;
+        let mut max_concurrent = None;
+        let mut oneway = false;
+        let mut no_trace = false;
+        let application_error = match parse_application_error_attr(&attrs) {
+            Ok(application_error) => application_error,
+            Err(e) => {
+                extend_errors!(errors, e);
+                false
+            }
+        };
+        // Attributes in the `tarpc` namespace configure code generation and aren't emitted.
+        attrs.retain(|attr| {
+            if is_tarpc_attr(attr, "application_error") {
+                return false;
+            }
+            if is_tarpc_attr(attr, "oneway") {
+                if !attr.tokens.is_empty() {
+                    extend_errors!(
+                        errors,
+                        syn::Error::new(attr.span(), "`tarpc::oneway` takes no arguments")
+                    );
+                } else if oneway {
+                    extend_errors!(
+                        errors,
+                        syn::Error::new(attr.span(), "`tarpc::oneway` appears more than once")
+                    );
+                }
+                oneway = true;
+                return false;
+            }
+            if is_tarpc_attr(attr, "no_trace") {
+                if !attr.tokens.is_empty() {
+                    extend_errors!(
+                        errors,
+                        syn::Error::new(attr.span(), "`tarpc::no_trace` takes no arguments")
+                    );
+                } else if no_trace {
+                    extend_errors!(
+                        errors,
+                        syn::Error::new(attr.span(), "`tarpc::no_trace` appears more than once")
+                    );
+                }
+                no_trace = true;
+                return false;
+            }
+            if is_tarpc_attr(attr, "id") {
+                if let Err(e) = parse_id_attr(attr, &mut id) {
+                    extend_errors!(errors, e);
+                }
+                return false;
+            }
+            if is_tarpc_attr(attr, "max_concurrent") {
+                if let Err(e) = parse_max_concurrent_attr(attr, &mut max_concurrent) {
+                    extend_errors!(errors, e);
+                }
+                return false;
+            }
+            if is_tarpc_attr(attr, "deadline") {
+                if let Err(e) = parse_deadline_attr(attr, &mut deadline) {
+                    extend_errors!(errors, e);
+                }
+                return false;
+            }
+            let (value, is_version) = if is_tarpc_attr(attr, "rename") {
+                (&mut rename, false)
+            } else if is_tarpc_attr(attr, "since") {
+                (&mut since, true)
+            } else if is_tarpc_attr(attr, "deprecated_since") {
+                (&mut deprecated_since, true)
+            } else {
+                return true;
+            };
+            if let Err(e) = parse_str_attr(attr, value, is_version) {
+                extend_errors!(errors, e);
+            }
+            false
+        });
+        // Serde attributes configure the generated enums, not the service trait.
+        let serde_attrs = take_serde_attrs(&mut attrs);
         input.parse::<Token![async]>()?;
         input.parse::<Token![fn]>()?;
         let ident = input.parse()?;
         let content;
         parenthesized!(content in input);
         let mut args = Vec::new();
-        let mut errors = Ok(());
+        let mut arg_serde_attrs = Vec::new();
+        let mut borrowed_args = Vec::new();
         for arg in content.parse_terminated::<FnArg, Comma>(FnArg::parse)? {
             match arg {
-                FnArg::Typed(captured) if matches!(&*captured.pat, Pat::Ident(_)) => {
+                FnArg::Typed(mut captured) if matches!(&*captured.pat, Pat::Ident(_)) => {
+                    match BorrowedArg::parse(&captured.ty) {
+                        Ok(borrowed) => borrowed_args.push(borrowed),
+                        Err(e) => {
+                            extend_errors!(errors, e);
+                            borrowed_args.push(None);
+                        }
+                    }
+                    arg_serde_attrs.push(take_serde_attrs(&mut captured.attrs));
                     args.push(captured);
                 }
                 FnArg::Typed(captured) => {
@@ -129,90 +507,516 @@ impl Parse for RpcMethod {
             }
         }
         errors?;
-        let output = input.parse()?;
-        input.parse::<Token![;]>()?;
+        let output: ReturnType = input.parse()?;
+        let default = if input.peek(token::Brace) {
+            Some(input.parse()?)
+        } else {
+            input.parse::<Token![;]>()?;
+            None
+        };
+        if let ReturnType::Type(_, ty) = &output {
+            if matches!(**ty, Type::ImplTrait(_)) && stream_item_type(ty).is_none() {
+                return Err(syn::Error::new(
+                    ty.span(),
+                    "methods can only return `impl Stream<Item = T>`, or a concrete type",
+                ));
+            }
+            let is_unit = matches!(&**ty, Type::Tuple(tuple) if tuple.elems.is_empty());
+            if oneway && !is_unit {
+                return Err(syn::Error::new(
+                    ty.span(),
+                    "`tarpc::oneway` methods can't return a value",
+                ));
+            }
+        }
 
         Ok(Self {
             attrs,
             ident,
             args,
             output,
+            rename,
+            since,
+            deprecated_since,
+            deadline,
+            id,
+            max_concurrent,
+            oneway,
+            no_trace,
+            serde_attrs,
+            arg_serde_attrs,
+            default,
+            borrowed_args,
+            shared_error: false,
+            application_error,
         })
     }
 }
 
-// If `derive_serde` meta item is not present, defaults to cfg!(feature = "serde1").
-// `derive_serde` can only be true when serde1 is enabled.
-struct DeriveSerde(bool);
+/// The meta items of `#[tarpc::service(...)]`.
+struct ServiceArgs {
+    /// Whether to derive serde's traits on the request and response enums. Defaults to
+    /// cfg!(feature = "serde1"), and can only be true when serde1 is enabled.
+    derive_serde: bool,
+    /// Whether to derive rkyv's traits on the request and response enums. Defaults to
+    /// cfg!(feature = "rkyv"), and can only be true when rkyv is enabled.
+    derive_rkyv: bool,
+    /// Extra traits to derive on the request and response enums, listed in `derive(...)`.
+    derives: Vec<Path>,
+    /// Whether the request enum has an `Unknown` variant catching requests for methods the
+    /// service doesn't know. Requires serde.
+    catch_unknown_methods: bool,
+    /// Set by `client_only` to skip generating the service trait and serve fn.
+    client_only: bool,
+    /// Set by `server_only` to skip generating the client stub.
+    server_only: bool,
+    /// Whether the request enum implements `tarpc::cli::CliRequest`. Requires serde.
+    cli: bool,
+    /// Whether the request enum has a `schema()` fn describing the service.
+    schema: bool,
+    /// Whether the request enum implements `Arbitrary` and has a `fuzz` fn. Requires serde.
+    fuzz: bool,
+    /// Whether each method gets a `tower::Service` newtype calling it.
+    tower: bool,
+    /// Whether to generate a client wrapper recording metrics and spans of each call.
+    instrumented_client: bool,
+    /// The directory of the golden samples of the generated wire compatibility tests, if set
+    /// with `wire_compat = "..."`. Requires serde.
+    wire_compat: Option<LitStr>,
+    /// The version of the service, if set with `version = "..."`, which the server attaches to
+    /// every response.
+    version: Option<LitStr>,
+    /// The visibility of the generated request, response, and client types, if set with
+    /// `vis = "..."`. Defaults to the visibility of the service trait.
+    types_vis: Option<Visibility>,
+    /// The services whose methods this service includes, set with `extends = Path`.
+    extends: Vec<Path>,
+    /// The prefix of the names of the generated types, if set with `prefix = "..."`. Defaults to
+    /// the name of the service trait.
+    prefix: Option<Ident>,
+    /// The name of the client, if set with `client_name = "..."`.
+    client_name: Option<Ident>,
+    /// The name of the request enum, if set with `request_name = "..."`.
+    request_name: Option<Ident>,
+    /// The name of the response enum, if set with `response_name = "..."`.
+    response_name: Option<Ident>,
+    /// Whether methods are sent as stable ids rather than variant indices. Requires serde.
+    method_ids: bool,
+    /// Whether args and responses of type `Bytes` are sent as payloads passed through
+    /// `Passthrough` codecs. Requires serde.
+    passthrough_bytes: bool,
+}
 
-impl Parse for DeriveSerde {
+impl Parse for ServiceArgs {
     fn parse(input: ParseStream) -> syn::Result<Self> {
-        let mut result = Ok(None);
-        let mut derive_serde = Vec::new();
-        let meta_items = input.parse_terminated::<MetaNameValue, Comma>(MetaNameValue::parse)?;
-        for meta in meta_items {
-            if meta.path.segments.len() != 1 {
-                extend_errors!(
-                    result,
-                    syn::Error::new(
-                        meta.span(),
-                        "tarpc::service does not support this meta item"
-                    )
-                );
-                continue;
+        let mut result = Ok(());
+        let mut derive_serde = None;
+        let mut derive_rkyv = None;
+        let mut catch_unknown_methods = None;
+        let mut client_only = None;
+        let mut server_only = None;
+        let mut cli = None;
+        let mut schema = None;
+        let mut method_ids = None;
+        let mut passthrough_bytes = None;
+        let mut fuzz = None;
+        let mut tower = None;
+        let mut instrumented_client = None;
+        let mut wire_compat = None;
+        let mut version = None;
+        let mut types_vis = None;
+        let mut prefix = None;
+        let mut client_name = None;
+        let mut request_name = None;
+        let mut response_name = None;
+        let mut derives = Vec::new();
+        let mut extends = Vec::new();
+        let mut meta_items = Vec::new();
+        // `extends = Path` isn't a meta item, because its value isn't a literal.
+        while !input.is_empty() {
+            if input.peek(Ident)
+                && input.peek2(Token![=])
+                && input.fork().parse::<Ident>()? == "extends"
+            {
+                input.parse::<Ident>()?;
+                input.parse::<Token![=]>()?;
+                let base: Path = input.parse()?;
+                if base
+                    .segments
+                    .iter()
+                    .any(|segment| !segment.arguments.is_empty())
+                {
+                    extend_errors!(
+                        result,
+                        syn::Error::new_spanned(&base, "generic services can't be extended")
+                    );
+                }
+                extends.push(base);
+            } else {
+                meta_items.push(input.parse::<Meta>()?);
             }
-            let segment = meta.path.segments.first().unwrap();
-            if segment.ident != "derive_serde" {
-                extend_errors!(
-                    result,
-                    syn::Error::new(
-                        meta.span(),
-                        "tarpc::service does not support this meta item"
-                    )
-                );
-                continue;
+            if input.is_empty() {
+                break;
             }
-            match meta.lit {
-                Lit::Bool(LitBool { value: true, .. }) if cfg!(feature = "serde1") => {
-                    result = result.and(Ok(Some(true)))
+            input.parse::<Comma>()?;
+        }
+        for meta in meta_items {
+            match meta {
+                Meta::List(list) if list.path.is_ident("derive") => {
+                    for nested in list.nested {
+                        match nested {
+                            NestedMeta::Meta(Meta::Path(path)) => derives.push(path),
+                            nested => extend_errors!(
+                                result,
+                                syn::Error::new(nested.span(), "expected a trait to derive")
+                            ),
+                        }
+                    }
                 }
-                Lit::Bool(LitBool { value: true, .. }) => {
-                    extend_errors!(
+                Meta::NameValue(meta) if meta.path.is_ident("derive_serde") => {
+                    let missing_feature = (!cfg!(feature = "serde1"))
+                        .then(|| "To enable serde, first enable the `serde1` feature of tarpc");
+                    if let Err(e) = parse_flag(&mut derive_serde, &meta, missing_feature) {
+                        extend_errors!(result, e);
+                    }
+                }
+                Meta::NameValue(meta) if meta.path.is_ident("derive_rkyv") => {
+                    let missing_feature = (!cfg!(feature = "rkyv"))
+                        .then(|| "To enable rkyv, first enable the `rkyv` feature of tarpc");
+                    if let Err(e) = parse_flag(&mut derive_rkyv, &meta, missing_feature) {
+                        extend_errors!(result, e);
+                    }
+                }
+                Meta::NameValue(meta) if meta.path.is_ident("catch_unknown_methods") => {
+                    if let Err(e) = parse_flag(&mut catch_unknown_methods, &meta, None) {
+                        extend_errors!(result, e);
+                    }
+                }
+                Meta::NameValue(meta) if meta.path.is_ident("cli") => {
+                    let missing_feature = (!cfg!(feature = "cli"))
+                        .then(|| "To generate a CLI, first enable the `cli` feature of tarpc");
+                    if let Err(e) = parse_flag(&mut cli, &meta, missing_feature) {
+                        extend_errors!(result, e);
+                    }
+                }
+                Meta::NameValue(meta) if meta.path.is_ident("vis") => {
+                    let vis = match &meta.lit {
+                        Lit::Str(vis) => vis.parse::<Visibility>().ok(),
+                        _ => None,
+                    };
+                    match vis {
+                        Some(_) if types_vis.is_some() => extend_errors!(
+                            result,
+                            syn::Error::new(meta.span(), "`vis` appears more than once")
+                        ),
+                        Some(vis) => types_vis = Some(vis),
+                        None => extend_errors!(
+                            result,
+                            syn::Error::new(
+                                meta.lit.span(),
+                                "`vis` expects a visibility, e.g. `vis = \"pub(crate)\"`"
+                            )
+                        ),
+                    }
+                }
+                Meta::NameValue(meta)
+                    if ["prefix", "client_name", "request_name", "response_name"]
+                        .iter()
+                        .any(|name| meta.path.is_ident(name)) =>
+                {
+                    let name = if meta.path.is_ident("prefix") {
+                        &mut prefix
+                    } else if meta.path.is_ident("client_name") {
+                        &mut client_name
+                    } else if meta.path.is_ident("request_name") {
+                        &mut request_name
+                    } else {
+                        &mut response_name
+                    };
+                    if let Err(e) = parse_ident(name, &meta) {
+                        extend_errors!(result, e);
+                    }
+                }
+                Meta::NameValue(meta) if meta.path.is_ident("fuzz") => {
+                    let missing_feature = (!cfg!(feature = "fuzz")).then(|| {
+                        "To generate a fuzzing harness, first enable the `fuzz` feature of tarpc"
+                    });
+                    if let Err(e) = parse_flag(&mut fuzz, &meta, missing_feature) {
+                        extend_errors!(result, e);
+                    }
+                }
+                Meta::NameValue(meta) if meta.path.is_ident("tower") => {
+                    let missing_feature = (!cfg!(feature = "tower")).then(|| {
+                        "To generate tower services, first enable the `tower` feature of tarpc"
+                    });
+                    if let Err(e) = parse_flag(&mut tower, &meta, missing_feature) {
+                        extend_errors!(result, e);
+                    }
+                }
+                Meta::NameValue(meta) if meta.path.is_ident("instrumented_client") => {
+                    let missing_feature = (!cfg!(feature = "metrics")).then(|| {
+                        "To generate an instrumented client, first enable the `metrics` feature of \
+                         tarpc"
+                    });
+                    if let Err(e) = parse_flag(&mut instrumented_client, &meta, missing_feature) {
+                        extend_errors!(result, e);
+                    }
+                }
+                Meta::NameValue(meta) if meta.path.is_ident("wire_compat") => match meta.lit {
+                    _ if !cfg!(feature = "wire-compat") => extend_errors!(
                         result,
                         syn::Error::new(
                             meta.span(),
-                            "To enable serde, first enable the `serde1` feature of tarpc"
+                            "To generate wire compatibility tests, first enable the `wire-compat` \
+                             feature of tarpc"
                         )
-                    );
+                    ),
+                    _ if wire_compat.is_some() => extend_errors!(
+                        result,
+                        syn::Error::new(meta.span(), "`wire_compat` appears more than once")
+                    ),
+                    Lit::Str(dir) => wire_compat = Some(dir),
+                    lit => extend_errors!(
+                        result,
+                        syn::Error::new(
+                            lit.span(),
+                            "`wire_compat` expects the directory of the golden samples, e.g. \
+                             `wire_compat = \"tests/wire\"`"
+                        )
+                    ),
+                },
+                Meta::NameValue(meta) if meta.path.is_ident("version") => match meta.lit {
+                    _ if version.is_some() => extend_errors!(
+                        result,
+                        syn::Error::new(meta.span(), "`version` appears more than once")
+                    ),
+                    Lit::Str(lit) if is_valid_version(&lit.value()) => version = Some(lit),
+                    lit => extend_errors!(
+                        result,
+                        syn::Error::new(
+                            lit.span(),
+                            "`version` expects a version made of dot-separated numbers, e.g. \
+                             `version = \"1.2\"`"
+                        )
+                    ),
+                },
+                Meta::NameValue(meta) if meta.path.is_ident("method_ids") => {
+                    if let Err(e) = parse_flag(&mut method_ids, &meta, None) {
+                        extend_errors!(result, e);
+                    }
+                }
+                Meta::NameValue(meta) if meta.path.is_ident("passthrough_bytes") => {
+                    let missing_feature = (!cfg!(feature = "serde-transport")).then(|| {
+                        "To pass payloads through serde transports, first enable the \
+                         `serde-transport` feature of tarpc"
+                    });
+                    if let Err(e) = parse_flag(&mut passthrough_bytes, &meta, missing_feature) {
+                        extend_errors!(result, e);
+                    }
                 }
-                Lit::Bool(LitBool { value: false, .. }) => result = result.and(Ok(Some(false))),
-                _ => extend_errors!(
+                Meta::NameValue(meta) if meta.path.is_ident("schema") => {
+                    if let Err(e) = parse_flag(&mut schema, &meta, None) {
+                        extend_errors!(result, e);
+                    }
+                }
+                Meta::Path(path)
+                    if path.is_ident("client_only") || path.is_ident("server_only") =>
+                {
+                    let flag = if path.is_ident("client_only") {
+                        &mut client_only
+                    } else {
+                        &mut server_only
+                    };
+                    if flag.replace(path.span()).is_some() {
+                        extend_errors!(
+                            result,
+                            syn::Error::new(
+                                path.span(),
+                                format!("`{}` appears more than once", path.get_ident().unwrap())
+                            )
+                        );
+                    }
+                }
+                meta if meta.path().is_ident("derive") => extend_errors!(
                     result,
                     syn::Error::new(
-                        meta.lit.span(),
-                        "`derive_serde` expects a value of type `bool`"
+                        meta.span(),
+                        "`derive` expects a list of traits, e.g. `derive(Clone, PartialEq)`"
                     )
                 ),
-            }
-            derive_serde.push(meta);
-        }
-        if derive_serde.len() > 1 {
-            for (i, derive_serde) in derive_serde.iter().enumerate() {
-                extend_errors!(
+                meta => extend_errors!(
                     result,
                     syn::Error::new(
-                        derive_serde.span(),
-                        format!(
-                            "`derive_serde` appears more than once (occurrence #{})",
-                            i + 1
-                        )
+                        meta.span(),
+                        "tarpc::service does not support this meta item"
                     )
-                );
+                ),
+            }
+        }
+        let derive_serde = derive_serde.unwrap_or(cfg!(feature = "serde1"));
+        let catch_unknown_methods = catch_unknown_methods.unwrap_or(false);
+        let cli = cli.unwrap_or(false);
+        if let (Some(_), Some(span)) = (client_only, server_only) {
+            extend_errors!(
+                result,
+                syn::Error::new(span, "`client_only` and `server_only` can't both be set")
+            );
+        }
+        if catch_unknown_methods && !derive_serde {
+            extend_errors!(
+                result,
+                syn::Error::new(
+                    input.span(),
+                    "`catch_unknown_methods` requires `derive_serde` to be enabled"
+                )
+            );
+        }
+        if cli && !derive_serde {
+            extend_errors!(
+                result,
+                syn::Error::new(input.span(), "`cli` requires `derive_serde` to be enabled")
+            );
+        }
+        let fuzz = fuzz.unwrap_or(false);
+        if fuzz && !derive_serde {
+            extend_errors!(
+                result,
+                syn::Error::new(input.span(), "`fuzz` requires `derive_serde` to be enabled")
+            );
+        }
+        let tower = tower.unwrap_or(false);
+        if let (true, Some(span)) = (tower, client_only) {
+            extend_errors!(
+                result,
+                syn::Error::new(
+                    span,
+                    "`tower` requires the server half, so `client_only` can't be set"
+                )
+            );
+        }
+        let instrumented_client = instrumented_client.unwrap_or(false);
+        if let (true, Some(span)) = (instrumented_client, server_only) {
+            extend_errors!(
+                result,
+                syn::Error::new(
+                    span,
+                    "`instrumented_client` requires the client half, so `server_only` can't be set"
+                )
+            );
+        }
+        if wire_compat.is_some() && !derive_serde {
+            extend_errors!(
+                result,
+                syn::Error::new(
+                    input.span(),
+                    "`wire_compat` requires `derive_serde` to be enabled"
+                )
+            );
+        }
+        let method_ids = method_ids.unwrap_or(false);
+        if method_ids && !derive_serde {
+            extend_errors!(
+                result,
+                syn::Error::new(
+                    input.span(),
+                    "`method_ids` requires `derive_serde` to be enabled"
+                )
+            );
+        }
+        let passthrough_bytes = passthrough_bytes.unwrap_or(false);
+        if passthrough_bytes && !derive_serde {
+            extend_errors!(
+                result,
+                syn::Error::new(
+                    input.span(),
+                    "`passthrough_bytes` requires `derive_serde` to be enabled"
+                )
+            );
+        }
+        result?;
+        Ok(Self {
+            derive_serde,
+            derive_rkyv: derive_rkyv.unwrap_or(cfg!(feature = "rkyv")),
+            derives,
+            catch_unknown_methods,
+            client_only: client_only.is_some(),
+            server_only: server_only.is_some(),
+            cli,
+            schema: schema.unwrap_or(false),
+            fuzz,
+            tower,
+            instrumented_client,
+            wire_compat,
+            version,
+            types_vis,
+            extends,
+            prefix,
+            client_name,
+            request_name,
+            response_name,
+            method_ids,
+            passthrough_bytes,
+        })
+    }
+}
+
+/// Parses the `bool` value of `meta` into `flag`. `missing_feature` is reported if the value is
+/// true but the feature it requires is disabled.
+fn parse_flag(
+    flag: &mut Option<bool>,
+    meta: &MetaNameValue,
+    missing_feature: Option<&str>,
+) -> syn::Result<()> {
+    let name = meta.path.get_ident().unwrap();
+    if flag.is_some() {
+        return Err(syn::Error::new(
+            meta.span(),
+            format!("`{name}` appears more than once"),
+        ));
+    }
+    match meta.lit {
+        Lit::Bool(LitBool { value: true, .. }) => match missing_feature {
+            Some(missing_feature) => return Err(syn::Error::new(meta.span(), missing_feature)),
+            None => *flag = Some(true),
+        },
+        Lit::Bool(LitBool { value: false, .. }) => *flag = Some(false),
+        _ => {
+            return Err(syn::Error::new(
+                meta.lit.span(),
+                format!("`{name}` expects a value of type `bool`"),
+            ))
+        }
+    }
+    Ok(())
+}
+
+/// Parses the identifier in the string value of `meta` into `ident`.
+fn parse_ident(ident: &mut Option<Ident>, meta: &MetaNameValue) -> syn::Result<()> {
+    let name = meta.path.get_ident().unwrap();
+    if ident.is_some() {
+        return Err(syn::Error::new(
+            meta.span(),
+            format!("`{name}` appears more than once"),
+        ));
+    }
+    match &meta.lit {
+        Lit::Str(lit) => match lit.parse::<Ident>() {
+            Ok(parsed) => *ident = Some(parsed),
+            Err(_) => {
+                return Err(syn::Error::new(
+                    lit.span(),
+                    format!("`{name}` expects an identifier, e.g. `{name} = \"Name\"`"),
+                ))
             }
+        },
+        lit => {
+            return Err(syn::Error::new(
+                lit.span(),
+                format!("`{name}` expects an identifier, e.g. `{name} = \"Name\"`"),
+            ))
         }
-        let derive_serde = result?.unwrap_or(cfg!(feature = "serde1"));
-        Ok(Self(derive_serde))
     }
+    Ok(())
 }
 
 /// A helper attribute to avoid a direct dependency on Serde.
@@ -234,94 +1038,20 @@ pub fn derive_serde(_attr: TokenStream, item: TokenStream) -> TokenStream {
     proc_macro::TokenStream::from(gen)
 }
 
-// If `derive_rkyv` meta item is not present, defaults to cfg!(feature = "rkyv").
-// `derive_rkyv` can only be true when rkyv is enabled.
-struct DeriveRkyv(bool);
-
-impl Parse for DeriveRkyv {
-    fn parse(input: ParseStream) -> syn::Result<Self> {
-        let mut result = Ok(None);
-        let mut derive_rkyv = Vec::new();
-        let meta_items = input.parse_terminated::<MetaNameValue, Comma>(MetaNameValue::parse)?;
-        for meta in meta_items {
-            if meta.path.segments.len() != 1 {
-                extend_errors!(
-                    result,
-                    syn::Error::new(
-                        meta.span(),
-                        "tarpc::service does not support this meta item"
-                    )
-                );
-                continue;
-            }
-            let segment = meta.path.segments.first().unwrap();
-            if segment.ident != "derive_rkyv" {
-                extend_errors!(
-                    result,
-                    syn::Error::new(
-                        meta.span(),
-                        "tarpc::service does not support this meta item"
-                    )
-                );
-                continue;
-            }
-            match meta.lit {
-                Lit::Bool(LitBool { value: true, .. }) if cfg!(feature = "rkyv") => {
-                    result = result.and(Ok(Some(true)))
-                }
-                Lit::Bool(LitBool { value: true, .. }) => {
-                    extend_errors!(
-                        result,
-                        syn::Error::new(
-                            meta.span(),
-                            "To enable rkyv, first enable the `rkyv` feature of tarpc"
-                        )
-                    );
-                }
-                Lit::Bool(LitBool { value: false, .. }) => result = result.and(Ok(Some(false))),
-                _ => extend_errors!(
-                    result,
-                    syn::Error::new(
-                        meta.lit.span(),
-                        "`derive_rkyv` expects a value of type `bool`"
-                    )
-                ),
-            }
-            derive_rkyv.push(meta);
-        }
-        if derive_rkyv.len() > 1 {
-            for (i, derive_rkyv) in derive_rkyv.iter().enumerate() {
-                extend_errors!(
-                    result,
-                    syn::Error::new(
-                        derive_rkyv.span(),
-                        format!(
-                            "`derive_rkyv` appears more than once (occurrence #{})",
-                            i + 1
-                        )
-                    )
-                );
-            }
-        }
-        let derive_rkyv = result?.unwrap_or(cfg!(feature = "rkyv"));
-        Ok(Self(derive_rkyv))
-    }
-}
-
 /// A helper attribute to avoid a direct dependency on rkyv.
 ///
 /// Adds the following annotations to the annotated item:
 ///
 /// ```rust
-/// #[derive(tarpc::rkyv::Serialize, tarpc::rkyv::Deserialize, tarpc::rkyv::Archive)]
-/// #[archive(crate = "tarpc::rkyv", check_bytes)]
+/// #[derive(::tarpc::rkyv::Serialize, ::tarpc::rkyv::Deserialize, ::tarpc::rkyv::Archive)]
+/// #[archive(crate = "::tarpc::rkyv", check_bytes)]
 /// # struct Foo;
 /// ```
 #[proc_macro_attribute]
 pub fn derive_rkyv(_attr: TokenStream, item: TokenStream) -> TokenStream {
     let mut gen: proc_macro2::TokenStream = quote! {
-        #[derive(tarpc::rkyv::Serialize, tarpc::rkyv::Deserialize, tarpc::rkyv::Archive)]
-        #[archive(crate = "tarpc::rkyv", check_bytes)]
+        #[derive(::tarpc::rkyv::Serialize, ::tarpc::rkyv::Deserialize, ::tarpc::rkyv::Archive)]
+        #[archive(crate = "::tarpc::rkyv", check_bytes)]
     };
     gen.extend(proc_macro2::TokenStream::from(item));
     proc_macro::TokenStream::from(gen)
@@ -336,14 +1066,38 @@ pub fn derive_rkyv(_attr: TokenStream, item: TokenStream) -> TokenStream {
 /// - ResponseFut Future
 #[proc_macro_attribute]
 pub fn service(attr: TokenStream, input: TokenStream) -> TokenStream {
-    let attr2 = attr.clone();
-    let derive_serde = parse_macro_input!(attr as DeriveSerde);
-    let derive_rkyv = parse_macro_input!(attr2 as DeriveRkyv);
+    let ServiceArgs {
+        derive_serde,
+        derive_rkyv,
+        ref derives,
+        catch_unknown_methods,
+        client_only,
+        server_only,
+        cli,
+        schema,
+        fuzz,
+        tower,
+        instrumented_client,
+        ref wire_compat,
+        ref version,
+        ref types_vis,
+        ref extends,
+        ref prefix,
+        ref client_name,
+        ref request_name,
+        ref response_name,
+        method_ids,
+        passthrough_bytes,
+    } = parse_macro_input!(attr as ServiceArgs);
     let unit_type: &Type = &parse_quote!(());
     let Service {
         ref attrs,
         ref vis,
         ref ident,
+        ref generics,
+        ref supertraits,
+        ref consts,
+        ref error,
         ref rpcs,
     } = parse_macro_input!(input as Service);
 
@@ -351,52 +1105,343 @@ pub fn service(attr: TokenStream, input: TokenStream) -> TokenStream {
         .iter()
         .map(|rpc| snake_to_camel(&rpc.ident.unraw().to_string()))
         .collect();
-    let args: &[&[PatType]] = &rpcs.iter().map(|rpc| &*rpc.args).collect::<Vec<_>>();
-    let derive_serialize = if derive_serde.0 {
+    // Borrowed args are sent as the owned types they're archived from.
+    let wire_args = &rpcs
+        .iter()
+        .map(|rpc| {
+            rpc.args
+                .iter()
+                .zip(&rpc.borrowed_args)
+                .map(|(arg, borrowed)| match borrowed {
+                    Some(borrowed) => PatType {
+                        ty: Box::new(borrowed.owned.clone()),
+                        ..arg.clone()
+                    },
+                    None => arg.clone(),
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let args: &[&[PatType]] = &wire_args.iter().map(|args| &**args).collect::<Vec<_>>();
+    let zero_copy = rpcs
+        .iter()
+        .any(|rpc| rpc.borrowed_args.iter().any(Option::is_some));
+    let derive_serialize = if derive_serde {
+        // With method ids, the derived impls are wrapped by impls mapping variant indices to ids.
+        let remote = method_ids.then(|| quote!(#[serde(remote = "Self")]));
         Some(
             quote! {#[derive(::tarpc::serde::Serialize, ::tarpc::serde::Deserialize)]
-            #[serde(crate = "::tarpc::serde")]},
+            #[serde(crate = "::tarpc::serde")]
+            #remote},
         )
     } else {
         None
     };
 
-    let derive_rkyv = if derive_rkyv.0 {
+    let derive_rkyv = if derive_rkyv {
         Some(
-            quote! {#[derive(tarpc::rkyv::Serialize, tarpc::rkyv::Deserialize, tarpc::rkyv::Archive)]
-        #[archive(crate = "tarpc::rkyv", check_bytes)]},
+            quote! {#[derive(::tarpc::rkyv::Serialize, ::tarpc::rkyv::Deserialize, ::tarpc::rkyv::Archive)]
+            #[archive(crate = "::tarpc::rkyv", check_bytes)]},
         )
     } else {
         None
     };
 
-    let methods = rpcs.iter().map(|rpc| &rpc.ident).collect::<Vec<_>>();
-    let request_names = methods
+    let variant_attrs = &rpcs
         .iter()
-        .map(|m| format!("{ident}.{m}"))
+        .map(|rpc| {
+            let wire_name = match &rpc.rename {
+                Some(rename) if derive_serde => Some(quote!(#[serde(rename = #rename)])),
+                _ => None,
+            };
+            // Without serde derives, serde attrs are dropped, so that services declaring them
+            // also build without serde.
+            let serde_attrs = rpc.serde_attrs.iter().filter(|_| derive_serde);
+            quote!(#wire_name #( #serde_attrs )*)
+        })
         .collect::<Vec<_>>();
-
-    ServiceGenerator {
+    let payload_attr = |ty: &Type| {
+        (passthrough_bytes && is_bytes(ty))
+            .then(|| quote!(#[serde(with = "::tarpc::serde_transport::payload")]))
+    };
+    let request_fields = &rpcs
+        .iter()
+        .zip(args)
+        .map(|(rpc, args)| {
+            args.iter()
+                .zip(&rpc.arg_serde_attrs)
+                .map(|(arg, serde_attrs)| {
+                    let serde_attrs = serde_attrs.iter().filter(|_| derive_serde);
+                    let payload_attr = payload_attr(&arg.ty);
+                    quote!(#( #serde_attrs )* #payload_attr #arg)
+                })
+                .collect()
+        })
+        .collect::<Vec<_>>();
+    let methods = rpcs.iter().map(|rpc| &rpc.ident).collect::<Vec<_>>();
+    let return_types = &rpcs
+        .iter()
+        .map(|rpc| match rpc.output {
+            ReturnType::Type(_, ref ty) => ty,
+            ReturnType::Default => unit_type,
+        })
+        .collect::<Vec<_>>();
+    let request_names = methods
+        .iter()
+        .map(|m| format!("{ident}.{m}"))
+        .collect::<Vec<_>>();
+    let stream_items = &return_types
+        .iter()
+        .map(|ty| stream_item_type(ty))
+        .collect::<Vec<_>>();
+    let response_types = &return_types
+        .iter()
+        .zip(stream_items)
+        .zip(rpcs)
+        .map(|((ty, stream_item), rpc)| {
+            stream_item
+                .or_else(|| application_result_ok_type(rpc, ty))
+                .unwrap_or(ty)
+        })
+        .collect::<Vec<_>>();
+    let response_variant_attrs = &variant_attrs
+        .iter()
+        .zip(response_types)
+        .map(|(attrs, ty)| {
+            let payload_attr = payload_attr(ty);
+            quote!(#attrs #payload_attr)
+        })
+        .collect::<Vec<_>>();
+
+    // The generated enums are only generic over the type parameters their variants use, because
+    // unused type parameters are an error.
+    let type_params = &generics
+        .type_params()
+        .map(|param| &param.ident)
+        .collect::<Vec<_>>();
+    let request_params = &type_params
+        .iter()
+        .copied()
+        .filter(|param| {
+            args.iter()
+                .flat_map(|args| args.iter())
+                .any(|arg| mentions_ident(arg.ty.to_token_stream(), param))
+        })
+        .collect::<Vec<_>>();
+    let response_params = &type_params
+        .iter()
+        .copied()
+        .filter(|param| {
+            response_types
+                .iter()
+                .any(|ty| mentions_ident(ty.to_token_stream(), param))
+        })
+        .collect::<Vec<_>>();
+    // The generated types are named after the service, unless renamed.
+    let prefix = prefix.as_ref().unwrap_or(ident);
+    let request_ident = &request_name
+        .clone()
+        .unwrap_or_else(|| format_ident!("{}Request", prefix));
+    let response_ident = &response_name
+        .clone()
+        .unwrap_or_else(|| format_ident!("{}Response", prefix));
+    let client_ident = &client_name
+        .clone()
+        .unwrap_or_else(|| format_ident!("{}Client", prefix));
+    if catch_unknown_methods {
+        if let Some(rpc) = rpcs
+            .iter()
+            .zip(camel_case_fn_names)
+            .find_map(|(rpc, name)| (name == "Unknown").then(|| rpc))
+        {
+            return syn::Error::new(
+                rpc.ident.span(),
+                format!("method name conflicts with generated variant `{request_ident}::Unknown`"),
+            )
+            .to_compile_error()
+            .into();
+        }
+    }
+
+    let bases = &extends.iter().map(BaseService::new).collect::<Vec<_>>();
+    for base in bases {
+        if let Some(rpc) = rpcs
+            .iter()
+            .zip(camel_case_fn_names)
+            .find_map(|(rpc, name)| (base.variant == name).then(|| rpc))
+        {
+            return syn::Error::new(
+                rpc.ident.span(),
+                format!(
+                    "method name conflicts with the variant `{request_ident}::{}` of the extended \
+                     service",
+                    base.variant
+                ),
+            )
+            .to_compile_error()
+            .into();
+        }
+    }
+
+    if zero_copy {
+        let unsupported = if derive_rkyv.is_none() {
+            Some("borrowed args require `derive_rkyv` to be enabled")
+        } else if !generics.params.is_empty() {
+            Some("borrowed args aren't supported on generic services")
+        } else if catch_unknown_methods {
+            Some("borrowed args aren't supported with `catch_unknown_methods`")
+        } else if cli {
+            Some("borrowed args aren't supported with `cli`")
+        } else if fuzz {
+            Some("borrowed args aren't supported with `fuzz`")
+        } else if tower {
+            Some("borrowed args aren't supported with `tower`")
+        } else if !bases.is_empty() {
+            Some("borrowed args aren't supported with `extends`")
+        } else {
+            None
+        };
+        if let Some(unsupported) = unsupported {
+            return syn::Error::new(ident.span(), unsupported)
+                .to_compile_error()
+                .into();
+        }
+    }
+    if cli && !generics.params.is_empty() {
+        return syn::Error::new(generics.span(), "`cli` isn't supported on generic services")
+            .to_compile_error()
+            .into();
+    }
+    if fuzz {
+        let unsupported = if !generics.params.is_empty() {
+            Some("`fuzz` isn't supported on generic services")
+        } else if catch_unknown_methods {
+            Some("`fuzz` isn't supported with `catch_unknown_methods`")
+        } else {
+            None
+        };
+        if let Some(unsupported) = unsupported {
+            return syn::Error::new(ident.span(), unsupported)
+                .to_compile_error()
+                .into();
+        }
+    }
+    if wire_compat.is_some() && !generics.params.is_empty() {
+        return syn::Error::new(
+            generics.span(),
+            "`wire_compat` isn't supported on generic services",
+        )
+        .to_compile_error()
+        .into();
+    }
+    if instrumented_client && !generics.params.is_empty() {
+        return syn::Error::new(
+            generics.span(),
+            "`instrumented_client` isn't supported on generic services",
+        )
+        .to_compile_error()
+        .into();
+    }
+    if tower && !generics.params.is_empty() {
+        return syn::Error::new(
+            generics.span(),
+            "`tower` isn't supported on generic services",
+        )
+        .to_compile_error()
+        .into();
+    }
+    let ids = match method_ids_of(rpcs, method_ids) {
+        Ok(ids) => ids,
+        Err(e) => return e.to_compile_error().into(),
+    };
+    if method_ids {
+        let unsupported = if !generics.params.is_empty() {
+            Some("`method_ids` isn't supported on generic services")
+        } else if catch_unknown_methods {
+            Some("`method_ids` isn't supported with `catch_unknown_methods`")
+        } else if !bases.is_empty() {
+            Some("`method_ids` isn't supported with `extends`")
+        } else {
+            None
+        };
+        if let Some(unsupported) = unsupported {
+            return syn::Error::new(ident.span(), unsupported)
+                .to_compile_error()
+                .into();
+        }
+    }
+
+    ServiceGenerator {
         service_ident: ident,
-        client_stub_ident: &format_ident!("{}Stub", ident),
-        server_ident: &format_ident!("Serve{}", ident),
-        client_ident: &format_ident!("{}Client", ident),
-        request_ident: &format_ident!("{}Request", ident),
-        response_ident: &format_ident!("{}Response", ident),
+        client_stub_ident: &format_ident!("{}Stub", prefix),
+        dyn_stub_ident: &format_ident!("Dyn{}Stub", prefix),
+        server_ident: &format_ident!("Serve{}", prefix),
+        client_ident,
+        request_ident,
+        response_ident,
+        prefix,
+        // Services with borrowed args send archived requests.
+        request_type: &if zero_copy {
+            quote!(::tarpc::zero_copy::ArchivedBytes<#request_ident>)
+        } else {
+            with_generic_args(request_ident, request_params)
+        },
+        response_type: &with_generic_args(response_ident, response_params),
+        generics,
+        supertraits,
+        consts,
+        error_type: error.as_ref(),
+        shared_error_oks: &rpcs
+            .iter()
+            .zip(return_types)
+            .map(|(rpc, ty)| {
+                rpc.shared_error
+                    .then(|| result_args(ty).map(|args| args[0]))
+                    .flatten()
+            })
+            .collect::<Vec<_>>(),
+        // Unlike bounds on `Self`, which are implied by bounds on the service, the other
+        // predicates of the where clause must be repeated wherever the type parameters are.
+        where_predicates: &generics
+            .where_clause
+            .iter()
+            .flat_map(|where_clause| &where_clause.predicates)
+            .filter(|predicate| {
+                !mentions_ident(
+                    predicate.to_token_stream(),
+                    &Ident::new("Self", Span::call_site()),
+                )
+            })
+            .collect::<Vec<_>>(),
+        type_params,
+        bounded_type_params: &generics
+            .type_params()
+            .map(|param| TypeParam {
+                eq_token: None,
+                default: None,
+                ..param.clone()
+            })
+            .collect::<Vec<_>>(),
+        request_params,
+        response_params,
         vis,
+        types_vis: types_vis.as_ref().unwrap_or(vis),
         args,
         method_attrs: &rpcs.iter().map(|rpc| &*rpc.attrs).collect::<Vec<_>>(),
+        method_cfgs: &rpcs
+            .iter()
+            .map(|rpc| {
+                let cfgs = rpc.attrs.iter().filter(|attr| attr.path.is_ident("cfg"));
+                quote!(#( #cfgs )*)
+            })
+            .collect::<Vec<_>>(),
         method_idents: &methods,
         request_names: &request_names,
         attrs,
         rpcs,
-        return_types: &rpcs
-            .iter()
-            .map(|rpc| match rpc.output {
-                ReturnType::Type(_, ref ty) => ty,
-                ReturnType::Default => unit_type,
-            })
-            .collect::<Vec<_>>(),
+        return_types,
+        response_types,
+        stream_items,
         arg_pats: &args
             .iter()
             .map(|args| args.iter().map(|arg| &*arg.pat).collect())
@@ -406,6 +1451,28 @@ pub fn service(attr: TokenStream, input: TokenStream) -> TokenStream {
             .zip(camel_case_fn_names.iter())
             .map(|(rpc, name)| Ident::new(name, rpc.ident.span()))
             .collect::<Vec<_>>(),
+        variant_attrs,
+        response_variant_attrs,
+        request_fields,
+        derives,
+        catch_unknown_methods,
+        client: !server_only,
+        server: !client_only,
+        cli,
+        schema,
+        fuzz,
+        tower,
+        instrumented_client,
+        // Services sent with serde can be served over serde transports in one line.
+        quickstart: cfg!(feature = "serde-transport")
+            && derive_serde
+            && generics.params.is_empty()
+            && !zero_copy,
+        wire_compat: wire_compat.as_ref(),
+        version: version.as_ref(),
+        zero_copy,
+        bases,
+        method_ids: method_ids.then(|| &*ids),
         derive_serialize: derive_serialize.as_ref(),
         derive_rkyv: derive_rkyv.as_ref(),
     }
@@ -418,25 +1485,133 @@ pub fn service(attr: TokenStream, input: TokenStream) -> TokenStream {
 struct ServiceGenerator<'a> {
     service_ident: &'a Ident,
     client_stub_ident: &'a Ident,
+    dyn_stub_ident: &'a Ident,
     server_ident: &'a Ident,
     client_ident: &'a Ident,
     request_ident: &'a Ident,
     response_ident: &'a Ident,
+    /// The prefix of the names of the generated types.
+    prefix: &'a Ident,
+    /// The requests sent over the wire: the request enum with its generic arguments, or its
+    /// archive for services with borrowed args.
+    request_type: &'a TokenStream2,
+    /// The response enum with its generic arguments.
+    response_type: &'a TokenStream2,
+    generics: &'a Generics,
+    /// The supertraits of the service trait, other than the services it extends.
+    supertraits: &'a [TypeParamBound],
+    /// The associated consts of the service trait.
+    consts: &'a [TraitItemConst],
+    /// The error type of methods returning `Result<T>`, if declared.
+    error_type: Option<&'a Type>,
+    /// For each method returning `Result<T>` with the declared error type, `T`.
+    shared_error_oks: &'a [Option<&'a Type>],
+    /// The predicates of the service's where clause that don't mention `Self`.
+    where_predicates: &'a [&'a WherePredicate],
+    type_params: &'a [&'a Ident],
+    /// The service's type parameters with their bounds but without their defaults, as declared in
+    /// impls.
+    bounded_type_params: &'a [TypeParam],
+    /// The type parameters used by the request enum.
+    request_params: &'a [&'a Ident],
+    /// The type parameters used by the response enum.
+    response_params: &'a [&'a Ident],
     vis: &'a Visibility,
+    /// The visibility of the request, response, and client types.
+    types_vis: &'a Visibility,
     attrs: &'a [Attribute],
     rpcs: &'a [RpcMethod],
     camel_case_idents: &'a [Ident],
     method_idents: &'a [&'a Ident],
     request_names: &'a [String],
     method_attrs: &'a [&'a [Attribute]],
+    /// The `#[cfg(...)]` attributes of each method, which gate everything generated for it.
+    method_cfgs: &'a [TokenStream2],
     args: &'a [&'a [PatType]],
     return_types: &'a [&'a Type],
+    /// The types sent in the response enum. Same as `return_types`, except that for methods
+    /// marked `#[tarpc::application_error]` only `T` of their `Result<T, E>` is sent, while
+    /// errors are sent as application errors.
+    response_types: &'a [&'a Type],
+    /// For each method returning `impl Stream<Item = T>`, `T`.
+    stream_items: &'a [Option<&'a Type>],
     arg_pats: &'a [Vec<&'a Pat>],
+    /// The serde attributes of each method's request and response variants.
+    variant_attrs: &'a [TokenStream2],
+    /// The serde attributes of each method's response variant, which also mark `Bytes` responses
+    /// as payloads if set with `passthrough_bytes = true`.
+    response_variant_attrs: &'a [TokenStream2],
+    /// The fields of each method's request variant, with their serde attributes.
+    request_fields: &'a [Vec<TokenStream2>],
+    /// Extra traits to derive on the request and response enums.
+    derives: &'a [Path],
+    /// Whether the request enum has an `Unknown` variant catching requests for unknown methods.
+    catch_unknown_methods: bool,
+    /// Whether to generate the client stub.
+    client: bool,
+    /// Whether to generate the service trait and serve fn.
+    server: bool,
+    /// Whether to implement `tarpc::cli::CliRequest` for the request enum.
+    cli: bool,
+    /// Whether to generate a `schema()` fn for the request enum.
+    schema: bool,
+    /// Whether to implement `Arbitrary` for the request enum and generate a `fuzz` fn for it.
+    fuzz: bool,
+    /// Whether to generate a `tower::Service` newtype for each method.
+    tower: bool,
+    /// Whether to generate a client wrapper recording metrics and spans of each call.
+    instrumented_client: bool,
+    /// Whether to generate `serve_tcp` and `serve_unix` fns on the service trait, for the
+    /// transports enabled.
+    quickstart: bool,
+    /// The directory of the golden samples, if wire compatibility tests are generated.
+    wire_compat: Option<&'a LitStr>,
+    /// The version of the service, if declared.
+    version: Option<&'a LitStr>,
+    /// Whether the service has borrowed args, so that it sends archived requests.
+    zero_copy: bool,
+    /// The services whose methods the service includes.
+    bases: &'a [BaseService],
+    /// The id of each method, if methods are sent as ids.
+    method_ids: Option<&'a [u32]>,
     derive_serialize: Option<&'a TokenStream2>,
     derive_rkyv: Option<&'a TokenStream2>,
 }
 
 impl<'a> ServiceGenerator<'a> {
+    /// Returns serde impls for `ty` that send method ids in place of the variant indices of its
+    /// derived impls, if methods are sent as ids.
+    fn impl_serde_with_method_ids(&self, ty: &Ident) -> Option<TokenStream2> {
+        self.method_ids?;
+        let request_ident = self.request_ident;
+        Some(quote! {
+            impl ::tarpc::serde::Serialize for #ty {
+                fn serialize<S>(&self, serializer: S) -> ::core::result::Result<S::Ok, S::Error>
+                    where S: ::tarpc::serde::Serializer
+                {
+                    #ty::serialize(
+                        self,
+                        ::tarpc::method_ids::IdSerializer::new(
+                            serializer,
+                            #request_ident::METHOD_IDS,
+                        ),
+                    )
+                }
+            }
+
+            impl<'de> ::tarpc::serde::Deserialize<'de> for #ty {
+                fn deserialize<D>(deserializer: D) -> ::core::result::Result<Self, D::Error>
+                    where D: ::tarpc::serde::Deserializer<'de>
+                {
+                    #ty::deserialize(::tarpc::method_ids::IdDeserializer::new(
+                        deserializer,
+                        #request_ident::METHOD_IDS,
+                    ))
+                }
+            }
+        })
+    }
+
     fn trait_service(&self) -> TokenStream2 {
         let &Self {
             attrs,
@@ -444,49 +1619,187 @@ impl<'a> ServiceGenerator<'a> {
             vis,
             return_types,
             service_ident,
-            client_stub_ident,
-            request_ident,
-            response_ident,
             server_ident,
+            generics,
+            supertraits,
+            consts,
+            type_params,
+            stream_items,
+            bases,
             ..
         } = self;
 
+        let where_clause = &generics.where_clause;
+        let base_services = bases.iter().map(|base| &base.service);
+        let quickstart_fns = self.quickstart_fns();
         let rpc_fns = rpcs
             .iter()
             .zip(return_types.iter())
+            .zip(stream_items.iter())
             .map(
                 |(
-                    RpcMethod {
-                        attrs, ident, args, ..
-                    },
-                    output,
+                    (
+                        RpcMethod {
+                            attrs,
+                            ident,
+                            args,
+                            default,
+                            ..
+                        },
+                        output,
+                    ),
+                    stream_item,
                 )| {
+                    // Response streams are sent by the channel's task, so they must outlive the
+                    // request handler.
+                    let stream_bounds = stream_item
+                        .is_some()
+                        .then(|| quote!(+ ::core::marker::Send + 'static));
+                    let body = match default {
+                        Some(default) => quote!(#default),
+                        None => quote!(;),
+                    };
                     quote! {
                         #( #attrs )*
-                        async fn #ident(self, context: ::tarpc::context::Context, #( #args ),*) -> #output;
+                        async fn #ident(self, context: ::tarpc::context::Context, #( #args ),*) -> #output #stream_bounds #body
                     }
                 },
             );
-
-        let stub_doc = format!("The stub trait for service [`{service_ident}`].");
         quote! {
             #( #attrs )*
-            #vis trait #service_ident: ::core::marker::Sized {
+            #vis trait #service_ident #generics:
+                #( #supertraits + )* #( #base_services + )* ::core::marker::Sized
+                #where_clause
+            {
+                #( #consts )*
+
                 #( #rpc_fns )*
 
                 /// Returns a serving function to use with
                 /// [InFlightRequest::execute](::tarpc::server::InFlightRequest::execute).
-                fn serve(self) -> #server_ident<Self> {
-                    #server_ident { service: self }
+                fn serve(self) -> #server_ident<Self, #( #type_params ),*> {
+                    #server_ident { service: self, marker: ::core::marker::PhantomData }
+                }
+
+                #quickstart_fns
+            }
+        }
+    }
+
+    /// Returns the fns of the service trait serving it over the serde transports enabled.
+    fn quickstart_fns(&self) -> Option<TokenStream2> {
+        let &Self {
+            quickstart,
+            service_ident,
+            request_type,
+            response_type,
+            stream_items,
+            ..
+        } = self;
+        if !quickstart {
+            return None;
+        }
+
+        // Services with streaming methods respond with bodies.
+        let body = if stream_items.iter().any(Option::is_some) {
+            "_body"
+        } else {
+            ""
+        };
+        let serve_fn = |transport: &str,
+                        addr_bound: TokenStream2,
+                        local_addr: TokenStream2,
+                        doc: &str| {
+            let serve_fn = format_ident!("serve_{}", transport);
+            let quickstart_fn = format_ident!("serve_{}{}", transport, body);
+            let transport = format_ident!("{}Transport", snake_to_camel(transport));
+            quote! {
+                #[doc = #doc]
+                /// See [`quickstart`](::tarpc::server::quickstart) for details.
+                fn #serve_fn<A, Codec, CodecFn>(
+                    self,
+                    addr: A,
+                    codec_fn: CodecFn,
+                    config: ::tarpc::server::quickstart::Config,
+                ) -> impl ::core::future::Future<
+                    Output = ::std::io::Result<
+                        ::tarpc::server::quickstart::Listening<
+                            #local_addr,
+                            impl ::core::future::Future<Output = ()>,
+                        >,
+                    >,
+                >
+                where
+                    Self: ::core::clone::Clone + ::core::marker::Send + 'static,
+                    A: #addr_bound,
+                    Codec: ::tarpc::tokio_serde::Serializer<::tarpc::Response<#response_type>>
+                        + ::tarpc::tokio_serde::Deserializer<::tarpc::ClientMessage<#request_type>>,
+                    CodecFn: ::core::ops::Fn() -> Codec,
+                    ::tarpc::server::quickstart::#transport<#request_type, #response_type, Codec>:
+                        ::tarpc::Transport<
+                            ::tarpc::Response<#response_type>,
+                            ::tarpc::ClientMessage<#request_type>,
+                        > + ::core::marker::Send + 'static,
+                {
+                    let serve = <Self as #service_ident>::serve(self);
+                    ::tarpc::server::quickstart::#quickstart_fn(addr, serve, codec_fn, config)
                 }
             }
+        };
+        let serve_tcp = cfg!(feature = "tcp").then(|| {
+            serve_fn(
+                "tcp",
+                quote!(::tarpc::server::quickstart::ToSocketAddrs),
+                quote!(::std::net::SocketAddr),
+                "Listens for TCP connections on `addr`, serving each with this server over the \
+                 transport encoded by `codec_fn`.",
+            )
+        });
+        let serve_unix = cfg!(feature = "unix").then(|| {
+            let serve_unix = serve_fn(
+                "unix",
+                quote!(::core::convert::AsRef<::std::path::Path>),
+                quote!(::tarpc::server::quickstart::UnixSocketAddr),
+                "Listens for Unix Domain Socket connections on the socket named by `addr`, \
+                 serving each with this server over the transport encoded by `codec_fn`.",
+            );
+            quote!(#[cfg(unix)] #serve_unix)
+        });
+        Some(quote!(#serve_tcp #serve_unix))
+    }
+
+    fn trait_client_stub(&self) -> TokenStream2 {
+        let &Self {
+            types_vis: vis,
+            service_ident,
+            client_stub_ident,
+            request_type,
+            response_type,
+            generics,
+            type_params,
+            bounded_type_params,
+            where_predicates,
+            server,
+            ..
+        } = self;
 
+        // Without the server half, there's no service trait to link to.
+        let stub_doc = if server {
+            format!("The stub trait for service [`{service_ident}`].")
+        } else {
+            format!("The stub trait for service `{service_ident}`.")
+        };
+        let client_stub = with_generic_args(client_stub_ident, type_params);
+        quote! {
             #[doc = #stub_doc]
-            #vis trait #client_stub_ident: ::tarpc::client::stub::Stub<Req = #request_ident, Resp = #response_ident> {
+            #vis trait #client_stub_ident #generics: ::tarpc::client::stub::Stub<Req = #request_type, Resp = #response_type>
+                where #( #where_predicates, )*
+            {
             }
 
-            impl<S> #client_stub_ident for S
-                where S: ::tarpc::client::stub::Stub<Req = #request_ident, Resp = #response_ident>
+            impl<S, #( #bounded_type_params ),*> #client_stub for S
+                where S: ::tarpc::client::stub::Stub<Req = #request_type, Resp = #response_type>,
+                    #( #where_predicates, )*
             {
             }
         }
@@ -494,14 +1807,28 @@ impl<'a> ServiceGenerator<'a> {
 
     fn struct_server(&self) -> TokenStream2 {
         let &Self {
-            vis, server_ident, ..
+            types_vis: vis,
+            server_ident,
+            type_params,
+            ..
         } = self;
 
         quote! {
             /// A serving function to use with [::tarpc::server::InFlightRequest::execute].
-            #[derive(Clone)]
-            #vis struct #server_ident<S> {
+            #vis struct #server_ident<S, #( #type_params ),*> {
                 service: S,
+                marker: ::core::marker::PhantomData<fn() -> (#( #type_params, )*)>,
+            }
+
+            impl<S, #( #type_params ),*> ::core::clone::Clone for #server_ident<S, #( #type_params ),*>
+                where S: ::core::clone::Clone
+            {
+                fn clone(&self) -> Self {
+                    #server_ident {
+                        service: self.service.clone(),
+                        marker: ::core::marker::PhantomData,
+                    }
+                }
             }
         }
     }
@@ -509,45 +1836,213 @@ impl<'a> ServiceGenerator<'a> {
     fn impl_serve_for_server(&self) -> TokenStream2 {
         let &Self {
             request_ident,
+            request_type,
             server_ident,
             service_ident,
             response_ident,
+            response_type,
             camel_case_idents,
             arg_pats,
             method_idents,
             request_names,
+            return_types,
+            type_params,
+            bounded_type_params,
+            where_predicates,
+            catch_unknown_methods,
+            stream_items,
+            rpcs,
+            zero_copy,
+            bases,
+            method_cfgs,
+            version,
             ..
         } = self;
 
+        // Services with streaming methods respond with bodies, and their other methods with
+        // bodies of a single response.
+        let streams = stream_items.iter().any(Option::is_some);
+        let serve_bodies = return_types
+            .iter()
+            .zip(camel_case_idents.iter())
+            .zip(method_idents.iter())
+            .zip(arg_pats.iter())
+            .zip(stream_items.iter())
+            .zip(rpcs.iter())
+            .map(
+                |(
+                    ((((return_type, camel_case_ident), method_ident), arg_pats), stream_item),
+                    rpc,
+                )| {
+                    let call = quote! {
+                        #service_ident::#method_ident(self.service, ctx, #( #arg_pats ),*).await
+                    };
+                    let response = if stream_item.is_some() {
+                        // A response stream holds its method's permit until it ends.
+                        let hold_permit = rpc.max_concurrent.is_some().then(|| quote!(let _ = &_permit;));
+                        return quote! {
+                            ::core::result::Result::Ok(::tarpc::server::body::Body::new(
+                                ::tarpc::futures::StreamExt::map(#call, move |item| {
+                                    #hold_permit
+                                    ::core::result::Result::Ok(#response_ident::#camel_case_ident(item))
+                                })
+                            ))
+                        };
+                    } else if application_result_ok_type(rpc, return_type).is_some() {
+                        quote! {
+                            match #call {
+                                ::core::result::Result::Ok(resp) => ::core::result::Result::Ok(
+                                    #response_ident::#camel_case_ident(resp)
+                                ),
+                                ::core::result::Result::Err(e) => ::core::result::Result::Err(
+                                    ::tarpc::ServerError::from(
+                                        ::core::convert::Into::<::tarpc::ApplicationError>::into(e)
+                                    )
+                                ),
+                            }
+                        }
+                    } else {
+                        quote! {
+                            ::core::result::Result::Ok(#response_ident::#camel_case_ident(#call))
+                        }
+                    };
+                    if streams {
+                        quote! {
+                            ::core::result::Result::map(#response, ::tarpc::server::body::Body::once)
+                        }
+                    } else {
+                        response
+                    }
+                },
+            );
+        let (resp, resp_bounds) = if streams {
+            (
+                quote!(::tarpc::server::body::Body<#response_type>),
+                quote!(, #response_type: ::core::marker::Send + 'static),
+            )
+        } else {
+            (response_type.clone(), quote!())
+        };
+        let service = with_generic_args(service_ident, type_params);
+        let (unknown_method, serve_unknown) = if catch_unknown_methods {
+            let detail = format!("{service_ident} doesn't implement the method requested");
+            (
+                quote! {
+                    #request_ident::Unknown(_) => ::core::option::Option::None,
+                },
+                quote! {
+                    #request_ident::Unknown(_) => ::core::result::Result::Err(
+                        ::tarpc::ServerError::unimplemented(::std::string::String::from(#detail))
+                    ),
+                },
+            )
+        } else {
+            (quote!(), quote!())
+        };
+
+        // Services with borrowed args serve their requests in place, from the validated archive.
+        let (request_variants, method_req, serve_req) = if zero_copy {
+            (
+                format_ident!("Archived{}", request_ident),
+                quote!(match req.get() {
+                    ::core::result::Result::Ok(req) => req,
+                    ::core::result::Result::Err(_) => return ::core::option::Option::None,
+                }),
+                quote!(req.get()?),
+            )
+        } else {
+            (request_ident.clone(), quote!(req), quote!(req))
+        };
+        // Requests of extended services are served by their serve fns.
+        let base_variants = &bases.iter().map(|base| &base.variant).collect::<Vec<_>>();
+        let base_serve_bodies = bases.iter().map(|base| {
+            let BaseService {
+                variant, service, ..
+            } = base;
+            let response = quote! {
+                ::core::result::Result::map(
+                    ::tarpc::server::Serve::serve(#service::serve(self.service), ctx, req).await,
+                    #response_ident::#variant,
+                )
+            };
+            if streams {
+                quote!(::core::result::Result::map(#response, ::tarpc::server::body::Body::once))
+            } else {
+                response
+            }
+        });
+        let arg_conversions = rpcs.iter().zip(arg_pats.iter()).map(|(rpc, arg_pats)| {
+            let conversions =
+                rpc.borrowed_args
+                    .iter()
+                    .zip(arg_pats.iter())
+                    .map(|(borrowed, arg_pat)| match borrowed {
+                        _ if !zero_copy => quote!(),
+                        Some(BorrowedArg { deref: true, .. }) => {
+                            quote!(let #arg_pat = ::core::ops::Deref::deref(#arg_pat);)
+                        }
+                        Some(BorrowedArg { deref: false, .. }) => quote!(),
+                        None => quote!(let #arg_pat = ::tarpc::zero_copy::deserialize(#arg_pat);),
+                    });
+            quote!(#( #conversions )*)
+        });
+        let limits = rpcs.iter().map(|rpc| {
+            rpc.max_concurrent.map(|max| {
+                quote! {
+                    static LIMIT: ::tarpc::server::limits::requests_per_method::MethodLimit =
+                        ::tarpc::server::limits::requests_per_method::MethodLimit::new(#max);
+                    let _permit = match LIMIT.acquire(&ctx).await {
+                        ::core::result::Result::Ok(permit) => permit,
+                        ::core::result::Result::Err(e) => return ::core::result::Result::Err(e),
+                    };
+                }
+            })
+        });
+
+        let announce_version =
+            version.map(|version| quote!(::tarpc::negotiation::announce_version(#version);));
+
         quote! {
-            impl<S> ::tarpc::server::Serve for #server_ident<S>
-                where S: #service_ident
+            // Serving a deprecated method isn't a use the service implementer can avoid.
+            #[allow(deprecated)]
+            impl<S, #( #bounded_type_params ),*> ::tarpc::server::Serve for #server_ident<S, #( #type_params ),*>
+                where S: #service #resp_bounds,
+                    #( #where_predicates, )*
             {
-                type Req = #request_ident;
-                type Resp = #response_ident;
+                type Req = #request_type;
+                type Resp = #resp;
 
-                fn method(&self, req: &#request_ident) -> ::core::option::Option<&'static str> {
-                    ::core::option::Option::Some(match req {
+                fn method(&self, req: &#request_type) -> ::core::option::Option<&'static str> {
+                    match #method_req {
                         #(
-                            #request_ident::#camel_case_idents{..} => {
-                                #request_names
+                            #method_cfgs
+                            #request_variants::#camel_case_idents{..} => {
+                                ::core::option::Option::Some(#request_names)
                             }
                         )*
-                    })
+                        #( #request_ident::#base_variants(req) => req.__method_name(), )*
+                        #unknown_method
+                    }
                 }
 
-                async fn serve(self, ctx: ::tarpc::context::Context, req: #request_ident)
-                    -> ::core::result::Result<#response_ident, ::tarpc::ServerError> {
-                    match req {
-                        #(
-                            #request_ident::#camel_case_idents{ #( #arg_pats ),* } => {
-                                ::core::result::Result::Ok(#response_ident::#camel_case_idents(
-                                    #service_ident::#method_idents(
-                                        self.service, ctx, #( #arg_pats ),*
-                                    ).await
-                                ))
-                            }
-                        )*
+                fn serve(self, ctx: ::tarpc::context::Context, req: #request_type)
+                    -> impl ::core::future::Future<
+                        Output = ::core::result::Result<#resp, ::tarpc::ServerError>
+                    > {
+                    async move {
+                        #announce_version
+                        match #serve_req {
+                            #(
+                                #method_cfgs
+                                #request_variants::#camel_case_idents{ #( #arg_pats ),* } => {
+                                    #arg_conversions
+                                    #limits
+                                    #serve_bodies
+                                }
+                            )*
+                            #( #request_ident::#base_variants(req) => { #base_serve_bodies } )*
+                            #serve_unknown
+                        }
                     }
                 }
             }
@@ -556,106 +2051,1069 @@ impl<'a> ServiceGenerator<'a> {
 
     fn enum_request(&self) -> TokenStream2 {
         let &Self {
+            derives,
             derive_serialize,
             derive_rkyv,
-            vis,
+            types_vis: vis,
             request_ident,
+            request_params,
             camel_case_idents,
-            args,
+            request_fields,
+            variant_attrs,
+            request_names,
+            method_attrs,
+            rpcs,
+            catch_unknown_methods,
+            bases,
+            method_cfgs,
             ..
         } = self;
+        let request = with_generic_args(request_ident, request_params);
+        let base_variants = &bases.iter().map(|base| &base.variant).collect::<Vec<_>>();
+        let base_requests = bases.iter().map(|base| &base.request).collect::<Vec<_>>();
+        let request_generics = quote!(<#( #request_params ),*>);
+        let base_docs = bases.iter().map(|base| {
+            let service = &base.service;
+            format!(
+                "A request for a method of the extended service `{}`.",
+                quote!(#service).to_string().replace(' ', "")
+            )
+        });
+        let variant_docs = method_attrs.iter().map(|attrs| {
+            attrs
+                .iter()
+                .filter(|attr| attr.path.is_ident("doc"))
+                .collect::<Vec<_>>()
+        });
+        let unknown_variant = catch_unknown_methods.then(|| {
+            quote! {
+                /// A request for a method the service doesn't know, e.g. because the client runs
+                /// a newer version of the service.
+                #[serde(rename = "<unknown>")]
+                Unknown(::tarpc::negotiation::UnknownMethod),
+            }
+        });
+        // Requests are read through a deserializer that maps the names of methods the service
+        // doesn't know to the `Unknown` variant, so that known methods still decode strictly.
+        let unknown_remote = catch_unknown_methods.then(|| quote!(#[serde(remote = "Self")]));
+        let unknown_serde_impls = catch_unknown_methods.then(|| {
+            quote! {
+                impl<#( #request_params: ::tarpc::serde::Serialize ),*> ::tarpc::serde::Serialize
+                    for #request
+                {
+                    fn serialize<S>(&self, serializer: S) -> ::core::result::Result<S::Ok, S::Error>
+                        where S: ::tarpc::serde::Serializer
+                    {
+                        #request_ident::serialize(self, serializer)
+                    }
+                }
+
+                impl<'de, #( #request_params: ::tarpc::serde::Deserialize<'de> ),*>
+                    ::tarpc::serde::Deserialize<'de> for #request
+                {
+                    fn deserialize<D>(deserializer: D) -> ::core::result::Result<Self, D::Error>
+                        where D: ::tarpc::serde::Deserializer<'de>
+                    {
+                        #request_ident::deserialize(
+                            ::tarpc::negotiation::UnknownMethodDeserializer::new(deserializer),
+                        )
+                    }
+                }
+            }
+        });
+        let unknown_arm = |value: TokenStream2| {
+            catch_unknown_methods.then(|| quote!(#request_ident::Unknown(_) => #value,))
+        };
+        let (unknown_version, unknown_supported) = (
+            unknown_arm(quote!(::core::option::Option::None)),
+            unknown_arm(quote!(false)),
+        );
+        let version = |version: Option<&LitStr>| match version {
+            Some(version) => quote!(::core::option::Option::Some(#version)),
+            None => quote!(::core::option::Option::None),
+        };
+        let since = &rpcs
+            .iter()
+            .map(|rpc| version(rpc.since.as_ref()))
+            .collect::<Vec<_>>();
+        let deprecated_since = &rpcs
+            .iter()
+            .map(|rpc| version(rpc.deprecated_since.as_ref()))
+            .collect::<Vec<_>>();
+        let service_version = version(self.version);
+        let no_trace = rpcs.iter().map(|rpc| rpc.no_trace);
+        let method_ids = self.method_ids.map(|ids| {
+            quote! {
+                /// The id sent for each method, in declaration order.
+                #vis const METHOD_IDS: &'static [u32] = &[ #( #method_cfgs #ids, )* ];
+            }
+        });
+        let serde_impls = self.impl_serde_with_method_ids(request_ident);
 
         quote! {
             /// The request sent over the wire from the client to the server.
             #[allow(missing_docs)]
-            #[derive(Debug)]
+            #[derive(Debug, #( #derives ),*)]
             #derive_serialize
+            #unknown_remote
             #derive_rkyv
-            #vis enum #request_ident {
-                #( #camel_case_idents{ #( #args ),* } ),*
+            #vis enum #request {
+                #(
+                    #( #variant_docs )*
+                    #method_cfgs
+                    #variant_attrs #camel_case_idents{ #( #request_fields ),* },
+                )*
+                #(
+                    #[doc = #base_docs]
+                    #base_variants(#base_requests),
+                )*
+                #unknown_variant
+            }
+
+            #(
+                impl #request_generics ::core::convert::From<#base_requests> for #request {
+                    fn from(request: #base_requests) -> Self {
+                        #request_ident::#base_variants(request)
+                    }
+                }
+            )*
+
+            #serde_impls
+            #unknown_serde_impls
+
+            impl<#( #request_params ),*> #request {
+                #method_ids
+
+                /// The version of the service, if declared with `version = "..."`.
+                #vis const VERSION: ::core::option::Option<&'static str> = #service_version;
+
+                /// The versions of the service that introduced and deprecated each method.
+                #vis const METHOD_VERSIONS: &'static [::tarpc::negotiation::MethodVersion] = &[
+                    #(
+                        #method_cfgs
+                        ::tarpc::negotiation::MethodVersion {
+                            method: #request_names,
+                            since: #since,
+                            deprecated_since: #deprecated_since,
+                        },
+                    )*
+                ];
+
+                /// Returns the version of the service that introduced the method requested, if
+                /// declared.
+                #vis fn since(&self) -> ::core::option::Option<&'static str> {
+                    match *self {
+                        #( #method_cfgs #request_ident::#camel_case_idents{..} => #since, )*
+                        #( #request_ident::#base_variants(ref req) => req.since(), )*
+                        #unknown_version
+                    }
+                }
+
+                /// Returns the version of the service that deprecated the method requested, if
+                /// declared.
+                #vis fn deprecated_since(&self) -> ::core::option::Option<&'static str> {
+                    match *self {
+                        #(
+                            #method_cfgs
+                            #request_ident::#camel_case_idents{..} => #deprecated_since,
+                        )*
+                        #( #request_ident::#base_variants(ref req) => req.deprecated_since(), )*
+                        #unknown_version
+                    }
+                }
+
+                /// The names of the methods the service declares, e.g. `"World.hello"`, in order of
+                /// declaration. The methods of the services it extends are in their own
+                /// `METHOD_NAMES`.
+                #vis const METHOD_NAMES: &'static [&'static str] = &[
+                    #( #method_cfgs #request_names, )*
+                ];
+
+                /// Returns the name of the method requested, e.g. `"World.hello"`, or
+                /// [`UnknownMethod::NAME`](::tarpc::negotiation::UnknownMethod::NAME) for a
+                /// method the service doesn't know.
+                #vis fn method_name(&self) -> &'static str {
+                    self.__method_name().unwrap_or(::tarpc::negotiation::UnknownMethod::NAME)
+                }
+
+                /// Returns the name of the service's method named `name`, if it has one. `name`
+                /// is either the full name of the method, e.g. `"World.hello"`, or its name within
+                /// the service, e.g. `"hello"`. Looks through the methods of the services it
+                /// extends, too.
+                #vis fn from_method_name(name: &str) -> ::core::option::Option<&'static str> {
+                    for &method in Self::METHOD_NAMES {
+                        if method == name || method.split_once('.').map(|(_, method)| method) == ::core::option::Option::Some(name) {
+                            return ::core::option::Option::Some(method);
+                        }
+                    }
+                    #(
+                        if let ::core::option::Option::Some(method) = <#base_requests>::from_method_name(name) {
+                            return ::core::option::Option::Some(method);
+                        }
+                    )*
+                    ::core::option::Option::None
+                }
+
+                #[doc(hidden)]
+                #vis fn __method_name(&self) -> ::core::option::Option<&'static str> {
+                    match *self {
+                        #(
+                            #method_cfgs
+                            #request_ident::#camel_case_idents{..} => {
+                                ::core::option::Option::Some(#request_names)
+                            }
+                        )*
+                        #( #request_ident::#base_variants(ref req) => req.__method_name(), )*
+                        #unknown_version
+                    }
+                }
+
+                /// Returns true iff a server at `version` of the service serves this request.
+                #vis fn is_supported_by(&self, version: &str) -> bool {
+                    match *self {
+                        #(
+                            #method_cfgs
+                            #request_ident::#camel_case_idents{..} => {
+                                ::tarpc::negotiation::is_supported(#since, version)
+                            }
+                        )*
+                        #( #request_ident::#base_variants(ref req) => req.is_supported_by(version), )*
+                        #unknown_supported
+                    }
+                }
+
+                /// Returns true iff the request's method is declared with `#[tarpc::no_trace]`,
+                /// so that it's served without a span. See `BaseChannel::with_untraced`.
+                #vis fn is_untraced(&self) -> bool {
+                    match *self {
+                        #( #method_cfgs #request_ident::#camel_case_idents{..} => #no_trace, )*
+                        #( #request_ident::#base_variants(ref req) => req.is_untraced(), )*
+                        #unknown_supported
+                    }
+                }
             }
         }
     }
 
     fn enum_response(&self) -> TokenStream2 {
         let &Self {
+            derives,
             derive_serialize,
             derive_rkyv,
-            vis,
+            types_vis: vis,
             response_ident,
+            response_params,
             camel_case_idents,
-            return_types,
+            response_types,
+            response_variant_attrs,
+            bases,
+            method_cfgs,
             ..
         } = self;
+        let response = with_generic_args(response_ident, response_params);
+        let base_variants = &bases.iter().map(|base| &base.variant).collect::<Vec<_>>();
+        let base_responses = bases.iter().map(|base| &base.response).collect::<Vec<_>>();
+        let response_generics = quote!(<#( #response_params ),*>);
+        let serde_impls = self.impl_serde_with_method_ids(response_ident);
 
         quote! {
             /// The response sent over the wire from the server to the client.
             #[allow(missing_docs)]
-            #[derive(Debug)]
+            #[derive(Debug, #( #derives ),*)]
             #derive_serialize
             #derive_rkyv
-            #vis enum #response_ident {
-                #( #camel_case_idents(#return_types) ),*
+            #vis enum #response {
+                #( #method_cfgs #response_variant_attrs #camel_case_idents(#response_types), )*
+                #( #base_variants(#base_responses), )*
             }
-        }
-    }
 
-    fn struct_client(&self) -> TokenStream2 {
-        let &Self {
-            vis,
-            client_ident,
-            request_ident,
-            response_ident,
-            ..
-        } = self;
+            #serde_impls
 
-        quote! {
-            #[allow(unused)]
-            #[derive(Clone, Debug)]
-            /// The client stub that makes RPC calls to the server. All request methods return
-            /// [Futures](::core::future::Future).
-            #vis struct #client_ident<
-                Stub = ::tarpc::client::Channel<#request_ident, #response_ident>
-            >(Stub);
+            #(
+                impl #response_generics ::core::convert::TryFrom<#response> for #base_responses {
+                    type Error = #response;
+
+                    #[allow(unreachable_patterns)]
+                    fn try_from(response: #response) -> ::core::result::Result<Self, #response> {
+                        match response {
+                            #response_ident::#base_variants(response) => {
+                                ::core::result::Result::Ok(response)
+                            }
+                            response => ::core::result::Result::Err(response),
+                        }
+                    }
+                }
+            )*
         }
     }
 
-    fn impl_client_new(&self) -> TokenStream2 {
+    fn impl_cli_request(&self) -> TokenStream2 {
         let &Self {
-            client_ident,
-            vis,
             request_ident,
             response_ident,
+            camel_case_idents,
+            method_idents,
+            method_attrs,
+            request_names,
+            arg_pats,
+            stream_items,
+            rpcs,
+            bases,
+            method_cfgs,
             ..
         } = self;
+        let base_variants = bases.iter().map(|base| &base.variant);
+        let kebab_case = |ident: &Ident| ident.unraw().to_string().replace('_', "-");
+        let subcommands = &method_idents
+            .iter()
+            .map(|ident| kebab_case(ident))
+            .collect::<Vec<_>>();
+        let arg_names = &arg_pats
+            .iter()
+            .map(|pats| {
+                pats.iter()
+                    .map(|pat| match pat {
+                        Pat::Ident(pat) => kebab_case(&pat.ident),
+                        _ => unreachable!("RPC args are idents"),
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        // The first line of a method's doc comment describes its subcommand.
+        let abouts = method_attrs.iter().map(|attrs| {
+            doc_lines(attrs)
+                .into_iter()
+                .next()
+                .map(|about| quote!(.about(#about)))
+        });
+        let kinds = stream_items.iter().zip(rpcs).map(|(stream_item, rpc)| {
+            if rpc.oneway {
+                quote!(::tarpc::cli::CallKind::Oneway)
+            } else if stream_item.is_some() {
+                quote!(::tarpc::cli::CallKind::Stream)
+            } else {
+                quote!(::tarpc::cli::CallKind::Unary)
+            }
+        });
 
         quote! {
-            impl #client_ident {
-                /// Returns a new client stub that sends requests over the given transport.
-                #vis fn new<T>(config: ::tarpc::client::Config, transport: T)
-                    -> ::tarpc::client::NewClient<
-                        Self,
-                        ::tarpc::client::RequestDispatch<#request_ident, #response_ident, T>
-                    >
-                where
-                    T: ::tarpc::Transport<::tarpc::ClientMessage<#request_ident>, ::tarpc::Response<#response_ident>>
+            impl ::tarpc::cli::CliRequest for #request_ident {
+                type Response = #response_ident;
+
+                fn command(name: &'static str) -> ::tarpc::cli::clap::Command<'static> {
+                    let command = ::tarpc::cli::clap::Command::new(name)
+                        .subcommand_required(true)
+                        .arg_required_else_help(true);
+                    #(
+                        #method_cfgs
+                        let command = command.subcommand(
+                            ::tarpc::cli::clap::Command::new(#subcommands)
+                                #abouts
+                                #(
+                                    .arg(
+                                        ::tarpc::cli::clap::Arg::new(#arg_names)
+                                            .long(#arg_names)
+                                            .value_name("JSON")
+                                            .takes_value(true)
+                                            .required(true)
+                                    )
+                                )*
+                        );
+                    )*
+                    command
+                }
+
+                fn from_arg_matches(matches: &::tarpc::cli::clap::ArgMatches)
+                    -> ::core::result::Result<::tarpc::cli::Call<Self>, ::tarpc::cli::CliError>
                 {
-                    let new_client = ::tarpc::client::new(config, transport);
-                    ::tarpc::client::NewClient {
-                        client: #client_ident(new_client.client),
+                    match matches.subcommand() {
+                        #(
+                            #method_cfgs
+                            ::core::option::Option::Some((#subcommands, matches)) => {
+                                ::core::result::Result::Ok(::tarpc::cli::Call::new(
+                                    #request_names,
+                                    #kinds,
+                                    #request_ident::#camel_case_idents {
+                                        #( #arg_pats: ::tarpc::cli::parse_arg(matches, #arg_names)?, )*
+                                    },
+                                ))
+                            }
+                        )*
+                        ::core::option::Option::Some((name, _)) => ::core::result::Result::Err(
+                            ::tarpc::cli::CliError::UnknownSubcommand(name.into())
+                        ),
+                        ::core::option::Option::None => {
+                            ::core::result::Result::Err(::tarpc::cli::CliError::MissingSubcommand)
+                        }
+                    }
+                }
+
+                fn response_to_json(response: Self::Response)
+                    -> ::tarpc::cli::serde_json::Result<::tarpc::cli::serde_json::Value>
+                {
+                    match response {
+                        #(
+                            #method_cfgs
+                            #response_ident::#camel_case_idents(response) => {
+                                ::tarpc::cli::serde_json::to_value(response)
+                            }
+                        )*
+                        // Requests of extended services aren't parsed from the command line.
+                        #(
+                            #response_ident::#base_variants(response) => {
+                                ::tarpc::cli::serde_json::to_value(response)
+                            }
+                        )*
+                    }
+                }
+            }
+        }
+    }
+
+    fn impl_schema(&self) -> TokenStream2 {
+        let &Self {
+            types_vis: vis,
+            service_ident,
+            request_ident,
+            request_params,
+            attrs,
+            rpcs,
+            args,
+            return_types,
+            response_types,
+            stream_items,
+            method_cfgs,
+            ..
+        } = self;
+        let request = with_generic_args(request_ident, request_params);
+        let service_name = service_ident.unraw().to_string();
+        let service_doc = doc_lines(attrs).join("\n");
+        let methods = rpcs.iter().enumerate().map(|(i, rpc)| {
+            let name = rpc.ident.unraw().to_string();
+            // The schema describes the enums as serde sees them, so it follows their renames.
+            let renames = SerdeRenames::parse(&rpc.serde_attrs, true)?;
+            let wire_name = match (renames.rename, &rpc.rename) {
+                (Some(rename), _) => rename,
+                (None, Some(rename)) => rename.value(),
+                (None, None) => snake_to_camel(&name),
+            };
+            let doc = doc_lines(&rpc.attrs).join("\n");
+            let arg_names = args[i]
+                .iter()
+                .zip(&rpc.arg_serde_attrs)
+                .map(|(arg, serde_attrs)| {
+                    let name = match &*arg.pat {
+                        Pat::Ident(pat) => pat.ident.unraw().to_string(),
+                        _ => unreachable!("RPC args are idents"),
+                    };
+                    Ok(
+                        match (
+                            SerdeRenames::parse(serde_attrs, false)?.rename,
+                            &renames.rename_all,
+                        ) {
+                            (Some(rename), _) => rename,
+                            (None, Some(rule)) => rule.apply(&name),
+                            (None, None) => name,
+                        },
+                    )
+                })
+                .collect::<syn::Result<Vec<_>>>()?;
+            let arg_types = args[i].iter().map(|arg| type_name(&arg.ty));
+            let output = type_name(response_types[i]);
+            let application_errors = stream_items[i].is_none()
+                && application_result_ok_type(rpc, return_types[i]).is_some();
+            let kind = if rpc.oneway {
+                quote!(::tarpc::schema::MethodKind::Oneway)
+            } else if stream_items[i].is_some() {
+                quote!(::tarpc::schema::MethodKind::Stream)
+            } else {
+                quote!(::tarpc::schema::MethodKind::Unary)
+            };
+            let version = |version: Option<&LitStr>| match version {
+                Some(version) => quote!(::core::option::Option::Some(#version)),
+                None => quote!(::core::option::Option::None),
+            };
+            let since = version(rpc.since.as_ref());
+            let deprecated_since = version(rpc.deprecated_since.as_ref());
+            let cfgs = &method_cfgs[i];
+            let id = match self.method_ids {
+                Some(ids) => {
+                    let id = ids[i];
+                    quote!(::core::option::Option::Some(#id))
+                }
+                None => quote!(::core::option::Option::None),
+            };
+            Ok(quote! {
+                #cfgs
+                ::tarpc::schema::MethodSchema {
+                    name: #name,
+                    wire_name: #wire_name,
+                    doc: #doc,
+                    args: &[
+                        #( ::tarpc::schema::ArgSchema { name: #arg_names, ty: #arg_types }, )*
+                    ],
+                    output: #output,
+                    application_errors: #application_errors,
+                    kind: #kind,
+                    since: #since,
+                    deprecated_since: #deprecated_since,
+                    id: #id,
+                }
+            })
+        });
+        let methods = match methods.collect::<syn::Result<Vec<_>>>() {
+            Ok(methods) => methods,
+            Err(e) => return e.to_compile_error(),
+        };
+
+        quote! {
+            impl<#( #request_params ),*> #request {
+                /// Returns a machine-readable description of the service.
+                #vis fn schema() -> ::tarpc::schema::ServiceSchema {
+                    ::tarpc::schema::ServiceSchema {
+                        name: #service_name,
+                        doc: #service_doc,
+                        methods: &[ #( #methods ),* ],
+                    }
+                }
+            }
+        }
+    }
+
+    fn impl_fuzz(&self) -> TokenStream2 {
+        let &Self {
+            types_vis: vis,
+            service_ident,
+            request_ident,
+            camel_case_idents,
+            arg_pats,
+            method_cfgs,
+            stream_items,
+            bases,
+            server,
+            ..
+        } = self;
+        let base_variants = bases.iter().map(|base| &base.variant);
+        let run = if stream_items.iter().any(Option::is_some) {
+            quote!(::tarpc::fuzz::run_body)
+        } else {
+            quote!(::tarpc::fuzz::run)
+        };
+        let doc = format!(
+            "Serves the request decoded or generated from a fuzzer's input with `service`, then \
+             serializes the response; see [`tarpc::fuzz`](::tarpc::fuzz) for details. Call it \
+             from a fuzz target, e.g. \
+             `fuzz_target!(|data: &[u8]| {request_ident}::fuzz(server, data))`."
+        );
+        // Without the server half, there's no service to serve requests with.
+        let fuzz_fn = server.then(|| {
+            quote! {
+                impl #request_ident {
+                    #[doc = #doc]
+                    #vis fn fuzz<S: #service_ident>(service: S, data: &[u8]) {
+                        #run(#service_ident::serve(service), data)
+                    }
+                }
+            }
+        });
+
+        quote! {
+            impl<'a> ::tarpc::fuzz::arbitrary::Arbitrary<'a> for #request_ident {
+                fn arbitrary(u: &mut ::tarpc::fuzz::arbitrary::Unstructured<'a>)
+                    -> ::tarpc::fuzz::arbitrary::Result<Self>
+                {
+                    let variants: &[
+                        fn(&mut ::tarpc::fuzz::arbitrary::Unstructured<'a>)
+                            -> ::tarpc::fuzz::arbitrary::Result<#request_ident>
+                    ] = &[
+                        #(
+                            #method_cfgs
+                            |u| ::core::result::Result::Ok(#request_ident::#camel_case_idents {
+                                #(
+                                    #arg_pats: ::tarpc::fuzz::arbitrary::Arbitrary::arbitrary(u)?,
+                                )*
+                            }),
+                        )*
+                        #(
+                            |u| ::core::result::Result::Ok(#request_ident::#base_variants(
+                                ::tarpc::fuzz::arbitrary::Arbitrary::arbitrary(u)?
+                            )),
+                        )*
+                    ];
+                    u.choose(variants)?(u)
+                }
+            }
+
+            #fuzz_fn
+        }
+    }
+
+    fn impl_tower_services(&self) -> TokenStream2 {
+        let &Self {
+            types_vis: vis,
+            service_ident,
+            prefix,
+            rpcs,
+            camel_case_idents,
+            method_idents,
+            method_cfgs,
+            args,
+            arg_pats,
+            return_types,
+            stream_items,
+            ..
+        } = self;
+
+        let services = rpcs.iter().enumerate().map(|(i, rpc)| {
+            let camel_case_ident = &camel_case_idents[i];
+            let method_ident = method_idents[i];
+            let cfgs = &method_cfgs[i];
+            let arg_pats = &arg_pats[i];
+            let arg_types = args[i].iter().map(|arg| &arg.ty);
+            let args_ident = format_ident!("{}{}Args", prefix, camel_case_ident);
+            let tower_ident = format_ident!("{}{}Service", prefix, camel_case_ident);
+            // Response streams are boxed, because their types can't be named.
+            let (response, into_response) = match stream_items[i] {
+                Some(item) => (
+                    quote!(::tarpc::futures::stream::BoxStream<'static, #item>),
+                    quote!(::tarpc::futures::StreamExt::boxed(response)),
+                ),
+                None => {
+                    let output = return_types[i];
+                    (quote!(#output), quote!(response))
+                }
+            };
+            let deprecated = rpc
+                .attrs
+                .iter()
+                .any(|attr| attr.path.is_ident("deprecated"))
+                .then(|| quote!(#[allow(deprecated)]));
+            let args_doc = format!("The args of [`{service_ident}::{method_ident}`].");
+            let tower_doc = format!(
+                "A [`tower::Service`](::tarpc::tower::Service) calling \
+                     [`{service_ident}::{method_ident}`] on the wrapped server."
+            );
+            quote! {
+                #cfgs
+                #[doc = #args_doc]
+                #[derive(Debug)]
+                #vis struct #args_ident {
+                    #( pub #arg_pats: #arg_types, )*
+                }
+
+                #cfgs
+                #[doc = #tower_doc]
+                #[derive(Clone, Debug)]
+                #vis struct #tower_ident<S>(pub S);
+
+                #cfgs
+                impl<S> ::tarpc::tower::Service<(::tarpc::context::Context, #args_ident)>
+                    for #tower_ident<S>
+                    where S: #service_ident + ::core::clone::Clone + 'static
+                {
+                    type Response = #response;
+                    type Error = ::core::convert::Infallible;
+                    type Future = ::tarpc::futures::future::LocalBoxFuture<
+                        'static,
+                        ::core::result::Result<Self::Response, Self::Error>,
+                    >;
+
+                    fn poll_ready(&mut self, _: &mut ::core::task::Context<'_>)
+                        -> ::core::task::Poll<::core::result::Result<(), Self::Error>>
+                    {
+                        ::core::task::Poll::Ready(::core::result::Result::Ok(()))
+                    }
+
+                    #deprecated
+                    fn call(
+                        &mut self,
+                        (ctx, #args_ident { #( #arg_pats ),* }): (
+                            ::tarpc::context::Context,
+                            #args_ident,
+                        ),
+                    ) -> Self::Future {
+                        let service = ::core::clone::Clone::clone(&self.0);
+                        ::std::boxed::Box::pin(async move {
+                            let response =
+                                #service_ident::#method_ident(service, ctx, #( #arg_pats ),*)
+                                    .await;
+                            ::core::result::Result::Ok(#into_response)
+                        })
+                    }
+                }
+            }
+        });
+
+        quote! {
+            #( #services )*
+        }
+    }
+
+    fn struct_instrumented_client(&self) -> TokenStream2 {
+        let &Self {
+            types_vis: vis,
+            service_ident,
+            prefix,
+            client_ident,
+            request_type,
+            response_type,
+            rpcs,
+            method_attrs,
+            method_idents,
+            args,
+            arg_pats,
+            response_types,
+            stream_items,
+            ..
+        } = self;
+
+        let instrumented_ident = format_ident!("{}InstrumentedClient", prefix);
+        let service_name = service_ident.unraw().to_string();
+        let (unary_fns, channel_fns): (Vec<_>, Vec<_>) = (0..method_idents.len())
+            .map(|i| {
+                let method_attrs = method_attrs[i];
+                let method_ident = method_idents[i];
+                let method_name = method_ident.unraw().to_string();
+                let args = args[i];
+                let arg_pats = &arg_pats[i];
+                let response_type = response_types[i];
+                let is_stream = stream_items[i].is_some();
+                let is_oneway = rpcs[i].oneway;
+                let result = if is_oneway {
+                    quote!(::core::result::Result<(), ::tarpc::client::RpcError>)
+                } else if is_stream {
+                    quote! {
+                        ::core::result::Result<
+                            impl ::tarpc::futures::Stream<
+                                Item = ::core::result::Result<#response_type, ::tarpc::client::RpcError>
+                            >,
+                            ::tarpc::client::RpcError
+                        >
+                    }
+                } else {
+                    self.client_result(i, response_type)
+                };
+                let method_fn = quote! {
+                    #[allow(unused)]
+                    #( #method_attrs )*
+                    #vis async fn #method_ident(&self, ctx: ::tarpc::context::Context, #( #args ),*)
+                        -> #result
+                    {
+                        ::tarpc::client::instrumented::call(
+                            #service_name,
+                            #method_name,
+                            self.0.#method_ident(ctx, #( #arg_pats ),*),
+                        )
+                        .await
+                    }
+                };
+                (is_stream || is_oneway, method_fn)
+            })
+            .partition(|(channel_only, _)| !channel_only);
+        let unary_fns = unary_fns.into_iter().map(|(_, method_fn)| method_fn);
+        let channel_fns = channel_fns
+            .into_iter()
+            .map(|(_, method_fn)| method_fn)
+            .collect::<Vec<_>>();
+        let channel_impl = (!channel_fns.is_empty()).then(|| {
+            quote! {
+                #[allow(deprecated)]
+                impl #instrumented_ident<::tarpc::client::Channel<#request_type, #response_type>> {
+                    #( #channel_fns )*
+                }
+            }
+        });
+        let doc = format!(
+            "A [`{client_ident}`] that records metrics of each call, and runs each call in a span \
+             annotated with the OpenTelemetry semantic conventions for RPC; see \
+             [`tarpc::client::instrumented`](::tarpc::client::instrumented) for details."
+        );
+
+        quote! {
+            #[doc = #doc]
+            #[derive(Clone, Debug)]
+            #vis struct #instrumented_ident<
+                Stub = ::tarpc::client::Channel<#request_type, #response_type>
+            >(#client_ident<Stub>);
+
+            impl<Stub> ::core::convert::From<#client_ident<Stub>> for #instrumented_ident<Stub> {
+                fn from(client: #client_ident<Stub>) -> Self {
+                    #instrumented_ident(client)
+                }
+            }
+
+            impl<Stub> #instrumented_ident<Stub> {
+                /// Returns the wrapped client.
+                #vis fn inner(&self) -> &#client_ident<Stub> {
+                    &self.0
+                }
+            }
+
+            #[allow(deprecated)]
+            impl<Stub> #instrumented_ident<Stub>
+                where Stub: ::tarpc::client::stub::Stub<
+                    Req = #request_type,
+                    Resp = #response_type>
+            {
+                #( #unary_fns )*
+            }
+
+            #channel_impl
+        }
+    }
+
+    fn wire_compat_tests(&self, dir: &LitStr) -> TokenStream2 {
+        let &Self {
+            service_ident,
+            prefix,
+            request_ident,
+            response_ident,
+            rpcs,
+            camel_case_idents,
+            method_idents,
+            method_cfgs,
+            arg_pats,
+            response_types,
+            derive_rkyv,
+            ..
+        } = self;
+
+        let module = format_ident!(
+            "{}_wire_compat",
+            camel_to_snake(&prefix.unraw().to_string())
+        );
+        let service_dir = service_ident.unraw().to_string();
+        let tests = rpcs.iter().enumerate().map(|(i, rpc)| {
+            let camel_case_ident = &camel_case_idents[i];
+            let method_ident = method_idents[i];
+            let cfgs = &method_cfgs[i];
+            let arg_pats = &arg_pats[i];
+            let wire_name = wire_name(rpc);
+            let request_name = format!("{wire_name}.request");
+            let response_name = format!("{wire_name}.response");
+            // Results don't implement `Default`, so their samples are the default success.
+            let response = match result_args(response_types[i]).as_deref() {
+                Some([_, _]) => quote! {
+                    ::core::result::Result::Ok(::core::default::Default::default())
+                },
+                _ => quote!(::core::default::Default::default()),
+            };
+            let check_rkyv = derive_rkyv.map(|_| {
+                quote! {
+                    ::tarpc::wire_compat::check_rkyv(&dir, #request_name, &request);
+                    ::tarpc::wire_compat::check_rkyv(&dir, #response_name, &response);
+                }
+            });
+            quote! {
+                #cfgs
+                #[test]
+                fn #method_ident() {
+                    let dir = ::std::path::Path::new(::core::env!("CARGO_MANIFEST_DIR"))
+                        .join(#dir)
+                        .join(#service_dir);
+                    let request = super::#request_ident::#camel_case_ident {
+                        #( #arg_pats: ::core::default::Default::default(), )*
+                    };
+                    let response = super::#response_ident::#camel_case_ident(#response);
+                    ::tarpc::wire_compat::check(&dir, #request_name, &request);
+                    ::tarpc::wire_compat::check(&dir, #response_name, &response);
+                    #check_rkyv
+                }
+            }
+        });
+
+        quote! {
+            /// Golden-sample tests of the wire representation of each method.
+            #[cfg(test)]
+            #[allow(deprecated)]
+            mod #module {
+                #( #tests )*
+            }
+        }
+    }
+
+    /// Returns the result type of the client's fn for the unary method `i`, which sends `response`
+    /// in the response enum.
+    fn client_result(&self, i: usize, response_type: &Type) -> TokenStream2 {
+        match (self.shared_error_oks[i], self.error_type) {
+            (Some(ok), Some(error)) => quote! {
+                ::core::result::Result<#ok, ::tarpc::client::CallError<#error>>
+            },
+            _ => quote! {
+                ::core::result::Result<#response_type, ::tarpc::client::RpcError>
+            },
+        }
+    }
+
+    fn trait_dyn_stub(&self) -> TokenStream2 {
+        let &Self {
+            types_vis: vis,
+            service_ident,
+            dyn_stub_ident,
+            client_ident,
+            request_type,
+            response_type,
+            generics,
+            type_params,
+            bounded_type_params,
+            where_predicates,
+            method_attrs,
+            method_idents,
+            args,
+            arg_pats,
+            response_types,
+            stream_items,
+            method_cfgs,
+            ..
+        } = self;
+
+        let results = (0..method_idents.len())
+            .map(|i| match stream_items[i] {
+                Some(item) => quote! {
+                    ::core::result::Result<
+                        ::tarpc::futures::stream::BoxStream<
+                            'static,
+                            ::core::result::Result<#item, ::tarpc::client::RpcError>
+                        >,
+                        ::tarpc::client::RpcError,
+                    >
+                },
+                None => self.client_result(i, response_types[i]),
+            })
+            .collect::<Vec<_>>();
+        let calls = method_idents
+            .iter()
+            .zip(arg_pats.iter())
+            .zip(stream_items.iter())
+            .map(|((method_ident, arg_pats), stream_item)| {
+                let call = quote! {
+                    #client_ident::#method_ident(self, ctx, #( #arg_pats ),*).await
+                };
+                if stream_item.is_some() {
+                    quote! {
+                        ::core::result::Result::Ok(::tarpc::futures::StreamExt::boxed(#call?))
+                    }
+                } else {
+                    call
+                }
+            });
+        let doc = format!(
+            "An object-safe stub for service `{service_ident}`, implemented by [`{client_ident}`] \
+             over a [`Channel`](::tarpc::client::Channel). Accept a `&dyn {dyn_stub_ident}` to \
+             call the service through real clients, mocks, and in-process implementations alike."
+        );
+        let dyn_stub = with_generic_args(dyn_stub_ident, type_params);
+
+        quote! {
+            #[doc = #doc]
+            #vis trait #dyn_stub_ident #generics where #( #where_predicates, )* {
+                #(
+                    #( #method_attrs )*
+                    fn #method_idents(&self, ctx: ::tarpc::context::Context, #( #args ),*)
+                        -> ::tarpc::futures::future::BoxFuture<'_, #results>;
+                )*
+            }
+
+            #[allow(deprecated)]
+            impl<#( #bounded_type_params ),*> #dyn_stub for #client_ident<
+                #( #type_params, )*
+                ::tarpc::client::Channel<#request_type, #response_type>
+            >
+                where #request_type: ::core::marker::Send + 'static,
+                    #response_type: ::core::marker::Send + 'static,
+                    #( #type_params: ::core::marker::Send + 'static, )*
+                    #( #where_predicates, )*
+            {
+                #(
+                    #method_cfgs
+                    fn #method_idents(&self, ctx: ::tarpc::context::Context, #( #args ),*)
+                        -> ::tarpc::futures::future::BoxFuture<'_, #results> {
+                        ::std::boxed::Box::pin(async move { #calls })
+                    }
+                )*
+            }
+        }
+    }
+
+    fn struct_client(&self) -> TokenStream2 {
+        let &Self {
+            types_vis: vis,
+            client_ident,
+            request_type,
+            response_type,
+            type_params,
+            ..
+        } = self;
+
+        quote! {
+            #[allow(unused)]
+            /// The client stub that makes RPC calls to the server. All request methods return
+            /// [Futures](::core::future::Future).
+            #vis struct #client_ident<
+                #( #type_params, )*
+                Stub = ::tarpc::client::Channel<#request_type, #response_type>
+            >(Stub, ::core::marker::PhantomData<fn() -> (#( #type_params, )*)>);
+
+            impl<#( #type_params, )* Stub> ::core::clone::Clone for #client_ident<#( #type_params, )* Stub>
+                where Stub: ::core::clone::Clone
+            {
+                fn clone(&self) -> Self {
+                    #client_ident(self.0.clone(), ::core::marker::PhantomData)
+                }
+            }
+
+            impl<#( #type_params, )* Stub> ::core::fmt::Debug for #client_ident<#( #type_params, )* Stub>
+                where Stub: ::core::fmt::Debug
+            {
+                fn fmt(&self, f: &mut ::core::fmt::Formatter<'_>) -> ::core::fmt::Result {
+                    f.debug_tuple(::core::stringify!(#client_ident)).field(&self.0).finish()
+                }
+            }
+        }
+    }
+
+    fn impl_client_new(&self) -> TokenStream2 {
+        let &Self {
+            client_ident,
+            types_vis: vis,
+            request_type,
+            response_type,
+            type_params,
+            bounded_type_params,
+            where_predicates,
+            ..
+        } = self;
+
+        quote! {
+            impl<#( #bounded_type_params ),*> #client_ident<#( #type_params ),*>
+                where #( #where_predicates, )*
+            {
+                /// Returns a new client stub that sends requests over the given transport.
+                #vis fn new<Transport>(config: ::tarpc::client::Config, transport: Transport)
+                    -> ::tarpc::client::NewClient<
+                        Self,
+                        ::tarpc::client::RequestDispatch<#request_type, #response_type, Transport>
+                    >
+                where
+                    Transport: ::tarpc::Transport<::tarpc::ClientMessage<#request_type>, ::tarpc::Response<#response_type>>
+                {
+                    let new_client = ::tarpc::client::new(config, transport);
+                    ::tarpc::client::NewClient {
+                        client: #client_ident(new_client.client, ::core::marker::PhantomData),
                         dispatch: new_client.dispatch,
                     }
                 }
             }
 
-            impl<Stub> ::core::convert::From<Stub> for #client_ident<Stub>
+            impl<#( #bounded_type_params, )* Stub> ::core::convert::From<Stub> for #client_ident<#( #type_params, )* Stub>
                 where Stub: ::tarpc::client::stub::Stub<
-                    Req = #request_ident,
-                    Resp = #response_ident>
+                    Req = #request_type,
+                    Resp = #response_type>,
+                    #( #where_predicates, )*
             {
                 /// Returns a new client stub that sends requests over the given transport.
                 fn from(stub: Stub) -> Self {
-                    #client_ident(stub)
+                    #client_ident(stub, ::core::marker::PhantomData)
                 }
 
             }
@@ -666,56 +3124,678 @@ impl<'a> ServiceGenerator<'a> {
         let &Self {
             client_ident,
             request_ident,
+            request_type,
             response_ident,
+            response_type,
             method_attrs,
-            vis,
+            types_vis: vis,
             method_idents,
             request_names,
             args,
-            return_types,
+            response_types,
             arg_pats,
             camel_case_idents,
+            type_params,
+            bounded_type_params,
+            where_predicates,
+            stream_items,
+            rpcs,
+            zero_copy,
+            bases,
             ..
         } = self;
 
-        quote! {
-            impl<Stub> #client_ident<Stub>
-                where Stub: ::tarpc::client::stub::Stub<
-                    Req = #request_ident,
-                    Resp = #response_ident>
-            {
-                #(
-                    #[allow(unused)]
-                    #( #method_attrs )*
-                    #vis fn #method_idents(&self, ctx: ::tarpc::context::Context, #( #args ),*)
-                        -> impl ::core::future::Future<Output = ::core::result::Result<#return_types, ::tarpc::client::RpcError>> + '_ {
-                        let request = #request_ident::#camel_case_idents { #( #arg_pats ),* };
-                        let resp = self.0.call(ctx, #request_names, request);
-                        async move {
-                            match resp.await? {
-                                #response_ident::#camel_case_idents(msg) => ::core::result::Result::Ok(msg),
-                                _ => ::core::unreachable!(),
+        let base_clients = bases.iter().map(|base| {
+            let BaseService {
+                service,
+                request,
+                response,
+                client,
+                client_fn,
+                ..
+            } = base;
+            let doc = format!(
+                "Returns a client of the extended service `{}`, sending its requests through this \
+                 client's stub.",
+                quote!(#service).to_string().replace(' ', "")
+            );
+            quote! {
+                #[doc = #doc]
+                #vis fn #client_fn(&self)
+                    -> #client<::tarpc::client::stub::embed::Embed<Stub, #request, #response>>
+                where
+                    Stub: ::core::clone::Clone
+                {
+                    #client::from(::tarpc::client::stub::embed::Embed::new(self.0.clone()))
+                }
+            }
+        });
+
+        let archive_request = zero_copy
+            .then(|| quote!(let request = ::tarpc::zero_copy::ArchivedBytes::new(&request);));
+        let seed_deadline = |rpc: &RpcMethod| {
+            let seed = rpc.deadline.map(|deadline| {
+                let (secs, nanos) = (deadline.as_secs(), deadline.subsec_nanos());
+                quote!(ctx.seed_deadline(::core::time::Duration::new(#secs, #nanos));)
+            });
+            let skip_trace = rpc.no_trace.then(|| quote!(ctx.skip_trace();));
+            (seed.is_some() || skip_trace.is_some()).then(|| {
+                quote! {
+                    let mut ctx = ctx;
+                    #seed
+                    #skip_trace
+                }
+            })
+        };
+
+        // Any stub can make unary calls, but only channels can receive response streams or send
+        // oneway requests.
+        let (unary_fns, channel_fns): (Vec<_>, Vec<_>) = (0..method_idents.len())
+            .map(|i| {
+                let method_attrs = method_attrs[i];
+                let method_ident = method_idents[i];
+                let args = args[i];
+                let arg_pats = &arg_pats[i];
+                let request_name = &request_names[i];
+                let camel_case_ident = &camel_case_idents[i];
+                let response_type = response_types[i];
+                let is_stream = stream_items[i].is_some();
+                let is_oneway = rpcs[i].oneway;
+                let seed_deadline = seed_deadline(&rpcs[i]);
+                let method_fn = if is_oneway {
+                    quote! {
+                        #[allow(unused)]
+                        #( #method_attrs )*
+                        #vis async fn #method_ident(&self, ctx: ::tarpc::context::Context, #( #args ),*)
+                            -> ::core::result::Result<(), ::tarpc::client::RpcError> {
+                            let request = #request_ident::#camel_case_ident { #( #arg_pats ),* };
+                            #archive_request
+                            #seed_deadline
+                            self.0.call_oneway(ctx, #request_name, request).await
+                        }
+                    }
+                } else if is_stream {
+                    quote! {
+                        #[allow(unused)]
+                        #( #method_attrs )*
+                        #vis async fn #method_ident(&self, ctx: ::tarpc::context::Context, #( #args ),*)
+                            -> ::core::result::Result<
+                                impl ::tarpc::futures::Stream<
+                                    Item = ::core::result::Result<#response_type, ::tarpc::client::RpcError>
+                                >,
+                                ::tarpc::client::RpcError
+                            > {
+                            let request = #request_ident::#camel_case_ident { #( #arg_pats ),* };
+                            #archive_request
+                            #seed_deadline
+                            let body = self.0.call_body(ctx, #request_name, request).await?;
+                            ::core::result::Result::Ok(::tarpc::futures::StreamExt::map(body, |resp| {
+                                match resp? {
+                                    #response_ident::#camel_case_ident(msg) => ::core::result::Result::Ok(msg),
+                                    _ => ::core::unreachable!(),
+                                }
+                            }))
+                        }
+                    }
+                } else {
+                    let result = self.client_result(i, response_type);
+                    // Errors of the declared error type are returned alongside RPC errors.
+                    let msg = if self.shared_error_oks[i].is_some() {
+                        quote!(msg.map_err(::tarpc::client::CallError::Service))
+                    } else {
+                        quote!(::core::result::Result::Ok(msg))
+                    };
+                    quote! {
+                        #[allow(unused)]
+                        #( #method_attrs )*
+                        #vis fn #method_ident(&self, ctx: ::tarpc::context::Context, #( #args ),*)
+                            -> impl ::core::future::Future<Output = #result> + '_ {
+                            let request = #request_ident::#camel_case_ident { #( #arg_pats ),* };
+                            #archive_request
+                            #seed_deadline
+                            let resp = self.0.call(ctx, #request_name, request);
+                            async move {
+                                match resp.await? {
+                                    #response_ident::#camel_case_ident(msg) => #msg,
+                                    _ => ::core::unreachable!(),
+                                }
                             }
                         }
                     }
-                )*
+                };
+                (is_stream || is_oneway, method_fn)
+            })
+            .partition(|(channel_only, _)| !channel_only);
+        let unary_fns = unary_fns.into_iter().map(|(_, method_fn)| method_fn);
+        let channel_fns = channel_fns
+            .into_iter()
+            .map(|(_, method_fn)| method_fn)
+            .collect::<Vec<_>>();
+        let channel_impl = (!channel_fns.is_empty()).then(|| {
+            quote! {
+                impl<#( #bounded_type_params ),*> #client_ident<
+                    #( #type_params, )*
+                    ::tarpc::client::Channel<#request_type, #response_type>
+                >
+                    where #( #where_predicates, )*
+                {
+                    #( #channel_fns )*
+                }
+            }
+        });
+
+        quote! {
+            impl<#( #bounded_type_params, )* Stub> #client_ident<#( #type_params, )* Stub>
+                where Stub: ::tarpc::client::stub::Stub<
+                    Req = #request_type,
+                    Resp = #response_type>,
+                    #( #where_predicates, )*
+            {
+                #( #unary_fns )*
+
+                #( #base_clients )*
             }
+
+            #channel_impl
         }
     }
 }
 
 impl<'a> ToTokens for ServiceGenerator<'a> {
     fn to_tokens(&self, output: &mut TokenStream2) {
-        output.extend(vec![
-            self.trait_service(),
-            self.struct_server(),
-            self.impl_serve_for_server(),
-            self.enum_request(),
-            self.enum_response(),
-            self.struct_client(),
-            self.impl_client_new(),
-            self.impl_client_rpc_methods(),
-        ])
+        if self.server {
+            output.extend(vec![
+                self.trait_service(),
+                self.struct_server(),
+                self.impl_serve_for_server(),
+            ]);
+        }
+        output.extend(vec![self.enum_request(), self.enum_response()]);
+        if self.cli {
+            output.extend(vec![self.impl_cli_request()]);
+        }
+        if self.schema {
+            output.extend(vec![self.impl_schema()]);
+        }
+        if self.fuzz {
+            output.extend(vec![self.impl_fuzz()]);
+        }
+        if self.server && self.tower {
+            output.extend(vec![self.impl_tower_services()]);
+        }
+        if let Some(dir) = self.wire_compat {
+            output.extend(vec![self.wire_compat_tests(dir)]);
+        }
+        if self.client {
+            output.extend(vec![
+                self.trait_client_stub(),
+                self.struct_client(),
+                self.impl_client_new(),
+                self.impl_client_rpc_methods(),
+                self.trait_dyn_stub(),
+            ]);
+            if self.instrumented_client {
+                output.extend(vec![self.struct_instrumented_client()]);
+            }
+        }
+    }
+}
+
+/// Returns the id of each method of a service: the one assigned with `#[tarpc::id = N]`, or else
+/// the one derived from its wire name. Ids can only be assigned if `method_ids` is set, and must
+/// be unique.
+fn method_ids_of(rpcs: &[RpcMethod], method_ids: bool) -> syn::Result<Vec<u32>> {
+    let mut errors = Ok(());
+    let mut ids = Vec::with_capacity(rpcs.len());
+    let mut methods_by_id = HashMap::new();
+    for rpc in rpcs {
+        let (id, span) = match rpc.id {
+            Some((_, span)) if !method_ids => {
+                extend_errors!(
+                    errors,
+                    syn::Error::new(span, "`tarpc::id` requires `method_ids = true`")
+                );
+                continue;
+            }
+            Some(id) => id,
+            None => (derived_id(&wire_name(rpc)), rpc.ident.span()),
+        };
+        if let Some(other) = methods_by_id.insert(id, &rpc.ident) {
+            extend_errors!(
+                errors,
+                syn::Error::new(
+                    span,
+                    format!(
+                        "id {id} of method `{}` is already used by method `{other}`",
+                        rpc.ident
+                    )
+                )
+            );
+        }
+        ids.push(id);
+    }
+    errors.map(|()| ids)
+}
+
+/// Returns the name of a method's variants in the serialized request and response enums.
+fn wire_name(rpc: &RpcMethod) -> String {
+    match &rpc.rename {
+        Some(rename) => rename.value(),
+        None => snake_to_camel(&rpc.ident.unraw().to_string()),
+    }
+}
+
+/// Returns the id derived from a method's wire name: the 32-bit FNV-1a hash of the name. Must
+/// match `tarpc::method_ids::derived_id`.
+fn derived_id(wire_name: &str) -> u32 {
+    wire_name.bytes().fold(0x811c_9dc5, |hash, byte| {
+        (hash ^ u32::from(byte)).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// Returns `T` if `ty` is written as `impl Stream<Item = T>`.
+///
+/// Methods returning such a type respond with a stream of responses, sent to the client one at a
+/// time.
+fn stream_item_type(ty: &Type) -> Option<&Type> {
+    let Type::ImplTrait(ty) = ty else {
+        return None;
+    };
+    ty.bounds.iter().find_map(|bound| {
+        let TypeParamBound::Trait(bound) = bound else {
+            return None;
+        };
+        let segment = bound.path.segments.last()?;
+        if segment.ident != "Stream" {
+            return None;
+        }
+        let PathArguments::AngleBracketed(args) = &segment.arguments else {
+            return None;
+        };
+        args.args.iter().find_map(|arg| match arg {
+            GenericArgument::Binding(binding) if binding.ident == "Item" => Some(&binding.ty),
+            _ => None,
+        })
+    })
+}
+
+/// Returns `T` if `rpc` sends application errors and `ty`, its return type, is `Result<T, E>`.
+///
+/// Errors of such methods are converted into `ApplicationError`s and sent to the client as
+/// application errors, and the generated client returns them as `RpcError::Application` instead
+/// of nesting results.
+fn application_result_ok_type<'a>(rpc: &RpcMethod, ty: &'a Type) -> Option<&'a Type> {
+    match result_args(ty).as_deref() {
+        Some(&[ok, _]) if rpc.application_error => Some(ok),
+        _ => None,
+    }
+}
+
+/// Returns whether `attrs` contain `#[tarpc::application_error]`.
+fn parse_application_error_attr(attrs: &[Attribute]) -> syn::Result<bool> {
+    let mut application_error = false;
+    for attr in attrs
+        .iter()
+        .filter(|attr| is_tarpc_attr(attr, "application_error"))
+    {
+        if !attr.tokens.is_empty() {
+            return Err(syn::Error::new(
+                attr.span(),
+                "`tarpc::application_error` takes no arguments",
+            ));
+        } else if application_error {
+            return Err(syn::Error::new(
+                attr.span(),
+                "`tarpc::application_error` appears more than once",
+            ));
+        }
+        application_error = true;
+    }
+    Ok(application_error)
+}
+
+/// Returns true iff `ty` is written as `Bytes` or `bytes::Bytes`.
+fn is_bytes(ty: &Type) -> bool {
+    let Type::Path(TypePath { qself: None, path }) = ty else {
+        return false;
+    };
+    let segments = path.segments.iter().collect::<Vec<_>>();
+    let names_bytes = match &*segments {
+        [ty] => path.leading_colon.is_none() && ty.ident == "Bytes",
+        [krate, ty] => krate.ident == "bytes" && ty.ident == "Bytes",
+        _ => false,
+    };
+    names_bytes && segments.iter().all(|segment| segment.arguments.is_empty())
+}
+
+/// Returns the type arguments of `ty` if it's written as `Result<...>`.
+fn result_args(ty: &Type) -> Option<Vec<&Type>> {
+    let Type::Path(TypePath { qself: None, path }) = ty else {
+        return None;
+    };
+    let segment = path.segments.last()?;
+    if segment.ident != "Result" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+    args.args
+        .iter()
+        .map(|arg| match arg {
+            GenericArgument::Type(ty) => Some(ty),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns the lines of the doc comment in `attrs`, trimmed.
+fn doc_lines(attrs: &[Attribute]) -> Vec<String> {
+    attrs
+        .iter()
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(Meta::NameValue(MetaNameValue {
+                path,
+                lit: Lit::Str(doc),
+                ..
+            })) if path.is_ident("doc") => Some(doc.value().trim().to_string()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Returns `ty` as written, with tokens spaced like rustfmt would, e.g. `Vec<String>`.
+fn type_name(ty: &Type) -> String {
+    let mut name = ty.to_token_stream().to_string();
+    for (spaced, unspaced) in [
+        (" :: ", "::"),
+        (":: ", "::"),
+        (" <", "<"),
+        ("< ", "<"),
+        (" >", ">"),
+        (" ,", ","),
+        (" ;", ";"),
+        ("& ", "&"),
+        ("( ", "("),
+        (" )", ")"),
+        ("[ ", "["),
+        (" ]", "]"),
+    ] {
+        name = name.replace(spaced, unspaced);
+    }
+    name
+}
+
+/// Converts a CamelCase ident to snake_case, e.g. `HealthCheck` to `health_check`.
+fn camel_to_snake(ident_str: &str) -> String {
+    let mut snake = String::new();
+    for (i, c) in ident_str.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        } else {
+            snake.push(c);
+        }
+    }
+    snake
+}
+
+/// Returns true iff `attr` is `#[tarpc::<name> ...]`.
+fn is_tarpc_attr(attr: &Attribute, name: &str) -> bool {
+    let mut segments = attr.path.segments.iter();
+    matches!(
+        (segments.next(), segments.next(), segments.next()),
+        (Some(tarpc), Some(attr), None) if tarpc.ident == "tarpc" && attr.ident == name
+    )
+}
+
+/// Parses the string value of the `#[tarpc::<name> = "..."]` attribute `attr` into `value`. If
+/// `is_version`, the string must be a version made of dot-separated numbers, like `1.2`.
+fn parse_str_attr(
+    attr: &Attribute,
+    value: &mut Option<LitStr>,
+    is_version: bool,
+) -> syn::Result<()> {
+    let name = &attr.path.segments.last().unwrap().ident;
+    let lit = match attr.parse_meta()? {
+        Meta::NameValue(MetaNameValue {
+            lit: Lit::Str(lit), ..
+        }) => lit,
+        _ if is_version => {
+            return Err(syn::Error::new(
+                attr.span(),
+                format!("`tarpc::{name}` expects a version: `#[tarpc::{name} = \"1.2\"]`"),
+            ))
+        }
+        _ => {
+            return Err(syn::Error::new(
+                attr.span(),
+                format!("`tarpc::{name}` expects a string: `#[tarpc::{name} = \"name\"]`"),
+            ))
+        }
+    };
+    if value.is_some() {
+        return Err(syn::Error::new(
+            attr.span(),
+            format!("`tarpc::{name}` appears more than once"),
+        ));
+    }
+    if is_version && !is_valid_version(&lit.value()) {
+        return Err(syn::Error::new(
+            lit.span(),
+            "expected a version made of dot-separated numbers, like \"1.2\"",
+        ));
+    }
+    *value = Some(lit);
+    Ok(())
+}
+
+/// Returns true iff `version` is made of dot-separated numbers, like `1.2`.
+fn is_valid_version(version: &str) -> bool {
+    version
+        .split('.')
+        .all(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Parses `#[tarpc::id = N]` into `id`.
+fn parse_id_attr(attr: &Attribute, id: &mut Option<(u32, Span)>) -> syn::Result<()> {
+    let lit = match attr.parse_meta()? {
+        Meta::NameValue(MetaNameValue {
+            lit: Lit::Int(lit), ..
+        }) => lit,
+        _ => {
+            return Err(syn::Error::new(
+                attr.span(),
+                "`tarpc::id` expects an integer: `#[tarpc::id = 1]`",
+            ))
+        }
+    };
+    if id.is_some() {
+        return Err(syn::Error::new(
+            attr.span(),
+            "`tarpc::id` appears more than once",
+        ));
+    }
+    *id = Some((lit.base10_parse()?, lit.span()));
+    Ok(())
+}
+
+/// Parses `#[tarpc::max_concurrent = N]` into `max_concurrent`.
+fn parse_max_concurrent_attr(
+    attr: &Attribute,
+    max_concurrent: &mut Option<usize>,
+) -> syn::Result<()> {
+    let lit = match attr.parse_meta()? {
+        Meta::NameValue(MetaNameValue {
+            lit: Lit::Int(lit), ..
+        }) => lit,
+        _ => {
+            return Err(syn::Error::new(
+                attr.span(),
+                "`tarpc::max_concurrent` expects an integer: `#[tarpc::max_concurrent = 4]`",
+            ))
+        }
+    };
+    if max_concurrent.is_some() {
+        return Err(syn::Error::new(
+            attr.span(),
+            "`tarpc::max_concurrent` appears more than once",
+        ));
+    }
+    match lit.base10_parse()? {
+        0 => Err(syn::Error::new(
+            lit.span(),
+            "at least one request must be allowed to execute",
+        )),
+        max => {
+            *max_concurrent = Some(max);
+            Ok(())
+        }
+    }
+}
+
+/// Parses `#[tarpc::deadline = "..."]` into `deadline`.
+fn parse_deadline_attr(attr: &Attribute, deadline: &mut Option<Duration>) -> syn::Result<()> {
+    let mut value = None;
+    parse_str_attr(attr, &mut value, false)?;
+    let lit = value.unwrap();
+    if deadline.is_some() {
+        return Err(syn::Error::new(
+            attr.span(),
+            "`tarpc::deadline` appears more than once",
+        ));
+    }
+    match humantime::parse_duration(&lit.value()) {
+        Ok(duration) if duration > Duration::ZERO => {
+            *deadline = Some(duration);
+            Ok(())
+        }
+        Ok(_) => Err(syn::Error::new(lit.span(), "the deadline must be positive")),
+        Err(e) => Err(syn::Error::new(
+            lit.span(),
+            format!("expected a duration, like \"30s\" or \"1m 30s\": {e}"),
+        )),
+    }
+}
+
+/// Removes the `#[serde(...)]` attributes from `attrs` and returns them.
+fn take_serde_attrs(attrs: &mut Vec<Attribute>) -> Vec<Attribute> {
+    let (serde_attrs, rest) = attrs
+        .drain(..)
+        .partition(|attr: &Attribute| attr.path.is_ident("serde"));
+    *attrs = rest;
+    serde_attrs
+}
+
+/// The renames in the `#[serde(...)]` attributes of a method or arg, which its schema follows.
+#[derive(Default)]
+struct SerdeRenames {
+    /// The name of the method's variants, or the arg's field.
+    rename: Option<String>,
+    /// How the fields of the method's request variant are renamed.
+    rename_all: Option<RenameRule>,
+}
+
+impl SerdeRenames {
+    /// Parses the serde attributes of a method, or of an arg if not `is_method`. Fails on any
+    /// attribute other than `rename`, `rename_all`, and `alias`, since they may change how args
+    /// serialize in ways the schema can't describe.
+    fn parse(attrs: &[Attribute], is_method: bool) -> syn::Result<Self> {
+        let mut renames = Self::default();
+        for attr in attrs {
+            let Meta::List(list) = attr.parse_meta()? else {
+                return Err(syn::Error::new(attr.span(), "expected `#[serde(...)]`"));
+            };
+            for nested in list.nested {
+                match nested {
+                    NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                        path,
+                        lit: Lit::Str(lit),
+                        ..
+                    })) if path.is_ident("rename") => renames.rename = Some(lit.value()),
+                    NestedMeta::Meta(Meta::NameValue(MetaNameValue {
+                        path,
+                        lit: Lit::Str(lit),
+                        ..
+                    })) if is_method && path.is_ident("rename_all") => {
+                        renames.rename_all = Some(RenameRule::parse(&lit)?)
+                    }
+                    NestedMeta::Meta(Meta::NameValue(MetaNameValue { path, .. }))
+                        if path.is_ident("alias") => {}
+                    nested => {
+                        return Err(syn::Error::new(
+                            nested.span(),
+                            "`schema = true` can't describe this serde attribute; only `rename`, \
+                             `rename_all` on methods, and `alias` are supported",
+                        ))
+                    }
+                }
+            }
+        }
+        Ok(renames)
+    }
+}
+
+/// A `rename_all` rule of serde, applied to the snake_case name of a field.
+enum RenameRule {
+    Lower,
+    Upper,
+    Pascal,
+    Camel,
+    ScreamingSnake,
+    Kebab,
+    ScreamingKebab,
+}
+
+impl RenameRule {
+    fn parse(lit: &LitStr) -> syn::Result<Self> {
+        Ok(match &*lit.value() {
+            "lowercase" | "snake_case" => RenameRule::Lower,
+            "UPPERCASE" => RenameRule::Upper,
+            "PascalCase" => RenameRule::Pascal,
+            "camelCase" => RenameRule::Camel,
+            "SCREAMING_SNAKE_CASE" => RenameRule::ScreamingSnake,
+            "kebab-case" => RenameRule::Kebab,
+            "SCREAMING-KEBAB-CASE" => RenameRule::ScreamingKebab,
+            _ => return Err(syn::Error::new(lit.span(), "unknown `rename_all` rule")),
+        })
+    }
+
+    fn apply(&self, field: &str) -> String {
+        match self {
+            RenameRule::Lower => field.to_string(),
+            RenameRule::Upper | RenameRule::ScreamingSnake => field.to_ascii_uppercase(),
+            RenameRule::Pascal => snake_to_camel(field),
+            RenameRule::Camel => {
+                let pascal = snake_to_camel(field);
+                let mut chars = pascal.chars();
+                chars.next().map_or_else(String::new, |first| {
+                    first.to_lowercase().chain(chars).collect()
+                })
+            }
+            RenameRule::Kebab => field.replace('_', "-"),
+            RenameRule::ScreamingKebab => field.replace('_', "-").to_ascii_uppercase(),
+        }
+    }
+}
+
+/// Returns true iff `tokens` contain `ident`, e.g. because a type uses a type parameter.
+fn mentions_ident(tokens: TokenStream2, ident: &Ident) -> bool {
+    tokens.into_iter().any(|token| match token {
+        proc_macro2::TokenTree::Ident(token) => token == *ident,
+        proc_macro2::TokenTree::Group(group) => mentions_ident(group.stream(), ident),
+        _ => false,
+    })
+}
+
+/// Returns `ident`, followed by `params` as generic arguments if there are any.
+fn with_generic_args(ident: &Ident, params: &[&Ident]) -> TokenStream2 {
+    if params.is_empty() {
+        quote!(#ident)
+    } else {
+        quote!(#ident<#( #params ),*>)
     }
 }
 
@@ -762,3 +3842,26 @@ fn snake_to_camel_underscore_consecutive() {
 fn snake_to_camel_capital_in_middle() {
     assert_eq!(snake_to_camel("aBc_dEf"), "AbcDef");
 }
+
+#[test]
+fn rename_rules_apply_to_fields() {
+    let apply = |rule| {
+        RenameRule::parse(&LitStr::new(rule, Span::call_site())).map(|rule| rule.apply("key_name"))
+    };
+    assert_eq!(apply("camelCase").unwrap(), "keyName");
+    assert_eq!(apply("PascalCase").unwrap(), "KeyName");
+    assert_eq!(apply("SCREAMING-KEBAB-CASE").unwrap(), "KEY-NAME");
+    assert!(apply("Title Case").is_err());
+}
+
+#[test]
+fn derived_id_is_fnv1a() {
+    assert_eq!(derived_id(""), 0x811c_9dc5);
+    assert_eq!(derived_id("a"), 0xe40c_292c);
+}
+
+#[test]
+fn camel_to_snake_basic() {
+    assert_eq!(camel_to_snake("HealthCheck"), "health_check");
+    assert_eq!(camel_to_snake("Health"), "health");
+}
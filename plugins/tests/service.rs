@@ -18,9 +18,7 @@ fn att_service_trait() {
             s
         }
 
-        async fn baz(self, _: context::Context) {
-            ()
-        }
+        async fn baz(self, _: context::Context) {}
     }
 }
 
@@ -50,9 +48,7 @@ fn raw_idents() {
             r#impl
         }
 
-        async fn r#async(self, _: context::Context) {
-            ()
-        }
+        async fn r#async(self, _: context::Context) {}
     }
 }
 
@@ -78,3 +74,465 @@ fn syntax() {
         async fn one_arg_implicit_return_error(one: String);
     }
 }
+
+#[test]
+fn generics() {
+    #[tarpc::service]
+    trait Generic<T: Clone, U, Unused> {
+        async fn echo(t: T) -> T;
+        async fn produce() -> U;
+        async fn consume(u: U, unused: Vec<Unused>);
+    }
+
+    impl<T: Clone, U: Default, Unused> Generic<T, U, Unused> for () {
+        async fn echo(self, _: context::Context, t: T) -> T {
+            t
+        }
+
+        async fn produce(self, _: context::Context) -> U {
+            U::default()
+        }
+
+        async fn consume(self, _: context::Context, _: U, _: Vec<Unused>) {}
+    }
+
+    #[tarpc::service]
+    trait OnlyResponse<T> {
+        async fn produce() -> T;
+    }
+
+    let _: OnlyResponseRequest = OnlyResponseRequest::Produce {};
+    let _: OnlyResponseResponse<i32> = OnlyResponseResponse::Produce(0);
+}
+
+#[test]
+fn custom_derives() {
+    #[tarpc::service(derive_serde = false, derive(Clone, PartialEq, Eq, Hash))]
+    trait Derives {
+        async fn add(x: i32, y: i32) -> i32;
+    }
+
+    let request = DerivesRequest::Add { x: 1, y: 2 };
+    assert_eq!(request.clone(), request);
+    assert_ne!(request, DerivesRequest::Add { x: 2, y: 1 });
+
+    let mut responses = std::collections::HashSet::new();
+    responses.insert(DerivesResponse::Add(3));
+    assert!(responses.contains(&DerivesResponse::Add(3).clone()));
+}
+
+#[test]
+fn serde_attrs() {
+    #[tarpc::service]
+    trait Evolving {
+        #[serde(alias = "Old")]
+        async fn new_name(#[serde(default)] added: Option<u32>, #[serde(rename = "b")] a: u32);
+    }
+
+    let _ = EvolvingRequest::NewName { added: None, a: 0 };
+}
+
+#[test]
+fn generated_halves() {
+    mod client {
+        #[tarpc::service(client_only)]
+        pub trait Sdk {
+            async fn get() -> u32;
+        }
+
+        // The service trait isn't generated, so its name is free.
+        #[allow(dead_code)]
+        pub struct Sdk;
+        #[allow(dead_code)]
+        pub struct ServeSdk;
+    }
+
+    mod server {
+        #[tarpc::service(server_only)]
+        pub trait Sdk {
+            async fn get() -> u32;
+        }
+
+        // The client stub isn't generated, so its names are free.
+        #[allow(dead_code)]
+        pub struct SdkClient;
+        #[allow(dead_code)]
+        pub struct SdkStub;
+    }
+
+    let _: fn(
+        tarpc::client::Channel<client::SdkRequest, client::SdkResponse>,
+    ) -> client::SdkClient = client::SdkClient::from;
+    let _ = server::SdkRequest::Get {};
+}
+
+#[test]
+fn schema() {
+    use tarpc::schema::MethodKind;
+
+    /// Stores values.
+    #[tarpc::service(schema = true)]
+    trait Store {
+        /// Puts a value.
+        ///
+        /// Replaces any previous value.
+        async fn put(key: String, value: Option<Vec<u8>>);
+        #[tarpc::rename = "Fetch"]
+        #[tarpc::since = "1.1"]
+        #[tarpc::application_error]
+        async fn get(key: String) -> Result<(u32, [u8; 4]), tarpc::ApplicationError>;
+        #[tarpc::oneway]
+        async fn r#type(r#type: std::collections::HashMap<String, u32>);
+    }
+
+    let schema = StoreRequest::schema();
+    assert_eq!(schema.name, "Store");
+    assert_eq!(schema.doc, "Stores values.");
+
+    let put = &schema.methods[0];
+    assert_eq!(put.name, "put");
+    assert_eq!(put.doc, "Puts a value.\n\nReplaces any previous value.");
+    assert_eq!((put.args[0].name, put.args[0].ty), ("key", "String"));
+    assert_eq!(put.args[1].ty, "Option<Vec<u8>>");
+    assert_eq!(put.output, "()");
+    assert_eq!(put.kind, MethodKind::Unary);
+
+    let get = schema.method("Fetch").unwrap();
+    assert_eq!(get.name, "get");
+    assert_eq!(get.output, "(u32, [u8; 4])");
+    assert!(get.application_errors);
+    assert_eq!(get.since, Some("1.1"));
+
+    let r#type = &schema.methods[2];
+    assert_eq!((r#type.name, r#type.wire_name), ("type", "Type"));
+    assert_eq!(r#type.args[0].ty, "std::collections::HashMap<String, u32>");
+    assert_eq!(r#type.kind, MethodKind::Oneway);
+
+    assert_eq!(
+        schema.typescript(),
+        "\
+/** Stores values. */
+export type StoreRequest =
+  /**
+   * Puts a value.
+   *
+   * Replaces any previous value.
+   */
+  | { Put: { key: string; value: number[] | null } }
+  | { Fetch: { key: string } }
+  | { Type: { type: Record<string, number> } };
+
+/** Stores values. */
+export type StoreResponse =
+  /**
+   * Puts a value.
+   *
+   * Replaces any previous value.
+   */
+  | { Put: null }
+  | { Fetch: [number, number[]] };
+"
+    );
+    assert!(schema
+        .json_schema()
+        .contains(r#""StoreResponse":{"oneOf":[{"type":"object","description":"Puts a value."#));
+}
+
+#[test]
+fn application_errors_are_declared_explicitly() {
+    // Only the attribute, not the name of the error type, makes errors application errors.
+    type ApplicationError = String;
+
+    struct NotFound;
+
+    impl From<NotFound> for tarpc::ApplicationError {
+        fn from(NotFound: NotFound) -> Self {
+            tarpc::ApplicationError::new(404, "not found")
+        }
+    }
+
+    #[tarpc::service(schema = true)]
+    trait Store {
+        async fn named(key: String) -> Result<(), ApplicationError>;
+        #[tarpc::application_error]
+        async fn marked(key: String) -> Result<u32, NotFound>;
+    }
+
+    let schema = StoreRequest::schema();
+    assert!(!schema.methods[0].application_errors);
+    assert_eq!(schema.methods[0].output, "Result<(), ApplicationError>");
+    assert!(schema.methods[1].application_errors);
+    assert_eq!(schema.methods[1].output, "u32");
+}
+
+#[test]
+fn schema_follows_serde_renames() {
+    #[tarpc::service(schema = true)]
+    trait Store {
+        #[serde(rename = "Fetch", rename_all = "camelCase")]
+        async fn get(key_name: String, #[serde(rename = "v", alias = "value")] value: u64) -> u64;
+    }
+
+    let schema = StoreRequest::schema();
+    let get = schema.method("Fetch").unwrap();
+    assert_eq!(get.name, "get");
+    assert_eq!((get.args[0].name, get.args[1].name), ("keyName", "v"));
+    assert!(schema
+        .typescript()
+        .contains("| { Fetch: { keyName: string; v: number /* u64 */ } }"));
+}
+
+#[test]
+fn deprecated_methods() {
+    // Generated code doesn't warn about the deprecated methods it uses.
+    #[deny(deprecated)]
+    mod service {
+        #[tarpc::service]
+        pub trait World {
+            #[deprecated(note = "use `greet`")]
+            async fn hello(name: String) -> String;
+            async fn greet(name: String) -> String;
+        }
+    }
+
+    let _ = service::WorldRequest::Hello {
+        name: "Ferris".into(),
+    };
+}
+
+#[test]
+fn types_visibility() {
+    mod service {
+        #[tarpc::service(vis = "pub(crate)")]
+        pub trait World {
+            async fn hello(name: String) -> String;
+        }
+    }
+
+    let _ = service::WorldRequest::Hello {
+        name: "Ferris".into(),
+    };
+    let _: fn(
+        tarpc::client::Channel<service::WorldRequest, service::WorldResponse>,
+    ) -> service::WorldClient = service::WorldClient::from;
+}
+
+#[test]
+fn cfg_gated_methods() {
+    use tarpc::cli::CliRequest;
+
+    #[tarpc::service(schema = true, cli = true)]
+    trait Device {
+        async fn status() -> String;
+        /// Only compiled with a type that doesn't exist in this build.
+        #[cfg(any())]
+        async fn flash(image: FirmwareImage) -> bool;
+        #[cfg(test)]
+        async fn reboot();
+    }
+
+    #[derive(Clone)]
+    struct DeviceServer;
+
+    impl Device for DeviceServer {
+        async fn status(self, _: context::Context) -> String {
+            "ok".into()
+        }
+
+        async fn reboot(self, _: context::Context) {}
+    }
+
+    let _ = DeviceServer.serve();
+    assert_eq!(
+        DeviceRequest::METHOD_VERSIONS
+            .iter()
+            .map(|version| version.method)
+            .collect::<Vec<_>>(),
+        ["Device.status", "Device.reboot"]
+    );
+    assert_eq!(DeviceRequest::schema().methods.len(), 2);
+    assert!(DeviceRequest::schema().method("Flash").is_none());
+    let command = DeviceRequest::command("device");
+    assert!(command.find_subcommand("flash").is_none());
+    assert!(command.find_subcommand("reboot").is_some());
+    assert_eq!(DeviceRequest::Reboot {}.since(), None);
+}
+
+#[test]
+fn custom_type_names() {
+    #[tarpc::service(prefix = "Greeting", client_name = "Greeter")]
+    trait World {
+        async fn hello(name: String) -> String;
+    }
+
+    #[tarpc::service(request_name = "Ping", response_name = "Pong")]
+    trait Health {
+        async fn check() -> bool;
+    }
+
+    #[derive(Clone)]
+    struct Server;
+
+    impl World for Server {
+        async fn hello(self, _: context::Context, name: String) -> String {
+            format!("Hello, {name}.")
+        }
+    }
+
+    let _: ServeGreeting<Server> = Server.serve();
+    let _ = GreetingRequest::Hello {
+        name: "Ferris".into(),
+    };
+    let _: fn(tarpc::client::Channel<GreetingRequest, GreetingResponse>) -> Greeter = Greeter::from;
+    let _: Option<&dyn DynGreetingStub> = None;
+
+    let _ = Ping::Check {};
+    let _ = Pong::Check(true);
+    let _: fn(tarpc::client::Channel<Ping, Pong>) -> HealthClient = HealthClient::from;
+}
+
+#[test]
+fn fuzz_harness() {
+    use futures::stream::{self, Stream};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tarpc::fuzz::arbitrary::{Arbitrary, Unstructured};
+
+    static SERVED: AtomicUsize = AtomicUsize::new(0);
+
+    #[tarpc::service(fuzz = true)]
+    trait Storage {
+        async fn put(key: String, value: Vec<u8>) -> usize;
+        async fn scan(prefix: String) -> impl Stream<Item = String>;
+    }
+
+    #[derive(Clone)]
+    struct StorageServer;
+
+    impl Storage for StorageServer {
+        async fn put(self, _: context::Context, _: String, value: Vec<u8>) -> usize {
+            SERVED.fetch_add(1, Ordering::SeqCst);
+            value.len()
+        }
+
+        async fn scan(
+            self,
+            _: context::Context,
+            prefix: String,
+        ) -> impl Stream<Item = String> + Send + 'static {
+            SERVED.fetch_add(1, Ordering::SeqCst);
+            stream::iter(vec![prefix])
+        }
+    }
+
+    let request = StorageRequest::arbitrary(&mut Unstructured::new(&[0; 16])).unwrap();
+    assert!(matches!(request, StorageRequest::Put { .. }));
+
+    // Odd first bytes generate requests, so every input is served.
+    for data in [&[1][..], &[3, 1, 2, 3], &[1, 255, 0, 0, 9]] {
+        StorageRequest::fuzz(StorageServer, data);
+    }
+    assert_eq!(SERVED.load(Ordering::SeqCst), 3);
+
+    // Even first bytes decode requests, so garbage isn't served.
+    StorageRequest::fuzz(StorageServer, &[0, 255, 255, 255, 255]);
+    assert_eq!(SERVED.load(Ordering::SeqCst), 3);
+}
+
+#[test]
+fn max_concurrent_methods() {
+    use futures::stream::{self, Stream};
+
+    #[tarpc::service]
+    trait Export {
+        #[tarpc::max_concurrent = 1]
+        async fn export(table: String) -> impl Stream<Item = Vec<u8>>;
+        #[tarpc::max_concurrent = 8]
+        async fn count(table: String) -> usize;
+    }
+
+    #[derive(Clone)]
+    struct ExportServer;
+
+    impl Export for ExportServer {
+        async fn export(
+            self,
+            _: context::Context,
+            table: String,
+        ) -> impl Stream<Item = Vec<u8>> + Send + 'static {
+            stream::iter(vec![table.into_bytes()])
+        }
+
+        async fn count(self, _: context::Context, _: String) -> usize {
+            0
+        }
+    }
+
+    fn assert_send<T: Send>(_: T) {}
+    assert_send(tarpc::server::Serve::serve(
+        ExportServer.serve(),
+        context::current(),
+        ExportRequest::Count { table: "t".into() },
+    ));
+}
+
+#[test]
+fn tower_services() {
+    use futures::{
+        executor::block_on,
+        stream::{self, Stream, StreamExt},
+    };
+    use tarpc::tower::Service;
+
+    #[tarpc::service(tower = true)]
+    trait Storage {
+        async fn put(key: String, value: Vec<u8>) -> usize;
+        async fn scan(prefix: String) -> impl Stream<Item = String>;
+        #[cfg(any())]
+        async fn removed();
+    }
+
+    #[derive(Clone)]
+    struct StorageServer;
+
+    impl Storage for StorageServer {
+        async fn put(self, _: context::Context, _: String, value: Vec<u8>) -> usize {
+            value.len()
+        }
+
+        async fn scan(
+            self,
+            _: context::Context,
+            prefix: String,
+        ) -> impl Stream<Item = String> + Send + 'static {
+            stream::iter(vec![format!("{prefix}/a"), format!("{prefix}/b")])
+        }
+    }
+
+    // Generic over the service, like tower middleware.
+    fn call<S, Req>(service: &mut S, req: Req) -> S::Response
+    where
+        S: Service<Req, Error = std::convert::Infallible>,
+    {
+        block_on(service.call(req)).unwrap()
+    }
+
+    let mut put = StoragePutService(StorageServer);
+    let args = StoragePutArgs {
+        key: "k".into(),
+        value: vec![1, 2, 3],
+    };
+    assert_eq!(call(&mut put, (context::current(), args)), 3);
+
+    let mut scan = StorageScanService(StorageServer);
+    let args = StorageScanArgs { prefix: "p".into() };
+    let keys = block_on(call(&mut scan, (context::current(), args)).collect::<Vec<_>>());
+    assert_eq!(keys, ["p/a", "p/b"]);
+}
+
+// Wire compatibility tests are generated in a module, so they can't be generated in a test fn.
+#[tarpc::service(wire_compat = "tests/wire")]
+trait Inventory {
+    async fn put(item: String, count: u32) -> Result<u32, String>;
+    #[tarpc::rename = "Count"]
+    async fn count_of(item: String) -> Option<u32>;
+}
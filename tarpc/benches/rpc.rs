@@ -0,0 +1,210 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Benchmarks round trips over the channel and TCP transports, the overhead of dispatching
+//! requests with varying numbers in flight, and how long other tasks wait for a turn while
+//! channels are flooded with requests, with and without `frames_per_yield`.
+//!
+//! Run with `cargo bench --all-features --bench rpc [-- <filter>]`. The benchmarks are measured by
+//! criterion, which reports the time per iteration and the requests per second of each, and how
+//! they changed since the last run on the same machine.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use futures::{future::join_all, prelude::*};
+use std::{future::Future, time::Duration};
+use tarpc::{
+    client, context,
+    serde_transport::tcp,
+    server::{self, incoming::Incoming, BaseChannel, Channel},
+    tokio_serde::formats::{Bincode, Json},
+    transport::channel,
+};
+use tokio::runtime::Runtime;
+
+const WARM_UP: Duration = Duration::from_millis(500);
+const MEASUREMENT: Duration = Duration::from_secs(2);
+/// The number of clients flooding servers while the latency of other tasks is measured.
+const FLOODING_CLIENTS: usize = 4;
+/// The sizes of the payloads echoed by the latency benchmarks.
+const PAYLOAD_SIZES: [usize; 3] = [0, 1024, 64 * 1024];
+
+#[tarpc::service]
+trait Bench {
+    /// Returns its payload.
+    async fn echo(payload: Vec<u8>) -> Vec<u8>;
+}
+
+#[derive(Clone)]
+struct BenchServer;
+
+impl Bench for BenchServer {
+    async fn echo(self, _: context::Context, payload: Vec<u8>) -> Vec<u8> {
+        payload
+    }
+}
+
+async fn spawn(fut: impl Future<Output = ()> + Send + 'static) {
+    tokio::spawn(fut);
+}
+
+fn runtime() -> Runtime {
+    tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()
+        .expect("failed to build the runtime")
+}
+
+/// Returns a client connected to a server over the channel transport, both of which yield after
+/// `frames_per_yield` messages.
+fn channel_client(runtime: &Runtime, frames_per_yield: Option<usize>) -> BenchClient {
+    let _entered = runtime.enter();
+    let (client_transport, server_transport) = channel::unbounded();
+    let config = server::Config {
+        frames_per_yield,
+        ..server::Config::default()
+    };
+    tokio::spawn(
+        BaseChannel::new(config, server_transport)
+            .execute(BenchServer.serve())
+            .for_each(spawn),
+    );
+    let mut config = client::Config::default();
+    config.frames_per_yield = frames_per_yield;
+    BenchClient::new(config, client_transport).spawn()
+}
+
+/// Returns a client connected to a server over TCP loopback, with json or bincode.
+fn tcp_client(runtime: &Runtime, bincode: bool) -> BenchClient {
+    macro_rules! connect {
+        ($codec:expr) => {{
+            let listener = tcp::listen("localhost:0", $codec)
+                .await
+                .expect("failed to listen");
+            let addr = listener.local_addr();
+            tokio::spawn(
+                listener
+                    .filter_map(|transport| async { transport.ok() })
+                    .map(BaseChannel::with_defaults)
+                    .execute(BenchServer.serve())
+                    .map(|channel| channel.for_each(spawn))
+                    .for_each(spawn),
+            );
+            let transport = tcp::connect(addr, $codec).await.expect("failed to connect");
+            BenchClient::new(client::Config::default(), transport).spawn()
+        }};
+    }
+
+    runtime.block_on(async {
+        if bincode {
+            connect!(Bincode::default)
+        } else {
+            connect!(Json::default)
+        }
+    })
+}
+
+/// Sends `in_flight` concurrent echo requests of `payload`.
+async fn echo_concurrently(client: &BenchClient, payload: &[u8], in_flight: usize) {
+    let calls = (0..in_flight).map(|_| client.echo(context::current(), payload.to_vec()));
+    for response in join_all(calls).await {
+        response.expect("echo failed");
+    }
+}
+
+/// Benchmarks the latency of echoing payloads of each size, and the throughput of echoing 64
+/// payloads of 1 KiB at once, in the group `name`.
+fn bench_round_trips(c: &mut Criterion, name: &str, runtime: &Runtime, client: &BenchClient) {
+    let mut group = c.benchmark_group(name);
+    group.throughput(Throughput::Elements(1));
+    for size in PAYLOAD_SIZES {
+        let payload = vec![0xa5; size];
+        group.bench_with_input(
+            BenchmarkId::new("latency", format!("{size}B")),
+            &payload,
+            |b, payload| {
+                b.to_async(runtime)
+                    .iter(|| echo_concurrently(client, payload, 1))
+            },
+        );
+    }
+    group.throughput(Throughput::Elements(64));
+    group.bench_function("throughput/64x1KiB", |b| {
+        b.to_async(runtime)
+            .iter(|| echo_concurrently(client, &[0xa5; 1024], 64))
+    });
+    group.finish();
+}
+
+fn channel_round_trips(c: &mut Criterion) {
+    let runtime = runtime();
+    let client = channel_client(&runtime, None);
+    bench_round_trips(c, "channel", &runtime, &client);
+}
+
+fn tcp_round_trips(c: &mut Criterion) {
+    let runtime = runtime();
+    for (format, bincode) in [("json", false), ("bincode", true)] {
+        let client = tcp_client(&runtime, bincode);
+        bench_round_trips(c, &format!("tcp/{format}"), &runtime, &client);
+    }
+}
+
+fn dispatch(c: &mut Criterion) {
+    let runtime = runtime();
+    let client = channel_client(&runtime, None);
+    let mut group = c.benchmark_group("dispatch");
+    for in_flight in [1, 16, 256, 1024] {
+        group.throughput(Throughput::Elements(in_flight as u64));
+        group.bench_with_input(
+            BenchmarkId::new("in_flight", in_flight),
+            &in_flight,
+            |b, &in_flight| {
+                b.to_async(&runtime)
+                    .iter(|| echo_concurrently(&client, &[], in_flight))
+            },
+        );
+    }
+    group.finish();
+}
+
+/// Each iteration is a round trip through the scheduler of a task sharing the workers with
+/// `FLOODING_CLIENTS` clients that keep 1024 requests in flight each.
+fn yield_when_flooded(c: &mut Criterion) {
+    let runtime = runtime();
+    let mut group = c.benchmark_group("yield/flooded");
+    for frames_per_yield in [None, Some(256), Some(32)] {
+        let name = match frames_per_yield {
+            Some(frames) => frames.to_string(),
+            None => "never".to_string(),
+        };
+        let floods = (0..FLOODING_CLIENTS)
+            .map(|_| {
+                let client = channel_client(&runtime, frames_per_yield);
+                runtime.spawn(async move {
+                    loop {
+                        echo_concurrently(&client, &[], 1024).await;
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+        group.bench_function(name, |b| {
+            b.to_async(&runtime).iter(|| async {
+                tokio::spawn(async {}).await.expect("task panicked");
+            })
+        });
+        for flood in floods {
+            flood.abort();
+        }
+    }
+    group.finish();
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().warm_up_time(WARM_UP).measurement_time(MEASUREMENT);
+    targets = channel_round_trips, tcp_round_trips, dispatch, yield_when_flooded
+}
+criterion_main!(benches);
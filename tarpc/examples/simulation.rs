@@ -0,0 +1,174 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Runs a client and server inside a deterministic simulation: a single-threaded runtime whose
+//! clock is paused, connected by an in-process network that can be partitioned. Timers fire as
+//! soon as nothing else can run, so every run takes the same course, on any machine, in no time.
+//!
+//! tarpc measures deadlines against tokio's clock, so they pass with simulated time, and draws its
+//! random numbers, like the trace IDs of requests, from a generator [seeded](tarpc::rng::seed) by
+//! the simulation. The same approach works with network simulators like turmoil, which run each
+//! host on tokio with a paused clock: connect and listen on the simulator's sockets with
+//! [`tcp::connect_with`](tarpc::serde_transport::tcp::connect_with) and
+//! [`tcp::listen_with`](tarpc::serde_transport::tcp::listen_with), or wrap its streams in a
+//! transport with [`serde_transport::new`](tarpc::serde_transport::new), and, if the simulator
+//! spawns tasks its own way, spawn dispatches with
+//! [`NewClient::spawn_with`](tarpc::client::NewClient::spawn_with) and requests with
+//! [`ExecutionStrategy::with_spawner`](tarpc::server::execution::ExecutionStrategy::with_spawner).
+
+use futures::prelude::*;
+use std::{
+    pin::Pin,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    task::{Context, Poll},
+    time::Duration,
+};
+use tarpc::{
+    client::{self, RpcError},
+    context,
+    server::{BaseChannel, Channel},
+    transport::channel::{self, ChannelError, UnboundedChannel},
+};
+use tokio::time::{self, Instant};
+
+#[tarpc::service]
+trait Worker {
+    /// Works for `millis` milliseconds.
+    async fn work(millis: u64) -> u64;
+}
+
+#[derive(Clone)]
+struct WorkerServer;
+
+impl Worker for WorkerServer {
+    async fn work(self, _: context::Context, millis: u64) -> u64 {
+        time::sleep(Duration::from_millis(millis)).await;
+        millis
+    }
+}
+
+/// An in-process network whose links drop every message sent while it's partitioned.
+#[derive(Clone, Default)]
+struct Network {
+    partitioned: Arc<AtomicBool>,
+}
+
+impl Network {
+    fn connect<Item, SinkItem>(&self) -> (Link<Item, SinkItem>, Link<SinkItem, Item>) {
+        let (a, b) = channel::unbounded();
+        (
+            Link {
+                inner: a,
+                partitioned: self.partitioned.clone(),
+            },
+            Link {
+                inner: b,
+                partitioned: self.partitioned.clone(),
+            },
+        )
+    }
+
+    fn partition(&self, partitioned: bool) {
+        self.partitioned.store(partitioned, Ordering::SeqCst);
+    }
+}
+
+/// One end of a connection across the [`Network`].
+struct Link<Item, SinkItem> {
+    inner: UnboundedChannel<Item, SinkItem>,
+    partitioned: Arc<AtomicBool>,
+}
+
+impl<Item, SinkItem> Stream for Link<Item, SinkItem> {
+    type Item = Result<Item, ChannelError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.inner.poll_next_unpin(cx)
+    }
+}
+
+impl<Item, SinkItem> Sink<SinkItem> for Link<Item, SinkItem> {
+    type Error = ChannelError;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready_unpin(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: SinkItem) -> Result<(), Self::Error> {
+        if self.partitioned.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        self.inner.start_send_unpin(item)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_flush_unpin(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_close_unpin(cx)
+    }
+}
+
+/// Starts a server on the network, returning a client connected to it and the server's task.
+fn start_server(network: &Network) -> (WorkerClient, tokio::task::JoinHandle<()>) {
+    let (client_link, server_link) = network.connect();
+    let server = tokio::spawn(
+        BaseChannel::with_defaults(server_link)
+            .execute(WorkerServer.serve())
+            .for_each(|response| async move {
+                tokio::spawn(response);
+            }),
+    );
+    let client = WorkerClient::new(client::Config::default(), client_link).spawn();
+    (client, server)
+}
+
+#[tokio::main(flavor = "current_thread", start_paused = true)]
+async fn main() -> anyhow::Result<()> {
+    tarpc::rng::seed(0);
+    let network = Network::default();
+    let (client, server) = start_server(&network);
+    let start = Instant::now();
+
+    // A healthy network: the request takes exactly as long as the work.
+    assert_eq!(client.work(context::current(), 250).await?, 250);
+    assert_eq!(start.elapsed(), Duration::from_millis(250));
+
+    // A partition: the request is lost, and the call fails once its deadline passes.
+    network.partition(true);
+    let ctx = context::current().with_timeout(Duration::from_secs(1));
+    let start = Instant::now();
+    assert!(matches!(
+        client.work(ctx, 0).await,
+        Err(RpcError::DeadlineExceeded)
+    ));
+    assert_eq!(start.elapsed(), Duration::from_secs(1));
+    network.partition(false);
+
+    // Work that outlasts the deadline: the client gives up when the deadline passes, rather than
+    // when the work would have finished.
+    let ctx = context::current().with_timeout(Duration::from_millis(100));
+    let start = Instant::now();
+    assert!(matches!(
+        client.work(ctx, 200).await,
+        Err(RpcError::DeadlineExceeded)
+    ));
+    assert_eq!(start.elapsed(), Duration::from_millis(100));
+
+    // A restart: the old connection is gone, and a new one reaches the new server.
+    server.abort();
+    let _ = server.await;
+    assert!(client.work(context::current(), 0).await.is_err());
+    let (client, _server) = start_server(&network);
+    assert_eq!(client.work(context::current(), 10).await?, 10);
+
+    println!("The simulation took the same course as always.");
+    Ok(())
+}
@@ -108,6 +108,7 @@ where
     Ok((listener, addr))
 }
 
+#[allow(clippy::type_complexity)]
 fn make_stub<Req, Resp, const N: usize>(
     backends: [impl Transport<ClientMessage<Arc<Req>>, Response<Resp>> + Send + Sync + 'static; N],
 ) -> retry::Retry<
@@ -181,7 +182,7 @@ async fn main() -> anyhow::Result<()> {
 
     let ctx = context::current();
     for _ in 1..=5 {
-        tracing::info!("{:?}", double_client.double(ctx, 1).await?);
+        tracing::info!("{:?}", double_client.double(ctx.clone(), 1).await?);
     }
 
     opentelemetry::global::shutdown_tracer_provider();
@@ -0,0 +1,21 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Decodes arbitrary bytes as the requests a server reads from a connection.
+//!
+//! Run with `cargo +nightly fuzz run decode_requests` from the `tarpc` directory.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+#[tarpc::service]
+trait World {
+    async fn hello(name: String) -> String;
+    async fn add(x: i64, y: i64) -> i64;
+}
+
+fuzz_target!(|data: &[u8]| tarpc::fuzz::decode_requests::<WorldRequest>(data));
@@ -0,0 +1,378 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Provides deserialization of requests into a per-request arena.
+//!
+//! Deeply nested requests make many small allocations when they're deserialized, and free each of
+//! them when they're dropped. A server can instead deserialize a request as a view whose strings
+//! and collections are allocated in a [`Bump`] arena owned by the request handler, so that all of
+//! them are freed in one shot when the handler returns its response. A view is any type
+//! implementing [`DeserializeIn`], and is deserialized from the same encoding as the owned request
+//! sent by the client:
+//!
+//! | View type             | Sent by the client as |
+//! |-----------------------|-----------------------|
+//! | `&'a str`             | `String`              |
+//! | `&'a [u8]`            | `Vec<u8>` or bytes    |
+//! | `&'a T`               | `T`                   |
+//! | [`String<'a>`]        | `String`              |
+//! | [`Vec<'a, T>`]        | `Vec<T>`              |
+//! | `Option<T>`, tuples   | themselves            |
+//! | primitives            | themselves            |
+//!
+//! Any self-describing or non-self-describing serde format can deserialize views. Handlers
+//! usually decode views from [`Lazy`](crate::serde_transport::Lazy) args, whose
+//! [bytes](crate::serde_transport::Lazy::as_bytes) they decode with the format the client encoded
+//! them with.
+//!
+//! # Example
+//!
+//! ```rust
+//! use tarpc::arena::{Bump, DeserializeIn, Vec};
+//!
+//! // Sent by the client as a `Vec<(String, u64)>`.
+//! let json = br#"[["apples",3],["pears",5]]"#;
+//!
+//! let arena = Bump::new();
+//! let mut deserializer = serde_json::Deserializer::from_slice(json);
+//! let fruit = Vec::<(&str, u64)>::deserialize_in(&arena, &mut deserializer)?;
+//! assert_eq!(fruit[1], ("pears", 5));
+//! // Frees every string and the vec at once.
+//! drop(fruit);
+//! drop(arena);
+//! # Ok::<(), serde_json::Error>(())
+//! ```
+
+pub use bumpalo::{
+    collections::{String, Vec},
+    Bump,
+};
+use serde::de::{self, DeserializeSeed, Deserializer, SeqAccess, Visitor};
+use std::{fmt, marker::PhantomData};
+
+/// A type that can be deserialized with its allocations made in an arena.
+pub trait DeserializeIn<'a>: Sized + 'a {
+    /// Deserializes a value, allocating in `arena`.
+    fn deserialize_in<'de, D: Deserializer<'de>>(
+        arena: &'a Bump,
+        deserializer: D,
+    ) -> Result<Self, D::Error>;
+}
+
+/// Deserializes a `T` in an arena, e.g. as the elements of a sequence.
+pub struct Seed<'a, T> {
+    arena: &'a Bump,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<'a, T> Seed<'a, T> {
+    /// Returns a seed that deserializes a `T` in `arena`.
+    pub fn new(arena: &'a Bump) -> Self {
+        Self {
+            arena,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, T> Clone for Seed<'a, T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<'a, T> Copy for Seed<'a, T> {}
+
+impl<'a, T> fmt::Debug for Seed<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Seed")
+            .field("allocated_bytes", &self.arena.allocated_bytes())
+            .finish()
+    }
+}
+
+impl<'a, 'de, T: DeserializeIn<'a>> DeserializeSeed<'de> for Seed<'a, T> {
+    type Value = T;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<T, D::Error> {
+        T::deserialize_in(self.arena, deserializer)
+    }
+}
+
+/// Implements `DeserializeIn` for types that don't allocate, by deserializing them as they are.
+macro_rules! deserialize_in_place {
+    ($($ty:ty),*) => {
+        $(
+            impl<'a> DeserializeIn<'a> for $ty {
+                fn deserialize_in<'de, D: Deserializer<'de>>(
+                    _: &'a Bump,
+                    deserializer: D,
+                ) -> Result<Self, D::Error> {
+                    serde::Deserialize::deserialize(deserializer)
+                }
+            }
+        )*
+    };
+}
+
+deserialize_in_place!(
+    (),
+    bool,
+    char,
+    u8,
+    u16,
+    u32,
+    u64,
+    u128,
+    usize,
+    i8,
+    i16,
+    i32,
+    i64,
+    i128,
+    isize,
+    f32,
+    f64
+);
+
+/// Deserializes strings into an arena.
+struct StrVisitor<'a>(&'a Bump);
+
+impl<'a, 'de> Visitor<'de> for StrVisitor<'a> {
+    type Value = &'a str;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a string")
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<&'a str, E> {
+        Ok(self.0.alloc_str(v))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<&'a str, E> {
+        let v = std::str::from_utf8(v)
+            .map_err(|_| de::Error::invalid_value(de::Unexpected::Bytes(v), &self))?;
+        Ok(self.0.alloc_str(v))
+    }
+}
+
+impl<'a> DeserializeIn<'a> for &'a str {
+    fn deserialize_in<'de, D: Deserializer<'de>>(
+        arena: &'a Bump,
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(StrVisitor(arena))
+    }
+}
+
+impl<'a> DeserializeIn<'a> for String<'a> {
+    fn deserialize_in<'de, D: Deserializer<'de>>(
+        arena: &'a Bump,
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        let s = <&str>::deserialize_in(arena, deserializer)?;
+        Ok(String::from_str_in(s, arena))
+    }
+}
+
+impl<'a> DeserializeIn<'a> for &'a [u8] {
+    fn deserialize_in<'de, D: Deserializer<'de>>(
+        arena: &'a Bump,
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        struct BytesVisitor<'a>(&'a Bump);
+
+        impl<'a, 'de> Visitor<'de> for BytesVisitor<'a> {
+            type Value = &'a [u8];
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("bytes")
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<&'a [u8], E> {
+                Ok(self.0.alloc_slice_copy(v))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<&'a [u8], A::Error> {
+                Ok(visit_vec(self.0, seq)?.into_bump_slice())
+            }
+        }
+
+        deserializer.deserialize_bytes(BytesVisitor(arena))
+    }
+}
+
+impl<'a, T: DeserializeIn<'a>> DeserializeIn<'a> for &'a T {
+    fn deserialize_in<'de, D: Deserializer<'de>>(
+        arena: &'a Bump,
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        Ok(arena.alloc(T::deserialize_in(arena, deserializer)?))
+    }
+}
+
+/// Collects the elements of `seq` into a vec in `arena`.
+fn visit_vec<'a, 'de, T: DeserializeIn<'a>, A: SeqAccess<'de>>(
+    arena: &'a Bump,
+    mut seq: A,
+) -> Result<Vec<'a, T>, A::Error> {
+    // Don't trust the size hint further than a small allocation.
+    let mut vec = Vec::with_capacity_in(seq.size_hint().unwrap_or(0).min(4096), arena);
+    while let Some(element) = seq.next_element_seed(Seed::new(arena))? {
+        vec.push(element);
+    }
+    Ok(vec)
+}
+
+impl<'a, T: DeserializeIn<'a>> DeserializeIn<'a> for Vec<'a, T> {
+    fn deserialize_in<'de, D: Deserializer<'de>>(
+        arena: &'a Bump,
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        struct VecVisitor<'a, T>(&'a Bump, PhantomData<fn() -> T>);
+
+        impl<'a, 'de, T: DeserializeIn<'a>> Visitor<'de> for VecVisitor<'a, T> {
+            type Value = Vec<'a, T>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a sequence")
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Vec<'a, T>, A::Error> {
+                visit_vec(self.0, seq)
+            }
+        }
+
+        deserializer.deserialize_seq(VecVisitor(arena, PhantomData))
+    }
+}
+
+impl<'a, T: DeserializeIn<'a>> DeserializeIn<'a> for Option<T> {
+    fn deserialize_in<'de, D: Deserializer<'de>>(
+        arena: &'a Bump,
+        deserializer: D,
+    ) -> Result<Self, D::Error> {
+        struct OptionVisitor<'a, T>(&'a Bump, PhantomData<fn() -> T>);
+
+        impl<'a, 'de, T: DeserializeIn<'a>> Visitor<'de> for OptionVisitor<'a, T> {
+            type Value = Option<T>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("an option")
+            }
+
+            fn visit_none<E: de::Error>(self) -> Result<Option<T>, E> {
+                Ok(None)
+            }
+
+            fn visit_unit<E: de::Error>(self) -> Result<Option<T>, E> {
+                Ok(None)
+            }
+
+            fn visit_some<D: Deserializer<'de>>(
+                self,
+                deserializer: D,
+            ) -> Result<Option<T>, D::Error> {
+                T::deserialize_in(self.0, deserializer).map(Some)
+            }
+        }
+
+        deserializer.deserialize_option(OptionVisitor(arena, PhantomData))
+    }
+}
+
+/// Implements `DeserializeIn` for tuples of views.
+macro_rules! deserialize_tuple_in {
+    ($(($len:literal, $($name:ident),+)),*) => {
+        $(
+            impl<'a, $($name: DeserializeIn<'a>),+> DeserializeIn<'a> for ($($name,)+) {
+                fn deserialize_in<'de, D: Deserializer<'de>>(
+                    arena: &'a Bump,
+                    deserializer: D,
+                ) -> Result<Self, D::Error> {
+                    struct TupleVisitor<'a, $($name),+>(&'a Bump, PhantomData<fn() -> ($($name,)+)>);
+
+                    impl<'a, 'de, $($name: DeserializeIn<'a>),+> Visitor<'de>
+                        for TupleVisitor<'a, $($name),+>
+                    {
+                        type Value = ($($name,)+);
+
+                        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                            write!(f, "a tuple of size {}", $len)
+                        }
+
+                        #[allow(non_snake_case)]
+                        fn visit_seq<A: SeqAccess<'de>>(
+                            self,
+                            mut seq: A,
+                        ) -> Result<Self::Value, A::Error> {
+                            let mut len = 0;
+                            $(
+                                let $name = seq
+                                    .next_element_seed(Seed::<$name>::new(self.0))?
+                                    .ok_or_else(|| de::Error::invalid_length(len, &self))?;
+                                len += 1;
+                            )+
+                            let _ = len;
+                            Ok(($($name,)+))
+                        }
+                    }
+
+                    deserializer.deserialize_tuple($len, TupleVisitor(arena, PhantomData))
+                }
+            }
+        )*
+    };
+}
+
+deserialize_tuple_in!(
+    (1, T0),
+    (2, T0, T1),
+    (3, T0, T1, T2),
+    (4, T0, T1, T2, T3),
+    (5, T0, T1, T2, T3, T4),
+    (6, T0, T1, T2, T3, T4, T5)
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bincode::Options;
+
+    #[test]
+    fn views_deserialize_from_owned_encodings() {
+        let owned: (
+            std::string::String,
+            std::vec::Vec<Option<u32>>,
+            std::vec::Vec<u8>,
+        ) = ("nested".into(), vec![Some(1), None], vec![1, 2, 3]);
+
+        let arena = Bump::new();
+        let json = serde_json::to_vec(&owned).unwrap();
+        let mut deserializer = serde_json::Deserializer::from_slice(&json);
+        let (s, options, bytes) =
+            <(String, Vec<Option<u32>>, &[u8])>::deserialize_in(&arena, &mut deserializer).unwrap();
+        assert_eq!(s, "nested");
+        assert_eq!(options, [Some(1), None]);
+        assert_eq!(bytes, [1, 2, 3]);
+
+        let bincode = bincode::serialize(&owned).unwrap();
+        let view: &(&str, Vec<Option<u32>>, &[u8]) = bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .deserialize_seed(Seed::new(&arena), &bincode)
+            .unwrap();
+        assert_eq!(view.0, "nested");
+        assert_eq!(view.1, [Some(1), None]);
+        assert_eq!(view.2, [1, 2, 3]);
+    }
+
+    #[test]
+    fn views_report_invalid_lengths() {
+        let arena = Bump::new();
+        let mut deserializer = serde_json::Deserializer::from_slice(b"[1]");
+        assert!(<(u8, u8)>::deserialize_in(&arena, &mut deserializer).is_err());
+    }
+}
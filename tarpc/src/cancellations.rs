@@ -1,21 +1,35 @@
 use futures::{prelude::*, task::*};
-use std::pin::Pin;
+use std::{pin::Pin, sync::Arc};
 use tokio::sync::mpsc;
 
-/// Sends request cancellation signals.
+/// Sends request cancellation signals: the ID of each request canceled, and whether the
+/// cancellation should be acknowledged.
 #[derive(Debug, Clone)]
-pub struct RequestCancellation(mpsc::UnboundedSender<u64>);
+pub struct RequestCancellation(Arc<[mpsc::UnboundedSender<(u64, bool)>]>);
 
 /// A stream of IDs of requests that have been canceled.
 #[derive(Debug)]
-pub struct CanceledRequests(mpsc::UnboundedReceiver<u64>);
+pub struct CanceledRequests {
+    shards: Vec<mpsc::UnboundedReceiver<(u64, bool)>>,
+    /// The shard whose cancellations are yielded first, so that no shard is starved.
+    next: usize,
+}
 
-/// Returns a channel to send request cancellation messages.
-pub fn cancellations() -> (RequestCancellation, CanceledRequests) {
+/// Returns a channel to send request cancellation messages, split into `shards` shards by request
+/// ID so that requests canceled at once on many threads don't all contend for one queue.
+pub fn cancellations(shards: usize) -> (RequestCancellation, CanceledRequests) {
     // Unbounded because messages are sent in the drop fn. This is fine, because it's still
     // bounded by the number of in-flight requests.
-    let (tx, rx) = mpsc::unbounded_channel();
-    (RequestCancellation(tx), CanceledRequests(rx))
+    let (txs, rxs): (Vec<_>, _) = (0..shards.max(1))
+        .map(|_| mpsc::unbounded_channel())
+        .unzip();
+    (
+        RequestCancellation(Arc::<[_]>::from(txs)),
+        CanceledRequests {
+            shards: rxs,
+            next: 0,
+        },
+    )
 }
 
 impl RequestCancellation {
@@ -29,14 +43,47 @@ impl RequestCancellation {
     /// useful primarily when request processing ends prematurely for requests with long deadlines
     /// which would otherwise continue to be tracked by the backing channel—a kind of leak.
     pub fn cancel(&self, request_id: u64) {
-        let _ = self.0.send(request_id);
+        self.send(request_id, false);
+    }
+
+    /// Cancels the request with ID `request_id`, asking for the cancellation to be acknowledged.
+    pub fn cancel_with_ack(&self, request_id: u64) {
+        self.send(request_id, true);
+    }
+
+    fn send(&self, request_id: u64, ack: bool) {
+        let shard = (request_id % self.0.len() as u64) as usize;
+        let _ = self.0[shard].send((request_id, ack));
     }
 }
 
 impl CanceledRequests {
     /// Polls for a cancelled request.
     pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<u64>> {
-        self.0.poll_recv(cx)
+        self.poll_recv_with_ack(cx)
+            .map(|canceled| canceled.map(|(request_id, _)| request_id))
+    }
+
+    /// Polls for a cancelled request and whether its cancellation should be acknowledged.
+    pub fn poll_recv_with_ack(&mut self, cx: &mut Context<'_>) -> Poll<Option<(u64, bool)>> {
+        let shards = self.shards.len();
+        let mut pending = false;
+        for i in 0..shards {
+            let shard = (self.next + i) % shards;
+            match self.shards[shard].poll_recv(cx) {
+                Poll::Ready(Some(canceled)) => {
+                    self.next = (shard + 1) % shards;
+                    return Poll::Ready(Some(canceled));
+                }
+                Poll::Ready(None) => {}
+                Poll::Pending => pending = true,
+            }
+        }
+        if pending {
+            Poll::Pending
+        } else {
+            Poll::Ready(None)
+        }
     }
 }
 
@@ -47,3 +94,21 @@ impl Stream for CanceledRequests {
         self.poll_recv(cx)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::cancellations;
+    use futures::prelude::*;
+
+    #[tokio::test]
+    async fn sharded_cancellations_are_all_received() {
+        let (cancellation, canceled_requests) = cancellations(3);
+        for request_id in 0..10 {
+            cancellation.cancel(request_id);
+        }
+        drop(cancellation);
+        let mut canceled = canceled_requests.collect::<Vec<_>>().await;
+        canceled.sort_unstable();
+        assert_eq!(canceled, (0..10).collect::<Vec<_>>());
+    }
+}
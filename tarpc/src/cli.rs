@@ -0,0 +1,185 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Provides command-line clients for services, for debugging them by hand.
+//!
+//! With `#[tarpc::service(cli = true)]`, the generated request enum implements [`CliRequest`]: it
+//! builds a [clap](clap) command with one subcommand per method, taking each arg as a JSON
+//! value, and parses the arg matches into a request. [`run`] then sends the request over a
+//! transport of the caller's choosing and writes the responses as lines of JSON.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use tarpc::{cli::CliRequest, tokio_serde::formats::Json};
+//!
+//! #[tarpc::service(cli = true)]
+//! trait World {
+//!     /// Says hello.
+//!     async fn hello(name: String) -> String;
+//! }
+//!
+//! # #[cfg(feature = "tcp")]
+//! #[tokio::main]
+//! async fn main() -> anyhow::Result<()> {
+//!     // Prints `"Hello, Ferris!"` when run as `world hello --name '"Ferris"'`.
+//!     let matches = WorldRequest::command("world").get_matches();
+//!     let transport = tarpc::serde_transport::tcp::connect("localhost:5000", Json::default).await?;
+//!     tarpc::cli::run::<WorldRequest, _, _, _>(transport, &matches, std::io::stdout()).await?;
+//!     Ok(())
+//! }
+//! # #[cfg(not(feature = "tcp"))]
+//! # fn main() {}
+//! ```
+
+use crate::{
+    client::{self, RpcError},
+    context, ClientMessage, Response, Transport,
+};
+use futures::StreamExt;
+use serde::de::DeserializeOwned;
+use std::io;
+
+pub use clap;
+#[doc(hidden)]
+pub use serde_json;
+
+/// How a method is called.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CallKind {
+    /// The method responds once.
+    Unary,
+    /// The method responds with a stream of responses.
+    Stream,
+    /// The method doesn't respond.
+    Oneway,
+}
+
+/// A request parsed from the command line, along with how to send it.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct Call<Req> {
+    /// The name of the method called, e.g. `World.hello`.
+    pub method: &'static str,
+    /// How the method is called.
+    pub kind: CallKind,
+    /// The request to send.
+    pub request: Req,
+}
+
+impl<Req> Call<Req> {
+    /// Returns a new call of `method`.
+    pub fn new(method: &'static str, kind: CallKind, request: Req) -> Self {
+        Self {
+            method,
+            kind,
+            request,
+        }
+    }
+}
+
+/// A request enum that can be parsed from the command line. Implemented by request enums
+/// generated with `#[tarpc::service(cli = true)]`.
+pub trait CliRequest: Sized {
+    /// The response enum of the service.
+    type Response;
+
+    /// Returns a command named `name` with one subcommand per method, named after the method in
+    /// kebab-case. Each arg of a method is a required `--<arg>` flag taking a JSON value.
+    fn command(name: &'static str) -> clap::Command<'static>;
+
+    /// Parses the subcommand of `matches`, which were matched against
+    /// [`command`](CliRequest::command), into a call.
+    fn from_arg_matches(matches: &clap::ArgMatches) -> Result<Call<Self>, CliError>;
+
+    /// Converts a response into the JSON value of its method's return type.
+    fn response_to_json(response: Self::Response) -> serde_json::Result<serde_json::Value>;
+}
+
+/// An error running a command-line client.
+#[derive(thiserror::Error, Debug)]
+#[non_exhaustive]
+pub enum CliError {
+    /// No subcommand was given.
+    #[error("expected a subcommand naming the method to call")]
+    MissingSubcommand,
+    /// The subcommand given doesn't name a method.
+    #[error("no method matches subcommand `{0}`")]
+    UnknownSubcommand(String),
+    /// An arg wasn't given.
+    #[error("missing arg `--{0}`")]
+    MissingArg(&'static str),
+    /// An arg wasn't a valid JSON value of the arg's type.
+    #[error("invalid value of arg `--{arg}`")]
+    InvalidArg {
+        /// The name of the arg.
+        arg: &'static str,
+        /// Why the value is invalid.
+        #[source]
+        source: serde_json::Error,
+    },
+    /// The call failed.
+    #[error(transparent)]
+    Rpc(#[from] RpcError),
+    /// A response couldn't be converted to JSON.
+    #[error("failed to convert a response to JSON")]
+    Json(#[source] serde_json::Error),
+    /// The responses couldn't be written.
+    #[error("failed to write a response")]
+    Io(#[from] io::Error),
+}
+
+/// Parses the JSON value of the arg `name` of `matches`.
+#[doc(hidden)]
+pub fn parse_arg<T: DeserializeOwned>(
+    matches: &clap::ArgMatches,
+    name: &'static str,
+) -> Result<T, CliError> {
+    let value = matches
+        .get_one::<String>(name)
+        .ok_or(CliError::MissingArg(name))?;
+    serde_json::from_str(value).map_err(|source| CliError::InvalidArg { arg: name, source })
+}
+
+/// Parses a call from `matches`, sends it over `transport`, and writes each response to `out` as
+/// a line of JSON.
+///
+/// `matches` must have been matched against [`Req::command`](CliRequest::command), e.g. as the
+/// matches of a subcommand of a larger command. The call is made with the default context.
+pub async fn run<Req, Resp, T, W>(
+    transport: T,
+    matches: &clap::ArgMatches,
+    mut out: W,
+) -> Result<(), CliError>
+where
+    Req: CliRequest<Response = Resp> + Send + 'static,
+    Resp: Send + 'static,
+    T: Transport<ClientMessage<Req>, Response<Resp>> + Send + 'static,
+    W: io::Write,
+{
+    let call = Req::from_arg_matches(matches)?;
+    let channel = client::new(client::Config::default(), transport).spawn();
+    let ctx = context::current();
+    let mut write_response = |response| -> Result<(), CliError> {
+        let json = Req::response_to_json(response).map_err(CliError::Json)?;
+        serde_json::to_writer(&mut out, &json).map_err(CliError::Json)?;
+        writeln!(out)?;
+        Ok(())
+    };
+    match call.kind {
+        CallKind::Unary => {
+            write_response(channel.call(ctx, call.method, call.request).await?)?;
+        }
+        CallKind::Stream => {
+            let mut body = channel.call_body(ctx, call.method, call.request).await?;
+            while let Some(response) = body.next().await {
+                write_response(response?)?;
+            }
+        }
+        CallKind::Oneway => channel.call_oneway(ctx, call.method, call.request).await?,
+    }
+    Ok(())
+}
@@ -7,23 +7,38 @@
 //! Provides a client that connects to a server and sends multiplexed requests.
 
 mod in_flight_requests;
+#[cfg(feature = "metrics")]
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+pub mod instrumented;
+pub mod response_extensions;
+pub mod sampling;
 pub mod stub;
 
+use crate::util;
 use crate::{
     cancellations::{cancellations, CanceledRequests, RequestCancellation},
-    context, trace, ChannelError, ClientMessage, Request, Response, ServerError, Transport,
+    context,
+    stats::{ChannelStats, RequestTimer, Role},
+    trace,
+    transport::{FlushPolicy, Flusher},
+    util::YieldBudget,
+    ApplicationError, CancellationReason, ChannelError, ClientMessage, Request, Response,
+    ResponseExtensions, ServerError, ServerErrorReason, Transport,
 };
-use futures::{prelude::*, ready, stream::Fuse, task::*};
+use futures::{future::BoxFuture, prelude::*, ready, stream::Fuse, task::*};
 use in_flight_requests::InFlightRequests;
 use pin_project::pin_project;
 use std::{
+    collections::VecDeque,
     convert::TryFrom,
     fmt,
+    net::SocketAddr,
     pin::Pin,
     sync::{
-        atomic::{AtomicUsize, Ordering},
-        Arc,
+        atomic::{self, AtomicU64, Ordering},
+        Arc, OnceLock,
     },
+    time::Duration,
 };
 use tokio::sync::{mpsc, oneshot};
 use tracing::Span;
@@ -36,17 +51,67 @@ pub struct Config {
     /// `max_in_flight_requests` controls the size of the map used by the client
     /// for storing pending requests.
     pub max_in_flight_requests: usize,
+    /// The number of shards the client's in-flight requests, their deadlines, and their
+    /// cancellations are split into. Channels with very many requests in flight can use more than
+    /// one shard to keep the structures tracking them small.
+    pub in_flight_shards: usize,
     /// The number of requests that can be buffered client-side before being sent.
     /// `pending_requests_buffer` controls the size of the channel clients use
     /// to communicate with the request dispatch task.
     pub pending_request_buffer: usize,
+    /// The priority of requests whose context has the [default
+    /// priority](context::Context::has_default_priority), i.e. that aren't made while handling a
+    /// request and whose priority isn't set.
+    pub priority: context::Priority,
+    /// Decides whether to sample the trace of each request, if set. Otherwise, the sampling
+    /// decision is that of the request's span.
+    pub sampler: Option<sampling::Sampler>,
+    /// The address of the server, if known, with which the spans of requests are annotated.
+    pub peer_addr: Option<SocketAddr>,
+    /// When the dispatch flushes the requests and cancellations it writes to the transport.
+    pub flush_policy: FlushPolicy,
+    /// The most messages that the dispatch reads and writes before yielding to the executor, even
+    /// if more are ready. Unbounded if `None`. A dispatch that never yields can starve the other
+    /// tasks on its worker while it's flooded with requests or responses.
+    pub frames_per_yield: Option<usize>,
+    /// The most cancellations of dropped requests that the dispatch writes in one
+    /// [`CancelBatch`](ClientMessage::CancelBatch) frame, so that dropping many response futures
+    /// at once doesn't flood the connection with frames. If 1, each cancellation is written in a
+    /// frame of its own, which servers predating batches require. Cancellations that ask for an
+    /// acknowledgement are always written on their own.
+    pub max_cancellations_per_frame: usize,
+    /// The most cancellations waiting to be written to the transport at once. Unbounded if
+    /// `None`. Requests dropped while the limit is reached are abandoned without telling the
+    /// server, like expired requests. Cancellations that ask for an acknowledgement always wait.
+    pub max_pending_cancellations: Option<usize>,
+    /// The number of abandoned requests, i.e. expired requests and requests canceled without an
+    /// acknowledgement, whose IDs the dispatch remembers until the server responds to them.
+    /// Request IDs wrap around after `u64::MAX` requests; a request reusing a remembered ID, or
+    /// the ID of a request still in flight, is sent with the next unused ID instead, and stale
+    /// responses to abandoned requests are dropped, so that no response reaches the wrong caller.
+    pub max_abandoned_requests: usize,
+    /// The number of partial responses of a [body](Channel::call_body) that the client buffers
+    /// while the body isn't read. Once a body's buffer is full, the dispatch stops reading
+    /// responses off the wire until the body is read, so a slow reader slows the server down
+    /// rather than buffering the whole body in memory.
+    pub partial_response_buffer: usize,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Config {
             max_in_flight_requests: 1_000,
+            in_flight_shards: 1,
             pending_request_buffer: 100,
+            priority: context::Priority::default(),
+            sampler: None,
+            peer_addr: None,
+            flush_policy: FlushPolicy::default(),
+            frames_per_yield: None,
+            max_cancellations_per_frame: 1,
+            max_pending_cancellations: None,
+            max_abandoned_requests: 1_000,
+            partial_response_buffer: 16,
         }
     }
 }
@@ -69,34 +134,59 @@ where
     #[cfg(feature = "tokio1")]
     #[cfg_attr(docsrs, doc(cfg(feature = "tokio1")))]
     pub fn spawn(self) -> C {
+        self.spawn_with(|dispatch| {
+            tokio::spawn(dispatch);
+        })
+    }
+
+    /// Spawns the dispatch with `spawn`, e.g. on the executor of a deterministic simulator,
+    /// logging the error it fails with, if any.
+    pub fn spawn_with<S>(self, spawn: S) -> C
+    where
+        S: FnOnce(BoxFuture<'static, ()>),
+    {
         let dispatch = self.dispatch.unwrap_or_else(move |e| {
             let e = anyhow::Error::new(e);
             tracing::warn!("Connection broken: {:?}", e);
         });
-        tokio::spawn(dispatch);
+        spawn(dispatch.boxed());
         self.client
     }
 }
 
+impl<Req, Resp, C> NewClient<Channel<Req, Resp>, RequestDispatch<Req, Resp, C>> {
+    /// Records the client's traffic in `stats`, rather than in counters of its own. Pass the
+    /// stats of a transport that counts bytes to collect all counters in one place.
+    pub fn with_stats(mut self, stats: ChannelStats) -> Self {
+        stats.set_role(Role::Client);
+        self.client.stats = stats.clone();
+        self.dispatch.stats = stats;
+        self
+    }
+}
+
 impl<C, D> fmt::Debug for NewClient<C, D> {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(fmt, "NewClient")
     }
 }
 
-const _CHECK_USIZE: () = assert!(
-    std::mem::size_of::<usize>() <= std::mem::size_of::<u64>(),
-    "usize is too big to fit in u64"
-);
-
 /// Handles communication from the client to request dispatch.
 #[derive(Debug)]
 pub struct Channel<Req, Resp> {
     to_dispatch: mpsc::Sender<DispatchRequest<Req, Resp>>,
     /// Channel to send a cancel message to the dispatcher.
     cancellation: RequestCancellation,
-    /// The ID to use for the next request to stage.
-    next_request_id: Arc<AtomicUsize>,
+    /// The ID to use for the next request to stage, which wraps around to 0 after `u64::MAX`.
+    next_request_id: Arc<AtomicU64>,
+    /// Counts the traffic of the channel.
+    stats: ChannelStats,
+    /// Decides whether to sample the traces of requests, if set.
+    sampler: Option<sampling::Sampler>,
+    /// The address of the server, if known.
+    peer_addr: Option<SocketAddr>,
+    /// The number of partial responses buffered by each body.
+    partial_response_buffer: usize,
 }
 
 impl<Req, Resp> Clone for Channel<Req, Resp> {
@@ -105,39 +195,34 @@ impl<Req, Resp> Clone for Channel<Req, Resp> {
             to_dispatch: self.to_dispatch.clone(),
             cancellation: self.cancellation.clone(),
             next_request_id: self.next_request_id.clone(),
+            stats: self.stats.clone(),
+            sampler: self.sampler.clone(),
+            peer_addr: self.peer_addr,
+            partial_response_buffer: self.partial_response_buffer,
         }
     }
 }
 
 impl<Req, Resp> Channel<Req, Resp> {
+    /// Returns the counters of the channel's traffic, which are shared with its dispatch.
+    pub fn stats(&self) -> &ChannelStats {
+        &self.stats
+    }
+
     /// Sends a request to the dispatch task to forward to the server, returning a [`Future`] that
     /// resolves to the response.
-    #[tracing::instrument(
-        name = "RPC",
-        skip(self, ctx, request_name, request),
-        fields(
-            rpc.trace_id = tracing::field::Empty,
-            rpc.deadline = %humantime::format_rfc3339(ctx.deadline),
-            otel.kind = "client",
-            otel.name = request_name)
-        )]
     pub async fn call(
         &self,
         mut ctx: context::Context,
         request_name: &'static str,
         request: Req,
     ) -> Result<Resp, RpcError> {
-        let span = Span::current();
-        ctx.trace_context = trace::Context::try_from(&span).unwrap_or_else(|_| {
-            tracing::trace!(
-                "OpenTelemetry subscriber not installed; making unsampled child context."
-            );
-            ctx.trace_context.new_child()
-        });
-        span.record("rpc.trace_id", &tracing::field::display(ctx.trace_id()));
+        let span = Self::span(&ctx, request_name, self.peer_addr);
+        self.trace(&mut ctx, &span, request_name);
+        let timer = RequestTimer::start(Role::Client, request_name);
         let (response_completion, mut response) = oneshot::channel();
-        let request_id =
-            u64::try_from(self.next_request_id.fetch_add(1, Ordering::Relaxed)).unwrap();
+        let request_id = self.next_request_id();
+        let wire_id = Arc::new(WireId::default());
 
         // ResponseGuard impls Drop to cancel in-flight requests. It should be created before
         // sending out the request; otherwise, the response future could be dropped after the
@@ -145,7 +230,7 @@ impl<Req, Resp> Channel<Req, Resp> {
         // logic inactive.
         let response_guard = ResponseGuard {
             response: &mut response,
-            request_id,
+            wire_id: wire_id.clone(),
             cancellation: &self.cancellation,
             cancel: true,
         };
@@ -154,24 +239,336 @@ impl<Req, Resp> Channel<Req, Resp> {
                 ctx,
                 span,
                 request_id,
+                wire_id,
                 request,
                 response_completion,
+                partial_responses: None,
+                oneway: false,
             })
             .await
             .map_err(|mpsc::error::SendError(_)| RpcError::Shutdown)?;
-        response_guard.response().await
+        let (response, extensions) = response_guard.response().await;
+        timer.finish(response.is_err());
+        response_extensions::record(extensions);
+        response
+    }
+
+    /// Sends a request whose response is a [body](crate::server::body::Body) streamed by the
+    /// server, returning the stream of responses once the request is sent to the dispatch task.
+    ///
+    /// The request's deadline applies to the body as a whole. Dropping the body before it ends
+    /// cancels the request.
+    pub async fn call_body(
+        &self,
+        mut ctx: context::Context,
+        request_name: &'static str,
+        request: Req,
+    ) -> Result<ResponseBody<Resp>, RpcError> {
+        let span = Self::span(&ctx, request_name, self.peer_addr);
+        self.trace(&mut ctx, &span, request_name);
+        let (response_completion, response) = oneshot::channel();
+        // The sender counts toward the capacity of the channel.
+        let (partial_responses_tx, partial_responses) =
+            futures::channel::mpsc::channel(self.partial_response_buffer.saturating_sub(1));
+        let request_id = self.next_request_id();
+        let wire_id = Arc::new(WireId::default());
+
+        // As in `call`, the body must exist before the request is sent out so that dropping it
+        // cancels the request.
+        let body = ResponseBody {
+            partial_responses,
+            response: Some(response),
+            cancellation: self.cancellation.clone(),
+            wire_id: wire_id.clone(),
+            timer: RequestTimer::start(Role::Client, request_name),
+        };
+        self.to_dispatch
+            .send(DispatchRequest {
+                ctx,
+                span,
+                request_id,
+                wire_id,
+                request,
+                response_completion,
+                partial_responses: Some(partial_responses_tx),
+                oneway: false,
+            })
+            .await
+            .map_err(|mpsc::error::SendError(_)| RpcError::Shutdown)?;
+        Ok(body)
+    }
+
+    /// Sends a request to the dispatch task to forward to the server, returning once the dispatch
+    /// task has accepted the request. The returned call resolves to the response, unless canceled
+    /// with [`CancelableCall::cancel`], which tells whether the server aborted the request or had
+    /// already completed it.
+    pub async fn call_cancelable(
+        &self,
+        mut ctx: context::Context,
+        request_name: &'static str,
+        request: Req,
+    ) -> Result<CancelableCall<Resp>, RpcError> {
+        let span = Self::span(&ctx, request_name, self.peer_addr);
+        self.trace(&mut ctx, &span, request_name);
+        let (response_completion, response) = oneshot::channel();
+        let request_id = self.next_request_id();
+        let wire_id = Arc::new(WireId::default());
+
+        // As in `call`, the call must exist before the request is sent out so that dropping it
+        // cancels the request.
+        let call = CancelableCall {
+            response: Some(response),
+            cancellation: self.cancellation.clone(),
+            wire_id: wire_id.clone(),
+            timer: RequestTimer::start(Role::Client, request_name),
+        };
+        self.to_dispatch
+            .send(DispatchRequest {
+                ctx,
+                span,
+                request_id,
+                wire_id,
+                request,
+                response_completion,
+                partial_responses: None,
+                oneway: false,
+            })
+            .await
+            .map_err(|mpsc::error::SendError(_)| RpcError::Shutdown)?;
+        Ok(call)
+    }
+
+    /// Sends a request to the dispatch task to forward to the server without awaiting a
+    /// response, returning once the dispatch task has accepted the request.
+    ///
+    /// The server executes the request but sends no response, so the client doesn't learn
+    /// whether the request succeeded, or even reached the server.
+    pub async fn call_oneway(
+        &self,
+        mut ctx: context::Context,
+        request_name: &'static str,
+        request: Req,
+    ) -> Result<(), RpcError> {
+        let span = Self::span(&ctx, request_name, self.peer_addr);
+        self.trace(&mut ctx, &span, request_name);
+        let (response_completion, _) = oneshot::channel();
+        self.to_dispatch
+            .send(DispatchRequest {
+                ctx,
+                span,
+                request_id: self.next_request_id(),
+                wire_id: Arc::default(),
+                request,
+                response_completion,
+                partial_responses: None,
+                oneway: true,
+            })
+            .await
+            .map_err(|mpsc::error::SendError(_)| RpcError::Shutdown)
+    }
+
+    /// Returns the span of a request, annotated with the OpenTelemetry semantic conventions for
+    /// RPC, or a disabled span if the request skips it.
+    fn span(
+        ctx: &context::Context,
+        request_name: &'static str,
+        peer_addr: Option<SocketAddr>,
+    ) -> Span {
+        if ctx.untraced {
+            return Span::none();
+        }
+        let (service, method) = util::rpc_service_and_method(request_name);
+        tracing::info_span!(
+            "RPC",
+            rpc.system = "tarpc",
+            rpc.service = service,
+            rpc.method = method,
+            rpc.trace_id = tracing::field::Empty,
+            rpc.deadline = %humantime::format_rfc3339(util::system_time(ctx.deadline)),
+            server.address = peer_addr.map(|addr| tracing::field::display(addr.ip())),
+            server.port = peer_addr.map(|addr| addr.port()),
+            otel.kind = "client",
+            otel.name = util::rpc_span_name(service, method),
+            otel.status_code = tracing::field::Empty,
+        )
+    }
+
+    /// Sets the trace context of a request about to be sent within `span`.
+    fn trace(&self, ctx: &mut context::Context, span: &Span, request_name: &'static str) {
+        ctx.trace_context = trace::Context::try_from(span).unwrap_or_else(|_| {
+            tracing::trace!(
+                "OpenTelemetry subscriber not installed; making unsampled child context."
+            );
+            ctx.trace_context.new_child()
+        });
+        if let Some(sampler) = &self.sampler {
+            ctx.trace_context.sampling_decision = sampler.sample(&sampling::SamplingRequest {
+                request_name,
+                trace_id: ctx.trace_context.trace_id,
+                span_decision: ctx.trace_context.sampling_decision,
+            });
+        }
+        span.record("rpc.trace_id", tracing::field::display(ctx.trace_id()));
+    }
+
+    fn next_request_id(&self) -> u64 {
+        self.next_request_id.fetch_add(1, Ordering::Relaxed)
     }
 }
 
 /// A server response that is completed by request dispatch when the corresponding response
 /// arrives off the wire.
 struct ResponseGuard<'a, Resp> {
-    response: &'a mut oneshot::Receiver<Result<Resp, RpcError>>,
+    response: &'a mut oneshot::Receiver<Completion<Resp>>,
     cancellation: &'a RequestCancellation,
-    request_id: u64,
+    wire_id: Arc<WireId>,
     cancel: bool,
 }
 
+/// The responses to a request sent with [`Channel::call_body`], yielded as they arrive off the
+/// wire.
+///
+/// The body ends after the server's final response, or after an error. Dropping the body before
+/// it ends cancels the request.
+#[derive(Debug)]
+pub struct ResponseBody<Resp> {
+    partial_responses: futures::channel::mpsc::Receiver<Completion<Resp>>,
+    /// Receives the final response; None once it has been received.
+    response: Option<oneshot::Receiver<Completion<Resp>>>,
+    cancellation: RequestCancellation,
+    wire_id: Arc<WireId>,
+    /// Times the body as a whole.
+    timer: RequestTimer,
+}
+
+impl<Resp> Stream for ResponseBody<Resp> {
+    type Item = Result<Resp, RpcError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        if self.response.is_none() {
+            return Poll::Ready(None);
+        }
+        // Dispatch drops the sender of partial responses when the request completes, so every
+        // partial response is received before the final one.
+        if let Some((response, _)) = ready!(self.partial_responses.poll_next_unpin(cx)) {
+            return Poll::Ready(Some(response));
+        }
+        let completion = ready!(self.response.as_mut().unwrap().poll_unpin(cx));
+        self.response = None;
+        let response = match completion {
+            Ok((response, extensions)) => {
+                response_extensions::record(extensions);
+                response
+            }
+            Err(oneshot::error::RecvError { .. }) => Err(RpcError::Shutdown),
+        };
+        self.timer.finish(response.is_err());
+        Poll::Ready(Some(response))
+    }
+}
+
+// Cancels the request when dropped, if not already complete.
+impl<Resp> Drop for ResponseBody<Resp> {
+    fn drop(&mut self) {
+        if let Some(response) = &mut self.response {
+            // See ResponseGuard for why the receiver is closed before canceling.
+            response.close();
+            self.wire_id.cancel(&self.cancellation);
+        }
+    }
+}
+
+/// A request sent with [`Channel::call_cancelable`], which resolves to the response.
+///
+/// Dropping the call before it resolves cancels the request without waiting for the outcome, as
+/// dropping the future returned by [`Channel::call`] does.
+#[derive(Debug)]
+pub struct CancelableCall<Resp> {
+    /// Receives the response; None once it has been received.
+    response: Option<oneshot::Receiver<Completion<Resp>>>,
+    cancellation: RequestCancellation,
+    wire_id: Arc<WireId>,
+    timer: RequestTimer,
+}
+
+/// The outcome of a request canceled with [`CancelableCall::cancel`].
+#[derive(Debug)]
+pub enum CancelOutcome<Resp> {
+    /// The server aborted the request before sending its response. The handler was stopped at
+    /// its next await point, unless it had just completed, so its work may be partially done.
+    Aborted,
+    /// The request completed before the server received the cancellation, with this response or
+    /// error. Servers that don't acknowledge cancellations complete every canceled request with
+    /// [`DeadlineExceeded`](RpcError::DeadlineExceeded) once its deadline passes.
+    Completed(Result<Resp, RpcError>),
+}
+
+impl<Resp> CancelableCall<Resp> {
+    /// Cancels the request, asking the server to acknowledge it, then resolves to whether the
+    /// server aborted the request or had already completed it, in which case its response raced
+    /// the cancellation.
+    pub async fn cancel(mut self) -> CancelOutcome<Resp> {
+        let mut response = self.response.take().expect("polled after completion");
+        let completion = match response.try_recv() {
+            Ok(completion) => Some(completion),
+            Err(oneshot::error::TryRecvError::Empty) => {
+                // A request not yet written can't be canceled with an acknowledgement, so it's
+                // written and completed.
+                if let Some(wire_id) = self.wire_id.get() {
+                    self.cancellation.cancel_with_ack(wire_id);
+                }
+                response.await.ok()
+            }
+            Err(oneshot::error::TryRecvError::Closed) => None,
+        };
+        let response = match completion {
+            Some((Err(RpcError::Server(error)), _))
+                if error.reason == Some(ServerErrorReason::Canceled) =>
+            {
+                self.timer.finish(true);
+                return CancelOutcome::Aborted;
+            }
+            Some((response, extensions)) => {
+                response_extensions::record(extensions);
+                response
+            }
+            None => Err(RpcError::Shutdown),
+        };
+        self.timer.finish(response.is_err());
+        CancelOutcome::Completed(response)
+    }
+}
+
+impl<Resp> Future for CancelableCall<Resp> {
+    type Output = Result<Resp, RpcError>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let response = self.response.as_mut().expect("polled after completion");
+        let completion = ready!(response.poll_unpin(cx));
+        self.response = None;
+        let response = match completion {
+            Ok((response, extensions)) => {
+                response_extensions::record(extensions);
+                response
+            }
+            Err(oneshot::error::RecvError { .. }) => Err(RpcError::Shutdown),
+        };
+        self.timer.finish(response.is_err());
+        Poll::Ready(response)
+    }
+}
+
+// Cancels the request when dropped, if not already complete.
+impl<Resp> Drop for CancelableCall<Resp> {
+    fn drop(&mut self) {
+        if let Some(response) = &mut self.response {
+            // See ResponseGuard for why the receiver is closed before canceling.
+            response.close();
+            self.wire_id.cancel(&self.cancellation);
+        }
+    }
+}
+
 /// An error that can occur in the processing of an RPC. This is not request-specific errors but
 /// rather cross-cutting errors that can always occur.
 #[derive(thiserror::Error, Debug)]
@@ -190,11 +587,81 @@ pub enum RpcError {
     DeadlineExceeded,
     /// The server aborted request processing.
     #[error("the server aborted request processing")]
-    Server(#[from] ServerError),
+    Server(#[source] ServerError),
+    /// The request handler returned an error.
+    #[error("the request handler returned an error")]
+    Application(#[source] ApplicationError),
+}
+
+impl RpcError {
+    /// Returns how long the server suggested waiting before sending the request again, if the
+    /// request failed because the server was overloaded.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            RpcError::Server(error) => error.retry_after,
+            _ => None,
+        }
+    }
+
+    /// Returns why the server failed the request, if it failed the request itself for one of the
+    /// reasons a client may want to handle, e.g. by sending it to another server if the server is
+    /// [draining](ServerErrorReason::Draining).
+    pub fn server_error_reason(&self) -> Option<ServerErrorReason> {
+        match self {
+            RpcError::Server(error) => error.reason,
+            _ => None,
+        }
+    }
 }
 
+impl From<ServerError> for RpcError {
+    fn from(error: ServerError) -> Self {
+        match error.application {
+            Some(error) => RpcError::Application(error),
+            None => RpcError::Server(error),
+        }
+    }
+}
+
+/// An error returned by a client fn of a method returning `Result<T>`, for services that declare
+/// the error type of such methods with `type Error = E;`.
+#[derive(Debug)]
+pub enum CallError<E> {
+    /// The method returned an error.
+    Service(E),
+    /// The RPC failed.
+    Rpc(RpcError),
+}
+
+impl<E> From<RpcError> for CallError<E> {
+    fn from(error: RpcError) -> Self {
+        CallError::Rpc(error)
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for CallError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallError::Service(error) => error.fmt(f),
+            CallError::Rpc(error) => error.fmt(f),
+        }
+    }
+}
+
+impl<E: std::error::Error> std::error::Error for CallError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CallError::Service(error) => error.source(),
+            CallError::Rpc(error) => error.source(),
+        }
+    }
+}
+
+/// The result of a request, along with the extensions the server attached to its response.
+type Completion<Resp> = (Result<Resp, RpcError>, ResponseExtensions);
+
 impl<Resp> ResponseGuard<'_, Resp> {
-    async fn response(mut self) -> Result<Resp, RpcError> {
+    async fn response(mut self) -> Completion<Resp> {
         let response = (&mut self.response).await;
         // Cancel drop logic once a response has been received.
         self.cancel = false;
@@ -204,7 +671,7 @@ impl<Resp> ResponseGuard<'_, Resp> {
                 // The oneshot is Canceled when the dispatch task ends. In that case,
                 // there's nothing listening on the other side, so there's no point in
                 // propagating cancellation.
-                Err(RpcError::Shutdown)
+                (Err(RpcError::Shutdown), ResponseExtensions::default())
             }
         }
     }
@@ -225,7 +692,7 @@ impl<Resp> Drop for ResponseGuard<'_, Resp> {
         // receiver as closed.
         self.response.close();
         if self.cancel {
-            self.cancellation.cancel(self.request_id);
+            self.wire_id.cancel(self.cancellation);
         }
     }
 }
@@ -240,20 +707,34 @@ where
     C: Transport<ClientMessage<Req>, Response<Resp>>,
 {
     let (to_dispatch, pending_requests) = mpsc::channel(config.pending_request_buffer);
-    let (cancellation, canceled_requests) = cancellations();
+    let (cancellation, canceled_requests) = cancellations(config.in_flight_shards);
+    let stats = ChannelStats::default();
+    stats.set_role(Role::Client);
+    let next_request_id = Arc::new(AtomicU64::new(0));
 
     NewClient {
         client: Channel {
             to_dispatch,
             cancellation,
-            next_request_id: Arc::new(AtomicUsize::new(0)),
+            next_request_id: next_request_id.clone(),
+            stats: stats.clone(),
+            sampler: config.sampler.clone(),
+            peer_addr: config.peer_addr,
+            partial_response_buffer: config.partial_response_buffer,
         },
         dispatch: RequestDispatch {
+            flusher: Flusher::new(config.flush_policy),
+            yield_budget: YieldBudget::new(config.frames_per_yield),
+            in_flight_requests: InFlightRequests::with_shards(config.in_flight_shards)
+                .with_max_abandoned(config.max_abandoned_requests),
             config,
             canceled_requests,
+            pending_cancellations: VecDeque::new(),
             transport: transport.fuse(),
-            in_flight_requests: InFlightRequests::default(),
             pending_requests,
+            next_request_id,
+            parked_response: None,
+            stats,
         },
     }
 }
@@ -271,19 +752,37 @@ pub struct RequestDispatch<Req, Resp, C> {
     pending_requests: mpsc::Receiver<DispatchRequest<Req, Resp>>,
     /// Requests that were dropped.
     canceled_requests: CanceledRequests,
+    /// Cancellations of requests no longer in flight, waiting to be written to the wire.
+    pending_cancellations: VecDeque<Cancellation>,
     /// Requests already written to the wire that haven't yet received responses.
-    in_flight_requests: InFlightRequests<Result<Resp, RpcError>>,
+    in_flight_requests: InFlightRequests<Completion<Resp>>,
+    /// Hands out the IDs of the requests of the client's channels, shared with them.
+    next_request_id: Arc<AtomicU64>,
     /// Configures limits to prevent unlimited resource usage.
     config: Config,
+    /// Counts the traffic of the channel.
+    stats: ChannelStats,
+    /// Decides when to flush the messages written to the transport.
+    flusher: Flusher,
+    /// Makes the dispatch yield after reading and writing `frames_per_yield` messages.
+    yield_budget: YieldBudget,
+    /// A partial response whose body has no room for it. No more responses are read until the
+    /// body does.
+    parked_response: Option<Response<Resp>>,
 }
 
 impl<Req, Resp, C> RequestDispatch<Req, Resp, C>
 where
     C: Transport<ClientMessage<Req>, Response<Resp>>,
 {
+    /// Returns the counters of the channel's traffic, which are shared with its client.
+    pub fn stats(&self) -> &ChannelStats {
+        &self.stats
+    }
+
     fn in_flight_requests<'a>(
         self: &'a mut Pin<&mut Self>,
-    ) -> &'a mut InFlightRequests<Result<Resp, RpcError>> {
+    ) -> &'a mut InFlightRequests<Completion<Resp>> {
         self.as_mut().project().in_flight_requests
     }
 
@@ -306,16 +805,21 @@ where
     ) -> Result<(), ChannelError<C::Error>> {
         self.transport_pin_mut()
             .start_send(message)
-            .map_err(ChannelError::Write)
+            .map_err(ChannelError::Write)?;
+        self.as_mut().project().flusher.written();
+        Ok(())
     }
 
     fn poll_flush<'a>(
         self: &'a mut Pin<&mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<Result<(), ChannelError<C::Error>>> {
-        self.transport_pin_mut()
+        ready!(self
+            .transport_pin_mut()
             .poll_flush(cx)
-            .map_err(ChannelError::Flush)
+            .map_err(ChannelError::Flush))?;
+        self.as_mut().project().flusher.flushed();
+        Poll::Ready(Ok(()))
     }
 
     fn poll_close<'a>(
@@ -341,21 +845,41 @@ where
         mut self: Pin<&mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<Option<Result<(), ChannelError<C::Error>>>> {
+        if let Some(request_id) = self.parked_response.as_ref().map(|r| r.request_id) {
+            ready!(self
+                .in_flight_requests()
+                .poll_partial_response_ready(request_id, cx));
+            let response = self.as_mut().project().parked_response.take().unwrap();
+            self.complete(response);
+            return Poll::Ready(Some(Ok(())));
+        }
         self.transport_pin_mut()
             .poll_next(cx)
             .map_err(|e| {
                 let e = Arc::new(e);
-                for span in self
-                    .in_flight_requests()
-                    .complete_all_requests(|| Err(RpcError::Receive(e.clone())))
-                {
+                for span in self.in_flight_requests().complete_all_requests(|| {
+                    (
+                        Err(RpcError::Receive(e.clone())),
+                        ResponseExtensions::default(),
+                    )
+                }) {
+                    span.record("otel.status_code", "ERROR");
                     let _entered = span.enter();
                     tracing::info!("ReceiveError");
                 }
                 ChannelError::Read(e)
             })
             .map_ok(|response| {
-                self.complete(response);
+                if response.partial
+                    && self
+                        .in_flight_requests()
+                        .poll_partial_response_ready(response.request_id, cx)
+                        .is_pending()
+                {
+                    *self.as_mut().project().parked_response = Some(response);
+                } else {
+                    self.complete(response);
+                }
             })
     }
 
@@ -368,13 +892,19 @@ where
             Closed,
         }
 
-        let pending_requests_status = match self.as_mut().poll_write_request(cx)? {
+        if self.flusher.is_due() {
+            ready!(self.poll_flush(cx)?);
+        }
+
+        // Cancellations are written before queued requests, so that the server learns of
+        // abandoned requests promptly, however many requests are waiting to be sent.
+        let canceled_requests_status = match self.as_mut().poll_write_cancel(cx)? {
             Poll::Ready(Some(())) => return Poll::Ready(Some(Ok(()))),
             Poll::Ready(None) => ReceiverStatus::Closed,
             Poll::Pending => ReceiverStatus::Pending,
         };
 
-        let canceled_requests_status = match self.as_mut().poll_write_cancel(cx)? {
+        let pending_requests_status = match self.as_mut().poll_write_request(cx)? {
             Poll::Ready(Some(())) => return Poll::Ready(Some(Ok(()))),
             Poll::Ready(None) => ReceiverStatus::Closed,
             Poll::Pending => ReceiverStatus::Pending,
@@ -383,13 +913,16 @@ where
         // Receiving Poll::Ready(None) when polling expired requests never indicates "Closed",
         // because there can temporarily be zero in-flight rquests. Therefore, there is no need to
         // track the status like is done with pending and cancelled requests.
-        if let Poll::Ready(Some(_)) = self
-            .in_flight_requests()
-            .poll_expired(cx, || Err(RpcError::DeadlineExceeded))
-        {
+        if let Poll::Ready(Some(_)) = self.in_flight_requests().poll_expired(cx, || {
+            (
+                Err(RpcError::DeadlineExceeded),
+                ResponseExtensions::default(),
+            )
+        }) {
             // Expired requests are considered complete; there is no compelling reason to send a
             // cancellation message to the server, since it will have already exhausted its
             // allotted processing time.
+            self.stats.record_deadline_expiration();
             return Poll::Ready(Some(Ok(())));
         }
 
@@ -399,8 +932,11 @@ where
                 Poll::Ready(None)
             }
             (ReceiverStatus::Pending, _) | (_, ReceiverStatus::Pending) => {
-                // No more messages to process, so flush any messages buffered in the transport.
-                ready!(self.poll_flush(cx)?);
+                // No more messages to process, so flush any messages buffered in the transport,
+                // unless the flush policy waits for more.
+                if self.as_mut().project().flusher.poll_idle(cx).is_ready() {
+                    ready!(self.poll_flush(cx)?);
+                }
 
                 // Even if we fully-flush, we return Pending, because we have no more requests
                 // or cancellations right now.
@@ -434,7 +970,8 @@ where
         loop {
             match ready!(self.pending_requests_mut().poll_recv(cx)) {
                 Some(request) => {
-                    if request.response_completion.is_closed() {
+                    // Oneway requests have no response to await.
+                    if !request.oneway && request.response_completion.is_closed() {
                         let _entered = request.span.enter();
                         tracing::info!("AbortRequest");
                         continue;
@@ -447,27 +984,88 @@ where
         }
     }
 
-    /// Yields the next pending cancellation, and, if one is ready, cancels the associated request.
+    /// Cancels the requests canceled since the last call, queueing their cancellations to be
+    /// written to the wire, unless more than `max_pending_cancellations` are already queued.
+    /// Returns true iff no more requests will be canceled.
+    fn collect_cancellations(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> bool {
+        loop {
+            let (request_id, ack) = match self.canceled_requests_mut().poll_recv_with_ack(cx) {
+                Poll::Ready(Some(canceled)) => canceled,
+                Poll::Ready(None) => return true,
+                Poll::Pending => return false,
+            };
+            let canceled = if ack {
+                self.in_flight_requests()
+                    .cancel_request_with_ack(request_id)
+            } else {
+                self.in_flight_requests()
+                    .cancel_request(request_id)
+                    .map(|(ctx, span)| (ctx.trace_context, span))
+            };
+            let Some((trace_context, span)) = canceled else {
+                continue;
+            };
+            if !ack
+                && matches!(self.config.max_pending_cancellations,
+                    Some(max) if self.pending_cancellations.len() >= max)
+            {
+                let _entered = span.enter();
+                tracing::info!("AbandonCancellation");
+                continue;
+            }
+            self.as_mut()
+                .project()
+                .pending_cancellations
+                .push_back(Cancellation {
+                    trace_context,
+                    span,
+                    request_id,
+                    ack,
+                });
+        }
+    }
+
+    /// Returns an unused ID to send a request with instead of `request_id`, which is still in use
+    /// because the request IDs wrapped around.
+    fn reassign_request_id(self: &mut Pin<&mut Self>, request_id: u64) -> u64 {
+        let reassigned_id = loop {
+            let id = self.next_request_id.fetch_add(1, Ordering::Relaxed);
+            if !self.in_flight_requests.is_in_use(id) {
+                break id;
+            }
+        };
+        tracing::info!(request_id, reassigned_id, "ReassignRequestId");
+        reassigned_id
+    }
+
+    /// Yields the next cancellations to write in one frame: up to `max_cancellations_per_frame`
+    /// cancellations of dropped requests, or one that asks for an acknowledgement.
     ///
-    /// Note that a request to cancel will only be yielded if the transport is *ready* to be
-    /// written to (i.e.  start_send would succeed).
+    /// Note that cancellations will only be yielded if the transport is *ready* to be written to
+    /// (i.e.  start_send would succeed).
     fn poll_next_cancellation(
         mut self: Pin<&mut Self>,
         cx: &mut Context<'_>,
-    ) -> Poll<Option<Result<(context::Context, Span, u64), ChannelError<C::Error>>>> {
+    ) -> Poll<Option<Result<Vec<Cancellation>, ChannelError<C::Error>>>> {
+        let closed = self.as_mut().collect_cancellations(cx);
+        if self.pending_cancellations.is_empty() {
+            return if closed {
+                Poll::Ready(None)
+            } else {
+                Poll::Pending
+            };
+        }
         ready!(self.ensure_writeable(cx)?);
 
-        loop {
-            match ready!(self.canceled_requests_mut().poll_next_unpin(cx)) {
-                Some(request_id) => {
-                    if let Some((ctx, span)) = self.in_flight_requests().cancel_request(request_id)
-                    {
-                        return Poll::Ready(Some(Ok((ctx, span, request_id))));
-                    }
-                }
-                None => return Poll::Ready(None),
-            }
-        }
+        let max = self.config.max_cancellations_per_frame;
+        let pending_cancellations = self.as_mut().project().pending_cancellations;
+        let batch_len = pending_cancellations
+            .iter()
+            .take(max)
+            .take_while(|cancellation| !cancellation.ack)
+            .count()
+            .max(1);
+        Poll::Ready(Some(Ok(pending_cancellations.drain(..batch_len).collect())))
     }
 
     /// Returns Ready if writing a message to the transport (i.e. via write_request or
@@ -491,13 +1089,27 @@ where
             ctx,
             span,
             request_id,
+            wire_id,
             request,
             response_completion,
+            partial_responses,
+            oneway,
         } = match ready!(self.as_mut().poll_next_request(cx)?) {
             Some(dispatch_request) => dispatch_request,
             None => return Poll::Ready(None),
         };
         let _entered = span.enter();
+        let request_id = if !oneway && self.in_flight_requests().is_in_use(request_id) {
+            self.reassign_request_id(request_id)
+        } else {
+            request_id
+        };
+        wire_id.record(request_id);
+        // A caller that dropped the request before its ID was recorded didn't cancel it.
+        if !oneway && response_completion.is_closed() {
+            tracing::info!("AbortRequest");
+            return Poll::Ready(Some(Ok(())));
+        }
         // poll_next_request only returns Ready if there is room to buffer another request.
         // Therefore, we can call write_request without fear of erroring due to a full
         // buffer.
@@ -506,17 +1118,55 @@ where
             message: request,
             context: context::Context {
                 deadline: ctx.deadline,
-                trace_context: ctx.trace_context,
+                trace_context: ctx.trace_context.clone(),
+                idempotency_key: ctx.idempotency_key,
+                routing_key: ctx.routing_key,
+                baggage: ctx.baggage.clone(),
+                priority: if ctx.has_default_priority() {
+                    self.config.priority
+                } else {
+                    ctx.priority
+                },
+                credentials: ctx.credentials.clone(),
+                extensions: context::Extensions::default(),
+                default_deadline: None,
+                default_priority: None,
+                untraced: false,
             },
+            oneway,
         });
+        if oneway {
+            match self.start_send(request) {
+                Ok(()) => {
+                    tracing::info!("SendOnewayRequest");
+                    self.stats.record_request_sent();
+                }
+                Err(e) => tracing::warn!("Failed to send oneway request: {}", e),
+            }
+            return Poll::Ready(Some(Ok(())));
+        }
         self.in_flight_requests()
-            .insert_request(request_id, ctx, span.clone(), response_completion)
-            .expect("Request IDs should be unique");
+            .insert_request(
+                request_id,
+                ctx,
+                span.clone(),
+                response_completion,
+                partial_responses,
+            )
+            .expect("the request ID was checked to be unused");
         match self.start_send(request) {
-            Ok(()) => tracing::info!("SendRequest"),
+            Ok(()) => {
+                tracing::info!("SendRequest");
+                self.stats.record_request_sent();
+            }
             Err(e) => {
-                self.in_flight_requests()
-                    .complete_request(request_id, Err(RpcError::Send(Box::new(e))));
+                self.in_flight_requests().complete_request(
+                    request_id,
+                    (
+                        Err(RpcError::Send(Box::new(e))),
+                        ResponseExtensions::default(),
+                    ),
+                );
             }
         }
         Poll::Ready(Some(Ok(())))
@@ -526,29 +1176,75 @@ where
         self: &'a mut Pin<&mut Self>,
         cx: &mut Context<'_>,
     ) -> Poll<Option<Result<(), ChannelError<C::Error>>>> {
-        let (context, span, request_id) = match ready!(self.as_mut().poll_next_cancellation(cx)?) {
-            Some(triple) => triple,
+        let mut batch = match ready!(self.as_mut().poll_next_cancellation(cx)?) {
+            Some(batch) => batch,
             None => return Poll::Ready(None),
         };
-        let _entered = span.enter();
 
+        // Only requests whose response future or body was dropped, or that were canceled with
+        // CancelableCall::cancel, are canceled; expired requests are abandoned without telling the
+        // server.
+        if batch.len() > 1 {
+            let cancel = ClientMessage::CancelBatch {
+                request_ids: batch
+                    .iter()
+                    .map(|cancellation| cancellation.request_id)
+                    .collect(),
+                reason: CancellationReason::Dropped,
+            };
+            self.start_send(cancel)?;
+            for cancellation in batch {
+                let _entered = cancellation.span.enter();
+                tracing::info!("CancelRequest");
+                self.stats.record_cancellation();
+            }
+            return Poll::Ready(Some(Ok(())));
+        }
+
+        let Cancellation {
+            trace_context,
+            span,
+            request_id,
+            ack,
+        } = batch.remove(0);
+        let _entered = span.enter();
         let cancel = ClientMessage::Cancel {
-            trace_context: context.trace_context,
+            trace_context,
             request_id,
+            reason: if ack {
+                CancellationReason::Explicit
+            } else {
+                CancellationReason::Dropped
+            },
+            ack,
         };
         self.start_send(cancel)?;
         tracing::info!("CancelRequest");
+        self.stats.record_cancellation();
         Poll::Ready(Some(Ok(())))
     }
 
     /// Sends a server response to the client task that initiated the associated request.
     fn complete(mut self: Pin<&mut Self>, response: Response<Resp>) -> bool {
-        if let Some(span) = self.in_flight_requests().complete_request(
-            response.request_id,
-            response.message.map_err(RpcError::Server),
-        ) {
+        let failed = response.message.is_err();
+        let result = (
+            response.message.map_err(RpcError::from),
+            response.extensions,
+        );
+        let span = if response.partial {
+            self.in_flight_requests()
+                .send_partial_response(response.request_id, result)
+        } else {
+            self.in_flight_requests()
+                .complete_request(response.request_id, result)
+        };
+        if let Some(span) = span {
+            if failed {
+                span.record("otel.status_code", "ERROR");
+            }
             let _entered = span.enter();
             tracing::info!("ReceiveResponse");
+            self.stats.record_response_received();
             return true;
         }
         false
@@ -566,6 +1262,7 @@ where
         cx: &mut Context<'_>,
     ) -> Poll<Result<(), ChannelError<C::Error>>> {
         loop {
+            ready!(self.as_mut().project().yield_budget.poll_proceed(cx));
             match (self.as_mut().pump_read(cx)?, self.as_mut().pump_write(cx)?) {
                 (Poll::Ready(None), _) => {
                     tracing::info!("Shutdown: read half closed, so shutting down.");
@@ -582,47 +1279,103 @@ where
                     );
                     match read {
                         Poll::Ready(Some(())) => continue,
-                        _ => return Poll::Pending,
+                        _ => {
+                            self.as_mut().project().yield_budget.reset();
+                            return Poll::Pending;
+                        }
                     }
                 }
                 (Poll::Ready(Some(())), _) | (_, Poll::Ready(Some(()))) => {}
-                _ => return Poll::Pending,
+                _ => {
+                    self.as_mut().project().yield_budget.reset();
+                    return Poll::Pending;
+                }
             }
         }
     }
 }
 
+/// A request being canceled by request dispatch.
+#[derive(Debug)]
+struct Cancellation {
+    trace_context: trace::Context,
+    span: Span,
+    request_id: u64,
+    /// Whether the server should acknowledge the cancellation.
+    ack: bool,
+}
+
 /// A server-bound request sent from a [`Channel`] to request dispatch, which will then manage
 /// the lifecycle of the request.
 #[derive(Debug)]
 struct DispatchRequest<Req, Resp> {
     pub ctx: context::Context,
     pub span: Span,
+    /// The ID the request's channel gave it, which it's written with unless still in use.
     pub request_id: u64,
+    /// Records the ID the request is written with, by which its caller cancels it.
+    pub wire_id: Arc<WireId>,
     pub request: Req,
-    pub response_completion: oneshot::Sender<Result<Resp, RpcError>>,
+    pub response_completion: oneshot::Sender<Completion<Resp>>,
+    pub partial_responses: Option<futures::channel::mpsc::Sender<Completion<Resp>>>,
+    /// Set if the request expects no response, so it isn't tracked once written.
+    pub oneway: bool,
+}
+
+/// The ID a request was written to the wire with, recorded by the dispatch when it writes the
+/// request. It's the ID the request's channel gave it, unless that ID was still in use because
+/// request IDs wrapped around, so another request in flight may share the ID the channel gave it,
+/// but never the ID it was written with.
+#[derive(Debug, Default)]
+struct WireId(OnceLock<u64>);
+
+impl WireId {
+    /// Records the ID the request is written with. The dispatch then checks whether the caller
+    /// dropped the request, so that it doesn't write a request that its caller couldn't cancel.
+    fn record(&self, request_id: u64) {
+        let _ = self.0.set(request_id);
+        // Pairs with the fence in `get`: either the caller sees the ID, or the dispatch sees
+        // that the caller closed its receiver.
+        atomic::fence(Ordering::SeqCst);
+    }
+
+    /// Returns the ID the request was written with, or None if it's not yet written.
+    fn get(&self) -> Option<u64> {
+        atomic::fence(Ordering::SeqCst);
+        self.0.get().copied()
+    }
+
+    /// Cancels the request, if it was written. The caller must close its receiver first, so that
+    /// the dispatch doesn't write a request not yet written.
+    fn cancel(&self, cancellation: &RequestCancellation) {
+        if let Some(request_id) = self.get() {
+            cancellation.cancel(request_id);
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::{
-        cancellations, Channel, DispatchRequest, RequestDispatch, ResponseGuard, RpcError,
+        cancellations, Channel, Completion, DispatchRequest, RequestDispatch, ResponseGuard,
+        RpcError, WireId,
     };
     use crate::{
         client::{in_flight_requests::InFlightRequests, Config},
         context::{self, current},
-        transport::{self, channel::UnboundedChannel},
-        ChannelError, ClientMessage, Response,
+        stats::ChannelStats,
+        transport::{self, channel::UnboundedChannel, FlushPolicy, Flusher},
+        util::YieldBudget,
+        CancellationReason, ChannelError, ClientMessage, Request, Response,
     };
     use assert_matches::assert_matches;
     use futures::{prelude::*, task::*};
     use std::{
-        convert::TryFrom,
         fmt::Display,
         marker::PhantomData,
         pin::Pin,
         sync::{
-            atomic::{AtomicUsize, Ordering},
+            atomic::{AtomicU64, Ordering},
             Arc,
         },
     };
@@ -641,27 +1394,109 @@ mod tests {
 
         dispatch
             .in_flight_requests
-            .insert_request(0, context::current(), Span::current(), tx)
+            .insert_request(0, context::current(), Span::current(), tx, None)
             .unwrap();
         server_channel
             .send(Response {
                 request_id: 0,
                 message: Ok("Resp".into()),
+                extensions: Default::default(),
+                partial: false,
             })
             .await
             .unwrap();
         assert_matches!(dispatch.as_mut().poll(cx), Poll::Pending);
-        assert_matches!(rx.try_recv(), Ok(Ok(resp)) if resp == "Resp");
+        assert_matches!(rx.try_recv(), Ok((Ok(resp), _)) if resp == "Resp");
+    }
+
+    #[tokio::test]
+    async fn partial_responses_stream_into_body() {
+        let (mut dispatch, channel, mut server_channel) = set_up();
+        let cx = &mut Context::from_waker(noop_waker_ref());
+        let body = channel
+            .call_body(context::current(), "", "hi".into())
+            .await
+            .unwrap();
+        assert_matches!(dispatch.as_mut().poll(cx), Poll::Pending);
+        let request_id = match server_channel.next().await {
+            Some(Ok(ClientMessage::Request(request))) => request.id,
+            message => panic!("Unexpected message: {:?}", message),
+        };
+
+        for (message, partial) in [("a", true), ("b", true), ("c", false)] {
+            server_channel
+                .send(Response {
+                    request_id,
+                    message: Ok(message.into()),
+                    extensions: Default::default(),
+                    partial,
+                })
+                .await
+                .unwrap();
+        }
+        assert_matches!(dispatch.as_mut().poll(cx), Poll::Pending);
+        assert!(dispatch.in_flight_requests.is_empty());
+        let responses: Vec<_> = body.try_collect().await.unwrap();
+        assert_eq!(responses, ["a", "b", "c"]);
+    }
+
+    #[tokio::test]
+    async fn slow_bodies_stop_dispatch_reading_responses() {
+        let (mut dispatch, mut channel, mut server_channel) = set_up();
+        channel.partial_response_buffer = 2;
+        let cx = &mut Context::from_waker(noop_waker_ref());
+        let mut body = channel
+            .call_body(context::current(), "", "hi".into())
+            .await
+            .unwrap();
+        assert_matches!(dispatch.as_mut().poll(cx), Poll::Pending);
+        let request_id = match server_channel.next().await {
+            Some(Ok(ClientMessage::Request(request))) => request.id,
+            message => panic!("Unexpected message: {:?}", message),
+        };
+
+        let messages = ["a", "b", "c", "d", "e"];
+        for (i, message) in messages.into_iter().enumerate() {
+            server_channel
+                .send(Response {
+                    request_id,
+                    message: Ok(message.into()),
+                    extensions: Default::default(),
+                    partial: i + 1 < messages.len(),
+                })
+                .await
+                .unwrap();
+        }
+        // The body holds two responses, and the dispatch holds the third, leaving the rest unread.
+        assert_matches!(dispatch.as_mut().poll(cx), Poll::Pending);
+        assert_matches!(&dispatch.parked_response, Some(response) if response.message == Ok("c".into()));
+        assert_eq!(dispatch.stats().responses_received(), 2);
+
+        assert_eq!(body.next().await.unwrap().unwrap(), "a");
+        assert_matches!(dispatch.as_mut().poll(cx), Poll::Pending);
+        assert_matches!(&dispatch.parked_response, Some(response) if response.message == Ok("d".into()));
+
+        let mut responses = vec![];
+        loop {
+            let _ = dispatch.as_mut().poll(cx);
+            match body.next().now_or_never() {
+                Some(Some(response)) => responses.push(response.unwrap()),
+                Some(None) => break,
+                None => panic!("the body should be readable"),
+            }
+        }
+        assert_eq!(responses, ["b", "c", "d", "e"]);
+        assert!(dispatch.in_flight_requests.is_empty());
     }
 
     #[tokio::test]
     async fn dispatch_response_cancels_on_drop() {
-        let (cancellation, mut canceled_requests) = cancellations();
+        let (cancellation, mut canceled_requests) = cancellations(1);
         let (_, mut response) = oneshot::channel();
         drop(ResponseGuard::<u32> {
             response: &mut response,
             cancellation: &cancellation,
-            request_id: 3,
+            wire_id: written_with(3),
             cancel: true,
         });
         // resp's drop() is run, which should send a cancel message.
@@ -671,22 +1506,28 @@ mod tests {
 
     #[tokio::test]
     async fn dispatch_response_doesnt_cancel_after_complete() {
-        let (cancellation, mut canceled_requests) = cancellations();
+        let (cancellation, mut canceled_requests) = cancellations(1);
         let (tx, mut response) = oneshot::channel();
-        tx.send(Ok(Response {
-            request_id: 0,
-            message: Ok("well done"),
-        }))
+        tx.send((
+            Ok(Response {
+                request_id: 0,
+                message: Ok("well done"),
+                extensions: Default::default(),
+                partial: false,
+            }),
+            Default::default(),
+        ))
         .unwrap();
         // resp's drop() is run, but should not send a cancel message.
         ResponseGuard {
             response: &mut response,
             cancellation: &cancellation,
-            request_id: 3,
+            wire_id: written_with(3),
             cancel: true,
         }
         .response()
         .await
+        .0
         .unwrap();
         drop(cancellation);
         let cx = &mut Context::from_waker(noop_waker_ref());
@@ -726,6 +1567,8 @@ mod tests {
             Response {
                 request_id: 0,
                 message: Ok("hello".into()),
+                extensions: Default::default(),
+                partial: false,
             },
         )
         .await;
@@ -770,6 +1613,317 @@ mod tests {
         assert!(dispatch.in_flight_requests.is_empty());
     }
 
+    #[allow(unstable_name_collisions)]
+    #[tokio::test]
+    async fn dropped_requests_are_canceled_with_their_reason() {
+        let (mut dispatch, mut channel, mut server_channel) = set_up();
+        let cx = &mut Context::from_waker(noop_waker_ref());
+        let (tx, mut rx) = oneshot::channel();
+
+        let req = send_request(&mut channel, "hi", tx, &mut rx).await;
+        assert!(dispatch.as_mut().pump_write(cx).ready().is_some());
+        drop(req);
+        assert!(dispatch.as_mut().pump_write(cx).ready().is_some());
+
+        assert_matches!(
+            server_channel.next().await,
+            Some(Ok(ClientMessage::Request(_)))
+        );
+        assert_matches!(
+            server_channel.next().await,
+            Some(Ok(ClientMessage::Cancel {
+                reason: CancellationReason::Dropped,
+                ..
+            }))
+        );
+    }
+
+    #[allow(unstable_name_collisions)]
+    #[tokio::test]
+    async fn dropped_requests_are_canceled_in_batches() {
+        let (mut dispatch, mut channel, mut server_channel) = set_up();
+        dispatch.config.max_cancellations_per_frame = 3;
+        let cx = &mut Context::from_waker(noop_waker_ref());
+        let (txs, mut rxs): (Vec<_>, Vec<_>) = (0..4).map(|_| oneshot::channel()).unzip();
+
+        for (tx, rx) in txs.into_iter().zip(&mut rxs) {
+            let mut req = send_request(&mut channel, "hi", tx, rx).await;
+            req.cancel = false;
+            assert!(dispatch.as_mut().pump_write(cx).ready().is_some());
+        }
+        for request_id in 0..4 {
+            channel.cancellation.cancel(request_id);
+        }
+        assert!(dispatch.as_mut().pump_write(cx).ready().is_some());
+        assert!(dispatch.as_mut().pump_write(cx).ready().is_some());
+        assert!(dispatch.in_flight_requests.is_empty());
+
+        for _ in 0..4 {
+            assert_matches!(
+                server_channel.next().await,
+                Some(Ok(ClientMessage::Request(_)))
+            );
+        }
+        assert_matches!(
+            server_channel.next().await,
+            Some(Ok(ClientMessage::CancelBatch { request_ids, .. })) if request_ids == [0, 1, 2]
+        );
+        assert_matches!(
+            server_channel.next().await,
+            Some(Ok(ClientMessage::Cancel { request_id: 3, .. }))
+        );
+    }
+
+    #[allow(unstable_name_collisions)]
+    #[tokio::test]
+    async fn cancellations_over_the_limit_are_abandoned() {
+        let (mut dispatch, mut channel, mut server_channel) = set_up();
+        dispatch.config.max_pending_cancellations = Some(2);
+        let cx = &mut Context::from_waker(noop_waker_ref());
+        let (txs, mut rxs): (Vec<_>, Vec<_>) = (0..4).map(|_| oneshot::channel()).unzip();
+
+        for (tx, rx) in txs.into_iter().zip(&mut rxs) {
+            let mut req = send_request(&mut channel, "hi", tx, rx).await;
+            req.cancel = false;
+            assert!(dispatch.as_mut().pump_write(cx).ready().is_some());
+        }
+        for request_id in 0..4 {
+            channel.cancellation.cancel(request_id);
+        }
+        assert!(dispatch.as_mut().pump_write(cx).ready().is_some());
+        assert!(dispatch.as_mut().pump_write(cx).ready().is_some());
+        // The abandoned requests are no longer in flight, even though the server wasn't told.
+        assert!(dispatch.in_flight_requests.is_empty());
+        assert!(dispatch.pending_cancellations.is_empty());
+
+        for _ in 0..4 {
+            assert_matches!(
+                server_channel.next().await,
+                Some(Ok(ClientMessage::Request(_)))
+            );
+        }
+        for id in 0..2 {
+            assert_matches!(
+                server_channel.next().await,
+                Some(Ok(ClientMessage::Cancel { request_id, .. })) if request_id == id
+            );
+        }
+    }
+
+    #[allow(unstable_name_collisions)]
+    #[tokio::test]
+    async fn request_ids_wrap_around() {
+        let (mut dispatch, mut channel, _server_channel) = set_up();
+        let cx = &mut Context::from_waker(noop_waker_ref());
+        channel.next_request_id.store(u64::MAX, Ordering::Relaxed);
+
+        for expected in [u64::MAX, 0] {
+            let (tx, mut rx) = oneshot::channel();
+            let _resp = send_request(&mut channel, "hi", tx, &mut rx).await;
+            let req = dispatch.as_mut().poll_next_request(cx).ready().unwrap();
+            assert_eq!(req.request_id, expected);
+        }
+    }
+
+    #[allow(unstable_name_collisions)]
+    #[tokio::test]
+    async fn requests_reusing_the_id_of_a_request_in_flight_are_reassigned() {
+        let (mut dispatch, channel, mut server_channel) = set_up();
+        let cx = &mut Context::from_waker(noop_waker_ref());
+
+        let (mut first, _) = send_request_with_id(&channel, 0, "first").await;
+        assert!(dispatch.as_mut().pump_write(cx).ready().is_some());
+
+        // A long-lived request outlasts a full cycle of request IDs, so the request reusing its
+        // ID is sent with the next unused one.
+        let (mut reused, _) = send_request_with_id(&channel, 0, "reused").await;
+        assert!(dispatch.as_mut().pump_write(cx).ready().is_some());
+        assert_matches!(
+            server_channel.next().await,
+            Some(Ok(ClientMessage::Request(Request { id: 0, message, .. }))) if message == "first"
+        );
+        assert_matches!(
+            server_channel.next().await,
+            Some(Ok(ClientMessage::Request(Request { id: 1, message, .. }))) if message == "reused"
+        );
+
+        // Each response reaches its own caller.
+        send_response(&mut server_channel, response_to(1, "reused")).await;
+        send_response(&mut server_channel, response_to(0, "first")).await;
+        assert!(dispatch.as_mut().pump_read(cx).ready().is_some());
+        assert!(dispatch.as_mut().pump_read(cx).ready().is_some());
+        assert_matches!(reused.try_recv(), Ok((Ok(resp), _)) if resp == "reused");
+        assert_matches!(first.try_recv(), Ok((Ok(resp), _)) if resp == "first");
+    }
+
+    #[allow(unstable_name_collisions)]
+    #[tokio::test]
+    async fn requests_sharing_an_id_are_canceled_by_the_ids_they_were_written_with() {
+        let (mut dispatch, channel, mut server_channel) = set_up();
+        let cx = &mut Context::from_waker(noop_waker_ref());
+
+        let (_first, first_id) = send_request_with_id(&channel, 0, "first").await;
+        assert!(dispatch.as_mut().pump_write(cx).ready().is_some());
+        assert_eq!(first_id.get(), Some(0));
+
+        // A request reusing the ID is dropped before it's written, so its caller doesn't cancel
+        // the first request, and the dispatch doesn't write it.
+        let (mut dropped, dropped_id) = send_request_with_id(&channel, 0, "dropped").await;
+        dropped.close();
+        dropped_id.cancel(&channel.cancellation);
+        let _ = dispatch.as_mut().pump_write(cx);
+        assert_eq!(dropped_id.get(), None);
+        assert_eq!(dispatch.in_flight_requests.len(), 1);
+
+        let (mut reused, reused_id) = send_request_with_id(&channel, 0, "reused").await;
+        assert!(dispatch.as_mut().pump_write(cx).ready().is_some());
+        assert_eq!(reused_id.get(), Some(1));
+
+        // Both callers still wait for their responses when the first request is canceled.
+        channel
+            .cancellation
+            .cancel_with_ack(first_id.get().unwrap());
+        assert!(dispatch.as_mut().pump_write(cx).ready().is_some());
+        reused.close();
+        reused_id.cancel(&channel.cancellation);
+        assert!(dispatch.as_mut().pump_write(cx).ready().is_some());
+        for expected in ["first", "reused"] {
+            assert_matches!(
+                server_channel.next().await,
+                Some(Ok(ClientMessage::Request(Request { message, .. }))) if message == expected
+            );
+        }
+        assert_matches!(
+            server_channel.next().await,
+            Some(Ok(ClientMessage::Cancel {
+                request_id: 0,
+                ack: true,
+                ..
+            }))
+        );
+        assert_matches!(
+            server_channel.next().await,
+            Some(Ok(ClientMessage::Cancel {
+                request_id: 1,
+                ack: false,
+                ..
+            }))
+        );
+        assert!(dispatch.in_flight_requests.contains(0));
+        assert!(!dispatch.in_flight_requests.contains(1));
+    }
+
+    #[allow(unstable_name_collisions)]
+    #[tokio::test]
+    async fn stale_responses_to_abandoned_requests_are_dropped() {
+        let (mut dispatch, mut channel, mut server_channel) = set_up();
+        dispatch.in_flight_requests = InFlightRequests::default().with_max_abandoned(10);
+        let cx = &mut Context::from_waker(noop_waker_ref());
+
+        // The first request is canceled, but the server responds before reading the cancellation.
+        let (tx, mut rx) = oneshot::channel();
+        let abandoned = send_request(&mut channel, "abandoned", tx, &mut rx).await;
+        assert!(dispatch.as_mut().pump_write(cx).ready().is_some());
+        drop(abandoned);
+        assert!(dispatch.as_mut().pump_write(cx).ready().is_some());
+        assert!(dispatch.in_flight_requests.is_empty());
+
+        // Until the stale response arrives, a request reusing the ID is sent with another.
+        let (mut reassigned, _) = send_request_with_id(&channel, 0, "reassigned").await;
+        assert!(dispatch.as_mut().pump_write(cx).ready().is_some());
+        assert!(dispatch.in_flight_requests.contains(1));
+
+        send_response(&mut server_channel, response_to(0, "stale")).await;
+        assert!(dispatch.as_mut().pump_read(cx).ready().is_some());
+        assert_matches!(reassigned.try_recv(), Err(_));
+
+        // Once it has, the ID is free, and the response to the new request reaches its caller.
+        let (mut reused, _) = send_request_with_id(&channel, 0, "reused").await;
+        assert!(dispatch.as_mut().pump_write(cx).ready().is_some());
+        assert!(dispatch.in_flight_requests.contains(0));
+        send_response(&mut server_channel, response_to(0, "fresh")).await;
+        assert!(dispatch.as_mut().pump_read(cx).ready().is_some());
+        assert_matches!(reused.try_recv(), Ok((Ok(resp), _)) if resp == "fresh");
+        send_response(&mut server_channel, response_to(1, "reassigned")).await;
+        assert!(dispatch.as_mut().pump_read(cx).ready().is_some());
+        assert_matches!(reassigned.try_recv(), Ok((Ok(resp), _)) if resp == "reassigned");
+    }
+
+    #[tokio::test]
+    async fn dispatch_yields_after_frames_per_yield() {
+        let (mut dispatch, mut channel, mut server_channel) = set_up();
+        dispatch.yield_budget = YieldBudget::new(Some(1));
+        let (waker, wakes) = futures_test::task::new_count_waker();
+        let cx = &mut Context::from_waker(&waker);
+        let (tx, mut rx) = oneshot::channel();
+
+        let _req = send_request(&mut channel, "hi", tx, &mut rx).await;
+        // Writing the request uses up the budget, so the dispatch yields before looking for more.
+        assert!(dispatch.as_mut().poll(cx).is_pending());
+        assert_eq!(wakes.get(), 1);
+        assert_matches!(
+            server_channel.next().await,
+            Some(Ok(ClientMessage::Request(request))) if request.message == "hi"
+        );
+    }
+
+    #[allow(unstable_name_collisions)]
+    #[tokio::test]
+    async fn cancellations_are_sent_before_queued_requests() {
+        let (mut dispatch, mut channel, mut server_channel) = set_up();
+        let cx = &mut Context::from_waker(noop_waker_ref());
+        let (tx, mut rx) = oneshot::channel();
+        let (queued_tx, mut queued_rx) = oneshot::channel();
+        let mut queued_channel = channel.clone();
+
+        let req = send_request(&mut channel, "hi", tx, &mut rx).await;
+        assert!(dispatch.as_mut().pump_write(cx).ready().is_some());
+        let _queued = send_request(&mut queued_channel, "queued", queued_tx, &mut queued_rx).await;
+        drop(req);
+        assert!(dispatch.as_mut().pump_write(cx).ready().is_some());
+        assert!(dispatch.as_mut().pump_write(cx).ready().is_some());
+
+        assert_matches!(
+            server_channel.next().await,
+            Some(Ok(ClientMessage::Request(request))) if request.id == 0
+        );
+        assert_matches!(
+            server_channel.next().await,
+            Some(Ok(ClientMessage::Cancel { request_id: 0, .. }))
+        );
+        assert_matches!(
+            server_channel.next().await,
+            Some(Ok(ClientMessage::Request(request))) if request.id == 1
+        );
+    }
+
+    #[allow(unstable_name_collisions)]
+    #[tokio::test]
+    async fn dispatch_counts_traffic() {
+        let (mut dispatch, mut channel, mut server_channel) = set_up();
+        let cx = &mut Context::from_waker(noop_waker_ref());
+        let stats = channel.stats().clone();
+        let (tx, mut rx) = oneshot::channel();
+
+        let _resp = send_request(&mut channel, "hi", tx, &mut rx).await;
+        assert!(dispatch.as_mut().pump_write(cx).ready().is_some());
+        assert_eq!(stats.requests_sent(), 1);
+
+        server_channel
+            .send(Response {
+                request_id: 0,
+                message: Ok("Resp".into()),
+                extensions: Default::default(),
+                partial: false,
+            })
+            .await
+            .unwrap();
+        assert_matches!(dispatch.as_mut().poll(cx), Poll::Pending);
+        assert_eq!(stats.responses_received(), 1);
+        assert_eq!(stats.cancellations(), 0);
+        assert_eq!(dispatch.stats().requests_sent(), 1);
+    }
+
     #[tokio::test]
     async fn stage_request_response_closed_skipped() {
         let (mut dispatch, mut channel, _server_channel) = set_up();
@@ -785,6 +1939,39 @@ mod tests {
         assert!(dispatch.as_mut().poll_next_request(cx).is_pending());
     }
 
+    #[test]
+    fn untraced_requests_skip_their_spans() {
+        let mut ctx = context::current();
+        tracing::subscriber::with_default(tracing_subscriber::registry(), || {
+            assert!(!Channel::<String, String>::span(&ctx, "hi", None).is_none());
+            ctx.skip_trace();
+            assert!(Channel::<String, String>::span(&ctx, "hi", None).is_none());
+        });
+    }
+
+    #[test]
+    fn spans_follow_the_rpc_semantic_conventions() {
+        let ctx = context::current();
+        tracing::subscriber::with_default(tracing_subscriber::registry(), || {
+            let span = Channel::<String, String>::span(
+                &ctx,
+                "World.hello",
+                Some(([127, 0, 0, 1], 8080).into()),
+            );
+            let fields = span.metadata().unwrap().fields();
+            for field in [
+                "rpc.system",
+                "rpc.service",
+                "rpc.method",
+                "server.address",
+                "server.port",
+                "otel.status_code",
+            ] {
+                assert!(fields.field(field).is_some(), "missing {field}");
+            }
+        });
+    }
+
     #[tokio::test]
     async fn test_shutdown_error() {
         let _ = tracing_subscriber::fmt().with_test_writer().try_init();
@@ -794,7 +1981,7 @@ mod tests {
         let resp = send_request(&mut channel, "hi", tx, &mut rx).await;
         drop(dispatch);
         // error on receive
-        assert_matches!(resp.response().await, Err(RpcError::Shutdown));
+        assert_matches!(resp.response().await.0, Err(RpcError::Shutdown));
         let (dispatch, channel, _) = set_up();
         drop(dispatch);
         // error on send
@@ -812,7 +1999,7 @@ mod tests {
 
         let resp = send_request(&mut channel, "hi", tx, &mut rx).await;
         assert!(dispatch.as_mut().poll(&mut cx).is_pending());
-        let res = resp.response().await;
+        let res = resp.response().await.0;
         assert_matches!(res, Err(RpcError::Send(_)));
         let client_error: anyhow::Error = res.unwrap_err().into();
         let mut chain = client_error.chain();
@@ -844,7 +2031,7 @@ mod tests {
             dispatch.as_mut().pump_read(&mut cx),
             Poll::Ready(Some(Err(ChannelError::Read(Arc::new(cause)))))
         );
-        assert_matches!(resp.response().await, Err(RpcError::Receive(_)));
+        assert_matches!(resp.response().await.0, Err(RpcError::Receive(_)));
     }
 
     #[tokio::test]
@@ -886,19 +2073,29 @@ mod tests {
         Context<'static>,
     ) {
         let (to_dispatch, pending_requests) = mpsc::channel(1);
-        let (cancellation, canceled_requests) = cancellations();
+        let (cancellation, canceled_requests) = cancellations(1);
         let transport: AlwaysErrorTransport<String> = AlwaysErrorTransport(cause, PhantomData);
         let dispatch = Box::pin(RequestDispatch::<String, String, _> {
             transport: transport.fuse(),
             pending_requests,
             canceled_requests,
+            pending_cancellations: Default::default(),
             in_flight_requests: InFlightRequests::default(),
             config: Config::default(),
+            stats: ChannelStats::default(),
+            flusher: Flusher::new(FlushPolicy::default()),
+            yield_budget: YieldBudget::new(None),
+            next_request_id: Arc::new(AtomicU64::new(0)),
+            parked_response: None,
         });
         let channel = Channel {
             to_dispatch,
             cancellation,
-            next_request_id: Arc::new(AtomicUsize::new(0)),
+            next_request_id: dispatch.next_request_id.clone(),
+            stats: dispatch.stats.clone(),
+            sampler: None,
+            peer_addr: None,
+            partial_response_buffer: 16,
         };
         let cx = Context::from_waker(noop_waker_ref());
         (dispatch, channel, cx)
@@ -980,21 +2177,31 @@ mod tests {
         let _ = tracing_subscriber::fmt().with_test_writer().try_init();
 
         let (to_dispatch, pending_requests) = mpsc::channel(1);
-        let (cancellation, canceled_requests) = cancellations();
+        let (cancellation, canceled_requests) = cancellations(1);
         let (client_channel, server_channel) = transport::channel::unbounded();
 
         let dispatch = RequestDispatch::<String, String, _> {
             transport: client_channel.fuse(),
             pending_requests,
             canceled_requests,
+            pending_cancellations: Default::default(),
             in_flight_requests: InFlightRequests::default(),
             config: Config::default(),
+            stats: ChannelStats::default(),
+            flusher: Flusher::new(FlushPolicy::default()),
+            yield_budget: YieldBudget::new(None),
+            next_request_id: Arc::new(AtomicU64::new(0)),
+            parked_response: None,
         };
 
         let channel = Channel {
             to_dispatch,
             cancellation,
-            next_request_id: Arc::new(AtomicUsize::new(0)),
+            next_request_id: dispatch.next_request_id.clone(),
+            stats: dispatch.stats.clone(),
+            sampler: None,
+            peer_addr: None,
+            partial_response_buffer: 16,
         };
 
         (Box::pin(dispatch), channel, server_channel)
@@ -1003,28 +2210,69 @@ mod tests {
     async fn send_request<'a>(
         channel: &'a mut Channel<String, String>,
         request: &str,
-        response_completion: oneshot::Sender<Result<String, RpcError>>,
-        response: &'a mut oneshot::Receiver<Result<String, RpcError>>,
+        response_completion: oneshot::Sender<Completion<String>>,
+        response: &'a mut oneshot::Receiver<Completion<String>>,
     ) -> ResponseGuard<'a, String> {
-        let request_id =
-            u64::try_from(channel.next_request_id.fetch_add(1, Ordering::Relaxed)).unwrap();
+        let request_id = channel.next_request_id.fetch_add(1, Ordering::Relaxed);
+        let wire_id = Arc::new(WireId::default());
         let request = DispatchRequest {
             ctx: context::current(),
             span: Span::current(),
             request_id,
+            wire_id: wire_id.clone(),
             request: request.to_string(),
             response_completion,
+            partial_responses: None,
+            oneway: false,
         };
         let response_guard = ResponseGuard {
             response,
             cancellation: &channel.cancellation,
-            request_id,
+            wire_id,
             cancel: true,
         };
         channel.to_dispatch.send(request).await.unwrap();
         response_guard
     }
 
+    /// Sends a request with the ID `request_id` to the dispatch, without canceling it when the
+    /// returned receiver is dropped. Also returns the ID the request is written with.
+    async fn send_request_with_id(
+        channel: &Channel<String, String>,
+        request_id: u64,
+        request: &str,
+    ) -> (oneshot::Receiver<Completion<String>>, Arc<WireId>) {
+        let (response_completion, response) = oneshot::channel();
+        let wire_id = Arc::new(WireId::default());
+        let request = DispatchRequest {
+            ctx: context::current(),
+            span: Span::current(),
+            request_id,
+            wire_id: wire_id.clone(),
+            request: request.to_string(),
+            response_completion,
+            partial_responses: None,
+            oneway: false,
+        };
+        channel.to_dispatch.send(request).await.unwrap();
+        (response, wire_id)
+    }
+
+    fn written_with(request_id: u64) -> Arc<WireId> {
+        let wire_id = Arc::new(WireId::default());
+        wire_id.record(request_id);
+        wire_id
+    }
+
+    fn response_to(request_id: u64, message: &str) -> Response<String> {
+        Response {
+            request_id,
+            message: Ok(message.into()),
+            extensions: Default::default(),
+            partial: false,
+        }
+    }
+
     async fn send_response(
         channel: &mut UnboundedChannel<ClientMessage<String>, Response<String>>,
         response: Response<String>,
@@ -1034,7 +2282,6 @@ mod tests {
 
     trait PollTest {
         type T;
-        fn unwrap(self) -> Poll<Self::T>;
         fn ready(self) -> Self::T;
     }
 
@@ -1044,15 +2291,6 @@ mod tests {
     {
         type T = Option<T>;
 
-        fn unwrap(self) -> Poll<Option<T>> {
-            match self {
-                Poll::Ready(Some(Ok(t))) => Poll::Ready(Some(t)),
-                Poll::Ready(None) => Poll::Ready(None),
-                Poll::Ready(Some(Err(e))) => panic!("{}", e.to_string()),
-                Poll::Pending => Poll::Pending,
-            }
-        }
-
         fn ready(self) -> Option<T> {
             match self {
                 Poll::Ready(Some(Ok(t))) => Some(t),
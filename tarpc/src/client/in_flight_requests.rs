@@ -1,10 +1,14 @@
 use crate::{
-    context,
-    util::{Compact, TimeUntil},
+    context, trace,
+    util::{
+        request_map::{self, RequestMap},
+        Compact,
+    },
 };
-use fnv::FnvHashMap;
+use fnv::FnvHashSet;
+use futures::channel::mpsc;
 use std::{
-    collections::hash_map,
+    collections::VecDeque,
     task::{Context, Poll},
 };
 use tokio::sync::oneshot;
@@ -12,18 +16,38 @@ use tokio_util::time::delay_queue::{self, DelayQueue};
 use tracing::Span;
 
 /// Requests already written to the wire that haven't yet received responses.
+///
+/// Requests are split into shards by ID, each tracking the deadlines of its own requests, so that
+/// channels with very many requests in flight don't keep them all in one map and timer queue.
+///
+/// The IDs of the most recent requests abandoned while in flight, by expiring or by being canceled
+/// without an acknowledgement, are remembered until the server responds to them, so that a stale
+/// response isn't delivered to a later request reusing the ID once request IDs wrap around.
 #[derive(Debug)]
 pub struct InFlightRequests<Resp> {
-    request_data: FnvHashMap<u64, RequestData<Resp>>,
+    shards: Vec<Shard<Resp>>,
+    /// The shard whose expired requests are yielded first, so that no shard is starved.
+    next_expired: usize,
+    /// The IDs of abandoned requests, oldest first, which may still receive responses. IDs freed
+    /// by a response stay queued until most of the queue is freed.
+    abandoned: VecDeque<u64>,
+    abandoned_ids: FnvHashSet<u64>,
+    /// The most abandoned request IDs remembered.
+    max_abandoned: usize,
+}
+
+/// The requests in flight of one shard.
+#[derive(Debug)]
+struct Shard<Resp> {
+    /// Keyed by the requests' IDs within the shard.
+    request_data: RequestMap<RequestData<Resp>>,
+    /// Holds the requests' full IDs.
     deadlines: DelayQueue<u64>,
 }
 
 impl<Resp> Default for InFlightRequests<Resp> {
     fn default() -> Self {
-        Self {
-            request_data: Default::default(),
-            deadlines: Default::default(),
-        }
+        Self::with_shards(1)
     }
 }
 
@@ -32,6 +56,8 @@ struct RequestData<Res> {
     ctx: context::Context,
     span: Span,
     response_completion: oneshot::Sender<Res>,
+    /// Receives the partial responses to the request, if the caller expects a body.
+    partial_responses: Option<mpsc::Sender<Res>>,
     /// The key to remove the timer for the request's deadline.
     deadline_key: delay_queue::Key,
 }
@@ -42,14 +68,92 @@ struct RequestData<Res> {
 pub struct AlreadyExistsError;
 
 impl<Res> InFlightRequests<Res> {
+    /// Returns no in-flight requests, split into `shards` shards.
+    pub fn with_shards(shards: usize) -> Self {
+        Self {
+            shards: (0..shards.max(1))
+                .map(|_| Shard {
+                    request_data: RequestMap::default(),
+                    deadlines: DelayQueue::default(),
+                })
+                .collect(),
+            next_expired: 0,
+            abandoned: VecDeque::new(),
+            abandoned_ids: FnvHashSet::default(),
+            max_abandoned: 0,
+        }
+    }
+
+    /// Remembers the IDs of the last `max` abandoned requests, rather than none.
+    pub fn with_max_abandoned(mut self, max: usize) -> Self {
+        self.max_abandoned = max;
+        self
+    }
+
+    /// Returns the shard of `request_id` and the request's ID within it.
+    fn shard_mut(&mut self, request_id: u64) -> (&mut Shard<Res>, u64) {
+        let (shard, key) = request_map::shard(request_id, self.shards.len());
+        (&mut self.shards[shard], key)
+    }
+
     /// Returns the number of in-flight requests.
     pub fn len(&self) -> usize {
-        self.request_data.len()
+        self.shards
+            .iter()
+            .map(|shard| shard.request_data.len())
+            .sum()
     }
 
     /// Returns true iff there are no requests in flight.
     pub fn is_empty(&self) -> bool {
-        self.request_data.is_empty()
+        self.shards
+            .iter()
+            .all(|shard| shard.request_data.is_empty())
+    }
+
+    /// Returns true iff the request `request_id` is in flight.
+    pub fn contains(&self, request_id: u64) -> bool {
+        let (shard, key) = request_map::shard(request_id, self.shards.len());
+        self.shards[shard].request_data.contains_key(key)
+    }
+
+    /// Returns true iff `request_id` is in use, by a request in flight or by an abandoned request
+    /// that may still receive a response.
+    pub fn is_in_use(&self, request_id: u64) -> bool {
+        self.contains(request_id)
+            || (!self.abandoned_ids.is_empty() && self.abandoned_ids.contains(&request_id))
+    }
+
+    /// Remembers that the request `request_id` was abandoned, forgetting the oldest abandoned
+    /// request if too many are remembered.
+    fn abandon(&mut self, request_id: u64) {
+        if self.max_abandoned == 0 || !self.abandoned_ids.insert(request_id) {
+            return;
+        }
+        self.abandoned.push_back(request_id);
+        while self.abandoned_ids.len() > self.max_abandoned {
+            match self.abandoned.pop_front() {
+                Some(oldest) => self.abandoned_ids.remove(&oldest),
+                None => break,
+            };
+        }
+    }
+
+    /// Drops a response to a request that isn't in flight. The final response to an abandoned
+    /// request frees its ID.
+    fn drop_response(&mut self, request_id: u64, partial: bool) {
+        if !self.abandoned_ids.contains(&request_id) {
+            tracing::debug!("No in-flight request found for request_id = {request_id}.");
+            return;
+        }
+        tracing::debug!(request_id, "DropStaleResponse");
+        if !partial {
+            self.abandoned_ids.remove(&request_id);
+            if self.abandoned.len() > 2 * self.abandoned_ids.len() {
+                let abandoned_ids = &self.abandoned_ids;
+                self.abandoned.retain(|id| abandoned_ids.contains(id));
+            }
+        }
     }
 
     /// Starts a request, unless a request with the same ID is already in flight.
@@ -59,61 +163,120 @@ impl<Res> InFlightRequests<Res> {
         ctx: context::Context,
         span: Span,
         response_completion: oneshot::Sender<Res>,
+        partial_responses: Option<mpsc::Sender<Res>>,
     ) -> Result<(), AlreadyExistsError> {
-        match self.request_data.entry(request_id) {
-            hash_map::Entry::Vacant(vacant) => {
-                let timeout = ctx.deadline.time_until();
-                let deadline_key = self.deadlines.insert(request_id, timeout);
-                vacant.insert(RequestData {
-                    ctx,
-                    span,
-                    response_completion,
-                    deadline_key,
-                });
-                Ok(())
-            }
-            hash_map::Entry::Occupied(_) => Err(AlreadyExistsError),
+        let (shard, key) = self.shard_mut(request_id);
+        if shard.request_data.contains_key(key) {
+            return Err(AlreadyExistsError);
         }
+        let timeout = ctx.time_remaining();
+        let deadline_key = shard.deadlines.insert(request_id, timeout);
+        shard.request_data.insert(
+            key,
+            RequestData {
+                ctx,
+                span,
+                response_completion,
+                partial_responses,
+                deadline_key,
+            },
+        );
+        Ok(())
     }
 
     /// Removes a request without aborting. Returns true iff the request was found.
     pub fn complete_request(&mut self, request_id: u64, result: Res) -> Option<Span> {
-        if let Some(request_data) = self.request_data.remove(&request_id) {
-            self.request_data.compact(0.1);
-            self.deadlines.remove(&request_data.deadline_key);
+        let (shard, key) = self.shard_mut(request_id);
+        if let Some(request_data) = shard.request_data.remove(key) {
+            shard.request_data.compact(0.1);
+            shard.deadlines.remove(&request_data.deadline_key);
             let _ = request_data.response_completion.send(result);
             return Some(request_data.span);
         }
 
-        tracing::debug!("No in-flight request found for request_id = {request_id}.");
-
         // If the response completion was absent, then the request was already canceled.
+        self.drop_response(request_id, false);
         None
     }
 
+    /// Polls for room for another partial response in the body of request `request_id`. Ready
+    /// unless the caller expects a body and hasn't yet read enough of the partial responses
+    /// already received.
+    pub fn poll_partial_response_ready(&mut self, request_id: u64, cx: &mut Context) -> Poll<()> {
+        let (shard, key) = self.shard_mut(request_id);
+        match shard
+            .request_data
+            .get_mut(key)
+            .and_then(|request_data| request_data.partial_responses.as_mut())
+        {
+            // A closed body is ready: the response is dropped along with the request.
+            Some(partial_responses) => partial_responses.poll_ready(cx).map(|_| ()),
+            None => Poll::Ready(()),
+        }
+    }
+
+    /// Sends a partial response to the caller of a request, leaving the request in flight. If the
+    /// caller doesn't expect a body, completes the request instead.
+    ///
+    /// The body must have room for the response, per
+    /// [`poll_partial_response_ready`](Self::poll_partial_response_ready); otherwise, the response
+    /// is dropped.
+    pub fn send_partial_response(&mut self, request_id: u64, result: Res) -> Option<Span> {
+        let (shard, key) = self.shard_mut(request_id);
+        match shard.request_data.get_mut(key) {
+            Some(request_data) => {
+                if let Some(partial_responses) = &mut request_data.partial_responses {
+                    let _ = partial_responses.try_send(result);
+                    return Some(request_data.span.clone());
+                }
+            }
+            None => {
+                self.drop_response(request_id, true);
+                return None;
+            }
+        }
+        self.complete_request(request_id, result)
+    }
+
     /// Completes all requests using the provided function.
     /// Returns Spans for all completes requests.
     pub fn complete_all_requests<'a>(
         &'a mut self,
         mut result: impl FnMut() -> Res + 'a,
     ) -> impl Iterator<Item = Span> + 'a {
-        self.deadlines.clear();
-        self.request_data.drain().map(move |(_, request_data)| {
-            let _ = request_data.response_completion.send(result());
-            request_data.span
-        })
+        self.shards
+            .iter_mut()
+            .flat_map(move |shard| {
+                shard.deadlines.clear();
+                shard.request_data.drain()
+            })
+            .map(move |request_data| {
+                let _ = request_data.response_completion.send(result());
+                request_data.span
+            })
     }
 
     /// Cancels a request without completing (typically used when a request handle was dropped
     /// before the request completed).
     pub fn cancel_request(&mut self, request_id: u64) -> Option<(context::Context, Span)> {
-        if let Some(request_data) = self.request_data.remove(&request_id) {
-            self.request_data.compact(0.1);
-            self.deadlines.remove(&request_data.deadline_key);
-            Some((request_data.ctx, request_data.span))
-        } else {
-            None
-        }
+        let (shard, key) = self.shard_mut(request_id);
+        let request_data = shard.request_data.remove(key)?;
+        shard.request_data.compact(0.1);
+        shard.deadlines.remove(&request_data.deadline_key);
+        self.abandon(request_id);
+        Some((request_data.ctx, request_data.span))
+    }
+
+    /// Returns the trace context and span of a request being canceled with an acknowledgement. The
+    /// request stays in flight, so that it's completed by its response or by the acknowledgement,
+    /// whichever the server sends.
+    pub fn cancel_request_with_ack(&mut self, request_id: u64) -> Option<(trace::Context, Span)> {
+        let (shard, key) = self.shard_mut(request_id);
+        let request_data = shard.request_data.get(key)?;
+        Some((
+            request_data.ctx.trace_context.clone(),
+            request_data.span.clone(),
+        ))
     }
 
     /// Yields a request that has expired, completing it with a TimedOut error.
@@ -122,10 +285,43 @@ impl<Res> InFlightRequests<Res> {
         &mut self,
         cx: &mut Context,
         expired_error: impl Fn() -> Res,
+    ) -> Poll<Option<u64>> {
+        let shards = self.shards.len();
+        let mut pending = false;
+        for i in 0..shards {
+            let shard = (self.next_expired + i) % shards;
+            match self.shards[shard].poll_expired(cx, shards, &expired_error) {
+                Poll::Ready(Some(request_id)) => {
+                    self.next_expired = (shard + 1) % shards;
+                    self.abandon(request_id);
+                    return Poll::Ready(Some(request_id));
+                }
+                Poll::Ready(None) => {}
+                Poll::Pending => pending = true,
+            }
+        }
+        if pending {
+            Poll::Pending
+        } else {
+            Poll::Ready(None)
+        }
+    }
+}
+
+impl<Res> Shard<Res> {
+    /// Yields a request of the shard, one of `shards`, that has expired, completing it with a
+    /// TimedOut error.
+    fn poll_expired(
+        &mut self,
+        cx: &mut Context,
+        shards: usize,
+        expired_error: impl Fn() -> Res,
     ) -> Poll<Option<u64>> {
         self.deadlines.poll_expired(cx).map(|expired| {
             let request_id = expired?.into_inner();
-            if let Some(request_data) = self.request_data.remove(&request_id) {
+            let key = request_map::shard(request_id, shards).1;
+            if let Some(request_data) = self.request_data.remove(key) {
+                request_data.span.record("otel.status_code", "ERROR");
                 let _entered = request_data.span.enter();
                 tracing::error!("DeadlineExceeded");
                 self.request_data.compact(0.1);
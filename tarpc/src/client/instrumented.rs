@@ -0,0 +1,102 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Provides consistent observability for clients: metrics of each method, and spans annotated
+//! with the OpenTelemetry semantic conventions for RPC.
+//!
+//! With `#[tarpc::service(instrumented_client = true)]`, the macro generates an instrumented
+//! client, e.g. `WorldInstrumentedClient` for a service `World`, which wraps the client and has
+//! the same fns. Each call:
+//!
+//! - runs in a span named `RPC` with the attributes `rpc.system = "tarpc"`, `rpc.service`,
+//!   `rpc.method`, and `otel.kind = "client"`, plus `otel.status_code = "ERROR"` if the call
+//!   fails. The span of the request is its child.
+//! - increments the counter [`CALLS`] of the [`metrics`](::metrics) facade, labeled with
+//!   `rpc.system`, `rpc.service`, `rpc.method`, and `rpc.outcome`, which is `ok` or `error`.
+//!
+//! The latency of each call is recorded, with the same labels, in the histogram [`DURATION`] by
+//! the client channel itself; see the [`stats`](crate::stats) module.
+//!
+//! # Example
+//!
+//! ```rust
+//! # use futures::{future, prelude::*};
+//! # use tarpc::{
+//! #     client, context,
+//! #     server::{self, Channel},
+//! # };
+//! #[tarpc::service(instrumented_client = true)]
+//! trait World {
+//!     async fn hello(name: String) -> String;
+//! }
+//!
+//! #[derive(Clone)]
+//! struct WorldServer;
+//!
+//! impl World for WorldServer {
+//!     async fn hello(self, _: context::Context, name: String) -> String {
+//!         format!("Hello, {name}!")
+//!     }
+//! }
+//!
+//! # #[cfg(feature = "tokio1")]
+//! #[tokio::main]
+//! async fn main() -> Result<(), client::RpcError> {
+//!     let (client_transport, server_transport) = tarpc::transport::channel::unbounded();
+//!     let server = server::BaseChannel::with_defaults(server_transport);
+//!     tokio::spawn(server.execute(WorldServer.serve()).for_each(|response| async move {
+//!         tokio::spawn(response);
+//!     }));
+//!
+//!     let client = WorldClient::new(client::Config::default(), client_transport).spawn();
+//!     let client = WorldInstrumentedClient::from(client);
+//!     assert_eq!(client.hello(context::current(), "Ferris".into()).await?, "Hello, Ferris!");
+//!     Ok(())
+//! }
+//! # #[cfg(not(feature = "tokio1"))]
+//! # fn main() {}
+//! ```
+
+use std::future::Future;
+use tracing::Instrument;
+
+/// The counter of calls.
+pub const CALLS: &str = "rpc.client.calls";
+
+/// The histogram of the latencies of calls, in seconds.
+pub const DURATION: &str = crate::stats::CLIENT_DURATION;
+
+/// Makes the call of `method` of `service`, counting it and running it in an annotated span.
+pub async fn call<T, E>(
+    service: &'static str,
+    method: &'static str,
+    call: impl Future<Output = Result<T, E>>,
+) -> Result<T, E> {
+    let span = tracing::info_span!(
+        "RPC",
+        rpc.system = "tarpc",
+        rpc.service = service,
+        rpc.method = method,
+        otel.kind = "client",
+        otel.name = %format_args!("{service}/{method}"),
+        otel.status_code = tracing::field::Empty,
+    );
+    let result = call.instrument(span.clone()).await;
+    let outcome = if result.is_ok() {
+        "ok"
+    } else {
+        span.record("otel.status_code", "ERROR");
+        "error"
+    };
+    let labels = [
+        ("rpc.system", "tarpc"),
+        ("rpc.service", service),
+        ("rpc.method", method),
+        ("rpc.outcome", outcome),
+    ];
+    metrics::counter!(CALLS, &labels).increment(1);
+    result
+}
@@ -0,0 +1,56 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Provides access to the [extensions](ResponseExtensions) a server attached to a response,
+//! without changing the signatures of client methods.
+//!
+//! # Example
+//!
+//! ```rust
+//! use tarpc::{client, context, server::{self, BaseChannel, Channel, response_extensions}, transport};
+//! use futures::prelude::*;
+//!
+//! # #[cfg(not(feature = "tokio1"))]
+//! # fn main() {}
+//! # #[cfg(feature = "tokio1")]
+//! #[tokio::main]
+//! async fn main() {
+//!     let (tx, rx) = transport::channel::unbounded();
+//!     let client = client::new(client::Config::default(), tx).spawn();
+//!     tokio::spawn(
+//!         BaseChannel::with_defaults(rx)
+//!             .execute(server::serve(|_, i: i32| async move {
+//!                 response_extensions::insert("cache", "hit");
+//!                 Ok(i + 1)
+//!             }))
+//!             .for_each(|response| response),
+//!     );
+//!
+//!     let (response, extensions) = client::response_extensions::capture(
+//!         client.call(context::current(), "AddOne", 1)).await;
+//!     assert_eq!(response.unwrap(), 2);
+//!     assert_eq!(extensions.get("cache"), Some("hit"));
+//! }
+//! ```
+
+use crate::{util::scoped, ResponseExtensions};
+use futures::prelude::*;
+use std::cell::RefCell;
+
+thread_local! {
+    static CAPTURED: RefCell<Option<ResponseExtensions>> = const { RefCell::new(None) };
+}
+
+/// Runs `future`, yielding its output along with the extensions of the last response received
+/// by an RPC made while running it. If no response was received, the extensions are empty.
+pub fn capture<F: Future>(future: F) -> impl Future<Output = (F::Output, ResponseExtensions)> {
+    scoped::Scoped::new(&CAPTURED, ResponseExtensions::default(), future)
+}
+
+/// Makes `extensions` available to the innermost enclosing [`capture`], if any.
+pub(crate) fn record(extensions: ResponseExtensions) {
+    scoped::with(&CAPTURED, |captured| *captured = extensions);
+}
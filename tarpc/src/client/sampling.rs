@@ -0,0 +1,163 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Provides samplers, which decide whether the traces of a client's requests are sampled.
+//!
+//! By default, the [sampling decision](SamplingDecision) sent with a request is that of
+//! the request's span. A client configured with a [`Sampler`] sends the sampler's decision
+//! instead, so that, e.g., the traces of a high-QPS method can be sampled down without disabling
+//! tracing altogether:
+//!
+//! ```
+//! use tarpc::client::{self, sampling::Sampler};
+//!
+//! let mut config = client::Config::default();
+//! config.sampler = Some(Sampler::per_method(
+//!     [("Metrics.report", Sampler::ratio(0.01))],
+//!     Sampler::always(),
+//! ));
+//! ```
+
+use crate::trace::{SamplingDecision, TraceId};
+use fnv::FnvHashMap;
+use std::{fmt, sync::Arc};
+
+/// A request whose trace is being sampled.
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct SamplingRequest {
+    /// The name of the request, e.g. `World.hello`.
+    pub request_name: &'static str,
+    /// The ID of the trace the request belongs to.
+    pub trace_id: TraceId,
+    /// The sampling decision of the request's span, which is sent if the client has no sampler.
+    pub span_decision: SamplingDecision,
+}
+
+/// Decides whether to sample the trace of each request.
+///
+/// Clones share the same decision fn.
+#[derive(Clone)]
+pub struct Sampler(Arc<dyn Fn(&SamplingRequest) -> SamplingDecision + Send + Sync>);
+
+impl Sampler {
+    /// Returns a sampler that decides with `decide`.
+    pub fn new<F>(decide: F) -> Self
+    where
+        F: Fn(&SamplingRequest) -> SamplingDecision + Send + Sync + 'static,
+    {
+        Self(Arc::new(decide))
+    }
+
+    /// Returns a sampler that samples every request.
+    pub fn always() -> Self {
+        Self::new(|_| SamplingDecision::Sampled)
+    }
+
+    /// Returns a sampler that samples no request.
+    pub fn never() -> Self {
+        Self::new(|_| SamplingDecision::Unsampled)
+    }
+
+    /// Returns a sampler that samples a `ratio` of traces, between 0 and 1. Traces are chosen by
+    /// their ID, so that every client with the same ratio makes the same decision for a trace.
+    pub fn ratio(ratio: f64) -> Self {
+        if ratio >= 1.0 {
+            return Self::always();
+        }
+        // Like OpenTelemetry's TraceIdRatioBased sampler, compares the low 63 bits of the ID.
+        let threshold = (ratio.max(0.0) * (1u64 << 63) as f64) as u64;
+        Self::new(move |request| {
+            if (u128::from(request.trace_id) as u64) >> 1 < threshold {
+                SamplingDecision::Sampled
+            } else {
+                SamplingDecision::Unsampled
+            }
+        })
+    }
+
+    /// Returns a sampler that defers to the sampler of each request's method, or to `default` for
+    /// methods that aren't listed. Methods are named like requests, e.g. `World.hello`.
+    pub fn per_method<I>(methods: I, default: Sampler) -> Self
+    where
+        I: IntoIterator<Item = (&'static str, Sampler)>,
+    {
+        let methods: FnvHashMap<_, _> = methods.into_iter().collect();
+        Self::new(move |request| {
+            methods
+                .get(request.request_name)
+                .unwrap_or(&default)
+                .sample(request)
+        })
+    }
+
+    /// Returns the decision for `request`.
+    pub fn sample(&self, request: &SamplingRequest) -> SamplingDecision {
+        (self.0)(request)
+    }
+}
+
+impl fmt::Debug for Sampler {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Sampler").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn request(request_name: &'static str, trace_id: u128) -> SamplingRequest {
+        SamplingRequest {
+            request_name,
+            trace_id: TraceId::from(trace_id),
+            span_decision: SamplingDecision::Sampled,
+        }
+    }
+
+    #[test]
+    fn ratio_samples_by_trace_id() {
+        let sampler = Sampler::ratio(0.5);
+        assert_eq!(
+            sampler.sample(&request("A.a", 0)),
+            SamplingDecision::Sampled
+        );
+        assert_eq!(
+            sampler.sample(&request("A.a", u64::MAX.into())),
+            SamplingDecision::Unsampled
+        );
+        assert_eq!(
+            Sampler::ratio(0.0).sample(&request("A.a", 0)),
+            SamplingDecision::Unsampled
+        );
+        assert_eq!(
+            Sampler::ratio(1.0).sample(&request("A.a", u64::MAX.into())),
+            SamplingDecision::Sampled
+        );
+    }
+
+    #[test]
+    fn per_method_defers_to_the_method_sampler() {
+        let sampler = Sampler::per_method([("A.noisy", Sampler::never())], Sampler::always());
+        assert_eq!(
+            sampler.sample(&request("A.noisy", 1)),
+            SamplingDecision::Unsampled
+        );
+        assert_eq!(
+            sampler.sample(&request("A.quiet", 1)),
+            SamplingDecision::Sampled
+        );
+    }
+
+    #[test]
+    fn custom_sampler_sees_the_span_decision() {
+        let sampler = Sampler::new(|request| request.span_decision);
+        assert_eq!(
+            sampler.sample(&request("A.a", 1)),
+            SamplingDecision::Sampled
+        );
+    }
+}
@@ -5,6 +5,8 @@ use crate::{
     context,
 };
 
+pub mod credentials;
+pub mod embed;
 pub mod load_balance;
 pub mod retry;
 
@@ -0,0 +1,88 @@
+//! Provides a stub that authenticates requests with credentials.
+
+use crate::{
+    client::{stub, RpcError},
+    context::{self, Credentials},
+};
+
+/// A stub that sets the [credentials](context::Context::credentials) of each request to those
+/// returned by a provider, e.g. a bearer token that's refreshed in the background. Requests whose
+/// context already carries credentials keep them.
+#[derive(Clone, Debug)]
+pub struct WithCredentials<Stub, P> {
+    stub: Stub,
+    provider: P,
+}
+
+impl<Stub, P> WithCredentials<Stub, P>
+where
+    P: Fn() -> Option<Credentials>,
+{
+    /// Returns a stub that sends requests through `stub` with the credentials returned by
+    /// `provider`, if any.
+    pub fn new(stub: Stub, provider: P) -> Self {
+        Self { stub, provider }
+    }
+}
+
+impl<Stub, P> stub::Stub for WithCredentials<Stub, P>
+where
+    Stub: stub::Stub,
+    P: Fn() -> Option<Credentials>,
+{
+    type Req = Stub::Req;
+    type Resp = Stub::Resp;
+
+    async fn call(
+        &self,
+        mut ctx: context::Context,
+        request_name: &'static str,
+        request: Self::Req,
+    ) -> Result<Self::Resp, RpcError> {
+        if ctx.credentials.is_none() {
+            ctx.credentials = (self.provider)();
+        }
+        self.stub.call(ctx, request_name, request).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::stub::Stub;
+    use futures::executor::block_on;
+
+    struct Whoami;
+
+    impl Stub for Whoami {
+        type Req = ();
+        type Resp = Option<String>;
+
+        async fn call(
+            &self,
+            ctx: context::Context,
+            _: &'static str,
+            _: (),
+        ) -> Result<Option<String>, RpcError> {
+            Ok(ctx
+                .credentials
+                .and_then(|credentials| credentials.as_str().map(String::from)))
+        }
+    }
+
+    #[test]
+    fn provided_credentials_fill_in_missing_ones() {
+        let stub = WithCredentials::new(Whoami, || Some(Credentials::from("token")));
+        assert_eq!(
+            block_on(stub.call(context::current(), "Whoami", ())).unwrap(),
+            Some("token".into())
+        );
+
+        let mut ctx = context::current();
+        ctx.credentials = Some("explicit".into());
+        assert_eq!(
+            block_on(stub.call(ctx, "Whoami", ())).unwrap(),
+            Some("explicit".into())
+        );
+    }
+}
@@ -0,0 +1,62 @@
+//! Provides a stub that calls a service embedded in another service.
+
+use crate::{
+    client::{stub, RpcError},
+    context,
+};
+use std::{io, marker::PhantomData, sync::Arc};
+
+/// A stub that calls a service embedded in another service, like a service extended with
+/// `#[tarpc::service(extends = ...)]`: requests of type `Req` are sent as requests of `Stub`, and
+/// responses of `Stub` are converted back into responses of type `Resp`.
+pub struct Embed<Stub, Req, Resp> {
+    stub: Stub,
+    marker: PhantomData<fn(Req) -> Resp>,
+}
+
+impl<Stub, Req, Resp> Embed<Stub, Req, Resp> {
+    /// Returns a stub that sends requests of the embedded service through `stub`.
+    pub fn new(stub: Stub) -> Self {
+        Self {
+            stub,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<Stub, Req, Resp> stub::Stub for Embed<Stub, Req, Resp>
+where
+    Stub: stub::Stub,
+    Stub::Req: From<Req>,
+    Resp: TryFrom<Stub::Resp>,
+{
+    type Req = Req;
+    type Resp = Resp;
+
+    async fn call(
+        &self,
+        ctx: context::Context,
+        request_name: &'static str,
+        request: Req,
+    ) -> Result<Resp, RpcError> {
+        let response = self.stub.call(ctx, request_name, request.into()).await?;
+        Resp::try_from(response).map_err(|_| {
+            RpcError::Receive(Arc::new(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the response is for a method of another service",
+            )))
+        })
+    }
+}
+
+impl<Stub: Clone, Req, Resp> Clone for Embed<Stub, Req, Resp> {
+    fn clone(&self) -> Self {
+        Self::new(self.stub.clone())
+    }
+}
+
+impl<Stub: std::fmt::Debug, Req, Resp> std::fmt::Debug for Embed<Stub, Req, Resp> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Embed").field("stub", &self.stub).finish()
+    }
+}
@@ -97,7 +97,8 @@ mod round_robin {
 /// Provides a stub that load-balances with a consistent hashing strategy.
 ///
 /// Each request is hashed, then mapped to a stub based on the hash. Equivalent requests will use
-/// the same stub.
+/// the same stub. Requests whose context has a [routing key](context::Context::routing_key) are
+/// mapped by the key instead, so that requests with the same key use the same stub.
 mod consistent_hash {
     use crate::{
         client::{stub, RpcError},
@@ -124,7 +125,7 @@ mod consistent_hash {
             request_name: &'static str,
             request: Self::Req,
         ) -> Result<Stub::Resp, RpcError> {
-            let index = usize::try_from(self.hash_request(&request) % self.stubs_len).expect(
+            let index = usize::try_from(self.hash_request(&ctx, &request) % self.stubs_len).expect(
                 "invariant broken: stubs_len is not larger than a usize, \
                          so the hash modulo stubs_len should always fit in a usize",
             );
@@ -173,9 +174,12 @@ mod consistent_hash {
             })
         }
 
-        fn hash_request(&self, req: &Stub::Req) -> u64 {
+        fn hash_request(&self, ctx: &context::Context, req: &Stub::Req) -> u64 {
             let mut hasher = self.hasher.build_hasher();
-            req.hash(&mut hasher);
+            match ctx.routing_key {
+                Some(routing_key) => routing_key.hash(&mut hasher),
+                None => req.hash(&mut hasher),
+            }
             hasher.finish()
         }
     }
@@ -221,6 +225,23 @@ mod consistent_hash {
             Ok(())
         }
 
+        #[tokio::test]
+        async fn routing_key_overrides_request() -> anyhow::Result<()> {
+            let stub = ConsistentHash::<_, FakeHasherBuilder>::with_hasher(
+                vec![
+                    Mock::new([('a', 0)]),
+                    Mock::new([('a', 1)]),
+                    Mock::new([('a', 2)]),
+                ],
+                FakeHasherBuilder::new([(7u64, 2)]),
+            )?;
+
+            let ctx = context::current().with_routing_key(7);
+            assert_eq!(stub.call(ctx, "", 'a').await?, 2);
+
+            Ok(())
+        }
+
         struct HashRecorder(Vec<u8>);
         impl Hasher for HashRecorder {
             fn write(&mut self, bytes: &[u8]) {
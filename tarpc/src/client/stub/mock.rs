@@ -40,10 +40,10 @@ where
             .cloned()
             .map(Ok)
             .unwrap_or_else(|| {
-                Err(RpcError::Server(ServerError {
-                    kind: io::ErrorKind::NotFound,
-                    detail: "mock (request, response) entry not found".into(),
-                }))
+                Err(RpcError::Server(ServerError::new(
+                    io::ErrorKind::NotFound,
+                    "mock (request, response) entry not found".into(),
+                )))
             })
     }
 }
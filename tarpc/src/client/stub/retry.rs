@@ -24,9 +24,21 @@ where
         for i in 1.. {
             let result = self
                 .stub
-                .call(ctx, request_name, Arc::clone(&request))
+                .call(ctx.clone(), request_name, Arc::clone(&request))
                 .await;
             if (self.should_retry)(&result, i) {
+                if let Some(retry_after) = result.as_ref().err().and_then(RpcError::retry_after) {
+                    if ctx.time_remaining() <= retry_after {
+                        tracing::trace!(
+                            ?retry_after,
+                            "Not retrying: the server's retry-after exceeds the deadline"
+                        );
+                        return result;
+                    }
+                    tracing::trace!(?retry_after, "Retrying on attempt {i} after backoff");
+                    tokio::time::sleep(retry_after).await;
+                    continue;
+                }
                 tracing::trace!("Retrying on attempt {i}");
                 continue;
             }
@@ -37,6 +49,10 @@ where
 }
 
 /// A Stub that retries requests based on response contents.
+///
+/// When the server answers with an [overloaded](crate::ServerError::overloaded) error that the
+/// retry predicate accepts, the stub waits for the server's suggested backoff before retrying, or
+/// returns the error if the backoff would exceed the request deadline.
 /// Note: to use this stub with Serde serialization, the "rc" feature of Serde needs to be enabled.
 #[derive(Clone, Debug)]
 pub struct Retry<F, Stub> {
@@ -54,3 +70,91 @@ where
         Self { stub, should_retry }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{client::stub::Stub, ServerError};
+    use std::{
+        cell::Cell,
+        time::{Duration, Instant},
+    };
+
+    /// Answers each request with an overloaded error until `failures` calls have been made.
+    struct Overloaded {
+        calls: Cell<u32>,
+        failures: u32,
+        retry_after: Duration,
+    }
+
+    impl stub::Stub for Overloaded {
+        type Req = Arc<()>;
+        type Resp = u32;
+
+        async fn call(
+            &self,
+            _: context::Context,
+            _: &'static str,
+            _: Arc<()>,
+        ) -> Result<u32, RpcError> {
+            self.calls.set(self.calls.get() + 1);
+            if self.calls.get() <= self.failures {
+                return Err(RpcError::Server(ServerError::overloaded(
+                    "busy".into(),
+                    self.retry_after,
+                )));
+            }
+            Ok(self.calls.get())
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn waits_for_retry_after() {
+        let stub = Retry::new(
+            Overloaded {
+                calls: Cell::new(0),
+                failures: 2,
+                retry_after: Duration::from_secs(1),
+            },
+            |result, _| result.is_err(),
+        );
+        let start = tokio::time::Instant::now();
+        assert_eq!(stub.call(context::current(), "", ()).await.unwrap(), 3);
+        assert!(start.elapsed() >= Duration::from_secs(2));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gives_up_when_retry_after_exceeds_deadline() {
+        let stub = Retry::new(
+            Overloaded {
+                calls: Cell::new(0),
+                failures: 1,
+                retry_after: Duration::from_secs(60 * 60),
+            },
+            |result, _| result.is_err(),
+        );
+        let start = Instant::now();
+        let result = stub.call(context::current(), "", ()).await;
+        assert_eq!(
+            result.unwrap_err().retry_after(),
+            Some(Duration::from_secs(60 * 60))
+        );
+        assert_eq!(stub.stub.calls.get(), 1);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gives_up_when_retry_after_is_unrepresentable() {
+        let stub = Retry::new(
+            Overloaded {
+                calls: Cell::new(0),
+                failures: 1,
+                retry_after: Duration::MAX,
+            },
+            |result, _| result.is_err(),
+        );
+        let result = stub.call(context::current(), "", ()).await;
+        assert_eq!(result.unwrap_err().retry_after(), Some(Duration::MAX));
+        assert_eq!(stub.stub.calls.get(), 1);
+    }
+}
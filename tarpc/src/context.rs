@@ -4,16 +4,26 @@
 // license that can be found in the LICENSE file or at
 // https://opensource.org/licenses/MIT.
 
-//! Provides a request context that carries a deadline and trace context. This context is sent from
-//! client to server and is used by the server to enforce response deadlines.
+//! Provides a request context that carries a deadline, trace context, and baggage. This context is
+//! sent from client to server and is used by the server to enforce response deadlines.
 
-use crate::trace::{self, TraceId};
+use crate::{
+    trace::{self, TraceId},
+    util,
+};
+use fnv::FnvHashMap;
+#[cfg(feature = "opentelemetry")]
 use opentelemetry::trace::TraceContextExt;
 use static_assertions::assert_impl_all;
 use std::{
-    convert::TryFrom,
-    time::{Duration, SystemTime},
+    any::{Any, TypeId},
+    cell::RefCell,
+    collections::BTreeMap,
+    fmt,
+    sync::Arc,
+    time::{Duration, Instant},
 };
+#[cfg(feature = "opentelemetry")]
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 /// A request context that carries request-scoped information like deadlines and trace information.
@@ -21,7 +31,20 @@ use tracing_opentelemetry::OpenTelemetrySpanExt;
 ///
 /// The context should not be stored directly in a server implementation, because the context will
 /// be different for each request in scope.
-#[derive(Clone, Copy, Debug)]
+///
+/// Contexts are usually made with [`current`], and adjusted with the `with_*` fns:
+///
+/// ```
+/// use std::time::Duration;
+/// use tarpc::context::{self, Priority};
+///
+/// let ctx = context::current()
+///     .with_timeout(Duration::from_secs(30))
+///     .with_priority(Priority::High)
+///     .with_baggage("locale", "de-CH");
+/// assert_eq!(ctx.baggage.get("locale"), Some("de-CH"));
+/// ```
+#[derive(Clone, Debug)]
 #[non_exhaustive]
 #[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(
@@ -32,94 +55,393 @@ use tracing_opentelemetry::OpenTelemetrySpanExt;
 pub struct Context {
     /// When the client expects the request to be complete by. The server should cancel the request
     /// if it is not complete by this time.
+    ///
+    /// The deadline is tracked on the monotonic clock, so that steps of the system clock, e.g. by
+    /// NTP, don't move it. Serde sends it as the time remaining, which the receiver adds to its
+    /// own clock; rkyv archives it as the time of the system clock, since the UNIX epoch.
     #[cfg_attr(feature = "serde1", serde(default = "ten_seconds_from_now"))]
     // Serialized as a Duration to prevent clock skew issues.
     #[cfg_attr(feature = "serde1", serde(with = "absolute_to_relative_time"))]
-    #[cfg_attr(feature = "rkyv", with(RkyvSystemTime))]
-    pub deadline: SystemTime,
+    #[cfg_attr(feature = "rkyv", with(RkyvInstant))]
+    pub deadline: Instant,
     /// Uniquely identifies requests originating from the same source.
     /// When a service handles a request by making requests itself, those requests should
     /// include the same `trace_id` as that included on the original request. This way,
     /// users can trace related actions across a distributed system.
     pub trace_context: trace::Context,
+    /// An optional client-chosen key identifying a logical operation. Requests that carry the same
+    /// key are retries of the same operation, which lets servers deduplicate them; see
+    /// [`server::idempotency`](crate::server::idempotency).
+    #[cfg_attr(feature = "serde1", serde(default))]
+    pub idempotency_key: Option<u64>,
+    /// An optional key, e.g. a tenant ID or the hash of a user ID, that routing layers route by:
+    /// client layers like [`ConsistentHash`](crate::client::stub::load_balance::ConsistentHash)
+    /// send requests with the same key to the same backend, and servers like
+    /// [`TenantRouter`](crate::server::routing::TenantRouter) serve each key with an isolated
+    /// backend. Unlike baggage, it's one field that all routing layers agree on.
+    #[cfg_attr(feature = "serde1", serde(default))]
+    pub routing_key: Option<u64>,
+    /// Request-scoped key-value pairs, like a locale or feature flags, which are propagated to
+    /// the server and from there into the requests it makes while handling the request.
+    #[cfg_attr(feature = "serde1", serde(default))]
+    pub baggage: Baggage,
+    /// How urgent the request is, for client dispatch and server scheduling layers to act on.
+    /// Inherited from the active request by [`current`](Context::current); otherwise, clients
+    /// send their [configured](crate::client::Config::priority) priority.
+    #[cfg_attr(feature = "serde1", serde(default))]
+    pub priority: Priority,
+    /// Opaque credentials authenticating the request, like a bearer token, for server auth hooks
+    /// like [`Authorize`](crate::server::authorization::Authorize) to check; client stubs like
+    /// [`WithCredentials`](crate::client::stub::credentials::WithCredentials) set them. Unlike
+    /// the rest of the context, they aren't inherited by [`current`](Context::current), so they
+    /// don't leak into the requests a server makes while handling the request.
+    #[cfg_attr(feature = "serde1", serde(default))]
+    pub credentials: Option<Credentials>,
+    /// Values that hooks attach to the request for later hooks and the handler to read, like the
+    /// principal an auth hook authenticated. Local to the process: they're neither sent over the
+    /// wire nor inherited by [`current`](Context::current).
+    #[cfg_attr(feature = "serde1", serde(skip))]
+    #[cfg_attr(feature = "rkyv", with(rkyv::with::Skip))]
+    pub extensions: Extensions,
+    /// The deadline [`current`](Context::current) defaulted to, if no request was active. Local to
+    /// the client, so that methods with a default deadline can replace it.
+    #[cfg_attr(feature = "serde1", serde(skip))]
+    #[cfg_attr(feature = "rkyv", with(rkyv::with::Skip))]
+    pub(crate) default_deadline: Option<Instant>,
+    /// The priority [`current`](Context::current) defaulted to, if no request was active. Local
+    /// to the client, so that it can send its configured priority instead.
+    #[cfg_attr(feature = "serde1", serde(skip))]
+    #[cfg_attr(feature = "rkyv", with(rkyv::with::Skip))]
+    pub(crate) default_priority: Option<Priority>,
+    /// Whether the client skips the span of the request. Local to the client.
+    #[cfg_attr(feature = "serde1", serde(skip))]
+    #[cfg_attr(feature = "rkyv", with(rkyv::with::Skip))]
+    pub(crate) untraced: bool,
+}
+
+/// How urgent a request is, from least to most urgent.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+#[cfg_attr(feature = "rkyv", archive(check_bytes))]
+#[repr(u8)]
+pub enum Priority {
+    /// Work that can wait indefinitely, e.g. batch jobs.
+    Background,
+    /// Work that can wait for more urgent requests.
+    Low,
+    /// The priority of most requests.
+    #[default]
+    Normal,
+    /// Work that should go before normal requests, e.g. interactive requests.
+    High,
+    /// Work that must go first, e.g. health checks and control-plane requests.
+    Critical,
+}
+
+/// Opaque credentials carried by a [`Context`], like a bearer token. Their `Debug` output is
+/// redacted, so that they don't end up in logs.
+#[derive(Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+#[cfg_attr(feature = "rkyv", archive(check_bytes))]
+pub struct Credentials(Vec<u8>);
+
+impl Credentials {
+    /// Returns credentials consisting of `bytes`.
+    pub fn new(bytes: impl Into<Vec<u8>>) -> Self {
+        Self(bytes.into())
+    }
+
+    /// Returns the bytes of the credentials.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// Returns the credentials as a string, if they're UTF-8, e.g. a bearer token.
+    pub fn as_str(&self) -> Option<&str> {
+        std::str::from_utf8(&self.0).ok()
+    }
 }
 
+impl From<String> for Credentials {
+    fn from(token: String) -> Self {
+        Self(token.into_bytes())
+    }
+}
+
+impl From<&str> for Credentials {
+    fn from(token: &str) -> Self {
+        Self(token.as_bytes().to_vec())
+    }
+}
+
+impl From<Vec<u8>> for Credentials {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Credentials(<redacted>)")
+    }
+}
+
+/// Request-scoped key-value pairs carried by a [`Context`].
+///
+/// Baggage is sent with each request, so it's limited to [`MAX_ENTRIES`](Baggage::MAX_ENTRIES)
+/// entries of [`MAX_SIZE`](Baggage::MAX_SIZE) bytes in total, and baggage exceeding the limits
+/// fails to deserialize with serde. The baggage of the request a server is handling is inherited by
+/// [`current`], so that it flows into downstream calls.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+#[cfg_attr(feature = "rkyv", archive(check_bytes))]
+pub struct Baggage(BTreeMap<String, String>);
+
+impl Baggage {
+    /// The maximum number of entries.
+    pub const MAX_ENTRIES: usize = 32;
+    /// The maximum total length of the keys and values, in bytes.
+    pub const MAX_SIZE: usize = 4096;
+
+    /// Sets the entry `key` to `value`, returning the previous value, if any. Fails, leaving the
+    /// baggage unchanged, if the entry would exceed the limits.
+    pub fn insert(
+        &mut self,
+        key: impl Into<String>,
+        value: impl Into<String>,
+    ) -> Result<Option<String>, BaggageError> {
+        let (key, value) = (key.into(), value.into());
+        let replaced = self.0.get(&key).map(|old| key.len() + old.len());
+        if replaced.is_none() && self.0.len() >= Self::MAX_ENTRIES {
+            return Err(BaggageError::TooManyEntries);
+        }
+        if self.size() - replaced.unwrap_or(0) + key.len() + value.len() > Self::MAX_SIZE {
+            return Err(BaggageError::TooLarge);
+        }
+        Ok(self.0.insert(key, value))
+    }
+
+    /// Returns the value of the entry `key`, if set.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(String::as_str)
+    }
+
+    /// Removes the entry `key`, returning its value, if set.
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        self.0.remove(key)
+    }
+
+    /// Returns an iterator over all entries, ordered by key.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Returns the number of entries.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns true iff there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Returns the total length of the keys and values, in bytes.
+    pub fn size(&self) -> usize {
+        self.0.iter().map(|(k, v)| k.len() + v.len()).sum()
+    }
+
+    /// Returns an error if the baggage exceeds the limits.
+    pub fn check_limits(&self) -> Result<(), BaggageError> {
+        if self.0.len() > Self::MAX_ENTRIES {
+            Err(BaggageError::TooManyEntries)
+        } else if self.size() > Self::MAX_SIZE {
+            Err(BaggageError::TooLarge)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(feature = "serde1")]
+impl<'de> serde::Deserialize<'de> for Baggage {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let baggage = Baggage(BTreeMap::deserialize(deserializer)?);
+        baggage.check_limits().map_err(serde::de::Error::custom)?;
+        Ok(baggage)
+    }
+}
+
+/// An error indicating baggage would exceed its limits.
+#[derive(thiserror::Error, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum BaggageError {
+    /// The baggage would have more than [`Baggage::MAX_ENTRIES`] entries.
+    #[error("baggage has more than {} entries", Baggage::MAX_ENTRIES)]
+    TooManyEntries,
+    /// The keys and values of the baggage would be longer than [`Baggage::MAX_SIZE`] bytes.
+    #[error("baggage is larger than {} bytes", Baggage::MAX_SIZE)]
+    TooLarge,
+}
+
+/// Request-local values, keyed by their type. See [`Context::extensions`].
+///
+/// Clones share the values, which can't be modified once inserted; insert a new value to replace
+/// one.
+#[derive(Clone, Default)]
+pub struct Extensions(FnvHashMap<TypeId, Arc<dyn Any + Send + Sync>>);
+
+impl Extensions {
+    /// Inserts `value`, replacing the value of the same type, if any.
+    pub fn insert<T: Any + Send + Sync>(&mut self, value: T) {
+        self.0.insert(TypeId::of::<T>(), Arc::new(value));
+    }
+
+    /// Returns the value of type `T`, if any.
+    pub fn get<T: Any + Send + Sync>(&self) -> Option<&T> {
+        self.0
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref())
+    }
+
+    /// Removes the value of type `T`. Returns true iff there was one.
+    pub fn remove<T: Any + Send + Sync>(&mut self) -> bool {
+        self.0.remove(&TypeId::of::<T>()).is_some()
+    }
+
+    /// Returns the number of values.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns true iff there are no values.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Extensions")
+            .field("len", &self.len())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Archives an [`Instant`] as the time of the system clock it corresponds to, since the UNIX
+/// epoch, as deadlines were archived when they were a [`SystemTime`](std::time::SystemTime), so
+/// that peers built before deadlines moved to the monotonic clock can still be called.
 #[cfg(feature = "rkyv")]
-struct RkyvSystemTime;
+struct RkyvInstant;
 
 #[cfg(feature = "rkyv")]
-impl rkyv::with::ArchiveWith<SystemTime> for RkyvSystemTime {
+impl RkyvInstant {
+    /// A century, which is representable on every supported platform.
+    const CENTURY: Duration = Duration::from_secs(100 * 365 * 24 * 60 * 60);
+
+    fn since_unix_epoch(instant: &Instant) -> Duration {
+        use std::time::SystemTime;
+
+        let remaining = instant.saturating_duration_since(crate::util::now());
+        let now = SystemTime::now();
+        now.checked_add(remaining)
+            .unwrap_or(now + Self::CENTURY)
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or(Duration::ZERO)
+    }
+
+    fn from_unix_epoch(since_unix_epoch: Duration) -> Instant {
+        use std::time::SystemTime;
+
+        let remaining = SystemTime::UNIX_EPOCH.checked_add(since_unix_epoch).map_or(
+            Self::CENTURY,
+            |deadline| {
+                deadline
+                    .duration_since(SystemTime::now())
+                    .unwrap_or(Duration::ZERO)
+            },
+        );
+        crate::util::instant_after(remaining)
+    }
+}
+
+#[cfg(feature = "rkyv")]
+impl rkyv::with::ArchiveWith<Instant> for RkyvInstant {
     type Archived = rkyv::Archived<Duration>;
     type Resolver = rkyv::Resolver<Duration>;
 
-    unsafe fn resolve_with(field: &SystemTime, pos: usize, _: (), out: *mut Self::Archived) {
+    unsafe fn resolve_with(field: &Instant, pos: usize, _: (), out: *mut Self::Archived) {
         use rkyv::Archive;
-        let deadline = field
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap_or(Duration::ZERO);
-        deadline.resolve(pos, (), out);
+        Self::since_unix_epoch(field).resolve(pos, (), out);
     }
 }
 
 #[cfg(feature = "rkyv")]
-impl<S: rkyv::Fallible + ?Sized> rkyv::with::SerializeWith<SystemTime, S> for RkyvSystemTime
+impl<S: rkyv::Fallible + ?Sized> rkyv::with::SerializeWith<Instant, S> for RkyvInstant
 where
     Duration: rkyv::Serialize<S>,
 {
-    fn serialize_with(field: &SystemTime, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+    fn serialize_with(field: &Instant, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
         use rkyv::Serialize;
-        let deadline = field
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap_or(Duration::ZERO);
-        deadline.serialize(serializer)
+        Self::since_unix_epoch(field).serialize(serializer)
     }
 }
 
 #[cfg(feature = "rkyv")]
-impl<D: rkyv::Fallible + ?Sized>
-    rkyv::with::DeserializeWith<rkyv::Archived<Duration>, SystemTime, D> for RkyvSystemTime
+impl<D: rkyv::Fallible + ?Sized> rkyv::with::DeserializeWith<rkyv::Archived<Duration>, Instant, D>
+    for RkyvInstant
 where
     rkyv::Archived<Duration>: rkyv::Deserialize<Duration, D>,
 {
     fn deserialize_with(
         field: &rkyv::Archived<Duration>,
         deserializer: &mut D,
-    ) -> Result<SystemTime, D::Error> {
+    ) -> Result<Instant, D::Error> {
         use rkyv::Deserialize;
-        Ok(SystemTime::UNIX_EPOCH + field.deserialize(deserializer)?)
+        Ok(Self::from_unix_epoch(field.deserialize(deserializer)?))
     }
 }
 
 #[cfg(feature = "serde1")]
-mod absolute_to_relative_time {
+pub(crate) mod absolute_to_relative_time {
     pub use serde::{Deserialize, Deserializer, Serialize, Serializer};
-    pub use std::time::{Duration, SystemTime};
+    pub use std::time::{Duration, Instant};
 
-    pub fn serialize<S>(deadline: &SystemTime, serializer: S) -> Result<S::Ok, S::Error>
+    pub fn serialize<S>(deadline: &Instant, serializer: S) -> Result<S::Ok, S::Error>
     where
         S: Serializer,
     {
-        let deadline = deadline
-            .duration_since(SystemTime::now())
-            .unwrap_or(Duration::ZERO);
+        let deadline = deadline.saturating_duration_since(crate::util::now());
         deadline.serialize(serializer)
     }
 
-    pub fn deserialize<'de, D>(deserializer: D) -> Result<SystemTime, D::Error>
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Instant, D::Error>
     where
         D: Deserializer<'de>,
     {
         let deadline = Duration::deserialize(deserializer)?;
-        Ok(SystemTime::now() + deadline)
+        Ok(crate::util::instant_after(deadline))
     }
 
     #[cfg(test)]
     #[derive(serde::Serialize, serde::Deserialize)]
-    struct AbsoluteToRelative(#[serde(with = "self")] SystemTime);
+    struct AbsoluteToRelative(#[serde(with = "self")] Instant);
 
     #[test]
     fn test_serialize() {
-        let now = SystemTime::now();
+        let now = Instant::now();
         let deadline = now + Duration::from_secs(10);
         let serialized_deadline = bincode::serialize(&AbsoluteToRelative(deadline)).unwrap();
         let deserialized_deadline: Duration = bincode::deserialize(&serialized_deadline).unwrap();
@@ -134,14 +456,14 @@ mod absolute_to_relative_time {
         let AbsoluteToRelative(deserialized_deadline) =
             bincode::deserialize(&serialized_deadline).unwrap();
         // TODO: how to avoid flakiness?
-        assert!(deserialized_deadline > SystemTime::now() + Duration::from_secs(9));
+        assert!(deserialized_deadline > Instant::now() + Duration::from_secs(9));
     }
 }
 
 assert_impl_all!(Context: Send, Sync);
 
-fn ten_seconds_from_now() -> SystemTime {
-    SystemTime::now() + Duration::from_secs(10)
+fn ten_seconds_from_now() -> Instant {
+    util::now() + Duration::from_secs(10)
 }
 
 /// Returns the context for the current request, or a default Context if no request is active.
@@ -149,29 +471,238 @@ pub fn current() -> Context {
     Context::current()
 }
 
+thread_local! {
+    /// The ID of the request currently being served on this thread.
+    pub(crate) static REQUEST_ID: RefCell<Option<u64>> = const { RefCell::new(None) };
+
+    /// What the requests made while serving the request currently being served on this thread
+    /// inherit from its context, which spans don't carry without OpenTelemetry.
+    #[cfg(not(feature = "opentelemetry"))]
+    pub(crate) static CURRENT: RefCell<Option<Inherited>> = const { RefCell::new(None) };
+}
+
+/// Returns the ID of the request being served, e.g. to correlate a handler's logs with the
+/// cancellation of its request, or `None` if called outside of a request handler executed by
+/// [`InFlightRequest::execute`](crate::server::InFlightRequest::execute).
+///
+/// Request IDs are chosen by the client, and are only unique among the requests in flight on the
+/// same channel.
+pub fn current_request_id() -> Option<u64> {
+    crate::util::scoped::with(&REQUEST_ID, |request_id| *request_id)
+}
+
+#[cfg(feature = "opentelemetry")]
 #[derive(Clone)]
-struct Deadline(SystemTime);
+struct Deadline(Instant);
+
+/// The parts of a request's context that the requests made while handling it inherit. It's all
+/// a server keeps of the context once the handler owns it, rather than a copy of the credentials
+/// and extensions that children don't inherit anyway.
+#[derive(Clone, Debug)]
+pub(crate) struct Inherited {
+    trace_context: trace::Context,
+    deadline: Instant,
+    default_deadline: Option<Instant>,
+    priority: Priority,
+    default_priority: Option<Priority>,
+    baggage: Baggage,
+}
+
+impl Inherited {
+    pub(crate) fn of(context: &Context) -> Self {
+        Self {
+            trace_context: context.trace_context.clone(),
+            deadline: context.deadline,
+            default_deadline: context.default_deadline,
+            priority: context.priority,
+            default_priority: context.default_priority,
+            baggage: context.baggage.clone(),
+        }
+    }
 
-impl Default for Deadline {
-    fn default() -> Self {
-        Self(ten_seconds_from_now())
+    /// Returns the context of a request made while handling the request.
+    pub(crate) fn into_child(self) -> Context {
+        let mut child = Context::root();
+        child.trace_context = self.trace_context.new_child();
+        child.deadline = self.deadline;
+        child.default_deadline = self.default_deadline;
+        child.priority = self.priority;
+        child.default_priority = self.default_priority;
+        child.baggage = self.baggage;
+        child
     }
 }
 
 impl Context {
     /// Returns the context for the current request, or a default Context if no request is active.
+    #[cfg(feature = "opentelemetry")]
     pub fn current() -> Self {
         let span = tracing::Span::current();
+        // Without a subscriber interested in the span, it carries no OpenTelemetry context, so
+        // skip looking for one.
+        if span.is_disabled() {
+            return Self::root();
+        }
+        let otel_context = span.context();
+        let mut context = Self::root();
+        if otel_context.has_active_span() {
+            context.trace_context = trace::Context::from(otel_context.span());
+        }
+        if let Some(Deadline(deadline)) = otel_context.get::<Deadline>() {
+            context.deadline = *deadline;
+            context.default_deadline = None;
+        }
+        if let Some(priority) = otel_context.get::<Priority>() {
+            context.priority = *priority;
+            context.default_priority = None;
+        }
+        if let Some(baggage) = otel_context.get::<Baggage>() {
+            context.baggage = baggage.clone();
+        }
+        context
+    }
+
+    /// Returns the context for the current request, or a default Context in a new trace if no
+    /// request is active.
+    ///
+    /// Without the `opentelemetry` feature, the current request is the one whose handler is being
+    /// polled by [`InFlightRequest::execute`](crate::server::InFlightRequest::execute), if any.
+    #[cfg(not(feature = "opentelemetry"))]
+    pub fn current() -> Self {
+        crate::util::scoped::with(&CURRENT, |parent| parent.clone().into_child())
+            .unwrap_or_else(|| Self::root().with_trace_context(trace::Context::new_root()))
+    }
+
+    /// Returns the context of a request independent of any active request: a new trace, the
+    /// default deadline ten seconds from now, and the default priority.
+    pub fn root() -> Self {
+        let deadline = ten_seconds_from_now();
         Self {
-            trace_context: trace::Context::try_from(&span)
-                .unwrap_or_else(|_| trace::Context::default()),
-            deadline: span
-                .context()
-                .get::<Deadline>()
-                .cloned()
-                .unwrap_or_default()
-                .0,
+            trace_context: trace::Context::default(),
+            deadline,
+            idempotency_key: None,
+            routing_key: None,
+            baggage: Baggage::default(),
+            priority: Priority::default(),
+            credentials: None,
+            extensions: Extensions::default(),
+            default_deadline: Some(deadline),
+            default_priority: Some(Priority::default()),
+            untraced: false,
+        }
+    }
+
+    /// Returns the context of a request made while handling this one, for when it isn't made
+    /// within the request's span, so that [`current`](Context::current) can't derive it. Like
+    /// `current`, the child continues the trace in a new span, and inherits the deadline,
+    /// priority, and baggage.
+    pub fn child(&self) -> Self {
+        Inherited::of(self).into_child()
+    }
+
+    /// Like [`child`](Context::child), but the deadline is `margin` earlier, leaving this request
+    /// time to respond after the child's deadline passes.
+    pub fn child_with_margin(&self, margin: Duration) -> Self {
+        let mut child = self.child();
+        child.deadline = self.deadline.checked_sub(margin).unwrap_or_else(util::now);
+        child.default_deadline = None;
+        child
+    }
+
+    /// Sets the deadline.
+    pub fn with_deadline(mut self, deadline: Instant) -> Self {
+        self.deadline = deadline;
+        self
+    }
+
+    /// Sets the deadline to `timeout` from now.
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        self.with_deadline(util::now() + timeout)
+    }
+
+    /// Sets the trace context.
+    pub fn with_trace_context(mut self, trace_context: trace::Context) -> Self {
+        self.trace_context = trace_context;
+        self
+    }
+
+    /// Sets the idempotency key.
+    pub fn with_idempotency_key(mut self, idempotency_key: u64) -> Self {
+        self.idempotency_key = Some(idempotency_key);
+        self
+    }
+
+    /// Sets the routing key.
+    pub fn with_routing_key(mut self, routing_key: u64) -> Self {
+        self.routing_key = Some(routing_key);
+        self
+    }
+
+    /// Sets the baggage entry `key` to `value`.
+    ///
+    /// # Panics
+    ///
+    /// If the entry would exceed the limits of [`Baggage`]; use [`Baggage::insert`] to handle
+    /// that case.
+    pub fn with_baggage(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        if let Err(e) = self.baggage.insert(key, value) {
+            panic!("{e}");
         }
+        self
+    }
+
+    /// Sets the priority.
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self.default_priority = None;
+        self
+    }
+
+    /// Sets the credentials.
+    pub fn with_credentials(mut self, credentials: impl Into<Credentials>) -> Self {
+        self.credentials = Some(credentials.into());
+        self
+    }
+
+    /// Returns how long until the deadline, or zero if it has passed. The deadline is on the
+    /// monotonic clock, so the time remaining doesn't jump with the system clock.
+    pub fn time_remaining(&self) -> Duration {
+        self.deadline.saturating_duration_since(util::now())
+    }
+
+    /// Returns true iff the deadline has passed.
+    pub fn has_expired(&self) -> bool {
+        self.deadline <= util::now()
+    }
+
+    /// Returns true iff the deadline is the default one of [`current`](Context::current), i.e. no
+    /// request was active to inherit a deadline from, and the deadline hasn't been set since.
+    pub fn has_default_deadline(&self) -> bool {
+        self.default_deadline == Some(self.deadline)
+    }
+
+    /// Replaces the default deadline of [`current`](Context::current) with `timeout` from now.
+    /// Deadlines inherited from an active request, or set explicitly, are kept.
+    ///
+    /// Clients of methods declared with `#[tarpc::deadline = "..."]` seed their deadline this way.
+    pub fn seed_deadline(&mut self, timeout: Duration) {
+        if self.has_default_deadline() {
+            self.deadline = util::now() + timeout;
+        }
+    }
+
+    /// Returns true iff the priority is the default one of [`current`](Context::current), i.e. no
+    /// request was active to inherit a priority from, and the priority hasn't been set since.
+    pub fn has_default_priority(&self) -> bool {
+        self.default_priority == Some(self.priority)
+    }
+
+    /// Makes the client send the request without creating a span for it. The request is still
+    /// sent with a trace context, a child of the current one, so that its trace stays connected.
+    ///
+    /// Clients of methods declared with `#[tarpc::no_trace]` skip their spans this way.
+    pub fn skip_trace(&mut self) {
+        self.untraced = true;
     }
 
     /// Returns the ID of the request-scoped trace.
@@ -187,8 +718,14 @@ pub(crate) trait SpanExt {
     fn set_context(&self, context: &Context);
 }
 
+#[cfg(feature = "opentelemetry")]
 impl SpanExt for tracing::Span {
     fn set_context(&self, context: &Context) {
+        // A disabled span has no parent to set, so don't bother building the OpenTelemetry
+        // context.
+        if self.is_disabled() {
+            return;
+        }
         self.set_parent(
             opentelemetry::Context::new()
                 .with_remote_span_context(opentelemetry::trace::SpanContext::new(
@@ -196,9 +733,225 @@ impl SpanExt for tracing::Span {
                     opentelemetry::trace::SpanId::from(context.trace_context.span_id),
                     opentelemetry::trace::TraceFlags::from(context.trace_context.sampling_decision),
                     true,
-                    opentelemetry::trace::TraceState::default(),
+                    opentelemetry::trace::TraceState::from(&context.trace_context.trace_state),
                 ))
-                .with_value(Deadline(context.deadline)),
+                .with_value(Deadline(context.deadline))
+                .with_value(context.baggage.clone())
+                .with_value(context.priority),
+        );
+    }
+}
+
+#[cfg(not(feature = "opentelemetry"))]
+impl SpanExt for tracing::Span {
+    fn set_context(&self, _: &Context) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn baggage_is_limited() {
+        let mut baggage = Baggage::default();
+        for i in 0..Baggage::MAX_ENTRIES {
+            baggage.insert(i.to_string(), "").unwrap();
+        }
+        assert_eq!(
+            baggage.insert("one too many", ""),
+            Err(BaggageError::TooManyEntries)
+        );
+        assert_eq!(baggage.insert("0", "replaced"), Ok(Some("".into())));
+
+        let mut baggage = Baggage::default();
+        let large = "x".repeat(Baggage::MAX_SIZE - 1);
+        baggage.insert("k", large.clone()).unwrap();
+        assert_eq!(baggage.insert("l", ""), Err(BaggageError::TooLarge));
+        assert_eq!(
+            baggage.insert("k", large + "x"),
+            Err(BaggageError::TooLarge)
         );
+        assert_eq!(baggage.get("k").map(str::len), Some(Baggage::MAX_SIZE - 1));
+    }
+
+    #[test]
+    fn builders_set_fields() {
+        let deadline = Instant::now() + Duration::from_secs(60);
+        let ctx = Context::root()
+            .with_deadline(deadline)
+            .with_idempotency_key(8)
+            .with_routing_key(9)
+            .with_priority(Priority::Normal)
+            .with_credentials("token");
+        assert_eq!(ctx.deadline, deadline);
+        assert!(!ctx.has_default_deadline());
+        assert_eq!((ctx.idempotency_key, ctx.routing_key), (Some(8), Some(9)));
+        assert!(!ctx.has_default_priority());
+        assert_eq!(ctx.credentials, Some(Credentials::from("token")));
+
+        let ctx = Context::root();
+        assert!(ctx.has_default_deadline() && ctx.has_default_priority());
+    }
+
+    #[test]
+    #[should_panic(expected = "baggage is larger")]
+    fn oversized_baggage_builder_panics() {
+        let _ = Context::root().with_baggage("k", "x".repeat(Baggage::MAX_SIZE));
+    }
+
+    #[test]
+    fn time_remaining_saturates() {
+        let ctx = Context::root().with_timeout(Duration::from_secs(60));
+        assert!(ctx.time_remaining() > Duration::from_secs(59));
+        assert!(!ctx.has_expired());
+
+        let ctx = ctx.with_deadline(Instant::now());
+        assert_eq!(ctx.time_remaining(), Duration::ZERO);
+        assert!(ctx.has_expired());
+    }
+
+    #[test]
+    fn extensions_are_keyed_by_type() {
+        #[derive(Debug, PartialEq)]
+        struct Principal(&'static str);
+
+        let mut extensions = Extensions::default();
+        extensions.insert(Principal("alice"));
+        extensions.insert(7u32);
+        extensions.insert(Principal("bob"));
+        assert_eq!(extensions.len(), 2);
+        assert_eq!(extensions.get::<Principal>(), Some(&Principal("bob")));
+        assert_eq!(extensions.clone().get::<u32>(), Some(&7));
+        assert!(extensions.remove::<u32>());
+        assert!(!extensions.remove::<u32>());
+        assert_eq!(extensions.get::<u32>(), None);
+    }
+
+    #[test]
+    fn child_continues_the_trace() {
+        let mut rng = rand::thread_rng();
+        let parent = Context::root()
+            .with_trace_context(trace::Context {
+                trace_id: TraceId::random(&mut rng),
+                span_id: trace::SpanId::random(&mut rng),
+                ..trace::Context::default()
+            })
+            .with_timeout(Duration::from_secs(60))
+            .with_priority(Priority::High)
+            .with_baggage("locale", "de-CH")
+            .with_idempotency_key(7);
+
+        let child = parent.child();
+        assert_eq!(child.trace_id(), parent.trace_id());
+        assert_ne!(child.trace_context.span_id, parent.trace_context.span_id);
+        assert_eq!(child.deadline, parent.deadline);
+        assert_eq!(child.priority, Priority::High);
+        assert_eq!(child.baggage.get("locale"), Some("de-CH"));
+        assert_eq!(child.idempotency_key, None);
+
+        let child = parent.child_with_margin(Duration::from_secs(5));
+        assert_eq!(child.deadline + Duration::from_secs(5), parent.deadline);
+        assert!(!child.has_default_deadline());
+    }
+
+    #[cfg(feature = "serde1")]
+    #[test]
+    fn oversized_baggage_fails_to_deserialize() {
+        let entries = (0..=Baggage::MAX_ENTRIES)
+            .map(|i| (i.to_string(), String::new()))
+            .collect::<BTreeMap<_, _>>();
+        let serialized = bincode::serialize(&entries).unwrap();
+        assert!(bincode::deserialize::<Baggage>(&serialized).is_err());
+    }
+
+    #[cfg(feature = "opentelemetry")]
+    #[test]
+    fn current_inherits_baggage_and_priority_of_span() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let subscriber = tracing_subscriber::registry().with(tracing_opentelemetry::layer());
+        tracing::subscriber::with_default(subscriber, || {
+            let mut ctx = current();
+            assert!(ctx.has_default_priority());
+            ctx.baggage.insert("locale", "de-CH").unwrap();
+            ctx.priority = Priority::High;
+            assert!(!ctx.has_default_priority());
+            let span = tracing::info_span!("request");
+            span.set_context(&ctx);
+            let _entered = span.enter();
+            let current = current();
+            assert_eq!(current.baggage.get("locale"), Some("de-CH"));
+            assert_eq!(current.priority, Priority::High);
+            assert!(!current.has_default_priority());
+        });
+    }
+
+    #[cfg(feature = "opentelemetry")]
+    #[test]
+    fn current_without_subscriber_is_root() {
+        tracing::subscriber::with_default(tracing::subscriber::NoSubscriber::default(), || {
+            let span = tracing::info_span!("request");
+            assert!(span.is_disabled());
+            span.set_context(&Context::root().with_priority(Priority::High));
+            let _entered = span.enter();
+            let current = current();
+            assert!(current.has_default_deadline());
+            assert!(current.has_default_priority());
+        });
+    }
+
+    #[cfg(feature = "opentelemetry")]
+    #[test]
+    fn current_inherits_trace_state_of_span() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let subscriber = tracing_subscriber::registry().with(tracing_opentelemetry::layer());
+        tracing::subscriber::with_default(subscriber, || {
+            let mut ctx = current();
+            ctx.trace_context.trace_state =
+                trace::TraceState::parse("rojo=00f067aa0ba902b7,congo=t61rcWkgMzE").unwrap();
+            let span = tracing::info_span!("request");
+            span.set_context(&ctx);
+            let _entered = span.enter();
+            assert_eq!(
+                current().trace_context.trace_state,
+                ctx.trace_context.trace_state
+            );
+        });
+    }
+
+    #[cfg(feature = "rkyv")]
+    #[test]
+    fn deadlines_are_archived_since_the_unix_epoch() {
+        use rkyv::Deserialize;
+        use std::time::SystemTime;
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+        #[archive(check_bytes)]
+        struct Wrapper(#[with(RkyvInstant)] Instant);
+
+        let since_unix_epoch = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            + Duration::from_secs(60);
+        // The archive of a `Duration`: its seconds, its nanoseconds, and padding.
+        let mut pinned = rkyv::AlignedVec::new();
+        pinned.extend_from_slice(&since_unix_epoch.as_secs().to_ne_bytes());
+        pinned.extend_from_slice(&0u32.to_ne_bytes());
+        pinned.extend_from_slice(&[0; 4]);
+
+        let archived =
+            rkyv::to_bytes::<_, 256>(&Wrapper(crate::util::now() + Duration::from_secs(60)))
+                .unwrap();
+        assert_eq!(archived.len(), pinned.len());
+        let secs = u64::from_ne_bytes(archived[..8].try_into().unwrap());
+        assert!(secs + 1 >= since_unix_epoch.as_secs() && secs <= since_unix_epoch.as_secs() + 1);
+
+        let Wrapper(deadline) = rkyv::check_archived_root::<Wrapper>(&pinned)
+            .unwrap()
+            .deserialize(&mut rkyv::Infallible)
+            .unwrap();
+        let remaining = deadline.saturating_duration_since(crate::util::now());
+        assert!(remaining > Duration::from_secs(58) && remaining <= Duration::from_secs(60));
     }
 }
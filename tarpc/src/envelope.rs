@@ -0,0 +1,715 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! The serde encoding of the envelopes, [`ClientMessage`] and [`Response`].
+//!
+//! The fields added to the envelopes since tarpc 0.34 have defaults, which self-describing formats
+//! like JSON fill in when older peers leave the fields out. Compact formats like bincode encode a
+//! struct as its fields in order, without names, so a peer that doesn't know a field misreads
+//! whatever follows it. In compact formats, an envelope that uses none of the added fields is
+//! therefore encoded exactly as 0.34 encoded it, and any other envelope is encoded as a variant
+//! that 0.34 didn't have, which older peers fail to decode rather than misread.
+
+use crate::{
+    context, trace, util, CancellationReason, ClientMessage, Request, Response, ResponseExtensions,
+    ServerError,
+};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::{io, time::Instant};
+
+/// The encoding of envelopes in human-readable formats: all of their fields, by name.
+mod fields {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(remote = "ClientMessage")]
+    pub enum ClientMessageDef<T> {
+        Request(Request<T>),
+        Cancel {
+            #[serde(default)]
+            trace_context: trace::Context,
+            request_id: u64,
+            #[serde(default)]
+            reason: CancellationReason,
+            #[serde(default)]
+            ack: bool,
+        },
+        CancelBatch {
+            request_ids: Vec<u64>,
+            #[serde(default)]
+            reason: CancellationReason,
+        },
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(remote = "Response")]
+    pub struct ResponseDef<T> {
+        pub request_id: u64,
+        pub message: Result<T, ServerError>,
+        #[serde(default)]
+        pub extensions: ResponseExtensions,
+        #[serde(default)]
+        pub partial: bool,
+    }
+
+    #[cfg(feature = "serde-transport")]
+    #[derive(Deserialize)]
+    #[serde(remote = "crate::serde_transport::ClientMessageHeader")]
+    pub enum ClientMessageHeaderDef {
+        Request { context: context::Context, id: u64 },
+    }
+}
+
+/// The encoding of envelopes in compact formats: the envelopes of tarpc 0.34, followed by
+/// variants for the envelopes that use fields added since.
+mod compact {
+    use super::*;
+
+    /// The trace context of 0.34, without a trace state.
+    #[derive(Clone, Copy, Serialize, Deserialize)]
+    #[serde(rename = "Context")]
+    pub struct TraceContext {
+        trace_id: trace::TraceId,
+        span_id: trace::SpanId,
+        sampling_decision: trace::SamplingDecision,
+    }
+
+    impl TraceContext {
+        /// Returns the trace context of 0.34 if `trace_context` has nothing more.
+        pub fn of(trace_context: &trace::Context) -> Option<Self> {
+            let trace::Context {
+                trace_id,
+                span_id,
+                sampling_decision,
+                trace_state,
+            } = trace_context;
+            trace_state.is_empty().then_some(Self {
+                trace_id: *trace_id,
+                span_id: *span_id,
+                sampling_decision: *sampling_decision,
+            })
+        }
+    }
+
+    impl From<TraceContext> for trace::Context {
+        fn from(trace_context: TraceContext) -> Self {
+            Self {
+                trace_id: trace_context.trace_id,
+                span_id: trace_context.span_id,
+                sampling_decision: trace_context.sampling_decision,
+                trace_state: trace::TraceState::default(),
+            }
+        }
+    }
+
+    /// The request context of 0.34.
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "Context")]
+    pub struct Context {
+        #[serde(with = "context::absolute_to_relative_time")]
+        deadline: Instant,
+        trace_context: TraceContext,
+    }
+
+    impl Context {
+        /// Returns the request context of 0.34 if `context` sends nothing more.
+        pub fn of(context: &context::Context) -> Option<Self> {
+            // Destructured so that fields added to the context must be considered here.
+            let context::Context {
+                deadline,
+                trace_context,
+                idempotency_key,
+                routing_key,
+                baggage,
+                priority,
+                credentials,
+                extensions: _,
+                default_deadline: _,
+                default_priority: _,
+                untraced: _,
+            } = context;
+            let only_baseline = idempotency_key.is_none()
+                && routing_key.is_none()
+                && baggage.is_empty()
+                && *priority == context::Priority::default()
+                && credentials.is_none();
+            Some(Self {
+                deadline: *deadline,
+                trace_context: TraceContext::of(trace_context).filter(|_| only_baseline)?,
+            })
+        }
+    }
+
+    impl From<Context> for context::Context {
+        fn from(baseline: Context) -> Self {
+            let mut context = context::Context::root();
+            context.deadline = baseline.deadline;
+            context.default_deadline = None;
+            context.default_priority = None;
+            context.trace_context = baseline.trace_context.into();
+            context
+        }
+    }
+
+    /// A request of 0.34.
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "Request")]
+    pub struct BaselineRequest<M> {
+        context: Context,
+        id: u64,
+        message: M,
+    }
+
+    impl<'a, T> BaselineRequest<&'a T> {
+        /// Returns the request of 0.34 if `request` sends nothing more.
+        pub fn of(request: &'a Request<T>) -> Option<Self> {
+            let Request {
+                context,
+                id,
+                message,
+                oneway,
+            } = request;
+            Some(Self {
+                context: Context::of(context).filter(|_| !oneway)?,
+                id: *id,
+                message,
+            })
+        }
+    }
+
+    impl<T> From<BaselineRequest<T>> for Request<T> {
+        fn from(request: BaselineRequest<T>) -> Self {
+            Self {
+                context: request.context.into(),
+                id: request.id,
+                message: request.message,
+                oneway: false,
+            }
+        }
+    }
+
+    /// A client message, generic over whether its fields are borrowed, for serializing, or
+    /// owned, for deserializing.
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "ClientMessage")]
+    pub enum ClientMessage<M, R, Ids, Trace> {
+        Request(BaselineRequest<M>),
+        Cancel {
+            trace_context: TraceContext,
+            request_id: u64,
+        },
+        CancelBatch {
+            request_ids: Ids,
+            reason: CancellationReason,
+        },
+        ExtendedRequest(R),
+        ExtendedCancel {
+            trace_context: Trace,
+            request_id: u64,
+            reason: CancellationReason,
+            ack: bool,
+        },
+    }
+
+    /// The server error of 0.34.
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "ServerError")]
+    pub struct ServerError<Detail> {
+        #[serde(serialize_with = "util::serde::serialize_io_error_kind_as_u32")]
+        #[serde(deserialize_with = "util::serde::deserialize_io_error_kind_from_u32")]
+        kind: io::ErrorKind,
+        detail: Detail,
+    }
+
+    impl<'a> ServerError<&'a str> {
+        /// Returns the server error of 0.34 if `error` sends nothing more.
+        pub fn of(error: &'a super::ServerError) -> Option<Self> {
+            let super::ServerError {
+                kind,
+                detail,
+                application,
+                retry_after,
+                reason,
+            } = error;
+            (application.is_none() && retry_after.is_none() && reason.is_none()).then_some(Self {
+                kind: *kind,
+                detail,
+            })
+        }
+    }
+
+    impl From<ServerError<String>> for super::ServerError {
+        fn from(error: ServerError<String>) -> Self {
+            Self::new(error.kind, error.detail)
+        }
+    }
+
+    /// The message of a response: the `Ok` and `Err` variants of the `Result` sent by 0.34,
+    /// followed by a variant for responses that use fields added since.
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "Result")]
+    pub enum Message<M, E, X> {
+        Ok(M),
+        Err(ServerError<E>),
+        Extended(X),
+    }
+
+    /// The fields of a response other than its request ID, for responses that use fields added
+    /// since 0.34.
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "ExtendedResponse")]
+    pub struct ExtendedResponse<M, X> {
+        pub message: M,
+        pub extensions: X,
+        pub partial: bool,
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "Response")]
+    pub struct Response<M> {
+        pub request_id: u64,
+        pub message: M,
+    }
+
+    #[cfg(feature = "serde-transport")]
+    #[derive(Deserialize)]
+    #[serde(rename = "ClientMessage")]
+    pub enum ClientMessageHeader {
+        Request { context: Context, id: u64 },
+        Cancel,
+        CancelBatch,
+        ExtendedRequest { context: context::Context, id: u64 },
+    }
+}
+
+impl<T: Serialize> Serialize for ClientMessage<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            return fields::ClientMessageDef::serialize(self, serializer);
+        }
+        type Compact<'a, T> =
+            compact::ClientMessage<&'a T, &'a Request<T>, &'a [u64], &'a trace::Context>;
+        let message: Compact<T> = match self {
+            ClientMessage::Request(request) => match compact::BaselineRequest::of(request) {
+                Some(request) => compact::ClientMessage::Request(request),
+                None => compact::ClientMessage::ExtendedRequest(request),
+            },
+            ClientMessage::Cancel {
+                trace_context,
+                request_id,
+                reason,
+                ack,
+            } => match compact::TraceContext::of(trace_context) {
+                Some(trace_context) if *reason == CancellationReason::default() && !ack => {
+                    compact::ClientMessage::Cancel {
+                        trace_context,
+                        request_id: *request_id,
+                    }
+                }
+                _ => compact::ClientMessage::ExtendedCancel {
+                    trace_context,
+                    request_id: *request_id,
+                    reason: *reason,
+                    ack: *ack,
+                },
+            },
+            ClientMessage::CancelBatch {
+                request_ids,
+                reason,
+            } => compact::ClientMessage::CancelBatch {
+                request_ids,
+                reason: *reason,
+            },
+        };
+        message.serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for ClientMessage<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            return fields::ClientMessageDef::deserialize(deserializer);
+        }
+        type Compact<T> = compact::ClientMessage<T, Request<T>, Vec<u64>, trace::Context>;
+        Ok(match Compact::deserialize(deserializer)? {
+            compact::ClientMessage::Request(request) => ClientMessage::Request(request.into()),
+            compact::ClientMessage::Cancel {
+                trace_context,
+                request_id,
+            } => ClientMessage::Cancel {
+                trace_context: trace_context.into(),
+                request_id,
+                reason: CancellationReason::default(),
+                ack: false,
+            },
+            compact::ClientMessage::CancelBatch {
+                request_ids,
+                reason,
+            } => ClientMessage::CancelBatch {
+                request_ids,
+                reason,
+            },
+            compact::ClientMessage::ExtendedRequest(request) => ClientMessage::Request(request),
+            compact::ClientMessage::ExtendedCancel {
+                trace_context,
+                request_id,
+                reason,
+                ack,
+            } => ClientMessage::Cancel {
+                trace_context,
+                request_id,
+                reason,
+                ack,
+            },
+        })
+    }
+}
+
+impl<T: Serialize> Serialize for Response<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            return fields::ResponseDef::serialize(self, serializer);
+        }
+        let Response {
+            request_id,
+            message,
+            extensions,
+            partial,
+        } = self;
+        let baseline = match message {
+            _ if !extensions.is_empty() || *partial => None,
+            Ok(message) => Some(compact::Message::Ok(message)),
+            Err(error) => compact::ServerError::of(error).map(compact::Message::Err),
+        };
+        compact::Response {
+            request_id: *request_id,
+            message: baseline.unwrap_or(compact::Message::Extended(compact::ExtendedResponse {
+                message,
+                extensions,
+                partial: *partial,
+            })),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de, T: Deserialize<'de>> Deserialize<'de> for Response<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if deserializer.is_human_readable() {
+            return fields::ResponseDef::deserialize(deserializer);
+        }
+        type Compact<T> = compact::Response<
+            compact::Message<
+                T,
+                String,
+                compact::ExtendedResponse<Result<T, ServerError>, ResponseExtensions>,
+            >,
+        >;
+        let response = Compact::deserialize(deserializer)?;
+        let (message, extensions, partial) = match response.message {
+            compact::Message::Ok(message) => (Ok(message), ResponseExtensions::default(), false),
+            compact::Message::Err(error) => {
+                (Err(error.into()), ResponseExtensions::default(), false)
+            }
+            compact::Message::Extended(extended) => {
+                (extended.message, extended.extensions, extended.partial)
+            }
+        };
+        Ok(Response {
+            request_id: response.request_id,
+            message,
+            extensions,
+            partial,
+        })
+    }
+}
+
+#[cfg(feature = "serde-transport")]
+impl<'de> Deserialize<'de> for crate::serde_transport::ClientMessageHeader {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use crate::serde_transport::ClientMessageHeader;
+        use serde::de::Error;
+
+        if deserializer.is_human_readable() {
+            return fields::ClientMessageHeaderDef::deserialize(deserializer);
+        }
+        match compact::ClientMessageHeader::deserialize(deserializer)? {
+            compact::ClientMessageHeader::Request { context, id } => {
+                Ok(ClientMessageHeader::Request {
+                    context: context.into(),
+                    id,
+                })
+            }
+            compact::ClientMessageHeader::ExtendedRequest { context, id } => {
+                Ok(ClientMessageHeader::Request { context, id })
+            }
+            compact::ClientMessageHeader::Cancel | compact::ClientMessageHeader::CancelBatch => {
+                Err(D::Error::custom("the message isn't a request"))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use std::time::Duration;
+
+    /// The envelopes of tarpc 0.34, as it declared them.
+    mod v0_34 {
+        use super::*;
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        pub enum ClientMessage<T> {
+            Request(Request<T>),
+            Cancel {
+                #[serde(default)]
+                trace_context: TraceContext,
+                request_id: u64,
+            },
+        }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        pub struct Request<T> {
+            pub context: Context,
+            pub id: u64,
+            pub message: T,
+        }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        pub struct Context {
+            /// Sent as the time remaining.
+            pub deadline: Duration,
+            pub trace_context: TraceContext,
+        }
+
+        #[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+        pub struct TraceContext {
+            pub trace_id: trace::TraceId,
+            pub span_id: trace::SpanId,
+            pub sampling_decision: trace::SamplingDecision,
+        }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        pub struct Response<T> {
+            pub request_id: u64,
+            pub message: Result<T, ServerError>,
+        }
+
+        #[derive(Debug, PartialEq, Serialize, Deserialize)]
+        pub struct ServerError {
+            #[serde(serialize_with = "util::serde::serialize_io_error_kind_as_u32")]
+            #[serde(deserialize_with = "util::serde::deserialize_io_error_kind_from_u32")]
+            pub kind: io::ErrorKind,
+            pub detail: String,
+        }
+    }
+
+    fn v0_34_trace_context() -> v0_34::TraceContext {
+        v0_34::TraceContext {
+            trace_id: 0x0102_0304_0506_0708_090a_0b0c_0d0e_0f10.into(),
+            span_id: 0x1112_1314_1516_1718.into(),
+            sampling_decision: trace::SamplingDecision::Sampled,
+        }
+    }
+
+    fn trace_context() -> trace::Context {
+        trace::Context {
+            trace_id: 0x0102_0304_0506_0708_090a_0b0c_0d0e_0f10.into(),
+            span_id: 0x1112_1314_1516_1718.into(),
+            sampling_decision: trace::SamplingDecision::Sampled,
+            trace_state: trace::TraceState::default(),
+        }
+    }
+
+    fn request(configure: impl FnOnce(&mut Request<String>)) -> ClientMessage<String> {
+        let mut context = context::Context::root();
+        context.deadline = util::now() + Duration::from_secs(3);
+        context.trace_context = trace_context();
+        let mut request = Request {
+            context,
+            id: 7,
+            message: "hello".into(),
+            oneway: false,
+        };
+        configure(&mut request);
+        ClientMessage::Request(request)
+    }
+
+    fn v0_34_request() -> v0_34::ClientMessage<String> {
+        v0_34::ClientMessage::Request(v0_34::Request {
+            context: v0_34::Context {
+                deadline: Duration::from_secs(3),
+                trace_context: v0_34_trace_context(),
+            },
+            id: 7,
+            message: "hello".into(),
+        })
+    }
+
+    fn response(message: Result<String, ServerError>) -> Response<String> {
+        Response {
+            request_id: 7,
+            message,
+            extensions: ResponseExtensions::default(),
+            partial: false,
+        }
+    }
+
+    // Time is paused so that deadlines are sent as exactly the time remaining.
+    #[tokio::test(start_paused = true)]
+    async fn client_messages_without_new_fields_are_encoded_as_by_0_34() -> bincode::Result<()> {
+        let encoded = bincode::serialize(&request(|_| {}))?;
+        assert_eq!(encoded, bincode::serialize(&v0_34_request())?);
+        assert_matches!(
+            bincode::deserialize(&bincode::serialize(&v0_34_request())?)?,
+            ClientMessage::<String>::Request(Request { id: 7, ref message, oneway: false, ref context })
+                if message == "hello"
+                    && context.deadline == util::now() + Duration::from_secs(3)
+                    && context.trace_context == trace_context()
+        );
+
+        let cancel = ClientMessage::<String>::Cancel {
+            trace_context: trace_context(),
+            request_id: 7,
+            reason: CancellationReason::default(),
+            ack: false,
+        };
+        let v0_34_cancel = v0_34::ClientMessage::<String>::Cancel {
+            trace_context: v0_34_trace_context(),
+            request_id: 7,
+        };
+        assert_eq!(
+            bincode::serialize(&cancel)?,
+            bincode::serialize(&v0_34_cancel)?
+        );
+        assert_matches!(
+            bincode::deserialize(&bincode::serialize(&v0_34_cancel)?)?,
+            ClientMessage::<String>::Cancel {
+                request_id: 7,
+                ack: false,
+                ..
+            }
+        );
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn client_messages_with_new_fields_are_rejected_by_0_34() -> bincode::Result<()> {
+        let extended = [
+            request(|request| request.oneway = true),
+            request(|request| {
+                request.context.trace_context.trace_state =
+                    trace::TraceState::parse("vendor=x").unwrap()
+            }),
+            request(|request| request.context.routing_key = Some(3)),
+            request(|request| {
+                request.context.baggage.insert("locale", "fr").unwrap();
+            }),
+            ClientMessage::Cancel {
+                trace_context: trace_context(),
+                request_id: 7,
+                reason: CancellationReason::default(),
+                ack: true,
+            },
+        ];
+        for message in extended {
+            let encoded = bincode::serialize(&message)?;
+            assert!(bincode::deserialize::<v0_34::ClientMessage<String>>(&encoded).is_err());
+            let decoded: ClientMessage<String> = bincode::deserialize(&encoded)?;
+            assert_eq!(bincode::serialize(&decoded)?, encoded);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn responses_without_new_fields_are_encoded_as_by_0_34() -> bincode::Result<()> {
+        let cases = [
+            (
+                response(Ok("hi".into())),
+                v0_34::Response {
+                    request_id: 7,
+                    message: Ok(String::from("hi")),
+                },
+            ),
+            (
+                response(Err(ServerError::new(
+                    io::ErrorKind::NotFound,
+                    "gone".into(),
+                ))),
+                v0_34::Response {
+                    request_id: 7,
+                    message: Err(v0_34::ServerError {
+                        kind: io::ErrorKind::NotFound,
+                        detail: "gone".into(),
+                    }),
+                },
+            ),
+        ];
+        for (response, v0_34_response) in cases {
+            let encoded = bincode::serialize(&v0_34_response)?;
+            assert_eq!(bincode::serialize(&response)?, encoded);
+            assert_eq!(
+                bincode::deserialize::<Response<String>>(&encoded)?,
+                response
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn responses_with_new_fields_are_rejected_by_0_34() -> bincode::Result<()> {
+        let mut with_extensions = response(Ok("hi".into()));
+        with_extensions.extensions.insert("version", "2.0");
+        let mut partial = response(Ok("hi".into()));
+        partial.partial = true;
+        let application_error = response(Err(crate::ApplicationError::new(3, "no").into()));
+
+        for response in [with_extensions, partial, application_error] {
+            let encoded = bincode::serialize(&response)?;
+            assert!(bincode::deserialize::<v0_34::Response<String>>(&encoded).is_err());
+            assert_eq!(
+                bincode::deserialize::<Response<String>>(&encoded)?,
+                response
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn unimplemented_errors_keep_their_kind() -> Result<(), Box<dyn std::error::Error>> {
+        let unimplemented = response(Err(ServerError::unimplemented("no such method".into())));
+        let decoded: Response<String> = bincode::deserialize(&bincode::serialize(&unimplemented)?)?;
+        assert_eq!(decoded, unimplemented);
+        let decoded: Response<String> =
+            serde_json::from_str(&serde_json::to_string(&unimplemented)?)?;
+        assert_eq!(decoded, unimplemented);
+        assert_matches!(
+            decoded.message,
+            Err(ServerError {
+                kind: io::ErrorKind::Unsupported,
+                ..
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn human_readable_envelopes_carry_new_fields_by_name() -> serde_json::Result<()> {
+        let mut partial = response(Ok("hi".into()));
+        partial.partial = true;
+        let json = serde_json::to_value(&partial)?;
+        assert_eq!(json["partial"], true);
+        assert_eq!(serde_json::from_value::<Response<String>>(json)?, partial);
+
+        // 0.34 peers leave out the new fields, which take their defaults.
+        let v0_34: Response<String> =
+            serde_json::from_str(r#"{"request_id":7,"message":{"Ok":"hi"}}"#)?;
+        assert_eq!(v0_34, response(Ok("hi".into())));
+        Ok(())
+    }
+}
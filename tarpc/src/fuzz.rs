@@ -0,0 +1,294 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Provides fuzzing harnesses for services, to catch panics decoding and serving requests.
+//!
+//! With `#[tarpc::service(fuzz = true)]`, the generated request enum implements
+//! [`Arbitrary`](arbitrary::Arbitrary) and has a `fuzz` fn, which runs a service on a fuzzer's
+//! input:
+//!
+//! 1. A request is either decoded from the raw input with bincode, or generated from it with
+//!    `Arbitrary` and sent through a bincode round trip, so that both malformed and well-formed
+//!    requests reach the service.
+//! 2. The request is served to completion with [`block_on`](futures::executor::block_on).
+//! 3. The response is serialized with bincode.
+//!
+//! Args of the service's methods must implement `Arbitrary`. Handlers that need a Tokio runtime
+//! should enter one in the fuzz target.
+//!
+//! # Example
+//!
+//! With [cargo-fuzz](https://github.com/rust-fuzz/cargo-fuzz), a fuzz target is one line:
+//!
+//! ```rust
+//! # use tarpc::context;
+//! #[tarpc::service(fuzz = true)]
+//! trait World {
+//!     async fn hello(name: String) -> String;
+//! }
+//!
+//! #[derive(Clone)]
+//! struct WorldServer;
+//!
+//! impl World for WorldServer {
+//!     async fn hello(self, _: context::Context, name: String) -> String {
+//!         format!("Hello, {name}!")
+//!     }
+//! }
+//!
+//! // In fuzz/fuzz_targets/world.rs:
+//! // libfuzzer_sys::fuzz_target!(|data: &[u8]| WorldRequest::fuzz(WorldServer, data));
+//! WorldRequest::fuzz(WorldServer, b"fuzzer input");
+//! ```
+//!
+//! # Decoding frames
+//!
+//! With the `serde-transport` and `serde-transport-bincode` features, [`decode_requests`] and
+//! [`decode_responses`] read a fuzzer's input as the byte stream of a connection, to check that
+//! decoding whatever a peer sends never panics. They exercise the length-delimited framing, the
+//! bincode format and the envelopes of the codecs in [`serde_transport`](crate::serde_transport).
+//! The targets in the `fuzz` directory of the repository run them.
+
+use crate::{
+    context,
+    server::{body::Body, Serve},
+};
+use arbitrary::{Arbitrary, Unstructured};
+use futures::{executor::block_on, StreamExt};
+use serde::{de::DeserializeOwned, Serialize};
+
+pub use arbitrary;
+
+/// Serves the request decoded or generated from `data`, then serializes the response.
+pub fn run<S>(serve: S, data: &[u8])
+where
+    S: Serve,
+    S::Req: Serialize + DeserializeOwned + for<'a> Arbitrary<'a>,
+    S::Resp: Serialize,
+{
+    if let Some(response) = serve_input(serve, data) {
+        serialize(&response);
+    }
+}
+
+/// Serves the request decoded or generated from `data`, then serializes each response of the
+/// body. For services with streaming methods.
+pub fn run_body<S, Resp>(serve: S, data: &[u8])
+where
+    S: Serve<Resp = Body<Resp>>,
+    S::Req: Serialize + DeserializeOwned + for<'a> Arbitrary<'a>,
+    Resp: Serialize,
+{
+    if let Some(body) = serve_input(serve, data) {
+        block_on(body.for_each(|response| {
+            if let Ok(response) = response {
+                serialize(&response);
+            }
+            futures::future::ready(())
+        }));
+    }
+}
+
+fn serve_input<S>(serve: S, data: &[u8]) -> Option<S::Resp>
+where
+    S: Serve,
+    S::Req: Serialize + DeserializeOwned + for<'a> Arbitrary<'a>,
+{
+    let request = decode_input(data)?;
+    block_on(serve.serve(context::current(), request)).ok()
+}
+
+/// Returns the request decoded or generated from `data`, or None if `data` doesn't decode.
+fn decode_input<Req>(data: &[u8]) -> Option<Req>
+where
+    Req: Serialize + DeserializeOwned + for<'a> Arbitrary<'a>,
+{
+    let (&mode, data) = data.split_first()?;
+    if mode % 2 == 0 {
+        return bincode::deserialize(data).ok();
+    }
+    let request = Req::arbitrary_take_rest(Unstructured::new(data)).ok()?;
+    let bytes = serialize(&request);
+    Some(bincode::deserialize(&bytes).expect("a serialized request failed to deserialize"))
+}
+
+fn serialize<T: Serialize>(value: &T) -> Vec<u8> {
+    bincode::serialize(value).expect("failed to serialize")
+}
+
+#[cfg(all(feature = "serde-transport", feature = "serde-transport-bincode"))]
+pub use frames::{decode_requests, decode_responses};
+
+#[cfg(all(feature = "serde-transport", feature = "serde-transport-bincode"))]
+mod frames {
+    use crate::{
+        serde_transport::{ClientMessageHeader, LazyArgs, Passthrough, Recoverable, Transport},
+        ClientMessage, Response,
+    };
+    use bincode::Options;
+    use futures::{executor::block_on, Stream, StreamExt};
+    use serde::{de::DeserializeOwned, Serialize};
+    use std::{
+        io,
+        pin::Pin,
+        task::{Context, Poll},
+    };
+    use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+    use tokio_serde::formats::Bincode;
+
+    /// Reads `data` as the bytes a server receives, decoding requests with the codec chosen by
+    /// its first byte:
+    ///
+    /// - 0: [`Bincode`]
+    /// - 1: [`Recoverable`] bincode
+    /// - 2: [`Passthrough`] bincode
+    /// - 3: [`LazyArgs`] bincode, whose args are then decoded
+    pub fn decode_requests<Req>(data: &[u8])
+    where
+        Req: DeserializeOwned + Serialize + Unpin,
+    {
+        let Some((&mode, data)) = data.split_first() else {
+            return;
+        };
+        match mode {
+            0 => read::<ClientMessage<Req>, Response<()>, _>(data, Bincode::default()),
+            1 => read::<ClientMessage<Req>, Response<()>, _>(
+                data,
+                Recoverable::new(
+                    Bincode::default(),
+                    Bincode::<ClientMessageHeader, (), _>::from(
+                        bincode::DefaultOptions::new().allow_trailing_bytes(),
+                    ),
+                ),
+            ),
+            2 => read::<ClientMessage<Req>, Response<()>, _>(
+                data,
+                Passthrough::new(Bincode::default()),
+            ),
+            3 => {
+                let codec = LazyArgs::new(
+                    Bincode::<_, Response<()>>::default(),
+                    Bincode::<Req, Req>::default(),
+                );
+                let transport = Transport::<_, _, Response<()>, _>::from((Input(data), codec));
+                block_on(transport.for_each(|message| {
+                    if let Ok(ClientMessage::Request(request)) = message {
+                        let _ = request.message.decode();
+                    }
+                    futures::future::ready(())
+                }));
+            }
+            _ => {}
+        }
+    }
+
+    /// Reads `data` as the bytes a client receives, decoding responses with the codec chosen by
+    /// its first byte:
+    ///
+    /// - 0: [`Bincode`]
+    /// - 1: [`Passthrough`] bincode
+    pub fn decode_responses<Resp>(data: &[u8])
+    where
+        Resp: DeserializeOwned + Serialize + Unpin,
+    {
+        let Some((&mode, data)) = data.split_first() else {
+            return;
+        };
+        match mode {
+            0 => read::<Response<Resp>, ClientMessage<()>, _>(data, Bincode::default()),
+            1 => read::<Response<Resp>, ClientMessage<()>, _>(
+                data,
+                Passthrough::new(Bincode::default()),
+            ),
+            _ => {}
+        }
+    }
+
+    /// Reads all the messages in `data` with `codec`.
+    fn read<Item, SinkItem, Codec>(data: &[u8], codec: Codec)
+    where
+        Codec: tokio_serde::Serializer<SinkItem> + tokio_serde::Deserializer<Item>,
+        for<'a> Transport<Input<'a>, Item, SinkItem, Codec>: Stream<Item = io::Result<Item>>,
+    {
+        block_on(Transport::from((Input(data), codec)).for_each(|_| futures::future::ready(())));
+    }
+
+    /// A byte stream that reads a fuzzer's input and discards what's written to it.
+    struct Input<'a>(&'a [u8]);
+
+    impl AsyncRead for Input<'_> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.0).poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncWrite for Input<'_> {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            Poll::Ready(Ok(buf.len()))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inputs_decode_or_generate_requests() {
+        assert_eq!(decode_input::<(u8, u8)>(&[]), None);
+        assert_eq!(decode_input::<(u8, u8)>(&[0, 1, 2]), Some((1, 2)));
+        assert_eq!(decode_input::<(u8, u8)>(&[0, 1]), None);
+        assert!(decode_input::<(u8, u8)>(&[1]).is_some());
+    }
+
+    #[cfg(all(feature = "serde-transport", feature = "serde-transport-bincode"))]
+    #[test]
+    fn hostile_frames_decode_without_panicking() {
+        use bincode::Options;
+
+        let request = bincode::DefaultOptions::new()
+            .serialize(&crate::ClientMessage::Request(crate::Request {
+                context: context::current(),
+                id: 1,
+                message: String::from("hi"),
+                oneway: false,
+            }))
+            .unwrap();
+        let mut frame = (request.len() as u32).to_be_bytes().to_vec();
+        frame.extend_from_slice(&request);
+
+        for mode in 0..=4 {
+            let inputs = [
+                vec![mode],
+                vec![mode, 0xff, 0xff, 0xff, 0xff, 0],
+                vec![mode, 0, 0, 0, 4, 0xff, 0xff, 0xff, 0xff],
+                [&[mode][..], &frame].concat(),
+                [&[mode][..], &frame[..frame.len() - 1]].concat(),
+                [&[mode][..], &frame, &frame[..7]].concat(),
+            ];
+            for input in inputs {
+                decode_requests::<String>(&input);
+                decode_responses::<String>(&input);
+            }
+        }
+    }
+}
@@ -46,9 +46,16 @@
 //!   server. Even for applications not connected to a distributed tracing collector, the
 //!   instrumentation can also be ingested by regular loggers like
 //!   [env_logger](https://github.com/env-logger-rs/env_logger/).
+//!
+//!   The OpenTelemetry integration is the default `opentelemetry` Cargo feature. Builds without
+//!   it don't depend on OpenTelemetry at all: contexts are propagated to
+//!   [`context::current`] only within the handler of a request, and requests made outside of
+//!   one start new traces with random IDs.
 //! - Serde serialization: enabling the `serde1` Cargo feature will make service requests and
 //!   responses `Serialize + Deserialize`. It's entirely optional, though: in-memory transports can
 //!   be used, as well, so the price of serialization doesn't have to be paid when it's not needed.
+//!   Builds enabling only the `rkyv` feature don't depend on serde at all: services derive only
+//!   rkyv's traits, and their `#[serde(...)]` attributes are dropped.
 //!
 //! ## Usage
 //! Add to your `Cargo.toml` dependencies:
@@ -57,6 +64,9 @@
 //! tarpc = "0.29"
 //! ```
 //!
+//! tarpc requires Rust 1.75 or later. Service traits are declared with async fns, and there's no
+//! fallback for older compilers.
+//!
 //! The `tarpc::service` attribute expands to a collection of items that form an rpc service.
 //! These generated types make it easy and ergonomic to write servers with less boilerplate.
 //! Simply implement the generated service trait, and you're off to the races!
@@ -212,6 +222,9 @@ pub use serde;
 #[doc(hidden)]
 pub use rkyv;
 
+#[doc(hidden)]
+pub use futures;
+
 #[cfg(feature = "serde-transport")]
 pub use {tokio_serde, tokio_util};
 
@@ -242,6 +255,9 @@ pub use tarpc_plugins::derive_rkyv;
 /// Attributes can be attached to each rpc. These attributes
 /// will then be attached to the generated service traits'
 /// corresponding `fn`s, as well as to the client stubs' RPCs.
+/// Doc comments also document the methods' request variants,
+/// and a method marked `#[deprecated]` warns its callers through the client,
+/// but not its implementers.
 ///
 /// The following items are expanded in the enclosing module:
 ///
@@ -249,25 +265,386 @@ pub use tarpc_plugins::derive_rkyv;
 ///   * `fn serve` -- turns a service impl into a request handler.
 /// * `Client` -- a client stub with a fn for each RPC.
 ///   * `fn new_stub` -- creates a new Client stub.
+/// * `DynServiceStub` -- an object-safe trait with a fn for each RPC, implemented by the client
+///   over a [`client::Channel`], so that code accepting a `&dyn DynServiceStub` can be handed real
+///   clients and mocks alike.
+///
+/// The service trait can have type parameters, with bounds, to define a family of services that
+/// only differ by the types they send. The client stub is generic over the same parameters, while
+/// the request and response enums are generic over the parameters their variants use:
+///
+/// ```
+/// #[tarpc::service]
+/// trait Store<K: std::hash::Hash + Eq, V> {
+///     async fn put(key: K, value: V);
+///     async fn get(key: K) -> Option<V>;
+/// }
+///
+/// type StringStore = StoreClient<String, String>;
+/// ```
+///
+/// Like any trait, the service trait can also have supertraits, a where clause, and associated
+/// consts, for bounds and constants that implementations of the service share. Predicates of the
+/// where clause that don't bound `Self` also bound the generated client and serve fn.
+///
+/// A service can declare the error type of its methods once, with `type Error = E;`, so that its
+/// methods returning `Result<T>` return `Result<T, E>`. The client returns their errors as
+/// [`CallError::Service`](client::CallError::Service), alongside RPC errors as
+/// [`CallError::Rpc`](client::CallError::Rpc), rather than in nested results:
+///
+/// ```
+/// #[tarpc::service]
+/// trait Store {
+///     type Error = String;
+///
+///     async fn get(key: String) -> Result<Vec<u8>>;
+///     async fn put(key: String, value: Vec<u8>) -> Result<()>;
+/// }
+///
+/// #[derive(Clone)]
+/// struct StoreServer;
+///
+/// impl Store for StoreServer {
+///     async fn get(self, _: tarpc::context::Context, key: String) -> Result<Vec<u8>, String> {
+///         Err(format!("{key} not found"))
+///     }
+///
+///     async fn put(self, _: tarpc::context::Context, _: String, _: Vec<u8>) -> Result<(), String> {
+///         Err("read-only".into())
+///     }
+/// }
+/// ```
+///
+/// Methods marked `#[tarpc::application_error]` and returning `Result<T, E>`, where
+/// `E: Into<ApplicationError>`, send their errors as [application errors](ApplicationError)
+/// instead, and the client returns them in [`RpcError`](client::RpcError)s. Marking
+/// `type Error = E;` with `#[tarpc::application_error]` does the same for all methods returning
+/// `Result<T>`:
+///
+/// ```
+/// use tarpc::ApplicationError;
+///
+/// struct NotFound(String);
+///
+/// impl From<NotFound> for ApplicationError {
+///     fn from(NotFound(key): NotFound) -> Self {
+///         ApplicationError::new(404, format!("{key} not found"))
+///     }
+/// }
+///
+/// #[tarpc::service]
+/// trait Store {
+///     #[tarpc::application_error]
+///     type Error = NotFound;
+///
+///     async fn get(key: String) -> Result<Vec<u8>>;
+/// }
+/// ```
+///
+/// Each method is sent as a variant of the request and response enums, serialized under the
+/// method's name in CamelCase. To rename a method without breaking compatibility with deployed
+/// peers, keep its serialized name with `#[tarpc::rename = "..."]`:
+///
+/// ```
+/// #[tarpc::service]
+/// trait Service {
+///     /// Formerly `hello`.
+///     #[tarpc::rename = "Hello"]
+///     async fn greet(name: String) -> String;
+/// }
+/// ```
+///
+/// Methods can have default bodies, which implementers of the service trait inherit unless they
+/// override them, so adding a method with a default body doesn't break existing servers. The
+/// default body takes the request context as `context`:
+///
+/// ```
+/// #[tarpc::service]
+/// trait Greeter {
+///     async fn hello(name: String) -> String;
+///     /// Added after deployment.
+///     async fn goodbye(name: String) -> String {
+///         format!("Goodbye, {name}.")
+///     }
+/// }
+/// ```
+///
+/// A method returning `impl Stream<Item = T>` responds with a stream of `T`, sent to the client
+/// one item at a time. Services with streaming methods serve [bodies](server::body::Body), so
+/// they're executed with [`execute_body`](server::Requests::execute_body), and their streaming
+/// methods can only be called through a [`client::Channel`]:
+///
+/// ```
+/// # use futures::Stream;
+/// #[tarpc::service]
+/// trait Feed {
+///     async fn watch(topic: String) -> impl Stream<Item = String>;
+/// }
+/// ```
+///
+/// A method marked `#[tarpc::oneway]` is fire-and-forget: the client sends the request without
+/// awaiting a response, and the server executes it without sending one. Oneway methods can't
+/// return a value, and like streaming methods, they can only be called through a
+/// [`client::Channel`]:
+///
+/// ```
+/// #[tarpc::service]
+/// trait Logger {
+///     #[tarpc::oneway]
+///     async fn log(line: String);
+/// }
+/// ```
+///
+/// Calls made with [`context::current()`] default to a deadline ten seconds from now. A method
+/// that needs more or less time can declare its own default with `#[tarpc::deadline = "..."]`,
+/// which the client uses in place of the ten-second default. Deadlines inherited from an active
+/// request, or set explicitly on the context, are kept:
+///
+/// ```
+/// #[tarpc::service]
+/// trait Reports {
+///     #[tarpc::deadline = "2m 30s"]
+///     async fn generate(month: u8) -> String;
+/// }
+/// ```
+///
+/// An expensive method can limit how many of its requests execute at once with
+/// `#[tarpc::max_concurrent = N]`; see [`server::limits::requests_per_method`] for details.
+///
+/// Tiny, frequent methods can be served without the cost of a span per request by marking them
+/// `#[tarpc::no_trace]`. The client skips the spans of their calls, and a server skips the spans
+/// of their requests when its channel is built with
+/// [`with_untraced`](server::BaseChannel::with_untraced) and the request enum's `is_untraced` fn.
+/// Their requests still carry a trace context, so the rest of the trace stays connected:
+///
+/// ```
+/// # use tarpc::server::BaseChannel;
+/// #[tarpc::service]
+/// trait Counter {
+///     #[tarpc::no_trace]
+///     async fn increment();
+///     async fn report() -> u64;
+/// }
+///
+/// # fn f(transport: tarpc::transport::channel::UnboundedChannel<
+/// #     tarpc::ClientMessage<CounterRequest>, tarpc::Response<CounterResponse>>) {
+/// let channel = BaseChannel::with_defaults(transport).with_untraced(CounterRequest::is_untraced);
+/// # }
+/// ```
+///
+/// The request enum names its methods for middleware, logging, and routing: `method_name` returns
+/// the name of a request's method, e.g. `"Counter.increment"`, `METHOD_NAMES` lists the names of
+/// the methods the service declares, and `from_method_name` looks up a method by its full name or
+/// its name within the service:
+///
+/// ```
+/// # #[tarpc::service]
+/// # trait Counter {
+/// #     async fn increment();
+/// # }
+/// assert_eq!(CounterRequest::Increment {}.method_name(), "Counter.increment");
+/// assert_eq!(CounterRequest::METHOD_NAMES, ["Counter.increment"]);
+/// assert_eq!(CounterRequest::from_method_name("increment"), Some("Counter.increment"));
+/// ```
+///
+/// Methods can be gated with `#[cfg(...)]`, which gates everything generated for them: their
+/// request and response variants, client methods, and serve arms. Optional functionality can be
+/// compiled out of some builds without a second service definition:
+///
+/// ```
+/// #[tarpc::service]
+/// trait Device {
+///     async fn status() -> String;
+///     #[cfg(feature = "firmware-updates")]
+///     async fn flash(image: Vec<u8>) -> bool;
+/// }
+/// ```
+///
+/// With rkyv, args declared as references, like `&str` or `&[u8]`, are borrowed from the archived
+/// request rather than copied into owned values on the server; see [`zero_copy`] for details.
+///
+/// `#[serde(...)]` attributes on a method are forwarded onto its request and response variants,
+/// and those on an arg onto its request variant field, so that a service's schema can evolve
+/// compatibly, e.g. by defaulting an arg added after deployment:
+///
+/// ```
+/// #[tarpc::service]
+/// trait Greeter {
+///     async fn greet(name: String, #[serde(default)] greeting: Option<String>) -> String;
+/// }
+/// ```
+///
+/// Without serde derives, e.g. in builds enabling only the `rkyv` feature, the attributes are
+/// dropped, so the same service definition builds with or without serde.
+///
+/// With the `serde-transport` feature, the service trait also has fns that serve it in one line:
+/// `serve_tcp` with the `tcp` feature, and `serve_unix` with the `unix` feature. Each listens on
+/// an address and serves the connections it accepts; see the `server::quickstart` module
+/// for details.
+///
+/// Crates that only need one half of a service, like an SDK that only calls it, can skip
+/// generating the other half with `#[tarpc::service(client_only)]`, which omits the service trait
+/// and serve fn, or `#[tarpc::service(server_only)]`, which omits the client stub. The request
+/// and response enums are always generated.
+///
+/// With the `cli` feature, `#[tarpc::service(cli = true)]` also generates a command-line client,
+/// with a subcommand per method taking JSON args, for debugging a service by hand; see [`cli`] for
+/// details.
+///
+/// `#[tarpc::service(schema = true)]` generates a `schema()` fn on the request enum, describing
+/// the service's methods for tooling, which renders as TypeScript typings and JSON Schemas of the
+/// service's requests and responses for web frontends; see [`schema`] for details.
+///
+/// With the `fuzz` feature, `#[tarpc::service(fuzz = true)]` generates a fuzzing harness that
+/// decodes, serves, and encodes requests built from a fuzzer's input; see the `fuzz` module for
+/// details.
+///
+/// With the `tower` feature, `#[tarpc::service(tower = true)]` generates a `tower::Service` for
+/// each method, so that methods can be wrapped in middleware or tested on their own; see the
+/// `tower` module for details.
+///
+/// With the `metrics` feature, client and server channels record the latency and size of their
+/// requests and responses in the `metrics` facade; see the `stats` module for details. Also,
+/// `#[tarpc::service(instrumented_client = true)]` generates a wrapper of the client, e.g.
+/// `WorldInstrumentedClient`, that counts each method's calls and annotates their spans with the OpenTelemetry semantic conventions for
+/// RPC; see the `client::instrumented` module for details.
+///
+/// With the `wire-compat` feature, `#[tarpc::service(wire_compat = "tests/wire")]` generates a
+/// test for each method that compares its encoded requests and responses with golden samples
+/// stored in `tests/wire`, failing if a change to the service alters how the method is sent; see
+/// the `wire_compat` module for details.
+///
+/// With the `serde-transport` feature, `#[tarpc::service(passthrough_bytes = true)]` marks args
+/// and responses of type `Bytes` as payloads, which `serde_transport::Passthrough` codecs send as
+/// they are rather than serializing them, so that services forwarding opaque blobs don't pay to
+/// encode and decode them; see `serde_transport::Passthrough` for details.
+///
+/// A service can include all the methods of other services with
+/// `#[tarpc::service(extends = path::to::Base)]`, so that common methods, like health checks, can
+/// be shared by many services. The base service becomes a supertrait of the service trait, the
+/// request and response enums wrap the base service's in a variant named after it, and the client
+/// gets an `as_base` fn returning a client of the base service. Since both traits have a `serve`
+/// fn, call the extended service's as `Service::serve(server)`:
+///
+/// ```
+/// mod health {
+///     #[tarpc::service]
+///     pub trait Health {
+///         async fn check() -> bool;
+///     }
+/// }
+///
+/// #[tarpc::service(extends = health::Health)]
+/// trait Service {
+///     async fn hello(name: String) -> String;
+/// }
+///
+/// # async fn call(client: ServiceClient) -> Result<bool, tarpc::client::RpcError> {
+/// client.as_health().check(tarpc::context::current()).await
+/// # }
+/// ```
+///
+/// The generated types take the visibility of the service trait. To keep the wire types out of a
+/// crate's public API, give the request, response, client, and server types their own visibility
+/// with `vis = "..."`:
+///
+/// ```
+/// #[tarpc::service(vis = "pub(crate)")]
+/// pub trait World {
+///     async fn hello(name: String) -> String;
+/// }
+/// ```
+///
+/// The generated types are named after the service trait, e.g. `WorldClient` and `WorldRequest`
+/// for `World`. To avoid collisions between services of the same name, or to match an existing
+/// API, `prefix = "..."` replaces the service name in all of them, and `client_name`,
+/// `request_name`, and `response_name` rename the client and the request and response enums
+/// outright. Services extended with `extends` must keep the default names:
+///
+/// ```
+/// #[tarpc::service(prefix = "Greeting", client_name = "Greeter")]
+/// trait World {
+///     async fn hello(name: String) -> String;
+/// }
+///
+/// let request = GreetingRequest::Hello { name: "Ferris".into() };
+/// # let _: Option<Greeter> = None;
+/// ```
+///
+/// Formats like bincode identify a method by its position in the service definition. With
+/// `method_ids = true`, methods are sent as stable ids instead, assigned with `#[tarpc::id = N]`
+/// or derived from their names, so that methods can be reordered; see [`method_ids`] for details.
+///
+/// Methods can declare the versions of the service that introduced and deprecated them with
+/// `#[tarpc::since = "..."]` and `#[tarpc::deprecated_since = "..."]`, so that clients can tell
+/// whether a server supports them. Clients learn the version of their server from the responses
+/// of a service declared with `#[tarpc::service(version = "...")]`; see [`negotiation`] for
+/// details.
+///
+/// The request and response enums always derive `Debug`. To derive more traits, e.g. for tests
+/// or fuzzing, list them in `derive(...)`:
+///
+/// ```
+/// #[tarpc::service(derive(Clone, PartialEq))]
+/// trait World {
+///     async fn hello(name: String) -> String;
+/// }
+///
+/// let request = WorldRequest::Hello { name: "Ferris".into() };
+/// assert_eq!(request.clone(), request);
+/// ```
 pub use tarpc_plugins::service;
 
+#[cfg(feature = "arena")]
+#[cfg_attr(docsrs, doc(cfg(feature = "arena")))]
+pub mod arena;
 pub(crate) mod cancellations;
+#[cfg(feature = "cli")]
+#[cfg_attr(docsrs, doc(cfg(feature = "cli")))]
+pub mod cli;
 pub mod client;
 pub mod context;
+#[cfg(feature = "serde1")]
+mod envelope;
+#[cfg(feature = "fuzz")]
+#[cfg_attr(docsrs, doc(cfg(feature = "fuzz")))]
+pub mod fuzz;
+#[cfg(feature = "serde1")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde1")))]
+pub mod method_ids;
+pub mod negotiation;
+pub mod rng;
+pub mod schema;
 pub mod server;
+pub mod stats;
+#[cfg(feature = "tower")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tower")))]
+pub mod tower;
 pub mod transport;
 pub(crate) mod util;
+#[cfg(feature = "wire-compat")]
+#[cfg_attr(docsrs, doc(cfg(feature = "wire-compat")))]
+pub mod wire_compat;
+#[cfg(feature = "rkyv")]
+#[cfg_attr(docsrs, doc(cfg(feature = "rkyv")))]
+pub mod zero_copy;
 
 pub use crate::transport::sealed::Transport;
 
-use anyhow::Context as _;
-use futures::task::*;
 use std::sync::Arc;
-use std::{error::Error, fmt::Display, io, time::SystemTime};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    io,
+    time::{Duration, Instant},
+};
 
 /// A message from a client to a server.
-#[derive(Debug)]
-#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+///
+/// In compact serde formats like bincode, which don't encode field names, messages that use none
+/// of the fields added since tarpc 0.34 are encoded as 0.34 encoded them, so that older peers can
+/// decode them. Other messages are encoded as variants older peers reject, rather than misread.
+#[derive(Clone, Debug)]
 #[cfg_attr(
     feature = "rkyv",
     derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
@@ -288,15 +665,52 @@ pub enum ClientMessage<T> {
     Cancel {
         /// The trace context associates the message with a specific chain of causally-related actions,
         /// possibly orchestrated across many distributed systems.
-        #[cfg_attr(feature = "serde1", serde(default))]
         trace_context: trace::Context,
         /// The ID of the request to cancel.
         request_id: u64,
+        /// Why the client abandoned the request, which the server records in the request's span.
+        reason: CancellationReason,
+        /// Set if the client asks the server to acknowledge that it aborted the request, with a
+        /// [canceled](ServerError::canceled) error. Otherwise, and if the request already
+        /// completed, the server sends nothing, so a client waiting for the outcome receives
+        /// either the request's response or the acknowledgement.
+        ack: bool,
+    },
+    /// A command to cancel several in-flight requests at once, sent by a client that
+    /// [coalesces cancellations](crate::client::Config::max_cancellations_per_frame) instead of
+    /// sending one [`Cancel`](ClientMessage::Cancel) per request. Servers handle it as though
+    /// each request had been canceled on its own, without an acknowledgement.
+    CancelBatch {
+        /// The IDs of the requests to cancel.
+        request_ids: Vec<u64>,
+        /// Why the client abandoned the requests.
+        reason: CancellationReason,
     },
 }
 
+/// Why a client canceled a request.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+#[cfg_attr(feature = "rkyv", archive(check_bytes))]
+#[non_exhaustive]
+pub enum CancellationReason {
+    /// The client didn't say, e.g. because it predates cancellation reasons.
+    #[default]
+    Unspecified,
+    /// The caller dropped the response future or body before the request completed.
+    Dropped,
+    /// The request's deadline passed, e.g. at a proxy that forwarded it.
+    DeadlineExceeded,
+    /// The caller canceled the request on purpose.
+    Explicit,
+}
+
 /// A request from a client to a server.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Debug)]
 #[non_exhaustive]
 #[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(
@@ -311,12 +725,18 @@ pub struct Request<T> {
     pub id: u64,
     /// The request body.
     pub message: T,
+    /// Set if the client expects no response, so the server executes the request without
+    /// sending one back.
+    #[cfg_attr(feature = "serde1", serde(default))]
+    pub oneway: bool,
 }
 
 /// A response from a server to a client.
+///
+/// Like [`ClientMessage`]s, responses that use none of the fields added since tarpc 0.34 are
+/// encoded as 0.34 encoded them in compact serde formats.
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 #[non_exhaustive]
-#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(
     feature = "rkyv",
     derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
@@ -327,6 +747,57 @@ pub struct Response<T> {
     pub request_id: u64,
     /// The response body, or an error if the request failed.
     pub message: Result<T, ServerError>,
+    /// Metadata attached to the response by the server.
+    pub extensions: ResponseExtensions,
+    /// True iff more responses to the same request follow, because the server is streaming a
+    /// [body](server::body::Body).
+    pub partial: bool,
+}
+
+/// Small pieces of string metadata that a server attaches to a response, e.g. the server's
+/// processing time or whether the response was served from a cache.
+///
+/// Request handlers add extensions with [`server::response_extensions::insert`]; clients read
+/// them with [`client::response_extensions::capture`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+#[cfg_attr(feature = "rkyv", archive(check_bytes))]
+pub struct ResponseExtensions(BTreeMap<String, String>);
+
+impl ResponseExtensions {
+    /// Sets the extension `key` to `value`, returning the previous value, if any.
+    pub fn insert(&mut self, key: impl Into<String>, value: impl Into<String>) -> Option<String> {
+        self.0.insert(key.into(), value.into())
+    }
+
+    /// Returns the value of the extension `key`, if set.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).map(String::as_str)
+    }
+
+    /// Removes the extension `key`, returning its value, if set.
+    pub fn remove(&mut self, key: &str) -> Option<String> {
+        self.0.remove(key)
+    }
+
+    /// Returns an iterator over all extensions, ordered by key.
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    /// Returns the number of extensions.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Returns true iff there are no extensions.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
 }
 
 /// An error indicating the server aborted the request early, e.g., due to request throttling.
@@ -353,6 +824,69 @@ pub struct ServerError {
     pub kind: io::ErrorKind,
     /// A message describing more detail about the error that occurred.
     pub detail: String,
+    /// Set if the error was returned by the request handler rather than by the server itself.
+    #[cfg_attr(feature = "serde1", serde(default))]
+    pub application: Option<ApplicationError>,
+    /// Set if the server was too busy to process the request, suggesting how long the client
+    /// should wait before sending it again.
+    #[cfg_attr(feature = "serde1", serde(default))]
+    pub retry_after: Option<Duration>,
+    /// Set if the server failed the request for a reason the client may want to handle, e.g.
+    /// by sending the request to another server.
+    #[cfg_attr(feature = "serde1", serde(default))]
+    pub reason: Option<ServerErrorReason>,
+}
+
+/// Why the server failed a request, for the [server errors](ServerError) a client may want to
+/// handle specially.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+#[cfg_attr(feature = "rkyv", archive(check_bytes))]
+pub enum ServerErrorReason {
+    /// The server is draining: it's shutting down soon and no longer accepts requests, so the
+    /// client should send them to another server.
+    Draining,
+    /// The server doesn't implement the method requested, e.g. because it runs an older version
+    /// of the service than the client.
+    Unimplemented,
+    /// The server aborted the request because the client canceled it, in acknowledgement of a
+    /// [cancellation](ClientMessage::Cancel) that asked for one.
+    Canceled,
+    /// The server couldn't decode the request, e.g. because the client sent args, like an enum
+    /// variant, that the server's version of the service doesn't know, so that sending the
+    /// request again won't help.
+    BadRequest,
+}
+
+/// An error returned by a request handler, as opposed to an error that occurred in the RPC
+/// framework or the transport.
+///
+/// Service methods marked `#[tarpc::application_error]` convert their errors into
+/// `ApplicationError`s and send them to the client in the response envelope, and the generated client surfaces them as
+/// [`RpcError::Application`](client::RpcError::Application).
+#[derive(thiserror::Error, Clone, Debug, PartialEq, Eq, Hash)]
+#[error("application error {code}: {message}")]
+#[non_exhaustive]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+#[cfg_attr(feature = "rkyv", archive(check_bytes))]
+pub struct ApplicationError {
+    /// A service-defined code identifying the type of error.
+    pub code: u32,
+    /// A human-readable description of the error.
+    pub message: String,
+    /// Whether the request may succeed if it is sent again.
+    pub retryable: bool,
+    /// Service-defined, serialized details of the error.
+    pub details: Vec<u8>,
 }
 
 #[cfg(feature = "rkyv")]
@@ -385,6 +919,7 @@ impl rkyv::with::ArchiveWith<io::ErrorKind> for RkyvErrorKind {
             Interrupted => 15,
             Other => 16,
             UnexpectedEof => 17,
+            Unsupported => 18,
             _ => 16,
         };
         discrim.resolve(pos, (), out);
@@ -421,6 +956,7 @@ where
             Interrupted => 15,
             Other => 16,
             UnexpectedEof => 17,
+            Unsupported => 18,
             _ => 16,
         };
         discrim.serialize(serializer)
@@ -459,6 +995,7 @@ where
             15 => Interrupted,
             16 => Other,
             17 => UnexpectedEof,
+            18 => Unsupported,
             _ => Other,
         })
     }
@@ -490,44 +1027,105 @@ where
 impl ServerError {
     /// Returns a new server error with `kind` and `detail`.
     pub fn new(kind: io::ErrorKind, detail: String) -> ServerError {
-        Self { kind, detail }
+        Self {
+            kind,
+            detail,
+            application: None,
+            retry_after: None,
+            reason: None,
+        }
+    }
+
+    /// Returns a new server error indicating the server is too busy to process the request, and
+    /// that the client should wait `retry_after` before sending it again.
+    pub fn overloaded(detail: String, retry_after: Duration) -> ServerError {
+        ServerError::new(io::ErrorKind::WouldBlock, detail).with_retry_after(retry_after)
+    }
+
+    /// Returns a new server error indicating the request's deadline passed before it could be
+    /// served.
+    pub fn deadline_exceeded(detail: String) -> ServerError {
+        ServerError::new(io::ErrorKind::TimedOut, detail)
+    }
+
+    /// Suggests that the client wait `retry_after` before sending the request again.
+    pub fn with_retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = Some(retry_after);
+        self
+    }
+
+    /// Returns a new server error indicating the server is draining, so that the client should
+    /// send the request to another server.
+    pub fn draining(detail: String) -> ServerError {
+        ServerError::new(io::ErrorKind::ConnectionRefused, detail)
+            .with_reason(ServerErrorReason::Draining)
+    }
+
+    /// Returns a new server error indicating the server doesn't implement the method requested.
+    pub fn unimplemented(detail: String) -> ServerError {
+        ServerError::new(io::ErrorKind::Unsupported, detail)
+            .with_reason(ServerErrorReason::Unimplemented)
+    }
+
+    /// Returns a new server error acknowledging that the server aborted the request because the
+    /// client canceled it.
+    pub fn canceled(detail: String) -> ServerError {
+        ServerError::new(io::ErrorKind::Interrupted, detail)
+            .with_reason(ServerErrorReason::Canceled)
+    }
+
+    /// Returns a new server error indicating the server couldn't decode the request.
+    pub fn bad_request(detail: String) -> ServerError {
+        ServerError::new(io::ErrorKind::InvalidData, detail)
+            .with_reason(ServerErrorReason::BadRequest)
+    }
+
+    /// Sets why the server failed the request.
+    pub fn with_reason(mut self, reason: ServerErrorReason) -> Self {
+        self.reason = Some(reason);
+        self
     }
 }
 
-impl<T> Request<T> {
-    /// Returns the deadline for this request.
-    pub fn deadline(&self) -> &SystemTime {
-        &self.context.deadline
+impl From<ApplicationError> for ServerError {
+    fn from(error: ApplicationError) -> Self {
+        Self {
+            kind: io::ErrorKind::Other,
+            detail: error.message.clone(),
+            application: Some(error),
+            retry_after: None,
+            reason: None,
+        }
     }
 }
 
-pub(crate) trait PollContext<T> {
-    fn context<C>(self, context: C) -> Poll<Option<anyhow::Result<T>>>
-    where
-        C: Display + Send + Sync + 'static;
+impl ApplicationError {
+    /// Returns a new, non-retryable application error with `code` and `message`.
+    pub fn new(code: u32, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            retryable: false,
+            details: Vec::new(),
+        }
+    }
 
-    fn with_context<C, F>(self, f: F) -> Poll<Option<anyhow::Result<T>>>
-    where
-        C: Display + Send + Sync + 'static,
-        F: FnOnce() -> C;
-}
+    /// Sets whether the request may succeed if it is sent again.
+    pub fn with_retryable(mut self, retryable: bool) -> Self {
+        self.retryable = retryable;
+        self
+    }
 
-impl<T, E> PollContext<T> for Poll<Option<Result<T, E>>>
-where
-    E: Error + Send + Sync + 'static,
-{
-    fn context<C>(self, context: C) -> Poll<Option<anyhow::Result<T>>>
-    where
-        C: Display + Send + Sync + 'static,
-    {
-        self.map(|o| o.map(|r| r.context(context)))
+    /// Attaches service-defined, serialized details to the error.
+    pub fn with_details(mut self, details: Vec<u8>) -> Self {
+        self.details = details;
+        self
     }
+}
 
-    fn with_context<C, F>(self, f: F) -> Poll<Option<anyhow::Result<T>>>
-    where
-        C: Display + Send + Sync + 'static,
-        F: FnOnce() -> C,
-    {
-        self.map(|o| o.map(|r| r.with_context(f)))
+impl<T> Request<T> {
+    /// Returns the deadline for this request.
+    pub fn deadline(&self) -> &Instant {
+        &self.context.deadline
     }
 }
@@ -0,0 +1,482 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Provides stable numeric ids for service methods.
+//!
+//! By default, serde formats that encode enum variants by index, like bincode, identify a method
+//! by its position in the service definition, so reordering methods breaks compatibility with
+//! deployed peers. With `#[tarpc::service(method_ids = true)]`, each method is instead sent as a
+//! stable id: the one assigned with `#[tarpc::id = N]`, or else one derived from the method's
+//! name on the wire. Formats that encode variants by name, like JSON, are unaffected.
+//!
+//! Small assigned ids are the most compact on the wire in formats with variable-length integers.
+//! Ids must be unique within a service; collisions are reported at compile time.
+//!
+//! # Example
+//!
+//! ```rust
+//! #[tarpc::service(method_ids = true)]
+//! trait Storage {
+//!     #[tarpc::id = 1]
+//!     async fn get(key: String) -> Option<String>;
+//!     #[tarpc::id = 2]
+//!     async fn put(key: String, value: String);
+//! }
+//!
+//! assert_eq!(StorageRequest::METHOD_IDS, [1, 2]);
+//! ```
+
+use serde::{
+    de::{self, DeserializeSeed, EnumAccess, Unexpected, Visitor},
+    forward_to_deserialize_any, Deserializer, Serialize, Serializer,
+};
+use std::fmt;
+
+/// Returns the id derived from the wire name of a method without an assigned id: the 32-bit
+/// FNV-1a hash of the name.
+pub const fn derived_id(wire_name: &str) -> u32 {
+    let bytes = wire_name.as_bytes();
+    let mut hash: u32 = 0x811c_9dc5;
+    let mut i = 0;
+    while i < bytes.len() {
+        hash ^= bytes[i] as u32;
+        hash = hash.wrapping_mul(0x0100_0193);
+        i += 1;
+    }
+    hash
+}
+
+/// Serializes an enum derived by serde, sending `ids[i]` in place of the variant index `i`.
+#[doc(hidden)]
+pub struct IdSerializer<S> {
+    inner: S,
+    ids: &'static [u32],
+}
+
+impl<S> IdSerializer<S> {
+    pub fn new(inner: S, ids: &'static [u32]) -> Self {
+        Self { inner, ids }
+    }
+
+    fn id(&self, variant_index: u32) -> u32 {
+        self.ids[variant_index as usize]
+    }
+}
+
+impl<S: Serializer> Serializer for IdSerializer<S> {
+    type Ok = S::Ok;
+    type Error = S::Error;
+    type SerializeSeq = S::SerializeSeq;
+    type SerializeTuple = S::SerializeTuple;
+    type SerializeTupleStruct = S::SerializeTupleStruct;
+    type SerializeTupleVariant = S::SerializeTupleVariant;
+    type SerializeMap = S::SerializeMap;
+    type SerializeStruct = S::SerializeStruct;
+    type SerializeStructVariant = S::SerializeStructVariant;
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+    ) -> Result<S::Ok, S::Error> {
+        let id = self.id(variant_index);
+        self.inner.serialize_unit_variant(name, id, variant)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        let id = self.id(variant_index);
+        self.inner
+            .serialize_newtype_variant(name, id, variant, value)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<S::SerializeTupleVariant, S::Error> {
+        let id = self.id(variant_index);
+        self.inner.serialize_tuple_variant(name, id, variant, len)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<S::SerializeStructVariant, S::Error> {
+        let id = self.id(variant_index);
+        self.inner.serialize_struct_variant(name, id, variant, len)
+    }
+
+    // The rest only forward to the inner serializer.
+
+    fn serialize_bool(self, v: bool) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_bool(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_i8(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_i16(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_i32(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_i64(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_i128(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_u8(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_u16(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_u32(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_u64(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_u128(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_f32(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_f64(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_char(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_str(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_bytes(v)
+    }
+
+    fn serialize_none(self) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_none()
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_some(value)
+    }
+
+    fn serialize_unit(self) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_unit()
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_unit_struct(name)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<S::Ok, S::Error> {
+        self.inner.serialize_newtype_struct(name, value)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<S::SerializeSeq, S::Error> {
+        self.inner.serialize_seq(len)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<S::SerializeTuple, S::Error> {
+        self.inner.serialize_tuple(len)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<S::SerializeTupleStruct, S::Error> {
+        self.inner.serialize_tuple_struct(name, len)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<S::SerializeMap, S::Error> {
+        self.inner.serialize_map(len)
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<S::SerializeStruct, S::Error> {
+        self.inner.serialize_struct(name, len)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+}
+
+/// Deserializes an enum derived by serde, reading `ids[i]` in place of the variant index `i`.
+#[doc(hidden)]
+pub struct IdDeserializer<D> {
+    inner: D,
+    ids: &'static [u32],
+}
+
+impl<D> IdDeserializer<D> {
+    pub fn new(inner: D, ids: &'static [u32]) -> Self {
+        Self { inner, ids }
+    }
+}
+
+impl<'de, D: Deserializer<'de>> Deserializer<'de> for IdDeserializer<D> {
+    type Error = D::Error;
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, D::Error> {
+        self.inner.deserialize_enum(
+            name,
+            variants,
+            EnumVisitor {
+                inner: visitor,
+                ids: self.ids,
+            },
+        )
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, D::Error> {
+        self.inner.deserialize_any(visitor)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option
+        unit unit_struct newtype_struct seq tuple tuple_struct map struct identifier ignored_any
+    }
+}
+
+struct EnumVisitor<V> {
+    inner: V,
+    ids: &'static [u32],
+}
+
+impl<'de, V: Visitor<'de>> Visitor<'de> for EnumVisitor<V> {
+    type Value = V::Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.inner.expecting(f)
+    }
+
+    fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<V::Value, A::Error> {
+        self.inner.visit_enum(IdEnumAccess {
+            inner: data,
+            ids: self.ids,
+        })
+    }
+}
+
+struct IdEnumAccess<A> {
+    inner: A,
+    ids: &'static [u32],
+}
+
+impl<'de, A: EnumAccess<'de>> EnumAccess<'de> for IdEnumAccess<A> {
+    type Error = A::Error;
+    type Variant = A::Variant;
+
+    fn variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<(T::Value, A::Variant), A::Error> {
+        self.inner.variant_seed(VariantSeed {
+            inner: seed,
+            ids: self.ids,
+        })
+    }
+}
+
+struct VariantSeed<T> {
+    inner: T,
+    ids: &'static [u32],
+}
+
+impl<'de, T: DeserializeSeed<'de>> DeserializeSeed<'de> for VariantSeed<T> {
+    type Value = T::Value;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<T::Value, D::Error> {
+        self.inner.deserialize(VariantDeserializer {
+            inner: deserializer,
+            ids: self.ids,
+        })
+    }
+}
+
+/// Deserializes the variant of an enum, mapping ids to variant indices.
+struct VariantDeserializer<D> {
+    inner: D,
+    ids: &'static [u32],
+}
+
+impl<'de, D: Deserializer<'de>> Deserializer<'de> for VariantDeserializer<D> {
+    type Error = D::Error;
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, D::Error> {
+        self.inner.deserialize_identifier(VariantVisitor {
+            inner: visitor,
+            ids: self.ids,
+        })
+    }
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, D::Error> {
+        self.inner.deserialize_any(VariantVisitor {
+            inner: visitor,
+            ids: self.ids,
+        })
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.inner.is_human_readable()
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf option
+        unit unit_struct newtype_struct seq tuple tuple_struct map struct enum ignored_any
+    }
+}
+
+struct VariantVisitor<V> {
+    inner: V,
+    ids: &'static [u32],
+}
+
+impl<'de, V: Visitor<'de>> Visitor<'de> for VariantVisitor<V> {
+    type Value = V::Value;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.inner.expecting(f)
+    }
+
+    fn visit_u64<E: de::Error>(self, id: u64) -> Result<V::Value, E> {
+        match self.ids.iter().position(|&known| u64::from(known) == id) {
+            Some(index) => self.inner.visit_u64(index as u64),
+            None => Err(E::invalid_value(
+                Unexpected::Unsigned(id),
+                &"a known method id",
+            )),
+        }
+    }
+
+    fn visit_str<E: de::Error>(self, name: &str) -> Result<V::Value, E> {
+        self.inner.visit_str(name)
+    }
+
+    fn visit_borrowed_str<E: de::Error>(self, name: &'de str) -> Result<V::Value, E> {
+        self.inner.visit_borrowed_str(name)
+    }
+
+    fn visit_string<E: de::Error>(self, name: String) -> Result<V::Value, E> {
+        self.inner.visit_string(name)
+    }
+
+    fn visit_bytes<E: de::Error>(self, name: &[u8]) -> Result<V::Value, E> {
+        self.inner.visit_bytes(name)
+    }
+
+    fn visit_borrowed_bytes<E: de::Error>(self, name: &'de [u8]) -> Result<V::Value, E> {
+        self.inner.visit_borrowed_bytes(name)
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, name: Vec<u8>) -> Result<V::Value, E> {
+        self.inner.visit_byte_buf(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    #[serde(remote = "Self")]
+    enum Method {
+        Get { key: String },
+        Put { key: String, value: u32 },
+    }
+
+    const IDS: &[u32] = &[7, 3];
+
+    impl serde::Serialize for Method {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            Method::serialize(self, IdSerializer::new(serializer, IDS))
+        }
+    }
+
+    impl<'de> serde::Deserialize<'de> for Method {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            Method::deserialize(IdDeserializer::new(deserializer, IDS))
+        }
+    }
+
+    #[test]
+    fn ids_replace_variant_indices() {
+        let put = Method::Put {
+            key: "k".into(),
+            value: 1,
+        };
+        let bytes = bincode::serialize(&put).unwrap();
+        assert_eq!(bytes[..4], 3u32.to_le_bytes());
+        assert_eq!(bincode::deserialize::<Method>(&bytes).unwrap(), put);
+
+        let mut unknown = bytes.clone();
+        unknown[..4].copy_from_slice(&1u32.to_le_bytes());
+        assert!(bincode::deserialize::<Method>(&unknown).is_err());
+    }
+
+    #[test]
+    fn names_are_unaffected() {
+        let get = Method::Get { key: "k".into() };
+        let json = serde_json::to_string(&get).unwrap();
+        assert_eq!(json, r#"{"Get":{"key":"k"}}"#);
+        assert_eq!(serde_json::from_str::<Method>(&json).unwrap(), get);
+    }
+
+    #[test]
+    fn derived_ids_hash_names() {
+        assert_eq!(derived_id(""), 0x811c_9dc5);
+        assert_eq!(derived_id("a"), 0xe40c_292c);
+    }
+}
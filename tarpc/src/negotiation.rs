@@ -0,0 +1,354 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Provides the building blocks of version negotiation between clients and servers of a service.
+//!
+//! Service methods can declare the version of the service that introduced them with
+//! `#[tarpc::since = "1.2"]`, and the version that deprecated them with
+//! `#[tarpc::deprecated_since = "1.3"]`. The generated request enum then lists the versions of
+//! every method in `METHOD_VERSIONS`, and a client that knows the version of its server can ask
+//! [`is_supported_by`](MethodVersion::is_supported_by) before sending a request the server
+//! doesn't know.
+//!
+//! A client learns the version of its server from any response: a service declared with
+//! `#[tarpc::service(version = "1.2")]` attaches its version to every response as the
+//! [`VERSION_EXTENSION`] extension, which a client reads with [`server_version`] from the
+//! extensions [captured](crate::client::response_extensions::capture) with the response.
+//!
+//! A server can also answer requests for methods it doesn't know, rather than failing to decode
+//! them: with `#[tarpc::service(catch_unknown_methods = true)]`, the request enum gets an
+//! `Unknown` variant matching any other method, which the generated server answers with an
+//! [unimplemented](crate::ServerError::unimplemented) error. Catching unknown methods requires a
+//! self-describing serialization format, like JSON.
+//!
+//! # Example
+//!
+//! ```rust
+//! #[tarpc::service]
+//! trait World {
+//!     async fn hello(name: String) -> String;
+//!     #[tarpc::since = "1.1"]
+//!     async fn goodbye(name: String) -> String;
+//! }
+//!
+//! let request = WorldRequest::Goodbye { name: "Ferris".into() };
+//! assert!(!request.is_supported_by("1.0"));
+//! assert!(request.is_supported_by("1.1"));
+//! assert_eq!(WorldRequest::METHOD_VERSIONS[1].since, Some("1.1"));
+//! ```
+
+use crate::{server::response_extensions, ResponseExtensions};
+use std::cmp::Ordering;
+
+/// The [response extension](ResponseExtensions) carrying the version of the service that served
+/// the request.
+pub const VERSION_EXTENSION: &str = "tarpc-service-version";
+
+/// Attaches `version` to the response to the request currently being served, as the
+/// [`VERSION_EXTENSION`], unless the response already carries a version. Services declared with
+/// `#[tarpc::service(version = "...")]` call it before serving each request, so an extending
+/// service announces its own version rather than that of the service it extends.
+///
+/// Does nothing outside of a request handler.
+pub fn announce_version(version: &str) {
+    response_extensions::with_current(|extensions| {
+        if extensions.get(VERSION_EXTENSION).is_none() {
+            extensions.insert(VERSION_EXTENSION, version);
+        }
+    });
+}
+
+/// Returns the version of the service that sent a response with `extensions`, if it announced
+/// one.
+pub fn server_version(extensions: &ResponseExtensions) -> Option<&str> {
+    extensions.get(VERSION_EXTENSION)
+}
+
+/// The versions of the service that introduced and deprecated a method.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MethodVersion {
+    /// The name of the method, e.g. `World.hello`.
+    pub method: &'static str,
+    /// The version that introduced the method, if declared with `#[tarpc::since]`.
+    pub since: Option<&'static str>,
+    /// The version that deprecated the method, if declared with `#[tarpc::deprecated_since]`.
+    pub deprecated_since: Option<&'static str>,
+}
+
+impl MethodVersion {
+    /// Returns true iff a server at `version` serves the method.
+    pub fn is_supported_by(&self, version: &str) -> bool {
+        is_supported(self.since, version)
+    }
+
+    /// Returns true iff the method is deprecated at `version`.
+    pub fn is_deprecated_at(&self, version: &str) -> bool {
+        self.deprecated_since.is_some_and(|deprecated_since| {
+            compare_versions(version, deprecated_since).is_some_and(Ordering::is_ge)
+        })
+    }
+}
+
+/// Returns true iff a server at `version` serves a method introduced at `since`.
+///
+/// Methods without a declared version are served by every version. A `version` that isn't made
+/// of dot-separated numbers doesn't serve methods with a declared version.
+pub fn is_supported(since: Option<&str>, version: &str) -> bool {
+    since.map_or(true, |since| {
+        compare_versions(version, since).is_some_and(Ordering::is_ge)
+    })
+}
+
+/// Compares two versions made of dot-separated numbers, like `1.2` or `2.0.1`. Missing trailing
+/// numbers count as zero, so `1.2` equals `1.2.0`.
+///
+/// Returns None if either version isn't made of dot-separated numbers.
+pub fn compare_versions(a: &str, b: &str) -> Option<Ordering> {
+    let a = parse_version(a)?;
+    let b = parse_version(b)?;
+    let len = a.len().max(b.len());
+    let component = |version: &[u64], i| version.get(i).copied().unwrap_or(0);
+    Some(
+        (0..len)
+            .map(|i| component(&a, i).cmp(&component(&b, i)))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal),
+    )
+}
+
+fn parse_version(version: &str) -> Option<Vec<u64>> {
+    version
+        .split('.')
+        .map(|component| {
+            if component.bytes().all(|b| b.is_ascii_digit()) {
+                component.parse().ok()
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// The payload of the `Unknown` variant of a request enum generated with
+/// `catch_unknown_methods = true`. Deserializes from any value, discarding it.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+#[cfg_attr(feature = "rkyv", archive(check_bytes))]
+pub struct UnknownMethod;
+
+impl UnknownMethod {
+    /// The method name of requests for unknown methods.
+    pub const NAME: &'static str = "<unknown>";
+}
+
+#[cfg(feature = "serde1")]
+impl serde::Serialize for UnknownMethod {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_unit()
+    }
+}
+
+#[cfg(feature = "serde1")]
+impl<'de> serde::Deserialize<'de> for UnknownMethod {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        serde::de::IgnoredAny::deserialize(deserializer).map(|_| UnknownMethod)
+    }
+}
+
+#[cfg(feature = "serde1")]
+pub use unknown_methods::UnknownMethodDeserializer;
+
+#[cfg(feature = "serde1")]
+mod unknown_methods {
+    use super::UnknownMethod;
+    use serde::{
+        de::{self, DeserializeSeed, EnumAccess, Visitor},
+        forward_to_deserialize_any, Deserializer,
+    };
+    use std::fmt;
+
+    /// Deserializes a request enum derived by serde, reading the method name first: names of
+    /// methods the enum knows are deserialized as usual, so malformed args are still errors, and
+    /// any other name as the variant named [`UnknownMethod::NAME`].
+    #[doc(hidden)]
+    pub struct UnknownMethodDeserializer<D> {
+        inner: D,
+    }
+
+    impl<D> UnknownMethodDeserializer<D> {
+        pub fn new(inner: D) -> Self {
+            Self { inner }
+        }
+    }
+
+    impl<'de, D: Deserializer<'de>> Deserializer<'de> for UnknownMethodDeserializer<D> {
+        type Error = D::Error;
+
+        fn deserialize_enum<V: Visitor<'de>>(
+            self,
+            name: &'static str,
+            variants: &'static [&'static str],
+            visitor: V,
+        ) -> Result<V::Value, D::Error> {
+            self.inner.deserialize_enum(
+                name,
+                variants,
+                EnumVisitor {
+                    inner: visitor,
+                    variants,
+                },
+            )
+        }
+
+        fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, D::Error> {
+            self.inner.deserialize_any(visitor)
+        }
+
+        fn is_human_readable(&self) -> bool {
+            self.inner.is_human_readable()
+        }
+
+        forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+            option unit unit_struct newtype_struct seq tuple tuple_struct map struct identifier
+            ignored_any
+        }
+    }
+
+    struct EnumVisitor<V> {
+        inner: V,
+        variants: &'static [&'static str],
+    }
+
+    impl<'de, V: Visitor<'de>> Visitor<'de> for EnumVisitor<V> {
+        type Value = V::Value;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            self.inner.expecting(f)
+        }
+
+        fn visit_enum<A: EnumAccess<'de>>(self, data: A) -> Result<V::Value, A::Error> {
+            self.inner.visit_enum(UnknownEnumAccess {
+                inner: data,
+                variants: self.variants,
+            })
+        }
+    }
+
+    struct UnknownEnumAccess<A> {
+        inner: A,
+        variants: &'static [&'static str],
+    }
+
+    impl<'de, A: EnumAccess<'de>> EnumAccess<'de> for UnknownEnumAccess<A> {
+        type Error = A::Error;
+        type Variant = A::Variant;
+
+        fn variant_seed<T: DeserializeSeed<'de>>(
+            self,
+            seed: T,
+        ) -> Result<(T::Value, A::Variant), A::Error> {
+            self.inner.variant_seed(VariantSeed {
+                inner: seed,
+                variants: self.variants,
+            })
+        }
+    }
+
+    struct VariantSeed<T> {
+        inner: T,
+        variants: &'static [&'static str],
+    }
+
+    impl<'de, T: DeserializeSeed<'de>> DeserializeSeed<'de> for VariantSeed<T> {
+        type Value = T::Value;
+
+        fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<T::Value, D::Error> {
+            deserializer.deserialize_identifier(VariantVisitor {
+                inner: self.inner,
+                variants: self.variants,
+            })
+        }
+    }
+
+    /// Visits the method name of a request, handing the seed of the variant either the name or,
+    /// if the enum doesn't know it, [`UnknownMethod::NAME`].
+    struct VariantVisitor<T> {
+        inner: T,
+        variants: &'static [&'static str],
+    }
+
+    impl<T> VariantVisitor<T> {
+        fn known(&self, name: &str) -> &'static str {
+            self.variants
+                .iter()
+                .copied()
+                .find(|&variant| variant == name)
+                .unwrap_or(UnknownMethod::NAME)
+        }
+    }
+
+    impl<'de, T: DeserializeSeed<'de>> Visitor<'de> for VariantVisitor<T> {
+        type Value = T::Value;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a method name")
+        }
+
+        fn visit_u64<E: de::Error>(self, index: u64) -> Result<T::Value, E> {
+            self.inner
+                .deserialize(de::value::U64Deserializer::new(index))
+        }
+
+        fn visit_str<E: de::Error>(self, name: &str) -> Result<T::Value, E> {
+            let name = self.known(name);
+            self.inner
+                .deserialize(de::value::BorrowedStrDeserializer::new(name))
+        }
+
+        fn visit_bytes<E: de::Error>(self, name: &[u8]) -> Result<T::Value, E> {
+            let name =
+                std::str::from_utf8(name).map_or(UnknownMethod::NAME, |name| self.known(name));
+            self.inner
+                .deserialize(de::value::BorrowedStrDeserializer::new(name))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_versions_numerically() {
+        assert_eq!(compare_versions("1.10", "1.9"), Some(Ordering::Greater));
+        assert_eq!(compare_versions("1.2", "1.2.0"), Some(Ordering::Equal));
+        assert_eq!(compare_versions("1", "1.0.1"), Some(Ordering::Less));
+        assert_eq!(compare_versions("1.x", "1.0"), None);
+        assert_eq!(compare_versions("1.+2", "1.0"), None);
+        assert_eq!(compare_versions("1..2", "1.0"), None);
+    }
+
+    #[test]
+    fn methods_without_versions_are_always_supported() {
+        assert!(is_supported(None, "0.1"));
+        assert!(is_supported(None, "not a version"));
+        assert!(is_supported(Some("1.2"), "1.2"));
+        assert!(!is_supported(Some("1.2"), "1.1.9"));
+        assert!(!is_supported(Some("1.2"), "not a version"));
+
+        let version = MethodVersion {
+            method: "World.hello",
+            since: Some("1.0"),
+            deprecated_since: Some("2.0"),
+        };
+        assert!(!version.is_deprecated_at("1.5"));
+        assert!(version.is_deprecated_at("2.0"));
+    }
+}
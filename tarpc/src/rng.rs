@@ -0,0 +1,63 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Seeds the random numbers tarpc draws, for deterministic simulations.
+//!
+//! tarpc draws random numbers for the trace and span IDs of requests and for the sampling
+//! decisions of [shadowed](crate::server::shadow) requests. By default, they come from the
+//! thread's [`ThreadRng`](rand::rngs::ThreadRng), so two runs of a simulation take different
+//! courses. A simulation running on one thread, like the current-thread runtimes of network
+//! simulators, can [`seed`] them so that every run draws the same numbers.
+//!
+//! With the `opentelemetry` feature, the trace IDs of new traces come from the ID generator of the
+//! OpenTelemetry tracer provider instead, which can be made deterministic when the provider is
+//! built.
+
+use rand::{rngs::StdRng, RngCore, SeedableRng};
+use std::cell::RefCell;
+
+thread_local! {
+    /// The generator of the random numbers drawn on this thread, if seeded.
+    static SEEDED: RefCell<Option<StdRng>> = const { RefCell::new(None) };
+}
+
+/// Draws the random numbers of this thread from a generator seeded with `seed`, until
+/// [`unseed`] is called.
+pub fn seed(seed: u64) {
+    SEEDED.with(|seeded| *seeded.borrow_mut() = Some(StdRng::seed_from_u64(seed)));
+}
+
+/// Draws the random numbers of this thread from its [`ThreadRng`](rand::rngs::ThreadRng) again.
+pub fn unseed() {
+    SEEDED.with(|seeded| *seeded.borrow_mut() = None);
+}
+
+/// Calls `f` with the generator of this thread's random numbers.
+pub(crate) fn with<T>(f: impl FnOnce(&mut dyn RngCore) -> T) -> T {
+    SEEDED.with(|seeded| match &mut *seeded.borrow_mut() {
+        Some(rng) => f(rng),
+        None => f(&mut rand::thread_rng()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn seeded_threads_draw_the_same_numbers() {
+        let draw = || with(|rng| rng.gen::<[u64; 4]>());
+        seed(7);
+        let first = draw();
+        seed(7);
+        assert_eq!(draw(), first);
+        seed(8);
+        assert_ne!(draw(), first);
+        unseed();
+        assert_ne!(draw(), first);
+    }
+}
@@ -0,0 +1,646 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Provides a machine-readable description of a service.
+//!
+//! With `#[tarpc::service(schema = true)]`, the generated request enum has a `schema()` fn
+//! describing the service's methods: their names on the wire, their args, what they return, and
+//! how they're called. With the `serde1` feature, the schema serializes, so tooling like gateways
+//! and clients in other languages can be generated from a running binary.
+//!
+//! Types are described as written in the service definition, e.g. `Vec<String>`.
+//!
+//! A schema can also be rendered as [TypeScript typings](ServiceSchema::typescript) or a
+//! [JSON Schema](ServiceSchema::json_schema) of the service's requests and responses, as they're
+//! serialized by the Json transport, so that web frontends can talk to a service with type
+//! safety. The typings cover the types of std and serde that serialize to fixed shapes; other
+//! types, like the structs of the service's crate, are left open as `unknown` and `{}`, named in
+//! a comment and a description.
+//!
+//! # Example
+//!
+//! ```rust
+//! use tarpc::schema::MethodKind;
+//!
+//! #[tarpc::service(schema = true)]
+//! trait World {
+//!     /// Says hello.
+//!     async fn hello(name: String) -> String;
+//! }
+//!
+//! let schema = WorldRequest::schema();
+//! assert_eq!(schema.name, "World");
+//! let hello = &schema.methods[0];
+//! assert_eq!(hello.wire_name, "Hello");
+//! assert_eq!(hello.doc, "Says hello.");
+//! assert_eq!(hello.args[0].ty, "String");
+//! assert_eq!(hello.output, "String");
+//! assert_eq!(hello.kind, MethodKind::Unary);
+//! assert_eq!(
+//!     schema.typescript(),
+//!     "\
+//! export type WorldRequest =
+//!   /** Says hello. */
+//!   | { Hello: { name: string } };
+//!
+//! export type WorldResponse =
+//!   /** Says hello. */
+//!   | { Hello: string };
+//! "
+//! );
+//! ```
+
+use std::fmt::Write;
+
+/// A description of a service.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize))]
+pub struct ServiceSchema {
+    /// The name of the service trait.
+    pub name: &'static str,
+    /// The doc comment of the service trait, or empty if it has none.
+    pub doc: &'static str,
+    /// The methods of the service, in declaration order.
+    pub methods: &'static [MethodSchema],
+}
+
+impl ServiceSchema {
+    /// Returns the method sent on the wire as `wire_name`, if any.
+    pub fn method(&self, wire_name: &str) -> Option<&MethodSchema> {
+        self.methods
+            .iter()
+            .find(|method| method.wire_name == wire_name)
+    }
+
+    /// Returns TypeScript typings of the service's requests and responses, as they're serialized
+    /// by the Json transport: the types `{Service}Request` and `{Service}Response`, with a case
+    /// per method. Oneway methods have no response, and the responses of streaming methods are
+    /// their items. Requests and responses are still wrapped in the
+    /// [`ClientMessage`](crate::ClientMessage) and [`Response`](crate::Response) envelopes on the
+    /// wire.
+    ///
+    /// The typings describe services whose methods are sent by name, i.e. not declared with
+    /// `method_ids = true`. They follow the `rename`, `rename_all`, and `alias` serde attributes
+    /// of methods and args; `schema = true` rejects other serde attributes there.
+    ///
+    /// Integers are typed as `number`, which is what `JSON.parse` returns for them, but a
+    /// `number` only holds integers up to 2<sup>53</sup> exactly. Integers of 64 bits or more,
+    /// like `u64`, are typed `number /* u64 */` to flag that their large values lose precision
+    /// in JavaScript; parse such responses with a parser that keeps big integers, or send them as
+    /// strings.
+    pub fn typescript(&self) -> String {
+        let mut typescript = String::new();
+        self.write_typescript_union(
+            &mut typescript,
+            "Request",
+            self.methods
+                .iter()
+                .map(|method| {
+                    let args = method
+                        .args
+                        .iter()
+                        .map(|arg| format!("{}: {}", arg.name, Type::parse(arg.ty).typescript()))
+                        .collect::<Vec<_>>();
+                    let args = if args.is_empty() {
+                        "{}".to_string()
+                    } else {
+                        format!("{{ {} }}", args.join("; "))
+                    };
+                    (method.doc, format!("{{ {}: {args} }}", method.wire_name))
+                })
+                .collect(),
+        );
+        typescript.push('\n');
+        self.write_typescript_union(
+            &mut typescript,
+            "Response",
+            self.methods
+                .iter()
+                .filter(|method| method.kind != MethodKind::Oneway)
+                .map(|method| {
+                    let output = Type::parse(method.output).typescript();
+                    (method.doc, format!("{{ {}: {output} }}", method.wire_name))
+                })
+                .collect(),
+        );
+        typescript
+    }
+
+    /// Returns a JSON Schema (draft 2020-12) of the service's requests and responses, as they're
+    /// serialized by the Json transport. The schema defines `{Service}Request` and
+    /// `{Service}Response` in its `$defs`, with the same shapes as the
+    /// [TypeScript typings](Self::typescript). Integers are exact in JSON Schema, so they're
+    /// typed `integer` whatever their width.
+    pub fn json_schema(&self) -> String {
+        let variant = |method: &MethodSchema, payload: String| {
+            variant_schema(method.wire_name, &payload, method.doc)
+        };
+        let requests = self.methods.iter().map(|method| {
+            let args = method
+                .args
+                .iter()
+                .map(|arg| (arg.name, Type::parse(arg.ty).json_schema()))
+                .collect::<Vec<_>>();
+            variant(method, object_schema(&args))
+        });
+        let responses = self
+            .methods
+            .iter()
+            .filter(|method| method.kind != MethodKind::Oneway)
+            .map(|method| variant(method, Type::parse(method.output).json_schema()));
+        let one_of = |variants: Vec<String>| format!(r#"{{"oneOf":[{}]}}"#, variants.join(","));
+
+        let mut schema =
+            String::from(r#"{"$schema":"https://json-schema.org/draft/2020-12/schema""#);
+        let _ = write!(schema, r#","title":{}"#, json_string(self.name));
+        if !self.doc.is_empty() {
+            let _ = write!(schema, r#","description":{}"#, json_string(self.doc));
+        }
+        let _ = write!(
+            schema,
+            r#","$defs":{{{}:{},{}:{}}}}}"#,
+            json_string(&format!("{}Request", self.name)),
+            one_of(requests.collect()),
+            json_string(&format!("{}Response", self.name)),
+            one_of(responses.collect()),
+        );
+        schema
+    }
+
+    fn write_typescript_union(&self, out: &mut String, name: &str, cases: Vec<(&str, String)>) {
+        out.push_str(&typescript_doc(self.doc, ""));
+        let _ = write!(out, "export type {}{name} =", self.name);
+        if cases.is_empty() {
+            out.push_str(" never");
+        }
+        for (doc, case) in cases {
+            out.push('\n');
+            out.push_str(&typescript_doc(doc, "  "));
+            let _ = write!(out, "  | {case}");
+        }
+        out.push_str(";\n");
+    }
+}
+
+/// A description of a service method.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize))]
+pub struct MethodSchema {
+    /// The name of the method in the service trait, e.g. `hello`.
+    pub name: &'static str,
+    /// The name of the method's variants in the serialized request and response enums, e.g.
+    /// `Hello`.
+    pub wire_name: &'static str,
+    /// The doc comment of the method, or empty if it has none.
+    pub doc: &'static str,
+    /// The args of the method, in declaration order.
+    pub args: &'static [ArgSchema],
+    /// The type sent in the method's responses: the item type of streaming methods, and `T` for
+    /// methods marked `#[tarpc::application_error]`.
+    pub output: &'static str,
+    /// Whether the method's errors are sent as application errors.
+    pub application_errors: bool,
+    /// How the method is called.
+    pub kind: MethodKind,
+    /// The version that introduced the method, if declared with `#[tarpc::since]`.
+    pub since: Option<&'static str>,
+    /// The version that deprecated the method, if declared with `#[tarpc::deprecated_since]`.
+    pub deprecated_since: Option<&'static str>,
+    /// The id the method is sent as, if the service is declared with `method_ids = true`.
+    pub id: Option<u32>,
+}
+
+/// A description of an arg of a service method.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize))]
+pub struct ArgSchema {
+    /// The name of the arg, which is also the name of its field in the request enum.
+    pub name: &'static str,
+    /// The type of the arg.
+    pub ty: &'static str,
+}
+
+/// How a method is called.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde1", serde(rename_all = "snake_case"))]
+pub enum MethodKind {
+    /// The method responds once.
+    Unary,
+    /// The method responds with a stream of responses.
+    Stream,
+    /// The method doesn't respond.
+    Oneway,
+}
+
+/// A type as written in a service definition, parsed just enough to describe how it serializes.
+#[derive(Debug, PartialEq, Eq)]
+enum Type<'a> {
+    /// A path, e.g. `std::vec::Vec<u8>`, named by its last segment.
+    Path {
+        raw: &'a str,
+        name: &'a str,
+        args: Vec<Type<'a>>,
+    },
+    /// A slice or array of the type.
+    Sequence(Box<Type<'a>>),
+    /// A reference.
+    Ref(Box<Type<'a>>),
+    /// A tuple; the unit type if empty.
+    Tuple(Vec<Type<'a>>),
+    /// Anything else, e.g. `dyn Trait`.
+    Other(&'a str),
+}
+
+impl<'a> Type<'a> {
+    fn parse(ty: &'a str) -> Self {
+        let ty = ty.trim();
+        if let Some(referent) = ty.strip_prefix('&') {
+            let mut referent = referent.trim_start();
+            if referent.starts_with('\'') {
+                referent = referent
+                    .split_once(' ')
+                    .map_or("", |(_, referent)| referent.trim_start());
+            }
+            let referent = referent.strip_prefix("mut ").unwrap_or(referent);
+            return Type::Ref(Box::new(Type::parse(referent)));
+        }
+        if let Some(elements) = ty.strip_prefix('(').and_then(|ty| ty.strip_suffix(')')) {
+            let mut elements = split_top_level(elements, ',');
+            if elements.len() == 1 && !elements[0].is_empty() && !ty.ends_with(",)") {
+                return Type::parse(elements[0]);
+            }
+            elements.retain(|element| !element.is_empty());
+            return Type::Tuple(elements.into_iter().map(Type::parse).collect());
+        }
+        if let Some(element) = ty.strip_prefix('[').and_then(|ty| ty.strip_suffix(']')) {
+            let element = split_top_level(element, ';')[0];
+            return Type::Sequence(Box::new(Type::parse(element)));
+        }
+        let (path, args) = match ty.find('<') {
+            Some(start) if ty.ends_with('>') => (&ty[..start], &ty[start + 1..ty.len() - 1]),
+            _ => (ty, ""),
+        };
+        let path = path.trim();
+        if path.is_empty()
+            || !path
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == ':')
+        {
+            return Type::Other(ty);
+        }
+        let args = split_top_level(args, ',')
+            .into_iter()
+            .filter(|arg| !arg.is_empty() && !arg.starts_with('\'') && !arg.contains('='))
+            .map(Type::parse)
+            .collect();
+        Type::Path {
+            raw: ty,
+            name: path.rsplit("::").next().unwrap_or(path),
+            args,
+        }
+    }
+
+    fn typescript(&self) -> String {
+        let array = |element: &Type| {
+            let element = element.typescript();
+            if element.contains(' ') {
+                format!("({element})[]")
+            } else {
+                format!("{element}[]")
+            }
+        };
+        match self {
+            Type::Ref(referent) => referent.typescript(),
+            Type::Sequence(element) => array(element),
+            Type::Tuple(elements) if elements.is_empty() => "null".into(),
+            Type::Tuple(elements) => format!(
+                "[{}]",
+                elements
+                    .iter()
+                    .map(Type::typescript)
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            Type::Path { name, args, .. } => match (*name, &args[..]) {
+                ("bool", []) => "boolean".into(),
+                (name, []) if is_wide_integer(name) => format!("number /* {name} */"),
+                (name, []) if is_number(name) => "number".into(),
+                (name, []) if is_string(name) => "string".into(),
+                ("Duration", []) => "{ secs: number; nanos: number }".into(),
+                ("SystemTime", []) => {
+                    "{ secs_since_epoch: number; nanos_since_epoch: number }".into()
+                }
+                ("Option", [some]) => format!("{} | null", some.typescript()),
+                (name, [element]) if is_sequence(name) => array(element),
+                (name, [_, value]) if is_map(name) => {
+                    format!("Record<string, {}>", value.typescript())
+                }
+                (name, [pointee]) if is_pointer(name) => pointee.typescript(),
+                ("Result", [ok, err]) => format!(
+                    "{{ Ok: {} }} | {{ Err: {} }}",
+                    ok.typescript(),
+                    err.typescript()
+                ),
+                _ => self.unknown_typescript(),
+            },
+            Type::Other(_) => self.unknown_typescript(),
+        }
+    }
+
+    fn unknown_typescript(&self) -> String {
+        format!("unknown /* {} */", self.raw().replace("*/", "*\\/"))
+    }
+
+    fn json_schema(&self) -> String {
+        let array =
+            |element: &Type| format!(r#"{{"type":"array","items":{}}}"#, element.json_schema());
+        match self {
+            Type::Ref(referent) => referent.json_schema(),
+            Type::Sequence(element) => array(element),
+            Type::Tuple(elements) if elements.is_empty() => r#"{"type":"null"}"#.into(),
+            Type::Tuple(elements) => format!(
+                r#"{{"type":"array","prefixItems":[{}],"minItems":{len},"maxItems":{len}}}"#,
+                elements
+                    .iter()
+                    .map(Type::json_schema)
+                    .collect::<Vec<_>>()
+                    .join(","),
+                len = elements.len()
+            ),
+            Type::Path { name, args, .. } => match (*name, &args[..]) {
+                ("bool", []) => r#"{"type":"boolean"}"#.into(),
+                ("f32" | "f64", []) => r#"{"type":"number"}"#.into(),
+                (name, []) if is_number(name) => INTEGER.into(),
+                ("char", []) => r#"{"type":"string","minLength":1,"maxLength":1}"#.into(),
+                (name, []) if is_string(name) => r#"{"type":"string"}"#.into(),
+                ("Duration", []) => {
+                    object_schema(&[("secs", INTEGER.into()), ("nanos", INTEGER.into())])
+                }
+                ("SystemTime", []) => object_schema(&[
+                    ("secs_since_epoch", INTEGER.into()),
+                    ("nanos_since_epoch", INTEGER.into()),
+                ]),
+                ("Option", [some]) => {
+                    format!(r#"{{"anyOf":[{},{{"type":"null"}}]}}"#, some.json_schema())
+                }
+                (name, [element]) if is_sequence(name) => array(element),
+                (name, [_, value]) if is_map(name) => format!(
+                    r#"{{"type":"object","additionalProperties":{}}}"#,
+                    value.json_schema()
+                ),
+                (name, [pointee]) if is_pointer(name) => pointee.json_schema(),
+                ("Result", [ok, err]) => format!(
+                    r#"{{"oneOf":[{},{}]}}"#,
+                    variant_schema("Ok", &ok.json_schema(), ""),
+                    variant_schema("Err", &err.json_schema(), "")
+                ),
+                _ => self.unknown_json_schema(),
+            },
+            Type::Other(_) => self.unknown_json_schema(),
+        }
+    }
+
+    fn unknown_json_schema(&self) -> String {
+        format!(r#"{{"description":{}}}"#, json_string(self.raw()))
+    }
+
+    fn raw(&self) -> &'a str {
+        match *self {
+            Type::Path { raw, .. } | Type::Other(raw) => raw,
+            _ => unreachable!("only paths and other types are unknown"),
+        }
+    }
+}
+
+fn is_number(name: &str) -> bool {
+    matches!(
+        name,
+        "u8" | "u16"
+            | "u32"
+            | "u64"
+            | "u128"
+            | "usize"
+            | "i8"
+            | "i16"
+            | "i32"
+            | "i64"
+            | "i128"
+            | "isize"
+            | "f32"
+            | "f64"
+            | "NonZeroU8"
+            | "NonZeroU16"
+            | "NonZeroU32"
+            | "NonZeroU64"
+            | "NonZeroUsize"
+    )
+}
+
+/// Returns true for integers that may not fit in the 53 bits of a JavaScript `number`.
+fn is_wide_integer(name: &str) -> bool {
+    matches!(
+        name,
+        "u64" | "u128" | "usize" | "i64" | "i128" | "isize" | "NonZeroU64" | "NonZeroUsize"
+    )
+}
+
+fn is_string(name: &str) -> bool {
+    matches!(
+        name,
+        "String" | "str" | "char" | "PathBuf" | "Path" | "IpAddr" | "SocketAddr"
+    )
+}
+
+fn is_sequence(name: &str) -> bool {
+    matches!(
+        name,
+        "Vec" | "VecDeque" | "LinkedList" | "HashSet" | "BTreeSet" | "BinaryHeap"
+    )
+}
+
+fn is_map(name: &str) -> bool {
+    matches!(name, "HashMap" | "BTreeMap")
+}
+
+fn is_pointer(name: &str) -> bool {
+    matches!(name, "Box" | "Rc" | "Arc" | "Cow")
+}
+
+/// Splits `s` at each `separator` outside of brackets.
+fn split_top_level(s: &str, separator: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let (mut depth, mut start) = (0, 0);
+    for (i, c) in s.char_indices() {
+        match c {
+            '<' | '(' | '[' => depth += 1,
+            '>' | ')' | ']' => depth -= 1,
+            c if c == separator && depth == 0 => {
+                parts.push(s[start..i].trim());
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(s[start..].trim());
+    parts
+}
+
+const INTEGER: &str = r#"{"type":"integer"}"#;
+
+/// Returns the schema of an object with exactly the given properties, and their schemas.
+fn object_schema(properties: &[(&str, String)]) -> String {
+    let names = properties
+        .iter()
+        .map(|(name, _)| json_string(name))
+        .collect::<Vec<_>>();
+    let properties = properties
+        .iter()
+        .map(|(name, schema)| format!("{}:{schema}", json_string(name)))
+        .collect::<Vec<_>>();
+    format!(
+        r#"{{"type":"object","properties":{{{}}},"required":[{}],"additionalProperties":false}}"#,
+        properties.join(","),
+        names.join(",")
+    )
+}
+
+/// Returns the schema of an externally tagged enum variant, described by `doc` unless it's
+/// empty.
+fn variant_schema(name: &str, payload: &str, doc: &str) -> String {
+    let mut schema = String::from(r#"{"type":"object""#);
+    if !doc.is_empty() {
+        let _ = write!(schema, r#","description":{}"#, json_string(doc));
+    }
+    let _ = write!(
+        schema,
+        r#","properties":{{{}:{payload}}},"required":[{0}],"additionalProperties":false}}"#,
+        json_string(name)
+    );
+    schema
+}
+
+fn json_string(s: &str) -> String {
+    let mut json = String::with_capacity(s.len() + 2);
+    json.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(json, "\\u{:04x}", c as u32);
+            }
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+/// Returns `doc` as a TSDoc comment, indented by `indent`, or nothing if it's empty.
+fn typescript_doc(doc: &str, indent: &str) -> String {
+    let doc = doc.replace("*/", "*\\/");
+    match doc.lines().collect::<Vec<_>>()[..] {
+        [] => String::new(),
+        [line] => format!("{indent}/** {line} */\n"),
+        ref lines => {
+            let mut comment = format!("{indent}/**\n");
+            for line in lines {
+                let _ = writeln!(
+                    comment,
+                    "{indent} *{}{line}",
+                    if line.is_empty() { "" } else { " " }
+                );
+            }
+            let _ = writeln!(comment, "{indent} */");
+            comment
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typescript(ty: &str) -> String {
+        Type::parse(ty).typescript()
+    }
+
+    #[test]
+    fn parses_types() {
+        assert_eq!(Type::parse("()"), Type::Tuple(vec![]));
+        assert_eq!(
+            Type::parse("&'a [u8]"),
+            Type::Ref(Box::new(Type::Sequence(Box::new(Type::Path {
+                raw: "u8",
+                name: "u8",
+                args: vec![],
+            }))))
+        );
+        assert_eq!(
+            Type::parse("std::borrow::Cow<'a, str>"),
+            Type::Path {
+                raw: "std::borrow::Cow<'a, str>",
+                name: "Cow",
+                args: vec![Type::Path {
+                    raw: "str",
+                    name: "str",
+                    args: vec![],
+                }],
+            }
+        );
+        assert_eq!(Type::parse("dyn Any"), Type::Other("dyn Any"));
+    }
+
+    #[test]
+    fn describes_types_in_typescript() {
+        assert_eq!(typescript("()"), "null");
+        assert_eq!(typescript("&mut str"), "string");
+        assert_eq!(typescript("Option<Vec<u8>>"), "number[] | null");
+        assert_eq!(typescript("Vec<Option<u8>>"), "(number | null)[]");
+        assert_eq!(typescript("(u32, [u8; 4])"), "[number, number[]]");
+        assert_eq!(typescript("(u32,)"), "[number]");
+        assert_eq!(typescript("Vec<u64>"), "(number /* u64 */)[]");
+        assert_eq!(
+            typescript("std::collections::HashMap<String, Arc<bool>>"),
+            "Record<string, boolean>"
+        );
+        assert_eq!(
+            typescript("Result<String, Error>"),
+            "{ Ok: string } | { Err: unknown /* Error */ }"
+        );
+        assert_eq!(typescript("Point<f64>"), "unknown /* Point<f64> */");
+    }
+
+    #[test]
+    fn describes_types_in_json_schema() {
+        assert_eq!(Type::parse("u8").json_schema(), r#"{"type":"integer"}"#);
+        assert_eq!(
+            Type::parse("Option<f32>").json_schema(),
+            r#"{"anyOf":[{"type":"number"},{"type":"null"}]}"#
+        );
+        assert_eq!(
+            Type::parse("(bool, String)").json_schema(),
+            r#"{"type":"array","prefixItems":[{"type":"boolean"},{"type":"string"}],"minItems":2,"maxItems":2}"#
+        );
+        assert_eq!(
+            Type::parse("Point").json_schema(),
+            r#"{"description":"Point"}"#
+        );
+    }
+
+    #[test]
+    fn escapes_docs() {
+        assert_eq!(json_string("a \"b\"\n\u{1}"), r#""a \"b\"\n\u0001""#);
+        assert_eq!(
+            typescript_doc("Ends */ early.\n\nTwo.", ""),
+            "/**\n * Ends *\\/ early.\n *\n * Two.\n */\n"
+        );
+    }
+}
@@ -8,9 +8,11 @@
 
 #![deny(missing_docs)]
 
+use crate::{stats::ChannelStats, transport::MalformedMessage};
+use bytes::{BufMut, Bytes, BytesMut};
+use count_bytes::CountBytes;
 use futures::{prelude::*, task::*};
 use pin_project::pin_project;
-use serde::{Deserialize, Serialize};
 use std::{error::Error, io, pin::Pin};
 use tokio::io::{AsyncRead, AsyncWrite};
 use tokio_serde::{Framed as SerdeFramed, *};
@@ -20,42 +22,47 @@ use tokio_util::codec::{length_delimited::LengthDelimitedCodec, Framed};
 #[pin_project]
 pub struct Transport<S, Item, SinkItem, Codec> {
     #[pin]
-    inner: SerdeFramed<Framed<S, LengthDelimitedCodec>, Item, SinkItem, Codec>,
+    inner: SerdeFramed<CountBytes<Framed<S, LengthDelimitedCodec>>, Item, SinkItem, Codec>,
+    /// Counts the transport toward the connection limits of the listener that accepted it until
+    /// the transport is dropped.
+    #[cfg(feature = "tcp")]
+    permit: Option<tcp::ConnectionPermit>,
 }
 
 impl<S, Item, SinkItem, Codec> Transport<S, Item, SinkItem, Codec> {
     /// Returns the inner transport over which messages are sent and received.
     pub fn get_ref(&self) -> &S {
-        self.inner.get_ref().get_ref()
+        self.inner.get_ref().inner.get_ref()
+    }
+
+    /// Returns the counters of the bytes read and written by the transport. Pass them to the
+    /// channel using the transport to collect all of its counters in one place.
+    pub fn stats(&self) -> &ChannelStats {
+        &self.inner.get_ref().stats
     }
 }
 
 impl<S, Item, SinkItem, Codec, CodecError> Stream for Transport<S, Item, SinkItem, Codec>
 where
     S: AsyncWrite + AsyncRead,
-    Item: for<'a> Deserialize<'a>,
     Codec: Deserializer<Item>,
     CodecError: Into<Box<dyn std::error::Error + Send + Sync>>,
-    SerdeFramed<Framed<S, LengthDelimitedCodec>, Item, SinkItem, Codec>:
+    SerdeFramed<CountBytes<Framed<S, LengthDelimitedCodec>>, Item, SinkItem, Codec>:
         Stream<Item = Result<Item, CodecError>>,
 {
     type Item = io::Result<Item>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<io::Result<Item>>> {
-        self.project()
-            .inner
-            .poll_next(cx)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+        self.project().inner.poll_next(cx).map_err(io::Error::other)
     }
 }
 
 impl<S, Item, SinkItem, Codec, CodecError> Sink<SinkItem> for Transport<S, Item, SinkItem, Codec>
 where
     S: AsyncWrite,
-    SinkItem: Serialize,
     Codec: Serializer<SinkItem>,
     CodecError: Into<Box<dyn Error + Send + Sync>>,
-    SerdeFramed<Framed<S, LengthDelimitedCodec>, Item, SinkItem, Codec>:
+    SerdeFramed<CountBytes<Framed<S, LengthDelimitedCodec>>, Item, SinkItem, Codec>:
         Sink<SinkItem, Error = CodecError>,
 {
     type Error = io::Error;
@@ -64,28 +71,226 @@ where
         self.project()
             .inner
             .poll_ready(cx)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+            .map_err(io::Error::other)
     }
 
     fn start_send(self: Pin<&mut Self>, item: SinkItem) -> io::Result<()> {
         self.project()
             .inner
             .start_send(item)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+            .map_err(io::Error::other)
     }
 
     fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
         self.project()
             .inner
             .poll_flush(cx)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+            .map_err(io::Error::other)
     }
 
     fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
         self.project()
             .inner
             .poll_close(cx)
-            .map_err(|e| io::Error::new(io::ErrorKind::Other, e))
+            .map_err(io::Error::other)
+    }
+}
+
+mod count_bytes {
+    use crate::stats::ChannelStats;
+    use bytes::{Bytes, BytesMut};
+    use futures::{prelude::*, ready, task::*};
+    use pin_project::pin_project;
+    use std::{
+        collections::VecDeque,
+        io::{self, IoSlice},
+        pin::Pin,
+    };
+    use tokio::io::AsyncWrite;
+    use tokio_util::codec::{Encoder, Framed, LengthDelimitedCodec};
+
+    /// The number of bytes of queued frames above which they're written before more are queued.
+    const BACKPRESSURE_BOUNDARY: usize = 128 * 1024;
+
+    /// The most slices passed to a single vectored write.
+    const MAX_SLICES: usize = 64;
+
+    /// Counts the bytes of the frames read from and written to a framed byte stream.
+    ///
+    /// If the byte stream supports vectored writes and frames have the default 4-byte length
+    /// header, frames are queued and written together with a vectored write when flushed, rather
+    /// than copied into the write buffer of the framed byte stream.
+    #[pin_project]
+    pub struct CountBytes<T> {
+        #[pin]
+        pub(super) inner: T,
+        pub(super) stats: ChannelStats,
+        /// The frames waiting to be written, if written with vectored writes.
+        pub(super) queued: Option<QueuedFrames>,
+    }
+
+    impl<S: AsyncWrite> CountBytes<Framed<S, LengthDelimitedCodec>> {
+        /// Wraps `framed_io`, writing its frames with vectored writes if possible.
+        pub(super) fn new(mut framed_io: Framed<S, LengthDelimitedCodec>) -> Self {
+            // The codec's settings aren't exposed, so check that it writes the default header by
+            // encoding a probe frame.
+            let mut probe = BytesMut::new();
+            let vectored = framed_io.get_ref().is_write_vectored()
+                && framed_io.write_buffer().is_empty()
+                && framed_io
+                    .codec_mut()
+                    .encode(Bytes::from_static(&[0xff]), &mut probe)
+                    .is_ok()
+                && probe[..] == [0, 0, 0, 1, 0xff];
+            Self {
+                inner: framed_io,
+                stats: ChannelStats::default(),
+                queued: vectored.then(QueuedFrames::default),
+            }
+        }
+    }
+
+    /// Frames waiting to be written, each with its length header.
+    #[derive(Default)]
+    pub(super) struct QueuedFrames {
+        frames: VecDeque<([u8; 4], Bytes)>,
+        /// The number of bytes of the first frame, including its header, already written.
+        written: usize,
+        /// The number of bytes waiting to be written.
+        len: usize,
+    }
+
+    impl QueuedFrames {
+        fn push(&mut self, frame: Bytes) -> io::Result<()> {
+            let header = u32::try_from(frame.len())
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "frame size too big"))?
+                .to_be_bytes();
+            self.len += header.len() + frame.len();
+            self.frames.push_back((header, frame));
+            Ok(())
+        }
+
+        /// Writes all queued frames to `io`, as many as fit in each vectored write.
+        fn poll_write<W: AsyncWrite>(
+            &mut self,
+            mut io: Pin<&mut W>,
+            cx: &mut Context<'_>,
+        ) -> Poll<io::Result<()>> {
+            while !self.frames.is_empty() {
+                let written = {
+                    let mut slices = [IoSlice::new(&[]); MAX_SLICES];
+                    let mut len = 0;
+                    let mut skip = self.written;
+                    let parts = self
+                        .frames
+                        .iter()
+                        .flat_map(|(header, frame)| [&header[..], &frame[..]]);
+                    for part in parts {
+                        if skip >= part.len() {
+                            skip -= part.len();
+                            continue;
+                        }
+                        if len == MAX_SLICES {
+                            break;
+                        }
+                        slices[len] = IoSlice::new(&part[skip..]);
+                        skip = 0;
+                        len += 1;
+                    }
+                    ready!(io.as_mut().poll_write_vectored(cx, &slices[..len]))?
+                };
+                if written == 0 {
+                    return Poll::Ready(Err(io::Error::new(
+                        io::ErrorKind::WriteZero,
+                        "failed to write frame to transport",
+                    )));
+                }
+                self.advance(written);
+            }
+            Poll::Ready(Ok(()))
+        }
+
+        fn advance(&mut self, written: usize) {
+            self.len -= written;
+            self.written += written;
+            while let Some((header, frame)) = self.frames.front() {
+                let frame_len = header.len() + frame.len();
+                if self.written < frame_len {
+                    break;
+                }
+                self.written -= frame_len;
+                self.frames.pop_front();
+            }
+        }
+    }
+
+    impl<T> Stream for CountBytes<T>
+    where
+        T: Stream<Item = io::Result<BytesMut>>,
+    {
+        type Item = io::Result<BytesMut>;
+
+        fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+            let this = self.project();
+            let frame = ready!(this.inner.poll_next(cx));
+            if let Some(Ok(frame)) = &frame {
+                this.stats.record_bytes_read(frame.len());
+            }
+            Poll::Ready(frame)
+        }
+    }
+
+    impl<S> Sink<Bytes> for CountBytes<Framed<S, LengthDelimitedCodec>>
+    where
+        S: AsyncWrite,
+    {
+        type Error = io::Error;
+
+        fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            let this = self.project();
+            match this.queued {
+                Some(queued) if queued.len >= BACKPRESSURE_BOUNDARY => {
+                    queued.poll_write(this.inner.get_pin_mut(), cx)
+                }
+                Some(_) => Poll::Ready(Ok(())),
+                None => Sink::<Bytes>::poll_ready(this.inner, cx),
+            }
+        }
+
+        fn start_send(self: Pin<&mut Self>, frame: Bytes) -> io::Result<()> {
+            let this = self.project();
+            let len = frame.len();
+            match this.queued {
+                Some(queued) => {
+                    if len > this.inner.codec().max_frame_length() {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            "frame size too big",
+                        ));
+                    }
+                    queued.push(frame)?;
+                }
+                None => this.inner.start_send(frame)?,
+            }
+            this.stats.record_bytes_written(len);
+            Ok(())
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            let mut this = self.project();
+            if let Some(queued) = this.queued {
+                ready!(queued.poll_write(this.inner.as_mut().get_pin_mut(), cx))?;
+            }
+            Sink::<Bytes>::poll_flush(this.inner, cx)
+        }
+
+        fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            let mut this = self.project();
+            if let Some(queued) = this.queued {
+                ready!(queued.poll_write(this.inner.as_mut().get_pin_mut(), cx))?;
+            }
+            Sink::<Bytes>::poll_close(this.inner, cx)
+        }
     }
 }
 
@@ -96,20 +301,18 @@ pub fn new<S, Item, SinkItem, Codec>(
 ) -> Transport<S, Item, SinkItem, Codec>
 where
     S: AsyncWrite + AsyncRead,
-    Item: for<'de> Deserialize<'de>,
-    SinkItem: Serialize,
     Codec: Serializer<SinkItem> + Deserializer<Item>,
 {
     Transport {
-        inner: SerdeFramed::new(framed_io, codec),
+        inner: SerdeFramed::new(CountBytes::new(framed_io), codec),
+        #[cfg(feature = "tcp")]
+        permit: None,
     }
 }
 
 impl<S, Item, SinkItem, Codec> From<(S, Codec)> for Transport<S, Item, SinkItem, Codec>
 where
     S: AsyncWrite + AsyncRead,
-    Item: for<'de> Deserialize<'de>,
-    SinkItem: Serialize,
     Codec: Serializer<SinkItem> + Deserializer<Item>,
 {
     fn from((io, codec): (S, Codec)) -> Self {
@@ -117,14 +320,859 @@ where
     }
 }
 
+/// A handshake that prefixes a connection, so that peers speaking incompatible versions of the
+/// protocol fail with a clear [`IncompatiblePeer`](handshake::IncompatiblePeer) error instead of
+/// decoding each other's frames into garbage.
+///
+/// Each side writes a [`Hello`](handshake::Hello) of eight bytes — a magic number, its protocol
+/// version and the feature bits it supports, in network byte order — before any frames, then reads
+/// the peer's. Peers are compatible iff their versions are equal; the features both sides can use
+/// are the intersection of their feature bits.
+///
+/// Both sides of a connection must exchange hellos: a peer that doesn't expects the hello to be
+/// the length of its first frame.
+pub mod handshake {
+    use std::{fmt, io};
+    use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+    /// The version of the protocol spoken by this crate.
+    pub const PROTOCOL_VERSION: u16 = 1;
+
+    /// The bytes that start every hello.
+    const MAGIC: [u8; 4] = *b"trpc";
+
+    /// What a peer announces about itself when a connection starts.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+    pub struct Hello {
+        /// The version of the protocol the peer speaks.
+        pub version: u16,
+        /// The optional features the peer supports, one per bit. The meaning of the bits is up
+        /// to the application.
+        pub features: u16,
+    }
+
+    impl Default for Hello {
+        fn default() -> Self {
+            Self::new()
+        }
+    }
+
+    impl Hello {
+        /// Returns a hello for [`PROTOCOL_VERSION`] without any features.
+        pub fn new() -> Self {
+            Self {
+                version: PROTOCOL_VERSION,
+                features: 0,
+            }
+        }
+
+        /// Sets the features supported.
+        pub fn with_features(mut self, features: u16) -> Self {
+            self.features = features;
+            self
+        }
+
+        fn encode(&self) -> [u8; 8] {
+            let mut bytes = [0; 8];
+            bytes[..4].copy_from_slice(&MAGIC);
+            bytes[4..6].copy_from_slice(&self.version.to_be_bytes());
+            bytes[6..].copy_from_slice(&self.features.to_be_bytes());
+            bytes
+        }
+
+        fn decode(bytes: [u8; 8]) -> Option<Self> {
+            if bytes[..4] != MAGIC {
+                return None;
+            }
+            Some(Self {
+                version: u16::from_be_bytes([bytes[4], bytes[5]]),
+                features: u16::from_be_bytes([bytes[6], bytes[7]]),
+            })
+        }
+    }
+
+    /// The error of a handshake with a peer that speaks an incompatible version of the protocol.
+    ///
+    /// Handshakes return it wrapped in an [`io::Error`] of kind
+    /// [`InvalidData`](io::ErrorKind::InvalidData); get it back with [`IncompatiblePeer::of`].
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct IncompatiblePeer {
+        /// The hello sent to the peer.
+        pub local: Hello,
+        /// The hello received from the peer, or `None` if the peer didn't send one, e.g. because
+        /// it predates handshakes.
+        pub peer: Option<Hello>,
+    }
+
+    impl IncompatiblePeer {
+        /// Returns the incompatible peer `error` was caused by, if any.
+        pub fn of(error: &io::Error) -> Option<&Self> {
+            error.get_ref()?.downcast_ref()
+        }
+    }
+
+    impl fmt::Display for IncompatiblePeer {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self.peer {
+                Some(peer) => write!(
+                    f,
+                    "the peer speaks protocol version {}, but this side speaks version {}",
+                    peer.version, self.local.version
+                ),
+                None => write!(
+                    f,
+                    "the peer didn't send a protocol handshake; it may predate them (this side \
+                     speaks protocol version {})",
+                    self.local.version
+                ),
+            }
+        }
+    }
+
+    impl std::error::Error for IncompatiblePeer {}
+
+    /// Sends `local` to the peer on the other end of `io` and returns the peer's hello. Fails with
+    /// an [`IncompatiblePeer`] error if the peer's version differs or it doesn't send a hello.
+    pub async fn exchange<S>(io: &mut S, local: Hello) -> io::Result<Hello>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        io.write_all(&local.encode()).await?;
+        io.flush().await?;
+        let mut bytes = [0; 8];
+        let peer = match io.read_exact(&mut bytes).await {
+            Ok(_) => Hello::decode(bytes),
+            // A peer that doesn't expect a hello usually disconnects when it reads one.
+            Err(e)
+                if matches!(
+                    e.kind(),
+                    io::ErrorKind::UnexpectedEof | io::ErrorKind::ConnectionReset
+                ) =>
+            {
+                None
+            }
+            Err(e) => return Err(e),
+        };
+        match peer {
+            Some(peer) if peer.version == local.version => Ok(peer),
+            peer => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                IncompatiblePeer { local, peer },
+            )),
+        }
+    }
+}
+
+/// The leading fields of a [`ClientMessage`](crate::ClientMessage), which can often be decoded
+/// even when the rest of the message can't.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ClientMessageHeader {
+    /// The header of a [request](crate::ClientMessage::Request).
+    Request {
+        /// The request context.
+        context: crate::context::Context,
+        /// The request ID.
+        id: u64,
+    },
+}
+
+/// A codec that reports messages it fails to decode as [`MalformedMessage`]s, so that a server
+/// can reject the offending request and keep serving the connection, if its
+/// [policy](crate::server::Config::malformed_message_policy) says to.
+///
+/// When decoding a message fails, `Recoverable` decodes its [header](ClientMessageHeader) with a
+/// second codec to salvage the request ID. The header codec must accept trailing data; for
+/// bincode, this means configuring it with `allow_trailing_bytes`.
+///
+/// ```rust
+/// use tarpc::{
+///     serde_transport::{self, ClientMessageHeader, Recoverable},
+///     ClientMessage, Response,
+/// };
+/// use tokio_serde::formats::Json;
+///
+/// # let (io, _) = tokio::io::duplex(1024);
+/// let codec = Recoverable::new(Json::default(), Json::<ClientMessageHeader, ()>::default());
+/// let transport =
+///     serde_transport::Transport::<_, ClientMessage<u64>, Response<u64>, _>::from((io, codec));
+/// # drop(transport);
+/// ```
+#[pin_project]
+#[derive(Debug, Default)]
+pub struct Recoverable<Codec, HeaderCodec> {
+    #[pin]
+    codec: Codec,
+    #[pin]
+    header_codec: HeaderCodec,
+}
+
+impl<Codec, HeaderCodec> Recoverable<Codec, HeaderCodec> {
+    /// Returns a codec that decodes messages with `codec`, and the headers of messages that
+    /// `codec` fails to decode with `header_codec`.
+    pub fn new(codec: Codec, header_codec: HeaderCodec) -> Self {
+        Self {
+            codec,
+            header_codec,
+        }
+    }
+}
+
+impl<Item, Codec, HeaderCodec> Deserializer<Item> for Recoverable<Codec, HeaderCodec>
+where
+    Codec: Deserializer<Item>,
+    Codec::Error: Into<Box<dyn Error + Send + Sync>>,
+    HeaderCodec: Deserializer<ClientMessageHeader>,
+{
+    type Error = io::Error;
+
+    fn deserialize(self: Pin<&mut Self>, src: &BytesMut) -> io::Result<Item> {
+        let this = self.project();
+        this.codec.deserialize(src).map_err(|e| {
+            let request_id = match this.header_codec.deserialize(src) {
+                Ok(ClientMessageHeader::Request { id, .. }) => Some(id),
+                Err(_) => None,
+            };
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                MalformedMessage::new(request_id, e),
+            )
+        })
+    }
+}
+
+impl<SinkItem, Codec, HeaderCodec> Serializer<SinkItem> for Recoverable<Codec, HeaderCodec>
+where
+    Codec: Serializer<SinkItem>,
+{
+    type Error = Codec::Error;
+
+    fn serialize(self: Pin<&mut Self>, item: &SinkItem) -> Result<Bytes, Self::Error> {
+        self.project().codec.serialize(item)
+    }
+}
+
+/// A codec that sends [`Bytes`] fields marked with [`payload`] as they are, rather than
+/// serializing them with the codec it wraps, so that services forwarding opaque blobs don't pay to
+/// encode and decode payloads they never inspect.
+///
+/// Each message is sent as a frame holding the message encoded by the wrapped codec, in which
+/// payloads are replaced by their positions, followed by the payloads themselves. Both peers must
+/// use the same wrapped codec wrapped in `Passthrough`. To also recover from malformed messages,
+/// wrap a [`Recoverable`] codec in `Passthrough`, rather than the other way around.
+///
+/// ```rust
+/// use bytes::Bytes;
+/// use tarpc::{serde_transport::{self, Passthrough}, ClientMessage, Response};
+/// use tokio_serde::formats::Json;
+///
+/// // `Bytes` can't be archived with rkyv.
+/// #[tarpc::service(passthrough_bytes = true, derive_rkyv = false)]
+/// trait Proxy {
+///     /// Forwards a blob, which is never serialized with JSON.
+///     async fn forward(blob: Bytes) -> Bytes;
+/// }
+///
+/// # let (io, _) = tokio::io::duplex(1024);
+/// let codec = Passthrough::new(Json::default());
+/// let transport = serde_transport::Transport::<
+///     _,
+///     ClientMessage<ProxyRequest>,
+///     Response<ProxyResponse>,
+///     _,
+/// >::from((io, codec));
+/// # drop(transport);
+/// ```
+#[pin_project]
+#[derive(Debug, Default)]
+pub struct Passthrough<Codec> {
+    #[pin]
+    codec: Codec,
+}
+
+impl<Codec> Passthrough<Codec> {
+    /// Returns a codec that encodes messages with `codec`, except for their payloads.
+    pub fn new(codec: Codec) -> Self {
+        Self { codec }
+    }
+}
+
+impl<SinkItem, Codec> Serializer<SinkItem> for Passthrough<Codec>
+where
+    Codec: Serializer<SinkItem>,
+    Codec::Error: Into<Box<dyn Error + Send + Sync>>,
+{
+    type Error = io::Error;
+
+    fn serialize(self: Pin<&mut Self>, item: &SinkItem) -> io::Result<Bytes> {
+        let (message, payloads) = payload::encode(|| self.project().codec.serialize(item));
+        let message = message.map_err(io::Error::other)?;
+        let message_len = u32::try_from(message.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "message too big"))?;
+        let payloads_len = payloads.iter().map(Bytes::len).sum::<usize>();
+        let mut frame = BytesMut::with_capacity(4 + message.len() + payloads_len);
+        frame.put_u32(message_len);
+        frame.put(message);
+        for payload in payloads {
+            frame.put(payload);
+        }
+        Ok(frame.freeze())
+    }
+}
+
+impl<Item, Codec> Deserializer<Item> for Passthrough<Codec>
+where
+    Codec: Deserializer<Item>,
+    Codec::Error: Into<Box<dyn Error + Send + Sync>>,
+{
+    type Error = io::Error;
+
+    fn deserialize(self: Pin<&mut Self>, src: &BytesMut) -> io::Result<Item> {
+        let (message, payloads) = split_length_prefixed(src)?;
+        let message = BytesMut::from(message);
+        let payloads = Bytes::copy_from_slice(payloads);
+        payload::decode(payloads, || self.project().codec.deserialize(&message))
+            .map_err(io::Error::other)
+    }
+}
+
+/// Splits `frame` into the part whose length is given by its first four bytes, in network byte
+/// order, and the rest, or fails if `frame` is too short to hold the part. The length is compared
+/// with the rest of the frame rather than offset by the four bytes, which could overflow where
+/// `usize` is 32 bits.
+fn split_length_prefixed(frame: &[u8]) -> io::Result<(&[u8], &[u8])> {
+    let truncated = || io::Error::new(io::ErrorKind::InvalidData, "truncated frame");
+    let (len, rest) = match frame {
+        [a, b, c, d, rest @ ..] => (u32::from_be_bytes([*a, *b, *c, *d]), rest),
+        _ => return Err(truncated()),
+    };
+    let len = usize::try_from(len).map_err(|_| truncated())?;
+    if len > rest.len() {
+        return Err(truncated());
+    }
+    Ok(rest.split_at(len))
+}
+
+/// Serializes [`Bytes`] fields as payloads sent as they are by [`Passthrough`] codecs, for use
+/// with `#[serde(with = "tarpc::serde_transport::payload")]`.
+///
+/// Other serializers serialize the fields as `Bytes` does, so the fields can also be sent with
+/// other codecs. [Services](crate::service) set with `passthrough_bytes = true` mark their args and
+/// responses of type `Bytes` as payloads.
+pub mod payload {
+    use bytes::Bytes;
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+    use std::cell::RefCell;
+
+    thread_local! {
+        /// The payloads of the message being encoded by a `Passthrough` codec, if any.
+        static ENCODING: RefCell<Option<Vec<Bytes>>> = const { RefCell::new(None) };
+        /// The payloads of the message being decoded by a `Passthrough` codec, if any.
+        static DECODING: RefCell<Option<Bytes>> = const { RefCell::new(None) };
+    }
+
+    /// Sets `slot` to `value` until dropped, then restores its previous value.
+    struct Restore<T: 'static> {
+        slot: &'static std::thread::LocalKey<RefCell<Option<T>>>,
+        previous: Option<T>,
+    }
+
+    impl<T> Restore<T> {
+        fn set(slot: &'static std::thread::LocalKey<RefCell<Option<T>>>, value: T) -> Self {
+            let previous = slot.with(|slot| slot.replace(Some(value)));
+            Self { slot, previous }
+        }
+    }
+
+    impl<T> Drop for Restore<T> {
+        fn drop(&mut self) {
+            let previous = self.previous.take();
+            self.slot.with(|slot| *slot.borrow_mut() = previous);
+        }
+    }
+
+    /// Runs `encode`, returning its result and the payloads set aside while it ran.
+    pub(super) fn encode<T>(encode: impl FnOnce() -> T) -> (T, Vec<Bytes>) {
+        let restore = Restore::set(&ENCODING, Vec::new());
+        let result = encode();
+        let payloads = ENCODING.with(|payloads| payloads.borrow_mut().take());
+        drop(restore);
+        (result, payloads.unwrap_or_default())
+    }
+
+    /// Runs `decode`, which takes the payloads it decodes from `payloads`.
+    pub(super) fn decode<T>(payloads: Bytes, decode: impl FnOnce() -> T) -> T {
+        let _restore = Restore::set(&DECODING, payloads);
+        decode()
+    }
+
+    /// Serializes `bytes` as a payload if encoded by a `Passthrough` codec, or else as `Bytes`
+    /// does.
+    pub fn serialize<S: Serializer>(bytes: &Bytes, serializer: S) -> Result<S::Ok, S::Error> {
+        let position = ENCODING.with(|payloads| {
+            let mut payloads = payloads.borrow_mut();
+            let payloads = payloads.as_mut()?;
+            let offset = payloads.iter().map(Bytes::len).sum::<usize>();
+            payloads.push(bytes.clone());
+            Some((offset as u64, bytes.len() as u64))
+        });
+        match position {
+            Some(position) => position.serialize(serializer),
+            None => bytes.serialize(serializer),
+        }
+    }
+
+    /// Deserializes a payload if decoded by a `Passthrough` codec, or else deserializes as
+    /// `Bytes` does.
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Bytes, D::Error> {
+        let payloads = match DECODING.with(|payloads| payloads.borrow().clone()) {
+            Some(payloads) => payloads,
+            None => return Bytes::deserialize(deserializer),
+        };
+        let (offset, len) = <(u64, u64)>::deserialize(deserializer)?;
+        usize::try_from(offset)
+            .ok()
+            .zip(usize::try_from(len).ok())
+            .and_then(|(offset, len)| Some(offset..offset.checked_add(len)?))
+            .filter(|range| range.end <= payloads.len())
+            .map(|range| payloads.slice(range))
+            .ok_or_else(|| de::Error::custom("payload out of bounds"))
+    }
+}
+
+/// A codec that can serialize items into a buffer it doesn't own, so that a [`Pooled`] codec can
+/// reuse one buffer for the frames of every item.
+pub trait SerializeInto<SinkItem> {
+    /// The type of error returned when serializing fails.
+    type Error;
+
+    /// Appends the serialization of `item` to `buf`.
+    fn serialize_into(
+        self: Pin<&mut Self>,
+        item: &SinkItem,
+        buf: &mut BytesMut,
+    ) -> Result<(), Self::Error>;
+}
+
+#[cfg(feature = "serde-transport-json")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde-transport-json")))]
+impl<Item, SinkItem: serde::Serialize> SerializeInto<SinkItem> for formats::Json<Item, SinkItem> {
+    type Error = io::Error;
+
+    fn serialize_into(self: Pin<&mut Self>, item: &SinkItem, buf: &mut BytesMut) -> io::Result<()> {
+        serde_json::to_writer(buf.writer(), item).map_err(Into::into)
+    }
+}
+
+#[cfg(feature = "serde-transport-bincode")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde-transport-bincode")))]
+impl<Item, SinkItem: serde::Serialize> SerializeInto<SinkItem>
+    for formats::Bincode<Item, SinkItem>
+{
+    type Error = io::Error;
+
+    fn serialize_into(self: Pin<&mut Self>, item: &SinkItem, buf: &mut BytesMut) -> io::Result<()> {
+        use bincode::Options;
+
+        // The options of `Bincode::default()`.
+        bincode::DefaultOptions::new()
+            .serialize_into(buf.writer(), item)
+            .map_err(io::Error::other)
+    }
+}
+
+impl<SinkItem, Codec, HeaderCodec> SerializeInto<SinkItem> for Recoverable<Codec, HeaderCodec>
+where
+    Codec: SerializeInto<SinkItem>,
+{
+    type Error = Codec::Error;
+
+    fn serialize_into(
+        self: Pin<&mut Self>,
+        item: &SinkItem,
+        buf: &mut BytesMut,
+    ) -> Result<(), Self::Error> {
+        self.project().codec.serialize_into(item, buf)
+    }
+}
+
+/// A codec that serializes items into a buffer it reuses for every frame, rather than into a new
+/// buffer per item, so that sending small messages, like the responses of most methods, doesn't
+/// allocate once the buffer is warm.
+///
+/// The frame of each item is split off the buffer, which reclaims its space once the frames split
+/// off it have been written to the wire and dropped. Items are encoded exactly as the wrapped codec
+/// encodes them, so only one peer needs to use `Pooled`.
+///
+/// ```rust
+/// # #[cfg(feature = "serde-transport-json")]
+/// # {
+/// use tarpc::{serde_transport::{self, Pooled}, ClientMessage, Response};
+/// use tokio_serde::formats::Json;
+///
+/// # let (io, _) = tokio::io::duplex(1024);
+/// let codec = Pooled::new(Json::default());
+/// let transport =
+///     serde_transport::Transport::<_, ClientMessage<u64>, Response<u64>, _>::from((io, codec));
+/// # drop(transport);
+/// # }
+/// ```
+#[pin_project]
+#[derive(Debug, Default)]
+pub struct Pooled<Codec> {
+    #[pin]
+    codec: Codec,
+    buf: BytesMut,
+}
+
+impl<Codec> Pooled<Codec> {
+    /// The capacity of the buffer, which is shared by the frames of many small items.
+    const CAPACITY: usize = 8 * 1024;
+    /// The spare capacity below which the buffer is reclaimed or replaced before serializing an
+    /// item.
+    const MIN_SPARE: usize = 512;
+
+    /// Returns a codec that serializes items with `codec`, into a reused buffer.
+    pub fn new(codec: Codec) -> Self {
+        Self {
+            codec,
+            buf: BytesMut::new(),
+        }
+    }
+}
+
+impl<SinkItem, Codec> Serializer<SinkItem> for Pooled<Codec>
+where
+    Codec: SerializeInto<SinkItem>,
+{
+    type Error = Codec::Error;
+
+    fn serialize(self: Pin<&mut Self>, item: &SinkItem) -> Result<Bytes, Self::Error> {
+        let this = self.project();
+        // Reclaims the space of the frames split off the buffer if they've all been dropped, or
+        // else moves on to a new buffer.
+        if this.buf.capacity() < Self::MIN_SPARE {
+            this.buf.reserve(Self::CAPACITY);
+        }
+        if let Err(e) = this.codec.serialize_into(item, this.buf) {
+            this.buf.clear();
+            return Err(e);
+        }
+        Ok(this.buf.split().freeze())
+    }
+}
+
+impl<Item, Codec> Deserializer<Item> for Pooled<Codec>
+where
+    Codec: Deserializer<Item>,
+{
+    type Error = Codec::Error;
+
+    fn deserialize(self: Pin<&mut Self>, src: &BytesMut) -> Result<Item, Self::Error> {
+        self.project().codec.deserialize(src)
+    }
+}
+
+/// A codec that sends the args of each request after its header, so that a server can decode the
+/// header of a request when it's received, but defer decoding its args until the request is
+/// served, by receiving them as [`Lazy`] args. Requests a server rejects before serving them, e.g.
+/// because their deadline has passed, because they duplicate a request in flight, or because a
+/// [hook](crate::server::request_hook) sheds them, are never decoded.
+///
+/// Each request is sent as a frame holding its [`ClientMessage`](crate::ClientMessage), without
+/// args, encoded by `Codec`, followed by its args encoded by `ArgsCodec`. Responses are encoded by
+/// `Codec` as they are. Both peers must use `LazyArgs` codecs; the server serves the requests it
+/// receives with a [`LazyServe`].
+///
+/// ```rust
+/// use tarpc::{
+///     serde_transport::{self, Lazy, LazyArgs},
+///     ClientMessage, Response,
+/// };
+/// use tokio_serde::formats::Json;
+///
+/// # let (io, _) = tokio::io::duplex(1024);
+/// let codec = LazyArgs::new(Json::default(), Json::<String, String>::default());
+/// let transport = serde_transport::Transport::<
+///     _,
+///     ClientMessage<Lazy<String>>,
+///     Response<String>,
+///     _,
+/// >::from((io, codec));
+/// # drop(transport);
+/// ```
+#[pin_project]
+#[derive(Debug, Default)]
+pub struct LazyArgs<Codec, ArgsCodec> {
+    #[pin]
+    codec: Codec,
+    #[pin]
+    args_codec: ArgsCodec,
+}
+
+impl<Codec, ArgsCodec> LazyArgs<Codec, ArgsCodec> {
+    /// Returns a codec that encodes messages without their args with `codec`, and args with
+    /// `args_codec`.
+    pub fn new(codec: Codec, args_codec: ArgsCodec) -> Self {
+        Self { codec, args_codec }
+    }
+}
+
+/// Returns the frame holding `header`, followed by `args`.
+fn lazy_args_frame(header: Bytes, args: &[u8]) -> io::Result<Bytes> {
+    let header_len = u32::try_from(header.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "header too big"))?;
+    let mut frame = BytesMut::with_capacity(4 + header.len() + args.len());
+    frame.put_u32(header_len);
+    frame.put(header);
+    frame.put(args);
+    Ok(frame.freeze())
+}
+
+/// Splits `frame` into its header and its args.
+fn split_lazy_args_frame(frame: &BytesMut) -> io::Result<(BytesMut, BytesMut)> {
+    let (header, args) = split_length_prefixed(frame)?;
+    Ok((BytesMut::from(header), BytesMut::from(args)))
+}
+
+impl<Req, Codec, ArgsCodec> Serializer<crate::ClientMessage<Req>> for LazyArgs<Codec, ArgsCodec>
+where
+    Codec: Serializer<crate::ClientMessage<()>>,
+    Codec::Error: Into<Box<dyn Error + Send + Sync>>,
+    ArgsCodec: Serializer<Req>,
+    ArgsCodec::Error: Into<Box<dyn Error + Send + Sync>>,
+{
+    type Error = io::Error;
+
+    fn serialize(self: Pin<&mut Self>, item: &crate::ClientMessage<Req>) -> io::Result<Bytes> {
+        use crate::{ClientMessage, Request};
+
+        let this = self.project();
+        let (header, args) = match item {
+            ClientMessage::Request(request) => {
+                let header = ClientMessage::Request(Request {
+                    context: request.context.clone(),
+                    id: request.id,
+                    message: (),
+                    oneway: request.oneway,
+                });
+                let args = this
+                    .args_codec
+                    .serialize(&request.message)
+                    .map_err(io::Error::other)?;
+                (header, args)
+            }
+            ClientMessage::Cancel {
+                trace_context,
+                request_id,
+                reason,
+                ack,
+            } => (
+                ClientMessage::Cancel {
+                    trace_context: trace_context.clone(),
+                    request_id: *request_id,
+                    reason: *reason,
+                    ack: *ack,
+                },
+                Bytes::new(),
+            ),
+            ClientMessage::CancelBatch {
+                request_ids,
+                reason,
+            } => (
+                ClientMessage::CancelBatch {
+                    request_ids: request_ids.clone(),
+                    reason: *reason,
+                },
+                Bytes::new(),
+            ),
+        };
+        let header = this.codec.serialize(&header).map_err(io::Error::other)?;
+        lazy_args_frame(header, &args)
+    }
+}
+
+impl<Req, Codec, ArgsCodec> Deserializer<crate::ClientMessage<Lazy<Req>>>
+    for LazyArgs<Codec, ArgsCodec>
+where
+    Codec: Deserializer<crate::ClientMessage<()>>,
+    Codec::Error: Into<Box<dyn Error + Send + Sync>>,
+    ArgsCodec: Deserializer<Req> + Default + Unpin,
+    ArgsCodec::Error: Into<Box<dyn Error + Send + Sync>>,
+{
+    type Error = io::Error;
+
+    fn deserialize(
+        self: Pin<&mut Self>,
+        src: &BytesMut,
+    ) -> io::Result<crate::ClientMessage<Lazy<Req>>> {
+        use crate::{ClientMessage, Request};
+
+        let (header, args) = split_lazy_args_frame(src)?;
+        let header = self
+            .project()
+            .codec
+            .deserialize(&header)
+            .map_err(io::Error::other)?;
+        Ok(match header {
+            ClientMessage::Request(request) => ClientMessage::Request(Request {
+                context: request.context,
+                id: request.id,
+                message: Lazy {
+                    args,
+                    decode: |args| {
+                        Pin::new(&mut ArgsCodec::default())
+                            .deserialize(args)
+                            .map_err(Into::into)
+                    },
+                },
+                oneway: request.oneway,
+            }),
+            ClientMessage::Cancel {
+                trace_context,
+                request_id,
+                reason,
+                ack,
+            } => ClientMessage::Cancel {
+                trace_context,
+                request_id,
+                reason,
+                ack,
+            },
+            ClientMessage::CancelBatch {
+                request_ids,
+                reason,
+            } => ClientMessage::CancelBatch {
+                request_ids,
+                reason,
+            },
+        })
+    }
+}
+
+impl<Resp, Codec, ArgsCodec> Serializer<crate::Response<Resp>> for LazyArgs<Codec, ArgsCodec>
+where
+    Codec: Serializer<crate::Response<Resp>>,
+{
+    type Error = Codec::Error;
+
+    fn serialize(self: Pin<&mut Self>, item: &crate::Response<Resp>) -> Result<Bytes, Self::Error> {
+        self.project().codec.serialize(item)
+    }
+}
+
+impl<Resp, Codec, ArgsCodec> Deserializer<crate::Response<Resp>> for LazyArgs<Codec, ArgsCodec>
+where
+    Codec: Deserializer<crate::Response<Resp>>,
+{
+    type Error = Codec::Error;
+
+    fn deserialize(
+        self: Pin<&mut Self>,
+        src: &BytesMut,
+    ) -> Result<crate::Response<Resp>, Self::Error> {
+        self.project().codec.deserialize(src)
+    }
+}
+
+/// The args of a request received by a [`LazyArgs`] codec, which aren't decoded until they're
+/// [served](LazyServe).
+pub struct Lazy<T> {
+    args: BytesMut,
+    decode: fn(&BytesMut) -> Result<T, Box<dyn Error + Send + Sync>>,
+}
+
+impl<T> Lazy<T> {
+    /// Returns the encoded args.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.args
+    }
+
+    /// Decodes the args.
+    pub fn decode(&self) -> Result<T, crate::ServerError> {
+        (self.decode)(&self.args).map_err(|e| {
+            crate::ServerError::bad_request(format!("the request args could not be decoded: {e}"))
+        })
+    }
+}
+
+impl<T> Clone for Lazy<T> {
+    fn clone(&self) -> Self {
+        Self {
+            args: self.args.clone(),
+            decode: self.decode,
+        }
+    }
+}
+
+impl<T> std::fmt::Debug for Lazy<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Lazy")
+            .field("len", &self.args.len())
+            .finish()
+    }
+}
+
+/// Serves requests with [`Lazy`] args by decoding them, then serving them with the wrapped
+/// [`Serve`](crate::server::Serve). Requests whose args can't be decoded are answered with a
+/// [bad request](crate::ServerError::bad_request) error.
+///
+/// Since the method of a request isn't known until its args are decoded, `LazyServe` doesn't
+/// [name](crate::server::Serve::method) the methods of the requests it serves.
+#[derive(Clone, Copy, Debug)]
+pub struct LazyServe<S> {
+    serve: S,
+}
+
+impl<S> LazyServe<S> {
+    /// Returns a [`Serve`](crate::server::Serve) that serves requests with `serve` once their
+    /// args are decoded.
+    pub fn new(serve: S) -> Self {
+        Self { serve }
+    }
+
+    /// Returns the inner serve fn.
+    pub fn get_ref(&self) -> &S {
+        &self.serve
+    }
+}
+
+impl<S> crate::server::Serve for LazyServe<S>
+where
+    S: crate::server::Serve,
+{
+    type Req = Lazy<S::Req>;
+    type Resp = S::Resp;
+
+    async fn serve(
+        self,
+        ctx: crate::context::Context,
+        req: Lazy<S::Req>,
+    ) -> Result<S::Resp, crate::ServerError> {
+        let req = req.decode()?;
+        self.serve.serve(ctx, req).await
+    }
+}
+
 #[cfg(feature = "tcp")]
 #[cfg_attr(docsrs, doc(cfg(feature = "tcp")))]
 /// TCP support for generic transport using Tokio.
+///
+/// Besides tokio's sockets, connections can be made and accepted by other means, e.g. by the
+/// sockets of a network simulator, with [`connect_with`] and [`listen_with`].
 pub mod tcp {
     use {
         super::*,
-        futures::ready,
-        std::{marker::PhantomData, net::SocketAddr},
+        fnv::FnvHashMap,
+        futures::{future::BoxFuture, ready, stream::FuturesUnordered},
+        handshake::Hello,
+        serde::{Deserialize, Serialize},
+        std::{
+            marker::PhantomData,
+            net::{IpAddr, SocketAddr},
+            sync::{Arc, Mutex},
+            time::Duration,
+        },
         tokio::net::{TcpListener, TcpStream, ToSocketAddrs},
         tokio_util::codec::length_delimited,
     };
@@ -132,42 +1180,84 @@ pub mod tcp {
     impl<Item, SinkItem, Codec> Transport<TcpStream, Item, SinkItem, Codec> {
         /// Returns the peer address of the underlying TcpStream.
         pub fn peer_addr(&self) -> io::Result<SocketAddr> {
-            self.inner.get_ref().get_ref().peer_addr()
+            self.get_ref().peer_addr()
         }
         /// Returns the local address of the underlying TcpStream.
         pub fn local_addr(&self) -> io::Result<SocketAddr> {
-            self.inner.get_ref().get_ref().local_addr()
+            self.get_ref().local_addr()
+        }
+    }
+
+    /// How long a peer has to send its hello once a connection is open.
+    const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /// The most connections an [`Incoming`] refuses or hands to handshakes in one poll, so that a
+    /// storm of connections it doesn't return doesn't starve the tasks sharing its worker.
+    const CONNECTIONS_PER_POLL: usize = 32;
+
+    /// Exchanges hellos with the peer on the other end of `io`, then returns `io`.
+    fn shake_hands<S>(mut io: S, hello: Hello) -> BoxFuture<'static, io::Result<S>>
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        async move {
+            tokio::time::timeout(HANDSHAKE_TIMEOUT, handshake::exchange(&mut io, hello))
+                .await
+                .map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::TimedOut,
+                        "the peer didn't send a protocol handshake in time",
+                    )
+                })??;
+            Ok(io)
         }
+        .boxed()
     }
 
     /// A connection Future that also exposes the length-delimited framing config.
     #[must_use]
     #[pin_project]
-    pub struct Connect<T, Item, SinkItem, CodecFn> {
+    pub struct Connect<T, Item, SinkItem, CodecFn, S = TcpStream> {
         #[pin]
         inner: T,
         codec_fn: CodecFn,
         config: length_delimited::Builder,
+        hello: Option<Hello>,
+        handshake: Option<BoxFuture<'static, io::Result<S>>>,
         ghost: PhantomData<(fn(SinkItem), fn() -> Item)>,
     }
 
-    impl<T, Item, SinkItem, Codec, CodecFn> Future for Connect<T, Item, SinkItem, CodecFn>
+    impl<T, Item, SinkItem, Codec, CodecFn, S> Future for Connect<T, Item, SinkItem, CodecFn, S>
     where
-        T: Future<Output = io::Result<TcpStream>>,
+        T: Future<Output = io::Result<S>>,
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
         Item: for<'de> Deserialize<'de>,
         SinkItem: Serialize,
         Codec: Serializer<SinkItem> + Deserializer<Item>,
         CodecFn: Fn() -> Codec,
     {
-        type Output = io::Result<Transport<TcpStream, Item, SinkItem, Codec>>;
+        type Output = io::Result<Transport<S, Item, SinkItem, Codec>>;
 
         fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
-            let io = ready!(self.as_mut().project().inner.poll(cx))?;
-            Poll::Ready(Ok(new(self.config.new_framed(io), (self.codec_fn)())))
+            loop {
+                let this = self.as_mut().project();
+                let io = match this.handshake {
+                    Some(handshake) => ready!(handshake.as_mut().poll(cx))?,
+                    None => {
+                        let io = ready!(this.inner.poll(cx))?;
+                        if let Some(hello) = *this.hello {
+                            *this.handshake = Some(shake_hands(io, hello));
+                            continue;
+                        }
+                        io
+                    }
+                };
+                return Poll::Ready(Ok(new(this.config.new_framed(io), (this.codec_fn)())));
+            }
         }
     }
 
-    impl<T, Item, SinkItem, CodecFn> Connect<T, Item, SinkItem, CodecFn> {
+    impl<T, Item, SinkItem, CodecFn, S> Connect<T, Item, SinkItem, CodecFn, S> {
         /// Returns an immutable reference to the length-delimited codec's config.
         pub fn config(&self) -> &length_delimited::Builder {
             &self.config
@@ -177,6 +1267,15 @@ pub mod tcp {
         pub fn config_mut(&mut self) -> &mut length_delimited::Builder {
             &mut self.config
         }
+
+        /// Exchanges `hello` with the server before any frames, failing with an
+        /// [`IncompatiblePeer`](handshake::IncompatiblePeer) error if the server speaks another
+        /// version of the protocol. The server must [exchange hellos](Incoming::with_handshake)
+        /// too.
+        pub fn with_handshake(mut self, hello: Hello) -> Self {
+            self.hello = Some(hello);
+            self
+        }
     }
 
     /// Connects to `addr`, wrapping the connection in a TCP transport.
@@ -190,11 +1289,30 @@ pub mod tcp {
         SinkItem: Serialize,
         Codec: Serializer<SinkItem> + Deserializer<Item>,
         CodecFn: Fn() -> Codec,
+    {
+        connect_with(TcpStream::connect(addr), codec_fn)
+    }
+
+    /// Wraps the connection made by `connect` in a TCP transport, e.g. to connect over the
+    /// sockets of a network simulator rather than tokio's.
+    pub fn connect_with<T, S, Item, SinkItem, Codec, CodecFn>(
+        connect: T,
+        codec_fn: CodecFn,
+    ) -> Connect<T, Item, SinkItem, CodecFn, S>
+    where
+        T: Future<Output = io::Result<S>>,
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        Item: for<'de> Deserialize<'de>,
+        SinkItem: Serialize,
+        Codec: Serializer<SinkItem> + Deserializer<Item>,
+        CodecFn: Fn() -> Codec,
     {
         Connect {
-            inner: TcpStream::connect(addr),
+            inner: connect,
             codec_fn,
             config: LengthDelimitedCodec::builder(),
+            hello: None,
+            handshake: None,
             ghost: PhantomData,
         }
     }
@@ -213,38 +1331,213 @@ pub mod tcp {
         listen_on(TcpListener::bind(addr).await?, codec_fn).await
     }
 
-    /// Wrap accepted connections from `listener` in TCP transports.
-    pub async fn listen_on<Item, SinkItem, Codec, CodecFn>(
-        listener: TcpListener,
-        codec_fn: CodecFn,
-    ) -> io::Result<Incoming<Item, SinkItem, Codec, CodecFn>>
-    where
-        Item: for<'de> Deserialize<'de>,
-        Codec: Serializer<SinkItem> + Deserializer<Item>,
-        CodecFn: Fn() -> Codec,
-    {
-        let local_addr = listener.local_addr()?;
-        Ok(Incoming {
-            listener,
-            codec_fn,
-            local_addr,
-            config: LengthDelimitedCodec::builder(),
-            ghost: PhantomData,
-        })
+    /// Wrap accepted connections from `listener` in TCP transports.
+    pub async fn listen_on<Item, SinkItem, Codec, CodecFn>(
+        listener: TcpListener,
+        codec_fn: CodecFn,
+    ) -> io::Result<Incoming<Item, SinkItem, Codec, CodecFn>>
+    where
+        Item: for<'de> Deserialize<'de>,
+        Codec: Serializer<SinkItem> + Deserializer<Item>,
+        CodecFn: Fn() -> Codec,
+    {
+        let local_addr = listener.local_addr()?;
+        Ok(incoming(listener, local_addr, codec_fn))
+    }
+
+    /// Wraps the connections yielded by `accept`, with the addresses of their peers, in TCP
+    /// transports, e.g. to accept connections on the sockets of a network simulator rather than
+    /// tokio's. `local_addr` is the address being listened on.
+    pub fn listen_with<S, Io, Item, SinkItem, Codec, CodecFn>(
+        accept: S,
+        local_addr: SocketAddr,
+        codec_fn: CodecFn,
+    ) -> Incoming<Item, SinkItem, Codec, CodecFn, StreamListener<S>>
+    where
+        S: Stream<Item = io::Result<(Io, SocketAddr)>>,
+        Io: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+        Item: for<'de> Deserialize<'de>,
+        Codec: Serializer<SinkItem> + Deserializer<Item>,
+        CodecFn: Fn() -> Codec,
+    {
+        let listener = StreamListener {
+            accept: Box::pin(accept),
+        };
+        incoming(listener, local_addr, codec_fn)
+    }
+
+    fn incoming<L, Item, SinkItem, Codec, CodecFn>(
+        listener: L,
+        local_addr: SocketAddr,
+        codec_fn: CodecFn,
+    ) -> Incoming<Item, SinkItem, Codec, CodecFn, L>
+    where
+        L: Listener,
+    {
+        Incoming {
+            listener,
+            codec_fn,
+            local_addr,
+            config: LengthDelimitedCodec::builder(),
+            limits: ConnectionLimits::default(),
+            open: Arc::default(),
+            hello: None,
+            handshakes: FuturesUnordered::new(),
+            ghost: PhantomData,
+        }
+    }
+
+    /// Accepts the connections of an [`Incoming`].
+    pub trait Listener {
+        /// An accepted connection.
+        type Io: AsyncRead + AsyncWrite + Unpin + Send + 'static;
+
+        /// Polls for the next connection, returning it with the address of its peer.
+        fn poll_accept(&mut self, cx: &mut Context<'_>)
+            -> Poll<io::Result<(Self::Io, SocketAddr)>>;
+    }
+
+    impl Listener for TcpListener {
+        type Io = TcpStream;
+
+        fn poll_accept(
+            &mut self,
+            cx: &mut Context<'_>,
+        ) -> Poll<io::Result<(TcpStream, SocketAddr)>> {
+            TcpListener::poll_accept(self, cx)
+        }
+    }
+
+    /// A [`Listener`] accepting the connections yielded by a stream, returned by [`listen_with`].
+    #[derive(Debug)]
+    pub struct StreamListener<S> {
+        accept: Pin<Box<S>>,
+    }
+
+    impl<S, Io> Listener for StreamListener<S>
+    where
+        S: Stream<Item = io::Result<(Io, SocketAddr)>>,
+        Io: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        type Io = Io;
+
+        fn poll_accept(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<(Io, SocketAddr)>> {
+            self.accept.poll_next_unpin(cx).map(|conn| {
+                conn.unwrap_or_else(|| {
+                    Err(io::Error::new(
+                        io::ErrorKind::NotConnected,
+                        "the listener stopped accepting connections",
+                    ))
+                })
+            })
+        }
+    }
+
+    /// Limits the connections an [`Incoming`] keeps open at once.
+    #[derive(Clone, Copy, Debug, Default)]
+    #[non_exhaustive]
+    pub struct ConnectionLimits {
+        /// The maximum number of connections open at once, if limited.
+        pub max_connections: Option<usize>,
+        /// The maximum number of connections from one IP address open at once, if limited.
+        /// Connections over this limit are always refused.
+        pub max_connections_per_ip: Option<usize>,
+        /// What to do with connections over [`max_connections`](Self::max_connections).
+        pub excess: ExcessConnections,
+    }
+
+    impl ConnectionLimits {
+        /// Returns limits that allow any number of connections.
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Limits the number of connections open at once.
+        pub fn with_max_connections(mut self, max_connections: usize) -> Self {
+            self.max_connections = Some(max_connections);
+            self
+        }
+
+        /// Limits the number of connections from one IP address open at once.
+        pub fn with_max_connections_per_ip(mut self, max_connections_per_ip: usize) -> Self {
+            self.max_connections_per_ip = Some(max_connections_per_ip);
+            self
+        }
+
+        /// Sets what to do with connections over the maximum.
+        pub fn with_excess(mut self, excess: ExcessConnections) -> Self {
+            self.excess = excess;
+            self
+        }
+
+        fn is_unlimited(&self) -> bool {
+            self.max_connections.is_none() && self.max_connections_per_ip.is_none()
+        }
+    }
+
+    /// What to do with connections over the maximum number of connections.
+    #[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+    pub enum ExcessConnections {
+        /// Stop accepting connections until one closes. Pending connections wait in the
+        /// listen backlog of the operating system.
+        #[default]
+        Queue,
+        /// Accept connections and close them immediately.
+        Refuse,
+    }
+
+    /// The connections open at once.
+    #[derive(Debug, Default)]
+    struct OpenConnections {
+        total: usize,
+        per_ip: FnvHashMap<IpAddr, usize>,
+        /// Woken when a connection closes while accepting is paused.
+        waker: Option<Waker>,
+    }
+
+    /// Counts a connection toward the limits until dropped.
+    pub(super) struct ConnectionPermit {
+        ip: IpAddr,
+        open: Arc<Mutex<OpenConnections>>,
+    }
+
+    impl Drop for ConnectionPermit {
+        fn drop(&mut self) {
+            let mut open = self.open.lock().unwrap();
+            open.total -= 1;
+            if let Some(count) = open.per_ip.get_mut(&self.ip) {
+                *count -= 1;
+                if *count == 0 {
+                    open.per_ip.remove(&self.ip);
+                }
+            }
+            if let Some(waker) = open.waker.take() {
+                waker.wake();
+            }
+        }
     }
 
-    /// A [`TcpListener`] that wraps connections in [transports](Transport).
+    /// A [`TcpListener`], or another [`Listener`], that wraps connections in
+    /// [transports](Transport).
     #[pin_project]
     #[derive(Debug)]
-    pub struct Incoming<Item, SinkItem, Codec, CodecFn> {
-        listener: TcpListener,
+    pub struct Incoming<Item, SinkItem, Codec, CodecFn, L: Listener = TcpListener> {
+        listener: L,
         local_addr: SocketAddr,
         codec_fn: CodecFn,
         config: length_delimited::Builder,
+        limits: ConnectionLimits,
+        open: Arc<Mutex<OpenConnections>>,
+        hello: Option<Hello>,
+        /// The accepted connections still exchanging hellos.
+        handshakes: FuturesUnordered<BoxFuture<'static, Handshake<L::Io>>>,
         ghost: PhantomData<(fn() -> Item, fn(SinkItem), Codec)>,
     }
 
-    impl<Item, SinkItem, Codec, CodecFn> Incoming<Item, SinkItem, Codec, CodecFn> {
+    /// The result of exchanging hellos with the peer at the address.
+    type Handshake<Io> = (io::Result<Io>, SocketAddr, Option<ConnectionPermit>);
+
+    impl<Item, SinkItem, Codec, CodecFn, L: Listener> Incoming<Item, SinkItem, Codec, CodecFn, L> {
         /// Returns the address being listened on.
         pub fn local_addr(&self) -> SocketAddr {
             self.local_addr
@@ -259,24 +1552,128 @@ pub mod tcp {
         pub fn config_mut(&mut self) -> &mut length_delimited::Builder {
             &mut self.config
         }
+
+        /// Limits the connections kept open at once. A connection stays open until its transport
+        /// is dropped.
+        pub fn with_limits(mut self, limits: ConnectionLimits) -> Self {
+            self.limits = limits;
+            self
+        }
+
+        /// Exchanges `hello` with each accepted connection before any frames. Connections from
+        /// peers speaking another version of the protocol, or that don't send a hello within 10
+        /// seconds, are closed. Clients must [exchange hellos](Connect::with_handshake) too.
+        pub fn with_handshake(mut self, hello: Hello) -> Self {
+            self.hello = Some(hello);
+            self
+        }
+
+        /// Returns the number of accepted connections that are still open, if connections are
+        /// limited.
+        pub fn open_connections(&self) -> usize {
+            self.open.lock().unwrap().total
+        }
+
+        /// Counts a connection from `ip` toward the limits, unless it would exceed them.
+        fn admit(&self, ip: IpAddr) -> Option<ConnectionPermit> {
+            let mut open = self.open.lock().unwrap();
+            if let Some(max) = self.limits.max_connections {
+                if open.total >= max {
+                    return None;
+                }
+            }
+            // Checked before counting, so that refused connections don't leave entries behind.
+            let per_ip = open.per_ip.get(&ip).copied().unwrap_or_default();
+            if let Some(max) = self.limits.max_connections_per_ip {
+                if per_ip >= max {
+                    return None;
+                }
+            }
+            open.per_ip.insert(ip, per_ip + 1);
+            open.total += 1;
+            Some(ConnectionPermit {
+                ip,
+                open: self.open.clone(),
+            })
+        }
     }
 
-    impl<Item, SinkItem, Codec, CodecFn> Stream for Incoming<Item, SinkItem, Codec, CodecFn>
+    impl<Item, SinkItem, Codec, CodecFn, L> Stream for Incoming<Item, SinkItem, Codec, CodecFn, L>
     where
+        L: Listener,
         Item: for<'de> Deserialize<'de>,
         SinkItem: Serialize,
         Codec: Serializer<SinkItem> + Deserializer<Item>,
         CodecFn: Fn() -> Codec,
     {
-        type Item = io::Result<Transport<TcpStream, Item, SinkItem, Codec>>;
+        type Item = io::Result<Transport<L::Io, Item, SinkItem, Codec>>;
 
         fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-            let conn: TcpStream =
-                ready!(Pin::new(&mut self.as_mut().project().listener).poll_accept(cx)?).0;
-            Poll::Ready(Some(Ok(new(
-                self.config.new_framed(conn),
-                (self.codec_fn)(),
-            ))))
+            for _ in 0..CONNECTIONS_PER_POLL {
+                if let Poll::Ready(Some((conn, peer_addr, permit))) =
+                    self.handshakes.poll_next_unpin(cx)
+                {
+                    match conn {
+                        Ok(conn) => return Poll::Ready(Some(Ok(self.transport(conn, permit)))),
+                        Err(e) => {
+                            tracing::warn!(%peer_addr, "HandshakeFailed: {}", e);
+                            continue;
+                        }
+                    }
+                }
+
+                if let (Some(max), ExcessConnections::Queue) =
+                    (self.limits.max_connections, self.limits.excess)
+                {
+                    let mut open = self.open.lock().unwrap();
+                    if open.total >= max {
+                        open.waker = Some(cx.waker().clone());
+                        return Poll::Pending;
+                    }
+                }
+
+                let (conn, peer_addr) = ready!(self.as_mut().project().listener.poll_accept(cx)?);
+                let permit = if self.limits.is_unlimited() {
+                    None
+                } else {
+                    match self.admit(peer_addr.ip()) {
+                        Some(permit) => Some(permit),
+                        None => {
+                            tracing::info!(%peer_addr, "RefuseConnection");
+                            continue;
+                        }
+                    }
+                };
+                if let Some(hello) = self.hello {
+                    let handshake = shake_hands(conn, hello);
+                    self.handshakes
+                        .push(async move { (handshake.await, peer_addr, permit) }.boxed());
+                    continue;
+                }
+                return Poll::Ready(Some(Ok(self.transport(conn, permit))));
+            }
+            // Yield to the tasks sharing the worker, then carry on.
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    impl<Item, SinkItem, Codec, CodecFn, L> Incoming<Item, SinkItem, Codec, CodecFn, L>
+    where
+        L: Listener,
+        Item: for<'de> Deserialize<'de>,
+        SinkItem: Serialize,
+        Codec: Serializer<SinkItem> + Deserializer<Item>,
+        CodecFn: Fn() -> Codec,
+    {
+        fn transport(
+            &self,
+            conn: L::Io,
+            permit: Option<ConnectionPermit>,
+        ) -> Transport<L::Io, Item, SinkItem, Codec> {
+            let mut transport = new(self.config.new_framed(conn), (self.codec_fn)());
+            transport.permit = permit;
+            transport
         }
     }
 }
@@ -288,6 +1685,7 @@ pub mod unix {
     use {
         super::*,
         futures::ready,
+        serde::{Deserialize, Serialize},
         std::{marker::PhantomData, path::Path},
         tokio::net::{unix::SocketAddr, UnixListener, UnixStream},
         tokio_util::codec::length_delimited,
@@ -296,11 +1694,11 @@ pub mod unix {
     impl<Item, SinkItem, Codec> Transport<UnixStream, Item, SinkItem, Codec> {
         /// Returns the socket address of the remote half of the underlying [`UnixStream`].
         pub fn peer_addr(&self) -> io::Result<SocketAddr> {
-            self.inner.get_ref().get_ref().peer_addr()
+            self.get_ref().peer_addr()
         }
         /// Returns the socket address of the local half of the underlying [`UnixStream`].
         pub fn local_addr(&self) -> io::Result<SocketAddr> {
-            self.inner.get_ref().get_ref().local_addr()
+            self.get_ref().local_addr()
         }
     }
 
@@ -625,6 +2023,7 @@ mod tests {
             transport.as_mut().poll_next(&mut ctx()),
             Poll::Ready(Some(Ok(ref s))) if s == "Test one, check check.");
         assert_matches!(transport.as_mut().poll_next(&mut ctx()), Poll::Ready(None));
+        assert_eq!(transport.stats().bytes_read(), 0x18);
     }
 
     #[test]
@@ -653,6 +2052,297 @@ mod tests {
             transport.get_ref().0.get_ref(),
             b"\x00\x00\x00\x18\"Test one, check check.\""
         );
+        assert_eq!(transport.stats().bytes_written(), 0x18);
+    }
+
+    /// Records the vectored writes made to it, accepting at most `max_write` bytes per write.
+    struct VectoredIo {
+        written: Vec<u8>,
+        writes: usize,
+        max_write: usize,
+    }
+
+    impl AsyncRead for VectoredIo {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            _: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            Poll::Pending
+        }
+    }
+
+    impl AsyncWrite for VectoredIo {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.poll_write_vectored(cx, &[io::IoSlice::new(buf)])
+        }
+
+        fn poll_write_vectored(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            bufs: &[io::IoSlice<'_>],
+        ) -> Poll<io::Result<usize>> {
+            self.writes += 1;
+            let mut len = 0;
+            for buf in bufs {
+                let n = buf.len().min(self.max_write - len);
+                self.written.extend_from_slice(&buf[..n]);
+                len += n;
+            }
+            Poll::Ready(Ok(len))
+        }
+
+        fn is_write_vectored(&self) -> bool {
+            true
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    /// Sends a few messages, then returns what was written, in how many writes.
+    fn send_all(max_write: usize) -> (Vec<u8>, usize, u64) {
+        let mut transport = Box::pin(Transport::from((
+            VectoredIo {
+                written: vec![],
+                writes: 0,
+                max_write,
+            },
+            SymmetricalJson::<String>::default(),
+        )));
+        for message in ["one", "two", ""] {
+            assert_matches!(
+                transport.as_mut().poll_ready(&mut ctx()),
+                Poll::Ready(Ok(()))
+            );
+            assert_matches!(transport.as_mut().start_send(message.into()), Ok(()));
+        }
+        assert_matches!(
+            transport.as_mut().poll_flush(&mut ctx()),
+            Poll::Ready(Ok(()))
+        );
+        let io = transport.get_ref();
+        (
+            io.written.clone(),
+            io.writes,
+            transport.stats().bytes_written(),
+        )
+    }
+
+    const ALL_FRAMES: &[u8] = b"\x00\x00\x00\x05\"one\"\x00\x00\x00\x05\"two\"\x00\x00\x00\x02\"\"";
+
+    #[test]
+    fn sink_coalesces_frames_into_one_vectored_write() {
+        let (written, writes, bytes_written) = send_all(usize::MAX);
+        assert_eq!(written, ALL_FRAMES);
+        assert_eq!(writes, 1);
+        assert_eq!(bytes_written, 12);
+    }
+
+    #[test]
+    fn sink_resumes_partial_vectored_writes() {
+        let (written, writes, _) = send_all(3);
+        assert_eq!(written, ALL_FRAMES);
+        assert_eq!(writes, ALL_FRAMES.len().div_ceil(3));
+    }
+
+    #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Forward {
+        to: String,
+        #[serde(with = "super::payload")]
+        blob: bytes::Bytes,
+    }
+
+    #[test]
+    fn passthrough_sends_payloads_after_the_message() {
+        use super::Passthrough;
+        use tokio_serde::{formats::Json, Deserializer, Serializer};
+
+        let forward = Forward {
+            to: "home".into(),
+            blob: bytes::Bytes::from_static(b"\xff\x00blob"),
+        };
+        let mut codec = Box::pin(Passthrough::new(Json::<Forward, Forward>::default()));
+        let frame = codec.as_mut().serialize(&forward).unwrap();
+        let message = br#"{"to":"home","blob":[0,6]}"#;
+        assert_eq!(frame[..4], (message.len() as u32).to_be_bytes());
+        assert_eq!(&frame[4..4 + message.len()], message);
+        assert_eq!(&frame[4 + message.len()..], b"\xff\x00blob");
+        assert_eq!(
+            codec.as_mut().deserialize(&frame[..].into()).unwrap(),
+            forward
+        );
+
+        // Other codecs serialize payloads as `Bytes` does.
+        let json = serde_json::to_string(&forward).unwrap();
+        assert_eq!(json, r#"{"to":"home","blob":[255,0,98,108,111,98]}"#);
+        assert_eq!(serde_json::from_str::<Forward>(&json).unwrap(), forward);
+    }
+
+    #[test]
+    fn passthrough_rejects_payloads_out_of_bounds() {
+        use super::Passthrough;
+        use tokio_serde::{formats::Json, Deserializer};
+
+        let message = br#"{"to":"home","blob":[2,6]}"#;
+        let mut frame = (message.len() as u32).to_be_bytes().to_vec();
+        frame.extend_from_slice(message);
+        frame.extend_from_slice(b"blob");
+        let mut codec = Box::pin(Passthrough::new(Json::<Forward, Forward>::default()));
+        assert_matches!(codec.as_mut().deserialize(&frame[..].into()), Err(_));
+        assert_matches!(codec.as_mut().deserialize(&frame[..2].into()), Err(_));
+    }
+
+    #[test]
+    fn truncated_length_prefixed_frames_are_rejected() {
+        use super::split_length_prefixed;
+
+        assert_eq!(
+            split_length_prefixed(b"\0\0\0\x02abc").unwrap(),
+            (&b"ab"[..], &b"c"[..])
+        );
+        assert_eq!(
+            split_length_prefixed(b"\0\0\0\0").unwrap(),
+            (&b""[..], &b""[..])
+        );
+        for frame in [
+            &b""[..],
+            b"\0\0\0",
+            b"\0\0\0\x04abc",
+            b"\xff\xff\xff\xffabc",
+        ] {
+            let error = split_length_prefixed(frame).unwrap_err();
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        }
+    }
+
+    type LazyJson = super::LazyArgs<
+        tokio_serde::formats::Json<crate::ClientMessage<()>, crate::ClientMessage<()>>,
+        tokio_serde::formats::Json<String, String>,
+    >;
+
+    #[cfg(feature = "serde-transport-json")]
+    #[test]
+    fn pooled_frames_reuse_one_buffer() {
+        use super::Pooled;
+        use tokio_serde::{formats::Json, Deserializer, Serializer};
+
+        let mut codec = Box::pin(Pooled::new(Json::<u64, u64>::default()));
+        let first = codec.as_mut().serialize(&1).unwrap();
+        let second = codec.as_mut().serialize(&22).unwrap();
+        assert_eq!(&first[..], b"1");
+        assert_eq!(&second[..], b"22");
+        // Frames held at once are split off the same buffer.
+        assert_eq!(second.as_ptr(), first[1..].as_ptr());
+        assert_eq!(codec.as_mut().deserialize(&second[..].into()).unwrap(), 22);
+
+        // Once the frames split off the buffer are dropped, its space is reclaimed rather than
+        // reallocated.
+        let start = first.as_ptr();
+        drop((first, second));
+        let reclaimed =
+            (0..10_000).any(|_| codec.as_mut().serialize(&333).unwrap().as_ptr() == start);
+        assert!(reclaimed, "the buffer wasn't reclaimed");
+    }
+
+    #[cfg(feature = "serde-transport-bincode")]
+    #[test]
+    fn pooled_frames_match_the_wrapped_codec() {
+        use super::Pooled;
+        use tokio_serde::{formats::Bincode, Serializer};
+
+        let item = (7u64, "seven".to_string());
+        let mut pooled = Box::pin(Pooled::new(Bincode::<(), (u64, String)>::default()));
+        let mut bincode = Box::pin(Bincode::<(), (u64, String)>::default());
+        assert_eq!(
+            pooled.as_mut().serialize(&item).unwrap(),
+            bincode.as_mut().serialize(&item).unwrap()
+        );
+    }
+
+    #[test]
+    fn lazy_args_are_decoded_on_demand() {
+        use super::Lazy;
+        use crate::{context, ClientMessage, Request};
+        use tokio_serde::{Deserializer, Serializer};
+
+        let mut codec = Box::pin(LazyJson::default());
+        let frame = codec
+            .as_mut()
+            .serialize(&ClientMessage::Request(Request {
+                context: context::current(),
+                id: 7,
+                message: "args".to_string(),
+                oneway: true,
+            }))
+            .unwrap();
+        assert_eq!(&frame[frame.len() - 6..], br#""args""#);
+
+        let message: ClientMessage<Lazy<String>> =
+            codec.as_mut().deserialize(&frame[..].into()).unwrap();
+        let request = assert_matches!(message, ClientMessage::Request(request) => request);
+        assert_eq!(request.id, 7);
+        assert!(request.oneway);
+        assert_eq!(request.message.as_bytes(), br#""args""#);
+        assert_eq!(request.message.decode().unwrap(), "args");
+
+        let frame = codec
+            .as_mut()
+            .serialize(&ClientMessage::<String>::Cancel {
+                trace_context: Default::default(),
+                request_id: 7,
+                reason: Default::default(),
+                ack: false,
+            })
+            .unwrap();
+        let message: ClientMessage<Lazy<String>> =
+            codec.as_mut().deserialize(&frame[..].into()).unwrap();
+        assert_matches!(message, ClientMessage::Cancel { request_id: 7, .. });
+    }
+
+    #[test]
+    fn lazy_args_that_dont_decode_are_rejected_when_served() {
+        use super::{Lazy, LazyServe};
+        use crate::{context, server, ClientMessage, Request};
+        use server::Serve;
+        use tokio_serde::{Deserializer, Serializer};
+
+        let mut codec = Box::pin(LazyJson::default());
+        let mut frame = bytes::BytesMut::from(
+            &codec
+                .as_mut()
+                .serialize(&ClientMessage::Request(Request {
+                    context: context::current(),
+                    id: 0,
+                    message: "args".to_string(),
+                    oneway: false,
+                }))
+                .unwrap()[..],
+        );
+        frame.truncate(frame.len() - 1);
+        let message: ClientMessage<Lazy<String>> = codec.as_mut().deserialize(&frame).unwrap();
+        let request = assert_matches!(message, ClientMessage::Request(request) => request);
+
+        let serve = LazyServe::new(server::serve(|_, args: String| async move { Ok(args) }));
+        let response =
+            futures::executor::block_on(serve.serve(context::current(), request.message));
+        assert_matches!(
+            response,
+            Err(crate::ServerError {
+                kind: io::ErrorKind::InvalidData,
+                reason: Some(crate::ServerErrorReason::BadRequest),
+                ..
+            })
+        );
     }
 
     #[cfg(feature = "tcp")]
@@ -674,6 +2364,172 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(feature = "tcp")]
+    #[tokio::test]
+    async fn tcp_queues_connections_over_limit() -> io::Result<()> {
+        use super::tcp::{self, ConnectionLimits};
+        use std::time::Duration;
+
+        let mut listener = tcp::listen("127.0.0.1:0", SymmetricalJson::<String>::default)
+            .await?
+            .with_limits(ConnectionLimits::new().with_max_connections(1));
+        let addr = listener.local_addr();
+
+        let _first = tcp::connect(addr, SymmetricalJson::<String>::default).await?;
+        let accepted = listener.next().await.unwrap()?;
+        assert_eq!(listener.open_connections(), 1);
+
+        let _second = tcp::connect(addr, SymmetricalJson::<String>::default).await?;
+        assert!(
+            tokio::time::timeout(Duration::from_millis(50), listener.next())
+                .await
+                .is_err()
+        );
+
+        drop(accepted);
+        let _accepted = listener.next().await.unwrap()?;
+        assert_eq!(listener.open_connections(), 1);
+        Ok(())
+    }
+
+    #[cfg(feature = "tcp")]
+    #[tokio::test]
+    async fn tcp_refuses_connections_over_per_ip_limit() -> io::Result<()> {
+        use super::tcp::{self, ConnectionLimits};
+
+        let mut listener = tcp::listen("127.0.0.1:0", SymmetricalJson::<String>::default)
+            .await?
+            .with_limits(ConnectionLimits::new().with_max_connections_per_ip(1));
+        let addr = listener.local_addr();
+
+        let _first = tcp::connect(addr, SymmetricalJson::<String>::default).await?;
+        let _accepted = listener.next().await.unwrap()?;
+
+        let mut refused = tcp::connect(addr, SymmetricalJson::<String>::default).await?;
+        tokio::spawn(async move { listener.next().await.map(|_| ()) });
+        assert_matches!(refused.next().await, None | Some(Err(_)));
+        Ok(())
+    }
+
+    #[cfg(feature = "tcp")]
+    #[test]
+    fn tcp_yields_while_refusing_a_storm_of_connections() {
+        use super::tcp::{self, ConnectionLimits};
+        use futures_test::task::new_count_waker;
+        use std::net::SocketAddr;
+
+        let addr: SocketAddr = ([10, 0, 0, 1], 4000).into();
+        let accept = futures::stream::iter((0..1000).map(|_| Ok((tokio::io::duplex(64).0, addr))));
+        let mut listener = tcp::listen_with(accept, addr, SymmetricalJson::<String>::default)
+            .with_limits(ConnectionLimits::new().with_max_connections_per_ip(0));
+        let (waker, wakes) = new_count_waker();
+        let cx = &mut Context::from_waker(&waker);
+        assert!(listener.poll_next_unpin(cx).is_pending());
+        assert_eq!(wakes.get(), 1);
+        assert_eq!(listener.open_connections(), 0);
+    }
+
+    #[tokio::test]
+    async fn handshakes_agree_on_common_features() -> io::Result<()> {
+        use super::handshake::{self, Hello};
+
+        let (mut a, mut b) = tokio::io::duplex(64);
+        let (a, b) = futures::join!(
+            handshake::exchange(&mut a, Hello::new().with_features(0b011)),
+            handshake::exchange(&mut b, Hello::new().with_features(0b110)),
+        );
+        assert_eq!(a?.features & b?.features, 0b010);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn handshakes_reject_other_versions() {
+        use super::handshake::{self, Hello, IncompatiblePeer};
+
+        let (mut a, mut b) = tokio::io::duplex(64);
+        let old = Hello {
+            version: 0,
+            features: 0,
+        };
+        let (a, _) = futures::join!(
+            handshake::exchange(&mut a, Hello::new()),
+            handshake::exchange(&mut b, old),
+        );
+        let e = a.unwrap_err();
+        assert_eq!(
+            IncompatiblePeer::of(&e),
+            Some(&IncompatiblePeer {
+                local: Hello::new(),
+                peer: Some(old),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn handshakes_reject_peers_without_one() {
+        use super::handshake::{self, Hello, IncompatiblePeer};
+        use tokio::io::AsyncWriteExt;
+
+        let (mut a, mut b) = tokio::io::duplex(64);
+        // A length-delimited frame, as sent by a peer that predates handshakes.
+        b.write_all(b"\0\0\0\x06\"test\"").await.unwrap();
+        let e = handshake::exchange(&mut a, Hello::new()).await.unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert_matches!(
+            IncompatiblePeer::of(&e),
+            Some(IncompatiblePeer { peer: None, .. })
+        );
+    }
+
+    #[cfg(feature = "tcp")]
+    #[tokio::test]
+    async fn tcp_with_handshake() -> io::Result<()> {
+        use super::{handshake::Hello, tcp};
+
+        let mut listener = tcp::listen("127.0.0.1:0", SymmetricalJson::<String>::default)
+            .await?
+            .with_handshake(Hello::new());
+        let addr = listener.local_addr();
+        tokio::spawn(async move {
+            let mut transport = listener.next().await.unwrap().unwrap();
+            let message = transport.next().await.unwrap().unwrap();
+            transport.send(message).await.unwrap();
+        });
+        let mut transport = tcp::connect(addr, SymmetricalJson::<String>::default)
+            .with_handshake(Hello::new())
+            .await?;
+        transport.send(String::from("test")).await?;
+        assert_matches!(transport.next().await, Some(Ok(s)) if s == "test");
+        Ok(())
+    }
+
+    #[cfg(feature = "tcp")]
+    #[tokio::test]
+    async fn tcp_handshake_with_old_server_fails() -> io::Result<()> {
+        use super::{
+            handshake::{Hello, IncompatiblePeer},
+            tcp,
+        };
+
+        let mut listener = tcp::listen("127.0.0.1:0", SymmetricalJson::<String>::default).await?;
+        let addr = listener.local_addr();
+        tokio::spawn(async move {
+            let mut transport = listener.next().await.unwrap().unwrap();
+            // The hello doesn't decode as a frame, so the server drops the connection.
+            transport.next().await;
+        });
+        let e = tcp::connect(addr, SymmetricalJson::<String>::default)
+            .with_handshake(Hello::new())
+            .await
+            .err()
+            .unwrap();
+        assert_matches!(
+            IncompatiblePeer::of(&e),
+            Some(IncompatiblePeer { peer: None, .. })
+        );
+        Ok(())
+    }
+
     #[cfg(feature = "tcp")]
     #[tokio::test]
     async fn tcp_on_existing_transport() -> io::Result<()> {
@@ -694,6 +2550,34 @@ mod tests {
         Ok(())
     }
 
+    #[cfg(feature = "tcp")]
+    #[tokio::test]
+    async fn tcp_with_handshake_over_other_sockets() -> io::Result<()> {
+        use super::{handshake::Hello, tcp};
+        use std::net::SocketAddr;
+
+        let (client, server) = tokio::io::duplex(1024);
+        let client_addr: SocketAddr = ([10, 0, 0, 1], 4000).into();
+        let server_addr: SocketAddr = ([10, 0, 0, 2], 80).into();
+        let accept = futures::stream::iter([Ok((server, client_addr))]);
+        let mut listener =
+            tcp::listen_with(accept, server_addr, SymmetricalJson::<String>::default)
+                .with_handshake(Hello::new());
+        assert_eq!(listener.local_addr(), server_addr);
+        tokio::spawn(async move {
+            let mut transport = listener.next().await.unwrap().unwrap();
+            let message = transport.next().await.unwrap().unwrap();
+            transport.send(message).await.unwrap();
+        });
+        let connect = futures::future::ready(Ok(client));
+        let mut transport = tcp::connect_with(connect, SymmetricalJson::<String>::default)
+            .with_handshake(Hello::new())
+            .await?;
+        transport.send(String::from("test")).await?;
+        assert_matches!(transport.next().await, Some(Ok(s)) if s == "test");
+        Ok(())
+    }
+
     #[cfg(all(unix, feature = "unix"))]
     #[tokio::test]
     async fn uds() -> io::Result<()> {
@@ -732,4 +2616,173 @@ mod tests {
         assert_matches!(transport.next().await, None);
         Ok(())
     }
+
+    #[derive(serde::Serialize)]
+    #[serde(untagged)]
+    enum Body {
+        Valid(u64),
+        Invalid(&'static str),
+    }
+
+    fn request<T>(id: u64, message: T) -> crate::ClientMessage<T> {
+        crate::ClientMessage::Request(crate::Request {
+            context: crate::context::current(),
+            id,
+            message,
+            oneway: false,
+        })
+    }
+
+    #[test]
+    fn recoverable_salvages_request_id() {
+        use super::{ClientMessageHeader, Recoverable};
+        use bincode::Options;
+        use bytes::BytesMut;
+        use tokio_serde::{formats::Bincode, Deserializer};
+
+        let codec = Recoverable::new(
+            Bincode::<crate::ClientMessage<u64>, ()>::default(),
+            Bincode::<ClientMessageHeader, (), _>::from(
+                bincode::DefaultOptions::new().allow_trailing_bytes(),
+            ),
+        );
+        pin_mut!(codec);
+        let frame = bincode::DefaultOptions::new()
+            .serialize(&request(7, Body::Invalid("oops")))
+            .unwrap();
+
+        let error = codec
+            .as_mut()
+            .deserialize(&BytesMut::from(&frame[..]))
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        assert_matches!(
+            crate::transport::MalformedMessage::find(&error),
+            Some(malformed) if malformed.request_id == Some(7)
+        );
+
+        let frame = bincode::DefaultOptions::new()
+            .serialize(&request(8, Body::Valid(1)))
+            .unwrap();
+        assert_matches!(
+            codec.deserialize(&BytesMut::from(&frame[..])),
+            Ok(crate::ClientMessage::Request(crate::Request {
+                id: 8,
+                message: 1,
+                ..
+            }))
+        );
+    }
+
+    #[tokio::test]
+    async fn server_rejects_malformed_request_and_keeps_serving() -> io::Result<()> {
+        use super::{ClientMessageHeader, Recoverable};
+        use crate::{
+            server::{self, BaseChannel, Channel},
+            ClientMessage, Response,
+        };
+        use tokio_serde::formats::Json;
+
+        let (client_io, server_io) = tokio::io::duplex(4096);
+        let server_transport = Transport::<_, ClientMessage<u64>, Response<u64>, _>::from((
+            server_io,
+            Recoverable::new(Json::default(), Json::<ClientMessageHeader, ()>::default()),
+        ));
+        let config = server::Config {
+            malformed_message_policy: server::MalformedMessagePolicy::Reject,
+            ..server::Config::default()
+        };
+        tokio::spawn(
+            BaseChannel::new(config, server_transport)
+                .execute(server::serve(|_, i: u64| async move { Ok(i + 1) }))
+                .for_each(|response| response),
+        );
+
+        let mut client_transport = Transport::<_, Response<u64>, ClientMessage<Body>, _>::from((
+            client_io,
+            Json::default(),
+        ));
+        client_transport
+            .send(request(7, Body::Invalid("oops")))
+            .await?;
+        client_transport.send(request(8, Body::Valid(1))).await?;
+
+        let rejected = client_transport.next().await.unwrap()?;
+        assert_eq!(rejected.request_id, 7);
+        assert_matches!(rejected.message, Err(ref e) if e.reason == Some(crate::ServerErrorReason::BadRequest));
+        let served = client_transport.next().await.unwrap()?;
+        assert_eq!(served.request_id, 8);
+        assert_eq!(served.message, Ok(2));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn server_closes_on_malformed_request_by_default() -> io::Result<()> {
+        use super::{ClientMessageHeader, Recoverable};
+        use crate::{server::BaseChannel, ChannelError, ClientMessage, Response};
+        use tokio_serde::formats::Json;
+
+        let (client_io, server_io) = tokio::io::duplex(4096);
+        let server_transport = Transport::<_, ClientMessage<u64>, Response<u64>, _>::from((
+            server_io,
+            Recoverable::new(Json::default(), Json::<ClientMessageHeader, ()>::default()),
+        ));
+        let mut channel = BaseChannel::with_defaults(server_transport);
+
+        let mut client_transport = Transport::<_, Response<u64>, ClientMessage<Body>, _>::from((
+            client_io,
+            Json::default(),
+        ));
+        client_transport
+            .send(request(7, Body::Invalid("oops")))
+            .await?;
+
+        assert_matches!(channel.next().await, Some(Err(ChannelError::Read(_))));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn server_handles_malformed_requests_per_policy() -> io::Result<()> {
+        use super::{ClientMessageHeader, Recoverable};
+        use crate::{
+            server::{self, BaseChannel, Channel, MalformedMessagePolicy},
+            ClientMessage, Response,
+        };
+        use tokio_serde::formats::Json;
+
+        for policy in [MalformedMessagePolicy::Skip, MalformedMessagePolicy::Close] {
+            let (client_io, server_io) = tokio::io::duplex(4096);
+            let server_transport = Transport::<_, ClientMessage<u64>, Response<u64>, _>::from((
+                server_io,
+                Recoverable::new(Json::default(), Json::<ClientMessageHeader, ()>::default()),
+            ));
+            let config = server::Config {
+                malformed_message_policy: policy,
+                ..server::Config::default()
+            };
+            tokio::spawn(
+                BaseChannel::new(config, server_transport)
+                    .execute(server::serve(|_, i: u64| async move { Ok(i + 1) }))
+                    .for_each(|response| response),
+            );
+
+            let mut client_transport = Transport::<_, Response<u64>, ClientMessage<Body>, _>::from(
+                (client_io, Json::default()),
+            );
+            client_transport
+                .send(request(7, Body::Invalid("oops")))
+                .await?;
+            client_transport.send(request(8, Body::Valid(1))).await?;
+
+            match policy {
+                MalformedMessagePolicy::Skip => {
+                    let served = client_transport.next().await.unwrap()?;
+                    assert_eq!(served.request_id, 8);
+                    assert_eq!(served.message, Ok(2));
+                }
+                _ => assert_matches!(client_transport.next().await, None),
+            }
+        }
+        Ok(())
+    }
 }
@@ -9,7 +9,11 @@
 use crate::{
     cancellations::{cancellations, CanceledRequests, RequestCancellation},
     context::{self, SpanExt},
-    trace, ChannelError, ClientMessage, Request, Response, ServerError, Transport,
+    stats::{ChannelStats, RequestTimer, Role},
+    trace,
+    transport::{FlushPolicy, Flusher, MalformedMessage},
+    util::{self, scoped::Scoped, YieldBudget},
+    ChannelError, ClientMessage, Request, Response, ResponseExtensions, ServerError, Transport,
 };
 use ::tokio::sync::mpsc;
 use futures::{
@@ -21,7 +25,18 @@ use futures::{
 };
 use in_flight_requests::{AlreadyExistsError, InFlightRequests};
 use pin_project::pin_project;
-use std::{convert::TryFrom, error::Error, fmt, marker::PhantomData, pin::Pin, sync::Arc};
+use std::{
+    collections::VecDeque,
+    convert::TryFrom,
+    error::Error,
+    fmt, io,
+    marker::PhantomData,
+    mem,
+    net::SocketAddr,
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 use tracing::{info_span, instrument::Instrument, Span};
 
 mod in_flight_requests;
@@ -29,14 +44,41 @@ pub mod request_hook;
 #[cfg(test)]
 mod testing;
 
+/// Adapts serve fns written against the associated-future form of [`Serve`].
+pub mod compat;
 /// Provides functionality to apply server limits.
 pub mod limits;
 
 /// Provides helper methods for streams of Channels.
 pub mod incoming;
 
+pub mod authorization;
+pub mod body;
+pub mod broadcast;
+pub mod execution;
+pub mod idempotency;
+pub mod introspection;
+pub mod lame_duck;
+#[cfg(all(
+    feature = "serde-transport",
+    any(feature = "tcp", all(unix, feature = "unix"))
+))]
+#[cfg_attr(
+    docsrs,
+    doc(cfg(all(
+        feature = "serde-transport",
+        any(feature = "tcp", all(unix, feature = "unix"))
+    )))
+)]
+pub mod quickstart;
+pub mod response_extensions;
+pub mod routing;
+pub mod shadow;
+
+use execution::RequestExecution;
 use request_hook::{
-    AfterRequest, BeforeRequest, HookThenServe, HookThenServeThenHook, ServeThenHook,
+    AfterRequest, BeforeRequest, HookThenServe, HookThenServeThenHook, InterceptResponse,
+    ServeThenHook, ServeThenIntercept,
 };
 
 /// Settings that control the behavior of [channels](Channel).
@@ -46,17 +88,217 @@ pub struct Config {
     /// responses to the [`Channel`]. In other words, this is the number of responses that can sit
     /// in the outbound queue before request handlers begin blocking.
     pub pending_response_buffer: usize,
+    /// Requests still running this long after they started are logged as slow, along with their
+    /// method name, trace ID, and elapsed time. A request is reported once, as soon as it crosses
+    /// the threshold, so that requests that hang are reported too. No requests are considered
+    /// slow if `None`.
+    pub slow_request_threshold: Option<Duration>,
+    /// Invoked for every request that exceeds the `slow_request_threshold`.
+    pub slow_request_hook: Option<SlowRequestHook>,
+    /// The furthest in the future, relative to when a request is received, that its deadline may
+    /// be. Unbounded if `None`.
+    pub max_deadline: Option<Duration>,
+    /// The soonest, relative to when a request is received, that its deadline may be. Unbounded if
+    /// `None`. Regardless, requests that arrive already expired are answered with a
+    /// [deadline-exceeded](ServerError::deadline_exceeded) error without being served.
+    pub min_deadline: Option<Duration>,
+    /// What to do with requests whose deadlines are outside of `min_deadline..=max_deadline`.
+    pub deadline_policy: DeadlinePolicy,
+    /// When [`Requests`] flushes the responses it writes to the channel.
+    pub flush_policy: FlushPolicy,
+    /// The number of shards the channel's in-flight requests, their deadlines, and their
+    /// cancellations are split into. Channels with very many requests in flight can use more than
+    /// one shard to keep the structures tracking them small.
+    pub in_flight_shards: usize,
+    /// The most bytes that the requests in flight on a channel may hold at once. Unbounded if
+    /// `None`. A request is assumed to hold the bytes of the frame it was read from, if the channel
+    /// [shares the counters](BaseChannel::with_stats) of a transport counting them, and at least
+    /// the size of its type.
+    pub max_in_flight_bytes: Option<usize>,
+    /// What to do with requests received while `max_in_flight_bytes` are in flight.
+    pub memory_limit_policy: MemoryLimitPolicy,
+    /// The most requests that may be in flight on a channel at once. Unbounded if `None`. While
+    /// the limit is reached, the channel stops reading requests from its transport, so that the
+    /// transport's own flow control, e.g. TCP's, slows down the client, rather than buffering or
+    /// [rejecting](limits::requests_per_channel::MaxRequests) the excess requests.
+    pub max_in_flight_requests: Option<usize>,
+    /// The most requests and responses that [`Requests`] reads and writes before yielding to the
+    /// executor, even if more are ready. Unbounded if `None`. Under a flood of requests, a
+    /// channel that never yields can starve the other tasks on its worker; a smaller value lets
+    /// them run sooner at the cost of the channel's throughput. Transports built on tokio's
+    /// resources also yield once the task's cooperative budget is spent, whatever this limit.
+    pub frames_per_yield: Option<usize>,
+    /// What to do with messages the transport reports as [malformed](MalformedMessage). Other
+    /// errors reading from the transport always close the channel.
+    pub malformed_message_policy: MalformedMessagePolicy,
+}
+
+/// What to do with a message read by a channel that the transport could not decode, as reported
+/// by a [`MalformedMessage`] error.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MalformedMessagePolicy {
+    /// Close the channel, yielding a [`ChannelError::Read`] error.
+    #[default]
+    Close,
+    /// Log the message and keep serving the channel.
+    Skip,
+    /// Respond with a [bad request](ServerError::bad_request) error if the ID of the request the
+    /// message carried could be salvaged, so that the client need not wait for the request's
+    /// deadline, and otherwise skip the message. Either way, keep serving the channel.
+    Reject,
+}
+
+/// What to do with requests received by a channel whose in-flight requests hold the most bytes
+/// allowed by [`Config::max_in_flight_bytes`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MemoryLimitPolicy {
+    /// Stop reading requests from the transport until enough in-flight requests complete, so that
+    /// the client is slowed down by the transport's own flow control.
+    #[default]
+    Backpressure,
+    /// Respond to requests that would exceed the limit with an
+    /// [`OutOfMemory`](io::ErrorKind::OutOfMemory) error without serving them.
+    Reject,
+}
+
+/// What to do with a request whose deadline is outside the bounds accepted by the server.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DeadlinePolicy {
+    /// Move the deadline to the nearest accepted bound and serve the request.
+    #[default]
+    Clamp,
+    /// Respond to the request with an [`InvalidInput`](io::ErrorKind::InvalidInput) error without
+    /// serving it.
+    Reject,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Config {
             pending_response_buffer: 100,
+            slow_request_threshold: None,
+            slow_request_hook: None,
+            max_deadline: None,
+            min_deadline: None,
+            deadline_policy: DeadlinePolicy::default(),
+            flush_policy: FlushPolicy::default(),
+            in_flight_shards: 1,
+            max_in_flight_bytes: None,
+            memory_limit_policy: MemoryLimitPolicy::default(),
+            max_in_flight_requests: None,
+            frames_per_yield: None,
+            malformed_message_policy: MalformedMessagePolicy::default(),
+        }
+    }
+}
+
+/// Describes a request that has run for longer than [`Config::slow_request_threshold`].
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct SlowRequest {
+    /// The ID of the slow request.
+    pub request_id: u64,
+    /// The name of the method called, if known.
+    pub method: Option<&'static str>,
+    /// The trace ID of the slow request.
+    pub trace_id: trace::TraceId,
+    /// How long the request had been running, including time spent buffering its response, when
+    /// it was reported.
+    pub elapsed: Duration,
+}
+
+/// A callback invoked with every [`SlowRequest`].
+#[derive(Clone)]
+pub struct SlowRequestHook(Arc<dyn Fn(&SlowRequest) + Send + Sync>);
+
+impl SlowRequestHook {
+    /// Returns a hook that calls `f` for every slow request.
+    pub fn new<F>(f: F) -> Self
+    where
+        F: Fn(&SlowRequest) + Send + Sync + 'static,
+    {
+        Self(Arc::new(f))
+    }
+}
+
+impl fmt::Debug for SlowRequestHook {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SlowRequestHook")
+    }
+}
+
+/// The slow request policy of the channel that produced an [`InFlightRequest`].
+#[derive(Clone, Debug)]
+struct SlowRequestPolicy {
+    threshold: Duration,
+    hook: Option<SlowRequestHook>,
+}
+
+impl SlowRequestPolicy {
+    fn from_config(config: &Config) -> Option<Self> {
+        Some(Self {
+            threshold: config.slow_request_threshold?,
+            hook: config.slow_request_hook.clone(),
+        })
+    }
+
+    fn report(&self, slow_request: SlowRequest) {
+        tracing::warn!(
+            rpc.method = slow_request.method.unwrap_or(""),
+            rpc.trace_id = %slow_request.trace_id,
+            elapsed = ?slow_request.elapsed,
+            "SlowRequest"
+        );
+        if let Some(hook) = &self.hook {
+            (hook.0)(&slow_request);
         }
     }
 }
 
 impl Config {
+    /// Applies the deadline bounds and policy to `deadline`, returning the deadline to use, or an
+    /// error if the request should be rejected.
+    fn bound_deadline(&self, deadline: Instant, now: Instant) -> Result<Instant, ServerError> {
+        if let Some(max) = self.max_deadline {
+            let latest = now + max;
+            if deadline > latest {
+                return match self.deadline_policy {
+                    DeadlinePolicy::Clamp => Ok(latest),
+                    DeadlinePolicy::Reject => Err(ServerError::new(
+                        io::ErrorKind::InvalidInput,
+                        format!(
+                            "request deadline {} is more than {max:?} in the future",
+                            humantime::format_rfc3339(util::system_time(deadline))
+                        ),
+                    )),
+                };
+            }
+        }
+        if let Some(min) = self.min_deadline {
+            let earliest = now + min;
+            if deadline < earliest {
+                return match self.deadline_policy {
+                    DeadlinePolicy::Clamp => Ok(earliest),
+                    DeadlinePolicy::Reject if deadline <= now => Err(ServerError::new(
+                        io::ErrorKind::InvalidInput,
+                        format!(
+                            "request deadline {} had already passed when the request was received",
+                            humantime::format_rfc3339(util::system_time(deadline))
+                        ),
+                    )),
+                    DeadlinePolicy::Reject => Err(ServerError::new(
+                        io::ErrorKind::InvalidInput,
+                        format!(
+                            "request deadline {} is less than {min:?} in the future",
+                            humantime::format_rfc3339(util::system_time(deadline))
+                        ),
+                    )),
+                };
+            }
+        }
+        Ok(deadline)
+    }
+
     /// Returns a channel backed by `transport` and configured with `self`.
     pub fn channel<Req, Resp, T>(self, transport: T) -> BaseChannel<Req, Resp, T>
     where
@@ -67,7 +309,11 @@ impl Config {
 }
 
 /// Equivalent to a `FnOnce(Req) -> impl Future<Output = Resp>`.
-#[allow(async_fn_in_trait)]
+///
+/// The future returned by [`serve`](Serve::serve) is not boxed, so serving a request requires
+/// no allocation beyond what the implementation itself does. Implementations can use `async fn`,
+/// or return any future directly. Types implementing the previous, associated-future form of this
+/// trait can be adapted with [`compat`].
 pub trait Serve {
     /// Type of request.
     type Req;
@@ -76,7 +322,11 @@ pub trait Serve {
     type Resp;
 
     /// Responds to a single request.
-    async fn serve(self, ctx: context::Context, req: Self::Req) -> Result<Self::Resp, ServerError>;
+    fn serve(
+        self,
+        ctx: context::Context,
+        req: Self::Req,
+    ) -> impl Future<Output = Result<Self::Resp, ServerError>>;
 
     /// Extracts a method name from the request.
     fn method(&self, _request: &Self::Req) -> Option<&'static str> {
@@ -167,6 +417,42 @@ pub trait Serve {
         ServeThenHook::new(self, hook)
     }
 
+    /// Passes the response to each request through a hook before it is sent.
+    ///
+    /// The hook receives the name of the method that was called, the request context, and the
+    /// response, and returns the response to send in its place. This can be used, for example, to
+    /// translate internal errors, attach [response extensions](response_extensions), or filter
+    /// fields based on the caller.
+    ///
+    /// Any type that implements [`InterceptResponse`] can be used as the hook. Types that
+    /// implement `FnMut(Option<&'static str>, &Context, Result<ResponseType, ServerError>) -> impl
+    /// Future<Output = Result<ResponseType, ServerError>>` can also be used.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use futures::{executor::block_on, future};
+    /// use tarpc::{context, ServerError, server::{Serve, serve}};
+    /// use std::io;
+    ///
+    /// let serve = serve(|_ctx, i: i32| async move {
+    ///         Err(ServerError::new(io::ErrorKind::Other, "database password is hunter2".into()))
+    ///     })
+    ///     .intercept(|_method, _ctx: &context::Context, resp: Result<i32, ServerError>| {
+    ///         future::ready(resp.map_err(|e| ServerError::new(e.kind, "internal error".into())))
+    ///     });
+    ///
+    /// let response = block_on(serve.serve(context::current(), 1));
+    /// assert_eq!(response.unwrap_err().detail, "internal error");
+    /// ```
+    fn intercept<Hook>(self, hook: Hook) -> ServeThenIntercept<Self, Hook>
+    where
+        Hook: InterceptResponse<Self::Resp>,
+        Self: Sized,
+    {
+        ServeThenIntercept::new(self, hook)
+    }
+
     /// Runs a hook before and after execution of the request.
     ///
     /// If the hook returns an error, the request will not be executed and the error will be
@@ -263,8 +549,12 @@ where
     type Req = Req;
     type Resp = Resp;
 
-    async fn serve(self, ctx: context::Context, req: Req) -> Result<Resp, ServerError> {
-        (self.f)(ctx, req).await
+    fn serve(
+        self,
+        ctx: context::Context,
+        req: Req,
+    ) -> impl Future<Output = Result<Resp, ServerError>> {
+        (self.f)(ctx, req)
     }
 }
 
@@ -291,6 +581,17 @@ pub struct BaseChannel<Req, Resp, T> {
     request_cancellation: RequestCancellation,
     /// Holds data necessary to clean up in-flight requests.
     in_flight_requests: InFlightRequests,
+    /// Error responses to malformed and expired requests, waiting to be written to the transport.
+    rejected_request_responses: VecDeque<Response<Resp>>,
+    /// Counts the traffic of the channel.
+    stats: ChannelStats,
+    /// Returns true for requests served without a span.
+    untraced: fn(&Req) -> bool,
+    /// The address of the client, if known.
+    peer_addr: Option<SocketAddr>,
+    /// Woken when in-flight requests complete or rejected requests are answered, if reading
+    /// requests stopped because the channel reached its limits.
+    read_waker: Option<Waker>,
     /// Types the request and response.
     ghost: PhantomData<(fn() -> Req, fn(Resp))>,
 }
@@ -301,13 +602,20 @@ where
 {
     /// Creates a new channel backed by `transport` and configured with `config`.
     pub fn new(config: Config, transport: T) -> Self {
-        let (request_cancellation, canceled_requests) = cancellations();
+        let (request_cancellation, canceled_requests) = cancellations(config.in_flight_shards);
+        let stats = ChannelStats::default();
+        stats.set_role(Role::Server);
         BaseChannel {
+            in_flight_requests: InFlightRequests::with_shards(config.in_flight_shards),
             config,
             transport: transport.fuse(),
             canceled_requests,
             request_cancellation,
-            in_flight_requests: InFlightRequests::default(),
+            rejected_request_responses: VecDeque::new(),
+            stats,
+            untraced: |_| false,
+            peer_addr: None,
+            read_waker: None,
             ghost: PhantomData,
         }
     }
@@ -327,6 +635,40 @@ where
         self.project().transport.get_pin_mut()
     }
 
+    /// Returns the counters of the channel's traffic. Clone the handle to read the counters
+    /// after the channel is moved.
+    pub fn stats(&self) -> &ChannelStats {
+        &self.stats
+    }
+
+    /// Records the channel's traffic in `stats`, rather than in counters of its own. Pass the
+    /// stats of a transport that counts bytes to collect all counters in one place.
+    pub fn with_stats(mut self, stats: ChannelStats) -> Self {
+        stats.set_role(Role::Server);
+        self.stats = stats;
+        self
+    }
+
+    /// Serves the requests for which `untraced` returns true without creating spans for them,
+    /// which saves the cost of spans for tiny, frequent requests. Pass the `is_untraced` fn of a
+    /// service's request enum to skip the spans of the methods declared with
+    /// `#[tarpc::no_trace]`.
+    ///
+    /// Untraced requests are still given a trace context, a child of the client's. Without a span,
+    /// [`context::current`] can't find it within their handlers, so handlers making requests of
+    /// their own should pass on the context they're given.
+    pub fn with_untraced(mut self, untraced: fn(&Req) -> bool) -> Self {
+        self.untraced = untraced;
+        self
+    }
+
+    /// Annotates the spans of requests with the address of the client, e.g. the peer address of
+    /// a TCP connection.
+    pub fn with_peer_addr(mut self, peer_addr: SocketAddr) -> Self {
+        self.peer_addr = Some(peer_addr);
+        self
+    }
+
     fn in_flight_requests_mut<'a>(self: &'a mut Pin<&mut Self>) -> &'a mut InFlightRequests {
         self.as_mut().project().in_flight_requests
     }
@@ -341,31 +683,213 @@ where
         self.as_mut().project().transport
     }
 
+    /// Handles a message that could not be decoded and won't close the channel. If the policy is
+    /// to reject it and its request ID is known, the client is sent an error response, so that it
+    /// need not wait for the request's deadline.
+    fn reject_malformed_message(mut self: Pin<&mut Self>, malformed: &MalformedMessage) {
+        let request_id = match self.config.malformed_message_policy {
+            MalformedMessagePolicy::Reject => malformed.request_id,
+            MalformedMessagePolicy::Skip | MalformedMessagePolicy::Close => None,
+        };
+        let Some(request_id) = request_id else {
+            tracing::warn!(error = %malformed.source, "SkipMalformedMessage");
+            return;
+        };
+        tracing::warn!(request_id, error = %malformed.source, "RejectMalformedRequest");
+        self.stats.record_request_received();
+        self.as_mut()
+            .project()
+            .rejected_request_responses
+            .push_back(Response {
+                request_id,
+                message: Err(ServerError::bad_request(format!(
+                    "the request could not be decoded: {}",
+                    malformed.source
+                ))),
+                extensions: ResponseExtensions::default(),
+                partial: false,
+            });
+    }
+
+    /// Responds to a request whose deadline had already passed when it was received, without
+    /// serving it.
+    fn reject_expired_request(mut self: Pin<&mut Self>, request: &Request<Req>) {
+        self.stats.record_deadline_expiration();
+        if request.oneway {
+            tracing::info!(request_id = request.id, "SkipExpiredOnewayRequest");
+            return;
+        }
+        tracing::info!(request_id = request.id, "RejectExpiredRequest");
+        self.as_mut()
+            .project()
+            .rejected_request_responses
+            .push_back(Response {
+                request_id: request.id,
+                message: Err(ServerError::deadline_exceeded(
+                    "the request deadline had already passed when the request was received".into(),
+                )),
+                extensions: ResponseExtensions::default(),
+                partial: false,
+            });
+    }
+
+    /// Tells the client that asked for it that the request was aborted, so it won't be sent a
+    /// response.
+    fn acknowledge_cancellation(mut self: Pin<&mut Self>, request_id: u64) {
+        tracing::info!(request_id, "AcknowledgeCancel");
+        self.as_mut()
+            .project()
+            .rejected_request_responses
+            .push_back(Response {
+                request_id,
+                message: Err(ServerError::canceled(
+                    "the request was aborted because the client canceled it".into(),
+                )),
+                extensions: ResponseExtensions::default(),
+                partial: false,
+            });
+    }
+
+    /// Writes the responses to malformed and expired requests and the acknowledgements of
+    /// cancellations to the transport. Returns ready once all are written.
+    fn poll_write_rejected_request_responses(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context,
+    ) -> Poll<Result<(), ChannelError<T::Error>>> {
+        while !self.rejected_request_responses.is_empty() {
+            ready!(self
+                .transport_pin_mut()
+                .poll_ready(cx)
+                .map_err(ChannelError::Ready)?);
+            let this = self.as_mut().project();
+            if let Some(response) = this.rejected_request_responses.pop_front() {
+                this.transport
+                    .start_send(response)
+                    .map_err(ChannelError::Write)?;
+                this.stats.record_response_sent();
+            }
+            self.as_mut().wake_reader();
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    /// Returns true iff the in-flight requests hold at least the most bytes allowed.
+    fn memory_exhausted(&self) -> bool {
+        matches!(self.config.max_in_flight_bytes, Some(max) if self.in_flight_requests.bytes() >= max)
+    }
+
+    /// Returns true iff the channel shouldn't read requests until some in flight complete or
+    /// some rejected are answered.
+    fn reading_paused(&self) -> bool {
+        (self.config.memory_limit_policy == MemoryLimitPolicy::Backpressure
+            && self.memory_exhausted())
+            || matches!(self.config.max_in_flight_requests, Some(max) if self.in_flight_requests.len() >= max)
+            || self.rejected_request_responses.len() >= self.config.pending_response_buffer
+    }
+
+    /// Wakes the task that stopped reading requests, if it can resume.
+    fn wake_reader(mut self: Pin<&mut Self>) {
+        if !self.reading_paused() {
+            if let Some(waker) = self.as_mut().project().read_waker.take() {
+                waker.wake();
+            }
+        }
+    }
+
+    /// Responds to a request that would hold more bytes than allowed, without serving it.
+    fn reject_oversized_request(mut self: Pin<&mut Self>, request: &Request<Req>, bytes: usize) {
+        if request.oneway {
+            tracing::warn!(request_id = request.id, bytes, "SkipOversizedOnewayRequest");
+            return;
+        }
+        tracing::warn!(request_id = request.id, bytes, "RejectOversizedRequest");
+        self.as_mut()
+            .project()
+            .rejected_request_responses
+            .push_back(Response {
+                request_id: request.id,
+                message: Err(ServerError::new(
+                    io::ErrorKind::OutOfMemory,
+                    "the server has no memory left for the request".into(),
+                )),
+                extensions: ResponseExtensions::default(),
+                partial: false,
+            });
+    }
+
+    /// Responds to a request whose deadline is outside the bounds accepted by the server, without
+    /// serving it.
+    fn reject_out_of_bounds_deadline(
+        mut self: Pin<&mut Self>,
+        request: &Request<Req>,
+        error: ServerError,
+    ) {
+        if request.oneway {
+            tracing::info!(request_id = request.id, "SkipOutOfBoundsDeadlineOnewayRequest");
+            return;
+        }
+        tracing::info!(request_id = request.id, "RejectOutOfBoundsDeadline: {}", error.detail);
+        self.as_mut()
+            .project()
+            .rejected_request_responses
+            .push_back(Response {
+                request_id: request.id,
+                message: Err(error),
+                extensions: ResponseExtensions::default(),
+                partial: false,
+            });
+    }
+
+    #[cfg(test)]
     fn start_request(
+        self: Pin<&mut Self>,
+        request: Request<Req>,
+    ) -> Result<TrackedRequest<Req>, AlreadyExistsError> {
+        self.start_sized_request(request, 0)
+    }
+
+    /// Starts tracking a request that holds `bytes` bytes.
+    fn start_sized_request(
         mut self: Pin<&mut Self>,
         mut request: Request<Req>,
+        bytes: usize,
     ) -> Result<TrackedRequest<Req>, AlreadyExistsError> {
-        let span = info_span!(
-            "RPC",
-            rpc.trace_id = %request.context.trace_id(),
-            rpc.deadline = %humantime::format_rfc3339(request.context.deadline),
-            otel.kind = "server",
-            otel.name = tracing::field::Empty,
-        );
-        span.set_context(&request.context);
-        request.context.trace_context = trace::Context::try_from(&span).unwrap_or_else(|_| {
-            tracing::trace!(
-                "OpenTelemetry subscriber not installed; making unsampled \
-                            child context."
+        let span = if (self.untraced)(&request.message) {
+            request.context.trace_context = request.context.trace_context.new_child();
+            Span::none()
+        } else {
+            let span = info_span!(
+                "RPC",
+                rpc.system = "tarpc",
+                rpc.service = tracing::field::Empty,
+                rpc.method = tracing::field::Empty,
+                rpc.trace_id = %request.context.trace_id(),
+                rpc.deadline = %humantime::format_rfc3339(util::system_time(request.context.deadline)),
+                network.peer.address = self.peer_addr.map(|addr| tracing::field::display(addr.ip())),
+                network.peer.port = self.peer_addr.map(|addr| addr.port()),
+                otel.kind = "server",
+                otel.name = tracing::field::Empty,
+                otel.status_code = tracing::field::Empty,
+                otel.status_message = tracing::field::Empty,
             );
-            request.context.trace_context.new_child()
-        });
+            span.set_context(&request.context);
+            request.context.trace_context = trace::Context::try_from(&span).unwrap_or_else(|_| {
+                tracing::trace!(
+                    "OpenTelemetry subscriber not installed; making unsampled \
+                            child context."
+                );
+                request.context.trace_context.new_child()
+            });
+            span
+        };
         let entered = span.enter();
         tracing::info!("ReceiveRequest");
-        let start = self.in_flight_requests_mut().start_request(
+        let start = self.in_flight_requests_mut().start_sized_request(
             request.id,
             request.context.deadline,
+            request.oneway,
             span.clone(),
+            bytes,
         );
         match start {
             Ok(abort_registration) => {
@@ -514,6 +1038,8 @@ where
         let (responses_tx, responses) = mpsc::channel(self.config().pending_response_buffer);
 
         Requests {
+            flusher: Flusher::new(self.config().flush_policy),
+            yield_budget: YieldBudget::new(self.config().frames_per_yield),
             channel: self,
             pending_responses: responses,
             responses_tx,
@@ -552,7 +1078,7 @@ where
     ///         MyInt(2));
     /// }
     /// ```
-    fn execute<S>(self, serve: S) -> impl Stream<Item = impl Future<Output = ()>>
+    fn execute<S>(self, serve: S) -> impl Stream<Item = RequestExecution<impl Future<Output = ()>>>
     where
         Self: Sized,
         S: Serve<Req = Self::Req, Resp = Self::Resp> + Clone,
@@ -609,19 +1135,71 @@ where
             let expiration_status = match self.in_flight_requests_mut().poll_expired(cx) {
                 // No need to send a response, since the client wouldn't be waiting for one
                 // anymore.
-                Poll::Ready(Some(_)) => Ready,
+                Poll::Ready(Some(_)) => {
+                    self.stats.record_deadline_expiration();
+                    Ready
+                }
                 Poll::Ready(None) => Closed,
                 Poll::Pending => Pending,
             };
 
-            let request_status = match self
-                .transport_pin_mut()
-                .poll_next(cx)
-                .map_err(|e| ChannelError::Read(Arc::new(e)))?
-            {
-                Poll::Ready(Some(message)) => match message {
-                    ClientMessage::Request(request) => {
-                        match self.as_mut().start_request(request) {
+            // Requests aren't read while the channel is at its limits, so that they wait in the
+            // transport rather than in memory; completing or answering requests wakes the channel.
+            if self.reading_paused() {
+                *self.as_mut().project().read_waker = Some(cx.waker().clone());
+                match cancellation_status.combine(expiration_status) {
+                    Ready => continue,
+                    Closed | Pending => return Poll::Pending,
+                }
+            }
+
+            let bytes_read = self.stats.bytes_read();
+            let request_status = match self.transport_pin_mut().poll_next(cx) {
+                Poll::Ready(Some(Err(e))) => match MalformedMessage::find(&e) {
+                    Some(malformed)
+                        if self.config.malformed_message_policy
+                            != MalformedMessagePolicy::Close =>
+                    {
+                        self.as_mut().reject_malformed_message(malformed);
+                        Ready
+                    }
+                    _ => return Poll::Ready(Some(Err(ChannelError::Read(Arc::new(e))))),
+                },
+                Poll::Ready(Some(Ok(message))) => match message {
+                    ClientMessage::Request(mut request) => {
+                        self.stats.record_request_received();
+                        if request.context.has_expired() {
+                            self.as_mut().reject_expired_request(&request);
+                            continue;
+                        }
+                        match self.config.bound_deadline(request.context.deadline, util::now()) {
+                            Ok(deadline) if deadline != request.context.deadline => {
+                                tracing::info!(
+                                    request_id = request.id,
+                                    requested_deadline = %humantime::format_rfc3339(
+                                        util::system_time(request.context.deadline)
+                                    ),
+                                    "ClampDeadline"
+                                );
+                                request.context.deadline = deadline;
+                            }
+                            Ok(_) => {}
+                            Err(error) => {
+                                self.as_mut().reject_out_of_bounds_deadline(&request, error);
+                                continue;
+                            }
+                        }
+                        let bytes = usize::try_from(self.stats.bytes_read() - bytes_read)
+                            .unwrap_or(usize::MAX)
+                            .max(mem::size_of::<Request<Req>>());
+                        if self.config.memory_limit_policy == MemoryLimitPolicy::Reject
+                            && matches!(self.config.max_in_flight_bytes,
+                                Some(max) if self.in_flight_requests.bytes().saturating_add(bytes) > max)
+                        {
+                            self.as_mut().reject_oversized_request(&request, bytes);
+                            continue;
+                        }
+                        match self.as_mut().start_sized_request(request, bytes) {
                             Ok(request) => return Poll::Ready(Some(Ok(request))),
                             Err(AlreadyExistsError) => {
                                 // Instead of closing the channel if a duplicate request is sent,
@@ -635,8 +1213,18 @@ where
                     ClientMessage::Cancel {
                         trace_context,
                         request_id,
+                        reason,
+                        ack,
                     } => {
-                        if !self.in_flight_requests_mut().cancel_request(request_id) {
+                        if self
+                            .in_flight_requests_mut()
+                            .cancel_request(request_id, reason)
+                        {
+                            self.stats.record_cancellation();
+                            if ack {
+                                self.as_mut().acknowledge_cancellation(request_id);
+                            }
+                        } else {
                             tracing::trace!(
                                 rpc.trace_id = %trace_context.trace_id,
                                 "Received cancellation, but response handler is already complete.",
@@ -644,6 +1232,26 @@ where
                         }
                         Ready
                     }
+                    ClientMessage::CancelBatch {
+                        request_ids,
+                        reason,
+                    } => {
+                        for request_id in request_ids {
+                            if self
+                                .in_flight_requests_mut()
+                                .cancel_request(request_id, reason)
+                            {
+                                self.stats.record_cancellation();
+                            } else {
+                                tracing::trace!(
+                                    request_id,
+                                    "Received cancellation, but response handler is already \
+                                     complete.",
+                                );
+                            }
+                        }
+                        Ready
+                    }
                 },
                 Poll::Ready(None) => Closed,
                 Poll::Pending => Pending,
@@ -675,7 +1283,8 @@ where
 {
     type Error = ChannelError<T::Error>;
 
-    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        ready!(self.as_mut().poll_write_rejected_request_responses(cx)?);
         self.project()
             .transport
             .poll_ready(cx)
@@ -683,24 +1292,42 @@ where
     }
 
     fn start_send(mut self: Pin<&mut Self>, response: Response<Resp>) -> Result<(), Self::Error> {
-        if let Some(span) = self
-            .in_flight_requests_mut()
-            .remove_request(response.request_id)
-        {
+        // The responses to requests of clients expecting none are dropped, as if they were sent.
+        let oneway = self.in_flight_requests_mut().is_oneway(response.request_id);
+        // A request stays in flight until its last response is sent.
+        let span = if response.partial {
+            self.in_flight_requests_mut()
+                .get_span(response.request_id)
+                .cloned()
+        } else {
+            self.in_flight_requests_mut()
+                .remove_request(response.request_id)
+        };
+        if !response.partial {
+            self.as_mut().wake_reader();
+        }
+        if let Some(span) = span {
             let _entered = span.enter();
+            if oneway {
+                tracing::info!("DropOnewayResponse");
+                return Ok(());
+            }
             tracing::info!("SendResponse");
-            self.project()
-                .transport
+            let this = self.project();
+            this.transport
                 .start_send(response)
-                .map_err(ChannelError::Write)
+                .map_err(ChannelError::Write)?;
+            this.stats.record_response_sent();
+            Ok(())
         } else {
             // If the request isn't tracked anymore, there's no need to send the response.
             Ok(())
         }
     }
 
-    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
         tracing::trace!("poll_flush");
+        ready!(self.as_mut().poll_write_rejected_request_responses(cx)?);
         self.project()
             .transport
             .poll_flush(cx)
@@ -755,6 +1382,10 @@ where
     pending_responses: mpsc::Receiver<Response<C::Resp>>,
     /// Handed out to request handlers to fan in responses.
     responses_tx: mpsc::Sender<Response<C::Resp>>,
+    /// Decides when to flush the responses written to the channel.
+    flusher: Flusher,
+    /// Makes the stream yield after reading and writing `frames_per_yield` messages.
+    yield_budget: YieldBudget,
 }
 
 impl<C> Requests<C>
@@ -801,6 +1432,7 @@ where
                     span,
                     response_guard,
                     response_tx: self.responses_tx.clone(),
+                    slow_request_policy: SlowRequestPolicy::from_config(self.channel.config()),
                 }
             },
         )
@@ -811,26 +1443,34 @@ where
         cx: &mut Context<'_>,
         read_half_closed: bool,
     ) -> Poll<Option<Result<(), C::Error>>> {
+        if self.flusher.is_due() {
+            ready!(self.poll_flush(cx)?);
+        }
         match self.as_mut().poll_next_response(cx)? {
             Poll::Ready(Some(response)) => {
                 // A Ready result from poll_next_response means the Channel is ready to be written
                 // to. Therefore, we can call start_send without worry of a full buffer.
                 self.channel_pin_mut().start_send(response)?;
+                self.as_mut().project().flusher.written();
                 Poll::Ready(Some(Ok(())))
             }
             Poll::Ready(None) => {
                 // Shutdown can't be done before we finish pumping out remaining responses.
-                ready!(self.channel_pin_mut().poll_flush(cx)?);
+                ready!(self.poll_flush(cx)?);
                 Poll::Ready(None)
             }
             Poll::Pending => {
-                // No more requests to process, so flush any requests buffered in the transport.
-                ready!(self.channel_pin_mut().poll_flush(cx)?);
+                let closing = read_half_closed && self.channel.in_flight_requests() == 0;
+                // No more requests to process, so flush any requests buffered in the transport,
+                // unless the flush policy waits for more and there may be more.
+                if closing || self.as_mut().project().flusher.poll_idle(cx).is_ready() {
+                    ready!(self.poll_flush(cx)?);
+                }
 
                 // Being here means there are no staged requests and all written responses are
-                // fully flushed. So, if the read half is closed and there are no in-flight
-                // requests, then we can close the write half.
-                if read_half_closed && self.channel.in_flight_requests() == 0 {
+                // flushed, unless the flush policy waits for more. So, if the read half is closed
+                // and there are no in-flight requests, then we can close the write half.
+                if closing {
                     Poll::Ready(None)
                 } else {
                     Poll::Pending
@@ -839,6 +1479,13 @@ where
         }
     }
 
+    /// Flushes the responses written to the channel.
+    fn poll_flush(self: &mut Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), C::Error>> {
+        ready!(self.channel_pin_mut().poll_flush(cx)?);
+        self.as_mut().project().flusher.flushed();
+        Poll::Ready(Ok(()))
+    }
+
     /// Yields a response ready to be written to the Channel sink.
     ///
     /// Note that a response will only be yielded if the Channel is *ready* to be written to (i.e.
@@ -865,7 +1512,7 @@ where
         cx: &mut Context<'_>,
     ) -> Poll<Option<Result<(), C::Error>>> {
         while self.channel_pin_mut().poll_ready(cx)?.is_pending() {
-            ready!(self.channel_pin_mut().poll_flush(cx)?);
+            ready!(self.poll_flush(cx)?);
         }
         Poll::Ready(Some(Ok(())))
     }
@@ -898,7 +1545,10 @@ where
     ///     assert_eq!(client.call(context::current(), "AddOne", 1).await.unwrap(), 2);
     /// }
     /// ```
-    pub fn execute<S>(self, serve: S) -> impl Stream<Item = impl Future<Output = ()>>
+    pub fn execute<S>(
+        self,
+        serve: S,
+    ) -> impl Stream<Item = RequestExecution<impl Future<Output = ()>>>
     where
         S: Serve<Req = C::Req, Resp = C::Resp> + Clone,
     {
@@ -911,7 +1561,32 @@ where
         .filter_map(|result| async move { result.ok() })
         .map(move |request| {
             let serve = serve.clone();
-            request.execute(serve)
+            RequestExecution::new(serve.method(&request.get().message), request.execute(serve))
+        })
+    }
+
+    /// Like [`execute`](Requests::execute), but executes requests with
+    /// [`InFlightRequest::execute_body`], streaming the [body](body::Body) each responds with.
+    pub fn execute_body<S>(
+        self,
+        serve: S,
+    ) -> impl Stream<Item = RequestExecution<impl Future<Output = ()>>>
+    where
+        S: Serve<Req = C::Req, Resp = body::Body<C::Resp>> + Clone,
+    {
+        self.take_while(|result| {
+            if let Err(e) = result {
+                tracing::warn!("Requests stream errored out: {}", e);
+            }
+            futures::future::ready(result.is_ok())
+        })
+        .filter_map(|result| async move { result.ok() })
+        .map(move |request| {
+            let serve = serve.clone();
+            RequestExecution::new(
+                serve.method(&request.get().message),
+                request.execute_body(serve),
+            )
         })
     }
 }
@@ -953,6 +1628,7 @@ pub struct InFlightRequest<Req, Res> {
     response_guard: ResponseGuard,
     span: Span,
     response_tx: mpsc::Sender<Response<Res>>,
+    slow_request_policy: Option<SlowRequestPolicy>,
 }
 
 impl<Req, Res> InFlightRequest<Req, Res> {
@@ -1008,6 +1684,90 @@ impl<Req, Res> InFlightRequest<Req, Res> {
     pub async fn execute<S>(self, serve: S)
     where
         S: Serve<Req = Req, Resp = Res>,
+    {
+        self.execute_then(
+            serve,
+            |response_tx, request_id, message, extensions| async move {
+                let response = Response {
+                    request_id,
+                    message,
+                    extensions,
+                    partial: false,
+                };
+                let _ = response_tx.send(response).await;
+            },
+        )
+        .await
+    }
+
+    /// Returns a [future](Future) that executes the request using a [service function](Serve)
+    /// that responds with a [`Body`](body::Body), streaming the body back to the [Channel] that
+    /// yielded this request one response at a time.
+    ///
+    /// Each response of the body is sent as soon as the channel has room to buffer it, so a large
+    /// body is never held in memory in full. The client receives all but the last response
+    /// marked [partial](Response::partial), and must call the request with
+    /// [`call_body`](crate::client::Channel::call_body) to receive them. Like
+    /// [`execute`](InFlightRequest::execute), the returned future stops executing if the request
+    /// is canceled or its deadline is reached, even while the body is being streamed.
+    pub async fn execute_body<S>(self, serve: S)
+    where
+        S: Serve<Req = Req, Resp = body::Body<Res>>,
+    {
+        self.execute_then(
+            serve,
+            |response_tx, request_id, message, extensions| async move {
+                let (mut body, mut last) = match message {
+                    Ok(mut body) => {
+                        let first = body.next().await.unwrap_or_else(|| {
+                            Err(ServerError::new(
+                                io::ErrorKind::UnexpectedEof,
+                                "the response body was empty.".into(),
+                            ))
+                        });
+                        (Some(body), first)
+                    }
+                    Err(e) => (None, Err(e)),
+                };
+                while let (Ok(_), Some(body)) = (&last, &mut body) {
+                    let Some(next) = body.next().await else {
+                        break;
+                    };
+                    let response = Response {
+                        request_id,
+                        message: last,
+                        extensions: ResponseExtensions::default(),
+                        partial: true,
+                    };
+                    if response_tx.send(response).await.is_err() {
+                        return;
+                    }
+                    last = next;
+                }
+                let response = Response {
+                    request_id,
+                    message: last,
+                    extensions,
+                    partial: false,
+                };
+                let _ = response_tx.send(response).await;
+            },
+        )
+        .await
+    }
+
+    /// Executes the request with `serve`, then passes its output to `respond` to send back to
+    /// the channel.
+    async fn execute_then<S, F, Fut>(self, serve: S, respond: F)
+    where
+        S: Serve<Req = Req>,
+        F: FnOnce(
+            mpsc::Sender<Response<Res>>,
+            u64,
+            Result<S::Resp, ServerError>,
+            ResponseExtensions,
+        ) -> Fut,
+        Fut: Future<Output = ()>,
     {
         let Self {
             response_tx,
@@ -1019,25 +1779,75 @@ impl<Req, Res> InFlightRequest<Req, Res> {
                     context,
                     message,
                     id: request_id,
+                    ..
                 },
+            slow_request_policy,
         } = self;
         let method = serve.method(&message);
-        span.record("otel.name", method.unwrap_or(""));
-        let _ = Abortable::new(
+        if let Some(method) = method {
+            let (service, method) = util::rpc_service_and_method(method);
+            span.record("rpc.service", service);
+            span.record("rpc.method", method);
+            span.record("otel.name", util::rpc_span_name(service, method));
+        }
+        let timer = RequestTimer::start(Role::Server, method.unwrap_or(""));
+        let trace_id = *context.trace_id();
+        let span_for_slow_request = slow_request_policy.as_ref().map(|_| span.clone());
+        let processing = Abortable::new(
             async move {
-                let message = serve.serve(context, message).await;
+                #[cfg(not(feature = "opentelemetry"))]
+                let current = context::Inherited::of(&context);
+                let serving = Scoped::new(
+                    &response_extensions::CURRENT,
+                    ResponseExtensions::default(),
+                    serve.serve(context, message),
+                );
+                let serving = Scoped::new(&context::REQUEST_ID, request_id, serving)
+                    .map(|(output, _)| output);
+                // Without OpenTelemetry, the span doesn't carry the context to the handler's
+                // requests.
+                #[cfg(not(feature = "opentelemetry"))]
+                let serving = Scoped::new(&context::CURRENT, current, serving)
+                    .map(|(output, _)| output);
+                let (message, extensions) = serving.await;
+                timer.finish(message.is_err());
+                if let Err(error) = &message {
+                    let span = Span::current();
+                    span.record("otel.status_code", "ERROR");
+                    span.record("otel.status_message", error.detail.as_str());
+                }
                 tracing::info!("CompleteRequest");
-                let response = Response {
-                    request_id,
-                    message,
-                };
-                let _ = response_tx.send(response).await;
+                respond(response_tx, request_id, message, extensions).await;
                 tracing::info!("BufferResponse");
             },
             abort_registration,
         )
-        .instrument(span)
-        .await;
+        .instrument(span);
+        match (slow_request_policy, span_for_slow_request) {
+            (Some(policy), Some(span)) => {
+                // Report the request when it crosses the threshold, rather than when it
+                // completes, so that requests that never complete are reported, too.
+                let start = tokio::time::Instant::now();
+                let slow = tokio::time::sleep(policy.threshold);
+                futures::pin_mut!(processing, slow);
+                if let future::Either::Right(((), processing)) =
+                    future::select(processing, slow).await
+                {
+                    span.in_scope(|| {
+                        policy.report(SlowRequest {
+                            request_id,
+                            method,
+                            trace_id,
+                            elapsed: start.elapsed(),
+                        })
+                    });
+                    let _ = processing.await;
+                }
+            }
+            _ => {
+                let _ = processing.await;
+            }
+        }
         // Request processing has completed, meaning either the channel canceled the request or
         // a request was sent back to the channel. Either way, the channel will clean up the
         // request data, so the request does not need to be canceled.
@@ -1060,6 +1870,7 @@ where
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
         loop {
+            ready!(self.as_mut().project().yield_budget.poll_proceed(cx));
             let read = self.as_mut().pump_read(cx).map_err(|e| {
                 tracing::trace!("read: {}", print_err(&e));
                 e
@@ -1087,6 +1898,7 @@ where
                         read.is_pending(),
                         write.is_pending()
                     );
+                    self.as_mut().project().yield_budget.reset();
                     return Poll::Pending;
                 }
             }
@@ -1097,13 +1909,14 @@ where
 #[cfg(test)]
 mod tests {
     use super::{
-        in_flight_requests::AlreadyExistsError, serve, AfterRequest, BaseChannel, BeforeRequest,
-        Channel, Config, Requests, Serve,
+        in_flight_requests::AlreadyExistsError, response_extensions, serve, AfterRequest,
+        BaseChannel, BeforeRequest, Channel, Config, DeadlinePolicy, MemoryLimitPolicy, Requests,
+        Serve, SlowRequestHook,
     };
     use crate::{
         context, trace,
         transport::channel::{self, UnboundedChannel},
-        ClientMessage, Request, Response, ServerError,
+        CancellationReason, ClientMessage, Request, Response, ServerError, ServerErrorReason,
     };
     use assert_matches::assert_matches;
     use futures::{
@@ -1111,12 +1924,13 @@ mod tests {
         prelude::*,
         Future,
     };
-    use futures_test::task::noop_context;
+    use futures_test::task::{new_count_waker, noop_context};
     use std::{
-        io,
+        io, mem,
         pin::Pin,
-        task::Poll,
-        time::{Duration, Instant, SystemTime},
+        sync::{Arc, Mutex},
+        task::{Context, Poll},
+        time::{Duration, Instant},
     };
 
     fn test_channel<Req, Resp>() -> (
@@ -1160,6 +1974,7 @@ mod tests {
         // Add 1 because capacity 0 is not supported (but is supported by transport::channel::bounded).
         let config = Config {
             pending_response_buffer: capacity + 1,
+            ..Config::default()
         };
         (Box::pin(BaseChannel::new(config, rx).requests()), tx)
     }
@@ -1169,6 +1984,7 @@ mod tests {
             context: context::current(),
             id: 0,
             message: req,
+            oneway: false,
         })
     }
 
@@ -1184,9 +2000,28 @@ mod tests {
         assert_matches!(serve.serve(context::current(), 7).await, Ok(7));
     }
 
+    #[tokio::test]
+    async fn serve_before_attaches_extensions() -> anyhow::Result<()> {
+        struct Principal(&'static str);
+
+        let serve = serve(|ctx: context::Context, i: i32| async move {
+            assert_eq!(
+                ctx.extensions.get::<Principal>().map(|p| p.0),
+                Some("alice")
+            );
+            Ok(i)
+        })
+        .before(|ctx: &mut context::Context, _: &i32| {
+            ctx.extensions.insert(Principal("alice"));
+            future::ready(Ok(()))
+        });
+        assert_eq!(serve.serve(context::current(), 7).await?, 7);
+        Ok(())
+    }
+
     #[tokio::test]
     async fn serve_before_mutates_context() -> anyhow::Result<()> {
-        struct SetDeadline(SystemTime);
+        struct SetDeadline(Instant);
         impl<Req> BeforeRequest<Req> for SetDeadline {
             async fn before(
                 &mut self,
@@ -1198,8 +2033,8 @@ mod tests {
             }
         }
 
-        let some_time = SystemTime::UNIX_EPOCH + Duration::from_secs(37);
-        let some_other_time = SystemTime::UNIX_EPOCH + Duration::from_secs(83);
+        let some_time = Instant::now() + Duration::from_secs(37);
+        let some_other_time = Instant::now() + Duration::from_secs(83);
 
         let serve = serve(move |ctx: context::Context, i| async move {
             assert_eq!(ctx.deadline, some_time);
@@ -1271,18 +2106,44 @@ mod tests {
                 id: 0,
                 context: context::current(),
                 message: (),
+                oneway: false,
             })
             .unwrap();
         assert_matches!(
             channel.as_mut().start_request(Request {
                 id: 0,
                 context: context::current(),
-                message: ()
+                message: (),
+                oneway: false,
             }),
             Err(AlreadyExistsError)
         );
     }
 
+    #[tokio::test]
+    async fn base_channel_skips_spans_of_untraced_requests() {
+        let (_tx, rx) = crate::transport::channel::unbounded();
+        let mut channel = Box::pin(
+            BaseChannel::<u8, (), _>::new(Config::default(), rx).with_untraced(|req| *req == 1),
+        );
+        let ctx = context::current();
+        tracing::subscriber::with_default(tracing_subscriber::registry(), || {
+            for (id, untraced) in [(0, false), (1, true)] {
+                let request = channel
+                    .as_mut()
+                    .start_request(Request {
+                        id,
+                        context: ctx.clone(),
+                        message: id as u8,
+                        oneway: false,
+                    })
+                    .unwrap();
+                assert_eq!(request.span.is_none(), untraced);
+                assert_eq!(request.request.context.trace_id(), ctx.trace_id());
+            }
+        });
+    }
+
     #[tokio::test]
     async fn base_channel_poll_next_aborts_multiple_requests() {
         let (mut channel, _tx) = test_channel::<(), ()>();
@@ -1294,6 +2155,7 @@ mod tests {
                 id: 0,
                 context: context::current(),
                 message: (),
+                oneway: false,
             })
             .unwrap();
         let req1 = channel
@@ -1302,6 +2164,7 @@ mod tests {
                 id: 1,
                 context: context::current(),
                 message: (),
+                oneway: false,
             })
             .unwrap();
         tokio::time::advance(std::time::Duration::from_secs(1000)).await;
@@ -1325,12 +2188,15 @@ mod tests {
                 id: 0,
                 context: context::current(),
                 message: (),
+                oneway: false,
             })
             .unwrap();
 
         tx.send(ClientMessage::Cancel {
             trace_context: trace::Context::default(),
             request_id: 0,
+            reason: CancellationReason::Dropped,
+            ack: false,
         })
         .await
         .unwrap();
@@ -1344,49 +2210,42 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn base_channel_with_closed_transport_and_in_flight_request_returns_pending() {
-        let (mut channel, tx) = test_channel::<(), ()>();
+    async fn base_channel_cancels_batches_of_requests() {
+        let (mut channel, mut tx) = test_channel::<(), ()>();
 
         tokio::time::pause();
-        let _abort_registration = channel
-            .as_mut()
-            .start_request(Request {
-                id: 0,
-                context: context::current(),
-                message: (),
-            })
-            .unwrap();
+        let reqs = [0, 1, 2].map(|id| {
+            channel
+                .as_mut()
+                .start_request(Request {
+                    id,
+                    context: context::current(),
+                    message: (),
+                    oneway: false,
+                })
+                .unwrap()
+        });
 
-        drop(tx);
-        assert_matches!(
-            channel.as_mut().poll_next(&mut noop_context()),
-            Poll::Pending
-        );
-    }
+        tx.send(ClientMessage::CancelBatch {
+            request_ids: vec![0, 2, 7],
+            reason: CancellationReason::Dropped,
+        })
+        .await
+        .unwrap();
 
-    #[tokio::test]
-    async fn base_channel_with_closed_transport_and_no_in_flight_requests_returns_closed() {
-        let (mut channel, tx) = test_channel::<(), ()>();
-        drop(tx);
         assert_matches!(
             channel.as_mut().poll_next(&mut noop_context()),
-            Poll::Ready(None)
+            Poll::Pending
         );
-    }
+        assert_eq!(channel.in_flight_requests.len(), 1);
 
-    #[tokio::test]
-    async fn base_channel_poll_next_yields_request() {
-        let (mut channel, mut tx) = test_channel::<(), ()>();
-        tx.send(fake_request(())).await.unwrap();
-
-        assert_matches!(
-            channel.as_mut().poll_next(&mut noop_context()),
-            Poll::Ready(Some(Ok(_)))
-        );
+        let [req0, _req1, req2] = reqs;
+        assert_matches!(test_abortable(req0.abort_registration).await, Err(Aborted));
+        assert_matches!(test_abortable(req2.abort_registration).await, Err(Aborted));
     }
 
     #[tokio::test]
-    async fn base_channel_poll_next_aborts_request_and_yields_request() {
+    async fn base_channel_acknowledges_cancellations_that_ask() {
         let (mut channel, mut tx) = test_channel::<(), ()>();
 
         tokio::time::pause();
@@ -1396,14 +2255,152 @@ mod tests {
                 id: 0,
                 context: context::current(),
                 message: (),
+                oneway: false,
             })
             .unwrap();
-        tokio::time::advance(std::time::Duration::from_secs(1000)).await;
 
-        tx.send(fake_request(())).await.unwrap();
-
-        assert_matches!(
-            channel.as_mut().poll_next(&mut noop_context()),
+        for request_id in [0, 1] {
+            tx.send(ClientMessage::Cancel {
+                trace_context: trace::Context::default(),
+                request_id,
+                reason: CancellationReason::Explicit,
+                ack: true,
+            })
+            .await
+            .unwrap();
+        }
+
+        assert_matches!(
+            channel.as_mut().poll_next(&mut noop_context()),
+            Poll::Pending
+        );
+        assert_matches!(test_abortable(req.abort_registration).await, Err(Aborted));
+        assert_matches!(
+            channel.as_mut().poll_flush(&mut noop_context()),
+            Poll::Ready(Ok(()))
+        );
+        // Only the request that was in flight is acknowledged.
+        let response = tx.next().await.unwrap().unwrap();
+        assert_eq!(response.request_id, 0);
+        assert_matches!(
+            response.message,
+            Err(ServerError {
+                reason: Some(ServerErrorReason::Canceled),
+                ..
+            })
+        );
+        assert!(tx.next().now_or_never().is_none());
+    }
+
+    #[tokio::test]
+    async fn base_channel_with_closed_transport_and_in_flight_request_returns_pending() {
+        let (mut channel, tx) = test_channel::<(), ()>();
+
+        tokio::time::pause();
+        let _abort_registration = channel
+            .as_mut()
+            .start_request(Request {
+                id: 0,
+                context: context::current(),
+                message: (),
+                oneway: false,
+            })
+            .unwrap();
+
+        drop(tx);
+        assert_matches!(
+            channel.as_mut().poll_next(&mut noop_context()),
+            Poll::Pending
+        );
+    }
+
+    #[tokio::test]
+    async fn base_channel_with_closed_transport_and_no_in_flight_requests_returns_closed() {
+        let (mut channel, tx) = test_channel::<(), ()>();
+        drop(tx);
+        assert_matches!(
+            channel.as_mut().poll_next(&mut noop_context()),
+            Poll::Ready(None)
+        );
+    }
+
+    #[tokio::test]
+    async fn base_channel_poll_next_yields_request() {
+        let (mut channel, mut tx) = test_channel::<(), ()>();
+        tx.send(fake_request(())).await.unwrap();
+
+        assert_matches!(
+            channel.as_mut().poll_next(&mut noop_context()),
+            Poll::Ready(Some(Ok(_)))
+        );
+    }
+
+    #[tokio::test]
+    async fn base_channel_counts_traffic() {
+        let (mut channel, mut tx) = test_channel::<(), ()>();
+        let stats = channel.stats().clone();
+
+        tx.send(fake_request(())).await.unwrap();
+        assert_matches!(
+            channel.as_mut().poll_next(&mut noop_context()),
+            Poll::Ready(Some(Ok(_)))
+        );
+        tx.send(ClientMessage::Cancel {
+            trace_context: trace::Context::default(),
+            request_id: 0,
+            reason: CancellationReason::Dropped,
+            ack: false,
+        })
+        .await
+        .unwrap();
+        tx.send(ClientMessage::Request(Request {
+            context: context::current(),
+            id: 1,
+            message: (),
+            oneway: false,
+        }))
+        .await
+        .unwrap();
+        assert_matches!(
+            channel.as_mut().poll_next(&mut noop_context()),
+            Poll::Ready(Some(Ok(_)))
+        );
+        channel
+            .as_mut()
+            .start_send(Response {
+                request_id: 1,
+                message: Ok(()),
+                extensions: Default::default(),
+                partial: false,
+            })
+            .unwrap();
+
+        assert_eq!(stats.requests_received(), 2);
+        assert_eq!(stats.cancellations(), 1);
+        assert_eq!(stats.responses_sent(), 1);
+        assert_eq!(stats.deadline_expirations(), 0);
+    }
+
+    #[tokio::test]
+    async fn base_channel_poll_next_aborts_request_and_yields_request() {
+        let (mut channel, mut tx) = test_channel::<(), ()>();
+
+        tokio::time::pause();
+        let req = channel
+            .as_mut()
+            .start_request(Request {
+                id: 0,
+                context: context::current(),
+                message: (),
+                oneway: false,
+            })
+            .unwrap();
+        tokio::time::advance(std::time::Duration::from_secs(1000)).await;
+
+        tx.send(fake_request(())).await.unwrap();
+
+        assert_matches!(
+            channel.as_mut().poll_next(&mut noop_context()),
             Poll::Ready(Some(Ok(_)))
         );
         assert_matches!(test_abortable(req.abort_registration).await, Err(Aborted));
@@ -1419,6 +2416,7 @@ mod tests {
                 id: 0,
                 context: context::current(),
                 message: (),
+                oneway: false,
             })
             .unwrap();
         assert_eq!(channel.in_flight_requests(), 1);
@@ -1427,11 +2425,67 @@ mod tests {
             .start_send(Response {
                 request_id: 0,
                 message: Ok(()),
+                extensions: Default::default(),
+                partial: false,
+            })
+            .unwrap();
+        assert_eq!(channel.in_flight_requests(), 0);
+    }
+
+    #[tokio::test]
+    async fn base_channel_start_send_keeps_request_in_flight_until_last_response() {
+        let (mut channel, _tx) = test_channel::<(), ()>();
+
+        channel
+            .as_mut()
+            .start_request(Request {
+                id: 0,
+                context: context::current(),
+                message: (),
+                oneway: false,
             })
             .unwrap();
+        for partial in [true, false] {
+            assert_eq!(channel.in_flight_requests(), 1);
+            channel
+                .as_mut()
+                .start_send(Response {
+                    request_id: 0,
+                    message: Ok(()),
+                    extensions: Default::default(),
+                    partial,
+                })
+                .unwrap();
+        }
         assert_eq!(channel.in_flight_requests(), 0);
     }
 
+    #[tokio::test]
+    async fn base_channel_start_send_drops_oneway_responses() {
+        let (mut channel, _tx) = test_channel::<(), ()>();
+
+        channel
+            .as_mut()
+            .start_request(Request {
+                id: 0,
+                context: context::current(),
+                message: (),
+                oneway: true,
+            })
+            .unwrap();
+        channel
+            .as_mut()
+            .start_send(Response {
+                request_id: 0,
+                message: Ok(()),
+                extensions: Default::default(),
+                partial: false,
+            })
+            .unwrap();
+        assert_eq!(channel.in_flight_requests(), 0);
+        assert_eq!(channel.stats().responses_sent(), 0);
+    }
+
     #[tokio::test]
     async fn in_flight_request_drop_cancels_request() {
         let (mut requests, mut tx) = test_requests::<(), ()>();
@@ -1470,6 +2524,494 @@ mod tests {
             .is_pending());
     }
 
+    #[test]
+    fn config_bound_deadline() {
+        let now = Instant::now() + Duration::from_secs(1000);
+        let mut config = Config {
+            max_deadline: Some(Duration::from_secs(10)),
+            min_deadline: Some(Duration::ZERO),
+            ..Config::default()
+        };
+
+        let ok = now + Duration::from_secs(5);
+        assert_matches!(config.bound_deadline(ok, now), Ok(d) if d == ok);
+        assert_matches!(
+            config.bound_deadline(now + Duration::from_secs(60), now),
+            Ok(d) if d == now + Duration::from_secs(10)
+        );
+        assert_matches!(
+            config.bound_deadline(now - Duration::from_secs(60), now),
+            Ok(d) if d == now
+        );
+
+        config.deadline_policy = DeadlinePolicy::Reject;
+        assert_matches!(config.bound_deadline(ok, now), Ok(d) if d == ok);
+        assert_matches!(
+            config.bound_deadline(now + Duration::from_secs(60), now),
+            Err(ServerError {
+                kind: io::ErrorKind::InvalidInput,
+                ..
+            })
+        );
+        assert_matches!(
+            config.bound_deadline(now - Duration::from_secs(60), now),
+            Err(ServerError { kind: io::ErrorKind::InvalidInput, ref detail, .. })
+                if detail.contains("already passed")
+        );
+    }
+
+    #[tokio::test]
+    async fn base_channel_rejects_expired_requests() {
+        let (mut channel, mut tx) = test_channel::<(), ()>();
+        for (id, oneway) in [(0, false), (1, true)] {
+            tx.send(ClientMessage::Request(Request {
+                context: context::current().with_deadline(Instant::now()),
+                id,
+                message: (),
+                oneway,
+            }))
+            .await
+            .unwrap();
+        }
+
+        assert!(channel.as_mut().poll_next(&mut noop_context()).is_pending());
+        assert_eq!(channel.in_flight_requests(), 0);
+        assert_matches!(
+            channel.as_mut().poll_flush(&mut noop_context()),
+            Poll::Ready(Ok(()))
+        );
+        let response = tx.next().await.unwrap().unwrap();
+        assert_eq!(response.request_id, 0);
+        assert_matches!(
+            response.message,
+            Err(ServerError {
+                kind: io::ErrorKind::TimedOut,
+                ..
+            })
+        );
+        assert_eq!(channel.stats().deadline_expirations(), 2);
+    }
+
+    #[tokio::test]
+    async fn base_channel_stops_reading_requests_at_max_in_flight() {
+        let (mut tx, rx) =
+            crate::transport::channel::unbounded::<Response<()>, ClientMessage<()>>();
+        let config = Config {
+            max_in_flight_requests: Some(1),
+            ..Config::default()
+        };
+        let mut channel = Box::pin(BaseChannel::new(config, rx));
+        for id in [0, 1] {
+            tx.send(ClientMessage::Request(Request {
+                context: context::current(),
+                id,
+                message: (),
+                oneway: false,
+            }))
+            .await
+            .unwrap();
+        }
+
+        assert_matches!(
+            channel.as_mut().poll_next(&mut noop_context()),
+            Poll::Ready(Some(Ok(request))) if request.request.id == 0
+        );
+        assert!(channel.as_mut().poll_next(&mut noop_context()).is_pending());
+        assert_eq!(channel.stats().requests_received(), 1);
+
+        channel
+            .as_mut()
+            .start_send(Response {
+                request_id: 0,
+                message: Ok(()),
+                extensions: Default::default(),
+                partial: false,
+            })
+            .unwrap();
+        assert_matches!(
+            channel.as_mut().poll_next(&mut noop_context()),
+            Poll::Ready(Some(Ok(request))) if request.request.id == 1
+        );
+    }
+
+    #[tokio::test]
+    async fn base_channel_stops_reading_requests_while_rejections_are_queued() {
+        let (mut tx, rx) =
+            crate::transport::channel::unbounded::<Response<()>, ClientMessage<()>>();
+        let config = Config {
+            pending_response_buffer: 1,
+            ..Config::default()
+        };
+        let mut channel = Box::pin(BaseChannel::new(config, rx));
+        for id in [0, 1] {
+            tx.send(ClientMessage::Request(Request {
+                context: context::current().with_deadline(Instant::now()),
+                id,
+                message: (),
+                oneway: false,
+            }))
+            .await
+            .unwrap();
+        }
+
+        assert!(channel.as_mut().poll_next(&mut noop_context()).is_pending());
+        assert_eq!(channel.stats().deadline_expirations(), 1);
+        assert_matches!(
+            channel.as_mut().poll_flush(&mut noop_context()),
+            Poll::Ready(Ok(()))
+        );
+        assert!(channel.as_mut().poll_next(&mut noop_context()).is_pending());
+        assert_eq!(channel.stats().deadline_expirations(), 2);
+    }
+
+    fn test_memory_limited_channel(
+        memory_limit_policy: MemoryLimitPolicy,
+    ) -> (
+        Pin<Box<BaseChannel<(), (), UnboundedChannel<ClientMessage<()>, Response<()>>>>>,
+        UnboundedChannel<Response<()>, ClientMessage<()>>,
+    ) {
+        let (tx, rx) = crate::transport::channel::unbounded();
+        // Room for a single request.
+        let config = Config {
+            max_in_flight_bytes: Some(mem::size_of::<Request<()>>()),
+            memory_limit_policy,
+            ..Config::default()
+        };
+        (Box::pin(BaseChannel::new(config, rx)), tx)
+    }
+
+    #[tokio::test]
+    async fn base_channel_stops_reading_requests_when_out_of_memory() {
+        let (mut channel, mut tx) = test_memory_limited_channel(MemoryLimitPolicy::Backpressure);
+        for id in [0, 1] {
+            tx.send(ClientMessage::Request(Request {
+                context: context::current(),
+                id,
+                message: (),
+                oneway: false,
+            }))
+            .await
+            .unwrap();
+        }
+
+        assert_matches!(
+            channel.as_mut().poll_next(&mut noop_context()),
+            Poll::Ready(Some(Ok(request))) if request.request.id == 0
+        );
+        let (waker, wakes) = new_count_waker();
+        assert!(channel
+            .as_mut()
+            .poll_next(&mut Context::from_waker(&waker))
+            .is_pending());
+        assert_eq!(channel.in_flight_requests(), 1);
+        assert_eq!(wakes.get(), 0);
+
+        channel
+            .as_mut()
+            .start_send(Response {
+                request_id: 0,
+                message: Ok(()),
+                extensions: Default::default(),
+                partial: false,
+            })
+            .unwrap();
+        assert!(wakes.get() > 0);
+        assert_matches!(
+            channel.as_mut().poll_next(&mut noop_context()),
+            Poll::Ready(Some(Ok(request))) if request.request.id == 1
+        );
+    }
+
+    #[tokio::test]
+    async fn base_channel_rejects_requests_when_out_of_memory() {
+        let (mut channel, mut tx) = test_memory_limited_channel(MemoryLimitPolicy::Reject);
+        for (id, oneway) in [(0, false), (1, false), (2, true)] {
+            tx.send(ClientMessage::Request(Request {
+                context: context::current(),
+                id,
+                message: (),
+                oneway,
+            }))
+            .await
+            .unwrap();
+        }
+
+        assert_matches!(
+            channel.as_mut().poll_next(&mut noop_context()),
+            Poll::Ready(Some(Ok(request))) if request.request.id == 0
+        );
+        assert!(channel.as_mut().poll_next(&mut noop_context()).is_pending());
+        assert_eq!(channel.in_flight_requests(), 1);
+        assert_matches!(
+            channel.as_mut().poll_flush(&mut noop_context()),
+            Poll::Ready(Ok(()))
+        );
+        let response = tx.next().await.unwrap().unwrap();
+        assert_eq!(response.request_id, 1);
+        assert_matches!(
+            response.message,
+            Err(ServerError {
+                kind: io::ErrorKind::OutOfMemory,
+                ..
+            })
+        );
+        assert!(tx.next().now_or_never().is_none());
+    }
+
+    #[tokio::test]
+    async fn requests_yield_after_frames_per_yield() {
+        let (mut tx, rx) =
+            crate::transport::channel::unbounded::<Response<()>, ClientMessage<()>>();
+        let config = Config {
+            frames_per_yield: Some(2),
+            ..Config::default()
+        };
+        let mut requests = Box::pin(BaseChannel::new(config, rx).requests());
+        for id in 0..3 {
+            tx.send(ClientMessage::Request(Request {
+                context: context::current(),
+                id,
+                message: (),
+                oneway: false,
+            }))
+            .await
+            .unwrap();
+        }
+
+        let (waker, wakes) = new_count_waker();
+        let cx = &mut Context::from_waker(&waker);
+        let mut in_flight = vec![];
+        for id in 0..2 {
+            assert_matches!(
+                requests.as_mut().poll_next(cx),
+                Poll::Ready(Some(Ok(request))) if request.get().id == id => in_flight.push(request)
+            );
+        }
+        assert!(requests.as_mut().poll_next(cx).is_pending());
+        assert_eq!(wakes.get(), 1);
+        assert_matches!(
+            requests.as_mut().poll_next(cx),
+            Poll::Ready(Some(Ok(request))) if request.get().id == 2
+        );
+    }
+
+    #[tokio::test]
+    async fn base_channel_clamps_deadline() {
+        let (mut tx, rx) =
+            crate::transport::channel::unbounded::<Response<()>, ClientMessage<()>>();
+        let config = Config {
+            max_deadline: Some(Duration::from_secs(10)),
+            ..Config::default()
+        };
+        let mut channel = Box::pin(BaseChannel::new(config, rx));
+        let mut ctx = context::current();
+        ctx.deadline = Instant::now() + Duration::from_secs(60 * 60 * 24 * 365);
+        tx.send(ClientMessage::Request(Request {
+            context: ctx,
+            id: 0,
+            message: (),
+            oneway: false,
+        }))
+        .await
+        .unwrap();
+
+        let req = match channel.as_mut().poll_next(&mut noop_context()) {
+            Poll::Ready(Some(Ok(req))) => req,
+            result => panic!("Unexpected result: {:?}", result),
+        };
+        assert!(req.request.context.deadline <= Instant::now() + Duration::from_secs(10));
+    }
+
+    #[tokio::test]
+    async fn base_channel_rejects_out_of_bounds_deadline() {
+        let (mut tx, rx) =
+            crate::transport::channel::unbounded::<Response<()>, ClientMessage<()>>();
+        let config = Config {
+            max_deadline: Some(Duration::from_secs(10)),
+            deadline_policy: DeadlinePolicy::Reject,
+            ..Config::default()
+        };
+        let mut channel = Box::pin(BaseChannel::new(config, rx));
+        for (id, oneway) in [(0, false), (1, true)] {
+            let mut ctx = context::current();
+            ctx.deadline = Instant::now() + Duration::from_secs(60);
+            tx.send(ClientMessage::Request(Request {
+                context: ctx,
+                id,
+                message: (),
+                oneway,
+            }))
+            .await
+            .unwrap();
+        }
+
+        assert!(channel.as_mut().poll_next(&mut noop_context()).is_pending());
+        assert_eq!(channel.in_flight_requests(), 0);
+        assert_matches!(
+            channel.as_mut().poll_flush(&mut noop_context()),
+            Poll::Ready(Ok(()))
+        );
+        let response = tx.next().await.unwrap().unwrap();
+        assert_eq!(response.request_id, 0);
+        assert_matches!(
+            response.message,
+            Err(ServerError {
+                kind: io::ErrorKind::InvalidInput,
+                ..
+            })
+        );
+        assert_matches!(tx.next().now_or_never(), None);
+    }
+
+    #[tokio::test]
+    async fn in_flight_request_execute_sends_response_extensions() {
+        let (mut requests, mut tx) = test_requests::<(), ()>();
+        tx.send(fake_request(())).await.unwrap();
+
+        let request = match requests.as_mut().poll_next(&mut noop_context()) {
+            Poll::Ready(Some(Ok(request))) => request,
+            result => panic!("Unexpected result: {:?}", result),
+        };
+        request
+            .execute(serve(|_, _| async {
+                assert!(response_extensions::insert("cache", "hit"));
+                Ok(())
+            }))
+            .await;
+        assert!(!response_extensions::insert("outside", "request"));
+
+        let response = requests
+            .as_mut()
+            .pending_responses_mut()
+            .recv()
+            .await
+            .unwrap();
+        assert_eq!(response.extensions.get("cache"), Some("hit"));
+        assert_eq!(response.extensions.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn in_flight_request_execute_exposes_request_id() {
+        let (mut requests, mut tx) = test_requests::<(), u64>();
+        tx.send(ClientMessage::Request(Request {
+            context: context::current(),
+            id: 7,
+            message: (),
+            oneway: false,
+        }))
+        .await
+        .unwrap();
+
+        let request = match requests.as_mut().poll_next(&mut noop_context()) {
+            Poll::Ready(Some(Ok(request))) => request,
+            result => panic!("Unexpected result: {:?}", result),
+        };
+        request
+            .execute(serve(|_, _| async {
+                Ok(context::current_request_id().unwrap())
+            }))
+            .await;
+        assert_eq!(context::current_request_id(), None);
+
+        let response = requests
+            .as_mut()
+            .pending_responses_mut()
+            .recv()
+            .await
+            .unwrap();
+        assert_eq!(response.request_id, 7);
+        assert_matches!(response.message, Ok(7));
+    }
+
+    #[tokio::test]
+    async fn in_flight_request_execute_body_streams_partial_responses() {
+        let (mut requests, mut tx) = test_requests::<(), i32>();
+        tx.send(fake_request(())).await.unwrap();
+
+        let request = match requests.as_mut().poll_next(&mut noop_context()) {
+            Poll::Ready(Some(Ok(request))) => request,
+            result => panic!("Unexpected result: {:?}", result),
+        };
+        request
+            .execute_body(serve(|_, _| async {
+                response_extensions::insert("streamed", "true");
+                Ok(super::body::Body::new(stream::iter([Ok(1), Ok(2), Ok(3)])))
+            }))
+            .await;
+
+        let mut responses = vec![];
+        while let Ok(response) = requests.as_mut().pending_responses_mut().try_recv() {
+            responses.push(response);
+        }
+        assert_eq!(
+            responses
+                .iter()
+                .map(|response| (response.message.clone(), response.partial))
+                .collect::<Vec<_>>(),
+            [(Ok(1), true), (Ok(2), true), (Ok(3), false)]
+        );
+        assert!(responses[0].extensions.is_empty());
+        assert_eq!(responses[2].extensions.get("streamed"), Some("true"));
+    }
+
+    #[tokio::test]
+    async fn in_flight_request_execute_reports_slow_request() {
+        tokio::time::pause();
+        let slow_requests = Arc::new(Mutex::new(vec![]));
+        let config = Config {
+            slow_request_threshold: Some(Duration::from_secs(1)),
+            slow_request_hook: Some(SlowRequestHook::new({
+                let slow_requests = slow_requests.clone();
+                move |slow_request| {
+                    slow_requests
+                        .lock()
+                        .unwrap()
+                        .push((slow_request.request_id, slow_request.elapsed))
+                }
+            })),
+            ..Config::default()
+        };
+        let (tx, rx) = crate::transport::channel::unbounded();
+        let mut requests = Box::pin(BaseChannel::new(config, rx).requests());
+        let mut tx: UnboundedChannel<Response<()>, ClientMessage<u64>> = tx;
+
+        for (id, secs) in [(0, 0), (1, 2), (2, u64::MAX)] {
+            tx.send(ClientMessage::Request(Request {
+                context: context::current(),
+                id,
+                message: secs,
+                oneway: false,
+            }))
+            .await
+            .unwrap();
+            let request = match requests.as_mut().poll_next(&mut noop_context()) {
+                Poll::Ready(Some(Ok(request))) => request,
+                result => panic!("Unexpected result: {:?}", result),
+            };
+            // The last request never completes, but is reported all the same.
+            let _ = tokio::time::timeout(
+                Duration::from_secs(3),
+                request.execute(serve(|_, secs| async move {
+                    if secs == u64::MAX {
+                        future::pending::<()>().await;
+                    }
+                    tokio::time::sleep(Duration::from_secs(secs)).await;
+                    Ok(())
+                })),
+            )
+            .await;
+        }
+
+        let slow_requests = slow_requests.lock().unwrap();
+        assert_matches!(
+            &slow_requests[..],
+            [(1, first), (2, second)]
+                if [first, second].iter().all(|elapsed| {
+                    (Duration::from_secs(1)..Duration::from_secs(2)).contains(elapsed)
+                })
+        );
+    }
+
     #[tokio::test]
     async fn requests_poll_next_response_returns_pending_when_buffer_full() {
         let (mut requests, _tx) = test_bounded_requests::<(), ()>(0);
@@ -1482,6 +3024,7 @@ mod tests {
                 id: 0,
                 context: context::current(),
                 message: (),
+                oneway: false,
             })
             .unwrap();
         requests
@@ -1490,6 +3033,8 @@ mod tests {
             .start_send(Response {
                 request_id: 0,
                 message: Ok(()),
+                extensions: Default::default(),
+                partial: false,
             })
             .unwrap();
 
@@ -1501,6 +3046,8 @@ mod tests {
             .send(Response {
                 request_id: 1,
                 message: Ok(()),
+                extensions: Default::default(),
+                partial: false,
             })
             .await
             .unwrap();
@@ -1512,6 +3059,7 @@ mod tests {
                 id: 1,
                 context: context::current(),
                 message: (),
+                oneway: false,
             })
             .unwrap();
 
@@ -1533,6 +3081,7 @@ mod tests {
                 id: 0,
                 context: context::current(),
                 message: (),
+                oneway: false,
             })
             .unwrap();
         requests
@@ -1541,6 +3090,8 @@ mod tests {
             .start_send(Response {
                 request_id: 0,
                 message: Ok(()),
+                extensions: Default::default(),
+                partial: false,
             })
             .unwrap();
 
@@ -1552,6 +3103,7 @@ mod tests {
                 id: 1,
                 context: context::current(),
                 message: (),
+                oneway: false,
             })
             .unwrap();
         requests
@@ -1561,6 +3113,8 @@ mod tests {
             .send(Response {
                 request_id: 1,
                 message: Ok(()),
+                extensions: Default::default(),
+                partial: false,
             })
             .await
             .unwrap();
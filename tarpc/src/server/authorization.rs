@@ -0,0 +1,232 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Provides a [`Serve`] wrapper that checks each request against an access control policy
+//! before executing it.
+//!
+//! An [`Authorizer`] decides whether a principal may call a method. The principal is supplied
+//! when wrapping the serve fn for a channel: typically it's an identity established by the
+//! transport, like a TLS client certificate, or by an authentication handshake. The authorizer
+//! also sees the request context, so it can check per-request
+//! [credentials](context::Context::credentials), like a bearer token. Denied requests are answered
+//! with a [`PermissionDenied`](std::io::ErrorKind::PermissionDenied) error without running the
+//! handler.
+//!
+//! # Example
+//!
+//! ```rust
+//! use futures::executor::block_on;
+//! use tarpc::{
+//!     context,
+//!     server::{authorization::{Authorize, Decision}, serve, Serve},
+//! };
+//!
+//! let policy = |principal: &&str, method: Option<&'static str>, _: &context::Context| {
+//!     match (*principal, method) {
+//!         ("admin", _) | (_, Some("Service.read")) => Decision::Allow,
+//!         _ => Decision::deny("only admins may write"),
+//!     }
+//! };
+//! let serve = serve(|_, i: i32| async move { Ok(i + 1) });
+//!
+//! let guest = Authorize::new(serve, "guest", policy);
+//! assert!(block_on(guest.serve(context::current(), 1)).is_err());
+//!
+//! let admin = Authorize::new(serve, "admin", policy);
+//! assert_eq!(block_on(admin.serve(context::current(), 1)), Ok(2));
+//! ```
+
+use crate::{context, server::Serve, ServerError};
+use std::io;
+
+/// The outcome of an authorization check.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Decision {
+    /// The request may be executed.
+    Allow,
+    /// The request must not be executed.
+    Deny {
+        /// Why the request was denied. Sent to the client.
+        reason: String,
+    },
+}
+
+impl Decision {
+    /// Returns a decision denying the request because of `reason`.
+    pub fn deny(reason: impl Into<String>) -> Self {
+        Decision::Deny {
+            reason: reason.into(),
+        }
+    }
+}
+
+/// Decides whether a principal may call a method.
+#[allow(async_fn_in_trait)]
+pub trait Authorizer<Principal> {
+    /// Returns whether `principal` may call `method` with the request context `ctx`.
+    ///
+    /// `method` is the name of the called method, if the serve fn knows it.
+    async fn authorize(
+        &self,
+        principal: &Principal,
+        method: Option<&'static str>,
+        ctx: &context::Context,
+    ) -> Decision;
+}
+
+impl<F, Principal> Authorizer<Principal> for F
+where
+    F: Fn(&Principal, Option<&'static str>, &context::Context) -> Decision,
+{
+    async fn authorize(
+        &self,
+        principal: &Principal,
+        method: Option<&'static str>,
+        ctx: &context::Context,
+    ) -> Decision {
+        self(principal, method, ctx)
+    }
+}
+
+/// A [`Serve`] wrapper that only executes requests allowed by an [`Authorizer`].
+#[derive(Clone, Debug)]
+pub struct Authorize<Serv, Principal, A> {
+    serve: Serv,
+    principal: Principal,
+    authorizer: A,
+}
+
+impl<Serv, Principal, A> Authorize<Serv, Principal, A> {
+    /// Returns a new `Authorize` that serves requests from `principal` with `serve`, if
+    /// `authorizer` allows them.
+    pub fn new(serve: Serv, principal: Principal, authorizer: A) -> Self {
+        Self {
+            serve,
+            principal,
+            authorizer,
+        }
+    }
+
+    /// Returns the inner serve fn.
+    pub fn get_ref(&self) -> &Serv {
+        &self.serve
+    }
+
+    /// Returns the principal whose requests are served.
+    pub fn principal(&self) -> &Principal {
+        &self.principal
+    }
+}
+
+impl<Serv, Principal, A> Serve for Authorize<Serv, Principal, A>
+where
+    Serv: Serve,
+    A: Authorizer<Principal>,
+{
+    type Req = Serv::Req;
+    type Resp = Serv::Resp;
+
+    async fn serve(self, ctx: context::Context, req: Self::Req) -> Result<Serv::Resp, ServerError> {
+        let method = self.serve.method(&req);
+        match self
+            .authorizer
+            .authorize(&self.principal, method, &ctx)
+            .await
+        {
+            Decision::Allow => self.serve.serve(ctx, req).await,
+            Decision::Deny { reason } => {
+                tracing::info!(rpc.method = method, %reason, "DenyRequest");
+                Err(ServerError::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("permission denied: {reason}"),
+                ))
+            }
+        }
+    }
+
+    fn method(&self, request: &Self::Req) -> Option<&'static str> {
+        self.serve.method(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::serve;
+    use assert_matches::assert_matches;
+    use futures::executor::block_on;
+    use std::cell::Cell;
+
+    #[derive(Clone, Copy)]
+    struct Named;
+
+    impl Serve for Named {
+        type Req = &'static str;
+        type Resp = ();
+
+        async fn serve(self, _: context::Context, _: &'static str) -> Result<(), ServerError> {
+            Ok(())
+        }
+
+        fn method(&self, req: &&'static str) -> Option<&'static str> {
+            Some(req)
+        }
+    }
+
+    fn read_only(_: &(), method: Option<&'static str>, _: &context::Context) -> Decision {
+        match method {
+            Some("read") => Decision::Allow,
+            _ => Decision::deny("read only"),
+        }
+    }
+
+    #[test]
+    fn authorizer_sees_method_names() {
+        let serve = Authorize::new(Named, (), read_only);
+        assert_eq!(
+            block_on(serve.clone().serve(context::current(), "read")),
+            Ok(())
+        );
+        assert_matches!(
+            block_on(serve.serve(context::current(), "write")),
+            Err(ServerError { kind: io::ErrorKind::PermissionDenied, ref detail, .. })
+                if detail.contains("read only")
+        );
+    }
+
+    #[test]
+    fn denied_requests_are_not_executed() {
+        let calls = &Cell::new(0);
+        let serve = serve(|_, _: ()| async move {
+            calls.set(calls.get() + 1);
+            Ok(())
+        });
+        let deny_all = |_: &(), _: Option<&'static str>, _: &context::Context| Decision::deny("no");
+
+        assert!(
+            block_on(Authorize::new(serve, (), deny_all).serve(context::current(), ())).is_err()
+        );
+        assert_eq!(calls.get(), 0);
+    }
+
+    #[test]
+    fn authorizer_sees_credentials() {
+        let bearer = |_: &(), _: Option<&'static str>, ctx: &context::Context| match ctx
+            .credentials
+            .as_ref()
+            .and_then(|c| c.as_str())
+        {
+            Some("secret") => Decision::Allow,
+            _ => Decision::deny("bad token"),
+        };
+        let serve = Authorize::new(Named, (), bearer);
+        assert!(block_on(serve.clone().serve(context::current(), "read")).is_err());
+
+        let mut ctx = context::current();
+        ctx.credentials = Some("secret".into());
+        assert_eq!(block_on(serve.serve(ctx, "read")), Ok(()));
+    }
+}
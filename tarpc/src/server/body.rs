@@ -0,0 +1,144 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Lets request handlers stream large responses without buffering them in memory.
+//!
+//! A handler executed with [`InFlightRequest::execute_body`](super::InFlightRequest::execute_body)
+//! responds with a [`Body`]: a stream of responses that is sent to the client one response at a
+//! time, as the channel has room to buffer them. With the `body-reader` feature, a body can be read
+//! from any [`AsyncRead`](tokio::io::AsyncRead), like a file, so multi-hundred-megabyte results are
+//! never materialized before serialization. Methods that respond with a single value use
+//! [`Body::once`]. The client receives the responses with
+//! [`call_body`](crate::client::Channel::call_body).
+//!
+//! # Example
+//!
+//! ```rust
+//! use futures::prelude::*;
+//! use tarpc::{
+//!     client, context,
+//!     server::{self, body::Body, BaseChannel, Channel},
+//!     transport,
+//! };
+//!
+//! # #[cfg(not(all(feature = "tokio1", feature = "body-reader")))]
+//! # fn main() {}
+//! # #[cfg(all(feature = "tokio1", feature = "body-reader"))]
+//! #[tokio::main]
+//! async fn main() -> anyhow::Result<()> {
+//!     let (tx, rx) = transport::channel::unbounded();
+//!     let client = client::new(client::Config::default(), tx).spawn();
+//!     let serve = server::serve(|_, name: String| async move {
+//!         Ok(match name.as_str() {
+//!             "big-file" => Body::from_reader(&b"lots of data"[..], |chunk| chunk.to_vec()),
+//!             _ => Body::once(b"small".to_vec()),
+//!         })
+//!     });
+//!     tokio::spawn(
+//!         BaseChannel::with_defaults(rx)
+//!             .requests()
+//!             .execute_body(serve)
+//!             .for_each(|request| async move {
+//!                 tokio::spawn(request);
+//!             }),
+//!     );
+//!
+//!     let body = client
+//!         .call_body(context::current(), "read", "big-file".to_string())
+//!         .await?;
+//!     let chunks: Vec<_> = body.try_collect().await?;
+//!     assert_eq!(chunks.concat(), b"lots of data");
+//!     Ok(())
+//! }
+//! ```
+
+use crate::ServerError;
+#[cfg(feature = "body-reader")]
+use bytes::Bytes;
+use futures::prelude::*;
+use std::{
+    fmt,
+    pin::Pin,
+    task::{Context, Poll},
+};
+#[cfg(feature = "body-reader")]
+use tokio::io::AsyncRead;
+#[cfg(feature = "body-reader")]
+use tokio_util::io::ReaderStream;
+
+/// The responses to a single request, sent to the client one at a time.
+///
+/// A body must yield at least one response. It ends early at its first error, which is sent to
+/// the client as the final response.
+pub struct Body<Resp> {
+    responses: Responses<Resp>,
+}
+
+enum Responses<Resp> {
+    /// A single response, held inline so that unary methods of streaming services aren't boxed.
+    Once(Option<Resp>),
+    Stream(Pin<Box<dyn Stream<Item = Result<Resp, ServerError>> + Send>>),
+}
+
+// The stream is boxed, so the body is Unpin even if the response isn't.
+impl<Resp> Unpin for Responses<Resp> {}
+
+impl<Resp> Body<Resp>
+where
+    Resp: Send + 'static,
+{
+    /// Returns a body streaming `responses`.
+    pub fn new(responses: impl Stream<Item = Result<Resp, ServerError>> + Send + 'static) -> Self {
+        Self {
+            responses: Responses::Stream(Box::pin(responses)),
+        }
+    }
+
+    /// Returns a body consisting of a single response.
+    pub fn once(response: Resp) -> Self {
+        Self {
+            responses: Responses::Once(Some(response)),
+        }
+    }
+
+    /// Returns a body streaming the contents of `reader`, converting each chunk read into a
+    /// response with `into_response`.
+    ///
+    /// An error reading from `reader` is sent to the client as a [`ServerError`] of the same
+    /// kind.
+    #[cfg(feature = "body-reader")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "body-reader")))]
+    pub fn from_reader<R, F>(reader: R, mut into_response: F) -> Self
+    where
+        R: AsyncRead + Send + 'static,
+        F: FnMut(Bytes) -> Resp + Send + 'static,
+    {
+        Self::new(ReaderStream::new(reader).map(move |chunk| match chunk {
+            Ok(chunk) => Ok(into_response(chunk)),
+            Err(e) => Err(ServerError::new(
+                e.kind(),
+                format!("failed to read the response body: {e}"),
+            )),
+        }))
+    }
+}
+
+impl<Resp> Stream for Body<Resp> {
+    type Item = Result<Resp, ServerError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match &mut self.responses {
+            Responses::Once(response) => Poll::Ready(response.take().map(Ok)),
+            Responses::Stream(responses) => responses.as_mut().poll_next(cx),
+        }
+    }
+}
+
+impl<Resp> fmt::Debug for Body<Resp> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Body").finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,560 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Provides a [`Broadcaster`] for sending a message to many connected clients at once.
+//!
+//! Each connection [subscribes](Broadcaster::subscribe) with a key describing the client, and
+//! [forwards](Subscription::forward) the messages yielded by its [`Subscription`] to the client as
+//! one-way requests. The requests are sent by a [client](crate::client) of a service the remote
+//! peer serves, which shares the connection with the server when both peers
+//! [split](crate::transport::symmetric::split) it. Dropping a subscription unregisters it.
+//!
+//! [`Incoming::subscribe_to`](crate::server::incoming::Incoming::subscribe_to) subscribes each
+//! incoming channel, and unregisters it when the channel closes, which ends its subscription, so
+//! disconnections are handled automatically.
+//!
+//! Each subscription queues a bounded number of messages. A subscriber whose queue is full when a
+//! message is broadcast lags too far behind to catch up, so it's unregistered: its subscription
+//! yields the messages already queued, then ends.
+//!
+//! # Example
+//!
+//! ```rust
+//! use futures::{executor::block_on, prelude::*};
+//! use tarpc::server::broadcast::Broadcaster;
+//!
+//! let broadcaster = Broadcaster::new();
+//! let mut admin = broadcaster.subscribe("admin");
+//! let mut guest = broadcaster.subscribe("guest");
+//!
+//! assert_eq!(broadcaster.broadcast("hello"), 2);
+//! assert_eq!(broadcaster.broadcast_filtered(|key| *key == "admin", "secret"), 1);
+//!
+//! assert_eq!(block_on(admin.next()), Some("hello"));
+//! assert_eq!(block_on(admin.next()), Some("secret"));
+//! assert_eq!(block_on(guest.next()), Some("hello"));
+//!
+//! // Disconnected clients are unregistered when their subscription is dropped.
+//! drop(guest);
+//! assert_eq!(broadcaster.broadcast("goodbye"), 1);
+//! ```
+//!
+//! Pushing the broadcast messages to a client over the connection it dialed:
+//!
+//! ```rust
+//! use futures::{channel::mpsc, prelude::*};
+//! use tarpc::{
+//!     client,
+//!     server::{self, broadcast::Broadcaster, BaseChannel, Channel},
+//!     transport::{channel, symmetric},
+//! };
+//!
+//! # #[cfg(not(feature = "tokio1"))]
+//! # fn main() {}
+//! # #[cfg(feature = "tokio1")]
+//! #[tokio::main]
+//! async fn main() -> anyhow::Result<()> {
+//!     let (server, client) = channel::unbounded();
+//!
+//!     // The server calls the client's service with the messages broadcast to it.
+//!     let broadcaster = Broadcaster::new();
+//!     let (to_client, _, demux) = symmetric::split::<_, String, (), (), ()>(server);
+//!     tokio::spawn(demux);
+//!     let to_client = client::new(client::Config::default(), to_client).spawn();
+//!     tokio::spawn(broadcaster.subscribe("client").forward(to_client, "Notify"));
+//!
+//!     // The client serves the server's pushed messages.
+//!     let (pushed_tx, mut pushed) = mpsc::unbounded();
+//!     let (_, from_server, demux) = symmetric::split::<_, (), (), String, ()>(client);
+//!     tokio::spawn(demux);
+//!     tokio::spawn(
+//!         BaseChannel::with_defaults(from_server)
+//!             .execute(server::serve(move |_, msg: String| {
+//!                 let pushed_tx = pushed_tx.clone();
+//!                 async move {
+//!                     pushed_tx.unbounded_send(msg).unwrap();
+//!                     Ok(())
+//!                 }
+//!             }))
+//!             .for_each(|response| async move {
+//!                 tokio::spawn(response);
+//!             }),
+//!     );
+//!
+//!     assert_eq!(broadcaster.broadcast("hello".to_string()), 1);
+//!     assert_eq!(pushed.next().await.as_deref(), Some("hello"));
+//!     Ok(())
+//! }
+//! ```
+
+use crate::{
+    client::{self, RpcError},
+    context,
+    server::{self, Channel},
+};
+use fnv::FnvHashMap;
+use futures::{prelude::*, ready, task::*};
+use pin_project::pin_project;
+use std::{
+    fmt,
+    pin::Pin,
+    sync::{Arc, Mutex, Weak},
+};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tracing::info;
+
+/// The number of messages queued per subscription by [`Broadcaster::new`].
+const DEFAULT_CAPACITY: usize = 100;
+
+/// A handle for sending messages to all, or a filtered subset of, the currently subscribed clients.
+///
+/// Clones share the same set of subscriptions.
+pub struct Broadcaster<K, Msg> {
+    registry: Arc<Mutex<Registry<K, Msg>>>,
+}
+
+struct Registry<K, Msg> {
+    next_id: u64,
+    capacity: usize,
+    subscribers: FnvHashMap<u64, Subscriber<K, Msg>>,
+}
+
+struct Subscriber<K, Msg> {
+    key: K,
+    messages: mpsc::Sender<Msg>,
+}
+
+impl<K, Msg> Broadcaster<K, Msg> {
+    /// Returns a new broadcaster without any subscriptions, whose subscriptions each queue up to
+    /// 100 messages.
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    /// Returns a new broadcaster without any subscriptions, whose subscriptions each queue up to
+    /// `capacity` messages.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `capacity` is zero.
+    pub fn with_capacity(capacity: usize) -> Self {
+        assert!(
+            capacity > 0,
+            "subscriptions must be able to queue a message"
+        );
+        Self {
+            registry: Arc::new(Mutex::new(Registry {
+                next_id: 0,
+                capacity,
+                subscribers: FnvHashMap::default(),
+            })),
+        }
+    }
+
+    /// Registers a new client identified by `key`, returning a stream of the messages broadcast to
+    /// it. The client stays registered until the returned subscription is dropped, or until it
+    /// lags behind.
+    pub fn subscribe(&self, key: K) -> Subscription<K, Msg> {
+        let mut registry = self.registry.lock().unwrap();
+        let (tx, rx) = mpsc::channel(registry.capacity);
+        let id = registry.next_id;
+        registry.next_id += 1;
+        registry
+            .subscribers
+            .insert(id, Subscriber { key, messages: tx });
+        Subscription {
+            registration: Registration {
+                id,
+                registry: Arc::downgrade(&self.registry),
+            },
+            messages: rx,
+        }
+    }
+
+    /// Returns the number of currently subscribed clients.
+    pub fn len(&self) -> usize {
+        self.registry.lock().unwrap().subscribers.len()
+    }
+
+    /// Returns true iff there are no subscribed clients.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<K, Msg> Broadcaster<K, Msg>
+where
+    Msg: Clone,
+{
+    /// Sends `msg` to every subscribed client. Returns the number of clients the message was
+    /// delivered to. Clients whose queues are full are unregistered rather than sent the message.
+    pub fn broadcast(&self, msg: Msg) -> usize {
+        self.broadcast_filtered(|_| true, msg)
+    }
+
+    /// Sends `msg` to every subscribed client whose key matches `filter`. Returns the number of
+    /// clients the message was delivered to.
+    pub fn broadcast_filtered<F>(&self, mut filter: F, msg: Msg) -> usize
+    where
+        F: FnMut(&K) -> bool,
+    {
+        let mut registry = self.registry.lock().unwrap();
+        let mut delivered = 0;
+        registry.subscribers.retain(|&id, subscriber| {
+            if !filter(&subscriber.key) {
+                return true;
+            }
+            match subscriber.messages.try_send(msg.clone()) {
+                Ok(()) => {
+                    delivered += 1;
+                    true
+                }
+                Err(TrySendError::Full(_)) => {
+                    info!(
+                        subscriber = id,
+                        "Unregistering a subscriber that lags behind"
+                    );
+                    false
+                }
+                // The subscription is being dropped; it will finish unregistering shortly.
+                Err(TrySendError::Closed(_)) => false,
+            }
+        });
+        delivered
+    }
+}
+
+impl<K, Msg> Default for Broadcaster<K, Msg> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K, Msg> Clone for Broadcaster<K, Msg> {
+    fn clone(&self) -> Self {
+        Self {
+            registry: self.registry.clone(),
+        }
+    }
+}
+
+impl<K, Msg> fmt::Debug for Broadcaster<K, Msg> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Broadcaster")
+            .field("subscribers", &self.len())
+            .finish()
+    }
+}
+
+/// A subscriber's place in the registry of a broadcaster. Unregisters the subscriber when dropped.
+struct Registration<K, Msg> {
+    id: u64,
+    registry: Weak<Mutex<Registry<K, Msg>>>,
+}
+
+impl<K, Msg> Registration<K, Msg> {
+    fn unregister(&self) {
+        // Don't care if the broadcaster is dropped.
+        if let Some(registry) = self.registry.upgrade() {
+            registry.lock().unwrap().subscribers.remove(&self.id);
+        }
+    }
+}
+
+impl<K, Msg> Drop for Registration<K, Msg> {
+    fn drop(&mut self) {
+        self.unregister();
+    }
+}
+
+/// A stream of the messages broadcast to a single client. Unregisters the client when dropped.
+///
+/// The stream ends once the client is unregistered and the messages already queued are read.
+pub struct Subscription<K, Msg> {
+    registration: Registration<K, Msg>,
+    messages: mpsc::Receiver<Msg>,
+}
+
+impl<K, Msg> Subscription<K, Msg> {
+    /// Pushes the messages broadcast to the client over `client`, sending each message as a
+    /// [one-way](client::Channel::call_oneway) request named `request_name`, in the order they
+    /// were broadcast.
+    ///
+    /// Completes once the subscription ends, or fails with [`RpcError::Shutdown`] once `client`
+    /// can no longer send requests, which unregisters the client.
+    pub async fn forward<Resp>(
+        mut self,
+        client: client::Channel<Msg, Resp>,
+        request_name: &'static str,
+    ) -> Result<(), RpcError> {
+        while let Some(msg) = self.next().await {
+            client
+                .call_oneway(context::current(), request_name, msg)
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+impl<K, Msg> Stream for Subscription<K, Msg> {
+    type Item = Msg;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Msg>> {
+        self.messages.poll_recv(cx)
+    }
+}
+
+impl<K, Msg> fmt::Debug for Subscription<K, Msg> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Subscription")
+            .field("id", &self.registration.id)
+            .finish()
+    }
+}
+
+/// An [`Incoming`](crate::server::incoming::Incoming) stream that subscribes each channel to a
+/// [`Broadcaster`] while the channel is open.
+#[pin_project]
+#[derive(Debug)]
+pub struct SubscribeChannels<S, K, Msg, F> {
+    #[pin]
+    listener: S,
+    broadcaster: Broadcaster<K, Msg>,
+    keymaker: F,
+}
+
+impl<S, K, Msg, F> SubscribeChannels<S, K, Msg, F> {
+    pub(crate) fn new(listener: S, broadcaster: Broadcaster<K, Msg>, keymaker: F) -> Self {
+        Self {
+            listener,
+            broadcaster,
+            keymaker,
+        }
+    }
+}
+
+impl<S, K, Msg, F> Stream for SubscribeChannels<S, K, Msg, F>
+where
+    S: Stream,
+    F: Fn(&S::Item) -> K,
+{
+    type Item = SubscribedChannel<S::Item, K, Msg>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let self_ = self.project();
+        let channel = match ready!(self_.listener.poll_next(cx)) {
+            Some(channel) => channel,
+            None => return Poll::Ready(None),
+        };
+        let subscription = self_.broadcaster.subscribe((self_.keymaker)(&channel));
+        Poll::Ready(Some(SubscribedChannel {
+            registration: Registration {
+                id: subscription.registration.id,
+                registry: subscription.registration.registry.clone(),
+            },
+            subscription: Some(subscription),
+            inner: channel,
+        }))
+    }
+}
+
+/// A channel that is subscribed to a [`Broadcaster`] by [`SubscribeChannels`]. The channel is
+/// unregistered when it closes or is dropped, which ends its subscription.
+#[pin_project]
+pub struct SubscribedChannel<C, K, Msg> {
+    #[pin]
+    inner: C,
+    registration: Registration<K, Msg>,
+    subscription: Option<Subscription<K, Msg>>,
+}
+
+impl<C, K, Msg> SubscribedChannel<C, K, Msg> {
+    /// Returns the inner channel.
+    pub fn get_ref(&self) -> &C {
+        &self.inner
+    }
+
+    /// Takes the stream of the messages broadcast to the channel, to
+    /// [forward](Subscription::forward) them to the client. Returns `None` if the subscription was
+    /// already taken.
+    pub fn take_subscription(&mut self) -> Option<Subscription<K, Msg>> {
+        self.subscription.take()
+    }
+
+    /// Returns the pinned inner channel.
+    fn inner_pin_mut<'a>(self: &'a mut Pin<&mut Self>) -> Pin<&'a mut C> {
+        self.as_mut().project().inner
+    }
+}
+
+impl<C, K, Msg> Stream for SubscribedChannel<C, K, Msg>
+where
+    C: Stream,
+{
+    type Item = C::Item;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let next = ready!(self.inner_pin_mut().poll_next(cx));
+        if next.is_none() {
+            self.registration.unregister();
+        }
+        Poll::Ready(next)
+    }
+}
+
+impl<C, I, K, Msg> Sink<I> for SubscribedChannel<C, K, Msg>
+where
+    C: Sink<I>,
+{
+    type Error = C::Error;
+
+    fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.inner_pin_mut().poll_ready(cx)
+    }
+
+    fn start_send(mut self: Pin<&mut Self>, item: I) -> Result<(), Self::Error> {
+        self.inner_pin_mut().start_send(item)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.inner_pin_mut().poll_flush(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.inner_pin_mut().poll_close(cx)
+    }
+}
+
+impl<C, K, Msg> AsRef<C> for SubscribedChannel<C, K, Msg> {
+    fn as_ref(&self) -> &C {
+        &self.inner
+    }
+}
+
+impl<C, K, Msg> Channel for SubscribedChannel<C, K, Msg>
+where
+    C: Channel,
+{
+    type Req = C::Req;
+    type Resp = C::Resp;
+    type Transport = C::Transport;
+
+    fn config(&self) -> &server::Config {
+        self.inner.config()
+    }
+
+    fn in_flight_requests(&self) -> usize {
+        self.inner.in_flight_requests()
+    }
+
+    fn transport(&self) -> &Self::Transport {
+        self.inner.transport()
+    }
+}
+
+impl<C, K, Msg> fmt::Debug for SubscribedChannel<C, K, Msg>
+where
+    C: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SubscribedChannel")
+            .field("inner", &self.inner)
+            .field("id", &self.registration.id)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{
+        incoming::Incoming,
+        testing::{cx, FakeChannel},
+    };
+    use assert_matches::assert_matches;
+
+    #[test]
+    fn dropping_subscription_unregisters() {
+        let broadcaster = Broadcaster::<(), ()>::new();
+        let subscription = broadcaster.subscribe(());
+        assert_eq!(broadcaster.len(), 1);
+        drop(subscription);
+        assert!(broadcaster.is_empty());
+        assert_eq!(broadcaster.broadcast(()), 0);
+    }
+
+    #[test]
+    fn subscription_outlives_broadcaster() {
+        let broadcaster = Broadcaster::<(), ()>::new();
+        let mut subscription = broadcaster.subscribe(());
+        drop(broadcaster);
+        assert_matches!(
+            Pin::new(&mut subscription).poll_next(&mut cx()),
+            Poll::Ready(None)
+        );
+    }
+
+    #[test]
+    fn filtered_broadcast_skips_unmatched_keys() {
+        let broadcaster = Broadcaster::new();
+        let mut even = broadcaster.subscribe(2);
+        let mut odd = broadcaster.subscribe(3);
+
+        assert_eq!(broadcaster.broadcast_filtered(|k| k % 2 == 0, "even"), 1);
+        assert_matches!(
+            Pin::new(&mut even).poll_next(&mut cx()),
+            Poll::Ready(Some("even"))
+        );
+        assert_matches!(Pin::new(&mut odd).poll_next(&mut cx()), Poll::Pending);
+    }
+
+    #[test]
+    fn lagging_subscriber_is_unregistered() {
+        let broadcaster = Broadcaster::with_capacity(1);
+        let mut subscription = broadcaster.subscribe(());
+
+        assert_eq!(broadcaster.broadcast(1), 1);
+        assert_eq!(broadcaster.broadcast(2), 0);
+        assert!(broadcaster.is_empty());
+        assert_matches!(
+            Pin::new(&mut subscription).poll_next(&mut cx()),
+            Poll::Ready(Some(1))
+        );
+        assert_matches!(
+            Pin::new(&mut subscription).poll_next(&mut cx()),
+            Poll::Ready(None)
+        );
+    }
+
+    #[test]
+    fn closed_channels_are_unregistered() {
+        let broadcaster = Broadcaster::new();
+        let channels = stream::iter([FakeChannel::default::<(), ()>()])
+            .subscribe_to(broadcaster.clone(), |_| "client");
+        futures::pin_mut!(channels);
+        let mut channel = match channels.as_mut().poll_next(&mut cx()) {
+            Poll::Ready(Some(channel)) => channel,
+            _ => panic!("expected a channel"),
+        };
+        let mut subscription = channel.take_subscription().unwrap();
+        assert!(channel.take_subscription().is_none());
+
+        assert_eq!(broadcaster.broadcast("hello"), 1);
+        assert_matches!(
+            Pin::new(&mut channel).poll_next(&mut cx()),
+            Poll::Ready(None)
+        );
+        assert!(broadcaster.is_empty());
+        assert_matches!(
+            Pin::new(&mut subscription).poll_next(&mut cx()),
+            Poll::Ready(Some("hello"))
+        );
+        assert_matches!(
+            Pin::new(&mut subscription).poll_next(&mut cx()),
+            Poll::Ready(None)
+        );
+    }
+}
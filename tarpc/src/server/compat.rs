@@ -0,0 +1,120 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Before `Serve` returned `impl Future`, implementations named their response future with an
+//! associated type. [`LegacyServe`] keeps that form available, and [`Compat`] adapts such
+//! implementations to [`Serve`], so they can be migrated one at a time.
+//!
+//! # Example
+//!
+//! ```rust
+//! use futures::{executor::block_on, future};
+//! use tarpc::{
+//!     context,
+//!     server::{compat::LegacyServe, Serve},
+//!     ServerError,
+//! };
+//!
+//! struct AddOne;
+//!
+//! impl LegacyServe<i32> for AddOne {
+//!     type Resp = i32;
+//!     type Fut = future::Ready<Result<i32, ServerError>>;
+//!
+//!     fn serve(self, _: context::Context, req: i32) -> Self::Fut {
+//!         future::ready(Ok(req + 1))
+//!     }
+//! }
+//!
+//! let serve = AddOne.compat();
+//! assert_eq!(block_on(serve.serve(context::current(), 1)), Ok(2));
+//! ```
+
+use crate::{context, server::Serve, ServerError};
+use futures::prelude::*;
+use std::{fmt, marker::PhantomData};
+
+/// A serve fn that names its response future with an associated type.
+pub trait LegacyServe<Req> {
+    /// Type of response.
+    type Resp;
+
+    /// Type of response future.
+    type Fut: Future<Output = Result<Self::Resp, ServerError>>;
+
+    /// Extracts a method name from the request.
+    fn method(&self, _request: &Req) -> Option<&'static str> {
+        None
+    }
+
+    /// Responds to a single request.
+    fn serve(self, ctx: context::Context, req: Req) -> Self::Fut;
+
+    /// Adapts `self` to [`Serve`].
+    fn compat(self) -> Compat<Self, Req>
+    where
+        Self: Sized,
+    {
+        Compat::new(self)
+    }
+}
+
+/// Adapts a [`LegacyServe`] to [`Serve`].
+pub struct Compat<S, Req> {
+    serve: S,
+    request: PhantomData<fn(Req)>,
+}
+
+impl<S, Req> Compat<S, Req> {
+    /// Returns a [`Serve`] that serves requests with `serve`.
+    pub fn new(serve: S) -> Self {
+        Self {
+            serve,
+            request: PhantomData,
+        }
+    }
+
+    /// Returns the inner serve fn.
+    pub fn get_ref(&self) -> &S {
+        &self.serve
+    }
+}
+
+impl<S, Req> Serve for Compat<S, Req>
+where
+    S: LegacyServe<Req>,
+{
+    type Req = Req;
+    type Resp = S::Resp;
+
+    fn serve(
+        self,
+        ctx: context::Context,
+        req: Req,
+    ) -> impl Future<Output = Result<S::Resp, ServerError>> {
+        self.serve.serve(ctx, req)
+    }
+
+    fn method(&self, request: &Req) -> Option<&'static str> {
+        self.serve.method(request)
+    }
+}
+
+impl<S: Clone, Req> Clone for Compat<S, Req> {
+    fn clone(&self) -> Self {
+        Self::new(self.serve.clone())
+    }
+}
+
+impl<S: Copy, Req> Copy for Compat<S, Req> {}
+
+impl<S: fmt::Debug, Req> fmt::Debug for Compat<S, Req> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Compat")
+            .field("serve", &self.serve)
+            .finish()
+    }
+}
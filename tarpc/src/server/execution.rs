@@ -0,0 +1,275 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Provides control over how the futures executing a channel's requests are driven.
+//!
+//! By default, each request is spawned as its own task, isolating requests from each other at
+//! the cost of a task per request. Alternatively, requests can be driven inline, within the task
+//! driving their channel, with bounded concurrency: cheaper for short handlers on hot paths, but
+//! a handler that blocks stalls the other requests of its channel. An [`ExecutionStrategy`]
+//! chooses between the two for all requests or per method.
+//!
+//! # Example
+//!
+//! ```rust
+//! use tarpc::{
+//!     context,
+//!     client,
+//!     server::{
+//!         self,
+//!         execution::{run, Execution, ExecutionStrategy},
+//!         BaseChannel, Channel,
+//!     },
+//!     transport,
+//! };
+//!
+//! # #[cfg(not(feature = "tokio1"))]
+//! # fn main() {}
+//! # #[cfg(feature = "tokio1")]
+//! #[tokio::main]
+//! async fn main() {
+//!     let (tx, rx) = transport::channel::unbounded();
+//!     let client = client::new(client::Config::default(), tx).spawn();
+//!     let strategy = ExecutionStrategy::new(Execution::Inline).with_max_inline_requests(16);
+//!     let requests =
+//!         BaseChannel::with_defaults(rx).execute(server::serve(|_, i| async move { Ok(i + 1) }));
+//!     tokio::spawn(run(requests, strategy));
+//!     assert_eq!(client.call(context::current(), "AddOne", 1).await.unwrap(), 2);
+//! }
+//! ```
+
+use fnv::FnvHashMap;
+use futures::{future::BoxFuture, prelude::*};
+use pin_project::pin_project;
+use std::{
+    fmt,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+};
+
+/// How the future executing a request is driven.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Execution {
+    /// Spawn the future as an independent task.
+    #[default]
+    Spawn,
+    /// Drive the future within the task driving its channel.
+    Inline,
+}
+
+/// Chooses how to drive the futures executing requests.
+#[derive(Clone, Debug)]
+pub struct ExecutionStrategy {
+    default: Execution,
+    methods: FnvHashMap<&'static str, Execution>,
+    max_inline_requests: usize,
+    spawner: Option<Spawner>,
+}
+
+/// Spawns the futures of requests executed with [`Execution::Spawn`]. Only `run` spawns
+/// futures, which requires tokio.
+#[derive(Clone)]
+#[cfg_attr(not(feature = "tokio1"), allow(dead_code))]
+struct Spawner(Arc<dyn Fn(BoxFuture<'static, ()>) + Send + Sync>);
+
+impl fmt::Debug for Spawner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Spawner")
+    }
+}
+
+impl ExecutionStrategy {
+    /// Returns a strategy that drives all requests with `default`. At most 100 requests are
+    /// driven inline at once.
+    pub fn new(default: Execution) -> Self {
+        Self {
+            default,
+            methods: FnvHashMap::default(),
+            max_inline_requests: 100,
+            spawner: None,
+        }
+    }
+
+    /// Drives requests to `method` with `execution`, rather than the default.
+    pub fn with_method(mut self, method: &'static str, execution: Execution) -> Self {
+        self.methods.insert(method, execution);
+        self
+    }
+
+    /// Limits the number of requests of a channel driven inline at once. Once reached, the
+    /// channel stops reading requests until an inline request completes.
+    pub fn with_max_inline_requests(mut self, max_inline_requests: usize) -> Self {
+        self.max_inline_requests = max_inline_requests.max(1);
+        self
+    }
+
+    /// Spawns requests executed with [`Execution::Spawn`], and the channels driven by
+    /// [`spawn_incoming_with`](super::incoming::spawn_incoming_with), with `spawn`, e.g. on the
+    /// executor of a deterministic simulator, rather than with `tokio::spawn`.
+    pub fn with_spawner<S>(mut self, spawn: S) -> Self
+    where
+        S: Fn(BoxFuture<'static, ()>) + Send + Sync + 'static,
+    {
+        self.spawner = Some(Spawner(Arc::new(spawn)));
+        self
+    }
+
+    /// Returns how requests to `method` are driven.
+    pub fn execution(&self, method: Option<&'static str>) -> Execution {
+        method
+            .and_then(|method| self.methods.get(method))
+            .copied()
+            .unwrap_or(self.default)
+    }
+
+    /// Spawns `future` with the spawner, if any, and otherwise with `tokio::spawn`.
+    #[cfg(feature = "tokio1")]
+    pub(crate) fn spawn(&self, future: impl Future<Output = ()> + Send + 'static) {
+        match &self.spawner {
+            Some(Spawner(spawn)) => spawn(future.boxed()),
+            None => {
+                tokio::spawn(future);
+            }
+        }
+    }
+}
+
+impl Default for ExecutionStrategy {
+    fn default() -> Self {
+        Self::new(Execution::default())
+    }
+}
+
+/// A future that executes a request, as yielded by [`Channel::execute`](super::Channel::execute).
+#[pin_project]
+#[derive(Debug)]
+pub struct RequestExecution<Fut> {
+    method: Option<&'static str>,
+    #[pin]
+    future: Fut,
+}
+
+impl<Fut> RequestExecution<Fut> {
+    pub(crate) fn new(method: Option<&'static str>, future: Fut) -> Self {
+        Self { method, future }
+    }
+
+    /// Returns the name of the method requested, if the serve fn knows it.
+    pub fn method(&self) -> Option<&'static str> {
+        self.method
+    }
+}
+
+impl<Fut: Future> Future for RequestExecution<Fut> {
+    type Output = Fut::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Fut::Output> {
+        self.project().future.poll(cx)
+    }
+}
+
+/// Drives the request executions of a channel according to `strategy`, returning once the
+/// channel closes and its inline requests complete.
+#[cfg(feature = "tokio1")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio1")))]
+pub async fn run<Fut>(
+    executions: impl Stream<Item = RequestExecution<Fut>>,
+    strategy: ExecutionStrategy,
+) where
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    use futures::{future::Either, pin_mut, stream::FuturesUnordered};
+
+    pin_mut!(executions);
+    let mut inline = FuturesUnordered::new();
+    loop {
+        let execution = if inline.len() >= strategy.max_inline_requests {
+            inline.next().await;
+            continue;
+        } else if inline.is_empty() {
+            executions.next().await
+        } else {
+            match future::select(executions.next(), inline.next()).await {
+                Either::Left((execution, _)) => execution,
+                Either::Right(_) => continue,
+            }
+        };
+        let Some(execution) = execution else {
+            break;
+        };
+        match strategy.execution(execution.method()) {
+            Execution::Spawn => strategy.spawn(execution),
+            Execution::Inline => inline.push(execution),
+        }
+    }
+    while inline.next().await.is_some() {}
+}
+
+#[cfg(all(test, feature = "tokio1"))]
+mod tests {
+    use super::*;
+    use futures::channel::oneshot;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn strategy_overrides_methods() {
+        let strategy =
+            ExecutionStrategy::new(Execution::Spawn).with_method("Service.add", Execution::Inline);
+        assert_eq!(strategy.execution(Some("Service.add")), Execution::Inline);
+        assert_eq!(strategy.execution(Some("Service.hey")), Execution::Spawn);
+        assert_eq!(strategy.execution(None), Execution::Spawn);
+    }
+
+    #[tokio::test]
+    async fn run_bounds_inline_requests() {
+        let started = Arc::new(Mutex::new(Vec::new()));
+        let (senders, executions): (Vec<_>, Vec<_>) = (0..3)
+            .map(|i| {
+                let (tx, rx) = oneshot::channel::<()>();
+                let started = started.clone();
+                let execution = RequestExecution::new(None, async move {
+                    started.lock().unwrap().push(i);
+                    let _ = rx.await;
+                });
+                (tx, execution)
+            })
+            .unzip();
+        let strategy = ExecutionStrategy::new(Execution::Inline).with_max_inline_requests(2);
+        let run = tokio::spawn(run(stream::iter(executions), strategy));
+
+        tokio::task::yield_now().await;
+        assert_eq!(*started.lock().unwrap(), [0, 1]);
+
+        let mut senders = senders.into_iter();
+        senders.next().unwrap().send(()).unwrap();
+        while started.lock().unwrap().len() < 3 {
+            tokio::task::yield_now().await;
+        }
+        drop(senders);
+        run.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn run_spawns_with_the_spawner() {
+        let spawned = Arc::new(Mutex::new(Vec::new()));
+        let executed = Arc::new(Mutex::new(Vec::new()));
+        let executions = (0..2).map(|i| {
+            let executed = executed.clone();
+            RequestExecution::new(None, async move { executed.lock().unwrap().push(i) })
+        });
+        let strategy = ExecutionStrategy::new(Execution::Spawn).with_spawner({
+            let spawned = spawned.clone();
+            move |execution| spawned.lock().unwrap().push(execution)
+        });
+        run(stream::iter(executions), strategy).await;
+        assert!(executed.lock().unwrap().is_empty());
+
+        let spawned = std::mem::take(&mut *spawned.lock().unwrap());
+        future::join_all(spawned).await;
+        assert_eq!(*executed.lock().unwrap(), [0, 1]);
+    }
+}
@@ -0,0 +1,458 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Provides a [`Serve`] wrapper that replays responses to duplicate deliveries of a request.
+//!
+//! A client that retries a request it believes was lost can set
+//! [`Context::idempotency_key`](crate::context::Context::idempotency_key) to the same value on
+//! every attempt. [`ReplayCache`] remembers the responses to recently completed requests, keyed by
+//! the identity of the client, the method requested, and the idempotency key, and answers
+//! duplicates from the cache instead of executing them again. A request reusing a key for another
+//! method isn't a duplicate, so it's executed rather than answered with the other method's
+//! response.
+//!
+//! Idempotency keys are chosen by clients, so they are only unique per client. The client identity
+//! is supplied by the server when wrapping the serve fn for a channel: typically it's the peer
+//! address or an authenticated principal.
+//!
+//! Only successful responses are remembered. Requests that fail with a [`ServerError`] are
+//! executed again when retried. Duplicates that arrive while the original request is still in
+//! flight are not deduplicated.
+//!
+//! Responses are stored in a [`DedupStore`], for the [TTL](ReplayCache::with_ttl) of the cache.
+//! [`InMemoryLru`] keeps them in the server's memory, which deduplicates the requests redelivered
+//! to the same server. Where requests are delivered at least once, but possibly to different
+//! replicas of a server, a store shared by the replicas, e.g. backed by Redis, gives effectively
+//! once execution:
+//!
+//! ```
+//! use std::{collections::HashMap, sync::Mutex, time::{Duration, Instant}};
+//! use tarpc::server::idempotency::{DedupKey, DedupStore};
+//!
+//! /// Stands in for a client of a key-value service shared by the replicas of a server.
+//! #[derive(Default)]
+//! struct KvClient(Mutex<HashMap<String, (String, Instant)>>);
+//!
+//! impl KvClient {
+//!     async fn get(&self, key: &str) -> Option<String> {
+//!         let entries = self.0.lock().unwrap();
+//!         let (value, expires_at) = entries.get(key)?;
+//!         (Instant::now() < *expires_at).then(|| value.clone())
+//!     }
+//!
+//!     async fn set_with_expiry(&self, key: String, value: String, ttl: Duration) {
+//!         self.0.lock().unwrap().insert(key, (value, Instant::now() + ttl));
+//!     }
+//! }
+//!
+//! /// Stores the responses of a service responding with strings in the key-value service.
+//! struct KvStore(KvClient);
+//!
+//! impl DedupStore<String, String> for KvStore {
+//!     async fn get(&self, key: &DedupKey<String>) -> Option<String> {
+//!         self.0.get(&kv_key(key)).await
+//!     }
+//!
+//!     async fn put(&self, key: DedupKey<String>, response: String, ttl: Duration) {
+//!         self.0.set_with_expiry(kv_key(&key), response, ttl).await
+//!     }
+//! }
+//!
+//! fn kv_key(key: &DedupKey<String>) -> String {
+//!     let method = key.method.unwrap_or_default();
+//!     format!("{}/{method}/{}", key.client_id, key.idempotency_key)
+//! }
+//! ```
+
+use crate::{context, server::Serve, ServerError};
+use fnv::FnvHashMap;
+use std::{
+    collections::BTreeMap,
+    hash::Hash,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Identifies a request for deduplication: the idempotency key chosen by the client, the identity
+/// of the client that chose it, and the method requested.
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DedupKey<ClientId> {
+    /// The identity of the client, supplied by the server.
+    pub client_id: ClientId,
+    /// The name of the method requested, if the serve fn [names it](Serve::method).
+    pub method: Option<&'static str>,
+    /// The [idempotency key](crate::context::Context::idempotency_key) of the request.
+    pub idempotency_key: u64,
+}
+
+/// Storage for the responses remembered by a [`ReplayCache`].
+#[allow(async_fn_in_trait)]
+pub trait DedupStore<ClientId, Resp> {
+    /// Returns the response stored for `key`, if any and its TTL hasn't elapsed.
+    async fn get(&self, key: &DedupKey<ClientId>) -> Option<Resp>;
+
+    /// Stores `response` as the response to the request identified by `key`, for `ttl`. Stores
+    /// may forget responses sooner, e.g. to bound their size.
+    async fn put(&self, key: DedupKey<ClientId>, response: Resp, ttl: Duration);
+}
+
+/// A [`DedupStore`] that keeps a bounded number of responses in memory, evicting the least
+/// recently used response when full.
+///
+/// Clones share the same underlying storage, so a single store can be used by all channels of a
+/// server.
+pub struct InMemoryLru<ClientId, Resp> {
+    inner: Arc<Mutex<Lru<DedupKey<ClientId>, Resp>>>,
+}
+
+impl<ClientId, Resp> InMemoryLru<ClientId, Resp> {
+    /// Returns a new store that holds at most `capacity` responses.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Lru::new(capacity))),
+        }
+    }
+
+    /// Returns the number of responses currently stored.
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().entries.len()
+    }
+
+    /// Returns true iff no responses are currently stored.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<ClientId, Resp> Clone for InMemoryLru<ClientId, Resp> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<ClientId, Resp> DedupStore<ClientId, Resp> for InMemoryLru<ClientId, Resp>
+where
+    ClientId: Hash + Eq + Clone,
+    Resp: Clone,
+{
+    async fn get(&self, key: &DedupKey<ClientId>) -> Option<Resp> {
+        self.inner
+            .lock()
+            .unwrap()
+            .get(key, crate::util::now())
+            .cloned()
+    }
+
+    async fn put(&self, key: DedupKey<ClientId>, response: Resp, ttl: Duration) {
+        let expires_at = crate::util::now().checked_add(ttl);
+        self.inner.lock().unwrap().insert(key, response, expires_at);
+    }
+}
+
+/// A least-recently-used map of entries that expire. Recency is tracked with a monotonically
+/// increasing tick per access; expired entries are removed when looked up or evicted.
+struct Lru<K, V> {
+    capacity: usize,
+    tick: u64,
+    /// The values, when they were last used, and when they expire, if ever.
+    entries: FnvHashMap<K, (V, u64, Option<Instant>)>,
+    recency: BTreeMap<u64, K>,
+}
+
+impl<K, V> Lru<K, V> {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            tick: 0,
+            entries: FnvHashMap::default(),
+            recency: BTreeMap::new(),
+        }
+    }
+}
+
+impl<K, V> Lru<K, V>
+where
+    K: Hash + Eq + Clone,
+{
+    fn get(&mut self, key: &K, now: Instant) -> Option<&V> {
+        let (_, last_used, expires_at) = self.entries.get(key)?;
+        if matches!(expires_at, Some(expires_at) if *expires_at <= now) {
+            self.recency.remove(last_used);
+            self.entries.remove(key);
+            return None;
+        }
+        let (value, last_used, _) = self.entries.get_mut(key)?;
+        self.recency.remove(last_used);
+        self.tick += 1;
+        *last_used = self.tick;
+        self.recency.insert(self.tick, key.clone());
+        Some(value)
+    }
+
+    fn insert(&mut self, key: K, value: V, expires_at: Option<Instant>) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.tick += 1;
+        if let Some((_, last_used, _)) = self
+            .entries
+            .insert(key.clone(), (value, self.tick, expires_at))
+        {
+            self.recency.remove(&last_used);
+        }
+        self.recency.insert(self.tick, key);
+        while self.entries.len() > self.capacity {
+            let Some(&oldest) = self.recency.keys().next() else {
+                break;
+            };
+            if let Some(evicted) = self.recency.remove(&oldest) {
+                self.entries.remove(&evicted);
+            }
+        }
+    }
+}
+
+/// A [`Serve`] wrapper that replays remembered responses for requests carrying an idempotency key
+/// that was already served for the same client.
+///
+/// Requests without an idempotency key are always executed.
+#[derive(Clone, Debug)]
+pub struct ReplayCache<Serv, ClientId, Store> {
+    serve: Serv,
+    client_id: ClientId,
+    store: Store,
+    ttl: Duration,
+}
+
+impl<Serv, ClientId, Store> ReplayCache<Serv, ClientId, Store> {
+    /// How long responses are remembered, unless [set otherwise](ReplayCache::with_ttl).
+    pub const DEFAULT_TTL: Duration = Duration::from_secs(60 * 60);
+
+    /// Returns a new `ReplayCache` that serves requests from `client_id` with `serve`, remembering
+    /// responses in `store` for [`DEFAULT_TTL`](ReplayCache::DEFAULT_TTL).
+    pub fn new(serve: Serv, client_id: ClientId, store: Store) -> Self {
+        Self {
+            serve,
+            client_id,
+            store,
+            ttl: Self::DEFAULT_TTL,
+        }
+    }
+
+    /// Sets how long responses are remembered. Retries of a request arriving later than `ttl`
+    /// after it completed are executed again.
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Returns the inner serve fn.
+    pub fn get_ref(&self) -> &Serv {
+        &self.serve
+    }
+}
+
+impl<Serv, ClientId, Store> Serve for ReplayCache<Serv, ClientId, Store>
+where
+    Serv: Serve,
+    Serv::Resp: Clone,
+    Store: DedupStore<ClientId, Serv::Resp>,
+{
+    type Req = Serv::Req;
+    type Resp = Serv::Resp;
+
+    async fn serve(self, ctx: context::Context, req: Self::Req) -> Result<Serv::Resp, ServerError> {
+        let ReplayCache {
+            serve,
+            client_id,
+            store,
+            ttl,
+        } = self;
+        let Some(idempotency_key) = ctx.idempotency_key else {
+            return serve.serve(ctx, req).await;
+        };
+        let key = DedupKey {
+            client_id,
+            method: serve.method(&req),
+            idempotency_key,
+        };
+        if let Some(response) = store.get(&key).await {
+            tracing::info!(idempotency_key, method = key.method, "ReplayResponse");
+            return Ok(response);
+        }
+        let response = serve.serve(ctx, req).await?;
+        store.put(key, response.clone(), ttl).await;
+        Ok(response)
+    }
+
+    fn method(&self, request: &Self::Req) -> Option<&'static str> {
+        self.serve.method(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::serve;
+    use futures::executor::block_on;
+    use std::{cell::Cell, io};
+
+    fn ctx_with_key(key: Option<u64>) -> context::Context {
+        let mut ctx = context::current();
+        ctx.idempotency_key = key;
+        ctx
+    }
+
+    #[test]
+    fn replays_duplicate_requests() {
+        let calls = &Cell::new(0);
+        let store = InMemoryLru::new(10);
+        let serve = serve(|_, i: i32| async move {
+            calls.set(calls.get() + 1);
+            Ok(i + calls.get())
+        });
+
+        let first = ReplayCache::new(serve, "client", store.clone());
+        assert_eq!(block_on(first.serve(ctx_with_key(Some(1)), 1)), Ok(2));
+        let duplicate = ReplayCache::new(serve, "client", store.clone());
+        assert_eq!(block_on(duplicate.serve(ctx_with_key(Some(1)), 1)), Ok(2));
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[test]
+    fn keys_are_scoped_to_clients() {
+        let calls = &Cell::new(0);
+        let store = InMemoryLru::new(10);
+        let serve = serve(|_, i: i32| async move {
+            calls.set(calls.get() + 1);
+            Ok(i)
+        });
+
+        block_on(ReplayCache::new(serve, "a", store.clone()).serve(ctx_with_key(Some(1)), 1))
+            .unwrap();
+        block_on(ReplayCache::new(serve, "b", store.clone()).serve(ctx_with_key(Some(1)), 1))
+            .unwrap();
+        assert_eq!(calls.get(), 2);
+        assert_eq!(store.len(), 2);
+    }
+
+    /// Serves requests naming their methods.
+    #[derive(Clone, Copy)]
+    struct Methods<'a>(&'a Cell<i32>);
+
+    impl Serve for Methods<'_> {
+        type Req = (&'static str, i32);
+        type Resp = String;
+
+        async fn serve(
+            self,
+            _: context::Context,
+            (method, i): Self::Req,
+        ) -> Result<String, ServerError> {
+            self.0.set(self.0.get() + 1);
+            Ok(format!("{method}({i})"))
+        }
+
+        fn method(&self, (method, _): &Self::Req) -> Option<&'static str> {
+            Some(method)
+        }
+    }
+
+    #[test]
+    fn keys_are_scoped_to_methods() {
+        let calls = &Cell::new(0);
+        let store = InMemoryLru::new(10);
+        let cache = ReplayCache::new(Methods(calls), "client", store.clone());
+
+        let get = block_on(cache.clone().serve(ctx_with_key(Some(1)), ("get", 1)));
+        assert_eq!(get.as_deref(), Ok("get(1)"));
+        let delete = block_on(cache.clone().serve(ctx_with_key(Some(1)), ("delete", 1)));
+        assert_eq!(delete.as_deref(), Ok("delete(1)"));
+        let replayed = block_on(cache.serve(ctx_with_key(Some(1)), ("get", 1)));
+        assert_eq!(replayed.as_deref(), Ok("get(1)"));
+        assert_eq!(calls.get(), 2);
+        assert_eq!(store.len(), 2);
+    }
+
+    #[test]
+    fn requests_without_keys_are_not_cached() {
+        let calls = &Cell::new(0);
+        let store = InMemoryLru::new(10);
+        let serve = serve(|_, i: i32| async move {
+            calls.set(calls.get() + 1);
+            Ok(i)
+        });
+
+        block_on(ReplayCache::new(serve, (), store.clone()).serve(ctx_with_key(None), 1)).unwrap();
+        block_on(ReplayCache::new(serve, (), store.clone()).serve(ctx_with_key(None), 1)).unwrap();
+        assert_eq!(calls.get(), 2);
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn errors_are_not_cached() {
+        let store = InMemoryLru::<(), i32>::new(10);
+        let serve = serve(|_, _: i32| async {
+            Err::<i32, _>(ServerError::new(io::ErrorKind::Other, "oops".into()))
+        });
+
+        let response =
+            block_on(ReplayCache::new(serve, (), store.clone()).serve(ctx_with_key(Some(1)), 1));
+        assert!(response.is_err());
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn responses_expire_after_the_ttl() {
+        let calls = &Cell::new(0);
+        let store = InMemoryLru::new(10);
+        let serve = serve(|_, i: i32| async move {
+            calls.set(calls.get() + 1);
+            Ok(i)
+        });
+
+        let cache = ReplayCache::new(serve, (), store.clone()).with_ttl(Duration::ZERO);
+        block_on(cache.clone().serve(ctx_with_key(Some(1)), 1)).unwrap();
+        block_on(cache.serve(ctx_with_key(Some(1)), 1)).unwrap();
+        assert_eq!(calls.get(), 2);
+    }
+
+    #[test]
+    fn lru_evicts_least_recently_used() {
+        let now = Instant::now();
+        let mut lru = Lru::new(2);
+        lru.insert(1, "one", None);
+        lru.insert(2, "two", None);
+        assert_eq!(lru.get(&1, now), Some(&"one"));
+        lru.insert(3, "three", None);
+
+        assert_eq!(lru.get(&2, now), None);
+        assert_eq!(lru.get(&1, now), Some(&"one"));
+        assert_eq!(lru.get(&3, now), Some(&"three"));
+        assert_eq!(lru.entries.len(), 2);
+        assert_eq!(lru.recency.len(), 2);
+    }
+
+    #[test]
+    fn lru_forgets_expired_entries() {
+        let now = Instant::now();
+        let mut lru = Lru::new(2);
+        lru.insert(1, "one", Some(now + Duration::from_secs(1)));
+        assert_eq!(lru.get(&1, now), Some(&"one"));
+        assert_eq!(lru.get(&1, now + Duration::from_secs(1)), None);
+        assert!(lru.entries.is_empty());
+        assert!(lru.recency.is_empty());
+    }
+
+    #[test]
+    fn lru_with_zero_capacity_stores_nothing() {
+        let mut lru = Lru::new(0);
+        lru.insert(1, "one", None);
+        assert_eq!(lru.get(&1, Instant::now()), None);
+    }
+}
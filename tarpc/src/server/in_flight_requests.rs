@@ -1,20 +1,45 @@
-use crate::util::{Compact, TimeUntil};
-use fnv::FnvHashMap;
+use crate::{
+    util::{
+        request_map::{self, RequestMap},
+        Compact, TimeUntil,
+    },
+    CancellationReason,
+};
 use futures::future::{AbortHandle, AbortRegistration};
 use std::{
-    collections::hash_map,
     task::{Context, Poll},
-    time::SystemTime,
+    time::Instant,
 };
 use tokio_util::time::delay_queue::{self, DelayQueue};
 use tracing::Span;
 
 /// A data structure that tracks in-flight requests. It aborts requests,
 /// either on demand or when a request deadline expires.
-#[derive(Debug, Default)]
+///
+/// Requests are split into shards by ID, each tracking the deadlines of its own requests, so that
+/// channels with very many requests in flight don't keep them all in one map and timer queue.
+#[derive(Debug)]
 pub struct InFlightRequests {
-    request_data: FnvHashMap<u64, RequestData>,
+    shards: Vec<Shard>,
+    /// The shard whose expired requests are yielded first, so that no shard is starved.
+    next_expired: usize,
+}
+
+/// The requests in flight of one shard.
+#[derive(Debug, Default)]
+struct Shard {
+    /// Keyed by the requests' IDs within the shard.
+    request_data: RequestMap<RequestData>,
+    /// Holds the requests' full IDs.
     deadlines: DelayQueue<u64>,
+    /// The approximate number of bytes held by the shard's requests.
+    bytes: usize,
+}
+
+impl Default for InFlightRequests {
+    fn default() -> Self {
+        Self::with_shards(1)
+    }
 }
 
 /// Data needed to clean up a single in-flight request.
@@ -26,6 +51,10 @@ struct RequestData {
     deadline_key: delay_queue::Key,
     /// The client span.
     span: Span,
+    /// Whether the client expects no response.
+    oneway: bool,
+    /// The approximate number of bytes held by the request.
+    bytes: usize,
 }
 
 /// An error returned when a request attempted to start with the same ID as a request already
@@ -34,47 +63,100 @@ struct RequestData {
 pub struct AlreadyExistsError;
 
 impl InFlightRequests {
+    /// Returns an empty set of in-flight requests split into `shards` shards.
+    pub fn with_shards(shards: usize) -> Self {
+        Self {
+            shards: (0..shards.max(1)).map(|_| Shard::default()).collect(),
+            next_expired: 0,
+        }
+    }
+
+    /// Returns the shard of `request_id` and the request's ID within it.
+    fn shard(&self, request_id: u64) -> (&Shard, u64) {
+        let (shard, key) = request_map::shard(request_id, self.shards.len());
+        (&self.shards[shard], key)
+    }
+
+    /// Returns the shard of `request_id` and the request's ID within it.
+    fn shard_mut(&mut self, request_id: u64) -> (&mut Shard, u64) {
+        let (shard, key) = request_map::shard(request_id, self.shards.len());
+        (&mut self.shards[shard], key)
+    }
+
     /// Returns the number of in-flight requests.
     pub fn len(&self) -> usize {
-        self.request_data.len()
+        self.shards
+            .iter()
+            .map(|shard| shard.request_data.len())
+            .sum()
+    }
+
+    /// Returns the approximate number of bytes held by the in-flight requests.
+    pub fn bytes(&self) -> usize {
+        self.shards.iter().map(|shard| shard.bytes).sum()
     }
 
     /// Starts a request, unless a request with the same ID is already in flight.
+    #[cfg(test)]
     pub fn start_request(
         &mut self,
         request_id: u64,
-        deadline: SystemTime,
+        deadline: Instant,
+        oneway: bool,
         span: Span,
     ) -> Result<AbortRegistration, AlreadyExistsError> {
-        match self.request_data.entry(request_id) {
-            hash_map::Entry::Vacant(vacant) => {
-                let timeout = deadline.time_until();
-                let (abort_handle, abort_registration) = AbortHandle::new_pair();
-                let deadline_key = self.deadlines.insert(request_id, timeout);
-                vacant.insert(RequestData {
-                    abort_handle,
-                    deadline_key,
-                    span,
-                });
-                Ok(abort_registration)
-            }
-            hash_map::Entry::Occupied(_) => Err(AlreadyExistsError),
+        self.start_sized_request(request_id, deadline, oneway, span, 0)
+    }
+
+    /// Starts a request holding approximately `bytes` bytes, unless a request with the same ID is
+    /// already in flight.
+    pub fn start_sized_request(
+        &mut self,
+        request_id: u64,
+        deadline: Instant,
+        oneway: bool,
+        span: Span,
+        bytes: usize,
+    ) -> Result<AbortRegistration, AlreadyExistsError> {
+        let (shard, key) = self.shard_mut(request_id);
+        if shard.request_data.contains_key(key) {
+            return Err(AlreadyExistsError);
         }
+        let timeout = deadline.time_until();
+        let (abort_handle, abort_registration) = AbortHandle::new_pair();
+        let deadline_key = shard.deadlines.insert(request_id, timeout);
+        shard.request_data.insert(
+            key,
+            RequestData {
+                abort_handle,
+                deadline_key,
+                span,
+                oneway,
+                bytes,
+            },
+        );
+        shard.bytes += bytes;
+        Ok(abort_registration)
     }
 
-    /// Cancels an in-flight request. Returns true iff the request was found.
-    pub fn cancel_request(&mut self, request_id: u64) -> bool {
+    /// Cancels an in-flight request, logging the client's `reason` in its span. Returns true iff
+    /// the request was found.
+    pub fn cancel_request(&mut self, request_id: u64, reason: CancellationReason) -> bool {
+        let (shard, key) = self.shard_mut(request_id);
         if let Some(RequestData {
             span,
             abort_handle,
             deadline_key,
-        }) = self.request_data.remove(&request_id)
+            bytes,
+            ..
+        }) = shard.request_data.remove(key)
         {
             let _entered = span.enter();
-            self.request_data.compact(0.1);
+            shard.bytes -= bytes;
+            shard.request_data.compact(0.1);
             abort_handle.abort();
-            self.deadlines.remove(&deadline_key);
-            tracing::info!("ReceiveCancel");
+            shard.deadlines.remove(&deadline_key);
+            tracing::info!(?reason, "ReceiveCancel");
             true
         } else {
             false
@@ -84,34 +166,86 @@ impl InFlightRequests {
     /// Removes a request without aborting. Returns true iff the request was found.
     /// This method should be used when a response is being sent.
     pub fn remove_request(&mut self, request_id: u64) -> Option<Span> {
-        if let Some(request_data) = self.request_data.remove(&request_id) {
-            self.request_data.compact(0.1);
-            self.deadlines.remove(&request_data.deadline_key);
+        let (shard, key) = self.shard_mut(request_id);
+        if let Some(request_data) = shard.request_data.remove(key) {
+            shard.bytes -= request_data.bytes;
+            shard.request_data.compact(0.1);
+            shard.deadlines.remove(&request_data.deadline_key);
             Some(request_data.span)
         } else {
             None
         }
     }
 
+    /// Returns the span of an in-flight request, keeping it in flight. This method should be used
+    /// when a partial response is being sent.
+    pub fn get_span(&self, request_id: u64) -> Option<&Span> {
+        let (shard, key) = self.shard(request_id);
+        shard
+            .request_data
+            .get(key)
+            .map(|request_data| &request_data.span)
+    }
+
+    /// Returns true iff the client of an in-flight request expects no response.
+    pub fn is_oneway(&self, request_id: u64) -> bool {
+        let (shard, key) = self.shard(request_id);
+        shard
+            .request_data
+            .get(key)
+            .is_some_and(|request_data| request_data.oneway)
+    }
+
     /// Yields a request that has expired, aborting any ongoing processing of that request.
     pub fn poll_expired(&mut self, cx: &mut Context) -> Poll<Option<u64>> {
+        let shards = self.shards.len();
+        let mut pending = false;
+        for i in 0..shards {
+            let shard = (self.next_expired + i) % shards;
+            match self.shards[shard].poll_expired(cx, shards) {
+                Poll::Ready(Some(request_id)) => {
+                    self.next_expired = (shard + 1) % shards;
+                    return Poll::Ready(Some(request_id));
+                }
+                Poll::Ready(None) => {}
+                Poll::Pending => pending = true,
+            }
+        }
+        if pending {
+            Poll::Pending
+        } else {
+            Poll::Ready(None)
+        }
+    }
+}
+
+impl Shard {
+    /// Yields a request of the shard, one of `shards`, that has expired, aborting any ongoing
+    /// processing of that request.
+    fn poll_expired(&mut self, cx: &mut Context, shards: usize) -> Poll<Option<u64>> {
         if self.deadlines.is_empty() {
             // TODO(https://github.com/tokio-rs/tokio/issues/4161)
             // This is a workaround for DelayQueue not always treating this case correctly.
             return Poll::Ready(None);
         }
         self.deadlines.poll_expired(cx).map(|expired| {
-            let expired = expired?;
+            let request_id = expired?.into_inner();
             if let Some(RequestData {
-                abort_handle, span, ..
-            }) = self.request_data.remove(expired.get_ref())
+                abort_handle,
+                span,
+                bytes,
+                ..
+            }) = self
+                .request_data
+                .remove(request_map::shard(request_id, shards).1)
             {
                 let _entered = span.enter();
+                self.bytes -= bytes;
                 self.request_data.compact(0.1);
                 abort_handle.abort();
                 tracing::error!("DeadlineExceeded");
             }
-            Some(expired.into_inner())
+            Some(request_id)
         })
     }
 }
@@ -119,8 +253,9 @@ impl InFlightRequests {
 /// When InFlightRequests is dropped, any outstanding requests are aborted.
 impl Drop for InFlightRequests {
     fn drop(&mut self) {
-        self.request_data
-            .values()
+        self.shards
+            .iter()
+            .flat_map(|shard| shard.request_data.values())
             .for_each(|request_data| request_data.abort_handle.abort())
     }
 }
@@ -136,12 +271,68 @@ mod tests {
     };
     use futures_test::task::noop_context;
 
+    #[tokio::test]
+    async fn sharded_requests_are_tracked_and_expire() {
+        let mut in_flight_requests = InFlightRequests::with_shards(4);
+        let deadline = Instant::now() + std::time::Duration::from_secs(10);
+        for request_id in 0..10 {
+            in_flight_requests
+                .start_request(request_id, deadline, request_id == 5, Span::current())
+                .unwrap();
+        }
+        assert_eq!(in_flight_requests.len(), 10);
+        assert_matches!(
+            in_flight_requests.start_request(6, deadline, false, Span::current()),
+            Err(AlreadyExistsError)
+        );
+        assert!(in_flight_requests.is_oneway(5));
+        assert!(!in_flight_requests.is_oneway(6));
+        assert!(in_flight_requests.cancel_request(6, CancellationReason::Dropped));
+        assert_matches!(in_flight_requests.remove_request(7), Some(_));
+        assert_matches!(in_flight_requests.remove_request(7), None);
+
+        tokio::time::pause();
+        tokio::time::advance(std::time::Duration::from_secs(1000)).await;
+        let mut expired = vec![];
+        while let Poll::Ready(Some(request_id)) =
+            in_flight_requests.poll_expired(&mut noop_context())
+        {
+            expired.push(request_id);
+        }
+        expired.sort_unstable();
+        assert_eq!(expired, [0, 1, 2, 3, 4, 5, 8, 9]);
+        assert_eq!(in_flight_requests.len(), 0);
+    }
+
+    #[tokio::test]
+    async fn bytes_are_released_with_their_requests() {
+        let mut in_flight_requests = InFlightRequests::with_shards(2);
+        let deadline = Instant::now() + std::time::Duration::from_secs(10);
+        for request_id in 0..3 {
+            in_flight_requests
+                .start_sized_request(request_id, deadline, false, Span::current(), 100)
+                .unwrap();
+        }
+        assert_eq!(in_flight_requests.bytes(), 300);
+        in_flight_requests.remove_request(0);
+        in_flight_requests.cancel_request(1, CancellationReason::Dropped);
+        assert_eq!(in_flight_requests.bytes(), 100);
+
+        tokio::time::pause();
+        tokio::time::advance(std::time::Duration::from_secs(1000)).await;
+        assert_matches!(
+            in_flight_requests.poll_expired(&mut noop_context()),
+            Poll::Ready(Some(2))
+        );
+        assert_eq!(in_flight_requests.bytes(), 0);
+    }
+
     #[tokio::test]
     async fn start_request_increases_len() {
         let mut in_flight_requests = InFlightRequests::default();
         assert_eq!(in_flight_requests.len(), 0);
         in_flight_requests
-            .start_request(0, SystemTime::now(), Span::current())
+            .start_request(0, Instant::now(), false, Span::current())
             .unwrap();
         assert_eq!(in_flight_requests.len(), 1);
     }
@@ -150,7 +341,7 @@ mod tests {
     async fn polling_expired_aborts() {
         let mut in_flight_requests = InFlightRequests::default();
         let abort_registration = in_flight_requests
-            .start_request(0, SystemTime::now(), Span::current())
+            .start_request(0, Instant::now(), false, Span::current())
             .unwrap();
         let mut abortable_future = Box::new(Abortable::new(pending::<()>(), abort_registration));
 
@@ -172,11 +363,11 @@ mod tests {
     async fn cancel_request_aborts() {
         let mut in_flight_requests = InFlightRequests::default();
         let abort_registration = in_flight_requests
-            .start_request(0, SystemTime::now(), Span::current())
+            .start_request(0, Instant::now(), false, Span::current())
             .unwrap();
         let mut abortable_future = Box::new(Abortable::new(pending::<()>(), abort_registration));
 
-        assert!(in_flight_requests.cancel_request(0));
+        assert!(in_flight_requests.cancel_request(0, CancellationReason::Dropped));
         assert_matches!(
             abortable_future.poll_unpin(&mut noop_context()),
             Poll::Ready(Err(_))
@@ -187,12 +378,13 @@ mod tests {
     #[tokio::test]
     async fn remove_request_doesnt_abort() {
         let mut in_flight_requests = InFlightRequests::default();
-        assert!(in_flight_requests.deadlines.is_empty());
+        assert!(in_flight_requests.shards[0].deadlines.is_empty());
 
         let abort_registration = in_flight_requests
             .start_request(
                 0,
-                SystemTime::now() + std::time::Duration::from_secs(10),
+                Instant::now() + std::time::Duration::from_secs(10),
+                false,
                 Span::current(),
             )
             .unwrap();
@@ -203,11 +395,11 @@ mod tests {
             in_flight_requests.poll_expired(&mut noop_context()),
             Poll::Pending
         );
-        assert!(!in_flight_requests.deadlines.is_empty());
+        assert!(!in_flight_requests.shards[0].deadlines.is_empty());
 
         assert_matches!(in_flight_requests.remove_request(0), Some(_));
         // Postcondition: No pending expirations
-        assert!(in_flight_requests.deadlines.is_empty());
+        assert!(in_flight_requests.shards[0].deadlines.is_empty());
         assert_matches!(
             in_flight_requests.poll_expired(&mut noop_context()),
             Poll::Ready(None)
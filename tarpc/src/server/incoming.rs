@@ -1,4 +1,6 @@
 use super::{
+    broadcast::{Broadcaster, SubscribeChannels},
+    execution::RequestExecution,
     limits::{channels_per_key::MaxChannelsPerKey, requests_per_channel::MaxRequestsPerChannel},
     Channel, Serve,
 };
@@ -25,12 +27,25 @@ where
         MaxRequestsPerChannel::new(self, n)
     }
 
+    /// Subscribes each channel to `broadcaster` with the key returned by `keymaker`, until the
+    /// channel closes or is dropped.
+    fn subscribe_to<K, Msg, KF>(
+        self,
+        broadcaster: Broadcaster<K, Msg>,
+        keymaker: KF,
+    ) -> SubscribeChannels<Self, K, Msg, KF>
+    where
+        KF: Fn(&C) -> K,
+    {
+        SubscribeChannels::new(self, broadcaster, keymaker)
+    }
+
     /// Returns a stream of channels in execution. Each channel in execution is a stream of
     /// futures, where each future is an in-flight request being rsponded to.
     fn execute<S>(
         self,
         serve: S,
-    ) -> impl Stream<Item = impl Stream<Item = impl Future<Output = ()>>>
+    ) -> impl Stream<Item = impl Stream<Item = RequestExecution<impl Future<Output = ()>>>>
     where
         S: Serve<Req = C::Req, Resp = C::Resp> + Clone,
     {
@@ -84,6 +99,52 @@ pub async fn spawn_incoming(
     }
 }
 
+#[cfg(feature = "tokio1")]
+/// Spawns all channels-in-execution, like [`spawn_incoming`], but drives the requests of each
+/// channel according to `strategy`: each request is either spawned or driven inline by its
+/// channel's task.
+///
+/// # Example
+/// ```rust
+/// use tarpc::{
+///     context,
+///     client::{self, NewClient},
+///     server::{
+///         self,
+///         execution::{Execution, ExecutionStrategy},
+///         incoming::{spawn_incoming_with, Incoming},
+///         serve, BaseChannel,
+///     },
+///     transport,
+/// };
+/// use futures::prelude::*;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let (tx, rx) = transport::channel::unbounded();
+///     let NewClient { client, dispatch } = client::new(client::Config::default(), tx);
+///     tokio::spawn(dispatch);
+///
+///     let incoming = stream::once(async move {
+///         BaseChannel::new(server::Config::default(), rx)
+///     }).execute(serve(|_, i| async move { Ok(i + 1) }));
+///     tokio::spawn(spawn_incoming_with(incoming, ExecutionStrategy::new(Execution::Inline)));
+///     assert_eq!(client.call(context::current(), "AddOne", 1).await.unwrap(), 2);
+/// }
+/// ```
+pub async fn spawn_incoming_with<Fut>(
+    incoming: impl Stream<Item = impl Stream<Item = RequestExecution<Fut>> + Send + 'static>,
+    strategy: super::execution::ExecutionStrategy,
+) where
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    use futures::pin_mut;
+    pin_mut!(incoming);
+    while let Some(channel) = incoming.next().await {
+        strategy.spawn(super::execution::run(channel, strategy.clone()));
+    }
+}
+
 impl<S, C> Incoming<C> for S
 where
     S: Sized + Stream<Item = C>,
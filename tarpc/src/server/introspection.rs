@@ -0,0 +1,275 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Provides a [`Serve`] wrapper that records which requests are executing, so that they can be
+//! inspected while they run.
+//!
+//! An [`InFlightRegistry`] tracks the requests executed by every serve fn wrapped with it. Share
+//! one registry among all channels of a server to enumerate the requests of the whole server, or
+//! use one per channel. A [snapshot](InFlightRegistry::snapshot) lists each request's method,
+//! trace ID, age, and deadline, which is useful for debugging stuck handlers from an admin
+//! endpoint or a signal handler.
+//!
+//! # Example
+//!
+//! ```rust
+//! use futures::{channel::oneshot, executor::block_on, prelude::*};
+//! use tarpc::{
+//!     context,
+//!     server::{introspection::{InFlightRegistry, Introspect}, serve, Serve},
+//! };
+//!
+//! let registry = InFlightRegistry::new();
+//! let (tx, rx) = oneshot::channel::<()>();
+//! let serve = Introspect::new(serve(|_, rx: oneshot::Receiver<()>| async move {
+//!     rx.await.unwrap();
+//!     Ok(())
+//! }), registry.clone());
+//!
+//! let mut response = Box::pin(serve.serve(context::current(), rx));
+//! assert!((&mut response).now_or_never().is_none());
+//! assert_eq!(registry.snapshot().len(), 1);
+//!
+//! tx.send(()).unwrap();
+//! block_on(response).unwrap();
+//! assert!(registry.snapshot().is_empty());
+//! ```
+
+use crate::{context, server::Serve, trace, util, ServerError};
+use fnv::FnvHashMap;
+use std::{
+    cmp::Reverse,
+    fmt,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// A description of a request that is executing.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct InFlightRequestInfo {
+    /// The name of the method called, if the serve fn knows it.
+    pub method: Option<&'static str>,
+    /// The ID of the trace the request belongs to.
+    pub trace_id: trace::TraceId,
+    /// How long the request has been executing.
+    pub age: Duration,
+    /// When the client will stop waiting for a response.
+    pub deadline: Instant,
+}
+
+impl fmt::Display for InFlightRequestInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} trace_id={} age={:?} deadline={}",
+            self.method.unwrap_or("<unknown>"),
+            self.trace_id,
+            self.age,
+            humantime::format_rfc3339(crate::util::system_time(self.deadline)),
+        )
+    }
+}
+
+/// Tracks the requests that are executing.
+///
+/// Clones share the same state.
+#[derive(Clone, Default)]
+pub struct InFlightRegistry {
+    requests: Arc<Mutex<Requests>>,
+}
+
+#[derive(Default)]
+struct Requests {
+    next_key: u64,
+    entries: FnvHashMap<u64, Entry>,
+}
+
+struct Entry {
+    method: Option<&'static str>,
+    trace_id: trace::TraceId,
+    started_at: Instant,
+    deadline: Instant,
+}
+
+impl InFlightRegistry {
+    /// Returns a new registry with no requests.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the number of requests executing.
+    pub fn len(&self) -> usize {
+        self.requests.lock().unwrap().entries.len()
+    }
+
+    /// Returns true iff no requests are executing.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the requests executing, oldest first.
+    pub fn snapshot(&self) -> Vec<InFlightRequestInfo> {
+        let now = util::now();
+        let mut snapshot: Vec<_> = self
+            .requests
+            .lock()
+            .unwrap()
+            .entries
+            .values()
+            .map(|entry| InFlightRequestInfo {
+                method: entry.method,
+                trace_id: entry.trace_id,
+                age: now.saturating_duration_since(entry.started_at),
+                deadline: entry.deadline,
+            })
+            .collect();
+        snapshot.sort_by_key(|info| Reverse(info.age));
+        snapshot
+    }
+
+    /// Records a request until the returned registration is dropped.
+    fn register(&self, method: Option<&'static str>, ctx: &context::Context) -> Registration {
+        let mut requests = self.requests.lock().unwrap();
+        let key = requests.next_key;
+        requests.next_key += 1;
+        requests.entries.insert(
+            key,
+            Entry {
+                method,
+                trace_id: *ctx.trace_id(),
+                started_at: util::now(),
+                deadline: ctx.deadline,
+            },
+        );
+        Registration {
+            key,
+            requests: self.requests.clone(),
+        }
+    }
+}
+
+impl fmt::Debug for InFlightRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InFlightRegistry")
+            .field("requests", &self.len())
+            .finish()
+    }
+}
+
+/// Removes a request from its registry when dropped, whether the request completed or was
+/// aborted.
+struct Registration {
+    key: u64,
+    requests: Arc<Mutex<Requests>>,
+}
+
+impl Drop for Registration {
+    fn drop(&mut self) {
+        self.requests.lock().unwrap().entries.remove(&self.key);
+    }
+}
+
+/// A [`Serve`] wrapper that records requests in an [`InFlightRegistry`] while they execute.
+#[derive(Clone, Debug)]
+pub struct Introspect<Serv> {
+    serve: Serv,
+    registry: InFlightRegistry,
+}
+
+impl<Serv> Introspect<Serv> {
+    /// Returns a new `Introspect` that serves requests with `serve`, recording them in
+    /// `registry`.
+    pub fn new(serve: Serv, registry: InFlightRegistry) -> Self {
+        Self { serve, registry }
+    }
+
+    /// Returns the inner serve fn.
+    pub fn get_ref(&self) -> &Serv {
+        &self.serve
+    }
+
+    /// Returns the registry requests are recorded in.
+    pub fn registry(&self) -> &InFlightRegistry {
+        &self.registry
+    }
+}
+
+impl<Serv> Serve for Introspect<Serv>
+where
+    Serv: Serve,
+{
+    type Req = Serv::Req;
+    type Resp = Serv::Resp;
+
+    async fn serve(self, ctx: context::Context, req: Self::Req) -> Result<Serv::Resp, ServerError> {
+        let _registration = self.registry.register(self.serve.method(&req), &ctx);
+        self.serve.serve(ctx, req).await
+    }
+
+    fn method(&self, request: &Self::Req) -> Option<&'static str> {
+        self.serve.method(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::channel::oneshot;
+
+    #[derive(Clone, Copy)]
+    struct Named;
+
+    impl Serve for Named {
+        type Req = oneshot::Receiver<()>;
+        type Resp = ();
+
+        async fn serve(
+            self,
+            _: context::Context,
+            rx: oneshot::Receiver<()>,
+        ) -> Result<(), ServerError> {
+            let _ = rx.await;
+            Ok(())
+        }
+
+        fn method(&self, _: &oneshot::Receiver<()>) -> Option<&'static str> {
+            Some("Named.wait")
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn snapshot_lists_requests_oldest_first() {
+        let registry = InFlightRegistry::new();
+        let (tx1, rx1) = oneshot::channel();
+        let ctx1 = context::current();
+        let first = tokio::spawn(Introspect::new(Named, registry.clone()).serve(ctx1.clone(), rx1));
+        tokio::task::yield_now().await;
+
+        tokio::time::advance(Duration::from_secs(3)).await;
+        let (_tx2, rx2) = oneshot::channel();
+        let second =
+            tokio::spawn(Introspect::new(Named, registry.clone()).serve(context::current(), rx2));
+        tokio::task::yield_now().await;
+        tokio::time::advance(Duration::from_secs(1)).await;
+
+        let snapshot = registry.snapshot();
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot[0].method, Some("Named.wait"));
+        assert_eq!(snapshot[0].trace_id, *ctx1.trace_id());
+        assert_eq!(snapshot[0].deadline, ctx1.deadline);
+        assert_eq!(snapshot[0].age, Duration::from_secs(4));
+        assert_eq!(snapshot[1].age, Duration::from_secs(1));
+
+        tx1.send(()).unwrap();
+        first.await.unwrap().unwrap();
+        assert_eq!(registry.len(), 1);
+
+        second.abort();
+        let _ = second.await;
+        assert!(registry.is_empty());
+    }
+}
@@ -0,0 +1,154 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Provides a lame-duck mode, in which a server rejects new requests so that clients move to
+//! other servers before it shuts down.
+//!
+//! A [`LameDuck`] switch is shared by the serve fns of a server, each wrapped in a [`Drainable`].
+//! Once the switch is [entered](LameDuck::enter), requests are answered with a
+//! [draining](crate::ServerError::draining) error without running the handler, while requests
+//! already executing run to completion. Connections stay open, so that clients learn of the drain
+//! from the response, and [balancers](crate::client::RpcError::server_error_reason) can send their
+//! requests elsewhere.
+//!
+//! # Example
+//!
+//! ```rust
+//! use futures::executor::block_on;
+//! use tarpc::{
+//!     context,
+//!     server::{lame_duck::{Drainable, LameDuck}, serve, Serve},
+//!     ServerErrorReason,
+//! };
+//!
+//! let lame_duck = LameDuck::new();
+//! let serve = Drainable::new(serve(|_, i: i32| async move { Ok(i + 1) }), lame_duck.clone());
+//! assert_eq!(block_on(serve.clone().serve(context::current(), 1)), Ok(2));
+//!
+//! lame_duck.enter();
+//! let error = block_on(serve.serve(context::current(), 1)).unwrap_err();
+//! assert_eq!(error.reason, Some(ServerErrorReason::Draining));
+//! ```
+
+use crate::{context, server::Serve, ServerError};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// A switch that puts a server in lame-duck mode.
+///
+/// Clones share the same state.
+#[derive(Clone, Debug, Default)]
+pub struct LameDuck {
+    draining: Arc<AtomicBool>,
+}
+
+impl LameDuck {
+    /// Returns a new switch, initially serving requests.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts draining: new requests are rejected.
+    pub fn enter(&self) {
+        if !self.draining.swap(true, Ordering::Relaxed) {
+            tracing::info!("EnterLameDuck");
+        }
+    }
+
+    /// Stops draining: new requests are served again.
+    pub fn exit(&self) {
+        if self.draining.swap(false, Ordering::Relaxed) {
+            tracing::info!("ExitLameDuck");
+        }
+    }
+
+    /// Returns true iff new requests are rejected.
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+}
+
+/// A [`Serve`] wrapper that rejects requests while its [`LameDuck`] switch is entered.
+#[derive(Clone, Debug)]
+pub struct Drainable<Serv> {
+    serve: Serv,
+    lame_duck: LameDuck,
+}
+
+impl<Serv> Drainable<Serv> {
+    /// Returns a new `Drainable` that serves requests with `serve` unless `lame_duck` is entered.
+    pub fn new(serve: Serv, lame_duck: LameDuck) -> Self {
+        Self { serve, lame_duck }
+    }
+
+    /// Returns the inner serve fn.
+    pub fn get_ref(&self) -> &Serv {
+        &self.serve
+    }
+}
+
+impl<Serv> Serve for Drainable<Serv>
+where
+    Serv: Serve,
+{
+    type Req = Serv::Req;
+    type Resp = Serv::Resp;
+
+    async fn serve(self, ctx: context::Context, req: Self::Req) -> Result<Serv::Resp, ServerError> {
+        if self.lame_duck.is_draining() {
+            tracing::info!("RejectDraining");
+            return Err(ServerError::draining("server is draining.".into()));
+        }
+        self.serve.serve(ctx, req).await
+    }
+
+    fn method(&self, request: &Self::Req) -> Option<&'static str> {
+        self.serve.method(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{client::RpcError, server::serve, ServerErrorReason};
+    use futures::{channel::oneshot, executor::block_on, prelude::*};
+
+    #[test]
+    fn executing_requests_complete_while_draining() {
+        let lame_duck = LameDuck::new();
+        let (tx, rx) = oneshot::channel::<()>();
+        let blocked = serve(|_, rx: oneshot::Receiver<()>| async move {
+            rx.await.unwrap();
+            Ok(())
+        });
+        let mut in_flight =
+            Box::pin(Drainable::new(blocked, lame_duck.clone()).serve(context::current(), rx));
+        assert!((&mut in_flight).now_or_never().is_none());
+
+        lame_duck.enter();
+        let (_tx, rx) = oneshot::channel::<()>();
+        let error =
+            block_on(Drainable::new(blocked, lame_duck.clone()).serve(context::current(), rx))
+                .unwrap_err();
+        assert_eq!(
+            RpcError::from(error).server_error_reason(),
+            Some(ServerErrorReason::Draining)
+        );
+
+        tx.send(()).unwrap();
+        assert_eq!(block_on(in_flight), Ok(()));
+
+        lame_duck.exit();
+        let (tx, rx) = oneshot::channel::<()>();
+        tx.send(()).unwrap();
+        assert_eq!(
+            block_on(Drainable::new(blocked, lame_duck).serve(context::current(), rx)),
+            Ok(())
+        );
+    }
+}
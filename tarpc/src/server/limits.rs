@@ -1,5 +1,15 @@
 /// Provides functionality to limit the number of active channels.
 pub mod channels_per_key;
 
+/// Provides a [serve fn](crate::server::Serve) that queues requests over a concurrency limit for a
+/// bounded time.
+pub mod request_queue;
+
 /// Provides a [channel](crate::server::Channel) that limits the number of in-flight requests.
 pub mod requests_per_channel;
+
+/// Provides the limit enforced on methods declared with `#[tarpc::max_concurrent = N]`.
+pub mod requests_per_method;
+
+/// Provides a [serve fn](crate::server::Serve) that limits the request rate of each peer.
+pub mod requests_per_peer;
@@ -0,0 +1,258 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Provides a [`Serve`] wrapper that limits the number of requests executing at once, queueing
+//! the excess for a bounded time.
+//!
+//! A [`RequestQueue`] is shared by the serve fns it limits, typically those of all channels of a
+//! server. Once [`max_concurrent_requests`](QueueLimits::max_concurrent_requests) requests are
+//! executing, further requests wait in a first-in, first-out queue. A request is rejected rather
+//! than executed if:
+//!
+//! * the queue already holds [`max_queued_requests`](QueueLimits::max_queued_requests) requests;
+//! * it waited in the queue for longer than the
+//!   [queueing budget](QueueLimits::max_queueing_time); or
+//! * its deadline passed while it waited, because the client has already given up on it.
+//!
+//! # Example
+//!
+//! ```rust
+//! use std::time::Duration;
+//! use tarpc::{
+//!     server::{
+//!         self,
+//!         limits::request_queue::{QueueLimits, Queued, RequestQueue},
+//!         Channel,
+//!     },
+//!     transport::channel,
+//! };
+//!
+//! let queue = RequestQueue::new(QueueLimits::new(100, 1_000, Duration::from_millis(500)));
+//! let serve = server::serve(|_, i: i32| async move { Ok(i + 1) });
+//!
+//! let (_client, transport) = channel::unbounded();
+//! let requests = server::BaseChannel::with_defaults(transport)
+//!     .execute(Queued::new(serve, queue.clone()));
+//! # drop(requests);
+//! ```
+
+use crate::{context, server::Serve, ServerError};
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+/// The limits applied by a [`RequestQueue`].
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct QueueLimits {
+    /// The maximum number of requests that may execute at once.
+    pub max_concurrent_requests: usize,
+    /// The maximum number of requests that may wait to execute.
+    pub max_queued_requests: usize,
+    /// The maximum time a request may wait to execute.
+    pub max_queueing_time: Duration,
+}
+
+impl QueueLimits {
+    /// Returns limits allowing `max_concurrent_requests` requests to execute at once, while up to
+    /// `max_queued_requests` more wait for at most `max_queueing_time`.
+    pub fn new(
+        max_concurrent_requests: usize,
+        max_queued_requests: usize,
+        max_queueing_time: Duration,
+    ) -> Self {
+        Self {
+            max_concurrent_requests,
+            max_queued_requests,
+            max_queueing_time,
+        }
+    }
+}
+
+/// Tracks the requests executing and waiting to execute.
+///
+/// Clones share the same state, so the serve fns of all channels of a server should share a
+/// single queue to limit the server as a whole.
+#[derive(Clone, Debug)]
+pub struct RequestQueue {
+    limits: QueueLimits,
+    executing: Arc<Semaphore>,
+    queued: Arc<AtomicUsize>,
+}
+
+impl RequestQueue {
+    /// Returns a new, empty queue that applies `limits`.
+    pub fn new(limits: QueueLimits) -> Self {
+        Self {
+            limits,
+            executing: Arc::new(Semaphore::new(limits.max_concurrent_requests)),
+            queued: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// Returns the limits applied by the queue.
+    pub fn limits(&self) -> &QueueLimits {
+        &self.limits
+    }
+
+    /// Returns the number of requests waiting to execute.
+    pub fn queued_requests(&self) -> usize {
+        self.queued.load(Ordering::Relaxed)
+    }
+
+    /// Waits until the request described by `ctx` may execute. The returned permit counts toward
+    /// the executing requests until dropped.
+    async fn admit(&self, ctx: &context::Context) -> Result<OwnedSemaphorePermit, ServerError> {
+        if let Ok(permit) = self.executing.clone().try_acquire_owned() {
+            return Ok(permit);
+        }
+        if self.queued.fetch_add(1, Ordering::Relaxed) >= self.limits.max_queued_requests {
+            self.queued.fetch_sub(1, Ordering::Relaxed);
+            tracing::info!(
+                max_queued_requests = self.limits.max_queued_requests,
+                "QueueFull"
+            );
+            return Err(ServerError::overloaded(
+                "server request queue is full.".into(),
+                self.limits.max_queueing_time,
+            ));
+        }
+        let _dequeue = Dequeue(&self.queued);
+
+        let budget = self.limits.max_queueing_time.min(ctx.time_remaining());
+        match tokio::time::timeout(budget, self.executing.clone().acquire_owned()).await {
+            Ok(Ok(permit)) => Ok(permit),
+            // The semaphore is never closed.
+            Ok(Err(_)) | Err(_) => {
+                tracing::info!(queueing_time = ?budget, "QueueTimeout");
+                Err(ServerError::new(
+                    io::ErrorKind::TimedOut,
+                    format!("request waited {budget:?} to execute."),
+                ))
+            }
+        }
+    }
+}
+
+/// Removes a request from the count of queued requests when dropped.
+struct Dequeue<'a>(&'a AtomicUsize);
+
+impl Drop for Dequeue<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+/// A [`Serve`] wrapper that executes requests once admitted by a [`RequestQueue`].
+#[derive(Clone, Debug)]
+pub struct Queued<Serv> {
+    serve: Serv,
+    queue: RequestQueue,
+}
+
+impl<Serv> Queued<Serv> {
+    /// Returns a new `Queued` that serves requests with `serve` once `queue` admits them.
+    pub fn new(serve: Serv, queue: RequestQueue) -> Self {
+        Self { serve, queue }
+    }
+
+    /// Returns the inner serve fn.
+    pub fn get_ref(&self) -> &Serv {
+        &self.serve
+    }
+}
+
+impl<Serv> Serve for Queued<Serv>
+where
+    Serv: Serve,
+{
+    type Req = Serv::Req;
+    type Resp = Serv::Resp;
+
+    async fn serve(self, ctx: context::Context, req: Self::Req) -> Result<Serv::Resp, ServerError> {
+        let _permit = self.queue.admit(&ctx).await?;
+        self.serve.serve(ctx, req).await
+    }
+
+    fn method(&self, request: &Self::Req) -> Option<&'static str> {
+        self.serve.method(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::serve;
+    use futures::channel::oneshot;
+    use std::time::Instant;
+
+    fn blocked(
+        queue: &RequestQueue,
+    ) -> (
+        oneshot::Sender<()>,
+        tokio::task::JoinHandle<Result<(), ServerError>>,
+    ) {
+        let (tx, rx) = oneshot::channel::<()>();
+        let serve = serve(|_, rx: oneshot::Receiver<()>| async move {
+            let _ = rx.await;
+            Ok(())
+        });
+        let handle = tokio::spawn(Queued::new(serve, queue.clone()).serve(context::current(), rx));
+        (tx, handle)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn queued_request_runs_when_slot_frees() {
+        let queue = RequestQueue::new(QueueLimits::new(1, 1, Duration::from_secs(10)));
+        let (tx1, first) = blocked(&queue);
+        tokio::task::yield_now().await;
+        let (tx2, second) = blocked(&queue);
+        tokio::task::yield_now().await;
+        assert_eq!(queue.queued_requests(), 1);
+
+        tx1.send(()).unwrap();
+        first.await.unwrap().unwrap();
+        tx2.send(()).unwrap();
+        second.await.unwrap().unwrap();
+        assert_eq!(queue.queued_requests(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn rejects_requests_over_queue_limits() {
+        let queue = RequestQueue::new(QueueLimits::new(1, 1, Duration::from_secs(1)));
+        let (_tx1, _first) = blocked(&queue);
+        tokio::task::yield_now().await;
+        let (_tx2, second) = blocked(&queue);
+        tokio::task::yield_now().await;
+
+        let (_tx3, third) = blocked(&queue);
+        let error = third.await.unwrap().unwrap_err();
+        assert_eq!(error.kind, io::ErrorKind::WouldBlock);
+        assert_eq!(error.retry_after, Some(Duration::from_secs(1)));
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        let error = second.await.unwrap().unwrap_err();
+        assert_eq!(error.kind, io::ErrorKind::TimedOut);
+        assert_eq!(queue.queued_requests(), 0);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn waits_no_longer_than_deadline() {
+        let queue = RequestQueue::new(QueueLimits::new(0, 1, Duration::from_secs(10)));
+        let mut ctx = context::current();
+        ctx.deadline = Instant::now();
+        let unit = serve(|_, _: ()| async { Ok(()) });
+
+        let error = Queued::new(unit, queue).serve(ctx, ()).await.unwrap_err();
+        assert_eq!(error.kind, io::ErrorKind::TimedOut);
+    }
+}
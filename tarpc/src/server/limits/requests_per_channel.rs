@@ -10,10 +10,13 @@ use crate::{
 };
 use futures::{prelude::*, ready, task::*};
 use pin_project::pin_project;
-use std::{io, pin::Pin};
+use std::{io, pin::Pin, time::Duration};
 
 /// A [`Channel`] that limits the number of concurrent requests by throttling.
 ///
+/// Throttled requests are read from the transport, then answered with an error. To instead leave
+/// excess requests unread in the transport, set [`Config::max_in_flight_requests`].
+///
 /// Note that this is a very basic throttling heuristic. It is easy to set a number that is too low
 /// for the resources available to the server. For production use cases, a more advanced throttler
 /// is likely needed.
@@ -21,6 +24,7 @@ use std::{io, pin::Pin};
 #[derive(Debug)]
 pub struct MaxRequests<C> {
     max_in_flight_requests: usize,
+    retry_after: Option<Duration>,
     #[pin]
     inner: C,
 }
@@ -41,9 +45,16 @@ where
     pub fn new(inner: C, max_in_flight_requests: usize) -> Self {
         MaxRequests {
             max_in_flight_requests,
+            retry_after: None,
             inner,
         }
     }
+
+    /// Suggests that clients of throttled requests wait `retry_after` before sending them again.
+    pub fn with_retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = Some(retry_after);
+        self
+    }
 }
 
 impl<C> Stream for MaxRequests<C>
@@ -65,12 +76,16 @@ where
                         "ThrottleRequest",
                     );
 
+                    let mut error = ServerError::new(
+                        io::ErrorKind::WouldBlock,
+                        "server throttled the request.".into(),
+                    );
+                    error.retry_after = *self.as_mut().project().retry_after;
                     self.as_mut().start_send(Response {
                         request_id: r.request.id,
-                        message: Err(ServerError {
-                            kind: io::ErrorKind::WouldBlock,
-                            detail: "server throttled the request.".into(),
-                        }),
+                        message: Err(error),
+                        extensions: Default::default(),
+                        partial: false,
                     })?;
                 }
                 None => return Poll::Ready(None),
@@ -185,7 +200,7 @@ mod tests {
     use pin_utils::pin_mut;
     use std::{
         marker::PhantomData,
-        time::{Duration, SystemTime},
+        time::{Duration, Instant},
     };
     use tracing::Span;
 
@@ -193,6 +208,7 @@ mod tests {
     async fn throttler_in_flight_requests() {
         let throttler = MaxRequests {
             max_in_flight_requests: 0,
+            retry_after: None,
             inner: FakeChannel::default::<isize, isize>(),
         };
 
@@ -203,7 +219,8 @@ mod tests {
                 .in_flight_requests
                 .start_request(
                     i,
-                    SystemTime::now() + Duration::from_secs(1),
+                    Instant::now() + Duration::from_secs(1),
+                    false,
                     Span::current(),
                 )
                 .unwrap();
@@ -215,6 +232,7 @@ mod tests {
     fn throttler_poll_next_done() {
         let throttler = MaxRequests {
             max_in_flight_requests: 0,
+            retry_after: None,
             inner: FakeChannel::default::<isize, isize>(),
         };
 
@@ -226,6 +244,7 @@ mod tests {
     fn throttler_poll_next_some() -> io::Result<()> {
         let throttler = MaxRequests {
             max_in_flight_requests: 1,
+            retry_after: None,
             inner: FakeChannel::default::<isize, isize>(),
         };
 
@@ -246,6 +265,7 @@ mod tests {
     fn throttler_poll_next_throttled() {
         let throttler = MaxRequests {
             max_in_flight_requests: 0,
+            retry_after: None,
             inner: FakeChannel::default::<isize, isize>(),
         };
 
@@ -253,15 +273,31 @@ mod tests {
         throttler.inner.push_req(1, 1);
         assert!(throttler.as_mut().poll_next(&mut testing::cx()).is_done());
         assert_eq!(throttler.inner.sink.len(), 1);
-        let resp = throttler.inner.sink.get(0).unwrap();
+        let resp = throttler.inner.sink.front().unwrap();
         assert_eq!(resp.request_id, 1);
         assert!(resp.message.is_err());
     }
 
+    #[test]
+    fn throttler_poll_next_throttled_with_retry_after() {
+        let throttler = MaxRequests::new(FakeChannel::default::<isize, isize>(), 0)
+            .with_retry_after(Duration::from_secs(3));
+
+        pin_mut!(throttler);
+        throttler.inner.push_req(1, 1);
+        assert!(throttler.as_mut().poll_next(&mut testing::cx()).is_done());
+        let resp = throttler.inner.sink.front().unwrap();
+        assert_eq!(
+            resp.message.as_ref().unwrap_err().retry_after,
+            Some(Duration::from_secs(3))
+        );
+    }
+
     #[test]
     fn throttler_poll_next_throttled_sink_not_ready() {
         let throttler = MaxRequests {
             max_in_flight_requests: 0,
+            retry_after: None,
             inner: PendingSink::default::<isize, isize>(),
         };
         pin_mut!(throttler);
@@ -317,6 +353,7 @@ mod tests {
     async fn throttler_start_send() {
         let throttler = MaxRequests {
             max_in_flight_requests: 0,
+            retry_after: None,
             inner: FakeChannel::default::<isize, isize>(),
         };
 
@@ -326,7 +363,8 @@ mod tests {
             .in_flight_requests
             .start_request(
                 0,
-                SystemTime::now() + Duration::from_secs(1),
+                Instant::now() + Duration::from_secs(1),
+                false,
                 Span::current(),
             )
             .unwrap();
@@ -335,14 +373,18 @@ mod tests {
             .start_send(Response {
                 request_id: 0,
                 message: Ok(1),
+                extensions: Default::default(),
+                partial: false,
             })
             .unwrap();
         assert_eq!(throttler.inner.in_flight_requests.len(), 0);
         assert_eq!(
-            throttler.inner.sink.get(0),
+            throttler.inner.sink.front(),
             Some(&Response {
                 request_id: 0,
                 message: Ok(1),
+                extensions: Default::default(),
+                partial: false,
             })
         );
     }
@@ -0,0 +1,108 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Provides a limit on the number of requests of a single method executing at once.
+//!
+//! A service method declared with `#[tarpc::max_concurrent = N]` is served by at most `N`
+//! requests at once, across all channels of the process, so that expensive methods can't
+//! overwhelm it even when the overall limits are generous. Further requests of the method wait
+//! for one to finish, and are rejected if their deadline passes first. Response streams count
+//! toward the limit until they end.
+//!
+//! # Example
+//!
+//! ```rust
+//! #[tarpc::service]
+//! trait Reports {
+//!     #[tarpc::max_concurrent = 4]
+//!     async fn generate(month: u8) -> String;
+//!     async fn list() -> Vec<String>;
+//! }
+//! ```
+
+use crate::{context, ServerError};
+use std::io;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// Limits the number of requests of a method executing at once. Generated serve fns hold one in
+/// a static for each method declared with `#[tarpc::max_concurrent = N]`.
+#[derive(Debug)]
+pub struct MethodLimit {
+    max_concurrent: usize,
+    permits: Semaphore,
+}
+
+impl MethodLimit {
+    /// Returns a limit allowing `max_concurrent` requests to execute at once.
+    pub const fn new(max_concurrent: usize) -> Self {
+        Self {
+            max_concurrent,
+            permits: Semaphore::const_new(max_concurrent),
+        }
+    }
+
+    /// Returns the maximum number of requests that may execute at once.
+    pub fn max_concurrent(&self) -> usize {
+        self.max_concurrent
+    }
+
+    /// Returns the number of requests executing.
+    pub fn executing(&self) -> usize {
+        self.max_concurrent - self.permits.available_permits()
+    }
+
+    /// Waits until the request described by `ctx` may execute, or until its deadline passes. The
+    /// returned permit counts toward the executing requests until dropped.
+    pub async fn acquire(
+        &self,
+        ctx: &context::Context,
+    ) -> Result<SemaphorePermit<'_>, ServerError> {
+        if let Ok(permit) = self.permits.try_acquire() {
+            return Ok(permit);
+        }
+        let budget = ctx.time_remaining();
+        match tokio::time::timeout(budget, self.permits.acquire()).await {
+            Ok(Ok(permit)) => Ok(permit),
+            // The semaphore is never closed.
+            Ok(Err(_)) | Err(_) => {
+                tracing::info!(
+                    max_concurrent = self.max_concurrent,
+                    "MethodConcurrencyTimeout"
+                );
+                Err(ServerError::new(
+                    io::ErrorKind::TimedOut,
+                    format!(
+                        "request waited {budget:?} for one of {} requests of its method to finish.",
+                        self.max_concurrent
+                    ),
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, Instant};
+
+    #[tokio::test]
+    async fn waits_for_permits_until_the_deadline() {
+        let limit = MethodLimit::new(1);
+        let mut ctx = context::current();
+        let permit = limit.acquire(&ctx).await.unwrap();
+        assert_eq!(limit.executing(), 1);
+
+        ctx.deadline = Instant::now() + Duration::from_millis(10);
+        let e = limit.acquire(&ctx).await.unwrap_err();
+        assert_eq!(e.kind, io::ErrorKind::TimedOut);
+
+        drop(permit);
+        assert_eq!(limit.executing(), 0);
+        ctx.deadline = Instant::now() + Duration::from_secs(10);
+        assert!(limit.acquire(&ctx).await.is_ok());
+    }
+}
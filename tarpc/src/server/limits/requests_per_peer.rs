@@ -0,0 +1,384 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Provides a [`Serve`] wrapper that limits the request rate and concurrency of each peer.
+//!
+//! A [`PeerRateLimiter`] is shared by all channels of a server and tracks a quota per peer key.
+//! The key is supplied when wrapping the serve fn for a channel: typically it's the peer IP
+//! address, a TLS identity, or an authenticated principal. Because the state is keyed by peer
+//! rather than by channel, a client can't evade its quota by opening more connections.
+//!
+//! Each peer's request rate is limited by a token bucket: the peer may send up to
+//! [`burst`](PeerQuota::burst) requests at once, after which requests are admitted at the
+//! [`sustained rate`](PeerQuota::requests_per_second). Requests over the quota are answered
+//! with an [overloaded](ServerError::overloaded) error suggesting when the peer may retry.
+//!
+//! # Example
+//!
+//! ```rust
+//! use tarpc::{
+//!     server::{
+//!         self,
+//!         limits::requests_per_peer::{PeerQuota, PeerRateLimiter, RateLimit},
+//!         Channel,
+//!     },
+//!     transport::channel,
+//! };
+//!
+//! let limiter = PeerRateLimiter::new(PeerQuota::new(100.0, 20).with_max_concurrent_requests(10));
+//! let serve = server::serve(|_, i: i32| async move { Ok(i + 1) });
+//!
+//! let (_client, transport) = channel::unbounded();
+//! let requests = server::BaseChannel::with_defaults(transport)
+//!     .execute(RateLimit::new(serve, "peer-1", limiter.clone()));
+//! # drop(requests);
+//! ```
+
+use crate::{context, server::Serve, util, ServerError};
+use fnv::FnvHashMap;
+use std::{
+    fmt,
+    hash::Hash,
+    io,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// The requests a single peer is allowed to make.
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct PeerQuota {
+    /// The number of requests per second a peer may sustain.
+    pub requests_per_second: f64,
+    /// The number of requests a peer may send at once, above the sustained rate.
+    pub burst: u32,
+    /// The maximum number of requests from a peer that may be in flight at once, if limited.
+    pub max_concurrent_requests: Option<usize>,
+}
+
+impl PeerQuota {
+    /// Returns a quota allowing bursts of `burst` requests, refilled at `requests_per_second`.
+    pub fn new(requests_per_second: f64, burst: u32) -> Self {
+        Self {
+            requests_per_second,
+            burst,
+            max_concurrent_requests: None,
+        }
+    }
+
+    /// Limits the number of requests from a peer that may be in flight at once.
+    pub fn with_max_concurrent_requests(mut self, max_concurrent_requests: usize) -> Self {
+        self.max_concurrent_requests = Some(max_concurrent_requests);
+        self
+    }
+}
+
+/// Tracks the quota usage of every peer of a server.
+///
+/// Clones share the same state, so a single limiter should be used by all channels of a server.
+pub struct PeerRateLimiter<K> {
+    quota: PeerQuota,
+    peers: Arc<Mutex<Peers<K>>>,
+}
+
+struct Peers<K> {
+    states: FnvHashMap<K, PeerState>,
+    /// The number of peers above which idle peers are forgotten.
+    sweep_at: usize,
+}
+
+/// The number of peers tracked before the first sweep of idle peers.
+const MIN_SWEEP_AT: usize = 64;
+
+#[derive(Debug)]
+struct PeerState {
+    tokens: f64,
+    refilled_at: Instant,
+    in_flight: usize,
+}
+
+impl PeerState {
+    fn refill(&mut self, quota: &PeerQuota, now: Instant) {
+        let elapsed = now
+            .saturating_duration_since(self.refilled_at)
+            .as_secs_f64();
+        self.tokens =
+            (self.tokens + elapsed * quota.requests_per_second).min(f64::from(quota.burst));
+        self.refilled_at = now;
+    }
+
+    /// True iff the peer has no requests in flight and a full bucket, making it indistinguishable
+    /// from a peer that was never seen.
+    fn is_idle(&self, quota: &PeerQuota) -> bool {
+        self.in_flight == 0 && self.tokens >= f64::from(quota.burst)
+    }
+}
+
+impl<K> PeerRateLimiter<K> {
+    /// Returns a new limiter that applies `quota` to each peer.
+    pub fn new(quota: PeerQuota) -> Self {
+        Self {
+            quota,
+            peers: Arc::new(Mutex::new(Peers {
+                states: FnvHashMap::default(),
+                sweep_at: MIN_SWEEP_AT,
+            })),
+        }
+    }
+
+    /// Returns the quota applied to each peer.
+    pub fn quota(&self) -> &PeerQuota {
+        &self.quota
+    }
+}
+
+impl<K> PeerRateLimiter<K>
+where
+    K: Hash + Eq + Clone,
+{
+    /// Admits a request from `peer` if it is within its quota. The returned permit counts
+    /// towards the peer's concurrent requests until dropped.
+    fn acquire(&self, peer: &K) -> Result<Permit<K>, ServerError> {
+        let now = util::now();
+        let mut peers = self.peers.lock().unwrap();
+        if peers.states.len() >= peers.sweep_at {
+            let quota = &self.quota;
+            peers.states.retain(|_, state| {
+                state.refill(quota, now);
+                !state.is_idle(quota)
+            });
+            peers.sweep_at = (peers.states.len() * 2).max(MIN_SWEEP_AT);
+        }
+        let state = peers
+            .states
+            .entry(peer.clone())
+            .or_insert_with(|| PeerState {
+                tokens: f64::from(self.quota.burst),
+                refilled_at: now,
+                in_flight: 0,
+            });
+        state.refill(&self.quota, now);
+
+        if let Some(max) = self.quota.max_concurrent_requests {
+            if state.in_flight >= max {
+                return Err(ServerError::new(
+                    io::ErrorKind::WouldBlock,
+                    format!("peer has the maximum of {max} requests in flight."),
+                ));
+            }
+        }
+        if state.tokens < 1.0 {
+            let detail = "peer exceeded its request rate.".to_string();
+            // A rate that is zero, or so low that the next token is further away than a
+            // Duration spans, gives no useful retry hint.
+            let rate = self.quota.requests_per_second;
+            let retry_after = (rate > 0.0)
+                .then(|| Duration::try_from_secs_f64((1.0 - state.tokens) / rate).ok())
+                .flatten();
+            return Err(match retry_after {
+                Some(retry_after) => ServerError::overloaded(detail, retry_after),
+                None => ServerError::new(io::ErrorKind::WouldBlock, detail),
+            });
+        }
+        state.tokens -= 1.0;
+        state.in_flight += 1;
+        Ok(Permit {
+            peer: peer.clone(),
+            peers: self.peers.clone(),
+        })
+    }
+}
+
+impl<K> Clone for PeerRateLimiter<K> {
+    fn clone(&self) -> Self {
+        Self {
+            quota: self.quota,
+            peers: self.peers.clone(),
+        }
+    }
+}
+
+impl<K> fmt::Debug for PeerRateLimiter<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PeerRateLimiter")
+            .field("quota", &self.quota)
+            .field("peers", &self.peers.lock().unwrap().states.len())
+            .finish()
+    }
+}
+
+/// Counts an admitted request towards its peer's concurrent requests until dropped.
+struct Permit<K: Hash + Eq> {
+    peer: K,
+    peers: Arc<Mutex<Peers<K>>>,
+}
+
+impl<K: Hash + Eq> Drop for Permit<K> {
+    fn drop(&mut self) {
+        if let Some(state) = self.peers.lock().unwrap().states.get_mut(&self.peer) {
+            state.in_flight -= 1;
+        }
+    }
+}
+
+/// A [`Serve`] wrapper that rejects requests exceeding the quota of the peer that sent them.
+#[derive(Clone, Debug)]
+pub struct RateLimit<Serv, K> {
+    serve: Serv,
+    peer: K,
+    limiter: PeerRateLimiter<K>,
+}
+
+impl<Serv, K> RateLimit<Serv, K> {
+    /// Returns a new `RateLimit` that serves requests from `peer` with `serve`, as long as they
+    /// are within the peer's quota tracked by `limiter`.
+    pub fn new(serve: Serv, peer: K, limiter: PeerRateLimiter<K>) -> Self {
+        Self {
+            serve,
+            peer,
+            limiter,
+        }
+    }
+
+    /// Returns the inner serve fn.
+    pub fn get_ref(&self) -> &Serv {
+        &self.serve
+    }
+}
+
+impl<Serv, K> Serve for RateLimit<Serv, K>
+where
+    Serv: Serve,
+    K: Hash + Eq + Clone,
+{
+    type Req = Serv::Req;
+    type Resp = Serv::Resp;
+
+    async fn serve(self, ctx: context::Context, req: Self::Req) -> Result<Serv::Resp, ServerError> {
+        let _permit = match self.limiter.acquire(&self.peer) {
+            Ok(permit) => permit,
+            Err(error) => {
+                tracing::info!(detail = %error.detail, "RateLimitRequest");
+                return Err(error);
+            }
+        };
+        self.serve.serve(ctx, req).await
+    }
+
+    fn method(&self, request: &Self::Req) -> Option<&'static str> {
+        self.serve.method(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::serve;
+    use assert_matches::assert_matches;
+    use futures::{channel::oneshot, prelude::*};
+
+    fn echo() -> impl Serve<Req = i32, Resp = i32> + Copy {
+        serve(|_, i: i32| async move { Ok(i) })
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn burst_then_sustained_rate() {
+        let limiter = PeerRateLimiter::new(PeerQuota::new(2.0, 3));
+        let call =
+            |peer| RateLimit::new(echo(), peer, limiter.clone()).serve(context::current(), 1);
+
+        for _ in 0..3 {
+            assert_eq!(call("a").await, Ok(1));
+        }
+        let error = call("a").await.unwrap_err();
+        assert_eq!(error.kind, io::ErrorKind::WouldBlock);
+        assert_eq!(error.retry_after, Some(Duration::from_millis(500)));
+
+        // Other peers have their own quota.
+        assert_eq!(call("b").await, Ok(1));
+
+        tokio::time::advance(Duration::from_millis(500)).await;
+        assert_eq!(call("a").await, Ok(1));
+        assert_matches!(call("a").await, Err(_));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn refills_with_tokio_time() {
+        let limiter = PeerRateLimiter::new(PeerQuota::new(1000.0, 1));
+        let call = || RateLimit::new(echo(), "a", limiter.clone()).serve(context::current(), 1);
+
+        assert_eq!(call().await, Ok(1));
+        // Wall time passing doesn't refill the quota while tokio's clock is paused.
+        std::thread::sleep(Duration::from_millis(10));
+        assert_matches!(call().await, Err(_));
+
+        tokio::time::advance(Duration::from_millis(1)).await;
+        assert_eq!(call().await, Ok(1));
+    }
+
+    #[tokio::test]
+    async fn tiny_rates_refuse_without_retry_after() {
+        let limiter = PeerRateLimiter::new(PeerQuota::new(1e-300, 1));
+        let call = || RateLimit::new(echo(), "a", limiter.clone()).serve(context::current(), 1);
+
+        assert_eq!(call().await, Ok(1));
+        let error = call().await.unwrap_err();
+        assert_eq!(error.kind, io::ErrorKind::WouldBlock);
+        assert_eq!(error.retry_after, None);
+    }
+
+    #[tokio::test]
+    async fn limits_concurrent_requests() {
+        let limiter =
+            PeerRateLimiter::new(PeerQuota::new(100.0, 100).with_max_concurrent_requests(1));
+        let (tx, rx) = oneshot::channel::<()>();
+        let blocked = serve(|_, _: ()| async move {
+            rx.await.unwrap();
+            Ok(())
+        });
+
+        let in_flight = tokio::spawn(
+            RateLimit::new(blocked, "a", limiter.clone()).serve(context::current(), ()),
+        );
+        tokio::task::yield_now().await;
+
+        let unit = serve(|_, _: ()| future::ready(Ok(())));
+        let error = RateLimit::new(unit, "a", limiter.clone())
+            .serve(context::current(), ())
+            .await
+            .unwrap_err();
+        assert_eq!(error.kind, io::ErrorKind::WouldBlock);
+        assert_eq!(error.retry_after, None);
+
+        tx.send(()).unwrap();
+        in_flight.await.unwrap().unwrap();
+        assert_eq!(
+            RateLimit::new(unit, "a", limiter.clone())
+                .serve(context::current(), ())
+                .await,
+            Ok(())
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn forgets_idle_peers() {
+        let limiter = PeerRateLimiter::new(PeerQuota::new(1.0, 1));
+        for peer in 0..MIN_SWEEP_AT {
+            RateLimit::new(echo(), peer, limiter.clone())
+                .serve(context::current(), 1)
+                .await
+                .unwrap();
+        }
+        assert_eq!(limiter.peers.lock().unwrap().states.len(), MIN_SWEEP_AT);
+
+        tokio::time::advance(Duration::from_secs(1)).await;
+        RateLimit::new(echo(), MIN_SWEEP_AT, limiter.clone())
+            .serve(context::current(), 1)
+            .await
+            .unwrap();
+        assert_eq!(limiter.peers.lock().unwrap().states.len(), 1);
+    }
+}
@@ -0,0 +1,447 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Provides one-line servers, which listen on a [serde transport](crate::serde_transport) and
+//! serve each connection with a [`BaseChannel`](super::BaseChannel).
+//!
+//! Each fn wires up the usual chain: it listens on an address, skips connections that fail to be
+//! accepted, wraps the others in channels configured by a [`Config`], and executes their requests.
+//! The returned [`Listening`] server knows the address it's bound to, and serves connections when
+//! awaited. To customize the chain, build it from [`serde_transport`] and
+//! [`Channel::execute`](super::Channel::execute) as usual.
+//!
+//! Connections are spread across a pool of [`Config::threads`] threads, each serving its
+//! connections on a tokio [`LocalSet`](tokio::task::LocalSet), and each request is spawned as a
+//! task of its own there. Since a connection stays on its thread, the futures of service methods
+//! needn't be `Send`; a handler that blocks stalls only the connections of its thread. Each
+//! channel has at most [`max_in_flight_requests`](super::Config::max_in_flight_requests)
+//! requests in flight, 1000 by default.
+//!
+//! Service traits generated by `#[tarpc::service]` have `serve_tcp` and `serve_unix` fns that
+//! serve the service this way, with the defaults of [`Config`].
+//!
+//! # Example
+//!
+//! ```rust
+//! # #[cfg(not(feature = "tcp"))]
+//! # fn main() {}
+//! # #[cfg(feature = "tcp")]
+//! # #[tokio::main]
+//! # async fn main() -> anyhow::Result<()> {
+//! use std::future::IntoFuture;
+//! use tarpc::{client, context, serde_transport, server::quickstart, tokio_serde::formats::Json};
+//!
+//! #[tarpc::service]
+//! trait World {
+//!     async fn hello(name: String) -> String;
+//! }
+//!
+//! #[derive(Clone)]
+//! struct WorldServer;
+//!
+//! impl World for WorldServer {
+//!     async fn hello(self, _: context::Context, name: String) -> String {
+//!         format!("Hello, {name}!")
+//!     }
+//! }
+//!
+//! let server = WorldServer
+//!     .serve_tcp("localhost:0", Json::default, quickstart::Config::default())
+//!     .await?;
+//! let addr = *server.local_addr();
+//! tokio::spawn(server.into_future());
+//!
+//! let transport = serde_transport::tcp::connect(addr, Json::default).await?;
+//! let client = WorldClient::new(client::Config::default(), transport).spawn();
+//! assert_eq!(client.hello(context::current(), "Ferris".into()).await?, "Hello, Ferris!");
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::{
+    serde_transport,
+    server::{body::Body, BaseChannel, Channel, Serve},
+    ClientMessage, Response, Transport,
+};
+use futures::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::{fmt, future::IntoFuture, io, num::NonZeroUsize, thread};
+use tokio_serde::{Deserializer, Serializer};
+use tokio_util::task::LocalPoolHandle;
+
+#[cfg(all(unix, feature = "unix"))]
+pub use tokio::net::unix::SocketAddr as UnixSocketAddr;
+#[cfg(feature = "tcp")]
+pub use tokio::net::ToSocketAddrs;
+
+/// A serde transport over TCP, carrying the requests and responses of a server.
+#[cfg(feature = "tcp")]
+pub type TcpTransport<Req, Resp, Codec> =
+    serde_transport::Transport<tokio::net::TcpStream, ClientMessage<Req>, Response<Resp>, Codec>;
+
+/// A serde transport over a Unix Domain Socket, carrying the requests and responses of a server.
+#[cfg(all(unix, feature = "unix"))]
+pub type UnixTransport<Req, Resp, Codec> =
+    serde_transport::Transport<tokio::net::UnixStream, ClientMessage<Req>, Response<Resp>, Codec>;
+
+/// Settings of the servers of this module.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Config {
+    /// The settings of each channel.
+    pub channel: super::Config,
+    /// The maximum number of connections served at once, if limited. Further connections wait in
+    /// the listen backlog of the operating system until one closes. Defaults to 1024.
+    pub max_channels: Option<usize>,
+    /// The maximum length of a frame on the wire. Defaults to 8 MiB.
+    pub max_frame_length: usize,
+    /// The number of threads serving connections. Defaults to the available parallelism.
+    pub threads: usize,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            channel: super::Config {
+                max_in_flight_requests: Some(1_000),
+                ..super::Config::default()
+            },
+            max_channels: Some(1024),
+            max_frame_length: 8 * 1024 * 1024,
+            threads: thread::available_parallelism().map_or(1, NonZeroUsize::get),
+        }
+    }
+}
+
+impl Config {
+    /// Sets the settings of each channel.
+    pub fn with_channel(mut self, channel: super::Config) -> Self {
+        self.channel = channel;
+        self
+    }
+
+    /// Limits the number of connections served at once, or lifts the limit if None.
+    pub fn with_max_channels(mut self, max_channels: Option<usize>) -> Self {
+        self.max_channels = max_channels;
+        self
+    }
+
+    /// Sets the maximum length of a frame on the wire.
+    pub fn with_max_frame_length(mut self, max_frame_length: usize) -> Self {
+        self.max_frame_length = max_frame_length;
+        self
+    }
+
+    /// Sets the number of threads serving connections. At least one thread serves them.
+    pub fn with_threads(mut self, threads: usize) -> Self {
+        self.threads = threads;
+        self
+    }
+}
+
+/// A server bound to an address, which serves connections when awaited. Serving ends only if
+/// the listener does.
+#[must_use = "servers do nothing unless awaited"]
+pub struct Listening<Addr, Fut> {
+    local_addr: Addr,
+    serving: Fut,
+}
+
+impl<Addr, Fut> Listening<Addr, Fut> {
+    /// Returns the address the server is bound to.
+    pub fn local_addr(&self) -> &Addr {
+        &self.local_addr
+    }
+}
+
+impl<Addr, Fut> IntoFuture for Listening<Addr, Fut>
+where
+    Fut: Future<Output = ()>,
+{
+    type Output = ();
+    type IntoFuture = Fut;
+
+    fn into_future(self) -> Fut {
+        self.serving
+    }
+}
+
+impl<Addr: fmt::Debug, Fut> fmt::Debug for Listening<Addr, Fut> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Listening")
+            .field("local_addr", &self.local_addr)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Listens for TCP connections on `addr`, serving each with `serve` over the transport encoded by
+/// `codec_fn`.
+#[cfg(feature = "tcp")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tcp")))]
+pub async fn serve_tcp<A, S, Codec, CodecFn>(
+    addr: A,
+    serve: S,
+    codec_fn: CodecFn,
+    config: Config,
+) -> io::Result<Listening<std::net::SocketAddr, impl Future<Output = ()>>>
+where
+    A: ToSocketAddrs,
+    S: Serve + Clone + Send + 'static,
+    S::Req: for<'de> Deserialize<'de> + 'static,
+    S::Resp: Serialize + 'static,
+    Codec: Serializer<Response<S::Resp>> + Deserializer<ClientMessage<S::Req>>,
+    CodecFn: Fn() -> Codec,
+    TcpTransport<S::Req, S::Resp, Codec>:
+        Transport<Response<S::Resp>, ClientMessage<S::Req>> + Send + 'static,
+{
+    let mut listener = serde_transport::tcp::listen(addr, codec_fn).await?;
+    listener
+        .config_mut()
+        .max_frame_length(config.max_frame_length);
+    let local_addr = listener.local_addr();
+    tracing::info!(%local_addr, "Listening");
+    let serving = serve_channels(listener, config, tcp_peer_addr, move |channel| {
+        channel.execute(serve.clone())
+    });
+    Ok(Listening {
+        local_addr,
+        serving,
+    })
+}
+
+/// Like [`serve_tcp`], but for services whose `serve` responds with [bodies](Body), i.e.
+/// services with streaming methods.
+#[cfg(feature = "tcp")]
+#[cfg_attr(docsrs, doc(cfg(feature = "tcp")))]
+pub async fn serve_tcp_body<A, S, Resp, Codec, CodecFn>(
+    addr: A,
+    serve: S,
+    codec_fn: CodecFn,
+    config: Config,
+) -> io::Result<Listening<std::net::SocketAddr, impl Future<Output = ()>>>
+where
+    A: ToSocketAddrs,
+    S: Serve<Resp = Body<Resp>> + Clone + Send + 'static,
+    S::Req: for<'de> Deserialize<'de> + 'static,
+    Resp: Serialize + 'static,
+    Codec: Serializer<Response<Resp>> + Deserializer<ClientMessage<S::Req>>,
+    CodecFn: Fn() -> Codec,
+    TcpTransport<S::Req, Resp, Codec>:
+        Transport<Response<Resp>, ClientMessage<S::Req>> + Send + 'static,
+{
+    let mut listener = serde_transport::tcp::listen(addr, codec_fn).await?;
+    listener
+        .config_mut()
+        .max_frame_length(config.max_frame_length);
+    let local_addr = listener.local_addr();
+    tracing::info!(%local_addr, "Listening");
+    let serving = serve_channels(listener, config, tcp_peer_addr, move |channel| {
+        channel.requests().execute_body(serve.clone())
+    });
+    Ok(Listening {
+        local_addr,
+        serving,
+    })
+}
+
+/// Listens for Unix Domain Socket connections on the socket named by `path`, serving each with
+/// `serve` over the transport encoded by `codec_fn`.
+#[cfg(all(unix, feature = "unix"))]
+#[cfg_attr(docsrs, doc(cfg(all(unix, feature = "unix"))))]
+pub async fn serve_unix<P, S, Codec, CodecFn>(
+    path: P,
+    serve: S,
+    codec_fn: CodecFn,
+    config: Config,
+) -> io::Result<Listening<UnixSocketAddr, impl Future<Output = ()>>>
+where
+    P: AsRef<std::path::Path>,
+    S: Serve + Clone + Send + 'static,
+    S::Req: for<'de> Deserialize<'de> + 'static,
+    S::Resp: Serialize + 'static,
+    Codec: Serializer<Response<S::Resp>> + Deserializer<ClientMessage<S::Req>>,
+    CodecFn: Fn() -> Codec,
+    UnixTransport<S::Req, S::Resp, Codec>:
+        Transport<Response<S::Resp>, ClientMessage<S::Req>> + Send + 'static,
+{
+    let mut listener = serde_transport::unix::listen(path, codec_fn).await?;
+    listener
+        .config_mut()
+        .max_frame_length(config.max_frame_length);
+    let local_addr = listener.local_addr().clone();
+    tracing::info!(?local_addr, "Listening");
+    let serving = serve_channels(
+        listener,
+        config,
+        |_| None,
+        move |channel| channel.execute(serve.clone()),
+    );
+    Ok(Listening {
+        local_addr,
+        serving,
+    })
+}
+
+/// Like [`serve_unix`], but for services whose `serve` responds with [bodies](Body), i.e.
+/// services with streaming methods.
+#[cfg(all(unix, feature = "unix"))]
+#[cfg_attr(docsrs, doc(cfg(all(unix, feature = "unix"))))]
+pub async fn serve_unix_body<P, S, Resp, Codec, CodecFn>(
+    path: P,
+    serve: S,
+    codec_fn: CodecFn,
+    config: Config,
+) -> io::Result<Listening<UnixSocketAddr, impl Future<Output = ()>>>
+where
+    P: AsRef<std::path::Path>,
+    S: Serve<Resp = Body<Resp>> + Clone + Send + 'static,
+    S::Req: for<'de> Deserialize<'de> + 'static,
+    Resp: Serialize + 'static,
+    Codec: Serializer<Response<Resp>> + Deserializer<ClientMessage<S::Req>>,
+    CodecFn: Fn() -> Codec,
+    UnixTransport<S::Req, Resp, Codec>:
+        Transport<Response<Resp>, ClientMessage<S::Req>> + Send + 'static,
+{
+    let mut listener = serde_transport::unix::listen(path, codec_fn).await?;
+    listener
+        .config_mut()
+        .max_frame_length(config.max_frame_length);
+    let local_addr = listener.local_addr().clone();
+    tracing::info!(?local_addr, "Listening");
+    let serving = serve_channels(
+        listener,
+        config,
+        |_| None,
+        move |channel| channel.requests().execute_body(serve.clone()),
+    );
+    Ok(Listening {
+        local_addr,
+        serving,
+    })
+}
+
+/// Returns the peer address of a TCP connection, with which the spans of its requests are
+/// annotated.
+#[cfg(feature = "tcp")]
+fn tcp_peer_addr<Item, SinkItem, Codec>(
+    transport: &serde_transport::Transport<tokio::net::TcpStream, Item, SinkItem, Codec>,
+) -> Option<std::net::SocketAddr> {
+    transport.peer_addr().ok()
+}
+
+/// Wraps each transport accepted in a channel annotated with its peer address, if any, and
+/// spawns the requests `execute` makes of it on the thread of the pool serving the channel.
+fn serve_channels<Req, Resp, T, E, Executions, Fut>(
+    transports: impl Stream<Item = io::Result<T>>,
+    config: Config,
+    peer_addr: fn(&T) -> Option<std::net::SocketAddr>,
+    execute: E,
+) -> impl Future<Output = ()>
+where
+    Req: 'static,
+    Resp: 'static,
+    T: Transport<Response<Resp>, ClientMessage<Req>> + Send + 'static,
+    E: Fn(BaseChannel<Req, Resp, T>) -> Executions + Clone + Send + 'static,
+    Executions: Stream<Item = super::execution::RequestExecution<Fut>>,
+    Fut: Future<Output = ()> + 'static,
+{
+    let Config {
+        channel,
+        max_channels,
+        threads,
+        ..
+    } = config;
+    let pool = LocalPoolHandle::new(threads.max(1));
+    transports
+        .filter_map(|transport| {
+            future::ready(
+                transport
+                    .map_err(|e| tracing::warn!(error = %e, "AcceptError"))
+                    .ok(),
+            )
+        })
+        .for_each_concurrent(max_channels, move |transport| {
+            let addr = peer_addr(&transport);
+            let channel = channel.clone();
+            let execute = execute.clone();
+            // The channel is made on its thread, so that its futures needn't be Send.
+            pool.spawn_pinned(move || async move {
+                let channel = BaseChannel::new(channel, transport);
+                let channel = match addr {
+                    Some(addr) => channel.with_peer_addr(addr),
+                    None => channel,
+                };
+                execute(channel)
+                    .for_each(|request| async move {
+                        tokio::task::spawn_local(request);
+                    })
+                    .await;
+            })
+            .map(|served| {
+                if let Err(e) = served {
+                    tracing::warn!(error = %e, "ChannelPanicked");
+                }
+            })
+        })
+}
+
+#[cfg(all(test, feature = "tcp", feature = "serde-transport-json"))]
+mod tests {
+    use super::*;
+    use crate::{client, context, server::serve};
+    use std::{
+        sync::{mpsc, Arc, Mutex},
+        time::Duration,
+    };
+    use tokio_serde::formats::Json;
+
+    #[tokio::test]
+    async fn blocking_handlers_stall_only_their_thread() -> anyhow::Result<()> {
+        let (entered_tx, mut entered) = tokio::sync::mpsc::unbounded_channel();
+        let (release, released) = mpsc::channel::<()>();
+        let released = Arc::new(Mutex::new(released));
+        let serve = serve(move |_, block: bool| {
+            let entered_tx = entered_tx.clone();
+            let released = released.clone();
+            async move {
+                if block {
+                    entered_tx.send(()).unwrap();
+                    released.lock().unwrap().recv().unwrap();
+                }
+                Ok(block)
+            }
+        });
+        let server = serve_tcp(
+            "localhost:0",
+            serve,
+            Json::default,
+            Config::default().with_threads(2),
+        )
+        .await?;
+        let addr = *server.local_addr();
+        tokio::spawn(server.into_future());
+        let connect = || async move {
+            let transport = serde_transport::tcp::connect(addr, Json::default).await?;
+            anyhow::Ok(client::new(client::Config::default(), transport).spawn())
+        };
+
+        let blocked = connect().await?;
+        let blocked = tokio::spawn(async move { blocked.call(context::current(), "", true).await });
+        entered.recv().await;
+
+        let other = connect().await?;
+        let response = tokio::time::timeout(
+            Duration::from_secs(5),
+            other.call(context::current(), "", false),
+        )
+        .await?;
+        assert!(!response?);
+
+        release.send(())?;
+        assert!(blocked.await??);
+        Ok(())
+    }
+}
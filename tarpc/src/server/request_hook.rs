@@ -12,6 +12,9 @@ mod before;
 /// A request hook that runs after a request is completed.
 mod after;
 
+/// A request hook that can transform or replace a response before it is sent.
+mod intercept;
+
 /// A request hook that runs both before a request is executed and after it is completed.
 mod before_and_after;
 
@@ -22,4 +25,5 @@ pub use {
         HookThenServe,
     },
     before_and_after::HookThenServeThenHook,
+    intercept::{InterceptResponse, ServeThenIntercept},
 };
@@ -65,8 +65,12 @@ where
         let ServeThenHook {
             serve, mut hook, ..
         } = self;
-        let mut resp = serve.serve(ctx, req).await;
+        let mut resp = serve.serve(ctx.clone(), req).await;
         hook.after(&mut ctx, &mut resp).await;
         resp
     }
+
+    fn method(&self, request: &Self::Req) -> Option<&'static str> {
+        self.serve.method(request)
+    }
 }
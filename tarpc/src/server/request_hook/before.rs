@@ -96,6 +96,10 @@ where
         hook.before(&mut ctx, &req).await?;
         serve.serve(ctx, req).await
     }
+
+    fn method(&self, request: &Self::Req) -> Option<&'static str> {
+        self.serve.method(request)
+    }
 }
 
 /// Returns a request hook builder that runs a series of hooks before request execution.
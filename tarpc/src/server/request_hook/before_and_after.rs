@@ -50,8 +50,12 @@ where
             serve, mut hook, ..
         } = self;
         hook.before(&mut ctx, &req).await?;
-        let mut resp = serve.serve(ctx, req).await;
+        let mut resp = serve.serve(ctx.clone(), req).await;
         hook.after(&mut ctx, &mut resp).await;
         resp
     }
+
+    fn method(&self, request: &Req) -> Option<&'static str> {
+        self.serve.method(request)
+    }
 }
@@ -0,0 +1,130 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Provides a hook that intercepts responses before they are sent.
+
+use crate::{context, server::Serve, ServerError};
+use futures::prelude::*;
+
+/// A hook that intercepts the response to a request before it is sent to the client.
+#[allow(async_fn_in_trait)]
+pub trait InterceptResponse<Resp> {
+    /// The function that is called with the response to a request, returning the response to
+    /// send in its place.
+    ///
+    /// `method` is the name of the method that was called, if the serve fn knows it.
+    async fn intercept(
+        &mut self,
+        method: Option<&'static str>,
+        ctx: &context::Context,
+        resp: Result<Resp, ServerError>,
+    ) -> Result<Resp, ServerError>;
+}
+
+impl<F, Fut, Resp> InterceptResponse<Resp> for F
+where
+    F: FnMut(Option<&'static str>, &context::Context, Result<Resp, ServerError>) -> Fut,
+    Fut: Future<Output = Result<Resp, ServerError>>,
+{
+    async fn intercept(
+        &mut self,
+        method: Option<&'static str>,
+        ctx: &context::Context,
+        resp: Result<Resp, ServerError>,
+    ) -> Result<Resp, ServerError> {
+        self(method, ctx, resp).await
+    }
+}
+
+/// A Service function that passes responses through a hook before returning them.
+pub struct ServeThenIntercept<Serv, Hook> {
+    serve: Serv,
+    hook: Hook,
+}
+
+impl<Serv, Hook> ServeThenIntercept<Serv, Hook> {
+    pub(crate) fn new(serve: Serv, hook: Hook) -> Self {
+        Self { serve, hook }
+    }
+}
+
+impl<Serv: Clone, Hook: Clone> Clone for ServeThenIntercept<Serv, Hook> {
+    fn clone(&self) -> Self {
+        Self {
+            serve: self.serve.clone(),
+            hook: self.hook.clone(),
+        }
+    }
+}
+
+impl<Serv, Hook> Serve for ServeThenIntercept<Serv, Hook>
+where
+    Serv: Serve,
+    Hook: InterceptResponse<Serv::Resp>,
+{
+    type Req = Serv::Req;
+    type Resp = Serv::Resp;
+
+    async fn serve(self, ctx: context::Context, req: Serv::Req) -> Result<Serv::Resp, ServerError> {
+        let ServeThenIntercept { serve, mut hook } = self;
+        let method = serve.method(&req);
+        let resp = serve.serve(ctx.clone(), req).await;
+        hook.intercept(method, &ctx, resp).await
+    }
+
+    fn method(&self, request: &Serv::Req) -> Option<&'static str> {
+        self.serve.method(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::serve;
+    use futures::executor::block_on;
+    use std::io;
+
+    #[derive(Clone, Copy)]
+    struct Named;
+
+    impl Serve for Named {
+        type Req = i32;
+        type Resp = i32;
+
+        async fn serve(self, _: context::Context, req: i32) -> Result<i32, ServerError> {
+            Ok(req)
+        }
+
+        fn method(&self, _: &i32) -> Option<&'static str> {
+            Some("Named.echo")
+        }
+    }
+
+    #[test]
+    fn hook_receives_method_and_response() {
+        let serve = Named
+            .before(|_: &mut context::Context, _: &i32| future::ready(Ok(())))
+            .intercept(
+                |method, _: &context::Context, resp: Result<i32, ServerError>| async move {
+                    assert_eq!(method, Some("Named.echo"));
+                    resp.map(|i| i * 10)
+                },
+            );
+        assert_eq!(serve.method(&1), Some("Named.echo"));
+        assert_eq!(block_on(serve.serve(context::current(), 2)), Ok(20));
+    }
+
+    #[test]
+    fn hook_can_replace_errors() {
+        let serve = serve(|_, _: i32| async {
+            Err::<i32, _>(ServerError::new(io::ErrorKind::Other, "oops".into()))
+        })
+        .intercept(|_, _: &context::Context, resp: Result<i32, ServerError>| {
+            future::ready(resp.or(Ok(0)))
+        });
+        assert_eq!(block_on(serve.serve(context::current(), 1)), Ok(0));
+    }
+}
@@ -0,0 +1,52 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Lets request handlers attach [extensions](ResponseExtensions) to the response of the request
+//! being served, without changing the signatures of service methods.
+//!
+//! Extensions can be added by the request handler itself, or by any [request
+//! hook](crate::server::request_hook) wrapping it.
+//!
+//! # Example
+//!
+//! ```rust
+//! use futures::future;
+//! use tarpc::{context, ServerError, server::{Serve, serve, response_extensions}};
+//!
+//! let serve = serve(|_ctx, i: i32| async move {
+//!     response_extensions::insert("cache", "miss");
+//!     Ok(i + 1)
+//! })
+//! .after(|_ctx: &mut context::Context, _resp: &mut Result<i32, ServerError>| {
+//!     response_extensions::insert("server-version", env!("CARGO_PKG_VERSION"));
+//!     future::ready(())
+//! });
+//! ```
+
+use crate::{util::scoped, ResponseExtensions};
+use std::cell::RefCell;
+
+thread_local! {
+    /// The extensions of the response to the request currently being served on this thread.
+    pub(crate) static CURRENT: RefCell<Option<ResponseExtensions>> = const { RefCell::new(None) };
+}
+
+/// Sets the extension `key` to `value` on the response to the request currently being served.
+///
+/// Returns false, doing nothing, if called outside of a request handler executed by
+/// [`InFlightRequest::execute`](crate::server::InFlightRequest::execute).
+pub fn insert(key: impl Into<String>, value: impl Into<String>) -> bool {
+    scoped::with(&CURRENT, |extensions| {
+        extensions.insert(key, value);
+    })
+    .is_some()
+}
+
+/// Runs `f` with the extensions of the response to the request currently being served, or returns
+/// `None` if called outside of a request handler.
+pub fn with_current<R>(f: impl FnOnce(&mut ResponseExtensions) -> R) -> Option<R> {
+    scoped::with(&CURRENT, f)
+}
@@ -0,0 +1,190 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Provides a [`Serve`] router that dispatches each request to the service of its tenant.
+//!
+//! A multi-tenant process can host an isolated backend per tenant behind one listener: each
+//! tenant's backend is a separate instance of the serve fn, with its own state, and a
+//! [`TenantRouter`] picks one by the [routing key](crate::context::Context::routing_key) of the
+//! request context, which clients set to the tenant's ID. Requests of unknown tenants, or without
+//! a routing key, are served by the fallback, if any, and are otherwise answered with a
+//! [`NotFound`](std::io::ErrorKind::NotFound) error.
+//!
+//! # Example
+//!
+//! ```rust
+//! use futures::executor::block_on;
+//! use tarpc::{
+//!     context,
+//!     server::{routing::TenantRouter, serve, Serve},
+//! };
+//!
+//! let backend = |offset: i32| serve(move |_, i: i32| async move { Ok(i + offset) });
+//! let router = TenantRouter::new()
+//!     .with_tenant(1, backend(100))
+//!     .with_tenant(2, backend(200));
+//!
+//! let mut ctx = context::current();
+//! ctx.routing_key = Some(2);
+//! assert_eq!(block_on(router.clone().serve(ctx.clone(), 1)), Ok(201));
+//!
+//! ctx.routing_key = Some(3);
+//! assert!(block_on(router.serve(ctx, 1)).is_err());
+//! ```
+
+use crate::{context, server::Serve, ServerError};
+use fnv::FnvHashMap;
+use std::{io, sync::Arc};
+
+/// A [`Serve`] router that serves each request with the serve fn of the request's tenant, named
+/// by its routing key.
+///
+/// Clones share the same routes, so cloning a router for each request is cheap.
+#[derive(Debug)]
+pub struct TenantRouter<Serv> {
+    routes: Arc<Routes<Serv>>,
+}
+
+#[derive(Clone, Debug)]
+struct Routes<Serv> {
+    tenants: FnvHashMap<u64, Serv>,
+    fallback: Option<Serv>,
+}
+
+impl<Serv> TenantRouter<Serv>
+where
+    Serv: Clone,
+{
+    /// Returns a new router with no tenants.
+    pub fn new() -> Self {
+        Self {
+            routes: Arc::new(Routes {
+                tenants: FnvHashMap::default(),
+                fallback: None,
+            }),
+        }
+    }
+
+    /// Serves requests of `tenant` with `serve`, replacing its previous serve fn, if any.
+    pub fn with_tenant(mut self, tenant: u64, serve: Serv) -> Self {
+        Arc::make_mut(&mut self.routes)
+            .tenants
+            .insert(tenant, serve);
+        self
+    }
+
+    /// Serves requests of unknown tenants, and requests without a routing key, with `serve`.
+    pub fn with_fallback(mut self, serve: Serv) -> Self {
+        Arc::make_mut(&mut self.routes).fallback = Some(serve);
+        self
+    }
+
+    /// Returns the serve fn of `tenant`, if any.
+    pub fn get(&self, tenant: u64) -> Option<&Serv> {
+        self.routes.tenants.get(&tenant)
+    }
+
+    /// Returns the number of tenants with a serve fn.
+    pub fn len(&self) -> usize {
+        self.routes.tenants.len()
+    }
+
+    /// Returns true iff no tenants have a serve fn.
+    pub fn is_empty(&self) -> bool {
+        self.routes.tenants.is_empty()
+    }
+
+    /// Returns the serve fn for requests of `tenant`.
+    fn route(&self, tenant: Option<u64>) -> Option<&Serv> {
+        tenant
+            .and_then(|tenant| self.routes.tenants.get(&tenant))
+            .or(self.routes.fallback.as_ref())
+    }
+}
+
+impl<Serv> Clone for TenantRouter<Serv> {
+    fn clone(&self) -> Self {
+        Self {
+            routes: self.routes.clone(),
+        }
+    }
+}
+
+impl<Serv> Default for TenantRouter<Serv>
+where
+    Serv: Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<Serv> Serve for TenantRouter<Serv>
+where
+    Serv: Serve + Clone,
+{
+    type Req = Serv::Req;
+    type Resp = Serv::Resp;
+
+    async fn serve(self, ctx: context::Context, req: Self::Req) -> Result<Serv::Resp, ServerError> {
+        match self.route(ctx.routing_key) {
+            Some(serve) => serve.clone().serve(ctx, req).await,
+            None => {
+                tracing::info!(tenant = ctx.routing_key, "UnknownTenant");
+                Err(ServerError::new(
+                    io::ErrorKind::NotFound,
+                    match ctx.routing_key {
+                        Some(tenant) => format!("no service for tenant {tenant}."),
+                        None => "the request has no routing key.".into(),
+                    },
+                ))
+            }
+        }
+    }
+
+    fn method(&self, request: &Self::Req) -> Option<&'static str> {
+        // Method names don't depend on the tenant, so any serve fn can name the method.
+        self.routes
+            .fallback
+            .as_ref()
+            .or_else(|| self.routes.tenants.values().next())
+            .and_then(|serve| serve.method(request))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::serve;
+    use futures::executor::block_on;
+
+    fn with_tenant(tenant: Option<u64>) -> context::Context {
+        let mut ctx = context::current();
+        ctx.routing_key = tenant;
+        ctx
+    }
+
+    #[test]
+    fn routes_by_tenant_then_fallback() {
+        let backend = |name: &'static str| serve(move |_, ()| async move { Ok(name) });
+        let router = TenantRouter::new()
+            .with_tenant(1, backend("one"))
+            .with_fallback(backend("fallback"));
+
+        let call = |tenant| block_on(router.clone().serve(with_tenant(tenant), ()));
+        assert_eq!(call(Some(1)), Ok("one"));
+        assert_eq!(call(Some(2)), Ok("fallback"));
+        assert_eq!(call(None), Ok("fallback"));
+    }
+
+    #[test]
+    fn rejects_unknown_tenants_without_fallback() {
+        let router = TenantRouter::new().with_tenant(1, serve(|_, ()| async { Ok(()) }));
+
+        let error = block_on(router.serve(with_tenant(Some(2)), ())).unwrap_err();
+        assert_eq!(error.kind, io::ErrorKind::NotFound);
+    }
+}
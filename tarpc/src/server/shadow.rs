@@ -0,0 +1,224 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Provides a [`Serve`] wrapper that mirrors a fraction of requests to a secondary service.
+//!
+//! Shadowing is useful for testing a new version of a service against production traffic: the
+//! primary service continues to answer every request, while a copy of some requests is handed to
+//! the secondary service, whose responses are discarded.
+//!
+//! Shadowed requests never delay the primary response. They are queued in a bounded buffer and
+//! processed by [`ShadowRequests::execute`], which, like [`Channel::execute`], yields futures that
+//! the caller awaits or spawns however it likes. When the buffer is full, shadowed requests are
+//! dropped.
+//!
+//! # Example
+//!
+//! ```rust
+//! use futures::{executor::block_on, prelude::*};
+//! use tarpc::{context, server::{Serve, serve, shadow}};
+//!
+//! let primary = serve(|_, i: i32| async move { Ok(i + 1) });
+//! let secondary = serve(|_, i: i32| async move { Ok(i + 2) });
+//! let (primary, shadowed) = shadow::shadow(primary, 1.0, 10);
+//!
+//! assert_eq!(block_on(primary.clone().serve(context::current(), 1)), Ok(2));
+//! drop(primary);
+//!
+//! // The secondary's responses are discarded.
+//! block_on(shadowed.execute(secondary).for_each(|rpc| rpc));
+//! ```
+//!
+//! [`Channel::execute`]: crate::server::Channel::execute
+
+use crate::{context, server::Serve, ServerError};
+use futures::{prelude::*, task::*};
+use rand::Rng;
+use std::{fmt, pin::Pin};
+use tokio::sync::mpsc;
+
+/// Returns a serve fn that serves requests with `serve` while copying a `fraction` of them,
+/// between 0.0 and 1.0, to the returned [`ShadowRequests`]. At most `buffer` shadowed requests
+/// are queued at a time.
+pub fn shadow<Serv>(
+    serve: Serv,
+    fraction: f64,
+    buffer: usize,
+) -> (Shadow<Serv>, ShadowRequests<Serv::Req>)
+where
+    Serv: Serve,
+{
+    let (tx, rx) = mpsc::channel(buffer.max(1));
+    (
+        Shadow {
+            serve,
+            fraction,
+            shadowed: tx,
+        },
+        ShadowRequests { shadowed: rx },
+    )
+}
+
+/// A [`Serve`] wrapper that copies a fraction of requests to a [`ShadowRequests`] stream.
+pub struct Shadow<Serv: Serve> {
+    serve: Serv,
+    fraction: f64,
+    shadowed: mpsc::Sender<(context::Context, Serv::Req)>,
+}
+
+impl<Serv: Serve> Shadow<Serv> {
+    /// Returns the inner serve fn.
+    pub fn get_ref(&self) -> &Serv {
+        &self.serve
+    }
+
+    fn should_shadow(&self) -> bool {
+        self.fraction >= 1.0
+            || (self.fraction > 0.0 && crate::rng::with(|rng| rng.gen::<f64>()) < self.fraction)
+    }
+}
+
+impl<Serv> Clone for Shadow<Serv>
+where
+    Serv: Serve + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            serve: self.serve.clone(),
+            fraction: self.fraction,
+            shadowed: self.shadowed.clone(),
+        }
+    }
+}
+
+impl<Serv: Serve> fmt::Debug for Shadow<Serv> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shadow")
+            .field("fraction", &self.fraction)
+            .finish()
+    }
+}
+
+impl<Serv> Serve for Shadow<Serv>
+where
+    Serv: Serve,
+    Serv::Req: Clone,
+{
+    type Req = Serv::Req;
+    type Resp = Serv::Resp;
+
+    async fn serve(self, ctx: context::Context, req: Self::Req) -> Result<Serv::Resp, ServerError> {
+        if self.should_shadow() {
+            if let Err(mpsc::error::TrySendError::Full(_)) =
+                self.shadowed.try_send((ctx.clone(), req.clone()))
+            {
+                tracing::debug!("Shadow buffer is full; dropping shadowed request.");
+            }
+        }
+        self.serve.serve(ctx, req).await
+    }
+
+    fn method(&self, request: &Self::Req) -> Option<&'static str> {
+        self.serve.method(request)
+    }
+}
+
+/// A stream of requests copied by a [`Shadow`]. The stream ends once all `Shadow`s that feed it
+/// are dropped.
+pub struct ShadowRequests<Req> {
+    shadowed: mpsc::Receiver<(context::Context, Req)>,
+}
+
+impl<Req> ShadowRequests<Req> {
+    /// Returns a stream of shadowed request executions. Each future serves one request with
+    /// `serve` and discards the response. The futures must be awaited or spawned to complete
+    /// their requests.
+    pub fn execute<S>(self, serve: S) -> impl Stream<Item = impl Future<Output = ()>>
+    where
+        S: Serve<Req = Req> + Clone,
+    {
+        self.map(move |(ctx, req)| {
+            let serve = serve.clone();
+            async move {
+                if let Err(e) = serve.serve(ctx, req).await {
+                    tracing::debug!("Shadowed request failed: {}", e);
+                }
+            }
+        })
+    }
+}
+
+impl<Req> Stream for ShadowRequests<Req> {
+    type Item = (context::Context, Req);
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        self.shadowed.poll_recv(cx)
+    }
+}
+
+impl<Req> fmt::Debug for ShadowRequests<Req> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ShadowRequests")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::{serve, testing::cx};
+    use assert_matches::assert_matches;
+    use futures::executor::block_on;
+
+    #[test]
+    fn shadows_nothing_at_zero_fraction() {
+        let (primary, mut shadowed) = shadow(serve(|_, i: i32| async move { Ok(i) }), 0.0, 10);
+        assert_eq!(
+            block_on(primary.clone().serve(context::current(), 1)),
+            Ok(1)
+        );
+        assert_matches!(Pin::new(&mut shadowed).poll_next(&mut cx()), Poll::Pending);
+    }
+
+    #[test]
+    fn shadows_everything_at_full_fraction() {
+        let (primary, mut shadowed) = shadow(serve(|_, i: i32| async move { Ok(i) }), 1.0, 10);
+        assert_eq!(
+            block_on(primary.clone().serve(context::current(), 1)),
+            Ok(1)
+        );
+        assert_matches!(
+            Pin::new(&mut shadowed).poll_next(&mut cx()),
+            Poll::Ready(Some((_, 1)))
+        );
+        drop(primary);
+        assert_matches!(
+            Pin::new(&mut shadowed).poll_next(&mut cx()),
+            Poll::Ready(None)
+        );
+    }
+
+    #[test]
+    fn full_buffer_drops_shadowed_requests() {
+        let (primary, mut shadowed) = shadow(serve(|_, i: i32| async move { Ok(i) }), 1.0, 1);
+        assert_eq!(
+            block_on(primary.clone().serve(context::current(), 1)),
+            Ok(1)
+        );
+        assert_eq!(
+            block_on(primary.clone().serve(context::current(), 2)),
+            Ok(2)
+        );
+        drop(primary);
+        assert_matches!(
+            Pin::new(&mut shadowed).poll_next(&mut cx()),
+            Poll::Ready(Some((_, 1)))
+        );
+        assert_matches!(
+            Pin::new(&mut shadowed).poll_next(&mut cx()),
+            Poll::Ready(None)
+        );
+    }
+}
@@ -12,7 +12,7 @@ use crate::{
 };
 use futures::{task::*, Sink, Stream};
 use pin_project::pin_project;
-use std::{collections::VecDeque, io, pin::Pin, time::SystemTime};
+use std::{collections::VecDeque, io, pin::Pin, time::Instant};
 use tracing::Span;
 
 #[pin_project]
@@ -89,15 +89,25 @@ where
 impl<Req, Resp> FakeChannel<io::Result<TrackedRequest<Req>>, Response<Resp>> {
     pub fn push_req(&mut self, id: u64, message: Req) {
         let (_, abort_registration) = futures::future::AbortHandle::new_pair();
-        let (request_cancellation, _) = cancellations();
+        let (request_cancellation, _) = cancellations(1);
         self.stream.push_back(Ok(TrackedRequest {
             request: Request {
                 context: context::Context {
-                    deadline: SystemTime::UNIX_EPOCH,
+                    deadline: Instant::now(),
                     trace_context: Default::default(),
+                    idempotency_key: None,
+                    routing_key: None,
+                    baggage: Default::default(),
+                    priority: Default::default(),
+                    credentials: None,
+                    extensions: context::Extensions::default(),
+                    default_deadline: None,
+                    default_priority: None,
+                    untraced: false,
                 },
                 id,
                 message,
+                oneway: false,
             },
             abort_registration,
             span: Span::none(),
@@ -112,7 +122,7 @@ impl<Req, Resp> FakeChannel<io::Result<TrackedRequest<Req>>, Response<Resp>> {
 
 impl FakeChannel<(), ()> {
     pub fn default<Req, Resp>() -> FakeChannel<io::Result<TrackedRequest<Req>>, Response<Resp>> {
-        let (request_cancellation, canceled_requests) = cancellations();
+        let (request_cancellation, canceled_requests) = cancellations(1);
         FakeChannel {
             stream: Default::default(),
             sink: Default::default(),
@@ -0,0 +1,280 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Provides counters describing the traffic of a single channel.
+//!
+//! Both [server channels](crate::server::BaseChannel::stats) and
+//! [client channels](crate::client::Channel::stats) record their traffic in a [`ChannelStats`].
+//! Channels don't know how messages are encoded, so bytes are counted by transports that support
+//! it, like the [serde transport](crate::serde_transport::Transport::stats); pass their stats to
+//! the channel to collect everything in one place.
+//!
+//! With the `metrics` feature, channels also record metrics in the [`metrics`](::metrics) facade,
+//! named per the OpenTelemetry semantic conventions for RPC, so that they can be exported with
+//! any of its recorders:
+//!
+//! - Client channels record the latency of each request in seconds in the histogram
+//!   [`CLIENT_DURATION`]; server channels record the latency of serving each request in
+//!   [`SERVER_DURATION`]. Both are labeled with `rpc.system`, `rpc.service`, `rpc.method`, and
+//!   `rpc.outcome`, which is `ok` or `error`. The latency of a request with a body covers the
+//!   whole body.
+//! - Channels whose stats are shared with a transport that counts bytes record the size of each
+//!   message read or written in [`CLIENT_REQUEST_SIZE`], [`CLIENT_RESPONSE_SIZE`],
+//!   [`SERVER_REQUEST_SIZE`], or [`SERVER_RESPONSE_SIZE`], labeled with `rpc.system`.
+
+#[cfg(feature = "metrics")]
+use std::sync::atomic::AtomicU8;
+use std::{
+    fmt,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+#[cfg(feature = "metrics")]
+use tokio::time::Instant;
+
+/// The histogram of the latencies of the requests of client channels, in seconds.
+#[cfg(feature = "metrics")]
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+pub const CLIENT_DURATION: &str = "rpc.client.duration";
+
+/// The histogram of the latencies of serving the requests of server channels, in seconds.
+#[cfg(feature = "metrics")]
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+pub const SERVER_DURATION: &str = "rpc.server.duration";
+
+/// The histogram of the sizes of the messages written by client channels, in bytes.
+#[cfg(feature = "metrics")]
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+pub const CLIENT_REQUEST_SIZE: &str = "rpc.client.request.size";
+
+/// The histogram of the sizes of the messages read by client channels, in bytes.
+#[cfg(feature = "metrics")]
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+pub const CLIENT_RESPONSE_SIZE: &str = "rpc.client.response.size";
+
+/// The histogram of the sizes of the messages read by server channels, in bytes.
+#[cfg(feature = "metrics")]
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+pub const SERVER_REQUEST_SIZE: &str = "rpc.server.request.size";
+
+/// The histogram of the sizes of the messages written by server channels, in bytes.
+#[cfg(feature = "metrics")]
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+pub const SERVER_RESPONSE_SIZE: &str = "rpc.server.response.size";
+
+/// Which end of a connection a channel is, which names the metrics of its messages.
+#[derive(Clone, Copy, Debug)]
+pub(crate) enum Role {
+    Client = 1,
+    Server = 2,
+}
+
+/// A handle to the counters of a channel.
+///
+/// Clones share the same counters, so a handle can be kept to read the counters after the
+/// channel has been moved into the task that drives it.
+#[derive(Clone, Default)]
+pub struct ChannelStats {
+    counters: Arc<Counters>,
+}
+
+#[derive(Default)]
+struct Counters {
+    requests_received: AtomicU64,
+    requests_sent: AtomicU64,
+    responses_received: AtomicU64,
+    responses_sent: AtomicU64,
+    cancellations: AtomicU64,
+    deadline_expirations: AtomicU64,
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+    /// The role of the channel recording in the counters, or 0 if not yet known.
+    #[cfg(feature = "metrics")]
+    role: AtomicU8,
+}
+
+impl ChannelStats {
+    /// Returns a new set of counters, all zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of requests a server channel has received.
+    pub fn requests_received(&self) -> u64 {
+        self.counters.requests_received.load(Ordering::Relaxed)
+    }
+
+    /// The number of requests a client channel has sent.
+    pub fn requests_sent(&self) -> u64 {
+        self.counters.requests_sent.load(Ordering::Relaxed)
+    }
+
+    /// The number of responses a client channel has received.
+    pub fn responses_received(&self) -> u64 {
+        self.counters.responses_received.load(Ordering::Relaxed)
+    }
+
+    /// The number of responses a server channel has sent.
+    pub fn responses_sent(&self) -> u64 {
+        self.counters.responses_sent.load(Ordering::Relaxed)
+    }
+
+    /// The number of in-flight requests that were canceled: by the client, for a server channel,
+    /// or to the server, for a client channel.
+    pub fn cancellations(&self) -> u64 {
+        self.counters.cancellations.load(Ordering::Relaxed)
+    }
+
+    /// The number of in-flight requests whose deadline expired.
+    pub fn deadline_expirations(&self) -> u64 {
+        self.counters.deadline_expirations.load(Ordering::Relaxed)
+    }
+
+    /// The number of bytes read by the channel's transport, if it counts them.
+    pub fn bytes_read(&self) -> u64 {
+        self.counters.bytes_read.load(Ordering::Relaxed)
+    }
+
+    /// The number of bytes written by the channel's transport, if it counts them.
+    pub fn bytes_written(&self) -> u64 {
+        self.counters.bytes_written.load(Ordering::Relaxed)
+    }
+
+    /// Counts the `bytes` of a message read by a transport.
+    pub fn record_bytes_read(&self, bytes: usize) {
+        self.counters
+            .bytes_read
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        self.record_size(bytes, [CLIENT_RESPONSE_SIZE, SERVER_REQUEST_SIZE]);
+    }
+
+    /// Counts the `bytes` of a message written by a transport.
+    pub fn record_bytes_written(&self, bytes: usize) {
+        self.counters
+            .bytes_written
+            .fetch_add(bytes as u64, Ordering::Relaxed);
+        #[cfg(feature = "metrics")]
+        self.record_size(bytes, [CLIENT_REQUEST_SIZE, SERVER_RESPONSE_SIZE]);
+    }
+
+    /// Records the size of a message in the histogram of the channel's role: the first of
+    /// `histograms` for a client, and the second for a server.
+    #[cfg(feature = "metrics")]
+    fn record_size(&self, bytes: usize, [client, server]: [&'static str; 2]) {
+        let histogram = match self.counters.role.load(Ordering::Relaxed) {
+            1 => client,
+            2 => server,
+            _ => return,
+        };
+        ::metrics::histogram!(histogram, "rpc.system" => "tarpc").record(bytes as f64);
+    }
+
+    /// Sets the role of the channel recording in the counters.
+    pub(crate) fn set_role(&self, role: Role) {
+        #[cfg(feature = "metrics")]
+        self.counters.role.store(role as u8, Ordering::Relaxed);
+        #[cfg(not(feature = "metrics"))]
+        let _ = role;
+    }
+
+    pub(crate) fn record_request_received(&self) {
+        self.counters
+            .requests_received
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_request_sent(&self) {
+        self.counters.requests_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_response_received(&self) {
+        self.counters
+            .responses_received
+            .fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_response_sent(&self) {
+        self.counters.responses_sent.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_cancellation(&self) {
+        self.counters.cancellations.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_deadline_expiration(&self) {
+        self.counters
+            .deadline_expirations
+            .fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Times a request, recording its latency in a histogram once it completes. Without the
+/// `metrics` feature, does nothing.
+#[derive(Debug)]
+pub(crate) struct RequestTimer {
+    #[cfg(feature = "metrics")]
+    histogram: &'static str,
+    #[cfg(feature = "metrics")]
+    request_name: &'static str,
+    #[cfg(feature = "metrics")]
+    start: Instant,
+}
+
+impl RequestTimer {
+    /// Starts timing the request `request_name`, e.g. `World.hello`, for `role`.
+    pub(crate) fn start(role: Role, request_name: &'static str) -> Self {
+        #[cfg(feature = "metrics")]
+        return Self {
+            histogram: match role {
+                Role::Client => CLIENT_DURATION,
+                Role::Server => SERVER_DURATION,
+            },
+            request_name,
+            start: Instant::now(),
+        };
+        #[cfg(not(feature = "metrics"))]
+        {
+            let _ = (role, request_name);
+            Self {}
+        }
+    }
+
+    /// Records the latency of the request, which failed iff `failed`.
+    pub(crate) fn finish(&self, failed: bool) {
+        #[cfg(feature = "metrics")]
+        {
+            let (service, method) = crate::util::rpc_service_and_method(self.request_name);
+            let labels = [
+                ("rpc.system", "tarpc"),
+                ("rpc.service", service),
+                ("rpc.method", method),
+                ("rpc.outcome", if failed { "error" } else { "ok" }),
+            ];
+            ::metrics::histogram!(self.histogram, &labels)
+                .record(self.start.elapsed().as_secs_f64());
+        }
+        #[cfg(not(feature = "metrics"))]
+        let _ = failed;
+    }
+}
+
+impl fmt::Debug for ChannelStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChannelStats")
+            .field("requests_received", &self.requests_received())
+            .field("requests_sent", &self.requests_sent())
+            .field("responses_received", &self.responses_received())
+            .field("responses_sent", &self.responses_sent())
+            .field("cancellations", &self.cancellations())
+            .field("deadline_expirations", &self.deadline_expirations())
+            .field("bytes_read", &self.bytes_read())
+            .field("bytes_written", &self.bytes_written())
+            .finish()
+    }
+}
@@ -0,0 +1,53 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Provides [tower](https://docs.rs/tower) services for the individual methods of a service.
+//!
+//! With `#[tarpc::service(tower = true)]`, each method `hello` of a service `World` gets:
+//!
+//! - `WorldHelloArgs`, a struct holding the method's args, and
+//! - `WorldHelloService<S>`, a newtype around a `World` server implementing
+//!   [`Service<(Context, WorldHelloArgs)>`](Service) by calling `hello`.
+//!
+//! Each method can then be wrapped in tower middleware, like timeouts or rate limits, or tested
+//! in isolation, rather than only wrapping the whole service. Methods returning streams respond
+//! with boxed streams. The services never fail, so their error type is
+//! [`Infallible`](std::convert::Infallible).
+//!
+//! Methods of the service trait don't promise to return `Send` futures, so neither do the
+//! generated services; spawn them with a local executor, like
+//! [`LocalSet`](https://docs.rs/tokio/latest/tokio/task/struct.LocalSet.html), if middleware
+//! requires it. To serve requests with wrapped methods, route them in a serve fn made with
+//! [`server::serve`](crate::server::serve).
+//!
+//! # Example
+//!
+//! ```rust
+//! # use tarpc::{context, tower::Service};
+//! #[tarpc::service(tower = true)]
+//! trait World {
+//!     async fn hello(name: String) -> String;
+//! }
+//!
+//! #[derive(Clone)]
+//! struct WorldServer;
+//!
+//! impl World for WorldServer {
+//!     async fn hello(self, _: context::Context, name: String) -> String {
+//!         format!("Hello, {name}!")
+//!     }
+//! }
+//!
+//! # futures::executor::block_on(async {
+//! let mut hello = WorldHelloService(WorldServer);
+//! let args = WorldHelloArgs { name: "Ferris".into() };
+//! let greeting = hello.call((context::current(), args)).await?;
+//! assert_eq!(greeting, "Hello, Ferris!");
+//! # Ok::<_, std::convert::Infallible>(())
+//! # }).unwrap();
+//! ```
+
+pub use tower_service::Service;
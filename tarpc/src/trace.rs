@@ -14,8 +14,12 @@
 //! either side.
 //!
 //! This crate's design is based on [opencensus
-//! tracing](https://opencensus.io/core-concepts/tracing/).
+//! tracing](https://opencensus.io/core-concepts/tracing/). Contexts convert to and from the
+//! `traceparent` and `tracestate` headers of [W3C Trace Context](https://www.w3.org/TR/trace-context/),
+//! and the single and multiple headers of [Zipkin B3](https://github.com/openzipkin/b3-propagation),
+//! so that traces continue across HTTP services and tarpc services.
 
+#[cfg(feature = "opentelemetry")]
 use opentelemetry::trace::TraceContextExt;
 use rand::Rng;
 use std::{
@@ -23,13 +27,14 @@ use std::{
     fmt::{self, Formatter},
     num::{NonZeroU128, NonZeroU64},
 };
+#[cfg(feature = "opentelemetry")]
 use tracing_opentelemetry::OpenTelemetrySpanExt;
 
 /// A context for tracing the execution of processes, distributed or otherwise.
 ///
 /// Consists of a span identifying an event, an optional parent span identifying a causal event
 /// that triggered the current span, and a trace with which all related spans are associated.
-#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone)]
 #[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(
     feature = "rkyv",
@@ -52,8 +57,22 @@ pub struct Context {
     /// then the downstream samplers are expected to respect that decision and also sample the
     /// trace. Otherwise, the full trace would not be able to be reconstructed.
     pub sampling_decision: SamplingDecision,
+    /// Vendor-specific trace data, which is propagated unchanged along the trace.
+    #[cfg_attr(feature = "serde1", serde(default))]
+    pub trace_state: TraceState,
 }
 
+/// The vendor-specific trace data of a [W3C `tracestate`](https://www.w3.org/TR/trace-context/#tracestate-header)
+/// header: a list of up to 32 `key=value` members.
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+#[cfg_attr(feature = "rkyv", archive(check_bytes))]
+pub struct TraceState(String);
+
 /// A 128-bit UUID identifying a trace. All spans caused by the same originating span share the
 /// same trace ID.
 #[derive(Default, PartialEq, Eq, Hash, Clone, Copy)]
@@ -81,7 +100,7 @@ pub struct SpanId(u64);
 /// dependencies. On the other hand, if an upstream process has chosen to sample this trace, then
 /// the downstream samplers are expected to respect that decision and also sample the trace.
 /// Otherwise, the full trace would not be able to be reconstructed reliably.
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, Copy)]
 #[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
 #[cfg_attr(
     feature = "rkyv",
@@ -93,17 +112,257 @@ pub enum SamplingDecision {
     /// The associated span was sampled by its creating process. Child spans must also be sampled.
     Sampled,
     /// The associated span was not sampled by its creating process.
+    #[default]
     Unsampled,
 }
 
 impl Context {
+    /// Constructs the context of a new, unsampled trace with random IDs.
+    #[cfg(not(feature = "opentelemetry"))]
+    pub(crate) fn new_root() -> Self {
+        crate::rng::with(|mut rng| Self {
+            trace_id: TraceId::random(&mut rng),
+            span_id: SpanId::random(&mut rng),
+            sampling_decision: SamplingDecision::Unsampled,
+            trace_state: TraceState::default(),
+        })
+    }
+
     /// Constructs a new context with the trace ID and sampling decision inherited from the parent.
     pub(crate) fn new_child(&self) -> Self {
         Self {
             trace_id: self.trace_id,
-            span_id: SpanId::random(&mut rand::thread_rng()),
+            span_id: crate::rng::with(|mut rng| SpanId::random(&mut rng)),
             sampling_decision: self.sampling_decision,
+            trace_state: self.trace_state.clone(),
+        }
+    }
+
+    /// Parses the context of [W3C Trace Context](https://www.w3.org/TR/trace-context/) headers:
+    /// the `traceparent` header, and the `tracestate` header, if any. An invalid `tracestate` is
+    /// discarded, as the spec requires.
+    pub fn from_w3c(
+        traceparent: &str,
+        tracestate: Option<&str>,
+    ) -> Result<Self, InvalidTraceparent> {
+        let traceparent = traceparent.trim();
+        let mut fields = traceparent.split('-');
+        let (Some(version), Some(trace_id), Some(span_id), Some(flags)) =
+            (fields.next(), fields.next(), fields.next(), fields.next())
+        else {
+            return Err(InvalidTraceparent);
+        };
+        let is_hex = |field: &str, len| {
+            field.len() == len
+                && field
+                    .bytes()
+                    .all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+        };
+        if !is_hex(version, 2)
+            || version == "ff"
+            || !is_hex(trace_id, 32)
+            || !is_hex(span_id, 16)
+            || !is_hex(flags, 2)
+        {
+            return Err(InvalidTraceparent);
+        }
+        // Later versions may append fields, but version 00 has exactly four.
+        if version == "00" && fields.next().is_some() {
+            return Err(InvalidTraceparent);
+        }
+        // The fields are hex of the right lengths, so they parse.
+        let trace_id = u128::from_str_radix(trace_id, 16).map_err(|_| InvalidTraceparent)?;
+        let span_id = u64::from_str_radix(span_id, 16).map_err(|_| InvalidTraceparent)?;
+        let flags = u8::from_str_radix(flags, 16).map_err(|_| InvalidTraceparent)?;
+        if trace_id == 0 || span_id == 0 {
+            return Err(InvalidTraceparent);
+        }
+        Ok(Self {
+            trace_id: TraceId(trace_id),
+            span_id: SpanId(span_id),
+            sampling_decision: if flags & 1 == 1 {
+                SamplingDecision::Sampled
+            } else {
+                SamplingDecision::Unsampled
+            },
+            trace_state: tracestate
+                .and_then(|tracestate| TraceState::parse(tracestate).ok())
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Returns the context's W3C `traceparent` header, e.g.
+    /// `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`. Its `tracestate` header is
+    /// [`trace_state`](Self::trace_state), unless empty.
+    pub fn traceparent(&self) -> String {
+        let flags = match self.sampling_decision {
+            SamplingDecision::Sampled => 1,
+            SamplingDecision::Unsampled => 0,
+        };
+        format!(
+            "00-{:032x}-{:016x}-{flags:02x}",
+            self.trace_id.0, self.span_id.0
+        )
+    }
+
+    /// Parses the context of a [B3 single header](https://github.com/openzipkin/b3-propagation#single-header),
+    /// e.g. `80f198ee56343ba864fe8b2a57d3eff7-e457b5a2e4d86bd1-1-05e3ac9a4f6e3b90`. The parent span
+    /// ID, if any, is ignored. A debug (`d`) sampling state counts as sampled, and an absent one
+    /// as unsampled.
+    ///
+    /// A header with only a sampling state, like `0`, carries no trace to continue, so it's
+    /// rejected.
+    pub fn from_b3(b3: &str) -> Result<Self, InvalidB3> {
+        let mut fields = b3.trim().split('-');
+        let (Some(trace_id), Some(span_id)) = (fields.next(), fields.next()) else {
+            return Err(InvalidB3);
+        };
+        let sampling_decision = match fields.next() {
+            None => SamplingDecision::Unsampled,
+            Some("1" | "d") => SamplingDecision::Sampled,
+            Some("0") => SamplingDecision::Unsampled,
+            Some(_) => return Err(InvalidB3),
+        };
+        if let Some(parent_span_id) = fields.next() {
+            parse_b3_id(parent_span_id, &[16])?;
+        }
+        if fields.next().is_some() {
+            return Err(InvalidB3);
+        }
+        Self::from_b3_ids(trace_id, span_id, sampling_decision)
+    }
+
+    /// Parses the context of [B3 multiple headers](https://github.com/openzipkin/b3-propagation#multiple-headers):
+    /// `X-B3-TraceId`, `X-B3-SpanId`, and, if present, `X-B3-Sampled` and `X-B3-Flags`. A debug
+    /// flag counts as sampled, and an absent sampling state as unsampled.
+    pub fn from_b3_multi(
+        trace_id: &str,
+        span_id: &str,
+        sampled: Option<&str>,
+        flags: Option<&str>,
+    ) -> Result<Self, InvalidB3> {
+        let sampling_decision = match (sampled.map(str::trim), flags.map(str::trim)) {
+            (_, Some("1")) => SamplingDecision::Sampled,
+            (_, Some(_)) => return Err(InvalidB3),
+            // Some older implementations send booleans.
+            (Some("1" | "true"), None) => SamplingDecision::Sampled,
+            (Some("0" | "false") | None, None) => SamplingDecision::Unsampled,
+            (Some(_), None) => return Err(InvalidB3),
+        };
+        Self::from_b3_ids(trace_id.trim(), span_id.trim(), sampling_decision)
+    }
+
+    fn from_b3_ids(
+        trace_id: &str,
+        span_id: &str,
+        sampling_decision: SamplingDecision,
+    ) -> Result<Self, InvalidB3> {
+        // 64-bit trace IDs are the low bits of a 128-bit ID.
+        let trace_id = parse_b3_id(trace_id, &[16, 32])?;
+        let span_id = parse_b3_id(span_id, &[16])?;
+        if trace_id == 0 || span_id == 0 {
+            return Err(InvalidB3);
+        }
+        Ok(Self {
+            trace_id: TraceId(trace_id),
+            // The span ID has 16 hex digits, so it fits.
+            span_id: SpanId(span_id as u64),
+            sampling_decision,
+            trace_state: TraceState::default(),
+        })
+    }
+
+    /// Returns the context's B3 single header, e.g.
+    /// `80f198ee56343ba864fe8b2a57d3eff7-e457b5a2e4d86bd1-1`.
+    pub fn b3(&self) -> String {
+        let [(_, trace_id), (_, span_id), (_, sampled)] = self.b3_multi();
+        format!("{trace_id}-{span_id}-{sampled}")
+    }
+
+    /// Returns the context's B3 multiple headers: `X-B3-TraceId`, `X-B3-SpanId`, and
+    /// `X-B3-Sampled`, paired with their values.
+    pub fn b3_multi(&self) -> [(&'static str, String); 3] {
+        let sampled = match self.sampling_decision {
+            SamplingDecision::Sampled => "1",
+            SamplingDecision::Unsampled => "0",
+        };
+        [
+            ("X-B3-TraceId", format!("{:032x}", self.trace_id.0)),
+            ("X-B3-SpanId", format!("{:016x}", self.span_id.0)),
+            ("X-B3-Sampled", sampled.into()),
+        ]
+    }
+}
+
+/// Parses a B3 ID of lowercase hex digits, of one of the lengths `lens`.
+fn parse_b3_id(id: &str, lens: &[usize]) -> Result<u128, InvalidB3> {
+    if !lens.contains(&id.len()) || !id.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+        return Err(InvalidB3);
+    }
+    u128::from_str_radix(id, 16).map_err(|_| InvalidB3)
+}
+
+impl TraceState {
+    /// The maximum number of members.
+    pub const MAX_MEMBERS: usize = 32;
+
+    /// Parses a W3C `tracestate` header, dropping empty members.
+    pub fn parse(tracestate: &str) -> Result<Self, InvalidTraceState> {
+        let members = tracestate
+            .split(',')
+            .map(|member| member.trim_matches(|c| c == ' ' || c == '\t'))
+            .filter(|member| !member.is_empty())
+            .collect::<Vec<_>>();
+        if members.len() > Self::MAX_MEMBERS {
+            return Err(InvalidTraceState);
+        }
+        for (i, member) in members.iter().enumerate() {
+            let Some((key, value)) = member.split_once('=') else {
+                return Err(InvalidTraceState);
+            };
+            let valid_key = !key.is_empty()
+                && key.len() <= 256
+                && key.starts_with(|c: char| c.is_ascii_lowercase() || c.is_ascii_digit())
+                && key.bytes().all(
+                    |b| matches!(b, b'a'..=b'z' | b'0'..=b'9' | b'_' | b'-' | b'*' | b'/' | b'@'),
+                );
+            let valid_value = !value.is_empty()
+                && value.len() <= 256
+                && value
+                    .bytes()
+                    .all(|b| matches!(b, b' '..=b'~') && b != b',' && b != b'=');
+            let unique = members[..i]
+                .iter()
+                .all(|other| other.split_once('=').map(|(other, _)| other) != Some(key));
+            if !valid_key || !valid_value || !unique {
+                return Err(InvalidTraceState);
+            }
         }
+        Ok(Self(members.join(",")))
+    }
+
+    /// Returns the value of the member `key`, if any.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.members()
+            .find(|(member, _)| *member == key)
+            .map(|(_, value)| value)
+    }
+
+    /// Returns an iterator over the members, in order.
+    pub fn members(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0
+            .split(',')
+            .filter_map(|member| member.split_once('='))
+    }
+
+    /// Returns the W3C `tracestate` header of the members.
+    pub fn header(&self) -> &str {
+        &self.0
+    }
+
+    /// Returns true iff there are no members.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
     }
 }
 
@@ -156,34 +415,52 @@ impl From<u64> for SpanId {
     }
 }
 
+#[cfg(feature = "opentelemetry")]
 impl From<opentelemetry::trace::TraceId> for TraceId {
     fn from(trace_id: opentelemetry::trace::TraceId) -> Self {
         Self::from(u128::from_be_bytes(trace_id.to_bytes()))
     }
 }
 
+#[cfg(feature = "opentelemetry")]
 impl From<TraceId> for opentelemetry::trace::TraceId {
     fn from(trace_id: TraceId) -> Self {
         Self::from_bytes(u128::from(trace_id).to_be_bytes())
     }
 }
 
+#[cfg(feature = "opentelemetry")]
+impl From<&TraceState> for opentelemetry::trace::TraceState {
+    fn from(trace_state: &TraceState) -> Self {
+        if trace_state.is_empty() {
+            return Self::default();
+        }
+        trace_state.header().parse().unwrap_or_default()
+    }
+}
+
+#[cfg(feature = "opentelemetry")]
 impl From<opentelemetry::trace::SpanId> for SpanId {
     fn from(span_id: opentelemetry::trace::SpanId) -> Self {
         Self::from(u64::from_be_bytes(span_id.to_bytes()))
     }
 }
 
+#[cfg(feature = "opentelemetry")]
 impl From<SpanId> for opentelemetry::trace::SpanId {
     fn from(span_id: SpanId) -> Self {
         Self::from_bytes(u64::from(span_id).to_be_bytes())
     }
 }
 
+#[cfg(feature = "opentelemetry")]
 impl TryFrom<&tracing::Span> for Context {
     type Error = NoActiveSpan;
 
     fn try_from(span: &tracing::Span) -> Result<Self, NoActiveSpan> {
+        if span.is_disabled() {
+            return Err(NoActiveSpan);
+        }
         let context = span.context();
         if context.has_active_span() {
             Ok(Self::from(context.span()))
@@ -193,6 +470,17 @@ impl TryFrom<&tracing::Span> for Context {
     }
 }
 
+/// Without the `opentelemetry` feature, spans carry no trace contexts.
+#[cfg(not(feature = "opentelemetry"))]
+impl TryFrom<&tracing::Span> for Context {
+    type Error = NoActiveSpan;
+
+    fn try_from(_: &tracing::Span) -> Result<Self, NoActiveSpan> {
+        Err(NoActiveSpan)
+    }
+}
+
+#[cfg(feature = "opentelemetry")]
 impl From<opentelemetry::trace::SpanRef<'_>> for Context {
     fn from(span: opentelemetry::trace::SpanRef<'_>) -> Self {
         let otel_ctx = span.span_context();
@@ -200,10 +488,12 @@ impl From<opentelemetry::trace::SpanRef<'_>> for Context {
             trace_id: TraceId::from(otel_ctx.trace_id()),
             span_id: SpanId::from(otel_ctx.span_id()),
             sampling_decision: SamplingDecision::from(otel_ctx),
+            trace_state: TraceState::parse(&otel_ctx.trace_state().header()).unwrap_or_default(),
         }
     }
 }
 
+#[cfg(feature = "opentelemetry")]
 impl From<SamplingDecision> for opentelemetry::trace::TraceFlags {
     fn from(decision: SamplingDecision) -> Self {
         match decision {
@@ -213,6 +503,7 @@ impl From<SamplingDecision> for opentelemetry::trace::TraceFlags {
     }
 }
 
+#[cfg(feature = "opentelemetry")]
 impl From<&opentelemetry::trace::SpanContext> for SamplingDecision {
     fn from(context: &opentelemetry::trace::SpanContext) -> Self {
         if context.is_sampled() {
@@ -223,16 +514,25 @@ impl From<&opentelemetry::trace::SpanContext> for SamplingDecision {
     }
 }
 
-impl Default for SamplingDecision {
-    fn default() -> Self {
-        Self::Unsampled
-    }
-}
-
 /// Returned when a [`Context`] cannot be constructed from a [`Span`](tracing::Span).
 #[derive(Debug)]
 pub struct NoActiveSpan;
 
+/// Returned when a W3C `traceparent` header is malformed.
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+#[error("invalid traceparent header")]
+pub struct InvalidTraceparent;
+
+/// Returned when a W3C `tracestate` header is malformed.
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+#[error("invalid tracestate header")]
+pub struct InvalidTraceState;
+
+/// Returned when B3 headers are malformed, or carry no trace IDs.
+#[derive(Debug, thiserror::Error, PartialEq, Eq)]
+#[error("invalid B3 headers")]
+pub struct InvalidB3;
+
 impl fmt::Display for TraceId {
     fn fmt(&self, f: &mut Formatter) -> Result<(), fmt::Error> {
         write!(f, "{:02x}", self.0)?;
@@ -279,3 +579,134 @@ mod u128_serde {
         )?))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+    #[test]
+    fn w3c_headers_round_trip() {
+        let context = Context::from_w3c(
+            TRACEPARENT,
+            Some("rojo=00f067aa0ba902b7, congo=t61rcWkgMzE"),
+        )
+        .unwrap();
+        assert_eq!(
+            u128::from(context.trace_id),
+            0x4bf92f3577b34da6a3ce929d0e0e4736
+        );
+        assert_eq!(u64::from(context.span_id), 0x00f067aa0ba902b7);
+        assert_eq!(context.sampling_decision, SamplingDecision::Sampled);
+        assert_eq!(context.trace_state.get("congo"), Some("t61rcWkgMzE"));
+        assert_eq!(
+            context.trace_state.header(),
+            "rojo=00f067aa0ba902b7,congo=t61rcWkgMzE"
+        );
+        assert_eq!(context.traceparent(), TRACEPARENT);
+        assert_eq!(context.new_child().trace_state, context.trace_state);
+    }
+
+    #[test]
+    fn malformed_traceparents_are_rejected() {
+        for traceparent in [
+            "",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e473-00f067aa0ba902b7-01",
+            "00-+bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        ] {
+            assert_eq!(
+                Context::from_w3c(traceparent, None),
+                Err(InvalidTraceparent),
+                "{traceparent}"
+            );
+        }
+        // Later versions may append fields.
+        let context = Context::from_w3c(
+            "01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00-extra",
+            None,
+        )
+        .unwrap();
+        assert_eq!(context.sampling_decision, SamplingDecision::Unsampled);
+    }
+
+    #[test]
+    fn b3_headers_round_trip() {
+        let context = Context::from_b3(
+            "80f198ee56343ba864fe8b2a57d3eff7-e457b5a2e4d86bd1-1-05e3ac9a4f6e3b90",
+        )
+        .unwrap();
+        assert_eq!(
+            u128::from(context.trace_id),
+            0x80f198ee56343ba864fe8b2a57d3eff7
+        );
+        assert_eq!(u64::from(context.span_id), 0xe457b5a2e4d86bd1);
+        assert_eq!(context.sampling_decision, SamplingDecision::Sampled);
+        assert_eq!(
+            context.b3(),
+            "80f198ee56343ba864fe8b2a57d3eff7-e457b5a2e4d86bd1-1"
+        );
+        assert_eq!(Context::from_b3(&context.b3()), Ok(context.clone()));
+
+        let [(_, trace_id), (_, span_id), (_, sampled)] = context.b3_multi();
+        assert_eq!(
+            Context::from_b3_multi(&trace_id, &span_id, Some(&sampled), None),
+            Ok(context)
+        );
+
+        let context =
+            Context::from_b3_multi("64fe8b2a57d3eff7", "e457b5a2e4d86bd1", None, Some("1"))
+                .unwrap();
+        assert_eq!(u128::from(context.trace_id), 0x64fe8b2a57d3eff7);
+        assert_eq!(context.sampling_decision, SamplingDecision::Sampled);
+        let context = Context::from_b3("64fe8b2a57d3eff7-e457b5a2e4d86bd1").unwrap();
+        assert_eq!(context.sampling_decision, SamplingDecision::Unsampled);
+    }
+
+    #[test]
+    fn malformed_b3_headers_are_rejected() {
+        for b3 in [
+            "",
+            "0",
+            "80f198ee56343ba864fe8b2a57d3eff7",
+            "80f198ee56343ba864fe8b2a57d3eff7-e457b5a2e4d86bd1-2",
+            "80f198ee56343ba864fe8b2a57d3eff7-e457b5a2e4d86bd1-1-05e3ac9a4f6e3b90-extra",
+            "80F198EE56343BA864FE8B2A57D3EFF7-e457b5a2e4d86bd1-1",
+            "80f198ee56343ba864fe8b2a57d3ef-e457b5a2e4d86bd1-1",
+            "00000000000000000000000000000000-e457b5a2e4d86bd1-1",
+            "80f198ee56343ba864fe8b2a57d3eff7-0000000000000000-1",
+        ] {
+            assert_eq!(Context::from_b3(b3), Err(InvalidB3), "{b3}");
+        }
+        assert_eq!(
+            Context::from_b3_multi("64fe8b2a57d3eff7", "e457b5a2e4d86bd1", Some("yes"), None),
+            Err(InvalidB3)
+        );
+    }
+
+    #[test]
+    fn malformed_tracestates_are_discarded() {
+        for tracestate in ["rojo", "Rojo=1", "rojo=1,rojo=2", "rojo=a=b", "rojo=é"] {
+            assert_eq!(TraceState::parse(tracestate), Err(InvalidTraceState));
+            let context = Context::from_w3c(TRACEPARENT, Some(tracestate)).unwrap();
+            assert!(context.trace_state.is_empty());
+        }
+        let members = (0..=TraceState::MAX_MEMBERS)
+            .map(|i| format!("k{i}=v"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            TraceState::parse(&members.join(",")),
+            Err(InvalidTraceState)
+        );
+        assert_eq!(
+            TraceState::parse(" ,vendor@tenant=x,, ").map(|state| state.0),
+            Ok("vendor@tenant=x".into())
+        );
+    }
+}
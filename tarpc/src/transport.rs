@@ -10,6 +10,193 @@
 //! can be plugged in, using whatever protocol it wants.
 
 pub mod channel;
+pub mod chaos;
+pub mod symmetric;
+
+use std::future::Future;
+use std::{
+    error::Error,
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+use tokio::time::Sleep;
+
+/// When a channel flushes the messages it has written to its transport.
+///
+/// Each flush of a byte-stream transport is usually a syscall, so flushing less often raises
+/// throughput at the cost of latency. Regardless of the policy, a channel also flushes whenever
+/// its transport has no room for more messages, and when it closes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum FlushPolicy {
+    /// Flush whenever there are no more messages ready to write.
+    #[default]
+    WhenIdle,
+    /// Flush after writing each message.
+    Immediately,
+    /// Flush once `max_messages` messages are written but unflushed, or `max_delay` after the
+    /// first unflushed message was written, whichever comes first. Idle channels don't flush
+    /// sooner, so messages wait up to `max_delay` to be sent.
+    Batch {
+        /// The number of messages that are flushed together.
+        max_messages: usize,
+        /// The longest a message waits to be flushed.
+        max_delay: Duration,
+    },
+    /// Flush batches whose size adapts to the load, like Nagle's algorithm with a cap.
+    ///
+    /// The batch size starts at one, which flushes like [`WhenIdle`](FlushPolicy::WhenIdle), so
+    /// under light load each message is flushed as soon as there are no more to write. Flushing
+    /// more messages than the batch size, or a full batch while more messages are waiting to be
+    /// written, doubles the batch size, up to `max_messages`, and flushing a batch before it
+    /// fills up halves it. While the batch size is above one, an idle channel waits up to
+    /// `max_delay` after the first unflushed message for the batch to fill. A `max_messages` of
+    /// one disables batching.
+    Adaptive {
+        /// The largest number of messages that are flushed together.
+        max_messages: usize,
+        /// The longest a message waits to be flushed.
+        max_delay: Duration,
+    },
+}
+
+/// Tracks the messages written but not yet flushed by a channel to decide when to flush them,
+/// according to a [`FlushPolicy`].
+#[derive(Debug)]
+pub(crate) struct Flusher {
+    policy: FlushPolicy,
+    /// The number of messages written since the last flush.
+    unflushed: usize,
+    /// The number of messages an adaptive policy currently flushes together.
+    batch_size: usize,
+    /// Whether there were no more messages to write when the written messages became due.
+    idle: bool,
+    /// Fires when the first unflushed message has waited the policy's `max_delay`.
+    timer: Option<Pin<Box<Sleep>>>,
+}
+
+impl Flusher {
+    pub(crate) fn new(policy: FlushPolicy) -> Self {
+        Self {
+            policy,
+            unflushed: 0,
+            batch_size: 1,
+            idle: false,
+            timer: None,
+        }
+    }
+
+    /// The number of messages an adaptive policy currently flushes together.
+    #[cfg(test)]
+    pub(crate) fn batch_size(&self) -> usize {
+        self.batch_size
+    }
+
+    /// Records that a message was written.
+    pub(crate) fn written(&mut self) {
+        self.unflushed += 1;
+        self.idle = false;
+        let max_delay = match self.policy {
+            FlushPolicy::Batch { max_delay, .. } => max_delay,
+            FlushPolicy::Adaptive { max_delay, .. } if self.batch_size > 1 => max_delay,
+            _ => return,
+        };
+        if self.timer.is_none() {
+            self.timer = Some(Box::pin(tokio::time::sleep(max_delay)));
+        }
+    }
+
+    /// Records that the written messages were flushed.
+    pub(crate) fn flushed(&mut self) {
+        if let FlushPolicy::Adaptive { max_messages, .. } = self.policy {
+            if self.unflushed > self.batch_size || (!self.idle && self.unflushed == self.batch_size)
+            {
+                // Messages are written faster than batches are flushed, so batch more of them.
+                self.batch_size = self.batch_size.saturating_mul(2).min(max_messages.max(1));
+            } else if self.idle && self.unflushed > 0 && self.unflushed < self.batch_size {
+                self.batch_size = (self.batch_size / 2).max(1);
+            }
+        }
+        self.unflushed = 0;
+        self.idle = false;
+        self.timer = None;
+    }
+
+    /// Returns true iff the written messages should be flushed before writing more.
+    pub(crate) fn is_due(&self) -> bool {
+        match self.policy {
+            FlushPolicy::WhenIdle => false,
+            FlushPolicy::Immediately => self.unflushed > 0,
+            FlushPolicy::Batch { max_messages, .. } => self.unflushed >= max_messages.max(1),
+            FlushPolicy::Adaptive { .. } => {
+                self.batch_size > 1 && self.unflushed >= self.batch_size
+            }
+        }
+    }
+
+    /// Returns ready iff the written messages should be flushed now that there are no more
+    /// messages to write. Otherwise, wakes `cx` when they should be.
+    pub(crate) fn poll_idle(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        let ready = self.unflushed == 0
+            || self.is_due()
+            || match &mut self.timer {
+                Some(timer) => timer.as_mut().poll(cx).is_ready(),
+                None => true,
+            };
+        if ready {
+            self.idle = true;
+            Poll::Ready(())
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// An error reading a message that could not be decoded but left the transport intact, so that
+/// subsequent messages can still be read.
+///
+/// Transports report such errors, possibly wrapped in other errors, to let a
+/// [server](crate::server::BaseChannel) reject the offending request and keep serving the
+/// connection, rather than closing it, as its
+/// [policy](crate::server::Config::malformed_message_policy) allows.
+#[derive(Debug, thiserror::Error)]
+#[error("could not decode message")]
+pub struct MalformedMessage {
+    /// The ID of the request carried by the message, if it could be salvaged.
+    pub request_id: Option<u64>,
+    /// Why the message could not be decoded.
+    #[source]
+    pub source: Box<dyn Error + Send + Sync + 'static>,
+}
+
+impl MalformedMessage {
+    /// Returns a new error for a message that could not be decoded because of `source`.
+    pub fn new(request_id: Option<u64>, source: impl Into<Box<dyn Error + Send + Sync>>) -> Self {
+        Self {
+            request_id,
+            source: source.into(),
+        }
+    }
+
+    /// Returns the `MalformedMessage` that caused `error`, if any, looking through both error
+    /// sources and the payloads of [`io::Error`]s.
+    pub fn find<'a>(mut error: &'a (dyn Error + 'static)) -> Option<&'a MalformedMessage> {
+        loop {
+            if let Some(malformed) = error.downcast_ref::<MalformedMessage>() {
+                return Some(malformed);
+            }
+            error = match error
+                .downcast_ref::<io::Error>()
+                .and_then(io::Error::get_ref)
+            {
+                Some(inner) => inner as &(dyn Error + 'static),
+                None => error.source()?,
+            };
+        }
+    }
+}
 
 pub(crate) mod sealed {
     use futures::prelude::*;
@@ -38,3 +225,121 @@ pub(crate) mod sealed {
         type TransportError = E;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{FlushPolicy, Flusher};
+    use futures::{future::poll_fn, task::noop_waker_ref};
+    use std::{
+        task::{Context, Poll},
+        time::Duration,
+    };
+
+    fn poll_idle(flusher: &mut Flusher) -> Poll<()> {
+        flusher.poll_idle(&mut Context::from_waker(noop_waker_ref()))
+    }
+
+    #[test]
+    fn immediate_flusher_is_due_after_each_message() {
+        let mut flusher = Flusher::new(FlushPolicy::Immediately);
+        assert!(!flusher.is_due());
+        flusher.written();
+        assert!(flusher.is_due());
+        flusher.flushed();
+        assert!(!flusher.is_due());
+    }
+
+    #[test]
+    fn idle_flusher_flushes_only_when_idle() {
+        let mut flusher = Flusher::new(FlushPolicy::WhenIdle);
+        for _ in 0..100 {
+            flusher.written();
+        }
+        assert!(!flusher.is_due());
+        assert_eq!(poll_idle(&mut flusher), Poll::Ready(()));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn batch_flusher_waits_for_messages_or_delay() {
+        let mut flusher = Flusher::new(FlushPolicy::Batch {
+            max_messages: 2,
+            max_delay: Duration::from_millis(10),
+        });
+        flusher.written();
+        assert!(!flusher.is_due());
+        assert_eq!(poll_idle(&mut flusher), Poll::Pending);
+        flusher.written();
+        assert!(flusher.is_due());
+        flusher.flushed();
+
+        flusher.written();
+        let start = tokio::time::Instant::now();
+        poll_fn(|cx| flusher.poll_idle(cx)).await;
+        assert_eq!(start.elapsed(), Duration::from_millis(10));
+    }
+
+    /// Writes `messages` messages like a channel does, flushing before each message when the
+    /// flusher is due and once it's idle.
+    async fn write_burst(flusher: &mut Flusher, messages: usize) {
+        for _ in 0..messages {
+            if flusher.is_due() {
+                flusher.flushed();
+            }
+            flusher.written();
+        }
+        poll_fn(|cx| flusher.poll_idle(cx)).await;
+        flusher.flushed();
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn adaptive_flusher_flushes_immediately_under_light_load() {
+        let mut flusher = Flusher::new(FlushPolicy::Adaptive {
+            max_messages: 64,
+            max_delay: Duration::from_millis(10),
+        });
+        let start = tokio::time::Instant::now();
+        for _ in 0..10 {
+            flusher.written();
+            assert_eq!(poll_idle(&mut flusher), Poll::Ready(()));
+            flusher.flushed();
+        }
+        assert_eq!(flusher.batch_size(), 1);
+        assert_eq!(start.elapsed(), Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn adaptive_flusher_grows_batches_under_load_up_to_the_cap() {
+        let mut flusher = Flusher::new(FlushPolicy::Adaptive {
+            max_messages: 16,
+            max_delay: Duration::from_millis(10),
+        });
+        write_burst(&mut flusher, 2).await;
+        assert_eq!(flusher.batch_size(), 2);
+        write_burst(&mut flusher, 2 + 4 + 8 + 16 * 4).await;
+        assert_eq!(flusher.batch_size(), 16);
+
+        // Batches that don't fill up wait for the delay and shrink the next batch.
+        let start = tokio::time::Instant::now();
+        write_burst(&mut flusher, 1).await;
+        assert_eq!(start.elapsed(), Duration::from_millis(10));
+        assert_eq!(flusher.batch_size(), 8);
+        for _ in 0..3 {
+            write_burst(&mut flusher, 1).await;
+        }
+        assert_eq!(flusher.batch_size(), 1);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn adaptive_flusher_with_batches_of_one_never_waits() {
+        let mut flusher = Flusher::new(FlushPolicy::Adaptive {
+            max_messages: 1,
+            max_delay: Duration::from_secs(1),
+        });
+        for _ in 0..10 {
+            write_burst(&mut flusher, 10).await;
+        }
+        assert_eq!(flusher.batch_size(), 1);
+        flusher.written();
+        assert_eq!(poll_idle(&mut flusher), Poll::Ready(()));
+    }
+}
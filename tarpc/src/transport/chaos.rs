@@ -0,0 +1,452 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Injects faults into the messages read from a transport, to test how applications cope with
+//! the failures tarpc's protocol must tolerate.
+//!
+//! A [`Chaos`] transport delays, drops, duplicates, reorders, or corrupts each message it reads
+//! with the probabilities of its [`Faults`]. The faults are chosen by a random number generator
+//! seeded with [`Faults::seed`], so a run that reads the same messages in the same order injects
+//! the same faults. Messages written are passed through untouched; wrap both ends of a connection
+//! to inject faults in both directions.
+//!
+//! Only frames can be corrupted, since a message is corrupted by flipping one of its bits. To
+//! corrupt the messages of a serde transport, wrap its frames with [`Chaos::frames`] before they
+//! are decoded, so that corrupted frames go through the decoding of real ones: most fail to
+//! decode, and are handled according to the server's
+//! [policy](crate::server::Config::malformed_message_policy), while some decode into other
+//! messages.
+//!
+//! ```rust
+//! # #[cfg(not(feature = "serde-transport"))]
+//! # fn main() {}
+//! # #[cfg(feature = "serde-transport")]
+//! # fn main() {
+//! use tarpc::{
+//!     serde_transport::{ClientMessageHeader, Recoverable},
+//!     server::BaseChannel,
+//!     tokio_serde::{formats::Json, Framed},
+//!     tokio_util::codec::LengthDelimitedCodec,
+//!     transport::chaos::{Chaos, Faults},
+//!     ClientMessage, Response,
+//! };
+//!
+//! # let (io, _) = tokio::io::duplex(1024);
+//! let frames = Chaos::frames(
+//!     LengthDelimitedCodec::builder().new_framed(io),
+//!     Faults::new(7).with_corrupt(0.01),
+//! );
+//! let codec = Recoverable::new(
+//!     Json::<ClientMessage<u64>, Response<u64>>::default(),
+//!     Json::<ClientMessageHeader, ()>::default(),
+//! );
+//! let transport = Framed::<_, ClientMessage<u64>, Response<u64>, _>::new(frames, codec);
+//! let channel = BaseChannel::with_defaults(transport);
+//! # drop(channel);
+//! # }
+//! ```
+//!
+//! # Example
+//!
+//! ```rust
+//! use futures::prelude::*;
+//! use tarpc::{
+//!     client, context,
+//!     server::{self, BaseChannel, Channel},
+//!     transport::{channel, chaos::{Chaos, Faults}},
+//! };
+//!
+//! # #[cfg(not(feature = "tokio1"))]
+//! # fn main() {}
+//! # #[cfg(feature = "tokio1")]
+//! #[tokio::main]
+//! async fn main() -> anyhow::Result<()> {
+//!     let (client_transport, server_transport) = channel::unbounded();
+//!     // The server reads some requests twice, and the client some responses out of order.
+//!     let server_transport = Chaos::new(server_transport, Faults::new(7).with_duplicate(0.5));
+//!     let client_transport = Chaos::new(client_transport, Faults::new(7).with_reorder(0.5));
+//!     tokio::spawn(
+//!         BaseChannel::with_defaults(server_transport)
+//!             .execute(server::serve(|_, i: u64| async move { Ok(i + 1) }))
+//!             .for_each(|response| async move {
+//!                 tokio::spawn(response);
+//!             }),
+//!     );
+//!
+//!     let client = client::new(client::Config::default(), client_transport).spawn();
+//!     let calls = (0..10).map(|i| client.call(context::current(), "AddOne", i));
+//!     for (i, response) in future::join_all(calls).await.into_iter().enumerate() {
+//!         assert_eq!(response?, i as u64 + 1);
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+#[cfg(feature = "serde-transport")]
+use bytes::BytesMut;
+use futures::{prelude::*, ready, task::*};
+use pin_project::pin_project;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use std::{collections::VecDeque, pin::Pin, time::Duration};
+use tokio::time::Sleep;
+
+/// The probabilities with which a [`Chaos`] transport injects each fault into a message read.
+///
+/// Faults are chosen independently, in the order of the fields below, so a message can be both
+/// duplicated and delayed, say. A dropped message suffers no other faults.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[non_exhaustive]
+pub struct Faults {
+    /// Seeds the random number generator that chooses the faults.
+    pub seed: u64,
+    /// The probability that a message is discarded.
+    pub drop: f64,
+    /// The probability that one bit of a frame is flipped. Only the frames read by a transport
+    /// made with [`Chaos::frames`] are corrupted.
+    pub corrupt: f64,
+    /// The probability that a message is read twice in a row.
+    pub duplicate: f64,
+    /// The probability that a message is held back and read after the next one. A message held
+    /// back waits for the next message read from the wrapped transport, or for the wrapped
+    /// transport to end.
+    pub reorder: f64,
+    /// The probability that a message is read only after a random delay of up to `max_delay`.
+    /// Messages read after it wait for it.
+    pub delay: f64,
+    /// The longest a message is delayed.
+    pub max_delay: Duration,
+}
+
+impl Faults {
+    /// Returns faults that never happen, chosen by a random number generator seeded with `seed`.
+    pub fn new(seed: u64) -> Self {
+        Self {
+            seed,
+            ..Self::default()
+        }
+    }
+
+    /// Drops messages with probability `p`.
+    pub fn with_drop(mut self, p: f64) -> Self {
+        self.drop = p;
+        self
+    }
+
+    /// Corrupts frames with probability `p`.
+    pub fn with_corrupt(mut self, p: f64) -> Self {
+        self.corrupt = p;
+        self
+    }
+
+    /// Duplicates messages with probability `p`.
+    pub fn with_duplicate(mut self, p: f64) -> Self {
+        self.duplicate = p;
+        self
+    }
+
+    /// Reorders messages with probability `p`.
+    pub fn with_reorder(mut self, p: f64) -> Self {
+        self.reorder = p;
+        self
+    }
+
+    /// Delays messages with probability `p` by up to `max_delay`.
+    pub fn with_delay(mut self, p: f64, max_delay: Duration) -> Self {
+        self.delay = p;
+        self.max_delay = max_delay;
+        self
+    }
+}
+
+/// A transport that injects [`Faults`] into the messages read from the transport it wraps.
+#[pin_project]
+#[derive(Debug)]
+pub struct Chaos<T, Item> {
+    #[pin]
+    inner: T,
+    faults: Faults,
+    rng: StdRng,
+    /// Messages to read before reading more from the wrapped transport.
+    ready: VecDeque<Item>,
+    /// A message held back until the next one is read.
+    held: Option<Item>,
+    /// A message waiting out its delay.
+    delayed: Option<(Item, Pin<Box<Sleep>>)>,
+    /// Corrupts a message, if messages can be corrupted.
+    corrupt: Option<fn(&mut Item, &mut StdRng)>,
+}
+
+impl<T, Item> Chaos<T, Item> {
+    /// Wraps `inner`, injecting `faults` into the messages read from it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `faults` corrupts messages, which only transports of frames can do; see
+    /// [`Chaos::frames`].
+    pub fn new(inner: T, faults: Faults) -> Self {
+        assert!(
+            faults.corrupt == 0.,
+            "only frames can be corrupted; wrap the frames of the transport with `Chaos::frames`"
+        );
+        Self::with_corrupt(inner, faults, None)
+    }
+
+    fn with_corrupt(inner: T, faults: Faults, corrupt: Option<fn(&mut Item, &mut StdRng)>) -> Self {
+        Self {
+            inner,
+            rng: StdRng::seed_from_u64(faults.seed),
+            faults,
+            ready: VecDeque::new(),
+            held: None,
+            delayed: None,
+            corrupt,
+        }
+    }
+
+    /// Returns the wrapped transport.
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+}
+
+#[cfg(feature = "serde-transport")]
+#[cfg_attr(docsrs, doc(cfg(feature = "serde-transport")))]
+impl<T> Chaos<T, BytesMut> {
+    /// Wraps `inner`, a transport of frames like a length-delimited
+    /// [`Framed`](tokio_util::codec::Framed), injecting `faults` into the frames read from it. A
+    /// frame is corrupted by flipping one of its bits.
+    pub fn frames(inner: T, faults: Faults) -> Self {
+        Self::with_corrupt(inner, faults, Some(flip_bit))
+    }
+}
+
+/// Flips a random bit of `frame`, if it has any.
+#[cfg(feature = "serde-transport")]
+fn flip_bit(frame: &mut BytesMut, rng: &mut StdRng) {
+    if !frame.is_empty() {
+        let bit = rng.gen_range(0..frame.len() * 8);
+        frame[bit / 8] ^= 1 << (bit % 8);
+    }
+}
+
+/// Returns true with probability `p`.
+fn happens(rng: &mut StdRng, p: f64) -> bool {
+    p > 0. && rng.gen::<f64>() < p
+}
+
+impl<T, Item, E> Stream for Chaos<T, Item>
+where
+    T: Stream<Item = Result<Item, E>>,
+    Item: Clone,
+{
+    type Item = Result<Item, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        loop {
+            if let Some((_, delay)) = this.delayed {
+                ready!(delay.as_mut().poll(cx));
+                let (item, _) = this.delayed.take().unwrap();
+                return Poll::Ready(Some(Ok(item)));
+            }
+            if let Some(item) = this.ready.pop_front() {
+                return Poll::Ready(Some(Ok(item)));
+            }
+            let mut item = match this.inner.as_mut().poll_next(cx) {
+                Poll::Ready(Some(Ok(item))) => item,
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                Poll::Ready(None) => return Poll::Ready(this.held.take().map(Ok)),
+                Poll::Pending => return Poll::Pending,
+            };
+            let rng = &mut *this.rng;
+            if happens(rng, this.faults.drop) {
+                tracing::trace!("ChaosDropMessage");
+                continue;
+            }
+            if let Some(corrupt) = this.corrupt {
+                if happens(rng, this.faults.corrupt) {
+                    tracing::trace!("ChaosCorruptMessage");
+                    corrupt(&mut item, rng);
+                }
+            }
+            if happens(rng, this.faults.duplicate) {
+                tracing::trace!("ChaosDuplicateMessage");
+                this.ready.push_back(item.clone());
+            }
+            if let Some(held) = this.held.take() {
+                this.ready.push_back(held);
+            } else if happens(rng, this.faults.reorder) {
+                tracing::trace!("ChaosReorderMessage");
+                *this.held = Some(item);
+                continue;
+            }
+            if happens(rng, this.faults.delay) {
+                let delay = this.faults.max_delay.mul_f64(rng.gen());
+                tracing::trace!(?delay, "ChaosDelayMessage");
+                *this.delayed = Some((item, Box::pin(tokio::time::sleep(delay))));
+                continue;
+            }
+            return Poll::Ready(Some(Ok(item)));
+        }
+    }
+}
+
+impl<T, Item, SinkItem> Sink<SinkItem> for Chaos<T, Item>
+where
+    T: Sink<SinkItem>,
+{
+    type Error = T::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.project().inner.poll_ready(cx)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: SinkItem) -> Result<(), Self::Error> {
+        self.project().inner.start_send(item)
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.project().inner.poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use assert_matches::assert_matches;
+    use std::io;
+
+    /// Reads the messages 0..n through a chaos transport with `faults`.
+    async fn read(n: u32, faults: Faults) -> Vec<Result<u32, io::Error>> {
+        Chaos::new(stream::iter((0..n).map(Ok)), faults)
+            .collect()
+            .await
+    }
+
+    async fn read_ok(n: u32, faults: Faults) -> Vec<u32> {
+        read(n, faults)
+            .await
+            .into_iter()
+            .map(Result::unwrap)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn faults_are_chosen_by_the_seed() {
+        let faults = |seed| {
+            Faults::new(seed)
+                .with_drop(0.2)
+                .with_duplicate(0.2)
+                .with_reorder(0.2)
+        };
+        let first = read_ok(100, faults(1)).await;
+        assert_eq!(first, read_ok(100, faults(1)).await);
+        assert_ne!(first, read_ok(100, faults(2)).await);
+        assert_ne!(first, (0..100).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn each_fault_is_injected() {
+        assert_eq!(read_ok(4, Faults::new(0)).await, [0, 1, 2, 3]);
+        assert_eq!(read_ok(4, Faults::new(0).with_drop(1.)).await, [0; 0]);
+        assert_eq!(
+            read_ok(2, Faults::new(0).with_duplicate(1.)).await,
+            [0, 0, 1, 1]
+        );
+        assert_eq!(
+            read_ok(4, Faults::new(0).with_reorder(1.)).await,
+            [1, 0, 3, 2]
+        );
+    }
+
+    #[cfg(feature = "serde-transport")]
+    #[tokio::test]
+    async fn corrupted_frames_have_one_bit_flipped() {
+        let frames = (0..50u8).map(|i| Ok::<_, io::Error>(BytesMut::from(&[i; 4][..])));
+        let faults = Faults::new(0).with_corrupt(1.);
+        let corrupted: Vec<BytesMut> = Chaos::frames(stream::iter(frames), faults)
+            .map(Result::unwrap)
+            .collect()
+            .await;
+        for (i, frame) in corrupted.iter().enumerate() {
+            let flipped: u32 = frame.iter().map(|byte| (byte ^ i as u8).count_ones()).sum();
+            assert_eq!(flipped, 1, "{frame:?}");
+        }
+    }
+
+    #[cfg(feature = "serde-transport")]
+    #[tokio::test]
+    async fn corrupted_frames_fail_to_decode() {
+        use crate::{
+            serde_transport::{ClientMessageHeader, Recoverable},
+            transport::MalformedMessage,
+        };
+        use tokio_serde::{formats::Json, Framed};
+
+        // A frame of a JSON string with one bit flipped fails to decode, unless the string's
+        // contents were corrupted.
+        let frames = (0..100).map(|_| Ok::<_, io::Error>(BytesMut::from(&b"\"ok\""[..])));
+        let frames = Chaos::frames(stream::iter(frames), Faults::new(0).with_corrupt(1.));
+        let codec = Recoverable::new(
+            Json::<String, ()>::default(),
+            Json::<ClientMessageHeader, ()>::default(),
+        );
+        let messages: Vec<Result<String, _>> = Framed::<_, String, (), _>::new(frames, codec)
+            .collect()
+            .await;
+        let malformed = messages
+            .iter()
+            .filter(|message| {
+                message
+                    .as_ref()
+                    .is_err_and(|e| MalformedMessage::find(e).is_some())
+            })
+            .count();
+        let other_strings = messages
+            .iter()
+            .filter(|message| message.as_ref().is_ok_and(|s| s != "ok"))
+            .count();
+        assert!(malformed > 0);
+        assert_eq!(malformed + other_strings, 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "only frames can be corrupted")]
+    fn only_frames_can_be_corrupted() {
+        let _ = Chaos::<_, u32>::new(
+            stream::iter([Ok::<u32, io::Error>(0)]),
+            Faults::new(0).with_corrupt(1.),
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn delayed_messages_keep_their_order() {
+        let start = tokio::time::Instant::now();
+        let faults = Faults::new(0).with_delay(1., Duration::from_secs(1));
+        assert_eq!(read_ok(3, faults).await, [0, 1, 2]);
+        assert!(start.elapsed() > Duration::ZERO);
+        assert!(start.elapsed() <= Duration::from_secs(3));
+    }
+
+    #[tokio::test]
+    async fn held_messages_wait_for_the_next_message() {
+        let (tx, rx) = futures::channel::mpsc::unbounded::<Result<u32, io::Error>>();
+        let mut chaos = Chaos::new(rx, Faults::new(0).with_reorder(1.));
+        tx.unbounded_send(Ok(0)).unwrap();
+        assert_matches!(chaos.next().now_or_never(), None);
+        tx.unbounded_send(Ok(1)).unwrap();
+        assert_matches!(chaos.next().await, Some(Ok(1)));
+        assert_matches!(chaos.next().await, Some(Ok(0)));
+        tx.unbounded_send(Ok(2)).unwrap();
+        drop(tx);
+        assert_matches!(chaos.next().await, Some(Ok(2)));
+        assert_matches!(chaos.next().await, None);
+    }
+}
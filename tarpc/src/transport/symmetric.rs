@@ -0,0 +1,421 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Lets both peers of a single connection act as client and server at the same time.
+//!
+//! Normally, the peer that dials a connection is the client and the peer that accepts it is the
+//! server. In topologies where only one side is able to dial, e.g. an agent behind a NAT
+//! connecting to its controller, the accepting side sometimes needs to call back into a service
+//! implemented by the dialing side. [`split`] demultiplexes one transport of [`Message`]s into a
+//! client transport and a server transport, so that each peer can run a
+//! [client](crate::client) and a [server](crate::server) over the same connection.
+//!
+//! Both peers must split their end of the connection, and the client request type of one peer
+//! must be the server request type of the other.
+//!
+//! Each half buffers a bounded number of incoming messages. While the half a message is for has
+//! a full buffer, no more messages are read from the connection, so a client or server that
+//! doesn't keep up pushes back on the remote peer instead of buffering without limit.
+//!
+//! # Example
+//!
+//! ```rust
+//! use futures::prelude::*;
+//! use tarpc::{
+//!     client, context,
+//!     server::{self, BaseChannel, Channel},
+//!     transport::{channel, symmetric},
+//! };
+//!
+//! # #[cfg(not(feature = "tokio1"))]
+//! # fn main() {}
+//! # #[cfg(feature = "tokio1")]
+//! #[tokio::main]
+//! async fn main() -> anyhow::Result<()> {
+//!     let (controller, agent) = channel::unbounded();
+//!
+//!     // The controller only calls the agent's service.
+//!     let (to_agent, _, demux) = symmetric::split::<_, String, usize, (), ()>(controller);
+//!     tokio::spawn(demux);
+//!
+//!     // The agent serves the controller's requests.
+//!     let (_, from_controller, demux) = symmetric::split::<_, (), (), String, usize>(agent);
+//!     tokio::spawn(demux);
+//!     tokio::spawn(
+//!         BaseChannel::with_defaults(from_controller)
+//!             .execute(server::serve(|_, s: String| async move { Ok(s.len()) }))
+//!             .for_each(|response| async move {
+//!                 tokio::spawn(response);
+//!             }),
+//!     );
+//!
+//!     let client = client::new(client::Config::default(), to_agent).spawn();
+//!     assert_eq!(client.call(context::current(), "len", "hello".into()).await?, 5);
+//!     Ok(())
+//! }
+//! ```
+
+use crate::{
+    transport::channel::{self, Channel},
+    ClientMessage, Response,
+};
+use futures::{prelude::*, ready, task::*};
+use pin_project::pin_project;
+use std::{fmt, pin::Pin};
+
+/// A message sent over a connection shared by a client and a server.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde1", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "rkyv",
+    derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)
+)]
+pub enum Message<Req, Resp> {
+    /// A request or cancellation sent by the sending peer's client.
+    Client(ClientMessage<Req>),
+    /// A response sent by the sending peer's server.
+    Response(Response<Resp>),
+}
+
+/// The transport yielded by [`split`] for a client calling the remote peer.
+pub type ClientTransport<Req, Resp> = Channel<Response<Resp>, ClientMessage<Req>>;
+
+/// The transport yielded by [`split`] for a server serving the remote peer.
+pub type ServerTransport<Req, Resp> = Channel<ClientMessage<Req>, Response<Resp>>;
+
+/// The number of messages buffered by each half returned by [`split`].
+const DEFAULT_CAPACITY: usize = 100;
+
+/// Splits `transport` into a transport for a client sending `ClientReq`s to the remote peer and a
+/// transport for a server answering the remote peer's `ServerReq`s. Each half buffers up to 100
+/// messages in each direction.
+///
+/// The returned [`Demultiplex`] future moves messages between `transport` and the two halves; it
+/// must be spawned or polled for either half to make progress. It completes when `transport`
+/// closes or fails, or once both halves are dropped.
+#[allow(clippy::type_complexity)]
+pub fn split<T, ClientReq, ClientResp, ServerReq, ServerResp>(
+    transport: T,
+) -> (
+    ClientTransport<ClientReq, ClientResp>,
+    ServerTransport<ServerReq, ServerResp>,
+    Demultiplex<T, ClientReq, ClientResp, ServerReq, ServerResp>,
+)
+where
+    T: Stream<Item = Result<Message<ServerReq, ClientResp>, T::Error>>,
+    T: Sink<Message<ClientReq, ServerResp>>,
+{
+    split_with_capacity(transport, DEFAULT_CAPACITY)
+}
+
+/// Like [`split`], but each half buffers up to `capacity` messages in each direction.
+#[allow(clippy::type_complexity)]
+pub fn split_with_capacity<T, ClientReq, ClientResp, ServerReq, ServerResp>(
+    transport: T,
+    capacity: usize,
+) -> (
+    ClientTransport<ClientReq, ClientResp>,
+    ServerTransport<ServerReq, ServerResp>,
+    Demultiplex<T, ClientReq, ClientResp, ServerReq, ServerResp>,
+)
+where
+    T: Stream<Item = Result<Message<ServerReq, ClientResp>, T::Error>>,
+    T: Sink<Message<ClientReq, ServerResp>>,
+{
+    let (client, client_end) = channel::bounded(capacity);
+    let (server, server_end) = channel::bounded(capacity);
+    (
+        client,
+        server,
+        Demultiplex {
+            transport,
+            client: Some(client_end),
+            server: Some(server_end),
+            unrouted: None,
+        },
+    )
+}
+
+/// Moves messages between a shared transport and the client and server transports returned by
+/// [`split`].
+#[pin_project]
+#[must_use = "the client and server transports make no progress unless the demultiplexer is polled"]
+pub struct Demultiplex<T, ClientReq, ClientResp, ServerReq, ServerResp> {
+    #[pin]
+    transport: T,
+    client: Option<Channel<ClientMessage<ClientReq>, Response<ClientResp>>>,
+    server: Option<Channel<Response<ServerResp>, ClientMessage<ServerReq>>>,
+    /// A message read from the transport for a half whose buffer was full.
+    unrouted: Option<Message<ServerReq, ClientResp>>,
+}
+
+impl<T, ClientReq, ClientResp, ServerReq, ServerResp>
+    Demultiplex<T, ClientReq, ClientResp, ServerReq, ServerResp>
+where
+    T: Stream<Item = Result<Message<ServerReq, ClientResp>, T::Error>>,
+    T: Sink<Message<ClientReq, ServerResp>>,
+{
+    /// Routes incoming messages to the client or server half. Messages for a half that was
+    /// dropped are discarded. While the half the last message read is for has a full buffer, no
+    /// more messages are read.
+    fn poll_read(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), T::Error>> {
+        loop {
+            let this = self.as_mut().project();
+            match this.unrouted.take() {
+                Some(Message::Client(message)) => {
+                    if let Err(message) = send_to_half(this.server, message, cx) {
+                        *this.unrouted = Some(Message::Client(message));
+                        return Poll::Pending;
+                    }
+                }
+                Some(Message::Response(response)) => {
+                    if let Err(response) = send_to_half(this.client, response, cx) {
+                        *this.unrouted = Some(Message::Response(response));
+                        return Poll::Pending;
+                    }
+                }
+                None => match ready!(this.transport.poll_next(cx)?) {
+                    Some(message) => *this.unrouted = Some(message),
+                    None => return Poll::Ready(Ok(())),
+                },
+            }
+        }
+    }
+
+    /// Writes outgoing messages from the client and server halves to the transport. Returns
+    /// ready once both halves are dropped and all their messages written.
+    fn poll_write(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), T::Error>> {
+        loop {
+            let mut this = self.as_mut().project();
+            if this.client.is_none() && this.server.is_none() {
+                return this.transport.poll_flush(cx);
+            }
+            if this.transport.as_mut().poll_ready(cx)?.is_pending() {
+                // Flush what has been written so far while waiting for the transport.
+                let _ = this.transport.poll_flush(cx)?;
+                return Poll::Pending;
+            }
+            let message = if let Poll::Ready(Some(message)) = poll_half(this.client, cx) {
+                Message::Client(message)
+            } else if let Poll::Ready(Some(response)) = poll_half(this.server, cx) {
+                Message::Response(response)
+            } else if this.client.is_none() && this.server.is_none() {
+                continue;
+            } else {
+                ready!(this.transport.poll_flush(cx)?);
+                return Poll::Pending;
+            };
+            this.transport.start_send(message)?;
+        }
+    }
+}
+
+/// Sends `item` to a half, or discards it if the half was dropped. Returns the item unsent while
+/// the half's buffer is full.
+fn send_to_half<Item, SinkItem>(
+    half: &mut Option<Channel<Item, SinkItem>>,
+    item: SinkItem,
+    cx: &mut Context,
+) -> Result<(), SinkItem> {
+    let Some(channel) = half else {
+        return Ok(());
+    };
+    match channel.poll_ready_unpin(cx) {
+        Poll::Pending => return Err(item),
+        Poll::Ready(Ok(())) if channel.start_send_unpin(item).is_ok() => {}
+        Poll::Ready(_) => {
+            tracing::trace!("Transport half dropped; discarding message.");
+            *half = None;
+        }
+    }
+    Ok(())
+}
+
+/// Polls for the next outgoing message of a half, dropping it once its other end is dropped.
+fn poll_half<Item, SinkItem>(
+    half: &mut Option<Channel<Item, SinkItem>>,
+    cx: &mut Context,
+) -> Poll<Option<Item>> {
+    let Some(channel) = half else {
+        return Poll::Ready(None);
+    };
+    match ready!(Pin::new(channel).poll_next(cx)) {
+        Some(Ok(item)) => Poll::Ready(Some(item)),
+        Some(Err(_)) | None => {
+            *half = None;
+            Poll::Ready(None)
+        }
+    }
+}
+
+impl<T, ClientReq, ClientResp, ServerReq, ServerResp> Future
+    for Demultiplex<T, ClientReq, ClientResp, ServerReq, ServerResp>
+where
+    T: Stream<Item = Result<Message<ServerReq, ClientResp>, T::Error>>,
+    T: Sink<Message<ClientReq, ServerResp>>,
+{
+    type Output = Result<(), T::Error>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        if self.as_mut().poll_read(cx)?.is_ready() {
+            tracing::trace!("Shared transport closed.");
+            return Poll::Ready(Ok(()));
+        }
+        if self.as_mut().poll_write(cx)?.is_ready() {
+            tracing::trace!("Client and server transports dropped; closing shared transport.");
+            return self.project().transport.poll_close(cx);
+        }
+        Poll::Pending
+    }
+}
+
+impl<T, ClientReq, ClientResp, ServerReq, ServerResp> fmt::Debug
+    for Demultiplex<T, ClientReq, ClientResp, ServerReq, ServerResp>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Demultiplex")
+            .field("client", &self.client.is_some())
+            .field("server", &self.server.is_some())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{context, transport::channel::ChannelError, Request};
+    use assert_matches::assert_matches;
+
+    fn cx() -> Context<'static> {
+        Context::from_waker(noop_waker_ref())
+    }
+
+    type Peer = channel::UnboundedChannel<Message<u32, String>, Message<u32, String>>;
+
+    fn request(id: u64, message: u32) -> ClientMessage<u32> {
+        ClientMessage::Request(Request {
+            context: context::current(),
+            id,
+            message,
+            oneway: false,
+        })
+    }
+
+    fn response(request_id: u64, message: &str) -> Response<String> {
+        Response {
+            request_id,
+            message: Ok(message.into()),
+            extensions: Default::default(),
+            partial: false,
+        }
+    }
+
+    #[test]
+    fn routes_messages_to_halves() {
+        let (local, mut remote): (Peer, Peer) = channel::unbounded();
+        let (mut client, mut server, demux) = split::<_, u32, String, u32, String>(local);
+        let mut demux = Box::pin(demux);
+
+        remote
+            .start_send_unpin(Message::Client(request(0, 1)))
+            .unwrap();
+        remote
+            .start_send_unpin(Message::Response(response(7, "reply")))
+            .unwrap();
+        assert_matches!(demux.as_mut().poll(&mut cx()), Poll::Pending);
+
+        assert_matches!(
+            server.poll_next_unpin(&mut cx()),
+            Poll::Ready(Some(Ok(ClientMessage::Request(Request {
+                id: 0,
+                message: 1,
+                ..
+            }))))
+        );
+        assert_matches!(
+            client.poll_next_unpin(&mut cx()),
+            Poll::Ready(Some(Ok(Response { request_id: 7, message: Ok(ref m), .. }))) if m == "reply"
+        );
+    }
+
+    #[test]
+    fn writes_messages_from_halves() {
+        let (local, mut remote): (Peer, Peer) = channel::unbounded();
+        let (mut client, mut server, demux) = split::<_, u32, String, u32, String>(local);
+        let mut demux = Box::pin(demux);
+
+        client.start_send_unpin(request(3, 4)).unwrap();
+        server.start_send_unpin(response(5, "done")).unwrap();
+        assert_matches!(demux.as_mut().poll(&mut cx()), Poll::Pending);
+
+        assert_matches!(
+            remote.poll_next_unpin(&mut cx()),
+            Poll::Ready(Some(Ok(Message::Client(ClientMessage::Request(Request {
+                id: 3,
+                message: 4,
+                ..
+            })))))
+        );
+        assert_matches!(
+            remote.poll_next_unpin(&mut cx()),
+            Poll::Ready(Some(Ok(Message::Response(Response { request_id: 5, .. }))))
+        );
+    }
+
+    #[test]
+    fn stops_reading_while_a_half_is_full() {
+        let (local, mut remote): (Peer, Peer) = channel::unbounded();
+        let (_client, mut server, demux) =
+            split_with_capacity::<_, u32, String, u32, String>(local, 0);
+        let mut demux = Box::pin(demux);
+
+        for id in 0..4 {
+            remote
+                .start_send_unpin(Message::Client(request(id, 0)))
+                .unwrap();
+        }
+        let mut ids = vec![];
+        while ids.len() < 4 {
+            assert_matches!(demux.as_mut().poll(&mut cx()), Poll::Pending);
+            let buffered = ids.len();
+            while let Poll::Ready(Some(Ok(ClientMessage::Request(request)))) =
+                server.poll_next_unpin(&mut cx())
+            {
+                ids.push(request.id);
+            }
+            assert_eq!(ids.len(), buffered + 1, "read past a full half");
+        }
+        assert_eq!(ids, [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn completes_when_halves_are_dropped() {
+        let (local, _remote): (Peer, Peer) = channel::unbounded();
+        let (client, server, demux) = split::<_, u32, String, u32, String>(local);
+        let mut demux = Box::pin(demux);
+        assert_matches!(demux.as_mut().poll(&mut cx()), Poll::Pending);
+
+        drop(client);
+        assert_matches!(demux.as_mut().poll(&mut cx()), Poll::Pending);
+        drop(server);
+        assert_matches!(
+            demux.as_mut().poll(&mut cx()),
+            Poll::Ready(Ok::<(), ChannelError>(()))
+        );
+    }
+
+    #[test]
+    fn completes_when_transport_closes() {
+        let (local, remote): (Peer, Peer) = channel::unbounded();
+        let (mut client, _server, demux) = split::<_, u32, String, u32, String>(local);
+        let mut demux = Box::pin(demux);
+
+        drop(remote);
+        assert_matches!(demux.as_mut().poll(&mut cx()), Poll::Ready(Ok(())));
+        drop(demux);
+        assert_matches!(client.poll_next_unpin(&mut cx()), Poll::Ready(None));
+    }
+}
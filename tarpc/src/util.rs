@@ -7,14 +7,18 @@
 use std::{
     collections::HashMap,
     hash::{BuildHasher, Hash},
-    time::{Duration, SystemTime},
+    task::{Context, Poll},
+    time::{Duration, Instant, SystemTime},
 };
 
+pub mod request_map;
+pub mod scoped;
 #[cfg(feature = "serde1")]
 #[cfg_attr(docsrs, doc(cfg(feature = "serde1")))]
 pub mod serde;
 
-/// Extension trait for [SystemTimes](SystemTime) in the future, i.e. deadlines.
+/// Extension trait for [SystemTimes](SystemTime) and [Instants](Instant) in the future, i.e.
+/// deadlines.
 pub trait TimeUntil {
     /// How much time from now until this time is reached.
     fn time_until(&self) -> Duration;
@@ -26,6 +30,95 @@ impl TimeUntil for SystemTime {
     }
 }
 
+impl TimeUntil for Instant {
+    fn time_until(&self) -> Duration {
+        self.saturating_duration_since(now())
+    }
+}
+
+/// The current instant of tokio's clock: that of the system, unless the runtime's time is paused
+/// or simulated, e.g. in tests or deterministic network simulators. Deadlines are measured against
+/// it, so that they pass with the time of the timers that enforce them.
+pub(crate) fn now() -> Instant {
+    tokio::time::Instant::now().into_std()
+}
+
+/// The instant `timeout` from now, or the furthest representable instant if that overflows, as
+/// can happen for untrusted timeouts read off the wire.
+#[cfg(any(feature = "serde1", feature = "rkyv"))]
+pub(crate) fn instant_after(timeout: Duration) -> Instant {
+    let now = now();
+    now.checked_add(timeout).unwrap_or_else(|| {
+        // A century is representable on every supported platform.
+        now + Duration::from_secs(100 * 365 * 24 * 60 * 60)
+    })
+}
+
+/// The time of the system clock corresponding to `instant`, for display.
+pub(crate) fn system_time(instant: Instant) -> SystemTime {
+    let (now, system_now) = (now(), SystemTime::now());
+    match instant.checked_duration_since(now) {
+        Some(remaining) => system_now + remaining,
+        None => system_now - now.duration_since(instant),
+    }
+}
+
+/// Splits a request name like `World.hello` into the `rpc.service` and `rpc.method` of the
+/// OpenTelemetry semantic conventions. Names without a service have an empty one.
+pub(crate) fn rpc_service_and_method(request_name: &'static str) -> (&'static str, &'static str) {
+    request_name.rsplit_once('.').unwrap_or(("", request_name))
+}
+
+/// The name of the span of a request, `{rpc.service}/{rpc.method}`, per the OpenTelemetry
+/// semantic conventions.
+pub(crate) fn rpc_span_name(service: &str, method: &str) -> String {
+    if service.is_empty() {
+        method.to_owned()
+    } else {
+        format!("{service}/{method}")
+    }
+}
+
+/// Makes a task that processes frames in a loop yield to the executor after every
+/// `frames_per_yield` frames, so that a flood of frames doesn't starve the tasks sharing its
+/// worker.
+#[derive(Debug)]
+pub(crate) struct YieldBudget {
+    frames_per_yield: Option<usize>,
+    /// The number of frames the task may process before it next yields.
+    remaining: usize,
+}
+
+impl YieldBudget {
+    pub(crate) fn new(frames_per_yield: Option<usize>) -> Self {
+        Self {
+            frames_per_yield,
+            remaining: frames_per_yield.unwrap_or_default().max(1),
+        }
+    }
+
+    /// Returns ready iff the task may process another frame. Otherwise, wakes the task so that it
+    /// is polled again once the tasks queued behind it had a turn.
+    pub(crate) fn poll_proceed(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        let frames_per_yield = match self.frames_per_yield {
+            Some(frames_per_yield) => frames_per_yield,
+            None => return Poll::Ready(()),
+        };
+        if self.remaining == 0 {
+            self.remaining = frames_per_yield.max(1);
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        self.remaining -= 1;
+        Poll::Ready(())
+    }
+
+    /// Records that the task yielded because it had no more frames to process.
+    pub(crate) fn reset(&mut self) {
+        self.remaining = self.frames_per_yield.unwrap_or_default().max(1);
+    }
+}
+
 /// Collection compaction; configurable `shrink_to_fit`.
 pub trait Compact {
     /// Compacts space if the ratio of length : capacity is less than `usage_ratio_threshold`.
@@ -44,6 +137,31 @@ where
     }
 }
 
+#[test]
+fn yield_budget_yields_after_its_frames() {
+    use futures::task::noop_waker_ref;
+    use futures_test::task::new_count_waker;
+
+    let (waker, wakes) = new_count_waker();
+    let cx = &mut Context::from_waker(&waker);
+    let mut budget = YieldBudget::new(Some(2));
+    assert_eq!(budget.poll_proceed(cx), Poll::Ready(()));
+    assert_eq!(budget.poll_proceed(cx), Poll::Ready(()));
+    assert_eq!(budget.poll_proceed(cx), Poll::Pending);
+    assert_eq!(wakes.get(), 1);
+    assert_eq!(budget.poll_proceed(cx), Poll::Ready(()));
+    budget.reset();
+    assert_eq!(budget.poll_proceed(cx), Poll::Ready(()));
+    assert_eq!(budget.poll_proceed(cx), Poll::Ready(()));
+    assert_eq!(budget.poll_proceed(cx), Poll::Pending);
+
+    let cx = &mut Context::from_waker(noop_waker_ref());
+    let mut unlimited = YieldBudget::new(None);
+    for _ in 0..1000 {
+        assert_eq!(unlimited.poll_proceed(cx), Poll::Ready(()));
+    }
+}
+
 #[test]
 fn test_compact() {
     let mut map = HashMap::with_capacity(2048);
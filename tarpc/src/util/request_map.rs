@@ -0,0 +1,258 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! A map of in-flight requests keyed by request ID, which exploits that clients allocate IDs
+//! sequentially.
+//!
+//! The requests in flight on a channel have IDs in a narrow, advancing range, so they're stored in
+//! a window of slots indexed by their offset from the oldest ID: looking up, inserting, and
+//! removing a request neither hashes its ID nor rebalances anything. IDs that don't fit the
+//! window, e.g. of a request that stays in flight long after those around it complete, spill into
+//! a hash map, so memory stays proportional to the number of requests in flight.
+
+use super::Compact;
+use fnv::FnvHashMap;
+use std::collections::VecDeque;
+
+/// The number of slots the window may always span, however few requests are in flight.
+const MIN_WINDOW: usize = 64;
+
+/// A map from request IDs to `T`.
+#[derive(Debug)]
+pub struct RequestMap<T> {
+    /// The ID of the first slot of `window`.
+    base: u64,
+    /// The slots of the IDs from `base`, in order. The first slot, if any, is occupied.
+    window: VecDeque<Option<T>>,
+    /// The values whose IDs didn't fit in the window when inserted or were evicted from it.
+    overflow: FnvHashMap<u64, T>,
+    /// The number of occupied slots in the window.
+    window_len: usize,
+}
+
+impl<T> Default for RequestMap<T> {
+    fn default() -> Self {
+        Self {
+            base: 0,
+            window: VecDeque::new(),
+            overflow: FnvHashMap::default(),
+            window_len: 0,
+        }
+    }
+}
+
+impl<T> RequestMap<T> {
+    /// Returns the number of entries.
+    pub fn len(&self) -> usize {
+        self.window_len + self.overflow.len()
+    }
+
+    /// Returns true iff there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of slots the window may span.
+    fn max_window(&self) -> usize {
+        MIN_WINDOW.max(2 * self.len())
+    }
+
+    /// The index in the window of the slot of `id`, if it's within the window.
+    fn index(&self, id: u64) -> Option<usize> {
+        let offset = usize::try_from(id.checked_sub(self.base)?).ok()?;
+        if offset < self.window.len() {
+            Some(offset)
+        } else {
+            None
+        }
+    }
+
+    /// Returns true iff there's an entry for `id`.
+    pub fn contains_key(&self, id: u64) -> bool {
+        self.get(id).is_some()
+    }
+
+    /// Returns the entry for `id`, if any.
+    pub fn get(&self, id: u64) -> Option<&T> {
+        if let Some(value) = self.index(id).and_then(|i| self.window[i].as_ref()) {
+            return Some(value);
+        }
+        if self.overflow.is_empty() {
+            return None;
+        }
+        self.overflow.get(&id)
+    }
+
+    /// Returns the entry for `id` mutably, if any.
+    pub fn get_mut(&mut self, id: u64) -> Option<&mut T> {
+        if let Some(i) = self.index(id) {
+            if self.window[i].is_some() {
+                return self.window[i].as_mut();
+            }
+        }
+        self.overflow.get_mut(&id)
+    }
+
+    /// Inserts an entry for `id`, which must not have one.
+    pub fn insert(&mut self, id: u64, value: T) {
+        debug_assert!(!self.contains_key(id), "request {id} is already in the map");
+        // Evict the oldest entries until `id` fits in the window.
+        let max_window = self.max_window() as u64;
+        while !self.window.is_empty() && id.saturating_sub(self.base) >= max_window {
+            if let Some(value) = self.window.pop_front().flatten() {
+                self.window_len -= 1;
+                self.overflow.insert(self.base, value);
+            }
+            self.base += 1;
+            self.trim();
+        }
+        if self.window.is_empty() {
+            self.base = id;
+        } else if id < self.base {
+            self.overflow.insert(id, value);
+            return;
+        }
+        let offset = (id - self.base) as usize;
+        if offset >= self.window.len() {
+            self.window.resize_with(offset + 1, || None);
+        }
+        self.window[offset] = Some(value);
+        self.window_len += 1;
+    }
+
+    /// Removes and returns the entry for `id`, if any.
+    pub fn remove(&mut self, id: u64) -> Option<T> {
+        if let Some(value) = self.index(id).and_then(|i| self.window[i].take()) {
+            self.window_len -= 1;
+            self.trim();
+            return Some(value);
+        }
+        if self.overflow.is_empty() {
+            return None;
+        }
+        self.overflow.remove(&id)
+    }
+
+    /// Removes the vacant slots at the ends of the window.
+    fn trim(&mut self) {
+        while let Some(None) = self.window.front() {
+            self.window.pop_front();
+            self.base += 1;
+        }
+        while let Some(None) = self.window.back() {
+            self.window.pop_back();
+        }
+    }
+
+    /// Returns an iterator over the entries, in no particular order.
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.window.iter().flatten().chain(self.overflow.values())
+    }
+
+    /// Removes all entries, returning an iterator over them in no particular order.
+    pub fn drain(&mut self) -> impl Iterator<Item = T> + '_ {
+        self.window_len = 0;
+        self.window
+            .drain(..)
+            .flatten()
+            .chain(self.overflow.drain().map(|(_, value)| value))
+    }
+}
+
+/// Splits a request ID into the index of the shard holding it, of `shards`, and its ID within the
+/// shard. Sequential request IDs are spread evenly across the shards and stay sequential within
+/// each shard, so that the window of each shard's map stays dense.
+pub fn shard(request_id: u64, shards: usize) -> (usize, u64) {
+    let shards = shards.max(1) as u64;
+    ((request_id % shards) as usize, request_id / shards)
+}
+
+impl<T> Compact for RequestMap<T> {
+    fn compact(&mut self, usage_ratio_threshold: f64) {
+        let usage_ratio_threshold = usage_ratio_threshold.clamp(f64::MIN_POSITIVE, 1.);
+        let cap = f64::max(1000., self.window.len() as f64 / usage_ratio_threshold);
+        self.window.shrink_to(cap as usize);
+        self.overflow.compact(usage_ratio_threshold);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sequential_ids_stay_in_the_window() {
+        let mut map = RequestMap::default();
+        for id in 0..100 {
+            map.insert(id, id);
+        }
+        for id in 0..90 {
+            assert_eq!(map.remove(id), Some(id));
+        }
+        for id in 100..200 {
+            map.insert(id, id);
+        }
+        assert_eq!(map.len(), 110);
+        assert!(map.overflow.is_empty());
+        assert_eq!(map.base, 90);
+        assert_eq!(map.get(150), Some(&150));
+        assert_eq!(map.get(50), None);
+    }
+
+    #[test]
+    fn stragglers_are_evicted_from_the_window() {
+        let mut map = RequestMap::default();
+        map.insert(0, 0);
+        for id in 1..10_000 {
+            map.insert(id, id);
+            assert_eq!(map.remove(id), Some(id));
+        }
+        assert_eq!(map.overflow.len(), 1);
+        assert_eq!(map.get(0), Some(&0));
+        map.insert(10_000, 10_000);
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.window.len(), 1);
+        assert_eq!(map.remove(0), Some(0));
+        assert_eq!(map.remove(0), None);
+        assert_eq!(map.get(10_000), Some(&10_000));
+    }
+
+    #[test]
+    fn ids_out_of_order_are_found() {
+        let mut map = RequestMap::default();
+        for id in [u64::MAX, 1_000_000, 5, 3, 4, 0] {
+            map.insert(id, id);
+        }
+        for id in [u64::MAX, 1_000_000, 5, 3, 4, 0] {
+            assert!(map.contains_key(id));
+        }
+        assert!(!map.contains_key(1));
+        let mut values = map.drain().collect::<Vec<_>>();
+        values.sort_unstable();
+        assert_eq!(values, [0, 3, 4, 5, 1_000_000, u64::MAX]);
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn shards_see_sequential_ids() {
+        assert_eq!(shard(13, 1), (0, 13));
+        assert_eq!(shard(13, 0), (0, 13));
+        let shards = (0..8).map(|id| shard(id, 4)).collect::<Vec<_>>();
+        assert_eq!(
+            shards,
+            [
+                (0, 0),
+                (1, 0),
+                (2, 0),
+                (3, 0),
+                (0, 1),
+                (1, 1),
+                (2, 1),
+                (3, 1)
+            ]
+        );
+    }
+}
@@ -0,0 +1,115 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! A future that exposes a value through a thread local while it is being polled, similar to a
+//! task-local, but without a dependency on a specific runtime.
+
+use futures::{prelude::*, ready, task::*};
+use pin_project::pin_project;
+use std::{cell::RefCell, pin::Pin, thread::LocalKey};
+
+/// A thread local holding the value of the innermost [`Scoped`] future being polled, if any.
+pub type ScopedKey<T> = LocalKey<RefCell<Option<T>>>;
+
+/// Runs `f` with the value of the innermost [`Scoped`] future currently being polled on this
+/// thread. Returns `None` if no such future is being polled.
+pub fn with<T, R>(key: &'static ScopedKey<T>, f: impl FnOnce(&mut T) -> R) -> Option<R> {
+    key.with(|cell| cell.borrow_mut().as_mut().map(f))
+}
+
+/// A future that installs `value` in `key` while polling the inner future, and yields the value
+/// alongside the inner future's output.
+#[pin_project]
+#[derive(Debug)]
+pub struct Scoped<T: 'static, F> {
+    key: &'static ScopedKey<T>,
+    value: Option<T>,
+    #[pin]
+    future: F,
+}
+
+impl<T, F> Scoped<T, F> {
+    pub fn new(key: &'static ScopedKey<T>, value: T, future: F) -> Self {
+        Self {
+            key,
+            value: Some(value),
+            future,
+        }
+    }
+}
+
+/// Restores the previously installed value when dropped, so that the scope is exited even if the
+/// inner future panics.
+struct Restore<'a, T: 'static> {
+    key: &'static ScopedKey<T>,
+    value: &'a mut Option<T>,
+    previous: Option<T>,
+}
+
+impl<T> Drop for Restore<'_, T> {
+    fn drop(&mut self) {
+        *self.value = self.key.with(|cell| cell.replace(self.previous.take()));
+    }
+}
+
+impl<T, F: Future> Future for Scoped<T, F> {
+    type Output = (F::Output, T);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let output = {
+            let previous = this.key.with(|cell| cell.replace(this.value.take()));
+            let _restore = Restore {
+                key: this.key,
+                value: &mut *this.value,
+                previous,
+            };
+            ready!(this.future.poll(cx))
+        };
+        let value = this
+            .value
+            .take()
+            .expect("Scoped future polled after completion");
+        Poll::Ready((output, value))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::block_on;
+
+    thread_local! {
+        static KEY: RefCell<Option<Vec<u32>>> = const { RefCell::new(None) };
+    }
+
+    #[test]
+    fn value_is_only_visible_while_polling() {
+        assert_eq!(with(&KEY, |v| v.push(0)), None);
+        let (_, value) = block_on(Scoped::new(&KEY, vec![], async {
+            with(&KEY, |v| v.push(1));
+            future::ready(()).await;
+            with(&KEY, |v| v.push(2));
+        }));
+        assert_eq!(value, [1, 2]);
+        assert_eq!(with(&KEY, |v| v.push(3)), None);
+    }
+
+    #[test]
+    fn nested_scopes_are_isolated() {
+        let (inner, outer) = block_on(Scoped::new(&KEY, vec![], async {
+            with(&KEY, |v| v.push(1));
+            let ((), inner) = Scoped::new(&KEY, vec![], async {
+                with(&KEY, |v| v.push(2));
+            })
+            .await;
+            with(&KEY, |v| v.push(3));
+            inner
+        }));
+        assert_eq!(inner, [2]);
+        assert_eq!(outer, [1, 3]);
+    }
+}
@@ -36,6 +36,7 @@ where
         Interrupted => 15,
         Other => 16,
         UnexpectedEof => 17,
+        Unsupported => 18,
         _ => 16,
     }
     .serialize(serializer)
@@ -68,6 +69,7 @@ where
         15 => Interrupted,
         16 => Other,
         17 => UnexpectedEof,
+        18 => Unsupported,
         _ => Other,
     })
 }
@@ -0,0 +1,155 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Provides golden-sample tests of the wire representation of services.
+//!
+//! With `#[tarpc::service(wire_compat = "dir")]`, the macro generates a test for each method,
+//! which encodes a sample request and response of the method in each format the service
+//! supports, and compares them with golden samples stored in `dir/{Service}/`, relative to the
+//! crate's manifest directory. The test fails if a change to the service alters how the method
+//! is sent, so that changes breaking compatibility with deployed peers are caught in CI.
+//!
+//! Samples hold the default value of each arg and response, so their types must implement
+//! [`Default`]. Golden samples are named after the method's wire name, so renaming a method with
+//! `#[tarpc::rename = "..."]` keeps them. Missing golden samples are written by the test, to be
+//! committed alongside the method; rerun the tests with `TARPC_BLESS=1` to overwrite changed
+//! samples when a change is intended.
+//!
+//! The formats are JSON and bincode, plus rkyv for services deriving rkyv's traits. The tests are
+//! generated in a module named after the service, so the service must be declared at module
+//! level rather than in a fn.
+//!
+//! # Example
+//!
+//! ```rust
+//! #[tarpc::service(wire_compat = "tests/wire")]
+//! trait World {
+//!     async fn hello(name: String) -> String;
+//! }
+//! ```
+
+use serde::{de::DeserializeOwned, Serialize};
+use std::{fs, path::Path};
+
+/// The environment variable that makes golden-sample tests overwrite changed samples.
+pub const BLESS_VAR: &str = "TARPC_BLESS";
+
+/// Checks the JSON and bincode encodings of `sample` against the golden samples named `name` in
+/// `dir`, writing them if they're missing.
+///
+/// # Panics
+///
+/// Panics if an encoding differs from its golden sample or if a golden sample doesn't decode.
+pub fn check<T>(dir: &Path, name: &str, sample: &T)
+where
+    T: Serialize + DeserializeOwned,
+{
+    assert_golden(
+        dir,
+        &format!("{name}.json"),
+        serde_json::to_vec_pretty(sample).expect("failed to encode sample as JSON"),
+        |golden| {
+            let decoded: T = serde_json::from_slice(golden).map_err(|e| e.to_string())?;
+            serde_json::to_vec_pretty(&decoded).map_err(|e| e.to_string())
+        },
+    );
+    assert_golden(
+        dir,
+        &format!("{name}.bincode"),
+        bincode::serialize(sample).expect("failed to encode sample with bincode"),
+        |golden| {
+            let decoded: T = bincode::deserialize(golden).map_err(|e| e.to_string())?;
+            bincode::serialize(&decoded).map_err(|e| e.to_string())
+        },
+    );
+}
+
+/// Checks the rkyv archive of `sample` against the golden sample named `name` in `dir`, writing
+/// it if it's missing.
+///
+/// # Panics
+///
+/// Panics if the archive differs from its golden sample or if the golden sample doesn't validate.
+#[cfg(feature = "rkyv")]
+pub fn check_rkyv<T>(dir: &Path, name: &str, sample: &T)
+where
+    T: rkyv::Archive + rkyv::Serialize<rkyv::ser::serializers::AllocSerializer<256>>,
+    T::Archived: for<'a> rkyv::CheckBytes<rkyv::validation::validators::DefaultValidator<'a>>
+        + rkyv::Deserialize<T, rkyv::Infallible>,
+{
+    let archive = |value: &T| {
+        rkyv::to_bytes::<_, 256>(value)
+            .map(|bytes| bytes.to_vec())
+            .map_err(|e| e.to_string())
+    };
+    assert_golden(
+        dir,
+        &format!("{name}.rkyv"),
+        archive(sample).expect("failed to archive sample"),
+        |golden| {
+            let mut aligned = rkyv::AlignedVec::with_capacity(golden.len());
+            aligned.extend_from_slice(golden);
+            let archived = rkyv::check_archived_root::<T>(&aligned).map_err(|e| e.to_string())?;
+            let decoded: T = match rkyv::Deserialize::deserialize(archived, &mut rkyv::Infallible) {
+                Ok(decoded) => decoded,
+                Err(never) => match never {},
+            };
+            archive(&decoded)
+        },
+    );
+}
+
+/// Compares `encoded` with the golden sample `file` in `dir`, and checks that the golden sample
+/// survives `round_trip`, which decodes and reencodes it.
+fn assert_golden(
+    dir: &Path,
+    file: &str,
+    encoded: Vec<u8>,
+    round_trip: impl FnOnce(&[u8]) -> Result<Vec<u8>, String>,
+) {
+    let path = dir.join(file);
+    let bless = std::env::var_os(BLESS_VAR).is_some_and(|value| value != "0");
+    let golden = match fs::read(&path) {
+        Ok(golden) if !bless => golden,
+        _ => {
+            fs::create_dir_all(dir).expect("failed to create the golden sample directory");
+            fs::write(&path, &encoded).expect("failed to write golden sample");
+            return;
+        }
+    };
+    assert!(
+        encoded == golden,
+        "the wire representation of {file} changed, which breaks compatibility with deployed \
+         peers. If the change is intended, rerun with {BLESS_VAR}=1 to update {}.",
+        path.display()
+    );
+    match round_trip(&golden) {
+        Ok(reencoded) => assert!(
+            reencoded == golden,
+            "the golden sample {} changed in a round trip",
+            path.display()
+        ),
+        Err(e) => panic!("the golden sample {} failed to decode: {e}", path.display()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checks_samples_against_golden_samples() {
+        let dir = std::env::temp_dir().join(format!("tarpc-wire-compat-{}", std::process::id()));
+        check(&dir, "Sample", &(1u8, String::from("a")));
+        assert!(dir.join("Sample.json").exists());
+        assert!(dir.join("Sample.bincode").exists());
+        check(&dir, "Sample", &(1u8, String::from("a")));
+
+        let changed = std::panic::catch_unwind(|| check(&dir, "Sample", &(2u8, String::new())));
+        assert!(changed.is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
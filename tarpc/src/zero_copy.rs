@@ -0,0 +1,221 @@
+// Copyright 2024 Google LLC
+//
+// Use of this source code is governed by an MIT-style
+// license that can be found in the LICENSE file or at
+// https://opensource.org/licenses/MIT.
+
+//! Provides zero-copy requests for services archived with rkyv.
+//!
+//! A service method can borrow its args from the received request, rather than owning copies of
+//! them, by declaring them as references to archived data:
+//!
+//! | Arg type            | Sent by the client as |
+//! |---------------------|-----------------------|
+//! | `&str`              | `String`              |
+//! | `&[T]`              | `Vec<T>`, where `T` is archived as itself, like `u8` |
+//! | `&ArchivedString`   | `String`              |
+//! | `&ArchivedVec<T>`   | `Vec<T>`, where `T` is archived as itself |
+//! | `&Archived<T>`      | `T`                   |
+//!
+//! A service with borrowed args sends its requests as [`ArchivedBytes`]: the client archives each
+//! request, and the server validates the archive, then serves it in place. Args that aren't
+//! borrowed are deserialized from the archive.
+//!
+//! # Example
+//!
+//! ```rust
+//! # use futures::{future, prelude::*};
+//! # use tarpc::{
+//! #     client, context,
+//! #     server::{self, Channel},
+//! # };
+//! #[tarpc::service]
+//! trait Storage {
+//!     /// Returns the length of a blob, without copying it.
+//!     async fn len(blob: &[u8]) -> usize;
+//! }
+//!
+//! #[derive(Clone)]
+//! struct StorageServer;
+//!
+//! impl Storage for StorageServer {
+//!     async fn len(self, _: context::Context, blob: &[u8]) -> usize {
+//!         blob.len()
+//!     }
+//! }
+//!
+//! # #[cfg(feature = "tokio1")]
+//! #[tokio::main]
+//! async fn main() -> Result<(), client::RpcError> {
+//!     let (client_transport, server_transport) = tarpc::transport::channel::unbounded();
+//!     let server = server::BaseChannel::with_defaults(server_transport);
+//!     tokio::spawn(server.execute(StorageServer.serve()).for_each(|response| async move {
+//!         tokio::spawn(response);
+//!     }));
+//!
+//!     let client = StorageClient::new(client::Config::default(), client_transport).spawn();
+//!     // The client sends the blob as an owned `Vec<u8>`.
+//!     assert_eq!(client.len(context::current(), vec![0; 1024]).await?, 1024);
+//!     Ok(())
+//! }
+//! # #[cfg(not(feature = "tokio1"))]
+//! # fn main() {}
+//! ```
+
+use crate::ServerError;
+use rkyv::{
+    ser::serializers::AllocSerializer, validation::validators::DefaultValidator, AlignedVec,
+    Archive, CheckBytes, Deserialize, Infallible, Serialize,
+};
+use std::{fmt, marker::PhantomData};
+
+/// A value of type `T`, archived with rkyv.
+pub struct ArchivedBytes<T> {
+    bytes: AlignedVec,
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> ArchivedBytes<T> {
+    /// Returns the archive in `bytes`, copied into an aligned buffer. The archive isn't
+    /// validated until it's read with [`get`](Self::get).
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let mut aligned = AlignedVec::with_capacity(bytes.len());
+        aligned.extend_from_slice(bytes);
+        Self {
+            bytes: aligned,
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns the bytes of the archive.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+impl<T> ArchivedBytes<T>
+where
+    T: Serialize<AllocSerializer<256>>,
+{
+    /// Archives `value`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `value` fails to archive, which only happens if it holds shared pointers that rkyv
+    /// can't archive.
+    pub fn new(value: &T) -> Self {
+        Self {
+            bytes: rkyv::to_bytes::<_, 256>(value).expect("failed to archive value"),
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T> ArchivedBytes<T>
+where
+    T: Archive,
+    T::Archived: for<'a> CheckBytes<DefaultValidator<'a>>,
+{
+    /// Validates the archive, then returns the archived value.
+    pub fn get(&self) -> Result<&T::Archived, ServerError> {
+        rkyv::check_archived_root::<T>(&self.bytes)
+            .map_err(|e| ServerError::bad_request(format!("invalid archived request: {e}")))
+    }
+}
+
+impl<T> Clone for ArchivedBytes<T> {
+    fn clone(&self) -> Self {
+        Self::from_bytes(&self.bytes)
+    }
+}
+
+impl<T> fmt::Debug for ArchivedBytes<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ArchivedBytes")
+            .field("len", &self.bytes.len())
+            .finish()
+    }
+}
+
+#[cfg(feature = "serde1")]
+impl<T> serde::Serialize for ArchivedBytes<T> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.bytes)
+    }
+}
+
+#[cfg(feature = "serde1")]
+impl<'de, T> serde::Deserialize<'de> for ArchivedBytes<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BytesVisitor<T>(PhantomData<fn() -> T>);
+
+        impl<'de, T> serde::de::Visitor<'de> for BytesVisitor<T> {
+            type Value = ArchivedBytes<T>;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("archived bytes")
+            }
+
+            fn visit_bytes<E: serde::de::Error>(self, bytes: &[u8]) -> Result<Self::Value, E> {
+                Ok(ArchivedBytes::from_bytes(bytes))
+            }
+
+            fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                self,
+                mut seq: A,
+            ) -> Result<Self::Value, A::Error> {
+                let mut bytes = AlignedVec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(byte) = seq.next_element()? {
+                    bytes.push(byte);
+                }
+                Ok(ArchivedBytes {
+                    bytes,
+                    marker: PhantomData,
+                })
+            }
+        }
+
+        deserializer.deserialize_bytes(BytesVisitor(PhantomData))
+    }
+}
+
+/// Deserializes an arg that isn't borrowed from its archive.
+#[doc(hidden)]
+pub fn deserialize<T>(archived: &T::Archived) -> T
+where
+    T: Archive,
+    T::Archived: Deserialize<T, Infallible>,
+{
+    match archived.deserialize(&mut Infallible) {
+        Ok(value) => value,
+        Err(never) => match never {},
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn get_validates_archive() {
+        let archived = ArchivedBytes::new(&vec![1u8, 2, 3]);
+        assert_eq!(archived.get().unwrap().as_slice(), [1, 2, 3]);
+
+        let copied = ArchivedBytes::<Vec<u8>>::from_bytes(archived.as_bytes());
+        assert_eq!(copied.get().unwrap().as_slice(), [1, 2, 3]);
+
+        let truncated = ArchivedBytes::<Vec<u8>>::from_bytes(&archived.as_bytes()[1..]);
+        let error = truncated.get().unwrap_err();
+        assert_eq!(error.kind, std::io::ErrorKind::InvalidData);
+        assert_eq!(error.reason, Some(crate::ServerErrorReason::BadRequest));
+    }
+
+    #[test]
+    fn server_errors_keep_their_kind() {
+        let error = crate::ServerError::unimplemented("no such method".into());
+        let archived = ArchivedBytes::new(&error);
+        let decoded: crate::ServerError = deserialize(archived.get().unwrap());
+        assert_eq!(decoded, error);
+        assert_eq!(decoded.kind, std::io::ErrorKind::Unsupported);
+    }
+}
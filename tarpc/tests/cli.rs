@@ -0,0 +1,75 @@
+use assert_matches::assert_matches;
+use futures::{prelude::*, stream};
+use tarpc::{
+    cli::{self, CliError, CliRequest},
+    context,
+    server::{BaseChannel, Channel},
+    transport::channel,
+};
+
+#[tarpc::service(cli = true)]
+trait Counter {
+    /// Counts from 1 to `to`.
+    async fn count(to: u32) -> impl Stream<Item = u32>;
+    /// Sums the numbers from `from` to `to`.
+    async fn total(from: u32, to_inclusive: u32) -> u32;
+}
+
+#[derive(Clone)]
+struct CounterServer;
+
+impl Counter for CounterServer {
+    async fn count(self, _: context::Context, to: u32) -> impl Stream<Item = u32> + Send + 'static {
+        stream::iter(1..=to)
+    }
+
+    async fn total(self, _: context::Context, from: u32, to_inclusive: u32) -> u32 {
+        (from..=to_inclusive).sum()
+    }
+}
+
+async fn run_counter(args: &[&str]) -> Result<String, CliError> {
+    let (tx, rx) = channel::unbounded();
+    tokio::spawn(
+        BaseChannel::with_defaults(rx)
+            .requests()
+            .execute_body(CounterServer.serve())
+            .for_each(|request| async move {
+                tokio::spawn(request);
+            }),
+    );
+    let matches = CounterRequest::command("counter")
+        .try_get_matches_from(args)
+        .unwrap();
+    let mut out = Vec::new();
+    cli::run::<CounterRequest, _, _, _>(tx, &matches, &mut out).await?;
+    Ok(String::from_utf8(out).unwrap())
+}
+
+#[tokio::test]
+async fn cli_calls_methods_with_json_args() -> anyhow::Result<()> {
+    let out = run_counter(&["counter", "total", "--from", "2", "--to-inclusive", "4"]).await?;
+    assert_eq!(out, "9\n");
+
+    let out = run_counter(&["counter", "count", "--to", "3"]).await?;
+    assert_eq!(out, "1\n2\n3\n");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn cli_rejects_invalid_args() {
+    assert_matches!(
+        run_counter(&["counter", "count", "--to", "\"three\""]).await,
+        Err(CliError::InvalidArg { arg: "to", .. })
+    );
+
+    let command = CounterRequest::command("counter");
+    assert!(command
+        .clone()
+        .try_get_matches_from(["counter", "total", "--from", "2"])
+        .is_err());
+    assert!(command.clone().try_get_matches_from(["counter"]).is_err());
+    let count = command.find_subcommand("count").unwrap();
+    assert_eq!(count.get_about(), Some("Counts from 1 to `to`."));
+}
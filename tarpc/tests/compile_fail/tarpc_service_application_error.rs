@@ -0,0 +1,7 @@
+#[tarpc::service]
+trait World {
+    #[tarpc::application_error]
+    async fn hello(name: String) -> String;
+}
+
+fn main() {}
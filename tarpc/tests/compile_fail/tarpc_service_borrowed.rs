@@ -0,0 +1,21 @@
+#[tarpc::service]
+trait Named {
+    async fn put(key: &'static str);
+}
+
+#[tarpc::service]
+trait Mutable {
+    async fn append(blob: &mut Vec<u8>);
+}
+
+#[tarpc::service]
+trait Unsupported {
+    async fn get(key: &String);
+}
+
+#[tarpc::service(derive_rkyv = false)]
+trait Unarchived {
+    async fn put(key: &str);
+}
+
+fn main() {}
@@ -0,0 +1,20 @@
+#[tarpc::service]
+trait World {
+    #[tarpc::deadline = "soon"]
+    async fn hello();
+}
+
+#[tarpc::service]
+trait Timeouts {
+    #[tarpc::deadline = "0s"]
+    async fn never();
+}
+
+#[tarpc::service]
+trait Repeated {
+    #[tarpc::deadline = "1s"]
+    #[tarpc::deadline = "2s"]
+    async fn twice();
+}
+
+fn main() {}
@@ -0,0 +1,16 @@
+#![deny(deprecated)]
+
+use tarpc::{client, context};
+
+#[tarpc::service]
+trait World {
+    #[deprecated(note = "use `greet`")]
+    async fn hello(name: String) -> String;
+    async fn greet(name: String) -> String;
+}
+
+async fn call(client: WorldClient) -> Result<String, client::RpcError> {
+    client.hello(context::current(), "Ferris".into()).await
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+#[tarpc::service(derive(Clone, PartialEq = true))]
+trait World {
+    async fn hello(name: String) -> String;
+}
+
+fn main() {}
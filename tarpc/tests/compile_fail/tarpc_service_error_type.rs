@@ -0,0 +1,14 @@
+#[tarpc::service]
+trait Store {
+    type Error = String;
+    type Error = std::io::Error;
+    async fn get(key: String) -> Result<String>;
+}
+
+#[tarpc::service]
+trait Cache {
+    type Value;
+    async fn get(key: String) -> Self::Value;
+}
+
+fn main() {}
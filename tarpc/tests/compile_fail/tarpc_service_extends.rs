@@ -0,0 +1,16 @@
+#[tarpc::service]
+trait Health {
+    async fn check() -> bool;
+}
+
+#[tarpc::service(extends = Health)]
+trait Conflicting {
+    async fn health() -> bool;
+}
+
+#[tarpc::service(extends = Store<String>)]
+trait Generic {
+    async fn hello(name: String) -> String;
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+#[tarpc::service]
+trait World<'a, const N: usize, S> {
+    async fn hello(name: String) -> String;
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+#[tarpc::service]
+trait Reports {
+    #[tarpc::max_concurrent = 0]
+    async fn generate();
+}
+
+#[tarpc::service]
+trait Exports {
+    #[tarpc::max_concurrent = "4"]
+    async fn export();
+}
+
+fn main() {}
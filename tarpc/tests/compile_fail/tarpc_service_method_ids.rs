@@ -0,0 +1,21 @@
+#[tarpc::service]
+trait World {
+    #[tarpc::id = 1]
+    async fn hello();
+}
+
+#[tarpc::service(method_ids = true)]
+trait Storage {
+    #[tarpc::id = 1]
+    async fn get();
+    #[tarpc::id = 1]
+    async fn put();
+}
+
+#[tarpc::service(method_ids = true)]
+trait Health {
+    #[tarpc::id = "one"]
+    async fn check();
+}
+
+fn main() {}
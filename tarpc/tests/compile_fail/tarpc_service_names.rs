@@ -0,0 +1,16 @@
+#[tarpc::service(prefix = "Not An Ident")]
+trait World {
+    async fn hello();
+}
+
+#[tarpc::service(client_name = "Greeter", client_name = "Welcomer")]
+trait Greetings {
+    async fn hello();
+}
+
+#[tarpc::service(request_name = true)]
+trait Health {
+    async fn check();
+}
+
+fn main() {}
@@ -0,0 +1,7 @@
+#[tarpc::service]
+trait World {
+    #[tarpc::oneway]
+    async fn hello() -> String;
+}
+
+fn main() {}
@@ -0,0 +1,8 @@
+#[tarpc::service]
+trait World {
+    #[tarpc::rename = "Goodbye"]
+    async fn hello();
+    async fn goodbye();
+}
+
+fn main() {}
@@ -0,0 +1,6 @@
+#[tarpc::service(schema = true)]
+trait World {
+    async fn hello(#[serde(default)] name: String) -> String;
+}
+
+fn main() {}
@@ -0,0 +1,7 @@
+#[tarpc::service]
+trait World {
+    #[tarpc::since = "1.x"]
+    async fn hello();
+}
+
+fn main() {}
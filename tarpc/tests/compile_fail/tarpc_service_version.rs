@@ -0,0 +1,6 @@
+#[tarpc::service(version = "1.x")]
+trait World {
+    async fn hello();
+}
+
+fn main() {}
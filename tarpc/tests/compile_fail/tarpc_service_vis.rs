@@ -0,0 +1,12 @@
+mod world {
+    #[tarpc::service(vis = "pub(self)")]
+    pub trait World {
+        async fn hello(name: String) -> String;
+    }
+}
+
+fn main() {
+    let _ = world::WorldRequest::Hello {
+        name: "Ferris".into(),
+    };
+}
@@ -0,0 +1,11 @@
+#[tarpc::service(vis = "public")]
+trait World {
+    async fn hello(name: String) -> String;
+}
+
+#[tarpc::service(vis = true)]
+trait Universe {
+    async fn hello(name: String) -> String;
+}
+
+fn main() {}
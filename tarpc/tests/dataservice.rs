@@ -7,6 +7,7 @@ use tarpc::{
 use tokio_serde::formats::Json;
 
 #[tarpc::derive_serde]
+#[cfg_attr(feature = "rkyv", tarpc::derive_rkyv)]
 #[derive(Debug, PartialEq, Eq)]
 pub enum TestData {
     Black,
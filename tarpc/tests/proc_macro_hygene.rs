@@ -4,6 +4,7 @@ extern crate tarpc as some_random_other_name;
 #[cfg(feature = "serde1")]
 mod serde1_feature {
     #[::tarpc::derive_serde]
+    #[allow(dead_code)]
     #[derive(Debug, PartialEq, Eq)]
     pub enum TestData {
         Black,
@@ -11,7 +12,8 @@ mod serde1_feature {
     }
 }
 
-#[::tarpc::service]
+// rkyv's derives are not hygienic, so they can't be used with `no_implicit_prelude`.
+#[::tarpc::service(derive_rkyv = false)]
 pub trait ColorProtocol {
     async fn get_opposite_color(color: u8) -> u8;
 }
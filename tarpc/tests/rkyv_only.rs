@@ -0,0 +1,57 @@
+//! Services build without serde in builds enabling only the `rkyv` feature, even if they declare
+//! serde attributes.
+
+use futures::prelude::*;
+use tarpc::{
+    client, context,
+    rkyv::{self, Deserialize},
+    server::{BaseChannel, Channel},
+    transport::channel,
+};
+
+#[tarpc::service]
+pub trait Greeter {
+    #[serde(alias = "Hi")]
+    async fn greet(name: String, #[serde(default)] greeting: Option<String>) -> String;
+}
+
+#[derive(Clone)]
+struct GreeterServer;
+
+impl Greeter for GreeterServer {
+    async fn greet(self, _: context::Context, name: String, greeting: Option<String>) -> String {
+        format!("{}, {name}!", greeting.as_deref().unwrap_or("Hello"))
+    }
+}
+
+#[tokio::test]
+async fn services_build_without_serde() -> anyhow::Result<()> {
+    let (tx, rx) = channel::unbounded();
+    tokio::spawn(
+        BaseChannel::with_defaults(rx)
+            .execute(GreeterServer.serve())
+            .for_each(|response| async move {
+                tokio::spawn(response);
+            }),
+    );
+    let client = GreeterClient::new(client::Config::default(), tx).spawn();
+    assert_eq!(
+        client
+            .greet(context::current(), "Ferris".into(), None)
+            .await?,
+        "Hello, Ferris!"
+    );
+
+    let request = GreeterRequest::Greet {
+        name: "Ferris".into(),
+        greeting: Some("Hi".into()),
+    };
+    let bytes = rkyv::to_bytes::<_, 256>(&request)?;
+    let archived =
+        rkyv::check_archived_root::<GreeterRequest>(&bytes).map_err(|e| anyhow::anyhow!("{e}"))?;
+    let request: GreeterRequest = archived.deserialize(&mut rkyv::Infallible)?;
+    let GreeterRequest::Greet { name, greeting } = request;
+    assert_eq!((name.as_str(), greeting.as_deref()), ("Ferris", Some("Hi")));
+
+    Ok(())
+}
@@ -3,12 +3,13 @@ use futures::{
     future::{join_all, ready},
     prelude::*,
 };
-use std::time::{Duration, SystemTime};
+use std::time::{Duration, Instant};
 use tarpc::{
     client::{self},
     context,
     server::{incoming::Incoming, BaseChannel, Channel},
     transport::channel,
+    ServerErrorReason,
 };
 use tokio::join;
 
@@ -57,9 +58,6 @@ async fn dropped_channel_aborts_in_flight_requests() -> anyhow::Result<()> {
     #[derive(Clone)]
     struct LoopServer;
 
-    #[derive(Debug)]
-    struct AllHandlersComplete;
-
     impl Loop for LoopServer {
         async fn r#loop(self, _: context::Context) {
             loop {
@@ -78,7 +76,7 @@ async fn dropped_channel_aborts_in_flight_requests() -> anyhow::Result<()> {
         let client = LoopClient::new(client::Config::default(), tx).spawn();
 
         let mut ctx = context::current();
-        ctx.deadline = SystemTime::now() + Duration::from_secs(60 * 60);
+        ctx.deadline = Instant::now() + Duration::from_secs(60 * 60);
         let _ = client.r#loop(ctx).await;
     });
 
@@ -95,132 +93,490 @@ async fn dropped_channel_aborts_in_flight_requests() -> anyhow::Result<()> {
     Ok(())
 }
 
-#[cfg(all(feature = "serde-transport", feature = "tcp"))]
 #[tokio::test]
-async fn serde_tcp() -> anyhow::Result<()> {
-    use tarpc::serde_transport;
-    use tokio_serde::formats::Json;
+async fn application_errors_are_surfaced_to_client() -> anyhow::Result<()> {
+    use tarpc::{client::RpcError, ApplicationError};
 
-    let _ = tracing_subscriber::fmt::try_init();
+    #[tarpc_plugins::service]
+    trait Divide {
+        #[tarpc::application_error]
+        async fn divide(x: i32, y: i32) -> Result<i32, ApplicationError>;
+    }
 
-    let transport = tarpc::serde_transport::tcp::listen("localhost:56789", Json::default).await?;
-    let addr = transport.local_addr();
+    #[derive(Clone)]
+    struct DivideServer;
+
+    impl Divide for DivideServer {
+        async fn divide(
+            self,
+            _: context::Context,
+            x: i32,
+            y: i32,
+        ) -> Result<i32, ApplicationError> {
+            if y == 0 {
+                return Err(ApplicationError::new(7, "division by zero").with_details(vec![1, 2]));
+            }
+            Ok(x / y)
+        }
+    }
+
+    let (tx, rx) = channel::unbounded();
     tokio::spawn(
-        transport
-            .take(1)
-            .filter_map(|r| async { r.ok() })
-            .map(BaseChannel::with_defaults)
-            .execute(Server.serve())
-            .map(|channel| channel.for_each(spawn))
-            .for_each(spawn),
+        BaseChannel::with_defaults(rx)
+            .execute(DivideServer.serve())
+            .for_each(|response| response),
     );
+    let client = DivideClient::new(client::Config::default(), tx).spawn();
 
-    let transport = serde_transport::tcp::connect(addr, Json::default).await?;
-    let client = ServiceClient::new(client::Config::default(), transport).spawn();
-
-    assert_matches!(client.add(context::current(), 1, 2).await, Ok(3));
+    assert_matches!(client.divide(context::current(), 6, 3).await, Ok(2));
+    let error = client.divide(context::current(), 1, 0).await;
     assert_matches!(
-        client.hey(context::current(), "Tim".to_string()).await,
-        Ok(ref s) if s == "Hey, Tim."
+        error,
+        Err(RpcError::Application(ApplicationError { code: 7, ref message, retryable: false, ref details, .. }))
+            if message == "division by zero" && details == &[1, 2]
     );
 
     Ok(())
 }
 
-#[cfg(all(feature = "serde-transport", feature = "unix", unix))]
 #[tokio::test]
-async fn serde_uds() -> anyhow::Result<()> {
-    use tarpc::serde_transport;
-    use tokio_serde::formats::Json;
+async fn declared_errors_are_surfaced_to_client() -> anyhow::Result<()> {
+    use tarpc::client::CallError;
 
-    let _ = tracing_subscriber::fmt::try_init();
+    #[tarpc_plugins::service]
+    trait Math {
+        type Error = String;
 
-    let sock = tarpc::serde_transport::unix::TempPathBuf::with_random("uds");
-    let transport = tarpc::serde_transport::unix::listen(&sock, Json::default).await?;
+        async fn divide(x: i32, y: i32) -> Result<i32>;
+        async fn negate(x: i32) -> i32;
+    }
+
+    #[derive(Clone)]
+    struct MathServer;
+
+    impl Math for MathServer {
+        async fn divide(self, _: context::Context, x: i32, y: i32) -> Result<i32, String> {
+            x.checked_div(y).ok_or_else(|| "division by zero".into())
+        }
+
+        async fn negate(self, _: context::Context, x: i32) -> i32 {
+            -x
+        }
+    }
+
+    let (tx, rx) = channel::unbounded();
     tokio::spawn(
-        transport
-            .take(1)
-            .filter_map(|r| async { r.ok() })
-            .map(BaseChannel::with_defaults)
-            .execute(Server.serve())
-            .map(|channel| channel.for_each(spawn))
-            .for_each(spawn),
+        BaseChannel::with_defaults(rx)
+            .execute(MathServer.serve())
+            .for_each(|response| response),
     );
+    let client = MathClient::new(client::Config::default(), tx).spawn();
 
-    let transport = serde_transport::unix::connect(&sock, Json::default).await?;
-    let client = ServiceClient::new(client::Config::default(), transport).spawn();
+    assert_matches!(client.divide(context::current(), 6, 3).await, Ok(2));
+    assert_matches!(
+        client.divide(context::current(), 1, 0).await,
+        Err(CallError::Service(ref e)) if e == "division by zero"
+    );
+    let stub: &dyn DynMathStub = &client;
+    assert_matches!(
+        stub.divide(context::current(), 1, 0).await,
+        Err(CallError::Service(ref e)) if e == "division by zero"
+    );
+    assert_eq!(client.negate(context::current(), 1).await?, -1);
 
-    // Save results using socket so we can clean the socket even if our test assertions fail
-    let res1 = client.add(context::current(), 1, 2).await;
-    let res2 = client.hey(context::current(), "Tim".to_string()).await;
+    drop(client);
+    let (tx, _) = channel::unbounded();
+    let client = MathClient::new(client::Config::default(), tx).spawn();
+    assert_matches!(
+        client.divide(context::current(), 1, 1).await,
+        Err(CallError::Rpc(_))
+    );
 
-    assert_matches!(res1, Ok(3));
-    assert_matches!(res2, Ok(ref s) if s == "Hey, Tim.");
+    Ok(())
+}
+
+/// Runs `test` on a single thread, recording its metrics in `recorder`, which, unlike a global
+/// recorder, doesn't see the metrics of concurrent tests.
+#[cfg(feature = "metrics")]
+fn with_local_recorder<F: Future>(
+    recorder: &metrics_util::debugging::DebuggingRecorder,
+    test: F,
+) -> F::Output {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    metrics::with_local_recorder(recorder, || runtime.block_on(test))
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+fn instrumented_clients_record_calls() -> anyhow::Result<()> {
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+    use tarpc::client::instrumented::{CALLS, DURATION};
+
+    #[tarpc_plugins::service(instrumented_client = true)]
+    trait Echo {
+        async fn echo(message: String) -> String;
+    }
+
+    #[derive(Clone)]
+    struct EchoServer;
+
+    impl Echo for EchoServer {
+        async fn echo(self, _: context::Context, message: String) -> String {
+            message
+        }
+    }
+
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    with_local_recorder(&recorder, async {
+        let (tx, rx) = channel::unbounded();
+        tokio::spawn(
+            BaseChannel::with_defaults(rx)
+                .execute(EchoServer.serve())
+                .for_each(spawn),
+        );
+        let client =
+            EchoInstrumentedClient::from(EchoClient::new(client::Config::default(), tx).spawn());
+        assert_eq!(client.echo(context::current(), "hi".into()).await?, "hi");
+        assert_eq!(client.echo(context::current(), "bye".into()).await?, "bye");
+        anyhow::Ok(())
+    })?;
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let metric = |name| {
+        snapshot
+            .iter()
+            .find(|(key, ..)| key.key().name() == name)
+            .map(|(key, _, _, value)| (key.key().labels().cloned().collect::<Vec<_>>(), value))
+            .unwrap()
+    };
+    let (labels, calls) = metric(CALLS);
+    assert_eq!(calls, &DebugValue::Counter(2));
+    assert!(labels
+        .iter()
+        .any(|label| label.key() == "rpc.method" && label.value() == "echo"));
+    assert!(labels
+        .iter()
+        .any(|label| label.key() == "rpc.outcome" && label.value() == "ok"));
+    assert_matches!(metric(DURATION).1, DebugValue::Histogram(latencies) if latencies.len() == 2);
+
+    Ok(())
+}
+
+#[cfg(feature = "metrics")]
+#[test]
+fn channels_record_metrics() -> anyhow::Result<()> {
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+    use tarpc::{serde_transport, stats};
+    use tokio_serde::formats::Json;
+    use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+    #[tarpc_plugins::service]
+    trait Echo {
+        async fn echo(message: String) -> String;
+    }
+
+    #[derive(Clone)]
+    struct EchoServer;
+
+    impl Echo for EchoServer {
+        async fn echo(self, _: context::Context, message: String) -> String {
+            message
+        }
+    }
+
+    let recorder = DebuggingRecorder::new();
+    let snapshotter = recorder.snapshotter();
+    with_local_recorder(&recorder, async {
+        let (client_io, server_io) = tokio::io::duplex(4096);
+        let transport = serde_transport::new(
+            Framed::new(server_io, LengthDelimitedCodec::new()),
+            Json::default(),
+        );
+        let stats = transport.stats().clone();
+        tokio::spawn(
+            BaseChannel::with_defaults(transport)
+                .with_stats(stats)
+                .execute(EchoServer.serve())
+                .for_each(spawn),
+        );
+        let transport = serde_transport::new(
+            Framed::new(client_io, LengthDelimitedCodec::new()),
+            Json::default(),
+        );
+        let stats = transport.stats().clone();
+        let client = EchoClient::from(
+            client::new(client::Config::default(), transport)
+                .with_stats(stats)
+                .spawn(),
+        );
+        assert_eq!(client.echo(context::current(), "hi".into()).await?, "hi");
+        anyhow::Ok(())
+    })?;
+
+    let snapshot = snapshotter.snapshot().into_vec();
+    let metric = |name| {
+        snapshot
+            .iter()
+            .find(|(key, ..)| key.key().name() == name)
+            .map(|(key, _, _, value)| (key.key().labels().cloned().collect::<Vec<_>>(), value))
+            .unwrap_or_else(|| panic!("no {name}"))
+    };
+    for name in [stats::CLIENT_DURATION, stats::SERVER_DURATION] {
+        let (labels, latencies) = metric(name);
+        assert_matches!(latencies, DebugValue::Histogram(latencies) if latencies.len() == 1);
+        assert!(labels
+            .iter()
+            .any(|label| label.key() == "rpc.service" && label.value() == "Echo"));
+        assert!(labels
+            .iter()
+            .any(|label| label.key() == "rpc.method" && label.value() == "echo"));
+    }
+    for name in [
+        stats::CLIENT_REQUEST_SIZE,
+        stats::CLIENT_RESPONSE_SIZE,
+        stats::SERVER_REQUEST_SIZE,
+        stats::SERVER_RESPONSE_SIZE,
+    ] {
+        assert_matches!(metric(name).1, DebugValue::Histogram(sizes) if !sizes.is_empty());
+    }
 
     Ok(())
 }
 
 #[tokio::test]
-async fn concurrent() -> anyhow::Result<()> {
-    let _ = tracing_subscriber::fmt::try_init();
+async fn symmetric_services_share_a_connection() -> anyhow::Result<()> {
+    use tarpc::transport::symmetric;
 
-    let (tx, rx) = channel::unbounded();
+    #[tarpc_plugins::service]
+    trait Callback {
+        async fn double(x: i32) -> i32;
+    }
+
+    #[derive(Clone)]
+    struct CallbackServer;
+
+    impl Callback for CallbackServer {
+        async fn double(self, _: context::Context, x: i32) -> i32 {
+            x * 2
+        }
+    }
+
+    let (agent, controller) = channel::unbounded();
+
+    // The agent dials the controller, serves callbacks, and calls the controller's service.
+    let (to_controller, from_controller, demux) = symmetric::split(agent);
+    tokio::spawn(demux);
     tokio::spawn(
-        stream::once(ready(rx))
-            .map(BaseChannel::with_defaults)
+        BaseChannel::with_defaults(from_controller)
+            .execute(CallbackServer.serve())
+            .for_each(spawn),
+    );
+    let service = ServiceClient::new(client::Config::default(), to_controller).spawn();
+
+    // The controller serves the agent's requests and calls back into the agent.
+    let (to_agent, from_agent, demux) = symmetric::split(controller);
+    tokio::spawn(demux);
+    tokio::spawn(
+        BaseChannel::with_defaults(from_agent)
             .execute(Server.serve())
-            .map(|channel| channel.for_each(spawn))
             .for_each(spawn),
     );
+    let callback = CallbackClient::new(client::Config::default(), to_agent).spawn();
 
-    let client = ServiceClient::new(client::Config::default(), tx).spawn();
+    let (sum, doubled) = join!(
+        service.add(context::current(), 1, 2),
+        callback.double(context::current(), 21)
+    );
+    assert_matches!(sum, Ok(3));
+    assert_matches!(doubled, Ok(42));
 
-    let req1 = client.add(context::current(), 1, 2);
-    let req2 = client.add(context::current(), 3, 4);
-    let req3 = client.hey(context::current(), "Tim".to_string());
+    Ok(())
+}
 
-    assert_matches!(req1.await, Ok(3));
-    assert_matches!(req2.await, Ok(7));
-    assert_matches!(req3.await, Ok(ref s) if s == "Hey, Tim.");
+#[tokio::test]
+async fn broadcasts_are_pushed_over_the_connection_clients_dialed() -> anyhow::Result<()> {
+    use futures::channel::mpsc;
+    use tarpc::{server::broadcast::Broadcaster, transport::symmetric};
+
+    #[tarpc_plugins::service(derive(Clone))]
+    trait Notifications {
+        #[tarpc::oneway]
+        async fn notify(message: String);
+    }
+
+    #[derive(Clone)]
+    struct NotificationsServer(mpsc::UnboundedSender<String>);
+
+    impl Notifications for NotificationsServer {
+        async fn notify(self, _: context::Context, message: String) {
+            self.0.unbounded_send(message).unwrap();
+        }
+    }
+
+    let broadcaster = Broadcaster::new();
+    let mut clients = vec![];
+    for name in ["a", "b"] {
+        let (client, server) = channel::unbounded();
+
+        // The server serves the client's requests, and pushes broadcasts to the client.
+        let (to_client, from_client, demux) = symmetric::split(server);
+        tokio::spawn(demux);
+        tokio::spawn(
+            BaseChannel::with_defaults(from_client)
+                .execute(Server.serve())
+                .for_each(spawn),
+        );
+        let to_client = client::new(client::Config::default(), to_client).spawn();
+        tokio::spawn(
+            broadcaster
+                .subscribe(name)
+                .forward(to_client, "Notifications.notify"),
+        );
+
+        // The client calls the server's service, and serves the pushed notifications.
+        let (notifications_tx, notifications) = mpsc::unbounded();
+        let (to_server, from_server, demux) = symmetric::split(client);
+        tokio::spawn(demux);
+        tokio::spawn(
+            BaseChannel::with_defaults(from_server)
+                .execute(NotificationsServer(notifications_tx).serve())
+                .for_each(spawn),
+        );
+        let service = ServiceClient::new(client::Config::default(), to_server).spawn();
+        clients.push((service, notifications));
+    }
+
+    let notify = |message: &str| NotificationsRequest::Notify {
+        message: message.into(),
+    };
+    assert_eq!(broadcaster.broadcast(notify("hello")), 2);
+    assert_eq!(
+        broadcaster.broadcast_filtered(|name| *name == "b", notify("only b")),
+        1
+    );
+    let (service_a, mut notifications_a) = clients.remove(0);
+    let (_, mut notifications_b) = clients.remove(0);
+    assert_eq!(notifications_a.next().await.as_deref(), Some("hello"));
+    assert_eq!(notifications_b.next().await.as_deref(), Some("hello"));
+    assert_eq!(notifications_b.next().await.as_deref(), Some("only b"));
+    // The pushed notifications share the connection with the client's own calls.
+    assert_matches!(service_a.add(context::current(), 1, 2).await, Ok(3));
 
     Ok(())
 }
 
 #[tokio::test]
-async fn concurrent_join() -> anyhow::Result<()> {
-    let _ = tracing_subscriber::fmt::try_init();
+async fn generic_services_serve_each_payload_type() -> anyhow::Result<()> {
+    use std::{
+        collections::HashMap,
+        hash::Hash,
+        sync::{Arc, Mutex},
+    };
+
+    #[tarpc_plugins::service]
+    trait Store<K: Eq + Hash, V: Clone> {
+        async fn put(key: K, value: V);
+        async fn get(key: K) -> Option<V>;
+    }
+
+    #[derive(Clone)]
+    struct MapStore<K, V>(Arc<Mutex<HashMap<K, V>>>);
+
+    impl<K: Eq + Hash, V: Clone> Store<K, V> for MapStore<K, V> {
+        async fn put(self, _: context::Context, key: K, value: V) {
+            self.0.lock().unwrap().insert(key, value);
+        }
+
+        async fn get(self, _: context::Context, key: K) -> Option<V> {
+            self.0.lock().unwrap().get(&key).cloned()
+        }
+    }
 
     let (tx, rx) = channel::unbounded();
     tokio::spawn(
-        stream::once(ready(rx))
-            .map(BaseChannel::with_defaults)
-            .execute(Server.serve())
-            .map(|channel| channel.for_each(spawn))
+        BaseChannel::with_defaults(rx)
+            .execute(MapStore::<String, u64>(Default::default()).serve())
             .for_each(spawn),
     );
+    let client = StoreClient::<String, u64>::new(client::Config::default(), tx).spawn();
 
-    let client = ServiceClient::new(client::Config::default(), tx).spawn();
-
-    let req1 = client.add(context::current(), 1, 2);
-    let req2 = client.add(context::current(), 3, 4);
-    let req3 = client.hey(context::current(), "Tim".to_string());
-
-    let (resp1, resp2, resp3) = join!(req1, req2, req3);
-    assert_matches!(resp1, Ok(3));
-    assert_matches!(resp2, Ok(7));
-    assert_matches!(resp3, Ok(ref s) if s == "Hey, Tim.");
+    client.put(context::current(), "one".into(), 1).await?;
+    assert_eq!(client.get(context::current(), "one".into()).await?, Some(1));
+    assert_eq!(client.get(context::current(), "two".into()).await?, None);
 
     Ok(())
 }
 
-#[cfg(test)]
-async fn spawn(fut: impl Future<Output = ()> + Send + 'static) {
-    tokio::spawn(fut);
+#[tokio::test]
+async fn services_declare_where_clauses_consts_and_supertraits() -> anyhow::Result<()> {
+    use std::fmt::Display;
+
+    #[tarpc_plugins::service]
+    trait Labeler<T>: Clone + Send
+    where
+        T: Display,
+        Self: Sync,
+    {
+        const PREFIX: &'static str;
+        const SEPARATOR: char = ':';
+
+        async fn label(value: T) -> String;
+    }
+
+    #[derive(Clone)]
+    struct Server;
+
+    impl<T: Display> Labeler<T> for Server {
+        const PREFIX: &'static str = "label";
+
+        async fn label(self, _: context::Context, value: T) -> String {
+            let prefix = <Self as Labeler<T>>::PREFIX;
+            format!("{prefix}{}{value}", <Self as Labeler<T>>::SEPARATOR)
+        }
+    }
+
+    let (tx, rx) = channel::unbounded();
+    tokio::spawn(
+        BaseChannel::with_defaults(rx)
+            .execute(Labeler::<u8>::serve(Server))
+            .for_each(spawn),
+    );
+    let client = LabelerClient::<u8>::new(client::Config::default(), tx).spawn();
+
+    assert_eq!(client.label(context::current(), 7).await?, "label:7");
+
+    Ok(())
 }
 
 #[tokio::test]
-async fn concurrent_join_all() -> anyhow::Result<()> {
-    let _ = tracing_subscriber::fmt::try_init();
+async fn dyn_stubs_swap_clients_and_mocks() -> anyhow::Result<()> {
+    use futures::future::BoxFuture;
+    use tarpc::client::RpcError;
+
+    async fn add_then_greet(stub: &dyn DynServiceStub) -> Result<String, RpcError> {
+        let sum = stub.add(context::current(), 1, 2).await?;
+        stub.hey(context::current(), sum.to_string()).await
+    }
+
+    struct Mock;
+
+    impl DynServiceStub for Mock {
+        fn add(&self, _: context::Context, x: i32, y: i32) -> BoxFuture<'_, Result<i32, RpcError>> {
+            Box::pin(async move { Ok(x * y) })
+        }
+
+        fn hey(
+            &self,
+            _: context::Context,
+            name: String,
+        ) -> BoxFuture<'_, Result<String, RpcError>> {
+            Box::pin(async move { Ok(format!("Mock {name}")) })
+        }
+    }
 
     let (tx, rx) = channel::unbounded();
     tokio::spawn(
@@ -228,48 +584,1831 @@ async fn concurrent_join_all() -> anyhow::Result<()> {
             .execute(Server.serve())
             .for_each(spawn),
     );
-
     let client = ServiceClient::new(client::Config::default(), tx).spawn();
 
-    let req1 = client.add(context::current(), 1, 2);
-    let req2 = client.add(context::current(), 3, 4);
-
-    let responses = join_all(vec![req1, req2]).await;
-    assert_matches!(responses[0], Ok(3));
-    assert_matches!(responses[1], Ok(7));
+    assert_eq!(add_then_greet(&client).await?, "Hey, 3.");
+    assert_eq!(add_then_greet(&Mock).await?, "Mock 2");
 
     Ok(())
 }
 
 #[tokio::test]
-async fn counter() -> anyhow::Result<()> {
+async fn streaming_methods_send_each_item() -> anyhow::Result<()> {
     #[tarpc::service]
     trait Counter {
-        async fn count() -> u32;
+        async fn count(to: u32) -> impl Stream<Item = u32>;
+        async fn total(to: u32) -> u32;
     }
 
-    struct CountService(u32);
+    #[derive(Clone)]
+    struct CounterServer;
 
-    impl Counter for &mut CountService {
-        async fn count(self, _: context::Context) -> u32 {
-            self.0 += 1;
-            self.0
+    impl Counter for CounterServer {
+        async fn count(
+            self,
+            _: context::Context,
+            to: u32,
+        ) -> impl Stream<Item = u32> + Send + 'static {
+            stream::iter(1..=to)
+        }
+
+        async fn total(self, _: context::Context, to: u32) -> u32 {
+            (1..=to).sum()
         }
     }
 
     let (tx, rx) = channel::unbounded();
-    tokio::spawn(async {
-        let mut requests = BaseChannel::with_defaults(rx).requests();
-        let mut counter = CountService(0);
+    tokio::spawn(
+        BaseChannel::with_defaults(rx)
+            .requests()
+            .execute_body(CounterServer.serve())
+            .for_each(|request| async move {
+                tokio::spawn(request);
+            }),
+    );
+    let client = CounterClient::new(client::Config::default(), tx).spawn();
 
-        while let Some(Ok(request)) = requests.next().await {
-            request.execute(counter.serve()).await;
+    let counts: Vec<_> = client
+        .count(context::current(), 3)
+        .await?
+        .try_collect()
+        .await?;
+    assert_eq!(counts, [1, 2, 3]);
+    assert_eq!(client.total(context::current(), 3).await?, 6);
+
+    Ok(())
+}
+
+#[cfg(feature = "tcp")]
+#[tokio::test]
+async fn services_serve_tcp_in_one_line() -> anyhow::Result<()> {
+    use std::future::IntoFuture;
+    use tarpc::{serde_transport, server::quickstart, tokio_serde::formats::Json};
+
+    #[tarpc::service]
+    trait Greeter {
+        async fn greet(name: String) -> String;
+    }
+
+    #[tarpc::service]
+    trait Counter {
+        async fn count(to: u32) -> impl Stream<Item = u32>;
+    }
+
+    #[derive(Clone)]
+    struct GreeterServer;
+
+    impl Greeter for GreeterServer {
+        async fn greet(self, _: context::Context, name: String) -> String {
+            format!("Hello, {name}!")
         }
-    });
+    }
 
-    let client = CounterClient::new(client::Config::default(), tx).spawn();
-    assert_matches!(client.count(context::current()).await, Ok(1));
-    assert_matches!(client.count(context::current()).await, Ok(2));
+    #[derive(Clone)]
+    struct CounterServer;
+
+    impl Counter for CounterServer {
+        async fn count(
+            self,
+            _: context::Context,
+            to: u32,
+        ) -> impl Stream<Item = u32> + Send + 'static {
+            stream::iter(1..=to)
+        }
+    }
+
+    let greeter = GreeterServer
+        .serve_tcp("localhost:0", Json::default, quickstart::Config::default())
+        .await?;
+    let greeter_addr = *greeter.local_addr();
+    tokio::spawn(greeter.into_future());
+    let counter = CounterServer
+        .serve_tcp("localhost:0", Json::default, quickstart::Config::default())
+        .await?;
+    let counter_addr = *counter.local_addr();
+    tokio::spawn(counter.into_future());
+
+    let transport = serde_transport::tcp::connect(greeter_addr, Json::default).await?;
+    let client = GreeterClient::new(client::Config::default(), transport).spawn();
+    assert_eq!(
+        client.greet(context::current(), "Ferris".into()).await?,
+        "Hello, Ferris!"
+    );
+
+    let transport = serde_transport::tcp::connect(counter_addr, Json::default).await?;
+    let client = CounterClient::new(client::Config::default(), transport).spawn();
+    let counts: Vec<_> = client
+        .count(context::current(), 3)
+        .await?
+        .try_collect()
+        .await?;
+    assert_eq!(counts, [1, 2, 3]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn default_methods_serve_unless_overridden() -> anyhow::Result<()> {
+    #[tarpc::service]
+    trait Greeter {
+        async fn hello(name: String) -> String;
+        async fn goodbye(name: String) -> String {
+            format!("Goodbye, {name}.")
+        }
+        async fn deadline() -> bool {
+            !context.has_expired()
+        }
+    }
+
+    #[derive(Clone)]
+    struct GreeterServer;
+
+    impl Greeter for GreeterServer {
+        async fn hello(self, _: context::Context, name: String) -> String {
+            format!("Hello, {name}.")
+        }
+
+        async fn deadline(self, _: context::Context) -> bool {
+            false
+        }
+    }
+
+    let (tx, rx) = channel::unbounded();
+    tokio::spawn(
+        BaseChannel::with_defaults(rx)
+            .execute(GreeterServer.serve())
+            .for_each(|request| async move {
+                tokio::spawn(request);
+            }),
+    );
+    let client = GreeterClient::new(client::Config::default(), tx).spawn();
+
+    assert_eq!(
+        client.hello(context::current(), "Tim".into()).await?,
+        "Hello, Tim."
+    );
+    assert_eq!(
+        client.goodbye(context::current(), "Tim".into()).await?,
+        "Goodbye, Tim."
+    );
+    assert!(!client.deadline(context::current()).await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn oneway_methods_are_executed_without_response() -> anyhow::Result<()> {
+    use futures::channel::mpsc;
+
+    #[tarpc::service]
+    trait Logger {
+        #[tarpc::oneway]
+        async fn log(line: String);
+        async fn flush() -> usize;
+    }
+
+    #[derive(Clone)]
+    struct LoggerServer(mpsc::UnboundedSender<String>);
+
+    impl Logger for LoggerServer {
+        async fn log(self, _: context::Context, line: String) {
+            self.0.unbounded_send(line).unwrap();
+        }
+
+        async fn flush(self, _: context::Context) -> usize {
+            0
+        }
+    }
+
+    let (lines_tx, mut lines) = mpsc::unbounded();
+    let (tx, rx) = channel::unbounded();
+    tokio::spawn(
+        BaseChannel::with_defaults(rx)
+            .execute(LoggerServer(lines_tx).serve())
+            .for_each(spawn),
+    );
+    let client = LoggerClient::new(client::Config::default(), tx).spawn();
+
+    client.log(context::current(), "hello".into()).await?;
+    assert_eq!(lines.next().await.as_deref(), Some("hello"));
+    assert_eq!(client.flush(context::current()).await?, 0);
+
+    Ok(())
+}
+
+#[cfg(feature = "serde-transport")]
+#[tokio::test]
+async fn renamed_methods_keep_their_wire_name() -> anyhow::Result<()> {
+    use tarpc::serde_transport;
+    use tokio_serde::formats::Json;
+    use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+    mod deployed {
+        #[tarpc::service]
+        pub trait Greeter {
+            async fn greet(name: String) -> String;
+        }
+    }
+
+    mod renamed {
+        #[tarpc::service]
+        pub trait Greeter {
+            #[tarpc::rename = "Greet"]
+            async fn say_hello(name: String) -> String;
+        }
+    }
+
+    #[derive(Clone)]
+    struct DeployedServer;
+
+    impl deployed::Greeter for DeployedServer {
+        async fn greet(self, _: context::Context, name: String) -> String {
+            format!("Hello, {name}.")
+        }
+    }
+
+    let (client_io, server_io) = tokio::io::duplex(4096);
+    let transport = serde_transport::new(
+        Framed::new(server_io, LengthDelimitedCodec::new()),
+        Json::default(),
+    );
+    tokio::spawn(
+        BaseChannel::with_defaults(transport)
+            .execute(deployed::Greeter::serve(DeployedServer))
+            .for_each(spawn),
+    );
+
+    let transport = serde_transport::new(
+        Framed::new(client_io, LengthDelimitedCodec::new()),
+        Json::default(),
+    );
+    let client = renamed::GreeterClient::new(client::Config::default(), transport).spawn();
+    assert_eq!(
+        client.say_hello(context::current(), "Tim".into()).await?,
+        "Hello, Tim."
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "serde-transport")]
+#[tokio::test]
+async fn serde_attrs_evolve_schemas_compatibly() -> anyhow::Result<()> {
+    use tarpc::serde_transport;
+    use tokio_serde::formats::Json;
+    use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+    mod deployed {
+        #[tarpc::service]
+        pub trait Greeter {
+            async fn greet(name: String) -> String;
+        }
+    }
+
+    mod evolved {
+        #[tarpc::service]
+        pub trait Greeter {
+            async fn greet(
+                name: String,
+                #[serde(default, skip_serializing_if = "Option::is_none")] greeting: Option<String>,
+            ) -> String;
+        }
+    }
+
+    #[derive(Clone)]
+    struct EvolvedServer;
+
+    impl evolved::Greeter for EvolvedServer {
+        async fn greet(
+            self,
+            _: context::Context,
+            name: String,
+            greeting: Option<String>,
+        ) -> String {
+            format!("{}, {name}.", greeting.as_deref().unwrap_or("Hello"))
+        }
+    }
+
+    let (client_io, server_io) = tokio::io::duplex(4096);
+    let transport = serde_transport::new(
+        Framed::new(server_io, LengthDelimitedCodec::new()),
+        Json::default(),
+    );
+    tokio::spawn(
+        BaseChannel::with_defaults(transport)
+            .execute(evolved::Greeter::serve(EvolvedServer))
+            .for_each(spawn),
+    );
+
+    let transport = serde_transport::new(
+        Framed::new(client_io, LengthDelimitedCodec::new()),
+        Json::default(),
+    );
+    let client = deployed::GreeterClient::new(client::Config::default(), transport).spawn();
+    assert_eq!(
+        client.greet(context::current(), "Tim".into()).await?,
+        "Hello, Tim."
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "serde-transport")]
+#[tokio::test]
+async fn unknown_methods_are_unimplemented() -> anyhow::Result<()> {
+    use tarpc::{client::RpcError, serde_transport};
+    use tokio_serde::formats::Json;
+    use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+    mod v1 {
+        #[tarpc::service(catch_unknown_methods = true)]
+        pub trait Greeter {
+            async fn hello(name: String) -> String;
+        }
+    }
+
+    mod v2 {
+        #[tarpc::service]
+        pub trait Greeter {
+            async fn hello(name: String) -> String;
+            #[tarpc::since = "2"]
+            async fn goodbye(name: String) -> String;
+        }
+    }
+
+    #[derive(Clone)]
+    struct V1Server;
+
+    impl v1::Greeter for V1Server {
+        async fn hello(self, _: context::Context, name: String) -> String {
+            format!("Hello, {name}.")
+        }
+    }
+
+    let (client_io, server_io) = tokio::io::duplex(4096);
+    let transport = serde_transport::new(
+        Framed::new(server_io, LengthDelimitedCodec::new()),
+        Json::default(),
+    );
+    tokio::spawn(
+        BaseChannel::with_defaults(transport)
+            .execute(v1::Greeter::serve(V1Server))
+            .for_each(spawn),
+    );
+
+    let transport = serde_transport::new(
+        Framed::new(client_io, LengthDelimitedCodec::new()),
+        Json::default(),
+    );
+    let client = v2::GreeterClient::new(client::Config::default(), transport).spawn();
+    let goodbye = v2::GreeterRequest::Goodbye { name: "Tim".into() };
+    assert!(!goodbye.is_supported_by("1"));
+
+    assert_matches!(
+        client.goodbye(context::current(), "Tim".into()).await,
+        Err(RpcError::Server(e)) if e.reason == Some(ServerErrorReason::Unimplemented)
+    );
+    // The channel survives requests for unknown methods.
+    assert_eq!(
+        client.hello(context::current(), "Tim".into()).await?,
+        "Hello, Tim."
+    );
+
+    let unknown = v1::GreeterRequest::Unknown(tarpc::negotiation::UnknownMethod);
+    assert_eq!(unknown.method_name(), "<unknown>");
+
+    Ok(())
+}
+
+#[cfg(feature = "serde-transport")]
+#[tokio::test]
+async fn unknown_arg_variants_are_bad_requests() -> anyhow::Result<()> {
+    use tarpc::{
+        serde_transport::{self, ClientMessageHeader, Recoverable},
+        server, ClientMessage,
+    };
+    use tokio_serde::formats::Json;
+    use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+    mod v1 {
+        #[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
+        pub enum Shape {
+            Square,
+        }
+
+        #[tarpc::service(derive_rkyv = false)]
+        pub trait Shapes {
+            async fn sides(shape: Shape) -> u32;
+        }
+    }
+
+    mod v2 {
+        #[derive(Clone, Copy, Debug, serde::Serialize, serde::Deserialize)]
+        pub enum Shape {
+            Square,
+            Triangle,
+        }
+
+        #[tarpc::service(derive_rkyv = false)]
+        pub trait Shapes {
+            async fn sides(shape: Shape) -> u32;
+        }
+    }
+
+    #[derive(Clone)]
+    struct V1Server;
+
+    impl v1::Shapes for V1Server {
+        async fn sides(self, _: context::Context, _: v1::Shape) -> u32 {
+            4
+        }
+    }
+
+    let (client_io, server_io) = tokio::io::duplex(4096);
+    let transport = serde_transport::new::<_, ClientMessage<v1::ShapesRequest>, _, _>(
+        Framed::new(server_io, LengthDelimitedCodec::new()),
+        Recoverable::new(Json::default(), Json::<ClientMessageHeader, ()>::default()),
+    );
+    let config = server::Config {
+        malformed_message_policy: server::MalformedMessagePolicy::Reject,
+        ..server::Config::default()
+    };
+    tokio::spawn(
+        BaseChannel::new(config, transport)
+            .execute(v1::Shapes::serve(V1Server))
+            .for_each(spawn),
+    );
+
+    let transport = serde_transport::new(
+        Framed::new(client_io, LengthDelimitedCodec::new()),
+        Json::default(),
+    );
+    let client = v2::ShapesClient::new(client::Config::default(), transport).spawn();
+
+    let error = client
+        .sides(context::current(), v2::Shape::Triangle)
+        .await
+        .unwrap_err();
+    assert_eq!(
+        error.server_error_reason(),
+        Some(ServerErrorReason::BadRequest),
+        "{error:?}"
+    );
+    assert_eq!(
+        client.sides(context::current(), v2::Shape::Square).await?,
+        4
+    );
+
+    Ok(())
+}
+
+#[test]
+fn known_methods_with_malformed_args_fail_to_decode() {
+    #[tarpc::service(catch_unknown_methods = true)]
+    trait Storage {
+        async fn get(key: String) -> Option<String>;
+    }
+
+    assert_matches!(
+        serde_json::from_str::<StorageRequest>(r#"{"Get":{"key":"k"}}"#),
+        Ok(StorageRequest::Get { key }) if key == "k"
+    );
+    assert_matches!(
+        serde_json::from_str::<StorageRequest>(r#"{"Put":{"key":"k","value":"v"}}"#),
+        Ok(StorageRequest::Unknown(_))
+    );
+    let error = serde_json::from_str::<StorageRequest>(r#"{"Get":{"key":5}}"#).unwrap_err();
+    assert!(error.to_string().contains("invalid type"), "{error}");
+}
+
+#[tokio::test]
+async fn clients_learn_the_server_version() -> anyhow::Result<()> {
+    use tarpc::{client::response_extensions, negotiation};
+
+    #[tarpc::service(version = "1.2")]
+    trait Greeter {
+        async fn hello(name: String) -> String;
+    }
+
+    #[tarpc::service(version = "2.0", extends = Greeter)]
+    trait Farewell {
+        async fn goodbye(name: String) -> String;
+    }
+
+    #[derive(Clone)]
+    struct GreeterServer;
+
+    impl Greeter for GreeterServer {
+        async fn hello(self, _: context::Context, name: String) -> String {
+            format!("Hello, {name}.")
+        }
+    }
+
+    impl Farewell for GreeterServer {
+        async fn goodbye(self, _: context::Context, name: String) -> String {
+            format!("Goodbye, {name}.")
+        }
+    }
+
+    assert_eq!(GreeterRequest::VERSION, Some("1.2"));
+    assert_eq!(ServiceRequest::VERSION, None);
+
+    let (tx, rx) = channel::unbounded();
+    tokio::spawn(
+        BaseChannel::with_defaults(rx)
+            .execute(Farewell::serve(GreeterServer))
+            .for_each(spawn),
+    );
+    let client = FarewellClient::new(client::Config::default(), tx).spawn();
+
+    // Requests for the methods of an extended service carry the version of the server's service.
+    let (hello, extensions) =
+        response_extensions::capture(client.as_greeter().hello(context::current(), "Tim".into()))
+            .await;
+    assert_eq!(hello?, "Hello, Tim.");
+    assert_eq!(negotiation::server_version(&extensions), Some("2.0"));
+
+    let (goodbye, extensions) =
+        response_extensions::capture(client.goodbye(context::current(), "Tim".into())).await;
+    assert_eq!(goodbye?, "Goodbye, Tim.");
+    assert_eq!(negotiation::server_version(&extensions), Some("2.0"));
+
+    Ok(())
+}
+
+#[cfg(all(feature = "serde-transport", feature = "tcp"))]
+#[tokio::test]
+async fn serde_tcp() -> anyhow::Result<()> {
+    use tarpc::serde_transport;
+    use tokio_serde::formats::Json;
+
+    let _ = tracing_subscriber::fmt::try_init();
+
+    let transport = tarpc::serde_transport::tcp::listen("localhost:56789", Json::default).await?;
+    let addr = transport.local_addr();
+    tokio::spawn(
+        transport
+            .take(1)
+            .filter_map(|r| async { r.ok() })
+            .map(BaseChannel::with_defaults)
+            .execute(Server.serve())
+            .map(|channel| channel.for_each(spawn))
+            .for_each(spawn),
+    );
+
+    let transport = serde_transport::tcp::connect(addr, Json::default).await?;
+    let client = ServiceClient::new(client::Config::default(), transport).spawn();
+
+    assert_matches!(client.add(context::current(), 1, 2).await, Ok(3));
+    assert_matches!(
+        client.hey(context::current(), "Tim".to_string()).await,
+        Ok(ref s) if s == "Hey, Tim."
+    );
+
+    Ok(())
+}
+
+#[cfg(all(feature = "serde-transport", feature = "unix", unix))]
+#[tokio::test]
+async fn serde_uds() -> anyhow::Result<()> {
+    use tarpc::serde_transport;
+    use tokio_serde::formats::Json;
+
+    let _ = tracing_subscriber::fmt::try_init();
+
+    let sock = tarpc::serde_transport::unix::TempPathBuf::with_random("uds");
+    let transport = tarpc::serde_transport::unix::listen(&sock, Json::default).await?;
+    tokio::spawn(
+        transport
+            .take(1)
+            .filter_map(|r| async { r.ok() })
+            .map(BaseChannel::with_defaults)
+            .execute(Server.serve())
+            .map(|channel| channel.for_each(spawn))
+            .for_each(spawn),
+    );
+
+    let transport = serde_transport::unix::connect(&sock, Json::default).await?;
+    let client = ServiceClient::new(client::Config::default(), transport).spawn();
+
+    // Save results using socket so we can clean the socket even if our test assertions fail
+    let res1 = client.add(context::current(), 1, 2).await;
+    let res2 = client.hey(context::current(), "Tim".to_string()).await;
+
+    assert_matches!(res1, Ok(3));
+    assert_matches!(res2, Ok(ref s) if s == "Hey, Tim.");
+
+    Ok(())
+}
+
+#[cfg(all(feature = "unix", unix))]
+#[tokio::test]
+async fn services_serve_unix_in_one_line() -> anyhow::Result<()> {
+    use std::future::IntoFuture;
+    use tarpc::{serde_transport, server::quickstart};
+    use tokio_serde::formats::Json;
+
+    let sock = serde_transport::unix::TempPathBuf::with_random("uds");
+    let server = Server
+        .serve_unix(
+            sock.as_ref().to_path_buf(),
+            Json::default,
+            quickstart::Config::default(),
+        )
+        .await?;
+    tokio::spawn(server.into_future());
+
+    let transport = serde_transport::unix::connect(&sock, Json::default).await?;
+    let client = ServiceClient::new(client::Config::default(), transport).spawn();
+    assert_eq!(client.add(context::current(), 1, 2).await?, 3);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn concurrent() -> anyhow::Result<()> {
+    let _ = tracing_subscriber::fmt::try_init();
+
+    let (tx, rx) = channel::unbounded();
+    tokio::spawn(
+        stream::once(ready(rx))
+            .map(BaseChannel::with_defaults)
+            .execute(Server.serve())
+            .map(|channel| channel.for_each(spawn))
+            .for_each(spawn),
+    );
+
+    let client = ServiceClient::new(client::Config::default(), tx).spawn();
+
+    let req1 = client.add(context::current(), 1, 2);
+    let req2 = client.add(context::current(), 3, 4);
+    let req3 = client.hey(context::current(), "Tim".to_string());
+
+    assert_matches!(req1.await, Ok(3));
+    assert_matches!(req2.await, Ok(7));
+    assert_matches!(req3.await, Ok(ref s) if s == "Hey, Tim.");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn concurrent_join() -> anyhow::Result<()> {
+    let _ = tracing_subscriber::fmt::try_init();
+
+    let (tx, rx) = channel::unbounded();
+    tokio::spawn(
+        stream::once(ready(rx))
+            .map(BaseChannel::with_defaults)
+            .execute(Server.serve())
+            .map(|channel| channel.for_each(spawn))
+            .for_each(spawn),
+    );
+
+    let client = ServiceClient::new(client::Config::default(), tx).spawn();
+
+    let req1 = client.add(context::current(), 1, 2);
+    let req2 = client.add(context::current(), 3, 4);
+    let req3 = client.hey(context::current(), "Tim".to_string());
+
+    let (resp1, resp2, resp3) = join!(req1, req2, req3);
+    assert_matches!(resp1, Ok(3));
+    assert_matches!(resp2, Ok(7));
+    assert_matches!(resp3, Ok(ref s) if s == "Hey, Tim.");
+
+    Ok(())
+}
+
+#[cfg(test)]
+async fn spawn(fut: impl Future<Output = ()> + Send + 'static) {
+    tokio::spawn(fut);
+}
+
+#[tokio::test]
+async fn concurrent_join_all() -> anyhow::Result<()> {
+    let _ = tracing_subscriber::fmt::try_init();
+
+    let (tx, rx) = channel::unbounded();
+    tokio::spawn(
+        BaseChannel::with_defaults(rx)
+            .execute(Server.serve())
+            .for_each(spawn),
+    );
+
+    let client = ServiceClient::new(client::Config::default(), tx).spawn();
+
+    let req1 = client.add(context::current(), 1, 2);
+    let req2 = client.add(context::current(), 3, 4);
+
+    let responses = join_all(vec![req1, req2]).await;
+    assert_matches!(responses[0], Ok(3));
+    assert_matches!(responses[1], Ok(7));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn counter() -> anyhow::Result<()> {
+    #[tarpc::service]
+    trait Counter {
+        async fn count() -> u32;
+    }
+
+    struct CountService(u32);
+
+    impl Counter for &mut CountService {
+        async fn count(self, _: context::Context) -> u32 {
+            self.0 += 1;
+            self.0
+        }
+    }
+
+    let (tx, rx) = channel::unbounded();
+    tokio::spawn(async {
+        let mut requests = BaseChannel::with_defaults(rx).requests();
+        let mut counter = CountService(0);
+
+        while let Some(Ok(request)) = requests.next().await {
+            request.execute(counter.serve()).await;
+        }
+    });
+
+    let client = CounterClient::new(client::Config::default(), tx).spawn();
+    assert_matches!(client.count(context::current()).await, Ok(1));
+    assert_matches!(client.count(context::current()).await, Ok(2));
+
+    Ok(())
+}
+
+#[cfg(all(feature = "serde-transport", feature = "rkyv"))]
+#[tokio::test]
+async fn borrowed_args_are_served_from_archive() -> anyhow::Result<()> {
+    use tarpc::{
+        rkyv::{vec::ArchivedVec, Archived},
+        serde_transport,
+    };
+    use tokio_serde::formats::Bincode;
+    use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+    #[tarpc::service]
+    trait Storage {
+        async fn put(key: &str, blob: &[u8], replicas: u32) -> String;
+        async fn checksum(blob: &ArchivedVec<u8>, seed: &Archived<u64>) -> u64;
+    }
+
+    #[derive(Clone)]
+    struct StorageServer;
+
+    impl Storage for StorageServer {
+        async fn put(self, _: context::Context, key: &str, blob: &[u8], replicas: u32) -> String {
+            format!("{key}: {} bytes x {replicas}", blob.len())
+        }
+
+        async fn checksum(
+            self,
+            _: context::Context,
+            blob: &ArchivedVec<u8>,
+            seed: &Archived<u64>,
+        ) -> u64 {
+            blob.iter().fold(*seed, |sum, &byte| sum + u64::from(byte))
+        }
+    }
+
+    let (client_io, server_io) = tokio::io::duplex(4096);
+    let transport = serde_transport::new(
+        Framed::new(server_io, LengthDelimitedCodec::new()),
+        Bincode::default(),
+    );
+    tokio::spawn(
+        BaseChannel::with_defaults(transport)
+            .execute(StorageServer.serve())
+            .for_each(|request| async move {
+                tokio::spawn(request);
+            }),
+    );
+
+    let transport = serde_transport::new(
+        Framed::new(client_io, LengthDelimitedCodec::new()),
+        Bincode::default(),
+    );
+    let client = StorageClient::new(client::Config::default(), transport).spawn();
+    assert_eq!(
+        client
+            .put(context::current(), "blob".into(), vec![0; 100], 3)
+            .await?,
+        "blob: 100 bytes x 3"
+    );
+    assert_eq!(
+        client
+            .checksum(context::current(), vec![1, 2, 3], 10)
+            .await?,
+        16
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn extended_services_serve_base_methods() -> anyhow::Result<()> {
+    mod health {
+        #[tarpc::service]
+        pub trait Health {
+            async fn check() -> bool;
+        }
+    }
+
+    mod introspection {
+        #[tarpc::service]
+        pub trait Introspection {
+            async fn version() -> String;
+        }
+    }
+
+    use health::Health;
+    use introspection::Introspection;
+
+    #[tarpc::service(extends = health::Health, extends = introspection::Introspection)]
+    trait Greeter {
+        async fn hello(name: String) -> String;
+    }
+
+    #[derive(Clone)]
+    struct GreeterServer;
+
+    impl Health for GreeterServer {
+        async fn check(self, _: context::Context) -> bool {
+            true
+        }
+    }
+
+    impl Introspection for GreeterServer {
+        async fn version(self, _: context::Context) -> String {
+            "1.0".into()
+        }
+    }
+
+    impl Greeter for GreeterServer {
+        async fn hello(self, _: context::Context, name: String) -> String {
+            format!("Hello, {name}.")
+        }
+    }
+
+    let (tx, rx) = channel::unbounded();
+    tokio::spawn(
+        BaseChannel::with_defaults(rx)
+            .execute(Greeter::serve(GreeterServer))
+            .for_each(|request| async move {
+                tokio::spawn(request);
+            }),
+    );
+    let client = GreeterClient::new(client::Config::default(), tx).spawn();
+
+    assert_eq!(
+        client.hello(context::current(), "Tim".into()).await?,
+        "Hello, Tim."
+    );
+    assert!(client.as_health().check(context::current()).await?);
+    assert_eq!(
+        client
+            .as_introspection()
+            .version(context::current())
+            .await?,
+        "1.0"
+    );
+
+    let request = GreeterRequest::from(health::HealthRequest::Check {});
+    assert_eq!(request.since(), None);
+    assert_eq!(request.method_name(), "Health.check");
+    assert_eq!(GreeterRequest::METHOD_NAMES, ["Greeter.hello"]);
+    assert_eq!(
+        GreeterRequest::from_method_name("Greeter.hello"),
+        Some("Greeter.hello")
+    );
+    assert_eq!(
+        GreeterRequest::from_method_name("version"),
+        Some("Introspection.version")
+    );
+    assert_eq!(GreeterRequest::from_method_name("goodbye"), None);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn method_deadlines_replace_the_default_deadline() -> anyhow::Result<()> {
+    #[tarpc::service]
+    trait Reports {
+        /// Returns how long the server has to respond.
+        #[tarpc::deadline = "1m"]
+        async fn generate() -> Duration;
+        async fn time_left() -> Duration;
+    }
+
+    #[derive(Clone)]
+    struct ReportsServer;
+
+    impl Reports for ReportsServer {
+        async fn generate(self, ctx: context::Context) -> Duration {
+            ctx.time_remaining()
+        }
+
+        async fn time_left(self, ctx: context::Context) -> Duration {
+            ctx.time_remaining()
+        }
+    }
+
+    let (tx, rx) = channel::unbounded();
+    tokio::spawn(
+        BaseChannel::with_defaults(rx)
+            .execute(ReportsServer.serve())
+            .for_each(|request| async move {
+                tokio::spawn(request);
+            }),
+    );
+    let client = ReportsClient::new(client::Config::default(), tx).spawn();
+
+    let time_left = client.generate(context::current()).await?;
+    assert!(time_left > Duration::from_secs(50), "{time_left:?}");
+
+    let time_left = client.time_left(context::current()).await?;
+    assert!(time_left <= Duration::from_secs(10), "{time_left:?}");
+
+    let mut ctx = context::current();
+    ctx.deadline = Instant::now() + Duration::from_secs(5);
+    assert!(!ctx.has_default_deadline());
+    let time_left = client.generate(ctx).await?;
+    assert!(time_left <= Duration::from_secs(5), "{time_left:?}");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn untraced_methods_keep_the_trace() -> anyhow::Result<()> {
+    use tarpc::trace::TraceId;
+
+    #[tarpc::service]
+    trait Counter {
+        #[tarpc::no_trace]
+        async fn increment() -> TraceId;
+        async fn get() -> TraceId;
+    }
+
+    #[derive(Clone)]
+    struct CounterServer;
+
+    impl Counter for CounterServer {
+        async fn increment(self, ctx: context::Context) -> TraceId {
+            *ctx.trace_id()
+        }
+
+        async fn get(self, ctx: context::Context) -> TraceId {
+            *ctx.trace_id()
+        }
+    }
+
+    assert!(CounterRequest::Increment {}.is_untraced());
+    assert!(!CounterRequest::Get {}.is_untraced());
+
+    let (tx, rx) = channel::unbounded();
+    tokio::spawn(
+        BaseChannel::with_defaults(rx)
+            .with_untraced(CounterRequest::is_untraced)
+            .execute(CounterServer.serve())
+            .for_each(spawn),
+    );
+    let client = CounterClient::new(client::Config::default(), tx).spawn();
+
+    let ctx = context::current();
+    assert_eq!(client.increment(ctx.clone()).await?, *ctx.trace_id());
+    assert_eq!(client.get(ctx.clone()).await?, *ctx.trace_id());
+
+    Ok(())
+}
+
+#[cfg(feature = "serde-transport")]
+#[tokio::test]
+async fn baggage_is_sent_to_the_server() -> anyhow::Result<()> {
+    use tarpc::serde_transport;
+    use tokio_serde::formats::Json;
+    use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+    #[tarpc::service]
+    trait Greeter {
+        async fn hello() -> String;
+    }
+
+    #[derive(Clone)]
+    struct GreeterServer;
+
+    impl Greeter for GreeterServer {
+        async fn hello(self, ctx: context::Context) -> String {
+            match ctx.baggage.get("locale") {
+                Some("de-CH") => "Grüezi.".into(),
+                _ => "Hello.".into(),
+            }
+        }
+    }
+
+    let (client_io, server_io) = tokio::io::duplex(4096);
+    let transport = serde_transport::new(
+        Framed::new(server_io, LengthDelimitedCodec::new()),
+        Json::default(),
+    );
+    tokio::spawn(
+        BaseChannel::with_defaults(transport)
+            .execute(GreeterServer.serve())
+            .for_each(spawn),
+    );
+    let transport = serde_transport::new(
+        Framed::new(client_io, LengthDelimitedCodec::new()),
+        Json::default(),
+    );
+    let client = GreeterClient::new(client::Config::default(), transport).spawn();
+
+    let mut ctx = context::current();
+    assert_eq!(client.hello(ctx.clone()).await?, "Hello.");
+    ctx.baggage.insert("locale", "de-CH")?;
+    assert_eq!(client.hello(ctx).await?, "Grüezi.");
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn clients_send_their_priority_unless_set() -> anyhow::Result<()> {
+    use tarpc::context::Priority;
+
+    #[tarpc::service]
+    trait Scheduler {
+        async fn priority() -> Priority;
+    }
+
+    #[derive(Clone)]
+    struct SchedulerServer;
+
+    impl Scheduler for SchedulerServer {
+        async fn priority(self, ctx: context::Context) -> Priority {
+            ctx.priority
+        }
+    }
+
+    let (tx, rx) = channel::unbounded();
+    tokio::spawn(
+        BaseChannel::with_defaults(rx)
+            .execute(SchedulerServer.serve())
+            .for_each(spawn),
+    );
+    let mut config = client::Config::default();
+    config.priority = Priority::Low;
+    let client = SchedulerClient::new(config, tx).spawn();
+
+    assert_eq!(client.priority(context::current()).await?, Priority::Low);
+    let mut ctx = context::current();
+    ctx.priority = Priority::Critical;
+    assert_eq!(client.priority(ctx).await?, Priority::Critical);
+
+    Ok(())
+}
+
+#[cfg(not(feature = "opentelemetry"))]
+#[tokio::test]
+async fn current_inherits_the_request_without_opentelemetry() -> anyhow::Result<()> {
+    #[tarpc::service]
+    trait Inherit {
+        async fn inherits() -> bool;
+    }
+
+    #[derive(Clone)]
+    struct InheritServer;
+
+    impl Inherit for InheritServer {
+        async fn inherits(self, ctx: context::Context) -> bool {
+            let current = context::current();
+            current.deadline == ctx.deadline
+                && current.trace_id() == ctx.trace_id()
+                && current.baggage.get("locale") == Some("de-CH")
+                && current.priority == ctx.priority
+                && ctx.credentials.is_some()
+                && current.credentials.is_none()
+        }
+    }
+
+    let (tx, rx) = channel::unbounded();
+    tokio::spawn(
+        BaseChannel::with_defaults(rx)
+            .execute(InheritServer.serve())
+            .for_each(spawn),
+    );
+    let client = InheritClient::new(client::Config::default(), tx).spawn();
+
+    let ctx = context::current()
+        .with_baggage("locale", "de-CH")
+        .with_priority(context::Priority::High)
+        .with_credentials("Bearer token");
+    assert!(!ctx.trace_id().is_none());
+    assert!(client.inherits(ctx).await?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn clients_send_the_sampling_decision_of_their_sampler() -> anyhow::Result<()> {
+    use tarpc::{client::sampling::Sampler, trace::SamplingDecision};
+
+    #[tarpc::service]
+    trait Telemetry {
+        async fn noisy() -> SamplingDecision;
+        async fn quiet() -> SamplingDecision;
+    }
+
+    #[derive(Clone)]
+    struct TelemetryServer;
+
+    impl Telemetry for TelemetryServer {
+        async fn noisy(self, ctx: context::Context) -> SamplingDecision {
+            ctx.trace_context.sampling_decision
+        }
+
+        async fn quiet(self, ctx: context::Context) -> SamplingDecision {
+            ctx.trace_context.sampling_decision
+        }
+    }
+
+    let (tx, rx) = channel::unbounded();
+    tokio::spawn(
+        BaseChannel::with_defaults(rx)
+            .execute(TelemetryServer.serve())
+            .for_each(spawn),
+    );
+    let mut config = client::Config::default();
+    config.sampler = Some(Sampler::per_method(
+        [("Telemetry.noisy", Sampler::never())],
+        Sampler::always(),
+    ));
+    let client = TelemetryClient::new(config, tx).spawn();
+
+    assert_eq!(
+        client.noisy(context::current()).await?,
+        SamplingDecision::Unsampled
+    );
+    assert_eq!(
+        client.quiet(context::current()).await?,
+        SamplingDecision::Sampled
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "serde-transport")]
+#[tokio::test]
+async fn credentials_are_sent_to_the_server() -> anyhow::Result<()> {
+    use tarpc::{
+        client::stub::credentials::WithCredentials,
+        context::Credentials,
+        serde_transport,
+        server::authorization::{Authorize, Decision},
+    };
+    use tokio_serde::formats::Json;
+    use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+    #[tarpc::service]
+    trait Vault {
+        async fn open() -> String;
+    }
+
+    #[derive(Clone)]
+    struct VaultServer;
+
+    impl Vault for VaultServer {
+        async fn open(self, _: context::Context) -> String {
+            "gold".into()
+        }
+    }
+
+    let bearer = |_: &(), _: Option<&'static str>, ctx: &context::Context| match ctx
+        .credentials
+        .as_ref()
+        .and_then(Credentials::as_str)
+    {
+        Some("Bearer secret") => Decision::Allow,
+        _ => Decision::deny("bad token"),
+    };
+    let (client_io, server_io) = tokio::io::duplex(4096);
+    let transport = serde_transport::new(
+        Framed::new(server_io, LengthDelimitedCodec::new()),
+        Json::default(),
+    );
+    tokio::spawn(
+        BaseChannel::with_defaults(transport)
+            .execute(Authorize::new(VaultServer.serve(), (), bearer))
+            .for_each(spawn),
+    );
+    let transport = serde_transport::new(
+        Framed::new(client_io, LengthDelimitedCodec::new()),
+        Json::default(),
+    );
+    let channel = client::new(client::Config::default(), transport).spawn();
+    let client = VaultClient::from(WithCredentials::new(channel, || {
+        Some(Credentials::from("Bearer secret"))
+    }));
+    assert_eq!(client.open(context::current()).await?, "gold");
+
+    let mut ctx = context::current();
+    ctx.credentials = Some("Bearer guess".into());
+    assert!(format!("{ctx:?}").contains("Credentials(<redacted>)"));
+    assert_matches!(
+        client.open(ctx).await,
+        Err(client::RpcError::Server(e)) if e.kind == std::io::ErrorKind::PermissionDenied
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "serde-transport")]
+#[tokio::test]
+async fn w3c_trace_context_is_sent_to_the_server() -> anyhow::Result<()> {
+    use tarpc::{serde_transport, trace};
+    use tokio_serde::formats::Json;
+    use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+    #[tarpc::service]
+    trait Tracer {
+        /// Returns the W3C headers of the request's trace context.
+        async fn headers() -> (String, String);
+    }
+
+    #[derive(Clone)]
+    struct TracerServer;
+
+    impl Tracer for TracerServer {
+        async fn headers(self, ctx: context::Context) -> (String, String) {
+            let trace_state = ctx.trace_context.trace_state.header().to_string();
+            (ctx.trace_context.traceparent(), trace_state)
+        }
+    }
+
+    let (client_io, server_io) = tokio::io::duplex(4096);
+    let transport = serde_transport::new(
+        Framed::new(server_io, LengthDelimitedCodec::new()),
+        Json::default(),
+    );
+    tokio::spawn(
+        BaseChannel::with_defaults(transport)
+            .execute(TracerServer.serve())
+            .for_each(spawn),
+    );
+    let transport = serde_transport::new(
+        Framed::new(client_io, LengthDelimitedCodec::new()),
+        Json::default(),
+    );
+    let client = TracerClient::new(client::Config::default(), transport).spawn();
+
+    // E.g. the headers of an HTTP request that the client is handling.
+    let mut ctx = context::current();
+    ctx.trace_context = trace::Context::from_w3c(
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        Some("congo=t61rcWkgMzE"),
+    )?;
+    let (traceparent, tracestate) = client.headers(ctx).await?;
+    assert!(traceparent.starts_with("00-4bf92f3577b34da6a3ce929d0e0e4736-"));
+    assert!(traceparent.ends_with("-01"));
+    assert_eq!(tracestate, "congo=t61rcWkgMzE");
+
+    Ok(())
+}
+
+#[cfg(feature = "serde-transport")]
+#[tokio::test]
+async fn method_ids_survive_reordered_methods() -> anyhow::Result<()> {
+    use tarpc::serde_transport;
+    use tokio_serde::formats::Bincode;
+    use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+    mod v1 {
+        #[tarpc::service(method_ids = true)]
+        pub trait Storage {
+            #[tarpc::id = 1]
+            async fn get(key: String) -> Option<String>;
+            #[tarpc::id = 2]
+            async fn put(key: String, value: String);
+            async fn len() -> usize;
+        }
+    }
+
+    // The same service, with its methods declared in a different order.
+    mod v2 {
+        #[tarpc::service(method_ids = true)]
+        pub trait Storage {
+            async fn len() -> usize;
+            #[tarpc::id = 2]
+            async fn put(key: String, value: String);
+            #[tarpc::id = 1]
+            async fn get(key: String) -> Option<String>;
+        }
+    }
+
+    #[derive(Clone)]
+    struct StorageServer;
+
+    impl v2::Storage for StorageServer {
+        async fn len(self, _: context::Context) -> usize {
+            7
+        }
+
+        async fn put(self, _: context::Context, _: String, _: String) {}
+
+        async fn get(self, _: context::Context, key: String) -> Option<String> {
+            Some(format!("value of {key}"))
+        }
+    }
+
+    let (client_io, server_io) = tokio::io::duplex(4096);
+    let transport = serde_transport::new(
+        Framed::new(server_io, LengthDelimitedCodec::new()),
+        Bincode::default(),
+    );
+    tokio::spawn(
+        BaseChannel::with_defaults(transport)
+            .execute(v2::Storage::serve(StorageServer))
+            .for_each(|request| async move {
+                tokio::spawn(request);
+            }),
+    );
+
+    let transport = serde_transport::new(
+        Framed::new(client_io, LengthDelimitedCodec::new()),
+        Bincode::default(),
+    );
+    let client = v1::StorageClient::new(client::Config::default(), transport).spawn();
+    assert_eq!(
+        client.get(context::current(), "a".into()).await?,
+        Some("value of a".into())
+    );
+    client
+        .put(context::current(), "a".into(), "b".into())
+        .await?;
+    assert_eq!(client.len(context::current()).await?, 7);
+
+    assert_eq!(v1::StorageRequest::METHOD_IDS[..2], [1, 2]);
+    assert_eq!(
+        v1::StorageRequest::METHOD_IDS[2],
+        v2::StorageRequest::METHOD_IDS[0]
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn max_concurrent_limits_requests_of_a_method() -> anyhow::Result<()> {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    #[tarpc::service]
+    trait Reports {
+        #[tarpc::max_concurrent = 2]
+        async fn generate() -> usize;
+    }
+
+    #[derive(Clone, Default)]
+    struct ReportsServer {
+        executing: Arc<AtomicUsize>,
+        max_executing: Arc<AtomicUsize>,
+    }
+
+    impl Reports for ReportsServer {
+        async fn generate(self, _: context::Context) -> usize {
+            let executing = self.executing.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_executing.fetch_max(executing, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            self.executing.fetch_sub(1, Ordering::SeqCst);
+            executing
+        }
+    }
+
+    let server = ReportsServer::default();
+    let (tx, rx) = channel::unbounded();
+    tokio::spawn(
+        BaseChannel::with_defaults(rx)
+            .execute(server.clone().serve())
+            .for_each(|request| async move {
+                tokio::spawn(request);
+            }),
+    );
+    let client = ReportsClient::new(client::Config::default(), tx).spawn();
+
+    let calls = (0..6).map(|_| client.generate(context::current()));
+    for response in join_all(calls).await {
+        assert!(response? <= 2);
+    }
+    assert_eq!(server.max_executing.load(Ordering::SeqCst), 2);
+
+    Ok(())
+}
+
+#[cfg(feature = "serde-transport")]
+#[tokio::test]
+async fn passthrough_bytes_are_sent_as_payloads() -> anyhow::Result<()> {
+    use bytes::Bytes;
+    use tarpc::serde_transport::{self, Passthrough};
+    use tokio_serde::formats::Json;
+    use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+    // `Bytes` can't be archived with rkyv.
+    #[tarpc::service(passthrough_bytes = true, derive_rkyv = false)]
+    trait Proxy {
+        async fn forward(to: String, blob: Bytes) -> Bytes;
+    }
+
+    #[derive(Clone)]
+    struct ProxyServer;
+
+    impl Proxy for ProxyServer {
+        async fn forward(self, _: context::Context, to: String, blob: Bytes) -> Bytes {
+            [to.as_bytes(), &blob].concat().into()
+        }
+    }
+
+    let (client_io, server_io) = tokio::io::duplex(4096);
+    let transport = serde_transport::new(
+        Framed::new(server_io, LengthDelimitedCodec::new()),
+        Passthrough::new(Json::default()),
+    );
+    tokio::spawn(
+        BaseChannel::with_defaults(transport)
+            .execute(ProxyServer.serve())
+            .for_each(spawn),
+    );
+
+    let transport = serde_transport::new(
+        Framed::new(client_io, LengthDelimitedCodec::new()),
+        Passthrough::new(Json::default()),
+    );
+    let client = ProxyClient::new(client::Config::default(), transport).spawn();
+    let blob = Bytes::from(vec![0xff; 1024]);
+    let forwarded = client
+        .forward(context::current(), "self:".into(), blob.clone())
+        .await?;
+    assert_eq!(&forwarded[..5], b"self:");
+    assert_eq!(forwarded.slice(5..), blob);
+
+    Ok(())
+}
+
+#[cfg(feature = "serde-transport")]
+#[tokio::test(start_paused = true)]
+async fn batch_flush_policy_delays_messages() -> anyhow::Result<()> {
+    use tarpc::{serde_transport, server, transport::FlushPolicy};
+    use tokio_serde::formats::Json;
+    use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+    let policy = FlushPolicy::Batch {
+        max_messages: 100,
+        max_delay: Duration::from_millis(10),
+    };
+    let (client_io, server_io) = tokio::io::duplex(4096);
+    let transport = serde_transport::new(
+        Framed::new(server_io, LengthDelimitedCodec::new()),
+        Json::default(),
+    );
+    let config = server::Config {
+        flush_policy: policy,
+        ..server::Config::default()
+    };
+    tokio::spawn(
+        BaseChannel::new(config, transport)
+            .execute(Server.serve())
+            .for_each(spawn),
+    );
+
+    let transport = serde_transport::new(
+        Framed::new(client_io, LengthDelimitedCodec::new()),
+        Json::default(),
+    );
+    let mut config = client::Config::default();
+    config.flush_policy = policy;
+    let client = ServiceClient::new(config, transport).spawn();
+    let start = tokio::time::Instant::now();
+    assert_eq!(client.add(context::current(), 1, 2).await?, 3);
+    // Both the request and the response wait to be flushed.
+    assert_eq!(start.elapsed(), Duration::from_millis(20));
+
+    Ok(())
+}
+
+#[cfg(feature = "serde-transport")]
+#[tokio::test(start_paused = true)]
+async fn adaptive_flush_policy_does_not_delay_light_load() -> anyhow::Result<()> {
+    use tarpc::{serde_transport, server, transport::FlushPolicy};
+    use tokio_serde::formats::Json;
+    use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+    let policy = FlushPolicy::Adaptive {
+        max_messages: 100,
+        max_delay: Duration::from_millis(10),
+    };
+    let (client_io, server_io) = tokio::io::duplex(4096);
+    let transport = serde_transport::new(
+        Framed::new(server_io, LengthDelimitedCodec::new()),
+        Json::default(),
+    );
+    let config = server::Config {
+        flush_policy: policy,
+        ..server::Config::default()
+    };
+    tokio::spawn(
+        BaseChannel::new(config, transport)
+            .execute(Server.serve())
+            .for_each(spawn),
+    );
+
+    let transport = serde_transport::new(
+        Framed::new(client_io, LengthDelimitedCodec::new()),
+        Json::default(),
+    );
+    let mut config = client::Config::default();
+    config.flush_policy = policy;
+    let client = ServiceClient::new(config, transport).spawn();
+    let start = tokio::time::Instant::now();
+    for i in 0..10 {
+        assert_eq!(client.add(context::current(), i, 2).await?, i + 2);
+    }
+    assert_eq!(start.elapsed(), Duration::ZERO);
+
+    let calls = (0..1000).map(|i| client.add(context::current(), i, 1));
+    for (i, response) in join_all(calls).await.into_iter().enumerate() {
+        assert_eq!(response?, i as i32 + 1);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn canceled_calls_learn_the_server_aborted_them() -> anyhow::Result<()> {
+    use std::sync::Arc;
+    use tarpc::client::CancelOutcome;
+    use tokio::sync::Notify;
+
+    #[tarpc::service]
+    trait Stall {
+        async fn stall();
+    }
+
+    #[derive(Clone)]
+    struct StallServer(Arc<Notify>);
+
+    impl Stall for StallServer {
+        async fn stall(self, _: context::Context) {
+            self.0.notify_one();
+            future::pending().await
+        }
+    }
+
+    let started = Arc::new(Notify::new());
+    let (tx, rx) = channel::unbounded();
+    tokio::spawn(
+        BaseChannel::with_defaults(rx)
+            .execute(StallServer(started.clone()).serve())
+            .for_each(spawn),
+    );
+    let client = client::new(client::Config::default(), tx).spawn();
+
+    let call = client
+        .call_cancelable(context::current(), "Stall.stall", StallRequest::Stall {})
+        .await?;
+    started.notified().await;
+    assert_matches!(call.cancel().await, CancelOutcome::Aborted);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn canceled_calls_that_raced_their_response_complete() -> anyhow::Result<()> {
+    use tarpc::client::CancelOutcome;
+
+    let (tx, rx) = channel::unbounded();
+    tokio::spawn(
+        BaseChannel::with_defaults(rx)
+            .execute(Server.serve())
+            .for_each(spawn),
+    );
+    let client = client::new(client::Config::default(), tx).spawn();
+
+    let call = client
+        .call_cancelable(
+            context::current(),
+            "Service.add",
+            ServiceRequest::Add { x: 1, y: 2 },
+        )
+        .await?;
+    while client.stats().responses_received() == 0 {
+        tokio::task::yield_now().await;
+    }
+    assert_matches!(
+        call.cancel().await,
+        CancelOutcome::Completed(Ok(ServiceResponse::Add(3)))
+    );
+
+    // Calls that aren't canceled resolve to their response.
+    let call = client
+        .call_cancelable(
+            context::current(),
+            "Service.add",
+            ServiceRequest::Add { x: 3, y: 4 },
+        )
+        .await?;
+    assert_matches!(call.await, Ok(ServiceResponse::Add(7)));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn sharded_channels_serve_concurrent_requests() -> anyhow::Result<()> {
+    use tarpc::server;
+
+    let (tx, rx) = channel::unbounded();
+    let config = server::Config {
+        in_flight_shards: 4,
+        ..server::Config::default()
+    };
+    tokio::spawn(
+        BaseChannel::new(config, rx)
+            .execute(Server.serve())
+            .for_each(spawn),
+    );
+
+    let mut config = client::Config::default();
+    config.in_flight_shards = 4;
+    let client = ServiceClient::new(config, tx).spawn();
+
+    let calls = (0..100).map(|i| client.add(context::current(), i, 1));
+    for (i, response) in join_all(calls).await.into_iter().enumerate() {
+        assert_eq!(response?, i as i32 + 1);
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "serde-transport")]
+#[tokio::test]
+async fn lazy_args_are_decoded_when_served() -> anyhow::Result<()> {
+    use tarpc::serde_transport::{self, LazyArgs, LazyServe};
+    use tokio_serde::formats::Json;
+    use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+    #[tarpc::service]
+    trait Greeter {
+        async fn greet(name: String) -> String;
+    }
+
+    #[derive(Clone)]
+    struct GreeterServer;
+
+    impl Greeter for GreeterServer {
+        async fn greet(self, _: context::Context, name: String) -> String {
+            format!("Hello, {name}!")
+        }
+    }
+
+    let (client_io, server_io) = tokio::io::duplex(4096);
+    let transport = serde_transport::new(
+        Framed::new(server_io, LengthDelimitedCodec::new()),
+        LazyArgs::new(
+            Json::default(),
+            Json::<GreeterRequest, GreeterRequest>::default(),
+        ),
+    );
+    tokio::spawn(
+        BaseChannel::with_defaults(transport)
+            .execute(LazyServe::new(GreeterServer.serve()))
+            .for_each(spawn),
+    );
+
+    let transport = serde_transport::new(
+        Framed::new(client_io, LengthDelimitedCodec::new()),
+        LazyArgs::new(
+            Json::default(),
+            Json::<GreeterRequest, GreeterRequest>::default(),
+        ),
+    );
+    let client = GreeterClient::new(client::Config::default(), transport).spawn();
+    assert_eq!(
+        client.greet(context::current(), "lazy".into()).await?,
+        "Hello, lazy!"
+    );
+
+    Ok(())
+}
+
+#[cfg(feature = "arena")]
+#[tokio::test]
+async fn lazy_args_are_decoded_into_a_request_arena() -> anyhow::Result<()> {
+    use std::io;
+    use tarpc::{
+        arena::{Bump, DeserializeIn, Vec},
+        serde_transport::{self, Lazy, LazyArgs},
+        server::Serve,
+        ServerError,
+    };
+    use tokio_serde::formats::Json;
+    use tokio_util::codec::{Framed, LengthDelimitedCodec};
+
+    /// Returns the longest of a list of words, which are decoded into an arena dropped with the
+    /// request.
+    #[derive(Clone)]
+    struct Longest;
+
+    impl Serve for Longest {
+        type Req = Lazy<std::vec::Vec<String>>;
+        type Resp = String;
+
+        async fn serve(self, _: context::Context, req: Self::Req) -> Result<String, ServerError> {
+            let arena = Bump::new();
+            let mut deserializer = serde_json::Deserializer::from_slice(req.as_bytes());
+            let words = Vec::<&str>::deserialize_in(&arena, &mut deserializer)
+                .map_err(|e| ServerError::new(io::ErrorKind::InvalidData, e.to_string()))?;
+            let longest = words.iter().max_by_key(|word| word.len()).unwrap_or(&"");
+            Ok(longest.to_string())
+        }
+    }
+
+    let (client_io, server_io) = tokio::io::duplex(4096);
+    let transport = serde_transport::new(
+        Framed::new(server_io, LengthDelimitedCodec::new()),
+        LazyArgs::new(
+            Json::default(),
+            Json::<std::vec::Vec<String>, ()>::default(),
+        ),
+    );
+    tokio::spawn(
+        BaseChannel::with_defaults(transport)
+            .execute(Longest)
+            .for_each(spawn),
+    );
+
+    let transport = serde_transport::new(
+        Framed::new(client_io, LengthDelimitedCodec::new()),
+        LazyArgs::new(
+            Json::default(),
+            Json::<(), std::vec::Vec<String>>::default(),
+        ),
+    );
+    let client = client::new(client::Config::default(), transport).spawn();
+    let words = vec!["a".to_string(), "nested".into(), "request".into()];
+    let longest: String = client.call(context::current(), "longest", words).await?;
+    assert_eq!(longest, "request");
+
+    Ok(())
+}
+
+#[tokio::test(start_paused = true)]
+async fn deadlines_follow_paused_time() -> anyhow::Result<()> {
+    use tarpc::client::RpcError;
+
+    #[tarpc::service]
+    trait Sleepy {
+        async fn sleep(duration: Duration);
+    }
+
+    #[derive(Clone)]
+    struct SleepyServer;
+
+    impl Sleepy for SleepyServer {
+        async fn sleep(self, _: context::Context, duration: Duration) {
+            tokio::time::sleep(duration).await;
+        }
+    }
+
+    let (tx, rx) = channel::unbounded();
+    tokio::spawn(
+        BaseChannel::with_defaults(rx)
+            .execute(SleepyServer.serve())
+            .for_each(spawn),
+    );
+    let client = SleepyClient::new(client::Config::default(), tx).spawn();
+
+    // Deadlines are measured against the paused clock, so a deadline passes once the clock
+    // reaches it, however little time passed on the system clock.
+    let ctx = context::current();
+    tokio::time::advance(Duration::from_secs(60)).await;
+    assert!(ctx.has_expired());
+    let start = tokio::time::Instant::now();
+    assert_matches!(
+        client.sleep(ctx, Duration::from_secs(1)).await,
+        Err(RpcError::DeadlineExceeded)
+    );
+    assert_eq!(start.elapsed(), Duration::ZERO);
+
+    client
+        .sleep(context::current(), Duration::from_secs(9))
+        .await?;
+    let start = tokio::time::Instant::now();
+    assert_matches!(
+        client
+            .sleep(context::current(), Duration::from_secs(11))
+            .await,
+        Err(RpcError::DeadlineExceeded)
+    );
+    assert!(start.elapsed() >= Duration::from_secs(10));
+    assert!(start.elapsed() < Duration::from_secs(11));
 
     Ok(())
 }